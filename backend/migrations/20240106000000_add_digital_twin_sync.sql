@@ -0,0 +1,26 @@
+-- Digital twin live sync
+-- Twins bound to a product are updated from tracking events and oracle snapshots
+-- using a per-twin mapping, and disagreements with predicted state are recorded.
+
+ALTER TABLE digital_twins
+    ADD COLUMN IF NOT EXISTS sync_mapping JSONB NOT NULL DEFAULT '{}';
+
+-- Divergence alerts raised when an observation disagrees with the twin's predicted state
+CREATE TABLE IF NOT EXISTS twin_divergences (
+    id UUID PRIMARY KEY,
+    twin_id UUID NOT NULL REFERENCES digital_twins(id) ON DELETE CASCADE,
+    tracking_event_id BIGINT REFERENCES tracking_events(id) ON DELETE SET NULL,
+    state_path VARCHAR(255) NOT NULL,
+    predicted_value JSONB,
+    observed_value JSONB,
+    source VARCHAR(255) NOT NULL,
+    detected_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMP WITH TIME ZONE
+);
+
+-- Indexes for twin_divergences
+CREATE INDEX idx_twin_divergences_twin ON twin_divergences(twin_id, detected_at DESC);
+CREATE INDEX idx_twin_divergences_open ON twin_divergences(twin_id) WHERE resolved_at IS NULL;
+
+COMMENT ON COLUMN digital_twins.sync_mapping IS 'Mapping from event/snapshot fields into current_state';
+COMMENT ON TABLE twin_divergences IS 'Observed values that disagreed with a twin''s predicted state';
@@ -0,0 +1,23 @@
+-- Anomaly findings scored for each new tracking event
+
+CREATE TABLE IF NOT EXISTS event_anomalies (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tracking_event_id BIGINT NOT NULL REFERENCES tracking_events(id) ON DELETE CASCADE,
+    product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    anomaly_type VARCHAR(50) NOT NULL,
+    -- 'impossible_travel', 'unusual_actor', 'out_of_order_timestamp', 'duplicate_data_hash', 'event_burst'
+    score DOUBLE PRECISION NOT NULL CHECK (score >= 0 AND score <= 1),
+    severity VARCHAR(20) NOT NULL,  -- 'low', 'medium', 'high'
+    details JSONB NOT NULL DEFAULT '{}',
+    detected_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    acknowledged_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_event_anomalies_product ON event_anomalies(product_id, detected_at DESC);
+CREATE INDEX idx_event_anomalies_type ON event_anomalies(anomaly_type, detected_at DESC);
+CREATE INDEX idx_event_anomalies_detected ON event_anomalies(detected_at DESC);
+
+-- Supports duplicate data hash lookups during scoring
+CREATE INDEX IF NOT EXISTS idx_tracking_events_data_hash ON tracking_events(data_hash);
+
+COMMENT ON TABLE event_anomalies IS 'Suspicious tracking events flagged by the anomaly detector';
@@ -0,0 +1,53 @@
+-- Structured locations
+-- Free-text event locations and product origins are resolved to coordinates,
+-- a geohash and optional UN/LOCODE / GS1 GLN identifiers.
+
+-- Geocoding table for known facilities, ports and warehouses
+CREATE TABLE IF NOT EXISTS known_locations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL,
+    normalized_name TEXT NOT NULL UNIQUE,
+    latitude DOUBLE PRECISION NOT NULL CHECK (latitude BETWEEN -90 AND 90),
+    longitude DOUBLE PRECISION NOT NULL CHECK (longitude BETWEEN -180 AND 180),
+    geohash VARCHAR(12) NOT NULL,
+    unlocode VARCHAR(5) UNIQUE,
+    gln VARCHAR(13) UNIQUE,
+    country_code CHAR(2),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Resolved position of each tracking event
+CREATE TABLE IF NOT EXISTS event_locations (
+    tracking_event_id BIGINT PRIMARY KEY REFERENCES tracking_events(id) ON DELETE CASCADE,
+    known_location_id UUID REFERENCES known_locations(id) ON DELETE SET NULL,
+    latitude DOUBLE PRECISION NOT NULL,
+    longitude DOUBLE PRECISION NOT NULL,
+    geohash VARCHAR(12) NOT NULL,
+    unlocode VARCHAR(5),
+    gln VARCHAR(13),
+    resolved_by VARCHAR(20) NOT NULL,  -- 'coordinates', 'unlocode', 'gln', 'name'
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Resolved origin of each product
+CREATE TABLE IF NOT EXISTS product_locations (
+    product_id TEXT PRIMARY KEY REFERENCES products(id) ON DELETE CASCADE,
+    known_location_id UUID REFERENCES known_locations(id) ON DELETE SET NULL,
+    latitude DOUBLE PRECISION NOT NULL,
+    longitude DOUBLE PRECISION NOT NULL,
+    geohash VARCHAR(12) NOT NULL,
+    unlocode VARCHAR(5),
+    gln VARCHAR(13),
+    resolved_by VARCHAR(20) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_known_locations_geohash ON known_locations(geohash text_pattern_ops);
+CREATE INDEX idx_event_locations_lat_lon ON event_locations(latitude, longitude);
+CREATE INDEX idx_event_locations_geohash ON event_locations(geohash text_pattern_ops);
+CREATE INDEX idx_product_locations_lat_lon ON product_locations(latitude, longitude);
+
+COMMENT ON TABLE known_locations IS 'Geocoding table resolving facility names, UN/LOCODEs and GLNs to coordinates';
+COMMENT ON TABLE event_locations IS 'Structured location resolved for each tracking event';
+COMMENT ON TABLE product_locations IS 'Structured origin location resolved for each product';
@@ -0,0 +1,7 @@
+-- Lookups used by EPCIS import: duplicate detection and EPC -> product resolution
+
+CREATE INDEX IF NOT EXISTS idx_tracking_events_epcis_event_id
+    ON tracking_events ((metadata->'epcis'->>'event_id'));
+
+CREATE INDEX IF NOT EXISTS idx_products_custom_epc
+    ON products ((custom_fields->>'epc'));
@@ -0,0 +1,41 @@
+-- Tamper-evident audit log: every row commits to its predecessor with a SHA-256
+-- hash chain, and the chain head is periodically anchored on Stellar.
+
+-- Deleting a user must not rewrite history, so user_id is no longer a foreign key
+ALTER TABLE audit_logs DROP CONSTRAINT IF EXISTS audit_logs_user_id_fkey;
+
+ALTER TABLE audit_logs
+    ALTER COLUMN resource_id TYPE TEXT USING resource_id::text,
+    ALTER COLUMN ip_address TYPE TEXT USING host(ip_address),
+    ALTER COLUMN created_at TYPE TIMESTAMP WITH TIME ZONE USING created_at AT TIME ZONE 'UTC',
+    ADD COLUMN IF NOT EXISTS sequence BIGINT,
+    ADD COLUMN IF NOT EXISTS prev_hash CHAR(64),
+    ADD COLUMN IF NOT EXISTS entry_hash CHAR(64);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_audit_logs_sequence ON audit_logs(sequence);
+
+-- Rows are append-only; verification would flag edits anyway, this stops them early
+CREATE OR REPLACE FUNCTION audit_logs_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_logs is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trg_audit_logs_append_only ON audit_logs;
+CREATE TRIGGER trg_audit_logs_append_only
+    BEFORE UPDATE OR DELETE ON audit_logs
+    FOR EACH ROW EXECUTE FUNCTION audit_logs_append_only();
+
+CREATE TABLE IF NOT EXISTS audit_anchors (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    sequence BIGINT NOT NULL,
+    entry_hash CHAR(64) NOT NULL,
+    network VARCHAR(20) NOT NULL DEFAULT 'stellar',
+    transaction_hash TEXT NOT NULL,
+    anchored_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_audit_anchors_sequence ON audit_anchors(sequence DESC);
+
+COMMENT ON COLUMN audit_logs.sequence IS 'Gapless position in the hash chain, starting at 1';
+COMMENT ON COLUMN audit_logs.entry_hash IS 'SHA-256 over the row contents and prev_hash';
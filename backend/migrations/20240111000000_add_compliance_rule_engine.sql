@@ -0,0 +1,94 @@
+-- Declarative, versioned compliance rules
+
+CREATE TABLE IF NOT EXISTS compliance_rules (
+    rule_id TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    compliance_type VARCHAR(100) NOT NULL,
+    description TEXT NOT NULL,
+    definition JSONB NOT NULL,
+    source_format VARCHAR(10) NOT NULL DEFAULT 'json' CHECK (source_format IN ('json', 'yaml')),
+    source TEXT,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (rule_id, version)
+);
+
+-- At most one live version per rule
+CREATE UNIQUE INDEX IF NOT EXISTS idx_compliance_rules_active
+    ON compliance_rules(rule_id) WHERE is_active;
+CREATE INDEX IF NOT EXISTS idx_compliance_rules_type ON compliance_rules(compliance_type);
+
+COMMENT ON COLUMN compliance_rules.definition IS 'Parsed rule (JSON), evaluated by ComplianceValidator';
+COMMENT ON COLUMN compliance_rules.source IS 'Definition exactly as submitted (JSON or YAML)';
+
+-- Validation results reference the exact rule version they were checked against.
+-- product_id becomes TEXT to match products(id).
+ALTER TABLE compliance_records DROP CONSTRAINT IF EXISTS compliance_records_product_id_fkey;
+ALTER TABLE compliance_records ALTER COLUMN product_id TYPE TEXT USING product_id::text;
+ALTER TABLE compliance_records
+    ADD CONSTRAINT compliance_records_product_id_fkey
+    FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE;
+ALTER TABLE compliance_records ALTER COLUMN user_id DROP NOT NULL;
+ALTER TABLE compliance_records
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ,
+    ALTER COLUMN updated_at TYPE TIMESTAMPTZ,
+    ALTER COLUMN verified_at TYPE TIMESTAMPTZ;
+ALTER TABLE compliance_records ADD COLUMN IF NOT EXISTS rule_id TEXT;
+ALTER TABLE compliance_records ADD COLUMN IF NOT EXISTS rule_version INTEGER;
+ALTER TABLE compliance_records
+    ADD CONSTRAINT compliance_records_rule_fkey
+    FOREIGN KEY (rule_id, rule_version) REFERENCES compliance_rules(rule_id, version);
+
+CREATE INDEX IF NOT EXISTS idx_compliance_records_product
+    ON compliance_records(product_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_compliance_records_rule
+    ON compliance_records(rule_id, rule_version);
+
+-- Built-in rules, previously hard-coded in ComplianceValidator
+INSERT INTO compliance_rules (rule_id, version, compliance_type, description, definition) VALUES
+('gdpr_residency', 1, 'gdpr', 'Ensure personal data is stored in EU data centers', '{
+  "rule_id": "gdpr_residency", "compliance_type": "gdpr",
+  "description": "Ensure personal data is stored in EU data centers",
+  "checks": [
+    {"type": "required", "field": "data_location"},
+    {"type": "constraint", "field": "data_location", "op": "eq", "value": "EU", "message": "GDPR: Data must be stored in EU"},
+    {"type": "required", "field": "consent", "message": "GDPR: User consent required"},
+    {"type": "required", "field": "right_to_be_forgotten_enabled", "severity": "warning", "message": "GDPR: Right to be forgotten not configured"}
+  ]}'),
+('fda_esig', 1, 'fda_21_cfr_11', 'Require electronic signatures for pharmaceutical records', '{
+  "rule_id": "fda_esig", "compliance_type": "fda_21_cfr_11",
+  "description": "Require electronic signatures for pharmaceutical records",
+  "checks": [
+    {"type": "required", "field": "digital_signature", "message": "FDA 21 CFR Part 11: Digital signature required"},
+    {"type": "required", "field": "signer_id", "message": "FDA 21 CFR Part 11: Signer identification required"},
+    {"type": "required", "field": "timestamp", "message": "FDA 21 CFR Part 11: Timestamp required"}
+  ]}'),
+('fsma_trace', 1, 'fsma', 'Maintain complete traceability for food products', '{
+  "rule_id": "fsma_trace", "compliance_type": "fsma",
+  "description": "Maintain complete traceability for food products",
+  "checks": [
+    {"type": "required", "field": "origin", "message": "FSMA: Product origin required"},
+    {"type": "required", "field": "processing_steps", "message": "FSMA: Processing steps required"},
+    {"type": "required", "field": "distribution", "message": "FSMA: Distribution information required"}
+  ]}'),
+('conflict_minerals', 1, 'conflict_minerals', 'Verify conflict-free mineral sourcing', '{
+  "rule_id": "conflict_minerals", "compliance_type": "conflict_minerals",
+  "description": "Verify conflict-free mineral sourcing",
+  "checks": [
+    {"type": "required", "field": "mineral_source", "message": "Conflict Minerals: Source information required"},
+    {"type": "required", "field": "audit_report", "message": "Conflict Minerals: Audit report required"},
+    {"type": "required", "field": "verified_by_auditor", "severity": "warning", "message": "Conflict Minerals: Awaiting auditor verification"},
+    {"type": "constraint", "field": "verified_by_auditor", "op": "eq", "value": true, "severity": "warning", "message": "Conflict Minerals: Awaiting auditor verification"}
+  ]}'),
+('organic_cert', 1, 'organic_certification', 'Verify organic certification status', '{
+  "rule_id": "organic_cert", "compliance_type": "organic_certification",
+  "description": "Verify organic certification status",
+  "checks": [
+    {"type": "required", "field": "certification_body", "message": "Organic: Certification body required"},
+    {"type": "required", "field": "cert_expiry"},
+    {"type": "constraint", "field": "cert_expiry", "op": "gt", "value": "now", "message": "Organic: Certification expired"},
+    {"type": "constraint", "field": "cert_expiry", "op": "gt", "value": "now+90d", "severity": "warning",
+     "message": "Organic: Certification expiring soon", "when": {"field": "cert_expiry", "op": "gt", "value": "now"}}
+  ]}')
+ON CONFLICT DO NOTHING;
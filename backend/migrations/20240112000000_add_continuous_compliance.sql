@@ -0,0 +1,18 @@
+-- Continuous per-product compliance evaluation
+
+ALTER TABLE compliance_records
+    ADD COLUMN IF NOT EXISTS source VARCHAR(20) NOT NULL DEFAULT 'manual'
+    CHECK (source IN ('manual', 'continuous'));
+
+COMMENT ON COLUMN compliance_records.source IS
+    'manual: explicit /compliance/check call; continuous: re-evaluated when the product receives an event';
+
+-- Current record per (product, rule) for continuous evaluation
+CREATE INDEX IF NOT EXISTS idx_compliance_records_continuous
+    ON compliance_records(product_id, rule_id, created_at DESC)
+    WHERE source = 'continuous';
+
+-- Scoped rules are looked up on every new event
+CREATE INDEX IF NOT EXISTS idx_compliance_rules_scoped
+    ON compliance_rules(rule_id)
+    WHERE is_active AND definition ? 'applies_to';
@@ -0,0 +1,48 @@
+-- EU Deforestation Regulation: origin plots, product lineage and due diligence
+
+CREATE TABLE IF NOT EXISTS product_origin_plots (
+    id UUID PRIMARY KEY,
+    product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    plot_reference VARCHAR(100),
+    country_code CHAR(2) NOT NULL,
+    commodity VARCHAR(20) NOT NULL,
+    geometry JSONB NOT NULL,
+    area_hectares DOUBLE PRECISION NOT NULL CHECK (area_hectares > 0),
+    production_start DATE,
+    production_end DATE,
+    deforestation_free_evidence TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_product_origin_plots_product ON product_origin_plots(product_id);
+
+COMMENT ON COLUMN product_origin_plots.geometry IS 'GeoJSON Point, Polygon or MultiPolygon (WGS84)';
+
+-- Which products were made or relabelled from which, recorded from events
+-- naming their inputs (metadata.input_products / metadata.epcis.input_epcs)
+CREATE TABLE IF NOT EXISTS product_lineage (
+    parent_product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    child_product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    event_id BIGINT NOT NULL REFERENCES tracking_events(id) ON DELETE CASCADE,
+    relation VARCHAR(50) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (parent_product_id, child_product_id, event_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_product_lineage_child ON product_lineage(child_product_id);
+
+-- Evaluated continuously for products in EUDR commodity categories
+INSERT INTO compliance_rules (rule_id, version, compliance_type, description, definition) VALUES
+('eudr_due_diligence', 1, 'eudr', 'EU Deforestation Regulation due diligence for relevant commodities', '{
+  "rule_id": "eudr_due_diligence", "compliance_type": "eudr",
+  "description": "EU Deforestation Regulation due diligence for relevant commodities",
+  "applies_to": {"categories": ["cattle", "cocoa", "coffee", "oil_palm", "rubber", "soya", "wood"]},
+  "checks": [
+    {"type": "constraint", "field": "eudr.plot_count", "op": "gte", "value": 1, "message": "EUDR: Geolocation of at least one origin plot required"},
+    {"type": "constraint", "field": "eudr.plots_missing_polygon", "op": "eq", "value": 0, "message": "EUDR: Plots over 4 ha must be geolocated by polygon"},
+    {"type": "constraint", "field": "eudr.plots_needing_evidence", "op": "eq", "value": 0, "message": "EUDR: Deforestation-free evidence missing for plots outside low-risk countries"},
+    {"type": "constraint", "field": "eudr.risk_level", "op": "ne", "value": "high", "severity": "warning", "message": "EUDR: Sourcing from a high-risk country requires enhanced scrutiny"},
+    {"type": "required", "field": "custom_fields.hs_code", "message": "EUDR: HS code required"},
+    {"type": "required", "field": "custom_fields.net_mass_kg", "message": "EUDR: Net mass required"}
+  ]}')
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,19 @@
+-- Per-product access grants, mirroring the on-chain AuthorizationContract.
+-- The owner (products.owner_address) needs no grant.
+
+CREATE TABLE IF NOT EXISTS product_access_grants (
+    product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    actor_address VARCHAR(56) NOT NULL,
+    permission VARCHAR(20) NOT NULL CHECK (permission IN ('add_events', 'edit')),
+    source VARCHAR(10) NOT NULL DEFAULT 'api' CHECK (source IN ('api', 'chain')),
+    granted_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (product_id, actor_address)
+);
+
+CREATE INDEX IF NOT EXISTS idx_product_access_grants_actor ON product_access_grants(actor_address);
+
+COMMENT ON COLUMN product_access_grants.permission IS
+    'add_events: authorized actor (on-chain add_authorized_actor); edit: delegate who may also change the product';
+COMMENT ON COLUMN product_access_grants.source IS
+    'api: granted through the REST API; chain: mirrored from AuthorizationContract';
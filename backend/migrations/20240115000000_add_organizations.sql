@@ -0,0 +1,87 @@
+-- Organizations (tenants). Products belong to the organization holding their
+-- owner address; API keys act within one organization. Data crosses
+-- organizations only through product_access_grants.
+
+CREATE TABLE IF NOT EXISTS organizations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL,
+    slug VARCHAR(64) NOT NULL UNIQUE,
+    -- Created automatically for a user who registers on their own
+    is_personal BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS organization_members (
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role VARCHAR(20) NOT NULL DEFAULT 'member' CHECK (role IN ('owner', 'admin', 'member', 'viewer')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (organization_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_organization_members_user ON organization_members(user_id);
+
+-- Stellar addresses operated by an organization. An address belongs to at
+-- most one organization.
+CREATE TABLE IF NOT EXISTS organization_addresses (
+    stellar_address VARCHAR(56) PRIMARY KEY,
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    label TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_organization_addresses_org ON organization_addresses(organization_id);
+
+ALTER TABLE products ADD COLUMN IF NOT EXISTS organization_id UUID REFERENCES organizations(id) ON DELETE SET NULL;
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS organization_id UUID REFERENCES organizations(id) ON DELETE CASCADE;
+ALTER TABLE carbon_credits ADD COLUMN IF NOT EXISTS organization_id UUID REFERENCES organizations(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_products_organization ON products(organization_id);
+CREATE INDEX IF NOT EXISTS idx_api_keys_organization ON api_keys(organization_id);
+CREATE INDEX IF NOT EXISTS idx_carbon_credits_organization ON carbon_credits(organization_id);
+
+-- Backfill: one personal organization per existing user. users.email and
+-- users.stellar_address are encrypted, so the name is generic and owner
+-- addresses are taken from the products each user registered.
+INSERT INTO organizations (id, name, slug, is_personal)
+SELECT gen_random_uuid(), 'Personal', 'user-' || id::text, TRUE FROM users
+ON CONFLICT (slug) DO NOTHING;
+
+INSERT INTO organization_members (organization_id, user_id, role)
+SELECT o.id, u.id, 'owner'
+FROM users u JOIN organizations o ON o.slug = 'user-' || u.id::text
+ON CONFLICT DO NOTHING;
+
+-- An owner address goes to the personal organization of whoever registered
+-- its first product
+INSERT INTO organization_addresses (stellar_address, organization_id, label)
+SELECT DISTINCT ON (p.owner_address) p.owner_address, o.id, 'personal'
+FROM products p JOIN organizations o ON o.slug = 'user-' || p.created_by
+WHERE length(p.owner_address) BETWEEN 1 AND 56
+ORDER BY p.owner_address, p.created_at
+ON CONFLICT DO NOTHING;
+
+UPDATE products p SET organization_id = a.organization_id
+FROM organization_addresses a WHERE a.stellar_address = p.owner_address;
+
+UPDATE api_keys k SET organization_id = o.id
+FROM organizations o WHERE o.slug = 'user-' || k.user_id::text;
+
+UPDATE carbon_credits c SET organization_id = o.id
+FROM organizations o WHERE o.slug = 'user-' || c.owner_id::text;
+
+-- Whether a product is visible to an organization (and, optionally, a member's
+-- own address): owned by it, or shared with one of its addresses by a grant
+CREATE OR REPLACE FUNCTION product_visible_to(viewer_org UUID, viewer_address TEXT, pid TEXT)
+RETURNS BOOLEAN LANGUAGE sql STABLE AS $$
+    SELECT EXISTS (
+        SELECT 1 FROM products p
+        WHERE p.id = pid AND (p.organization_id = viewer_org OR p.owner_address = viewer_address)
+    ) OR EXISTS (
+        SELECT 1 FROM product_access_grants g
+        LEFT JOIN organization_addresses a ON a.stellar_address = g.actor_address
+        WHERE g.product_id = pid
+          AND (a.organization_id = viewer_org OR g.actor_address = viewer_address)
+    )
+$$;
@@ -0,0 +1,22 @@
+-- Sign-In-With-Stellar (SEP-10): one-time challenge transactions a wallet
+-- signs to prove it holds the account's key
+
+CREATE TABLE IF NOT EXISTS wallet_challenges (
+    nonce VARCHAR(64) PRIMARY KEY,
+    stellar_address VARCHAR(56) NOT NULL,
+    -- `sign_in`, or `organization:<id>` to link the address to an organization
+    purpose VARCHAR(64) NOT NULL DEFAULT 'sign_in',
+    -- The challenge as issued: base64 XDR envelope signed by the server
+    transaction TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    consumed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_wallet_challenges_expires ON wallet_challenges(expires_at);
+
+-- Set the first time the user signs in with their wallet
+ALTER TABLE users ADD COLUMN IF NOT EXISTS stellar_address_verified_at TIMESTAMPTZ;
+
+COMMENT ON COLUMN users.stellar_address_verified_at IS
+    'When the user last proved control of stellar_address by signing a wallet challenge';
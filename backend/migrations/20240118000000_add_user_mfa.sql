@@ -0,0 +1,25 @@
+-- TOTP two-factor authentication. The shared secret is encrypted with the
+-- application encryption key under a random nonce; recovery codes are stored
+-- as SHA-256 hashes so a submitted code can be looked up directly.
+
+CREATE TABLE IF NOT EXISTS user_mfa (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    totp_secret TEXT NOT NULL,
+    -- NULL until the user proves their authenticator works
+    enabled_at TIMESTAMPTZ,
+    -- Last 30-second step a code was accepted for; older steps are refused
+    last_used_step BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS user_recovery_codes (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    code_hash TEXT NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, code_hash)
+);
+
+-- When the session last proved a second factor; sensitive endpoints ask for a
+-- recent one
+ALTER TABLE auth_sessions ADD COLUMN IF NOT EXISTS mfa_verified_at TIMESTAMPTZ;
@@ -0,0 +1,12 @@
+-- Per-key permissions. Scopes name what a key may read or change; existing
+-- keys keep the read-only access API keys have always had. The product,
+-- category and IP lists are optional narrowing; NULL means no restriction.
+
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS scopes TEXT[] NOT NULL DEFAULT ARRAY[
+    'products:read', 'events:read', 'locations:read', 'epcis:read',
+    'stats:read', 'financial:read', 'compliance:read', 'compliance:check'
+]::TEXT[];
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS allowed_product_ids TEXT[];
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS allowed_categories TEXT[];
+-- IP addresses and CIDR blocks the key may be used from
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS allowed_ips TEXT[];
@@ -0,0 +1,64 @@
+-- Outbound webhooks. A webhook belongs to an organization and only hears
+-- about products that organization can see, optionally narrowed to some
+-- event types and products. Every event it should hear about becomes a row in
+-- webhook_deliveries, a durable queue the delivery worker drains; each try is
+-- logged in webhook_delivery_attempts. Secrets are encrypted with the
+-- application encryption key.
+
+ALTER TABLE webhooks ADD COLUMN IF NOT EXISTS organization_id UUID REFERENCES organizations(id) ON DELETE CASCADE;
+-- NULL means every product the organization can see
+ALTER TABLE webhooks ADD COLUMN IF NOT EXISTS product_ids TEXT[];
+ALTER TABLE webhooks ADD COLUMN IF NOT EXISTS description TEXT;
+
+UPDATE webhooks SET events = '{}' WHERE events IS NULL;
+ALTER TABLE webhooks ALTER COLUMN events SET NOT NULL;
+
+-- Webhooks that predate organizations belong to their owner's personal one
+UPDATE webhooks w SET organization_id = (
+    SELECT m.organization_id FROM organization_members m
+    JOIN organizations o ON o.id = m.organization_id
+    WHERE m.user_id = w.user_id AND o.is_personal
+    LIMIT 1
+)
+WHERE organization_id IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_webhooks_organization_id ON webhooks(organization_id);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    webhook_id UUID NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+    -- Shared by every delivery of one event, and by replays
+    event_id UUID NOT NULL,
+    event_type TEXT NOT NULL,
+    product_id TEXT,
+    payload JSONB NOT NULL,
+    -- pending until a 2xx response, dead after too many failures
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'delivered', 'dead')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_status_code INTEGER,
+    last_error TEXT,
+    -- The dead or delivered delivery this one re-sends
+    replay_of UUID REFERENCES webhook_deliveries(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    delivered_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_due
+    ON webhook_deliveries(next_attempt_at) WHERE status = 'pending';
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_webhook
+    ON webhook_deliveries(webhook_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS webhook_delivery_attempts (
+    id BIGSERIAL PRIMARY KEY,
+    delivery_id UUID NOT NULL REFERENCES webhook_deliveries(id) ON DELETE CASCADE,
+    attempt INTEGER NOT NULL,
+    -- NULL when no response came back
+    status_code INTEGER,
+    error TEXT,
+    duration_ms INTEGER NOT NULL,
+    attempted_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_delivery_attempts_delivery
+    ON webhook_delivery_attempts(delivery_id, attempt);
@@ -0,0 +1,27 @@
+-- API key usage metering. Requests made with a key are counted in Redis per
+-- hour and endpoint and moved here in batches; counts are added to the row of
+-- their hour, so a batch that arrives late or in parts still adds up. Units
+-- are what the requests cost against the key's quota.
+
+CREATE TABLE IF NOT EXISTS api_key_usage_hourly (
+    api_key_id UUID NOT NULL REFERENCES api_keys(id) ON DELETE CASCADE,
+    hour TIMESTAMPTZ NOT NULL,
+    method TEXT NOT NULL,
+    -- Route pattern, e.g. /api/v1/products/:id
+    route TEXT NOT NULL,
+    requests BIGINT NOT NULL DEFAULT 0,
+    units BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (api_key_id, hour, method, route)
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_key_usage_hourly_hour ON api_key_usage_hourly(hour);
+
+-- One usage invoice per user and month, so the billing run can be repeated
+CREATE TABLE IF NOT EXISTS api_usage_invoices (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    period_start DATE NOT NULL,
+    -- NULL while the invoice is being created, or when nothing was due
+    invoice_id UUID REFERENCES invoices(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, period_start)
+);
@@ -0,0 +1,45 @@
+-- Alerting. Rules are evaluated by every instance in turn; what each alert
+-- last looked like, and when a notification about it last went out, is kept
+-- here so that a restart neither forgets a firing alert nor notifies again.
+
+CREATE TABLE IF NOT EXISTS alert_states (
+    -- rule:subject
+    fingerprint TEXT PRIMARY KEY,
+    rule TEXT NOT NULL,
+    subject TEXT NOT NULL DEFAULT '',
+    severity TEXT NOT NULL CHECK (severity IN ('info', 'warning', 'critical')),
+    status TEXT NOT NULL CHECK (status IN ('firing', 'resolved')),
+    summary TEXT NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    last_notified_at TIMESTAMPTZ,
+    resolved_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_alert_states_status ON alert_states(status);
+
+CREATE TABLE IF NOT EXISTS alert_silences (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- NULL matches every rule or subject
+    rule TEXT,
+    subject TEXT,
+    reason TEXT NOT NULL,
+    starts_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ends_at TIMESTAMPTZ NOT NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (ends_at > starts_at)
+);
+
+CREATE INDEX IF NOT EXISTS idx_alert_silences_ends_at ON alert_silences(ends_at);
+
+-- Conditions reported to the backend rather than measured by it, such as an
+-- oracle feed's circuit breaker or a failed contract upgrade
+CREATE TABLE IF NOT EXISTS alert_signals (
+    kind TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    active BOOLEAN NOT NULL,
+    detail TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (kind, subject)
+);
@@ -0,0 +1,4 @@
+-- W3C traceparent of the request that queued a webhook delivery, so that its
+-- attempts, however much later the worker makes them, join the same trace.
+
+ALTER TABLE webhook_deliveries ADD COLUMN IF NOT EXISTS traceparent TEXT;
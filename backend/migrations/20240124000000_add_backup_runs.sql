@@ -0,0 +1,17 @@
+-- Scheduled backups. Every instance runs the scheduler; the row it claims
+-- here under an advisory lock stops the others from taking the same backup.
+-- Backups themselves live on disk, described by their own manifest.
+
+CREATE TABLE IF NOT EXISTS backup_runs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- Directory name of the backup, e.g. 20240601T020000Z
+    backup_id TEXT NOT NULL UNIQUE,
+    status TEXT NOT NULL CHECK (status IN ('running', 'completed', 'failed')),
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ,
+    rows BIGINT,
+    bytes BIGINT,
+    error TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_backup_runs_started_at ON backup_runs(started_at);
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use crate::blockchain::config::BlockchainConfigManager;
+use crate::blockchain::provider::StellarProvider;
+use crate::blockchain::types::TransactionStatus;
+use crate::blockchain::{BlockchainNetwork, BlockchainProvider, SmartContractCall, Transaction};
+use crate::models::TrackingEvent;
+
+/// Writes off-chain records to Stellar: tracking events through the
+/// ChainLogistics Soroban contract, and bare digests as memo-hash transactions.
+#[derive(Clone)]
+pub struct ChainAnchor {
+    provider: Arc<dyn BlockchainProvider>,
+    contract_address: String,
+    source_account: Option<String>,
+}
+
+impl ChainAnchor {
+    pub fn new(
+        provider: Arc<dyn BlockchainProvider>,
+        contract_address: String,
+        source_account: Option<String>,
+    ) -> Self {
+        Self {
+            provider,
+            contract_address,
+            source_account,
+        }
+    }
+
+    /// Builds an anchor from `STELLAR_CONTRACT_ID` and `STELLAR_ANCHOR_ACCOUNT`,
+    /// or `None` when neither is set.
+    pub fn from_env() -> Option<Self> {
+        let config = BlockchainConfigManager::new().get_config(BlockchainNetwork::Stellar)?;
+        let source_account = std::env::var("STELLAR_ANCHOR_ACCOUNT")
+            .ok()
+            .filter(|a| !a.is_empty());
+        if config.contract_address.is_empty() && source_account.is_none() {
+            return None;
+        }
+        Some(Self::new(
+            Arc::new(StellarProvider::new(config.rpc_url)),
+            config.contract_address,
+            source_account,
+        ))
+    }
+
+    /// Whether tracking events can be recorded on the contract
+    pub fn has_contract(&self) -> bool {
+        !self.contract_address.is_empty()
+    }
+
+    /// Records a tracking event through `add_tracking_event`. Returns the contract result.
+    pub async fn anchor_event(&self, event: &TrackingEvent) -> Result<String, String> {
+        if !self.has_contract() {
+            return Err("STELLAR_CONTRACT_ID is not configured".to_string());
+        }
+        let call = SmartContractCall {
+            method: "add_tracking_event".to_string(),
+            params: vec![
+                event.actor_address.clone(),
+                event.product_id.clone(),
+                event.event_type.clone(),
+                event.location.clone(),
+                event.data_hash.clone(),
+                event.note.clone(),
+                event.metadata.to_string(),
+            ],
+            contract_address: self.contract_address.clone(),
+        };
+        self.provider.call_contract(&call).await
+    }
+
+    /// Publishes a hex SHA-256 digest as the memo of a zero-value self-payment
+    /// from the anchor account. Returns the transaction hash.
+    pub async fn anchor_digest(&self, digest: &str) -> Result<String, String> {
+        let account = self
+            .source_account
+            .clone()
+            .ok_or("STELLAR_ANCHOR_ACCOUNT is not configured")?;
+        if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("'{}' is not a SHA-256 digest", digest));
+        }
+        let tx = Transaction {
+            hash: String::new(),
+            from: account.clone(),
+            to: account,
+            value: "0".to_string(),
+            data: Some(digest.to_string()),
+            gas_price: None,
+            gas_limit: None,
+            nonce: None,
+            status: TransactionStatus::Pending,
+            confirmations: 0,
+            timestamp: chrono::Utc::now().timestamp(),
+        };
+        self.provider.send_transaction(&tx).await
+    }
+}
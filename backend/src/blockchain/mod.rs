@@ -1,7 +1,9 @@
 pub mod config;
 pub mod types;
 pub mod provider;
+pub mod anchor;
 
 pub use config::BlockchainConfig;
 pub use types::{BlockchainNetwork, Transaction, SmartContractCall};
 pub use provider::BlockchainProvider;
+pub use anchor::ChainAnchor;
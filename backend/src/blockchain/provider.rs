@@ -1,5 +1,12 @@
+use crate::blockchain::types::TransactionStatus;
 use crate::blockchain::{BlockchainNetwork, Transaction, SmartContractCall};
+use crate::monitoring::telemetry;
 use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tracing::Instrument;
+
+const RPC_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[async_trait]
 pub trait BlockchainProvider: Send + Sync {
@@ -13,11 +20,52 @@ pub trait BlockchainProvider: Send + Sync {
 
 pub struct StellarProvider {
     rpc_url: String,
+    client: reqwest::Client,
 }
 
 impl StellarProvider {
     pub fn new(rpc_url: String) -> Self {
-        StellarProvider { rpc_url }
+        let client = reqwest::Client::builder()
+            .timeout(RPC_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        StellarProvider { rpc_url, client }
+    }
+
+    /// Calls a Soroban RPC method and returns its result. The request carries
+    /// the current trace on in `traceparent`.
+    async fn rpc(&self, method: &str, params: Value) -> Result<Value, String> {
+        let span = tracing::info_span!(
+            "soroban_rpc",
+            otel.name = %format!("soroban {}", method),
+            otel.kind = "client",
+            rpc.system = "jsonrpc",
+            rpc.method = %method,
+        );
+        async {
+            let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+            let response = telemetry::inject(self.client.post(&self.rpc_url))
+                .json(&body)
+                .send()
+                .await
+                .map_err(|e| format!("Soroban RPC {} failed: {}", method, e))?;
+            if !response.status().is_success() {
+                return Err(format!("Soroban RPC {} responded with {}", method, response.status()));
+            }
+            let mut reply: Value = response
+                .json()
+                .await
+                .map_err(|e| format!("Soroban RPC {} returned invalid JSON: {}", method, e))?;
+            if let Some(error) = reply.get("error") {
+                return Err(format!("Soroban RPC {} failed: {}", method, error));
+            }
+            reply
+                .get_mut("result")
+                .map(Value::take)
+                .ok_or_else(|| format!("Soroban RPC {} returned no result", method))
+        }
+        .instrument(span)
+        .await
     }
 }
 
@@ -33,9 +81,36 @@ impl BlockchainProvider for StellarProvider {
         Ok("tx_hash".to_string())
     }
 
-    async fn get_transaction(&self, _hash: &str) -> Result<Transaction, String> {
-        // Implementation for Stellar transaction retrieval
-        Err("Not implemented".to_string())
+    /// Looks the transaction up with `getTransaction`. Source and destination
+    /// are left empty; they would have to be decoded from the envelope XDR.
+    async fn get_transaction(&self, hash: &str) -> Result<Transaction, String> {
+        let result = self.rpc("getTransaction", json!({ "hash": hash })).await?;
+        let status = match result["status"].as_str() {
+            Some("SUCCESS") => TransactionStatus::Confirmed,
+            Some("FAILED") => TransactionStatus::Failed,
+            Some("NOT_FOUND") => return Err(format!("Transaction {} not found", hash)),
+            other => return Err(format!("Unexpected transaction status {:?}", other)),
+        };
+        let ledger = result["ledger"].as_u64().unwrap_or(0);
+        let latest_ledger = result["latestLedger"].as_u64().unwrap_or(ledger);
+        // A number in newer RPC versions, a string in older ones
+        let created_at = result["createdAt"]
+            .as_i64()
+            .or_else(|| result["createdAt"].as_str().and_then(|t| t.parse().ok()))
+            .unwrap_or(0);
+        Ok(Transaction {
+            hash: hash.to_string(),
+            from: String::new(),
+            to: String::new(),
+            value: "0".to_string(),
+            data: None,
+            gas_price: None,
+            gas_limit: None,
+            nonce: None,
+            status,
+            confirmations: (latest_ledger.saturating_sub(ledger) + 1).min(u32::MAX as u64) as u32,
+            timestamp: created_at,
+        })
     }
 
     async fn call_contract(&self, _call: &SmartContractCall) -> Result<String, String> {
@@ -1,5 +1,15 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// `prev_hash` of the first entry in the chain
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuditLogEntry {
@@ -95,3 +105,309 @@ impl AuditLogger {
         )
     }
 }
+
+// ── Hash chain ────────────────────────────────────────────────────────────────
+
+/// A persisted row of `audit_logs`. Each row commits to its predecessor through
+/// `prev_hash`, so editing, inserting or deleting a row breaks the chain.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct AuditRecord {
+    pub id: Uuid,
+    pub sequence: i64,
+    pub user_id: Option<Uuid>,
+    pub action: String,
+    pub resource_type: Option<String>,
+    pub resource_id: Option<String>,
+    pub changes: Option<Value>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub prev_hash: String,
+    pub entry_hash: String,
+}
+
+impl AuditRecord {
+    /// SHA-256 over every column except `id` and `entry_hash`. JSON is
+    /// canonicalized because JSONB does not preserve key order.
+    pub fn compute_hash(&self) -> String {
+        let fields = [
+            self.sequence.to_string(),
+            self.prev_hash.clone(),
+            self.user_id.map(|u| u.to_string()).unwrap_or_default(),
+            self.action.clone(),
+            self.resource_type.clone().unwrap_or_default(),
+            self.resource_id.clone().unwrap_or_default(),
+            self.changes.as_ref().map(canonical_json).unwrap_or_default(),
+            self.ip_address.clone().unwrap_or_default(),
+            self.user_agent.clone().unwrap_or_default(),
+            self.created_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+        ];
+        hex::encode(Sha256::digest(fields.join("\u{1f}").as_bytes()))
+    }
+}
+
+/// Entry to append to the chain; sequence, hashes and timestamp are assigned on insert
+#[derive(Debug, Clone)]
+pub struct NewAuditEntry {
+    pub user_id: Option<Uuid>,
+    pub action: String,
+    pub resource_type: Option<String>,
+    pub resource_id: Option<String>,
+    pub changes: Option<Value>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// Chain head published on-chain
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct AuditAnchor {
+    pub id: Uuid,
+    pub sequence: i64,
+    pub entry_hash: String,
+    pub network: String,
+    pub transaction_hash: String,
+    pub anchored_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditChainVerification {
+    pub valid: bool,
+    pub verified_entries: i64,
+    pub head_sequence: Option<i64>,
+    pub head_hash: Option<String>,
+    pub latest_anchor: Option<AuditAnchor>,
+    pub issues: Vec<AuditChainIssue>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditChainIssueKind {
+    /// Sequence numbers are missing: rows were deleted
+    Gap,
+    /// `prev_hash` does not match the preceding row
+    BrokenLink,
+    /// The stored hash does not match the row contents: the row was edited
+    HashMismatch,
+    /// The row differs from the hash anchored on-chain for its sequence
+    AnchorMismatch,
+    /// An anchored sequence no longer exists: the tail was deleted
+    Truncated,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AuditChainIssue {
+    pub sequence: i64,
+    pub kind: AuditChainIssueKind,
+    pub detail: String,
+}
+
+/// Position reached while verifying the chain page by page
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainCursor {
+    pub next_sequence: i64,
+    pub prev_hash: String,
+}
+
+impl Default for ChainCursor {
+    fn default() -> Self {
+        Self {
+            next_sequence: 1,
+            prev_hash: GENESIS_HASH.to_string(),
+        }
+    }
+}
+
+/// Verifies `records` (ordered by sequence) continuing from `cursor`.
+/// `anchors` maps anchored sequences to their on-chain entry hash.
+pub fn verify_chain(
+    records: &[AuditRecord],
+    cursor: &mut ChainCursor,
+    anchors: &HashMap<i64, String>,
+) -> Vec<AuditChainIssue> {
+    let mut issues = Vec::new();
+    let mut issue = |sequence: i64, kind: AuditChainIssueKind, detail: String| {
+        issues.push(AuditChainIssue {
+            sequence,
+            kind,
+            detail,
+        })
+    };
+
+    for record in records {
+        if record.sequence != cursor.next_sequence {
+            // The link check would only repeat the gap
+            issue(
+                record.sequence,
+                AuditChainIssueKind::Gap,
+                format!(
+                    "expected sequence {}, found {}",
+                    cursor.next_sequence, record.sequence
+                ),
+            );
+        } else if record.prev_hash != cursor.prev_hash {
+            issue(
+                record.sequence,
+                AuditChainIssueKind::BrokenLink,
+                "prev_hash does not match the preceding entry".to_string(),
+            );
+        }
+
+        if record.compute_hash() != record.entry_hash {
+            issue(
+                record.sequence,
+                AuditChainIssueKind::HashMismatch,
+                "entry contents do not match entry_hash".to_string(),
+            );
+        }
+
+        if let Some(anchored) = anchors.get(&record.sequence) {
+            if anchored != &record.entry_hash {
+                issue(
+                    record.sequence,
+                    AuditChainIssueKind::AnchorMismatch,
+                    format!("anchored hash is {}", anchored),
+                );
+            }
+        }
+
+        cursor.next_sequence = record.sequence + 1;
+        cursor.prev_hash = record.entry_hash.clone();
+    }
+
+    issues
+}
+
+/// Reports anchors beyond the last verified sequence
+pub fn check_truncation(cursor: &ChainCursor, anchors: &HashMap<i64, String>) -> Vec<AuditChainIssue> {
+    let mut missing: Vec<i64> = anchors
+        .keys()
+        .copied()
+        .filter(|seq| *seq >= cursor.next_sequence)
+        .collect();
+    missing.sort_unstable();
+    missing
+        .into_iter()
+        .map(|sequence| AuditChainIssue {
+            sequence,
+            kind: AuditChainIssueKind::Truncated,
+            detail: "anchored entry is missing".to_string(),
+        })
+        .collect()
+}
+
+/// JSON with object keys sorted at every level
+pub fn canonical_json(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let body: Vec<String> = keys
+                .into_iter()
+                .map(|k| format!("{}:{}", Value::String(k.clone()), canonical_json(&map[k])))
+                .collect();
+            format!("{{{}}}", body.join(","))
+        }
+        Value::Array(items) => {
+            let body: Vec<String> = items.iter().map(canonical_json).collect();
+            format!("[{}]", body.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    fn chain(len: i64) -> Vec<AuditRecord> {
+        let mut prev = GENESIS_HASH.to_string();
+        (1..=len)
+            .map(|sequence| {
+                let mut record = AuditRecord {
+                    id: Uuid::new_v4(),
+                    sequence,
+                    user_id: None,
+                    action: "POST /api/v1/admin/products".to_string(),
+                    resource_type: Some("products".to_string()),
+                    resource_id: None,
+                    changes: Some(json!({"status": 201, "method": "POST"})),
+                    ip_address: Some("10.0.0.1".to_string()),
+                    user_agent: None,
+                    created_at: Utc.timestamp_opt(1_700_000_000 + sequence, 0).unwrap(),
+                    prev_hash: prev.clone(),
+                    entry_hash: String::new(),
+                };
+                record.entry_hash = record.compute_hash();
+                prev = record.entry_hash.clone();
+                record
+            })
+            .collect()
+    }
+
+    fn kinds(issues: &[AuditChainIssue]) -> Vec<AuditChainIssueKind> {
+        issues.iter().map(|i| i.kind).collect()
+    }
+
+    #[test]
+    fn test_intact_chain_verifies_across_pages() {
+        let records = chain(5);
+        let mut cursor = ChainCursor::default();
+        let anchors = HashMap::from([(3, records[2].entry_hash.clone())]);
+
+        assert!(verify_chain(&records[..2], &mut cursor, &anchors).is_empty());
+        assert!(verify_chain(&records[2..], &mut cursor, &anchors).is_empty());
+        assert!(check_truncation(&cursor, &anchors).is_empty());
+        assert_eq!(cursor.next_sequence, 6);
+    }
+
+    #[test]
+    fn test_detects_edit_and_deletion() {
+        let mut records = chain(4);
+        records[1].action = "DELETE /api/v1/admin/products/:id".to_string();
+        let issues = verify_chain(&records, &mut ChainCursor::default(), &HashMap::new());
+        assert_eq!(kinds(&issues), vec![AuditChainIssueKind::HashMismatch]);
+
+        let mut records = chain(4);
+        records.remove(2);
+        let issues = verify_chain(&records, &mut ChainCursor::default(), &HashMap::new());
+        assert_eq!(kinds(&issues), vec![AuditChainIssueKind::Gap]);
+    }
+
+    #[test]
+    fn test_rehashed_rewrite_is_caught_by_anchor() {
+        // An attacker edits row 2 and recomputes every later hash
+        let original = chain(3);
+        let mut forged = chain(3);
+        forged[1].user_agent = Some("forged".to_string());
+        for i in 1..forged.len() {
+            forged[i].prev_hash = forged[i - 1].entry_hash.clone();
+            forged[i].entry_hash = forged[i].compute_hash();
+        }
+
+        let anchors = HashMap::from([(3, original[2].entry_hash.clone())]);
+        let issues = verify_chain(&forged, &mut ChainCursor::default(), &anchors);
+        assert_eq!(kinds(&issues), vec![AuditChainIssueKind::AnchorMismatch]);
+    }
+
+    #[test]
+    fn test_truncated_tail_is_reported() {
+        let records = chain(4);
+        let anchors = HashMap::from([(4, records[3].entry_hash.clone())]);
+        let mut cursor = ChainCursor::default();
+        verify_chain(&records[..3], &mut cursor, &anchors);
+        assert_eq!(
+            kinds(&check_truncation(&cursor, &anchors)),
+            vec![AuditChainIssueKind::Truncated]
+        );
+    }
+
+    #[test]
+    fn test_canonical_json_sorts_keys() {
+        assert_eq!(
+            canonical_json(&json!({"b": 1, "a": {"d": [true, null], "c": "x"}})),
+            r#"{"a":{"c":"x","d":[true,null]},"b":1}"#
+        );
+    }
+}
@@ -2,6 +2,6 @@ pub mod rules;
 pub mod validator;
 pub mod audit;
 
-pub use rules::{ComplianceRule, ComplianceType};
-pub use validator::ComplianceValidator;
+pub use rules::{ComplianceRule, ComplianceType, RuleFormat};
+pub use validator::{ComplianceValidator, ValidationResult};
 pub use audit::AuditLogger;
@@ -1,4 +1,20 @@
+/// Declarative compliance rules.
+///
+/// A rule is plain data (JSON or YAML) stored in `compliance_rules`, so new
+/// requirements can be rolled out without a release. Each rule is a list of
+/// checks evaluated by `ComplianceValidator`:
+///   - `required`:        a field must be present
+///   - `constraint`:      a field compared against a literal (numbers, dates, lists, lengths)
+///   - `pattern`:         a string field must match a regex
+///   - `compare_fields`:  two fields of the same record compared with each other
+///   - `event_sequence`:  every trigger event needs a matching event within a time window
+///
+/// Any check can carry a `when` condition, which makes it apply only to
+/// records where that condition holds (cross-field requirements).
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ComplianceType {
@@ -9,6 +25,7 @@ pub enum ComplianceType {
     OrganicCertification,
     SOC2,
     ISO27001,
+    EUDR,
 }
 
 impl ComplianceType {
@@ -21,6 +38,7 @@ impl ComplianceType {
             ComplianceType::OrganicCertification => "organic_certification",
             ComplianceType::SOC2 => "soc2",
             ComplianceType::ISO27001 => "iso27001",
+            ComplianceType::EUDR => "eudr",
         }
     }
 
@@ -33,77 +51,425 @@ impl ComplianceType {
             ComplianceType::OrganicCertification => 1095, // 3 years
             ComplianceType::SOC2 => 1095, // 3 years
             ComplianceType::ISO27001 => 1095, // 3 years
+            ComplianceType::EUDR => 1825, // 5 years
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// ── Rule definition ───────────────────────────────────────────────────────────
+
+/// Outcome weight of a failed check. Only `error` makes a record non-compliant.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    #[default]
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ComplianceRule {
     pub rule_id: String,
-    pub compliance_type: ComplianceType,
+    /// Assigned by the store when the rule is saved; ignored on input
+    #[serde(default)]
+    pub version: i32,
+    /// Free-form so new regimes need no code change; known values match `ComplianceType::as_str`
+    pub compliance_type: String,
     pub description: String,
-    pub validation_logic: String,
-    pub required_fields: Vec<String>,
-    pub is_active: bool,
+    /// Severity for checks that do not set their own
+    #[serde(default)]
+    pub severity: Severity,
+    pub checks: Vec<RuleCheck>,
+    /// Products evaluated against this rule whenever they receive an event.
+    /// Rules without a scope only run on explicit checks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub applies_to: Option<RuleScope>,
+}
+
+/// Product selector for continuous evaluation. Every non-empty list must
+/// match; a scope with all lists empty applies to every product.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct RuleScope {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<String>,
+    /// Matches when the product has any of these tags
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Matches when the product holds any of these certifications
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub certifications: Vec<String>,
+}
+
+impl RuleScope {
+    pub fn matches(&self, category: &str, tags: &[String], certifications: &[String]) -> bool {
+        let any = |wanted: &[String], have: &[String]| {
+            wanted.is_empty()
+                || wanted
+                    .iter()
+                    .any(|w| have.iter().any(|h| h.eq_ignore_ascii_case(w)))
+        };
+        any(&self.categories, &[category.to_string()])
+            && any(&self.tags, tags)
+            && any(&self.certifications, certifications)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RuleCheck {
+    #[serde(flatten)]
+    pub kind: CheckKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    /// Reported instead of the generated message when the check fails
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// The check only applies when this condition holds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<Condition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CheckKind {
+    Required {
+        field: String,
+    },
+    /// Skipped when the field is absent; pair with `required` to demand it
+    Constraint {
+        field: String,
+        op: Operator,
+        value: Value,
+    },
+    Pattern {
+        field: String,
+        #[schema(value_type = String)]
+        regex: RulePattern,
+    },
+    CompareFields {
+        field: String,
+        op: Operator,
+        other_field: String,
+    },
+    EventSequence {
+        trigger: EventMatcher,
+        require: EventMatcher,
+        within_hours: f64,
+        #[serde(default)]
+        direction: SequenceDirection,
+    },
+}
+
+/// A regex compiled when the rule is deserialized, so a definition with a
+/// malformed pattern is rejected on save and evaluation reuses the compiled
+/// form. Serializes back to its source string.
+#[derive(Debug, Clone)]
+pub struct RulePattern(regex::Regex);
+
+impl RulePattern {
+    pub fn new(source: &str) -> Result<Self, regex::Error> {
+        regex::Regex::new(source).map(RulePattern)
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.0.is_match(text)
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl std::fmt::Display for RulePattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for RulePattern {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for RulePattern {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        RulePattern::new(&source)
+            .map_err(|e| serde::de::Error::custom(format!("invalid regex: {}", e)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Operator {
+    Eq,
+    Ne,
+    /// Numbers, or dates when both sides are RFC3339 / relative (`now`, `now+90d`)
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    In,
+    NotIn,
+    MinLength,
+    MaxLength,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Condition {
+    pub field: String,
+    /// Omit to test only that the field is present
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub op: Option<Operator>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+}
+
+/// Selects tracking events by type and/or a metadata field being present
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct EventMatcher {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_field: Option<String>,
+}
+
+/// Where the required event may fall relative to the trigger
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SequenceDirection {
+    Before,
+    After,
+    #[default]
+    Either,
+}
+
+/// Source format of a submitted rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleFormat {
+    Json,
+    Yaml,
+}
+
+impl RuleFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleFormat::Json => "json",
+            RuleFormat::Yaml => "yaml",
+        }
+    }
 }
 
 impl ComplianceRule {
-    pub fn gdpr_data_residency() -> Self {
-        ComplianceRule {
-            rule_id: "gdpr_residency".to_string(),
-            compliance_type: ComplianceType::GDPR,
-            description: "Ensure personal data is stored in EU data centers".to_string(),
-            validation_logic: "data_location == 'EU'".to_string(),
-            required_fields: vec!["data_location".to_string(), "consent".to_string()],
-            is_active: true,
+    /// Parses and validates a submitted definition
+    pub fn parse(source: &str, format: RuleFormat) -> Result<Self, String> {
+        let rule: ComplianceRule = match format {
+            RuleFormat::Json => serde_json::from_str(source).map_err(|e| e.to_string())?,
+            RuleFormat::Yaml => serde_yaml::from_str(source).map_err(|e| e.to_string())?,
+        };
+        rule.check_definition()?;
+        Ok(rule)
+    }
+
+    /// Rejects definitions that could never evaluate cleanly, so problems
+    /// surface when the rule is saved rather than on every validation.
+    pub fn check_definition(&self) -> Result<(), String> {
+        let id_ok = !self.rule_id.is_empty()
+            && self.rule_id.len() <= 100
+            && self
+                .rule_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.');
+        if !id_ok {
+            return Err("rule_id must be 1-100 characters of [A-Za-z0-9_.-]".to_string());
+        }
+        if self.compliance_type.trim().is_empty() {
+            return Err("compliance_type is required".to_string());
+        }
+        if self.checks.is_empty() {
+            return Err("a rule needs at least one check".to_string());
         }
+
+        for (index, check) in self.checks.iter().enumerate() {
+            check_definition(check).map_err(|e| format!("checks[{}]: {}", index, e))?;
+        }
+        Ok(())
     }
+}
 
-    pub fn fda_electronic_signature() -> Self {
-        ComplianceRule {
-            rule_id: "fda_esig".to_string(),
-            compliance_type: ComplianceType::FDA21CFR11,
-            description: "Require electronic signatures for pharmaceutical records".to_string(),
-            validation_logic: "has_digital_signature && signature_timestamp".to_string(),
-            required_fields: vec!["digital_signature".to_string(), "signer_id".to_string()],
-            is_active: true,
+fn check_definition(check: &RuleCheck) -> Result<(), String> {
+    if let Some(condition) = &check.when {
+        require_field(&condition.field)?;
+        match (&condition.op, &condition.value) {
+            (Some(op), Some(value)) => check_operand(*op, value)?,
+            (None, None) => {}
+            _ => return Err("`when` needs both `op` and `value`, or neither".to_string()),
         }
     }
 
-    pub fn fsma_traceability() -> Self {
-        ComplianceRule {
-            rule_id: "fsma_trace".to_string(),
-            compliance_type: ComplianceType::FSMA,
-            description: "Maintain complete traceability for food products".to_string(),
-            validation_logic: "has_complete_chain_of_custody".to_string(),
-            required_fields: vec![
-                "origin".to_string(),
-                "processing_steps".to_string(),
-                "distribution".to_string(),
-            ],
-            is_active: true,
+    match &check.kind {
+        CheckKind::Required { field } => require_field(field),
+        CheckKind::Constraint { field, op, value } => {
+            require_field(field)?;
+            check_operand(*op, value)
+        }
+        // The regex itself was compiled, and rejected if malformed, during deserialization
+        CheckKind::Pattern { field, .. } => require_field(field),
+        CheckKind::CompareFields {
+            field,
+            op,
+            other_field,
+        } => {
+            require_field(field)?;
+            require_field(other_field)?;
+            match op {
+                Operator::In | Operator::NotIn | Operator::MinLength | Operator::MaxLength => {
+                    Err(format!("operator {:?} cannot compare two fields", op))
+                }
+                _ => Ok(()),
+            }
+        }
+        CheckKind::EventSequence {
+            trigger,
+            require,
+            within_hours,
+            ..
+        } => {
+            for matcher in [trigger, require] {
+                if matcher.event_type.is_none() && matcher.metadata_field.is_none() {
+                    return Err("event matchers need an event_type or metadata_field".to_string());
+                }
+            }
+            if !within_hours.is_finite() || *within_hours <= 0.0 {
+                return Err("within_hours must be positive".to_string());
+            }
+            Ok(())
         }
     }
+}
+
+fn require_field(field: &str) -> Result<(), String> {
+    if field.trim().is_empty() {
+        Err("field must not be empty".to_string())
+    } else {
+        Ok(())
+    }
+}
 
-    pub fn conflict_minerals_due_diligence() -> Self {
-        ComplianceRule {
-            rule_id: "conflict_minerals".to_string(),
-            compliance_type: ComplianceType::ConflictMinerals,
-            description: "Verify conflict-free mineral sourcing".to_string(),
-            validation_logic: "has_due_diligence_report && verified_by_auditor".to_string(),
-            required_fields: vec!["mineral_source".to_string(), "audit_report".to_string()],
-            is_active: true,
+fn check_operand(op: Operator, value: &Value) -> Result<(), String> {
+    match op {
+        Operator::In | Operator::NotIn if !value.is_array() => {
+            Err(format!("operator {:?} needs an array value", op))
         }
+        Operator::MinLength | Operator::MaxLength if value.as_u64().is_none() => {
+            Err(format!("operator {:?} needs a non-negative integer", op))
+        }
+        Operator::Gt | Operator::Gte | Operator::Lt | Operator::Lte
+            if !value.is_number() && value.as_str().and_then(parse_date).is_none() =>
+        {
+            Err(format!("operator {:?} needs a number or a date", op))
+        }
+        _ => Ok(()),
     }
+}
+
+// ── Value helpers ─────────────────────────────────────────────────────────────
+
+/// Resolves a dotted path (`origin.country`, `steps.0.name`) inside a JSON value
+pub fn lookup<'a>(data: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(data, |current, segment| match current {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        })
+        .filter(|value| !value.is_null())
+}
 
-    pub fn organic_certification() -> Self {
-        ComplianceRule {
-            rule_id: "organic_cert".to_string(),
-            compliance_type: ComplianceType::OrganicCertification,
-            description: "Verify organic certification status".to_string(),
-            validation_logic: "has_valid_organic_cert && cert_not_expired".to_string(),
-            required_fields: vec!["certification_body".to_string(), "cert_expiry".to_string()],
-            is_active: true,
+/// RFC3339 timestamps, plain dates, or `now` with an optional `+/-N` offset in
+/// `h` or `d` (`now+90d`)
+pub fn parse_date(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    if let Some(offset) = text.strip_prefix("now") {
+        if offset.is_empty() {
+            return Some(Utc::now());
         }
+        let (sign, rest) = match offset.as_bytes()[0] {
+            b'+' => (1, &offset[1..]),
+            b'-' => (-1, &offset[1..]),
+            _ => return None,
+        };
+        let unit = rest.chars().last()?;
+        let amount: i64 = rest[..rest.len() - unit.len_utf8()].parse().ok()?;
+        let duration = match unit {
+            'h' => Duration::hours(amount),
+            'd' => Duration::days(amount),
+            _ => return None,
+        };
+        return Some(Utc::now() + duration * sign);
+    }
+
+    DateTime::parse_from_rfc3339(text)
+        .map(|d| d.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+                .map(|d| d.and_utc())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rejects_invalid_definitions() {
+        let bad_regex = r#"{"rule_id": "r", "compliance_type": "gdpr", "description": "d",
+            "checks": [{"type": "pattern", "field": "f", "regex": "("}]}"#;
+        let err = ComplianceRule::parse(bad_regex, RuleFormat::Json).unwrap_err();
+        assert!(err.contains("invalid regex"), "{}", err);
+
+        let stored: Result<ComplianceRule, _> = serde_json::from_str(bad_regex);
+        assert!(stored.is_err(), "stored definitions are compiled on load too");
+
+        let bad_operand = "rule_id: r\ncompliance_type: gdpr\ndescription: d\nchecks:\n  - {type: constraint, field: f, op: in, value: EU}\n";
+        assert!(ComplianceRule::parse(bad_operand, RuleFormat::Yaml).is_err());
+
+        let bad_id = r#"{"rule_id": "a b", "compliance_type": "gdpr", "description": "d",
+            "checks": [{"type": "required", "field": "f"}]}"#;
+        assert!(ComplianceRule::parse(bad_id, RuleFormat::Json).is_err());
+    }
+
+    #[test]
+    fn test_rule_scope_matching() {
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let scope = RuleScope {
+            categories: strings(&["Food", "Beverage"]),
+            tags: vec![],
+            certifications: strings(&["organic"]),
+        };
+        assert!(scope.matches("food", &[], &strings(&["ISO9001", "Organic"])));
+        assert!(!scope.matches("food", &[], &strings(&["ISO9001"])));
+        assert!(!scope.matches("electronics", &[], &strings(&["organic"])));
+        assert!(RuleScope::default().matches("anything", &[], &[]));
+    }
+
+    #[test]
+    fn test_lookup_and_relative_dates() {
+        let data = serde_json::json!({"origin": {"plots": [{"id": "P1"}]}, "empty": null});
+        assert_eq!(lookup(&data, "origin.plots.0.id"), Some(&Value::from("P1")));
+        assert!(lookup(&data, "origin.plots.1.id").is_none());
+        assert!(lookup(&data, "empty").is_none());
+
+        let ahead = parse_date("now+90d").unwrap() - Utc::now();
+        assert!((ahead - Duration::days(90)).num_seconds().abs() < 5);
+        assert!(parse_date("now+90x").is_none());
+        assert!(parse_date("2024-03-01").is_some());
     }
 }
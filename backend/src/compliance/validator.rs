@@ -1,130 +1,155 @@
-use crate::compliance::{ComplianceRule, ComplianceType};
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use utoipa::ToSchema;
+
+use crate::compliance::rules::{
+    lookup, parse_date, CheckKind, ComplianceRule, Condition, EventMatcher, Operator, RuleCheck,
+    SequenceDirection, Severity,
+};
+use crate::models::TrackingEvent;
 
 pub struct ComplianceValidator;
 
-#[derive(Debug, Clone)]
+/// One failed check
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Finding {
+    /// Index into the rule's `checks`
+    pub check: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ValidationResult {
     pub is_compliant: bool,
-    pub compliance_type: ComplianceType,
+    pub rule_id: String,
+    /// Version of the rule definition the data was checked against
+    pub rule_version: i32,
+    pub compliance_type: String,
     pub violations: Vec<String>,
     pub warnings: Vec<String>,
+    pub findings: Vec<Finding>,
 }
 
 impl ComplianceValidator {
-    pub fn validate(rule: &ComplianceRule, data: &Value) -> ValidationResult {
-        let mut violations = Vec::new();
-        let mut warnings = Vec::new();
-
-        // Check required fields
-        for field in &rule.required_fields {
-            if data.get(field).is_none() {
-                violations.push(format!("Missing required field: {}", field));
-            }
-        }
+    /// Evaluates `data` (and, for sequence checks, the product's `events`)
+    /// against every check of `rule`.
+    pub fn validate(
+        rule: &ComplianceRule,
+        data: &Value,
+        events: &[TrackingEvent],
+    ) -> ValidationResult {
+        let mut findings = Vec::new();
 
-        // Type-specific validations
-        match rule.compliance_type {
-            ComplianceType::GDPR => {
-                Self::validate_gdpr(data, &mut violations, &mut warnings);
-            }
-            ComplianceType::FDA21CFR11 => {
-                Self::validate_fda(data, &mut violations, &mut warnings);
-            }
-            ComplianceType::FSMA => {
-                Self::validate_fsma(data, &mut violations, &mut warnings);
-            }
-            ComplianceType::ConflictMinerals => {
-                Self::validate_conflict_minerals(data, &mut violations, &mut warnings);
+        for (index, check) in rule.checks.iter().enumerate() {
+            if let Some(condition) = &check.when {
+                if !condition_holds(condition, data) {
+                    continue;
+                }
             }
-            ComplianceType::OrganicCertification => {
-                Self::validate_organic(data, &mut violations, &mut warnings);
+            let severity = check.severity.unwrap_or(rule.severity);
+            for message in Self::run_check(check, data, events) {
+                findings.push(Finding {
+                    check: index,
+                    severity,
+                    message,
+                });
             }
-            _ => {}
         }
 
+        let messages = |severity: Severity| {
+            findings
+                .iter()
+                .filter(|f| f.severity == severity)
+                .map(|f| f.message.clone())
+                .collect::<Vec<_>>()
+        };
+        let violations = messages(Severity::Error);
+        let warnings = messages(Severity::Warning);
+
         ValidationResult {
             is_compliant: violations.is_empty(),
-            compliance_type: rule.compliance_type,
+            rule_id: rule.rule_id.clone(),
+            rule_version: rule.version,
+            compliance_type: rule.compliance_type.clone(),
             violations,
             warnings,
+            findings,
         }
     }
 
-    fn validate_gdpr(data: &Value, violations: &mut Vec<String>, warnings: &mut Vec<String>) {
-        if let Some(location) = data.get("data_location") {
-            if location.as_str() != Some("EU") {
-                violations.push("GDPR: Data must be stored in EU".to_string());
-            }
-        }
-
-        if data.get("consent").is_none() {
-            violations.push("GDPR: User consent required".to_string());
-        }
-
-        if data.get("right_to_be_forgotten_enabled").is_none() {
-            warnings.push("GDPR: Right to be forgotten not configured".to_string());
-        }
-    }
-
-    fn validate_fda(data: &Value, violations: &mut Vec<String>, _warnings: &mut Vec<String>) {
-        if data.get("digital_signature").is_none() {
-            violations.push("FDA 21 CFR Part 11: Digital signature required".to_string());
-        }
-
-        if data.get("signer_id").is_none() {
-            violations.push("FDA 21 CFR Part 11: Signer identification required".to_string());
-        }
-
-        if data.get("timestamp").is_none() {
-            violations.push("FDA 21 CFR Part 11: Timestamp required".to_string());
-        }
-    }
-
-    fn validate_fsma(data: &Value, violations: &mut Vec<String>, _warnings: &mut Vec<String>) {
-        if data.get("origin").is_none() {
-            violations.push("FSMA: Product origin required".to_string());
-        }
+    /// Failure messages for a single check; empty when it passes
+    fn run_check(check: &RuleCheck, data: &Value, events: &[TrackingEvent]) -> Vec<String> {
+        let fail = |generated: String| vec![check.message.clone().unwrap_or(generated)];
 
-        if data.get("processing_steps").is_none() {
-            violations.push("FSMA: Processing steps required".to_string());
-        }
-
-        if data.get("distribution").is_none() {
-            violations.push("FSMA: Distribution information required".to_string());
-        }
-    }
-
-    fn validate_conflict_minerals(data: &Value, violations: &mut Vec<String>, warnings: &mut Vec<String>) {
-        if data.get("mineral_source").is_none() {
-            violations.push("Conflict Minerals: Source information required".to_string());
-        }
-
-        if data.get("audit_report").is_none() {
-            violations.push("Conflict Minerals: Audit report required".to_string());
-        }
-
-        if data.get("verified_by_auditor").as_bool() != Some(true) {
-            warnings.push("Conflict Minerals: Awaiting auditor verification".to_string());
-        }
-    }
-
-    fn validate_organic(data: &Value, violations: &mut Vec<String>, warnings: &mut Vec<String>) {
-        if data.get("certification_body").is_none() {
-            violations.push("Organic: Certification body required".to_string());
-        }
-
-        if let Some(expiry) = data.get("cert_expiry") {
-            if let Some(expiry_str) = expiry.as_str() {
-                if let Ok(expiry_date) = chrono::DateTime::parse_from_rfc3339(expiry_str) {
-                    if expiry_date < chrono::Utc::now() {
-                        violations.push("Organic: Certification expired".to_string());
-                    } else if expiry_date.timestamp() - chrono::Utc::now().timestamp() < 7776000 {
-                        // 90 days
-                        warnings.push("Organic: Certification expiring soon".to_string());
-                    }
+        match &check.kind {
+            CheckKind::Required { field } => match lookup(data, field) {
+                Some(_) => vec![],
+                None => fail(format!("Missing required field: {}", field)),
+            },
+            CheckKind::Constraint { field, op, value } => match lookup(data, field) {
+                None => vec![],
+                Some(actual) if compare(*op, actual, value) == Some(true) => vec![],
+                Some(_) => fail(format!("{} must be {} {}", field, describe(*op), value)),
+            },
+            CheckKind::Pattern { field, regex } => {
+                let Some(actual) = lookup(data, field) else {
+                    return vec![];
+                };
+                if actual.as_str().is_some_and(|text| regex.is_match(text)) {
+                    vec![]
+                } else {
+                    fail(format!("{} does not match {}", field, regex))
                 }
             }
+            CheckKind::CompareFields {
+                field,
+                op,
+                other_field,
+            } => match (lookup(data, field), lookup(data, other_field)) {
+                (Some(left), Some(right)) if compare(*op, left, right) != Some(true) => fail(
+                    format!("{} must be {} {}", field, describe(*op), other_field),
+                ),
+                _ => vec![],
+            },
+            CheckKind::EventSequence {
+                trigger,
+                require,
+                within_hours,
+                direction,
+            } => {
+                let window = Duration::seconds((within_hours * 3600.0) as i64);
+                events
+                    .iter()
+                    .filter(|e| event_matches(trigger, e))
+                    .filter(|t| {
+                        !events.iter().any(|e| {
+                            let offset = e.timestamp - t.timestamp;
+                            let in_direction = match direction {
+                                SequenceDirection::Before => offset <= Duration::zero(),
+                                SequenceDirection::After => offset >= Duration::zero(),
+                                SequenceDirection::Either => true,
+                            };
+                            in_direction && offset.abs() <= window && event_matches(require, e)
+                        })
+                    })
+                    .map(|t| {
+                        let generated = format!(
+                            "{} event {} at {} has no {} event within {}h",
+                            describe_matcher(trigger),
+                            t.id,
+                            t.timestamp.to_rfc3339(),
+                            describe_matcher(require),
+                            within_hours
+                        );
+                        match &check.message {
+                            Some(message) => format!("{} (event {})", message, t.id),
+                            None => generated,
+                        }
+                    })
+                    .collect()
+            }
         }
     }
 
@@ -140,7 +165,9 @@ impl ComplianceValidator {
             "compliance_rate": if total > 0 { (compliant as f64 / total as f64) * 100.0 } else { 0.0 },
             "details": validations.iter().map(|v| {
                 json!({
-                    "type": v.compliance_type.as_str(),
+                    "type": v.compliance_type,
+                    "rule_id": v.rule_id,
+                    "rule_version": v.rule_version,
                     "is_compliant": v.is_compliant,
                     "violations": v.violations,
                     "warnings": v.warnings,
@@ -149,3 +176,241 @@ impl ComplianceValidator {
         })
     }
 }
+
+fn condition_holds(condition: &Condition, data: &Value) -> bool {
+    match (
+        lookup(data, &condition.field),
+        condition.op,
+        &condition.value,
+    ) {
+        (None, _, _) => false,
+        (Some(actual), Some(op), Some(expected)) => compare(op, actual, expected) == Some(true),
+        (Some(_), _, _) => true,
+    }
+}
+
+/// `None` when the two values cannot be compared with `op`
+fn compare(op: Operator, left: &Value, right: &Value) -> Option<bool> {
+    match op {
+        Operator::Eq => Some(values_equal(left, right)),
+        Operator::Ne => Some(!values_equal(left, right)),
+        Operator::In => Some(right.as_array()?.iter().any(|v| values_equal(left, v))),
+        Operator::NotIn => Some(!right.as_array()?.iter().any(|v| values_equal(left, v))),
+        Operator::MinLength => Some(length(left)? >= right.as_u64()? as usize),
+        Operator::MaxLength => Some(length(left)? <= right.as_u64()? as usize),
+        Operator::Gt | Operator::Gte | Operator::Lt | Operator::Lte => {
+            let ordering = match (left.as_f64(), right.as_f64()) {
+                (Some(l), Some(r)) => l.partial_cmp(&r)?,
+                _ => parse_date(left.as_str()?)?.cmp(&parse_date(right.as_str()?)?),
+            };
+            Some(match op {
+                Operator::Gt => ordering.is_gt(),
+                Operator::Gte => ordering.is_ge(),
+                Operator::Lt => ordering.is_lt(),
+                _ => ordering.is_le(),
+            })
+        }
+    }
+}
+
+fn values_equal(left: &Value, right: &Value) -> bool {
+    match (left.as_f64(), right.as_f64()) {
+        (Some(l), Some(r)) => l == r,
+        _ => left == right,
+    }
+}
+
+fn length(value: &Value) -> Option<usize> {
+    match value {
+        Value::String(s) => Some(s.chars().count()),
+        Value::Array(items) => Some(items.len()),
+        _ => None,
+    }
+}
+
+fn describe(op: Operator) -> &'static str {
+    match op {
+        Operator::Eq => "equal to",
+        Operator::Ne => "different from",
+        Operator::Gt => "greater than",
+        Operator::Gte => "at least",
+        Operator::Lt => "less than",
+        Operator::Lte => "at most",
+        Operator::In => "one of",
+        Operator::NotIn => "none of",
+        Operator::MinLength => "of length at least",
+        Operator::MaxLength => "of length at most",
+    }
+}
+
+fn event_matches(matcher: &EventMatcher, event: &TrackingEvent) -> bool {
+    matcher
+        .event_type
+        .as_ref()
+        .is_none_or(|t| t.eq_ignore_ascii_case(&event.event_type))
+        && matcher
+            .metadata_field
+            .as_ref()
+            .is_none_or(|f| lookup(&event.metadata, f).is_some())
+}
+
+fn describe_matcher(matcher: &EventMatcher) -> String {
+    match (&matcher.event_type, &matcher.metadata_field) {
+        (Some(t), Some(f)) => format!("{} (with {})", t, f),
+        (Some(t), None) => t.clone(),
+        (None, Some(f)) => format!("'{}' reading", f),
+        (None, None) => "any".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compliance::rules::RuleFormat;
+    use chrono::{TimeZone, Utc};
+
+    fn rule(yaml: &str) -> ComplianceRule {
+        ComplianceRule::parse(yaml, RuleFormat::Yaml).unwrap()
+    }
+
+    fn event(id: i64, event_type: &str, hour: u32, metadata: Value) -> TrackingEvent {
+        let timestamp = Utc.with_ymd_and_hms(2024, 3, 1, hour, 0, 0).unwrap();
+        TrackingEvent {
+            id,
+            product_id: "PROD-1".to_string(),
+            actor_address: "GACTOR".to_string(),
+            timestamp,
+            event_type: event_type.to_string(),
+            location: "Rotterdam".to_string(),
+            data_hash: format!("hash-{}", id),
+            note: String::new(),
+            metadata,
+            created_at: timestamp,
+        }
+    }
+
+    #[test]
+    fn test_presence_constraint_and_pattern() {
+        let rule = rule(
+            r#"
+rule_id: gdpr_residency
+compliance_type: gdpr
+description: EU data residency
+checks:
+  - type: required
+    field: consent
+  - type: constraint
+    field: data_location
+    op: in
+    value: [EU, EEA]
+  - type: pattern
+    field: contact.email
+    regex: "^[^@]+@[^@]+$"
+    severity: warning
+"#,
+        );
+
+        let ok = ComplianceValidator::validate(
+            &rule,
+            &json!({"consent": true, "data_location": "EU", "contact": {"email": "a@b.eu"}}),
+            &[],
+        );
+        assert!(ok.is_compliant, "{:?}", ok.findings);
+
+        let bad = ComplianceValidator::validate(
+            &rule,
+            &json!({"data_location": "US", "contact": {"email": "nope"}}),
+            &[],
+        );
+        assert!(!bad.is_compliant);
+        assert_eq!(bad.violations.len(), 2);
+        assert_eq!(
+            bad.warnings,
+            vec!["contact.email does not match ^[^@]+@[^@]+$"]
+        );
+    }
+
+    #[test]
+    fn test_when_condition_and_relative_dates() {
+        let rule = rule(
+            r#"
+rule_id: organic_cert
+compliance_type: organic_certification
+description: Organic certificate validity
+checks:
+  - type: constraint
+    field: cert_expiry
+    op: gt
+    value: now
+    message: "Organic: Certification expired"
+  - type: constraint
+    field: cert_expiry
+    op: gt
+    value: now+90d
+    severity: warning
+    message: "Organic: Certification expiring soon"
+    when: { field: cert_expiry, op: gt, value: now }
+"#,
+        );
+        let in_days = |days: i64| (Utc::now() + Duration::days(days)).to_rfc3339();
+
+        let expired =
+            ComplianceValidator::validate(&rule, &json!({"cert_expiry": in_days(-1)}), &[]);
+        assert_eq!(expired.violations, vec!["Organic: Certification expired"]);
+        assert!(expired.warnings.is_empty());
+
+        let soon = ComplianceValidator::validate(&rule, &json!({"cert_expiry": in_days(30)}), &[]);
+        assert!(soon.is_compliant);
+        assert_eq!(soon.warnings, vec!["Organic: Certification expiring soon"]);
+
+        let fine = ComplianceValidator::validate(&rule, &json!({"cert_expiry": in_days(365)}), &[]);
+        assert!(fine.findings.is_empty());
+    }
+
+    #[test]
+    fn test_cross_field_comparison() {
+        let rule = rule(
+            r#"{"rule_id": "dates", "compliance_type": "fsma", "description": "d",
+                "checks": [{"type": "compare_fields", "field": "shipped_at", "op": "gte", "other_field": "harvested_at"}]}"#,
+        );
+        let ok = json!({"harvested_at": "2024-03-01", "shipped_at": "2024-03-02T10:00:00Z"});
+        let bad = json!({"harvested_at": "2024-03-05", "shipped_at": "2024-03-02T10:00:00Z"});
+        assert!(ComplianceValidator::validate(&rule, &ok, &[]).is_compliant);
+        assert_eq!(
+            ComplianceValidator::validate(&rule, &bad, &[]).violations,
+            vec!["shipped_at must be at least harvested_at"]
+        );
+    }
+
+    #[test]
+    fn test_event_sequence_cold_chain_within_window() {
+        let rule = rule(
+            r#"
+rule_id: cold_chain_ship
+compliance_type: fsma
+description: Cold-chain reading within 4h of shipping
+checks:
+  - type: event_sequence
+    trigger: { event_type: SHIP }
+    require: { metadata_field: temperature }
+    within_hours: 4
+"#,
+        );
+        let events = vec![
+            event(1, "SHIP", 8, json!({})),
+            event(2, "CHECKPOINT", 10, json!({"temperature": 4.2})),
+            event(3, "SHIP", 20, json!({})),
+            event(4, "CHECKPOINT", 1, json!({"temperature": 3.9})),
+        ];
+        let result = ComplianceValidator::validate(&rule, &json!({}), &events);
+
+        assert!(!result.is_compliant);
+        assert_eq!(result.violations.len(), 1);
+        assert!(
+            result.violations[0].starts_with("SHIP event 3 "),
+            "{}",
+            result.violations[0]
+        );
+        assert_eq!(result.rule_id, "cold_chain_ship");
+    }
+}
@@ -7,8 +7,15 @@ pub struct Config {
     pub server: ServerConfig,
     pub redis: RedisConfig,
     pub security: SecurityConfig,
+    pub rate_limit: RateLimitConfig,
+    pub monitoring: MonitoringConfig,
+    pub alerting: AlertingConfig,
+    pub telemetry: TelemetryConfig,
+    pub backup: BackupConfig,
     pub encryption_key: String,
     pub jwt_secret: String,
+    pub jwt: JwtConfig,
+    pub web_auth: WebAuthConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,6 +41,35 @@ pub struct RedisConfig {
     pub url: String,
 }
 
+/// Token lifetimes and signing keys. With no `keys` configured, tokens are
+/// signed with HS256 using `jwt_secret` under the key ID `default`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwtConfig {
+    pub access_token_ttl_secs: i64,
+    pub refresh_token_ttl_secs: i64,
+    /// Key new tokens are signed with; defaults to the first key
+    pub signing_key_id: Option<String>,
+    /// Every key tokens are accepted from. Keep a retired key here until the
+    /// tokens it signed have expired.
+    #[serde(default)]
+    pub keys: Vec<JwtKeyConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwtKeyConfig {
+    /// Sent as the token's `kid` header
+    pub kid: String,
+    /// `HS256`, `RS256` or `EdDSA`
+    pub algorithm: String,
+    /// HS256 shared secret
+    pub secret: Option<String>,
+    /// RS256/EdDSA private key, as PEM text or a path to a PEM file. Keys
+    /// without one only verify.
+    pub private_key: Option<String>,
+    /// RS256/EdDSA public key, as PEM text or a path to a PEM file
+    pub public_key: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecurityConfig {
     pub enforce_https: bool,
@@ -41,6 +77,131 @@ pub struct SecurityConfig {
     pub allowed_origins: Vec<String>,
 }
 
+/// Requests per minute for callers without an API key tier. Requests to
+/// exports and simulations count as several.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// Signed-in users
+    pub user_per_minute: u32,
+    /// Unauthenticated requests, per client address
+    pub anonymous_per_minute: u32,
+    /// Seconds' worth of the limit a caller may use at once
+    pub burst_secs: u32,
+}
+
+/// Wallet sign-in, following SEP-10. Challenges are transactions from the
+/// `signing_key` account, which stellar.toml publishes as `SIGNING_KEY`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebAuthConfig {
+    /// Host name of this server, sent as the challenge's `web_auth_domain`
+    pub domain: String,
+    /// Domain serving stellar.toml; names the challenge's first operation
+    pub home_domain: String,
+    /// Secret seed (`S...`) challenges are signed with. Outside production a
+    /// throwaway key is made at startup when it is unset.
+    pub signing_key: Option<String>,
+    /// Network challenge signatures are bound to
+    pub network_passphrase: String,
+}
+
+/// Operational endpoints and telemetry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonitoringConfig {
+    /// Bearer token Prometheus must send to `GET /metrics`. Without one the
+    /// endpoint refuses every scrape unless `metrics_public` is set.
+    pub metrics_token: Option<String>,
+    /// Serve `GET /metrics` without a token, for deployments that only expose
+    /// it on an internal network. Not allowed in production.
+    pub metrics_public: bool,
+    /// Seconds between samples of process, pool, Redis and websocket usage
+    pub sample_interval_secs: u64,
+    /// Samples kept for the infrastructure history; 720 at 10 seconds is two hours
+    pub sample_history: usize,
+}
+
+/// Alert rules and where their notifications go. Alerts are always logged;
+/// each sink is used when it is configured.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertingConfig {
+    /// YAML file of alert rules; built-in rules are used when unset
+    pub rules_path: Option<String>,
+    pub evaluation_interval_secs: u64,
+    /// How often a firing alert is notified again
+    pub repeat_interval_secs: u64,
+    /// Receives each notification as JSON
+    pub webhook_url: Option<String>,
+    /// Signs webhook notifications like outbound webhooks are signed
+    pub webhook_secret: Option<String>,
+    /// Slack incoming webhook, or anything accepting the same format
+    pub slack_webhook_url: Option<String>,
+    /// Integration key of a PagerDuty Events API v2 service
+    pub pagerduty_routing_key: Option<String>,
+    pub smtp: Option<SmtpConfig>,
+}
+
+/// Where finished spans go
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceExporter {
+    None,
+    Otlp,
+    Stdout,
+    File,
+}
+
+impl TraceExporter {
+    /// Parses `OTEL_TRACES_EXPORTER`; `console` is accepted for `stdout`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "" | "none" => Some(Self::None),
+            "otlp" => Some(Self::Otlp),
+            "stdout" | "console" => Some(Self::Stdout),
+            "file" => Some(Self::File),
+            _ => None,
+        }
+    }
+}
+
+/// Distributed tracing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    pub exporter: TraceExporter,
+    /// OTLP/HTTP collector; `/v1/traces` is appended
+    pub otlp_endpoint: String,
+    /// Where the file exporter appends spans, one JSON batch per line
+    pub file_path: String,
+    pub service_name: String,
+    /// Share of new traces that are recorded. Traces continued from an
+    /// incoming `traceparent` follow the caller's decision.
+    pub sample_ratio: f64,
+}
+
+/// Scheduled logical backups
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupConfig {
+    /// Directory each backup gets a subdirectory of
+    pub dir: String,
+    /// Hours between scheduled backups; 0 turns the schedule off
+    pub interval_hours: u64,
+    /// Backups older than this are deleted
+    pub retention_days: i64,
+    /// Newest backups kept whatever their age
+    pub retention_min_count: usize,
+    /// 32-character key backups are encrypted with; `encryption_key` when unset
+    pub encryption_key: Option<String>,
+}
+
+/// Mail server alert emails are sent through, using STARTTLS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -84,10 +245,124 @@ impl Default for Config {
                     .map(|s| s.trim().to_string())
                     .collect(),
             },
+            rate_limit: RateLimitConfig {
+                user_per_minute: env::var("RATE_LIMIT_USER_PER_MINUTE")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(600),
+                anonymous_per_minute: env::var("RATE_LIMIT_ANONYMOUS_PER_MINUTE")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(60),
+                burst_secs: 10,
+            },
+            monitoring: MonitoringConfig {
+                metrics_token: env::var("METRICS_TOKEN").ok().filter(|t| !t.is_empty()),
+                metrics_public: env::var("METRICS_PUBLIC")
+                    .unwrap_or_else(|_| "false".to_string())
+                    .parse()
+                    .unwrap_or(false),
+                sample_interval_secs: env::var("MONITORING_SAMPLE_INTERVAL_SECS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(10),
+                sample_history: env::var("MONITORING_SAMPLE_HISTORY")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(720),
+            },
+            alerting: AlertingConfig {
+                rules_path: env::var("ALERT_RULES_PATH").ok().filter(|p| !p.is_empty()),
+                evaluation_interval_secs: env::var("ALERT_EVALUATION_INTERVAL_SECS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(30),
+                repeat_interval_secs: env::var("ALERT_REPEAT_INTERVAL_SECS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(4 * 3600),
+                webhook_url: env::var("ALERT_WEBHOOK_URL").ok().filter(|u| !u.is_empty()),
+                webhook_secret: env::var("ALERT_WEBHOOK_SECRET").ok().filter(|s| !s.is_empty()),
+                slack_webhook_url: env::var("ALERT_SLACK_WEBHOOK_URL")
+                    .ok()
+                    .filter(|u| !u.is_empty()),
+                pagerduty_routing_key: env::var("ALERT_PAGERDUTY_ROUTING_KEY")
+                    .ok()
+                    .filter(|k| !k.is_empty()),
+                smtp: env::var("SMTP_HOST")
+                    .ok()
+                    .filter(|h| !h.is_empty())
+                    .map(|host| SmtpConfig {
+                        host,
+                        port: env::var("SMTP_PORT")
+                            .ok()
+                            .and_then(|v| v.parse().ok())
+                            .unwrap_or(587),
+                        username: env::var("SMTP_USERNAME").ok(),
+                        password: env::var("SMTP_PASSWORD").ok(),
+                        from: env::var("ALERT_EMAIL_FROM").unwrap_or_default(),
+                        to: env::var("ALERT_EMAIL_TO")
+                            .unwrap_or_default()
+                            .split(',')
+                            .map(|s| s.trim().to_string())
+                            .filter(|s| !s.is_empty())
+                            .collect(),
+                    }),
+            },
+            telemetry: TelemetryConfig {
+                exporter: env::var("OTEL_TRACES_EXPORTER")
+                    .ok()
+                    .and_then(|name| TraceExporter::from_name(&name))
+                    .unwrap_or(TraceExporter::None),
+                otlp_endpoint: env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+                    .unwrap_or_else(|_| "http://localhost:4318".to_string()),
+                file_path: env::var("OTEL_TRACES_FILE")
+                    .unwrap_or_else(|_| "traces.jsonl".to_string()),
+                service_name: env::var("OTEL_SERVICE_NAME")
+                    .unwrap_or_else(|_| "chainlogistics-backend".to_string()),
+                sample_ratio: env::var("OTEL_TRACES_SAMPLER_ARG")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(1.0),
+            },
+            backup: BackupConfig {
+                dir: env::var("BACKUP_DIR").unwrap_or_else(|_| "/backups".to_string()),
+                interval_hours: env::var("BACKUP_INTERVAL_HOURS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(24),
+                retention_days: env::var("BACKUP_RETENTION_DAYS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(30),
+                retention_min_count: env::var("BACKUP_RETENTION_MIN_COUNT")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(7),
+                encryption_key: env::var("BACKUP_ENCRYPTION_KEY").ok().filter(|k| !k.is_empty()),
+            },
             encryption_key: env::var("ENCRYPTION_KEY")
                 .unwrap_or_else(|_| "0123456789abcdef0123456789abcdef".to_string()), // 32 chars for AES-256
             jwt_secret: env::var("JWT_SECRET")
                 .unwrap_or_else(|_| "default_jwt_secret_change_me_in_production".to_string()),
+            jwt: JwtConfig {
+                access_token_ttl_secs: 900,        // 15 minutes
+                refresh_token_ttl_secs: 2_592_000, // 30 days
+                signing_key_id: env::var("JWT_SIGNING_KEY_ID").ok(),
+                keys: Vec::new(),
+            },
+            web_auth: {
+                let domain =
+                    env::var("WEB_AUTH_DOMAIN").unwrap_or_else(|_| "localhost".to_string());
+                WebAuthConfig {
+                    home_domain: env::var("WEB_AUTH_HOME_DOMAIN")
+                        .unwrap_or_else(|_| domain.clone()),
+                    domain,
+                    signing_key: env::var("WEB_AUTH_SIGNING_KEY").ok().filter(|k| !k.is_empty()),
+                    network_passphrase: env::var("STELLAR_NETWORK_PASSPHRASE")
+                        .unwrap_or_else(|_| "Test SDF Network ; September 2015".to_string()),
+                }
+            },
         }
     }
 }
@@ -111,9 +386,32 @@ impl Config {
 
         let config: Config = cfg.try_deserialize()?;
         config.validate()?;
+        // Production metrics name routes and report error rates and pool
+        // sizes; they are never served to whoever asks
+        if profile == "production" && config.monitoring.metrics_token.is_none() {
+            return Err(config::ConfigError::Message(
+                "monitoring.metrics_token (METRICS_TOKEN) is required in production".to_string(),
+            ));
+        }
+        // Wallets check challenges come from the key stellar.toml names, so it
+        // has to stay the same across restarts and instances
+        if profile == "production" && config.web_auth.signing_key.is_none() {
+            return Err(config::ConfigError::Message(
+                "web_auth.signing_key (WEB_AUTH_SIGNING_KEY) is required in production"
+                    .to_string(),
+            ));
+        }
         Ok(config)
     }
 
+    /// Key backups are encrypted and signed with
+    pub fn backup_key(&self) -> &str {
+        self.backup
+            .encryption_key
+            .as_deref()
+            .unwrap_or(&self.encryption_key)
+    }
+
     fn validate(&self) -> Result<(), config::ConfigError> {
         if self.database.url.trim().is_empty() {
             return Err(config::ConfigError::Message(
@@ -130,11 +428,104 @@ impl Config {
                 "jwt_secret must be at least 16 characters".to_string(),
             ));
         }
+        if self.jwt.access_token_ttl_secs <= 0
+            || self.jwt.refresh_token_ttl_secs <= self.jwt.access_token_ttl_secs
+        {
+            return Err(config::ConfigError::Message(
+                "jwt.refresh_token_ttl_secs must exceed a positive jwt.access_token_ttl_secs"
+                    .to_string(),
+            ));
+        }
         if self.encryption_key.trim().len() != 32 {
             return Err(config::ConfigError::Message(
                 "encryption_key must be exactly 32 characters (AES-256 key)".to_string(),
             ));
         }
+        if self.rate_limit.user_per_minute == 0
+            || self.rate_limit.anonymous_per_minute == 0
+            || !(1..=60).contains(&self.rate_limit.burst_secs)
+        {
+            return Err(config::ConfigError::Message(
+                "rate_limit limits must be positive and burst_secs between 1 and 60".to_string(),
+            ));
+        }
+        if self
+            .monitoring
+            .metrics_token
+            .as_ref()
+            .is_some_and(|token| token.trim().len() < 16)
+        {
+            return Err(config::ConfigError::Message(
+                "monitoring.metrics_token must be at least 16 characters".to_string(),
+            ));
+        }
+        if self.monitoring.sample_interval_secs == 0 || self.monitoring.sample_history == 0 {
+            return Err(config::ConfigError::Message(
+                "monitoring.sample_interval_secs and sample_history must be positive".to_string(),
+            ));
+        }
+        if self.alerting.evaluation_interval_secs == 0 || self.alerting.repeat_interval_secs == 0 {
+            return Err(config::ConfigError::Message(
+                "alerting.evaluation_interval_secs and repeat_interval_secs must be positive"
+                    .to_string(),
+            ));
+        }
+        if let Some(smtp) = &self.alerting.smtp {
+            if smtp.from.trim().is_empty() || smtp.to.is_empty() {
+                return Err(config::ConfigError::Message(
+                    "alerting.smtp needs a from address and at least one recipient".to_string(),
+                ));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.telemetry.sample_ratio) {
+            return Err(config::ConfigError::Message(
+                "telemetry.sample_ratio must be between 0 and 1".to_string(),
+            ));
+        }
+        if self.telemetry.exporter == TraceExporter::File
+            && self.telemetry.file_path.trim().is_empty()
+        {
+            return Err(config::ConfigError::Message(
+                "telemetry.file_path is required for the file exporter".to_string(),
+            ));
+        }
+        if self.backup.dir.trim().is_empty()
+            || self.backup.retention_days <= 0
+            || self.backup.retention_min_count == 0
+        {
+            return Err(config::ConfigError::Message(
+                "backup.dir must be set, and retention_days and retention_min_count positive"
+                    .to_string(),
+            ));
+        }
+        if self
+            .backup
+            .encryption_key
+            .as_ref()
+            .is_some_and(|key| key.len() != 32)
+        {
+            return Err(config::ConfigError::Message(
+                "backup.encryption_key must be exactly 32 characters (AES-256 key)".to_string(),
+            ));
+        }
+        if self.web_auth.domain.trim().is_empty()
+            || self.web_auth.network_passphrase.trim().is_empty()
+            // The first operation is named "<home_domain> auth", at most 64 bytes
+            || self.web_auth.home_domain.trim().is_empty()
+            || self.web_auth.home_domain.len() > 59
+            || self.web_auth.domain.len() > 64
+        {
+            return Err(config::ConfigError::Message(
+                "web_auth needs a network_passphrase, a domain of at most 64 characters and a home_domain of at most 59".to_string(),
+            ));
+        }
+        if let Some(seed) = &self.web_auth.signing_key {
+            crate::services::sep10::signing_key(seed).map_err(|_| {
+                config::ConfigError::Message(
+                    "web_auth.signing_key must be a Stellar secret seed (S...)".to_string(),
+                )
+            })?;
+        }
         if self.server.tls_enabled
             && (self.server.tls_cert_path.is_none() || self.server.tls_key_path.is_none())
         {
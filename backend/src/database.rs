@@ -2,6 +2,7 @@ use sqlx::{PgPool, Pool, Postgres};
 use std::time::Duration;
 use crate::config::DatabaseConfig;
 use crate::models::*;
+use crate::models::organization::TenantScope;
 
 #[derive(Debug, Clone)]
 pub struct Database {
@@ -31,6 +32,26 @@ impl Database {
         sqlx::migrate!("./migrations").run(&self.pool).await
     }
 
+    /// Applies the migrations up to and including `version` to an empty
+    /// database, so a backup taken at that version can be restored into it
+    pub async fn migrate_to(&self, version: i64) -> Result<(), sqlx::migrate::MigrateError> {
+        use sqlx::migrate::{Migrate, MigrateError};
+
+        let migrator = sqlx::migrate!("./migrations");
+        if !migrator.iter().any(|m| m.version == version) {
+            return Err(MigrateError::VersionMissing(version));
+        }
+        let mut conn = self.pool.acquire().await?;
+        conn.ensure_migrations_table().await?;
+        for migration in migrator
+            .iter()
+            .filter(|m| m.version <= version && !m.migration_type.is_down_migration())
+        {
+            conn.apply(migration).await?;
+        }
+        Ok(())
+    }
+
     // Health check
     pub async fn health_check(&self) -> Result<(), sqlx::Error> {
         sqlx::query("SELECT 1")
@@ -54,29 +75,44 @@ pub trait ProductRepository {
         filters: Option<ProductFilters>,
     ) -> Result<Vec<Product>, sqlx::Error>;
     async fn count_products(&self, filters: Option<ProductFilters>) -> Result<i64, sqlx::Error>;
-    async fn search_products(&self, query: &str, limit: i64) -> Result<Vec<Product>, sqlx::Error>;
+    async fn search_products(
+        &self,
+        query: &str,
+        limit: i64,
+        scope: Option<&TenantScope>,
+    ) -> Result<Vec<Product>, sqlx::Error>;
 }
 
 #[async_trait::async_trait]
 pub trait EventRepository {
     async fn create_event(&self, event: NewTrackingEvent) -> Result<TrackingEvent, sqlx::Error>;
-    async fn get_event(&self, id: i64) -> Result<Option<TrackingEvent>, sqlx::Error>;
+    async fn get_event(
+        &self,
+        id: i64,
+        scope: Option<&TenantScope>,
+    ) -> Result<Option<TrackingEvent>, sqlx::Error>;
     async fn list_events_by_product(
         &self,
         product_id: &str,
         offset: i64,
         limit: i64,
+        scope: Option<&TenantScope>,
     ) -> Result<Vec<TrackingEvent>, sqlx::Error>;
-    async fn count_events_by_product(&self, product_id: &str) -> Result<i64, sqlx::Error>;
+    async fn count_events_by_product(
+        &self,
+        product_id: &str,
+        scope: Option<&TenantScope>,
+    ) -> Result<i64, sqlx::Error>;
     async fn list_events_by_type(
         &self,
         product_id: &str,
         event_type: &str,
         offset: i64,
         limit: i64,
+        scope: Option<&TenantScope>,
     ) -> Result<Vec<TrackingEvent>, sqlx::Error>;
     async fn get_product_stats(&self, product_id: &str) -> Result<Option<ProductStats>, sqlx::Error>;
-    async fn get_global_stats(&self) -> Result<GlobalStats, sqlx::Error>;
+    async fn get_global_stats(&self, scope: Option<&TenantScope>) -> Result<GlobalStats, sqlx::Error>;
 }
 
 #[async_trait::async_trait]
@@ -107,6 +143,12 @@ pub struct ProductFilters {
     pub is_active: Option<bool>,
     pub created_after: Option<chrono::DateTime<chrono::Utc>>,
     pub created_before: Option<chrono::DateTime<chrono::Utc>>,
+    /// Restricts results to products visible to an organization
+    pub scope: Option<TenantScope>,
+    /// Restricts results to these products, for product-limited API keys
+    pub product_ids: Option<Vec<String>>,
+    /// Restricts results to these categories, for category-limited API keys
+    pub categories: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
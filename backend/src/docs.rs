@@ -4,10 +4,10 @@ use utoipa_swagger_ui::SwaggerUi;
 use crate::handlers::{
     product::{ProductResponse, PaginatedProductsResponse, CreateProductRequest, UpdateProductRequest},
     event::{EventResponse, PaginatedEventsResponse, CreateEventRequest, ListEventsQuery},
-    auth::{LoginRequest, RegisterRequest, AuthResponse},
+    auth::{LoginRequest, RegisterRequest, AuthResponse, WalletChallengeRequest, WalletLoginRequest, RefreshRequest, RevokedSessionsResponse},
     api_keys::{CreateApiKeyRequest, ApiKeyCreatedResponse, ApiKeyResponse},
     financial::{CreateTransactionRequest, CreateInvoiceRequest, FinancingRequestBody},
-    compliance::{ComplianceCheckRequest, ComplianceReportResponse},
+    compliance::{ComplianceCheckRequest, ComplianceCheckResponse, ComplianceReportResponse},
 };
 
 #[derive(OpenApi)]
@@ -37,13 +37,40 @@ use crate::handlers::{
         crate::handlers::product::get_product,
         crate::handlers::product::update_product,
         crate::handlers::product::delete_product,
+        crate::handlers::access::list_product_access,
+        crate::handlers::access::grant_product_access,
+        crate::handlers::access::revoke_product_access,
         // Event endpoints
         crate::handlers::event::list_events,
         crate::handlers::event::create_event,
         crate::handlers::event::get_event,
+        crate::handlers::location::get_event_location,
+        crate::handlers::location::events_nearby,
+        crate::handlers::location::events_in_bounding_box,
+        // EPCIS endpoints
+        crate::handlers::epcis::export_product,
+        crate::handlers::epcis::export_events,
+        crate::handlers::epcis::import_document,
+        // Location endpoints
+        crate::handlers::location::list_known_locations,
+        crate::handlers::location::create_known_location,
+        crate::handlers::location::resolve_location,
         // Authentication endpoints
         crate::handlers::auth::login,
         crate::handlers::auth::register,
+        crate::handlers::auth::wallet_challenge,
+        crate::handlers::auth::wallet_login,
+        crate::handlers::auth::refresh,
+        crate::handlers::auth::logout,
+        crate::handlers::auth::logout_all,
+        crate::handlers::auth::list_sessions,
+        crate::handlers::auth::revoke_session,
+        crate::handlers::mfa::get_mfa_status,
+        crate::handlers::mfa::enroll_mfa,
+        crate::handlers::mfa::activate_mfa,
+        crate::handlers::mfa::verify_mfa,
+        crate::handlers::mfa::regenerate_recovery_codes,
+        crate::handlers::mfa::disable_mfa,
         // Stats endpoints
         crate::handlers::stats::get_stats,
         // Health endpoints
@@ -74,12 +101,49 @@ use crate::handlers::{
         // Compliance endpoints
         crate::handlers::compliance::check_compliance,
         crate::handlers::compliance::get_compliance_report,
+        crate::handlers::compliance::evaluate_product_compliance,
+        crate::handlers::compliance::list_compliance_rules,
+        crate::handlers::compliance::list_compliance_rule_versions,
+        crate::handlers::compliance::create_compliance_rule,
+        crate::handlers::compliance::deactivate_compliance_rule,
         crate::handlers::compliance::generate_audit_report,
+        crate::handlers::audit::verify_audit_log,
+        crate::handlers::audit::anchor_audit_log,
+        // EUDR endpoints
+        crate::handlers::eudr::list_origin_plots,
+        crate::handlers::eudr::add_origin_plot,
+        crate::handlers::eudr::delete_origin_plot,
+        crate::handlers::eudr::get_product_lineage,
+        crate::handlers::eudr::export_due_diligence_statement,
         // API Key endpoints
         crate::handlers::api_keys::create_key,
         crate::handlers::api_keys::list_keys,
         crate::handlers::api_keys::revoke_key,
         crate::handlers::api_keys::rotate_key,
+        crate::handlers::api_keys::get_key_usage,
+        // Realtime
+        crate::handlers::websocket::connect,
+        // Webhooks
+        crate::handlers::webhooks::create_webhook,
+        crate::handlers::webhooks::list_webhooks,
+        crate::handlers::webhooks::get_webhook,
+        crate::handlers::webhooks::update_webhook,
+        crate::handlers::webhooks::delete_webhook,
+        crate::handlers::webhooks::list_deliveries,
+        crate::handlers::webhooks::get_delivery,
+        crate::handlers::webhooks::replay_delivery,
+        // Organization endpoints
+        crate::handlers::organization::create_organization,
+        crate::handlers::organization::list_organizations,
+        crate::handlers::organization::get_organization,
+        crate::handlers::organization::list_members,
+        crate::handlers::organization::add_member,
+        crate::handlers::organization::update_member,
+        crate::handlers::organization::remove_member,
+        crate::handlers::organization::list_addresses,
+        crate::handlers::organization::address_challenge,
+        crate::handlers::organization::add_address,
+        crate::handlers::organization::remove_address,
     ),
     components(
         schemas(
@@ -88,26 +152,94 @@ use crate::handlers::{
             PaginatedProductsResponse,
             CreateProductRequest,
             UpdateProductRequest,
+            crate::models::access::ProductPermission,
+            crate::models::access::GrantSource,
+            crate::models::access::ProductAccessGrant,
+            crate::models::access::GrantProductAccessRequest,
             // Event schemas
             EventResponse,
             PaginatedEventsResponse,
             CreateEventRequest,
             ListEventsQuery,
+            // EPCIS schemas
+            crate::models::epcis::EpcisFormat,
+            crate::models::epcis::EpcisImportResult,
+            crate::models::epcis::EpcisAnchorOutcome,
+            // Location schemas
+            crate::models::location::KnownLocation,
+            crate::models::location::CreateKnownLocationRequest,
+            crate::models::location::ResolvedBy,
+            crate::models::location::ResolvedLocation,
+            crate::models::location::EventLocation,
+            crate::models::location::LocatedEvent,
             // Auth schemas
             LoginRequest,
             RegisterRequest,
             AuthResponse,
+            WalletChallengeRequest,
+            WalletLoginRequest,
+            crate::models::WalletChallenge,
+            RefreshRequest,
+            RevokedSessionsResponse,
+            crate::models::AuthSession,
+            crate::models::MfaStatus,
+            crate::models::MfaEnrollment,
+            crate::handlers::mfa::ActivateMfaRequest,
+            crate::handlers::mfa::VerifyMfaRequest,
+            crate::handlers::mfa::RecoveryCodesResponse,
+            crate::handlers::mfa::StepUpResponse,
             // API Key schemas
             CreateApiKeyRequest,
             ApiKeyCreatedResponse,
             ApiKeyResponse,
+            crate::handlers::api_keys::ApiKeyUsageResponse,
+            crate::models::EndpointUsage,
+            crate::models::HourlyUsage,
+            // Webhook schemas
+            crate::models::Webhook,
+            crate::models::WebhookDelivery,
+            crate::models::WebhookDeliveryStatus,
+            crate::models::WebhookDeliveryAttempt,
+            crate::handlers::webhooks::CreateWebhookRequest,
+            crate::handlers::webhooks::UpdateWebhookRequest,
+            crate::handlers::webhooks::WebhookCreatedResponse,
+            crate::handlers::webhooks::WebhookDeliveryDetail,
             // Financial schemas
             CreateTransactionRequest,
             CreateInvoiceRequest,
             FinancingRequestBody,
             // Compliance schemas
             ComplianceCheckRequest,
+            ComplianceCheckResponse,
             ComplianceReportResponse,
+            crate::models::compliance::StoredComplianceRule,
+            crate::models::compliance::ComplianceRecord,
+            crate::models::compliance::ComplianceStatusChange,
+            crate::compliance::ValidationResult,
+            crate::compliance::validator::Finding,
+            crate::compliance::rules::Severity,
+            crate::compliance::rules::RuleFormat,
+            crate::compliance::audit::AuditAnchor,
+            crate::compliance::audit::AuditChainVerification,
+            crate::compliance::audit::AuditChainIssue,
+            crate::compliance::audit::AuditChainIssueKind,
+            // EUDR schemas
+            crate::models::eudr::OriginPlot,
+            crate::models::eudr::NewOriginPlot,
+            crate::models::eudr::LineageEdge,
+            crate::models::eudr::ProductOriginPlots,
+            crate::models::eudr::DdsActivity,
+            // Organization schemas
+            crate::models::organization::Organization,
+            crate::models::organization::OrgRole,
+            crate::models::organization::OrganizationMember,
+            crate::models::organization::OrganizationAddress,
+            crate::models::organization::OrganizationMembership,
+            crate::models::organization::CreateOrganizationRequest,
+            crate::models::organization::AddMemberRequest,
+            crate::models::organization::UpdateMemberRequest,
+            crate::models::organization::AddressChallengeRequest,
+            crate::models::organization::AddAddressRequest,
             // Model schemas
             crate::models::ApiKeyTier,
             crate::models::UserRole,
@@ -119,13 +251,19 @@ use crate::handlers::{
     tags(
         (name = "products", description = "Product management operations"),
         (name = "events", description = "Tracking event operations"),
+        (name = "locations", description = "Known locations and geocoding"),
+        (name = "epcis", description = "GS1 EPCIS 2.0 import and export"),
         (name = "stats", description = "Statistics and analytics"),
         (name = "health", description = "Health check endpoints"),
         (name = "authentication", description = "User authentication and registration"),
         (name = "carbon", description = "Carbon footprint management and trading"),
         (name = "financial", description = "Financial transactions and invoicing"),
         (name = "compliance", description = "Compliance checking and audit reports"),
-        (name = "api_keys", description = "API key management")
+        (name = "eudr", description = "EU Deforestation Regulation origin plots, lineage and due diligence"),
+        (name = "api_keys", description = "API key management"),
+        (name = "organizations", description = "Organizations, members and the Stellar addresses they operate"),
+        (name = "realtime", description = "Websocket live updates"),
+        (name = "webhooks", description = "Signed outbound webhooks, their delivery log and replays")
     ),
     security(
         ("api_key" = []),
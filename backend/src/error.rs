@@ -17,6 +17,7 @@ pub enum ErrorCode {
     TokenExpired = 1002,
     TokenInvalid = 1003,
     InsufficientPermissions = 1004,
+    MfaRequired = 1005,
     
     // Validation Errors (1100-1199)
     ValidationFailed = 1100,
@@ -75,6 +76,9 @@ pub enum AppError {
     #[error("Token invalid")]
     TokenInvalid,
     
+    #[error("Two-factor authentication required")]
+    MfaRequired(String),
+    
     #[error("Insufficient permissions")]
     Forbidden(String),
     
@@ -213,7 +217,7 @@ impl IntoResponse for AppError {
                 (
                     StatusCode::UNAUTHORIZED,
                     ErrorCode::TokenExpired,
-                    "Your session has expired. Please log in again.".to_string(),
+                    "Your access token has expired. Refresh it or log in again.".to_string(),
                     None,
                 )
             }
@@ -231,6 +235,20 @@ impl IntoResponse for AppError {
                 )
             }
             
+            AppError::MfaRequired(ref msg) => {
+                tracing::info!(
+                    correlation_id = %correlation_id,
+                    reason = %msg,
+                    "Two-factor authentication required"
+                );
+                (
+                    StatusCode::UNAUTHORIZED,
+                    ErrorCode::MfaRequired,
+                    msg.clone(),
+                    None,
+                )
+            }
+            
             // Authorization Errors
             AppError::Forbidden(ref msg) => {
                 tracing::warn!(
@@ -427,7 +445,10 @@ impl IntoResponse for AppError {
             response = response.with_details(details);
         }
 
-        (status, Json(response)).into_response()
+        let mut response = (status, Json(response)).into_response();
+        // Lets the metrics middleware count errors by code
+        response.extensions_mut().insert(code);
+        response
     }
 }
 
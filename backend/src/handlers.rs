@@ -4,6 +4,7 @@ pub mod product;
 pub mod event;
 pub mod user;
 pub mod auth;
+pub mod mfa;
 pub mod stats;
 pub mod health;
 pub mod financial;
@@ -11,3 +12,12 @@ pub mod compliance;
 pub mod carbon;
 pub mod digital_twin;
 pub mod api_keys;
+pub mod location;
+pub mod monitoring;
+pub mod epcis;
+pub mod audit;
+pub mod eudr;
+pub mod access;
+pub mod organization;
+pub mod websocket;
+pub mod webhooks;
@@ -0,0 +1,127 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+    Extension,
+};
+
+use crate::{
+    error::AppError,
+    middleware::auth::AuthContext,
+    models::access::{GrantProductAccessRequest, GrantSource, ProductAccessGrant},
+    services::access_control::ProductAction,
+    validation::{validate_product_id, validate_stellar_address},
+    AppState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/{id}/access",
+    tag = "products",
+    params(
+        ("id" = String, Path, description = "Product ID")
+    ),
+    responses(
+        (status = 200, description = "Addresses granted access besides the owner", body = [ProductAccessGrant]),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Product not found")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn list_product_access(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<ProductAccessGrant>>, AppError> {
+    validate_product_id(&id)?;
+    state
+        .access_service
+        .authorize_product(&auth, &id, ProductAction::Read)
+        .await?;
+    let grants = state.access_service.list_grants(&id).await?;
+    Ok(Json(grants))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/products/{id}/access",
+    tag = "products",
+    params(
+        ("id" = String, Path, description = "Product ID")
+    ),
+    request_body = GrantProductAccessRequest,
+    responses(
+        (status = 201, description = "Access granted (or changed)", body = ProductAccessGrant),
+        (status = 400, description = "Bad request - invalid Stellar address"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not the product owner"),
+        (status = 404, description = "Product not found")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn grant_product_access(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<String>,
+    Json(request): Json<GrantProductAccessRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    validate_product_id(&id)?;
+    validate_stellar_address(&request.actor_address)?;
+    let product = state
+        .access_service
+        .authorize_product(&auth, &id, ProductAction::ManageAccess)
+        .await?;
+    if request.actor_address == product.owner_address {
+        return Err(AppError::Validation(
+            "The product owner already has full access".to_string(),
+        ));
+    }
+
+    let grant = state
+        .access_service
+        .grant(
+            &id,
+            &request.actor_address,
+            request.permission,
+            GrantSource::Api,
+            Some(auth.user_id),
+        )
+        .await?;
+    Ok((StatusCode::CREATED, Json(grant)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/products/{id}/access/{actor_address}",
+    tag = "products",
+    params(
+        ("id" = String, Path, description = "Product ID"),
+        ("actor_address" = String, Path, description = "Stellar address to revoke")
+    ),
+    responses(
+        (status = 204, description = "Access revoked"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not the product owner"),
+        (status = 404, description = "Product or grant not found")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn revoke_product_access(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path((id, actor_address)): Path<(String, String)>,
+) -> Result<StatusCode, AppError> {
+    validate_product_id(&id)?;
+    state
+        .access_service
+        .authorize_product(&auth, &id, ProductAction::ManageAccess)
+        .await?;
+    state.access_service.revoke(&id, &actor_address).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
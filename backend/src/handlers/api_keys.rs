@@ -1,5 +1,5 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::Json,
 };
@@ -10,15 +10,28 @@ use utoipa::ToSchema;
 
 use crate::{
     AppState,
+    database::ApiKeyRepository,
     error::AppError,
-    models::{ApiKey, ApiKeyTier, NewApiKey},
+    models::{ApiKey, ApiKeyTier, EndpointUsage, HourlyUsage, NewApiKey},
+    services::{api_key_policy, usage_metering},
 };
 
+/// Longest period a usage report covers
+const MAX_USAGE_REPORT_DAYS: i64 = 93;
+
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateApiKeyRequest {
     pub name: String,
     pub tier: Option<ApiKeyTier>,
     pub expires_at: Option<DateTime<Utc>>,
+    /// What the key may do, e.g. `["events:write"]`. Defaults to read-only access.
+    pub scopes: Option<Vec<String>>,
+    /// Limit the key to these products
+    pub product_ids: Option<Vec<String>>,
+    /// Limit the key to products in these categories
+    pub categories: Option<Vec<String>>,
+    /// IP addresses or CIDR blocks the key may be used from
+    pub allowed_ips: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -27,6 +40,12 @@ pub struct ApiKeyCreatedResponse {
     pub name: String,
     pub tier: ApiKeyTier,
     pub key: String,
+    /// Organization the key acts for
+    pub organization_id: Option<Uuid>,
+    pub scopes: Vec<String>,
+    pub product_ids: Option<Vec<String>>,
+    pub categories: Option<Vec<String>>,
+    pub allowed_ips: Option<Vec<String>>,
     pub expires_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
 }
@@ -36,6 +55,11 @@ pub struct ApiKeyResponse {
     pub id: Uuid,
     pub name: String,
     pub tier: ApiKeyTier,
+    pub organization_id: Option<Uuid>,
+    pub scopes: Vec<String>,
+    pub product_ids: Option<Vec<String>>,
+    pub categories: Option<Vec<String>>,
+    pub allowed_ips: Option<Vec<String>>,
     pub is_active: bool,
     pub expires_at: Option<DateTime<Utc>>,
     pub last_used_at: Option<DateTime<Utc>>,
@@ -48,6 +72,11 @@ impl From<ApiKey> for ApiKeyResponse {
             id: k.id,
             name: k.name,
             tier: k.tier,
+            organization_id: k.organization_id,
+            scopes: k.scopes,
+            product_ids: k.allowed_product_ids,
+            categories: k.allowed_categories,
+            allowed_ips: k.allowed_ips,
             is_active: k.is_active,
             expires_at: k.expires_at,
             last_used_at: k.last_used_at,
@@ -56,6 +85,25 @@ impl From<ApiKey> for ApiKeyResponse {
     }
 }
 
+/// Trims and de-duplicates a product or category restriction. An empty list
+/// would lock the key out of everything, so it is refused.
+fn restriction_list(field: &str, values: Option<Vec<String>>) -> Result<Option<Vec<String>>, AppError> {
+    let Some(values) = values else {
+        return Ok(None);
+    };
+    let mut list: Vec<String> = Vec::new();
+    for value in values {
+        let value = value.trim().to_string();
+        if !value.is_empty() && !list.contains(&value) {
+            list.push(value);
+        }
+    }
+    if list.is_empty() {
+        return Err(AppError::Validation(format!("{} must not be empty when given", field)));
+    }
+    Ok(Some(list))
+}
+
 #[utoipa::path(
     post,
     path = "/api/v1/keys",
@@ -63,7 +111,7 @@ impl From<ApiKey> for ApiKeyResponse {
     request_body = CreateApiKeyRequest,
     responses(
         (status = 201, description = "API key created successfully", body = ApiKeyCreatedResponse),
-        (status = 400, description = "Bad request - invalid input"),
+        (status = 400, description = "Bad request - invalid input, unknown scope or malformed IP allowlist"),
         (status = 401, description = "Unauthorized"),
         (status = 429, description = "Rate limit exceeded")
     ),
@@ -80,16 +128,23 @@ pub async fn create_key(
         return Err(AppError::Validation("Key name must not be empty".into()));
     }
 
+    let scopes = match &req.scopes {
+        Some(scopes) => api_key_policy::normalize_scopes(scopes).map_err(AppError::Validation)?,
+        None => api_key_policy::DEFAULT_SCOPES.iter().map(|s| s.to_string()).collect(),
+    };
+    let allowed_product_ids = restriction_list("product_ids", req.product_ids)?;
+    let allowed_categories = restriction_list("categories", req.categories)?;
+    let allowed_ips = req
+        .allowed_ips
+        .map(|entries| api_key_policy::normalize_allowlist(&entries))
+        .transpose()
+        .map_err(AppError::Validation)?;
+
     let plaintext = crate::services::ApiKeyService::generate_api_key();
     let key_hash = crate::services::ApiKeyService::hash_api_key(&plaintext);
 
     let tier = req.tier.unwrap_or(ApiKeyTier::Basic);
-    let rate_limit = match tier {
-        ApiKeyTier::Basic => 60,
-        ApiKeyTier::Standard => 300,
-        ApiKeyTier::Premium => 1000,
-        ApiKeyTier::Enterprise => 5000,
-    };
+    let rate_limit = tier.requests_per_minute() as i32;
 
     let new_key = NewApiKey {
         user_id: auth.user_id,
@@ -98,6 +153,12 @@ pub async fn create_key(
         tier,
         rate_limit_per_minute: rate_limit,
         expires_at: req.expires_at,
+        // Keys act for the organization selected when they were created
+        organization_id: auth.organization_id,
+        scopes,
+        allowed_product_ids,
+        allowed_categories,
+        allowed_ips,
     };
 
     let created = state.api_key_service.create_api_key(new_key).await?;
@@ -109,6 +170,11 @@ pub async fn create_key(
             name: created.name,
             tier: created.tier,
             key: plaintext,
+            organization_id: created.organization_id,
+            scopes: created.scopes,
+            product_ids: created.allowed_product_ids,
+            categories: created.allowed_categories,
+            allowed_ips: created.allowed_ips,
             expires_at: created.expires_at,
             created_at: created.created_at,
         }),
@@ -225,6 +291,11 @@ pub async fn rotate_key(
         tier: old_key.tier,
         rate_limit_per_minute: old_key.rate_limit_per_minute,
         expires_at: old_key.expires_at,
+        organization_id: old_key.organization_id,
+        scopes: old_key.scopes,
+        allowed_product_ids: old_key.allowed_product_ids,
+        allowed_categories: old_key.allowed_categories,
+        allowed_ips: old_key.allowed_ips,
     };
 
     let created = state.api_key_service.create_api_key(new_key).await?;
@@ -236,8 +307,118 @@ pub async fn rotate_key(
             name: created.name,
             tier: created.tier,
             key: plaintext,
+            organization_id: created.organization_id,
+            scopes: created.scopes,
+            product_ids: created.allowed_product_ids,
+            categories: created.allowed_categories,
+            allowed_ips: created.allowed_ips,
             expires_at: created.expires_at,
             created_at: created.created_at,
         }),
     ))
 }
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiKeyUsageResponse {
+    pub api_key_id: Uuid,
+    pub tier: ApiKeyTier,
+    /// Units the key may use per calendar month
+    pub monthly_quota: u64,
+    /// Units used so far this month
+    pub used_this_month: u64,
+    pub remaining_this_month: u64,
+    /// `ok`, `warning` from 80% of the quota, or `exceeded`
+    pub quota_status: String,
+    /// Whether requests are refused once the quota is used up, rather than
+    /// billed as overage
+    pub hard_limit: bool,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Requests and units over the report period
+    pub requests: i64,
+    pub units: i64,
+    /// Use per endpoint over the period, costliest first
+    pub endpoints: Vec<EndpointUsage>,
+    /// Use per hour over the period; hours without requests are left out
+    pub hourly: Vec<HourlyUsage>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/keys/{id}/usage",
+    tag = "api_keys",
+    params(
+        ("id" = Uuid, Path, description = "API Key ID"),
+        ("from" = Option<DateTime<Utc>>, Query, description = "Start of the report, inclusive; defaults to the start of this month"),
+        ("to" = Option<DateTime<Utc>>, Query, description = "End of the report, exclusive; defaults to now")
+    ),
+    responses(
+        (status = 200, description = "Usage of the API key", body = ApiKeyUsageResponse),
+        (status = 400, description = "Bad request - invalid period"),
+        (status = 404, description = "API key not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - cannot read another user's key usage"),
+        (status = 429, description = "Rate limit exceeded")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn get_key_usage(
+    State(state): State<AppState>,
+    axum::Extension(auth): axum::Extension<crate::middleware::auth::AuthContext>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<ApiKeyUsageResponse>, AppError> {
+    let key = state
+        .api_key_service
+        .get_api_key(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("API key {} not found", id)))?;
+
+    if key.user_id != auth.user_id {
+        return Err(AppError::Forbidden("Cannot read another user's key usage".into()));
+    }
+
+    let now = Utc::now();
+    let from = query.from.unwrap_or_else(|| usage_metering::month_start(now));
+    let to = query.to.unwrap_or(now);
+    if from >= to {
+        return Err(AppError::Validation("from must be before to".into()));
+    }
+    if to - from > chrono::Duration::days(MAX_USAGE_REPORT_DAYS) {
+        return Err(AppError::Validation(format!(
+            "A usage report covers at most {} days",
+            MAX_USAGE_REPORT_DAYS
+        )));
+    }
+
+    let used_this_month = state.usage_service.month_to_date(id).await?;
+    let monthly_quota = key.tier.monthly_quota();
+    let endpoints = state.usage_service.endpoint_usage(id, from, to).await?;
+    let hourly = state.usage_service.hourly_usage(id, from, to).await?;
+
+    Ok(Json(ApiKeyUsageResponse {
+        api_key_id: id,
+        quota_status: usage_metering::quota_level(used_this_month, monthly_quota, false)
+            .as_str()
+            .to_string(),
+        hard_limit: usage_metering::plan(&key.tier).hard_limit,
+        tier: key.tier,
+        monthly_quota,
+        used_this_month,
+        remaining_this_month: monthly_quota.saturating_sub(used_this_month),
+        from,
+        to,
+        requests: endpoints.iter().map(|e| e.requests).sum(),
+        units: endpoints.iter().map(|e| e.units).sum(),
+        endpoints,
+        hourly,
+    }))
+}
@@ -0,0 +1,54 @@
+use axum::{extract::State, response::Json};
+
+use crate::{
+    compliance::audit::{AuditAnchor, AuditChainVerification},
+    error::AppError,
+    AppState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/audit/verify",
+    tag = "compliance",
+    responses(
+        (status = 200, description = "Audit log hash chain verified; `valid` is false when gaps or edits were found", body = AuditChainVerification),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - insufficient permissions"),
+        (status = 429, description = "Rate limit exceeded")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn verify_audit_log(
+    State(state): State<AppState>,
+) -> Result<Json<AuditChainVerification>, AppError> {
+    let verification = state.audit_service.verify().await?;
+    if !verification.valid {
+        tracing::warn!(
+            issues = verification.issues.len(),
+            "Audit log verification found integrity issues"
+        );
+    }
+    Ok(Json(verification))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/audit/anchor",
+    tag = "compliance",
+    responses(
+        (status = 200, description = "Chain head anchored on Stellar; null when the head was already anchored", body = Option<AuditAnchor>),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Anchoring is not configured or the transaction failed")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn anchor_audit_log(
+    State(state): State<AppState>,
+) -> Result<Json<Option<AuditAnchor>>, AppError> {
+    let anchor = state.audit_service.anchor_head().await?;
+    Ok(Json(anchor))
+}
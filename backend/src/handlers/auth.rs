@@ -1,22 +1,41 @@
-use axum::{extract::State, Json};
+use axum::{extract::{Path, State}, http::{header, HeaderMap, StatusCode}, Extension, Json};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
-use crate::{AppState, error::AppError, models::{UserRole, NewUser, User}, middleware::auth::Claims, validation::{validate_email, validate_string}};
+use uuid::Uuid;
+use crate::{AppState, error::AppError, database::UserRepository, models::{UserRole, NewUser, User, WalletChallenge, AuthSession}, middleware::{audit::client_ip, auth::AuthContext}, services::sep10::ChallengePurpose, validation::{validate_email, validate_string}};
 use bcrypt::verify;
-use jsonwebtoken::{encode, Header, EncodingKey};
 
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct LoginRequest {
     pub email: String,
     pub password: String,
+    /// Required once two-factor authentication is enabled, unless
+    /// `recovery_code` is given
+    pub totp_code: Option<String>,
+    pub recovery_code: Option<String>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct AuthResponse {
+    /// Short-lived access token for the `Authorization: Bearer` header
     pub token: String,
+    /// Seconds until `token` expires
+    pub expires_in: i64,
+    /// Single-use token for `POST /api/v1/auth/refresh`
+    pub refresh_token: String,
     pub user: User,
 }
 
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RevokedSessionsResponse {
+    pub revoked: u64,
+}
+
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct RegisterRequest {
     pub email: String,
@@ -25,6 +44,65 @@ pub struct RegisterRequest {
     pub stellar_address: Option<String>,
 }
 
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct WalletChallengeRequest {
+    pub stellar_address: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct WalletLoginRequest {
+    /// The challenge transaction with the account's signature added, as a
+    /// base64 XDR envelope
+    pub transaction: String,
+    /// Required once two-factor authentication is enabled, unless
+    /// `recovery_code` is given
+    pub totp_code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+/// Asks users with two-factor authentication for their second factor. Returns
+/// whether one was proven.
+async fn check_second_factor(
+    state: &AppState,
+    user: &User,
+    totp_code: Option<&str>,
+    recovery_code: Option<&str>,
+) -> Result<bool, AppError> {
+    if !state.mfa_service.is_enabled(user.id).await? {
+        return Ok(false);
+    }
+    if totp_code.is_none() && recovery_code.is_none() {
+        return Err(AppError::MfaRequired(
+            "Enter a code from your authenticator app or a recovery code".to_string(),
+        ));
+    }
+    if !state.mfa_service.verify(user.id, totp_code, recovery_code).await? {
+        return Err(AppError::MfaRequired("Invalid two-factor code".to_string()));
+    }
+    Ok(true)
+}
+
+/// Opens a session for `user` and returns its first token pair
+async fn start_session(
+    state: &AppState,
+    headers: &HeaderMap,
+    user: User,
+    mfa_verified: bool,
+) -> Result<AuthResponse, AppError> {
+    let user_agent = headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok());
+    let ip_address = client_ip(headers);
+    let tokens = state
+        .session_service
+        .start(&user, mfa_verified, user_agent, ip_address.as_deref())
+        .await?;
+    Ok(AuthResponse {
+        token: tokens.access_token,
+        expires_in: tokens.expires_in,
+        refresh_token: tokens.refresh_token,
+        user,
+    })
+}
+
 #[utoipa::path(
     post,
     path = "/api/v1/admin/auth/login",
@@ -32,12 +110,13 @@ pub struct RegisterRequest {
     request_body = LoginRequest,
     responses(
         (status = 200, description = "Login successful", body = AuthResponse),
-        (status = 401, description = "Invalid credentials"),
+        (status = 401, description = "Invalid credentials, or a missing or invalid two-factor code (`MFA_REQUIRED`)"),
         (status = 429, description = "Rate limit exceeded")
     )
 )]
 pub async fn login(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(req): Json<LoginRequest>,
 ) -> Result<Json<AuthResponse>, AppError> {
     validate_email(&req.email)?;
@@ -56,29 +135,201 @@ pub async fn login(
         return Err(AppError::Unauthorized);
     }
 
+    let mfa_verified = check_second_factor(
+        &state,
+        &user,
+        req.totp_code.as_deref(),
+        req.recovery_code.as_deref(),
+    )
+    .await?;
+
     // Update last login
     let _ = state.user_service.update_last_login(user.id).await;
 
-    // Issue JWT
-    let expiration = chrono::Utc::now()
-        .checked_add_signed(chrono::Duration::hours(24))
-        .expect("valid timestamp")
-        .timestamp() as usize;
+    Ok(Json(start_session(&state, &headers, user, mfa_verified).await?))
+}
 
-    let claims = Claims {
-        sub: user.id.to_string(),
-        exp: expiration,
-        role: user.role.clone(),
-    };
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/stellar/challenge",
+    tag = "authentication",
+    request_body = WalletChallengeRequest,
+    responses(
+        (status = 200, description = "Challenge transaction for the wallet to sign; valid for 5 minutes", body = WalletChallenge),
+        (status = 400, description = "Bad request - not a Stellar account address"),
+        (status = 429, description = "Rate limit exceeded")
+    )
+)]
+pub async fn wallet_challenge(
+    State(state): State<AppState>,
+    Json(req): Json<WalletChallengeRequest>,
+) -> Result<Json<WalletChallenge>, AppError> {
+    let challenge = state
+        .wallet_auth_service
+        .issue_challenge(req.stellar_address.trim(), ChallengePurpose::SignIn)
+        .await?;
+    Ok(Json(challenge))
+}
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(state.config.jwt_secret.as_bytes()),
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/stellar/login",
+    tag = "authentication",
+    request_body = WalletLoginRequest,
+    responses(
+        (status = 200, description = "Signature verified; JWT issued for the account's user", body = AuthResponse),
+        (status = 401, description = "Unknown, expired or used challenge, a transaction other than the one issued, a missing or extra signature, no active user with this address, or a missing or invalid two-factor code (`MFA_REQUIRED`)"),
+        (status = 429, description = "Rate limit exceeded")
     )
-    .map_err(|_| AppError::Internal("Token issuance failed".to_string()))?;
+)]
+pub async fn wallet_login(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<WalletLoginRequest>,
+) -> Result<Json<AuthResponse>, AppError> {
+    let address = state
+        .wallet_auth_service
+        .redeem_challenge(&req.transaction, ChallengePurpose::SignIn)
+        .await?;
+
+    let mut user = state.user_service.get_user_by_stellar_address(address.as_str()).await?
+        .ok_or(AppError::Unauthorized)?;
+
+    if !user.is_active {
+        return Err(AppError::Unauthorized);
+    }
+
+    // The challenge is spent either way; a client that gets MFA_REQUIRED asks
+    // for a new one
+    let mfa_verified = check_second_factor(
+        &state,
+        &user,
+        req.totp_code.as_deref(),
+        req.recovery_code.as_deref(),
+    )
+    .await?;
 
-    Ok(Json(AuthResponse { token, user }))
+    let _ = state.user_service.update_last_login(user.id).await;
+    state.wallet_auth_service.mark_address_verified(user.id).await?;
+    user.stellar_address_verified_at = Some(chrono::Utc::now());
+    state.organization_service.ensure_personal_address(&user, &address).await?;
+
+    Ok(Json(start_session(&state, &headers, user, mfa_verified).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/refresh",
+    tag = "authentication",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "New access token and refresh token; the old refresh token is spent", body = AuthResponse),
+        (status = 401, description = "Unknown, expired or already used refresh token, or session revoked"),
+        (status = 429, description = "Rate limit exceeded")
+    )
+)]
+pub async fn refresh(
+    State(state): State<AppState>,
+    Json(req): Json<RefreshRequest>,
+) -> Result<Json<AuthResponse>, AppError> {
+    let (session, refresh_token) = state.session_service.rotate(req.refresh_token.trim()).await?;
+
+    // Pick up role changes and deactivation at every refresh
+    let user = state.user_service.get_user(session.user_id).await?
+        .ok_or(AppError::Unauthorized)?;
+    if !user.is_active {
+        state.session_service.revoke_all(user.id).await?;
+        return Err(AppError::Unauthorized);
+    }
+
+    let (token, expires_in) = state.session_service.access_token(&user, session.id)?;
+    Ok(Json(AuthResponse { token, expires_in, refresh_token, user }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/logout",
+    tag = "authentication",
+    responses(
+        (status = 204, description = "Current session revoked; its access and refresh tokens stop working"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn logout(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+) -> Result<StatusCode, AppError> {
+    let session_id = auth.session_id.ok_or(AppError::Unauthorized)?;
+    state.session_service.revoke(auth.user_id, session_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/logout-all",
+    tag = "authentication",
+    responses(
+        (status = 200, description = "Every session of the user revoked, including the current one", body = RevokedSessionsResponse),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn logout_all(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+) -> Result<Json<RevokedSessionsResponse>, AppError> {
+    let revoked = state.session_service.revoke_all(auth.user_id).await?;
+    Ok(Json(RevokedSessionsResponse { revoked }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/sessions",
+    tag = "authentication",
+    responses(
+        (status = 200, description = "The user's open sessions, most recently used first", body = [AuthSession]),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+) -> Result<Json<Vec<AuthSession>>, AppError> {
+    let sessions = state.session_service.list(auth.user_id).await?;
+    Ok(Json(sessions))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/auth/sessions/{id}",
+    tag = "authentication",
+    params(
+        ("id" = Uuid, Path, description = "Session ID")
+    ),
+    responses(
+        (status = 204, description = "Session revoked"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "No open session of this user with that ID")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    state.session_service.revoke(auth.user_id, id).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 #[utoipa::path(
@@ -109,6 +360,7 @@ pub async fn register(
         role: req.role,
     };
 
-    let user = state.user_service.create_user(new_user).await?;
+    // Every user starts in an organization of their own
+    let user = state.user_service.register(new_user).await?;
     Ok(Json(user))
 }
@@ -2,19 +2,40 @@ use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
     response::{IntoResponse, Json},
+    Extension,
 };
 use uuid::Uuid;
 
 use crate::{
     error::AppError,
+    middleware::auth::AuthContext,
     models::carbon::{
         CalculateFootprintRequest, CreateTradeRequest, GenerateCreditRequest,
         GenerateReportRequest, ListCreditsQuery, ListTradesQuery, PurchaseCreditRequest,
         RequestVerificationRequest, RetireCreditRequest,
     },
+    models::UserRole,
+    services::{access_control::ProductAction, carbon::holds_credit},
     AppState,
 };
 
+/// Loads a credit the caller holds (administrators see every credit); others
+/// are reported as not found
+async fn visible_credit(
+    state: &AppState,
+    auth: &AuthContext,
+    id: Uuid,
+) -> Result<crate::models::carbon::CarbonCredit, AppError> {
+    let credit = state.carbon_service.get_credit(id).await?;
+    if matches!(auth.role, UserRole::Administrator)
+        || holds_credit(&credit, auth.user_id, auth.organization_id)
+    {
+        Ok(credit)
+    } else {
+        Err(AppError::NotFound(format!("Credit {} not found", id)))
+    }
+}
+
 // ── Footprint ─────────────────────────────────────────────────────────────────
 
 #[utoipa::path(
@@ -25,6 +46,8 @@ use crate::{
     responses(
         (status = 201, description = "Carbon footprint calculated successfully"),
         (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not allowed to edit this product"),
+        (status = 404, description = "Product not found"),
         (status = 429, description = "Rate limit exceeded")
     ),
     security(
@@ -34,8 +57,13 @@ use crate::{
 /// POST /api/v1/carbon/footprint/calculate
 pub async fn calculate_footprint(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Json(req): Json<CalculateFootprintRequest>,
 ) -> Result<impl IntoResponse, AppError> {
+    state
+        .access_service
+        .authorize_product(&auth, &req.product_id, ProductAction::Edit)
+        .await?;
     let record = state.carbon_service.calculate_footprint(&req).await?;
     Ok((StatusCode::CREATED, Json(record)))
 }
@@ -73,6 +101,7 @@ pub async fn preview_footprint(
     responses(
         (status = 200, description = "Footprints listed successfully"),
         (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Product not found"),
         (status = 429, description = "Rate limit exceeded")
     ),
     security(
@@ -82,8 +111,13 @@ pub async fn preview_footprint(
 /// GET /api/v1/carbon/footprint/:product_id
 pub async fn list_footprints(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Path(product_id): Path<String>,
 ) -> Result<Json<serde_json::Value>, AppError> {
+    state
+        .access_service
+        .authorize_product(&auth, &product_id, ProductAction::Read)
+        .await?;
     let records = state.carbon_service.list_footprints(&product_id).await?;
     Ok(Json(serde_json::json!({ "footprints": records, "total": records.len() })))
 }
@@ -98,6 +132,8 @@ pub async fn list_footprints(
     responses(
         (status = 201, description = "Carbon credit generated successfully"),
         (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not allowed to edit the footprint's product"),
+        (status = 404, description = "Footprint or product not found"),
         (status = 429, description = "Rate limit exceeded")
     ),
     security(
@@ -107,11 +143,19 @@ pub async fn list_footprints(
 /// POST /api/v1/carbon/credits/generate
 pub async fn generate_credit(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Json(req): Json<GenerateCreditRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    // TODO: extract real owner_id from auth context
-    let owner_id = Uuid::nil();
-    let credit = state.carbon_service.generate_credit(owner_id, &req).await?;
+    // Credits can only be minted from footprints of products the caller may edit
+    let footprint = state.carbon_service.get_footprint(req.footprint_id).await?;
+    state
+        .access_service
+        .authorize_product(&auth, &footprint.product_id, ProductAction::Edit)
+        .await?;
+    let credit = state
+        .carbon_service
+        .generate_credit(auth.user_id, auth.organization_id, &req)
+        .await?;
     Ok((StatusCode::CREATED, Json(credit)))
 }
 
@@ -132,11 +176,13 @@ pub async fn generate_credit(
 /// GET /api/v1/carbon/credits
 pub async fn list_credits(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Query(query): Query<ListCreditsQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    // TODO: extract real owner_id from auth context
-    let owner_id = Uuid::nil();
-    let credits = state.carbon_service.list_credits(owner_id, &query).await?;
+    let credits = state
+        .carbon_service
+        .list_credits(auth.user_id, auth.organization_id, &query)
+        .await?;
     Ok(Json(serde_json::json!({ "credits": credits, "total": credits.len() })))
 }
 
@@ -160,9 +206,10 @@ pub async fn list_credits(
 /// GET /api/v1/carbon/credits/:id
 pub async fn get_credit(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Path(id): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let credit = state.carbon_service.get_credit(id).await?;
+    let credit = visible_credit(&state, &auth, id).await?;
     Ok(Json(serde_json::json!(credit)))
 }
 
@@ -183,11 +230,13 @@ pub async fn get_credit(
 /// POST /api/v1/carbon/credits/retire
 pub async fn retire_credit(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Json(req): Json<RetireCreditRequest>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    // TODO: extract real owner_id from auth context
-    let owner_id = Uuid::nil();
-    let credit = state.carbon_service.retire_credit(owner_id, &req).await?;
+    let credit = state
+        .carbon_service
+        .retire_credit(auth.user_id, auth.organization_id, &req)
+        .await?;
     Ok(Json(serde_json::json!(credit)))
 }
 
@@ -254,11 +303,13 @@ pub async fn list_trades(
 /// POST /api/v1/carbon/market/list
 pub async fn list_credit_for_sale(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Json(req): Json<CreateTradeRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    // TODO: extract real seller_id from auth context
-    let seller_id = Uuid::nil();
-    let trade = state.carbon_service.create_trade(seller_id, &req).await?;
+    let trade = state
+        .carbon_service
+        .create_trade(auth.user_id, auth.organization_id, &req)
+        .await?;
     Ok((StatusCode::CREATED, Json(trade)))
 }
 
@@ -279,11 +330,13 @@ pub async fn list_credit_for_sale(
 /// POST /api/v1/carbon/market/purchase
 pub async fn purchase_credit(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Json(req): Json<PurchaseCreditRequest>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    // TODO: extract real buyer_id from auth context
-    let buyer_id = Uuid::nil();
-    let trade = state.carbon_service.purchase_credit(buyer_id, &req).await?;
+    let trade = state
+        .carbon_service
+        .purchase_credit(auth.user_id, auth.organization_id, &req)
+        .await?;
     Ok(Json(serde_json::json!(trade)))
 }
 
@@ -306,13 +359,12 @@ pub async fn purchase_credit(
 /// POST /api/v1/carbon/verify
 pub async fn request_verification(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Json(req): Json<RequestVerificationRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    // TODO: extract real requester_id from auth context
-    let requester_id = Uuid::nil();
     let verification = state
         .carbon_service
-        .request_verification(requester_id, &req)
+        .request_verification(auth.user_id, auth.organization_id, &req)
         .await?;
     Ok((StatusCode::CREATED, Json(verification)))
 }
@@ -336,8 +388,10 @@ pub async fn request_verification(
 /// GET /api/v1/carbon/verify/:credit_id
 pub async fn list_verifications(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Path(credit_id): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>, AppError> {
+    visible_credit(&state, &auth, credit_id).await?;
     let verifications = state.carbon_service.list_verifications(credit_id).await?;
     Ok(Json(
         serde_json::json!({ "verifications": verifications, "total": verifications.len() }),
@@ -363,11 +417,10 @@ pub async fn list_verifications(
 /// POST /api/v1/carbon/reports
 pub async fn generate_report(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Json(req): Json<GenerateReportRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    // TODO: extract real owner_id from auth context
-    let owner_id = Uuid::nil();
-    let report = state.carbon_service.generate_report(owner_id, &req).await?;
+    let report = state.carbon_service.generate_report(auth.user_id, &req).await?;
     Ok((StatusCode::CREATED, Json(report)))
 }
 
@@ -387,9 +440,8 @@ pub async fn generate_report(
 /// GET /api/v1/carbon/reports
 pub async fn list_reports(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    // TODO: extract real owner_id from auth context
-    let owner_id = Uuid::nil();
-    let reports = state.carbon_service.list_reports(owner_id).await?;
+    let reports = state.carbon_service.list_reports(auth.user_id).await?;
     Ok(Json(serde_json::json!({ "reports": reports, "total": reports.len() })))
 }
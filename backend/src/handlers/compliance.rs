@@ -1,37 +1,78 @@
 use axum::{
-    extract::{Path, State, Json},
-    http::StatusCode,
+    extract::{Path, Query, State, Json},
+    http::{header, HeaderMap, StatusCode},
     response::IntoResponse,
+    Extension,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use utoipa::ToSchema;
 use crate::AppState;
-use crate::compliance::{ComplianceValidator, ComplianceRule, ComplianceType};
+use crate::compliance::{RuleFormat, ValidationResult};
+use crate::error::AppError;
+use crate::middleware::auth::AuthContext;
+use crate::models::compliance::{
+    ComplianceRecord, ComplianceRuleQuery, ComplianceRuleUploadQuery, StoredComplianceRule,
+};
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ComplianceCheckRequest {
     pub compliance_type: String,
-    pub data: Value,
+    /// Data to validate; defaults to the product's own record when `product_id` is set
+    pub data: Option<Value>,
+    /// Check a single rule instead of every active rule of the type
+    pub rule_id: Option<String>,
+    /// Product the data belongs to; enables event-sequence checks
+    pub product_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ComplianceCheckResponse {
+    pub is_compliant: bool,
+    pub compliance_type: String,
+    pub violations: Vec<String>,
+    pub warnings: Vec<String>,
+    /// One result per rule, each naming the rule version it was checked against
+    pub results: Vec<ValidationResult>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ComplianceReportResponse {
     pub product_id: String,
-    pub compliance_checks: Vec<Value>,
+    /// Current continuous result of each applicable rule
+    pub compliance_checks: Vec<ComplianceRecord>,
+    /// `compliant`, `non_compliant`, or `not_evaluated` when no rule applies yet
     pub overall_status: String,
 }
 
+impl ComplianceReportResponse {
+    fn new(product_id: String, compliance_checks: Vec<ComplianceRecord>) -> Self {
+        let overall_status = if compliance_checks.is_empty() {
+            "not_evaluated"
+        } else if compliance_checks.iter().all(|c| c.status == "compliant") {
+            "compliant"
+        } else {
+            "non_compliant"
+        };
+        Self {
+            product_id,
+            compliance_checks,
+            overall_status: overall_status.to_string(),
+        }
+    }
+}
+
 #[utoipa::path(
     post,
     path = "/api/v1/compliance/check",
     tag = "compliance",
     request_body = ComplianceCheckRequest,
     responses(
-        (status = 200, description = "Compliance check completed successfully"),
-        (status = 400, description = "Bad request - unknown compliance type"),
+        (status = 200, description = "Compliance check completed and recorded", body = ComplianceCheckResponse),
+        (status = 400, description = "Bad request - no active rules for the compliance type"),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden - insufficient permissions"),
+        (status = 404, description = "Product not found"),
         (status = 429, description = "Rate limit exceeded")
     ),
     security(
@@ -39,35 +80,144 @@ pub struct ComplianceReportResponse {
     )
 )]
 pub async fn check_compliance(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Json(req): Json<ComplianceCheckRequest>,
-) -> impl IntoResponse {
-    let compliance_type = match req.compliance_type.as_str() {
-        "gdpr" => ComplianceType::GDPR,
-        "fda_21_cfr_11" => ComplianceType::FDA21CFR11,
-        "fsma" => ComplianceType::FSMA,
-        "conflict_minerals" => ComplianceType::ConflictMinerals,
-        "organic_certification" => ComplianceType::OrganicCertification,
-        _ => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": "Unknown compliance type"}))).into_response(),
-    };
+) -> Result<Json<ComplianceCheckResponse>, AppError> {
+    let results = state
+        .compliance_service
+        .check(
+            &req.compliance_type,
+            req.rule_id.as_deref(),
+            req.product_id.as_deref(),
+            req.data,
+            Some(auth.user_id),
+        )
+        .await?;
+
+    Ok(Json(ComplianceCheckResponse {
+        is_compliant: results.iter().all(|r| r.is_compliant),
+        compliance_type: req.compliance_type,
+        violations: results.iter().flat_map(|r| r.violations.clone()).collect(),
+        warnings: results.iter().flat_map(|r| r.warnings.clone()).collect(),
+        results,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/compliance/rules",
+    tag = "compliance",
+    params(ComplianceRuleQuery),
+    responses(
+        (status = 200, description = "Compliance rules", body = Vec<StoredComplianceRule>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - insufficient permissions"),
+        (status = 429, description = "Rate limit exceeded")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn list_compliance_rules(
+    State(state): State<AppState>,
+    Query(query): Query<ComplianceRuleQuery>,
+) -> Result<Json<Vec<StoredComplianceRule>>, AppError> {
+    Ok(Json(state.compliance_service.list_rules(&query).await?))
+}
 
-    let rule = match compliance_type {
-        ComplianceType::GDPR => ComplianceRule::gdpr_data_residency(),
-        ComplianceType::FDA21CFR11 => ComplianceRule::fda_electronic_signature(),
-        ComplianceType::FSMA => ComplianceRule::fsma_traceability(),
-        ComplianceType::ConflictMinerals => ComplianceRule::conflict_minerals_due_diligence(),
-        ComplianceType::OrganicCertification => ComplianceRule::organic_certification(),
-        _ => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": "Unknown compliance type"}))).into_response(),
-    };
+#[utoipa::path(
+    get,
+    path = "/api/v1/compliance/rules/{rule_id}/versions",
+    tag = "compliance",
+    params(
+        ("rule_id" = String, Path, description = "Rule ID")
+    ),
+    responses(
+        (status = 200, description = "All versions of the rule, newest first", body = Vec<StoredComplianceRule>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - insufficient permissions"),
+        (status = 404, description = "Rule not found"),
+        (status = 429, description = "Rate limit exceeded")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn list_compliance_rule_versions(
+    State(state): State<AppState>,
+    Path(rule_id): Path<String>,
+) -> Result<Json<Vec<StoredComplianceRule>>, AppError> {
+    Ok(Json(state.compliance_service.rule_versions(&rule_id).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/compliance/rules",
+    tag = "compliance",
+    params(ComplianceRuleUploadQuery),
+    request_body(content = String, description = "Rule definition as JSON or YAML (`application/yaml`)"),
+    responses(
+        (status = 201, description = "Rule saved as a new active version", body = StoredComplianceRule),
+        (status = 400, description = "Invalid rule definition"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - admin access required"),
+        (status = 429, description = "Rate limit exceeded")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn create_compliance_rule(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Query(query): Query<ComplianceRuleUploadQuery>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<impl IntoResponse, AppError> {
+    let format = query.format.unwrap_or_else(|| {
+        let is_yaml = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|ct| ct.contains("yaml"));
+        if is_yaml {
+            RuleFormat::Yaml
+        } else {
+            RuleFormat::Json
+        }
+    });
 
-    let result = ComplianceValidator::validate(&rule, &req.data);
+    let rule = state
+        .compliance_service
+        .save_rule(&body, format, Some(auth.user_id))
+        .await?;
+    Ok((StatusCode::CREATED, Json(rule)))
+}
 
-    (StatusCode::OK, Json(serde_json::json!({
-        "is_compliant": result.is_compliant,
-        "compliance_type": result.compliance_type.as_str(),
-        "violations": result.violations,
-        "warnings": result.warnings,
-    }))).into_response()
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/compliance/rules/{rule_id}",
+    tag = "compliance",
+    params(
+        ("rule_id" = String, Path, description = "Rule ID")
+    ),
+    responses(
+        (status = 204, description = "Rule deactivated; its versions are kept"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - admin access required"),
+        (status = 404, description = "No active rule with this ID"),
+        (status = 429, description = "Rate limit exceeded")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn deactivate_compliance_rule(
+    State(state): State<AppState>,
+    Path(rule_id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    state.compliance_service.deactivate_rule(&rule_id).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 #[utoipa::path(
@@ -81,6 +231,7 @@ pub async fn check_compliance(
         (status = 200, description = "Compliance report retrieved successfully", body = ComplianceReportResponse),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden - insufficient permissions"),
+        (status = 404, description = "Product not found"),
         (status = 429, description = "Rate limit exceeded")
     ),
     security(
@@ -88,18 +239,38 @@ pub async fn check_compliance(
     )
 )]
 pub async fn get_compliance_report(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
     Path(product_id): Path<String>,
-) -> impl IntoResponse {
-    // The current backend does not yet persist compliance checks.
-    // Returning a structured placeholder keeps the API stable while storage is implemented.
-    let report = ComplianceReportResponse {
-        product_id,
-        compliance_checks: vec![],
-        overall_status: "not_implemented".to_string(),
-    };
+) -> Result<Json<ComplianceReportResponse>, AppError> {
+    let records = state.compliance_service.product_status(&product_id).await?;
+    Ok(Json(ComplianceReportResponse::new(product_id, records)))
+}
 
-    (StatusCode::NOT_IMPLEMENTED, Json(report)).into_response()
+#[utoipa::path(
+    post,
+    path = "/api/v1/compliance/report/{product_id}/evaluate",
+    tag = "compliance",
+    params(
+        ("product_id" = String, Path, description = "Product ID")
+    ),
+    responses(
+        (status = 200, description = "Product re-evaluated against its applicable rules", body = ComplianceReportResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - insufficient permissions"),
+        (status = 404, description = "Product not found"),
+        (status = 429, description = "Rate limit exceeded")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn evaluate_product_compliance(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>,
+) -> Result<Json<ComplianceReportResponse>, AppError> {
+    state.compliance_service.evaluate_product(&product_id).await?;
+    let records = state.compliance_service.product_status(&product_id).await?;
+    Ok(Json(ComplianceReportResponse::new(product_id, records)))
 }
 
 #[utoipa::path(
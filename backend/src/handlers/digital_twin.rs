@@ -1,5 +1,5 @@
 use axum::{
-    extract::{Path, Query, State},
+    extract::{Extension, Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
     Json,
@@ -9,14 +9,34 @@ use uuid::Uuid;
 
 use crate::{
     error::AppError,
+    middleware::auth::AuthContext,
     models::digital_twin::*,
+    monitoring::alerts::SIGNAL_ORACLE_CIRCUIT_BREAKER,
+    services::access_control::ProductAction,
     AppState,
     validation::{validate_string, sanitize_input},
 };
 
+/// Loads the twin and checks that the caller may perform `action` on its
+/// product; twins of products the caller cannot see are not found
+async fn authorize_twin(
+    state: &AppState,
+    auth: &AuthContext,
+    twin_id: Uuid,
+    action: ProductAction<'_>,
+) -> Result<DigitalTwin, AppError> {
+    let twin = state.digital_twin_service.get_twin(twin_id).await?;
+    state
+        .access_service
+        .authorize_product(auth, &twin.product_id, action)
+        .await?;
+    Ok(twin)
+}
+
 /// Create a new digital twin
 pub async fn create_digital_twin(
     State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
     Json(mut request): Json<CreateDigitalTwinRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     validate_string("name", &request.name, 128)?;
@@ -24,6 +44,10 @@ pub async fn create_digital_twin(
     
     request.name = sanitize_input(&request.name);
     request.description = sanitize_input(&request.description);
+    state
+        .access_service
+        .authorize_product(&auth_context, &request.product_id, ProductAction::Edit)
+        .await?;
 
     let twin = state
         .digital_twin_service
@@ -36,12 +60,10 @@ pub async fn create_digital_twin(
 /// Get digital twin by ID
 pub async fn get_digital_twin(
     State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
     Path(twin_id): Path<Uuid>,
 ) -> Result<impl IntoResponse, AppError> {
-    let twin = state
-        .digital_twin_service
-        .get_twin(twin_id)
-        .await?;
+    let twin = authorize_twin(&state, &auth_context, twin_id, ProductAction::Read).await?;
 
     Ok(Json(twin))
 }
@@ -49,8 +71,13 @@ pub async fn get_digital_twin(
 /// Get all digital twins for a product
 pub async fn get_product_twins(
     State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
     Path(product_id): Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
+    state
+        .access_service
+        .authorize_product(&auth_context, &product_id, ProductAction::Read)
+        .await?;
     let twins = state
         .digital_twin_service
         .get_twins_by_product(&product_id)
@@ -62,9 +89,11 @@ pub async fn get_product_twins(
 /// Update digital twin state
 pub async fn update_twin_state(
     State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
     Path(twin_id): Path<Uuid>,
     Json(request): Json<UpdateTwinStateRequest>,
 ) -> Result<impl IntoResponse, AppError> {
+    authorize_twin(&state, &auth_context, twin_id, ProductAction::Edit).await?;
     let state_record = state
         .digital_twin_service
         .update_twin_state(twin_id, request)
@@ -86,9 +115,11 @@ fn default_limit() -> i64 {
 
 pub async fn get_state_history(
     State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
     Path(twin_id): Path<Uuid>,
     Query(query): Query<StateHistoryQuery>,
 ) -> Result<impl IntoResponse, AppError> {
+    authorize_twin(&state, &auth_context, twin_id, ProductAction::Read).await?;
     let states = state
         .digital_twin_service
         .get_state_history(twin_id, query.limit)
@@ -100,8 +131,10 @@ pub async fn get_state_history(
 /// Create a simulation
 pub async fn create_simulation(
     State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
     Json(request): Json<CreateSimulationRequest>,
 ) -> Result<impl IntoResponse, AppError> {
+    authorize_twin(&state, &auth_context, request.twin_id, ProductAction::Edit).await?;
     let simulation = state
         .digital_twin_service
         .create_simulation(request)
@@ -113,8 +146,15 @@ pub async fn create_simulation(
 /// Run a simulation
 pub async fn run_simulation(
     State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
     Path(simulation_id): Path<Uuid>,
 ) -> Result<impl IntoResponse, AppError> {
+    let simulation = state
+        .digital_twin_service
+        .get_simulation(simulation_id)
+        .await?;
+    authorize_twin(&state, &auth_context, simulation.twin_id, ProductAction::Edit).await?;
+
     let result = state
         .digital_twin_service
         .run_simulation(simulation_id)
@@ -126,12 +166,14 @@ pub async fn run_simulation(
 /// Get simulation by ID
 pub async fn get_simulation(
     State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
     Path(simulation_id): Path<Uuid>,
 ) -> Result<impl IntoResponse, AppError> {
     let simulation = state
         .digital_twin_service
         .get_simulation(simulation_id)
         .await?;
+    authorize_twin(&state, &auth_context, simulation.twin_id, ProductAction::Read).await?;
 
     Ok(Json(simulation))
 }
@@ -139,8 +181,10 @@ pub async fn get_simulation(
 /// List simulations for a twin
 pub async fn list_simulations(
     State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
     Path(twin_id): Path<Uuid>,
 ) -> Result<impl IntoResponse, AppError> {
+    authorize_twin(&state, &auth_context, twin_id, ProductAction::Read).await?;
     let simulations = state
         .digital_twin_service
         .list_simulations(twin_id)
@@ -152,8 +196,10 @@ pub async fn list_simulations(
 /// Create a prediction
 pub async fn create_prediction(
     State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
     Json(request): Json<PredictionRequest>,
 ) -> Result<impl IntoResponse, AppError> {
+    authorize_twin(&state, &auth_context, request.twin_id, ProductAction::Edit).await?;
     let prediction = state
         .digital_twin_service
         .create_prediction(request)
@@ -165,8 +211,10 @@ pub async fn create_prediction(
 /// Get twin analytics
 pub async fn get_twin_analytics(
     State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
     Path(twin_id): Path<Uuid>,
 ) -> Result<impl IntoResponse, AppError> {
+    authorize_twin(&state, &auth_context, twin_id, ProductAction::Read).await?;
     let analytics = state
         .digital_twin_service
         .get_twin_analytics(twin_id)
@@ -175,6 +223,125 @@ pub async fn get_twin_analytics(
     Ok(Json(analytics))
 }
 
+/// Replace the sync mapping that drives a twin from events and snapshots
+pub async fn update_sync_mapping(
+    State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
+    Path(twin_id): Path<Uuid>,
+    Json(mapping): Json<TwinSyncMapping>,
+) -> Result<impl IntoResponse, AppError> {
+    for field in &mapping.fields {
+        validate_string("source", &field.source, 255)?;
+        validate_string("target", &field.target, 255)?;
+    }
+    authorize_twin(&state, &auth_context, twin_id, ProductAction::Edit).await?;
+
+    let twin = state
+        .digital_twin_service
+        .update_sync_mapping(twin_id, mapping)
+        .await?;
+
+    Ok(Json(twin))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DivergenceQuery {
+    #[serde(default)]
+    open_only: bool,
+}
+
+/// List divergence alerts for a twin
+pub async fn list_divergences(
+    State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
+    Path(twin_id): Path<Uuid>,
+    Query(query): Query<DivergenceQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    authorize_twin(&state, &auth_context, twin_id, ProductAction::Read).await?;
+    let divergences = state
+        .digital_twin_service
+        .list_divergences(twin_id, query.open_only)
+        .await?;
+
+    Ok(Json(divergences))
+}
+
+/// Ingest an oracle snapshot for every twin of a product
+pub async fn ingest_oracle_snapshot(
+    State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
+    Path(product_id): Path<String>,
+    Json(mut request): Json<OracleSnapshotRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    validate_string("source", &request.source, 128)?;
+    request.source = sanitize_input(&request.source);
+    state
+        .access_service
+        .authorize_product(&auth_context, &product_id, ProductAction::Edit)
+        .await?;
+
+    // Snapshots relayed from the oracle contract say whether their feed's
+    // circuit breaker is tripped, which the alert rules watch
+    let breaker = request
+        .snapshot
+        .get("feed_id")
+        .and_then(|v| v.as_str())
+        .filter(|feed_id| !feed_id.is_empty() && feed_id.len() <= 64)
+        .zip(request.snapshot.get("circuit_broken").and_then(|v| v.as_bool()))
+        .map(|(feed_id, broken)| (feed_id.to_string(), broken));
+    if let Some((feed_id, broken)) = breaker {
+        if let Err(e) = state
+            .alert_service
+            .report_signal(SIGNAL_ORACLE_CIRCUIT_BREAKER, &feed_id, broken, None)
+            .await
+        {
+            tracing::warn!(feed_id = %feed_id, "Failed to record oracle circuit breaker state: {}", e);
+        }
+    }
+
+    let divergences = state
+        .digital_twin_service
+        .sync_from_snapshot(&product_id, request)
+        .await?;
+
+    Ok(Json(divergences))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EtaQuery {
+    #[serde(default = "default_confidence")]
+    confidence: f64,
+}
+
+fn default_confidence() -> f64 {
+    0.9
+}
+
+/// Predict arrival time for an in-flight product
+pub async fn predict_eta(
+    State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
+    Path(product_id): Path<String>,
+    Query(query): Query<EtaQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    if !(0.5..=0.99).contains(&query.confidence) {
+        return Err(AppError::Validation(
+            "confidence must be between 0.5 and 0.99".to_string(),
+        ));
+    }
+    state
+        .access_service
+        .authorize_product(&auth_context, &product_id, ProductAction::Read)
+        .await?;
+
+    let eta = state
+        .digital_twin_service
+        .predict_eta(&product_id, query.confidence)
+        .await?;
+
+    Ok(Json(eta))
+}
+
 /// Health check for digital twin service
 pub async fn digital_twin_health() -> impl IntoResponse {
     Json(serde_json::json!({
@@ -186,7 +353,9 @@ pub async fn digital_twin_health() -> impl IntoResponse {
             "simulations",
             "predictions",
             "optimizations",
-            "analytics"
+            "analytics",
+            "live_sync",
+            "eta_prediction"
         ]
     }))
 }
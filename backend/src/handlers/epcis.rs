@@ -0,0 +1,146 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use chrono::Utc;
+
+use crate::{
+    error::AppError,
+    middleware::auth::AuthContext,
+    models::epcis::{
+        EpcisFormat, EpcisImportQuery, EpcisImportResult, EpcisProductExportQuery,
+        EpcisRangeExportQuery,
+    },
+    services::{
+        access_control::ProductAction,
+        epcis::{self, EpcisEvent},
+    },
+    validation::{sanitize_input, validate_product_id},
+    AppState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/epcis/products/{product_id}",
+    tag = "epcis",
+    params(
+        ("product_id" = String, Path, description = "Product ID"),
+        EpcisProductExportQuery
+    ),
+    responses(
+        (status = 200, description = "EPCIS 2.0 document (JSON-LD or XML) with all events of the product"),
+        (status = 404, description = "Product not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Rate limit exceeded")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn export_product(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(product_id): Path<String>,
+    Query(query): Query<EpcisProductExportQuery>,
+) -> Result<Response, AppError> {
+    validate_product_id(&product_id)?;
+    state
+        .access_service
+        .authorize_product(&auth, &product_id, ProductAction::Read)
+        .await?;
+    let events: Vec<EpcisEvent> = state
+        .epcis_service
+        .export_product(&sanitize_input(&product_id))
+        .await?;
+    Ok(document_response(&events, query.format.unwrap_or_default()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/epcis/events",
+    tag = "epcis",
+    params(EpcisRangeExportQuery),
+    responses(
+        (status = 200, description = "EPCIS 2.0 document (JSON-LD or XML) with events in the time range"),
+        (status = 400, description = "Bad request - invalid time range"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Rate limit exceeded")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn export_events(
+    State(state): State<AppState>,
+    Query(query): Query<EpcisRangeExportQuery>,
+) -> Result<Response, AppError> {
+    let product_id = match &query.product_id {
+        Some(id) => {
+            validate_product_id(id)?;
+            Some(sanitize_input(id))
+        }
+        None => None,
+    };
+    let events: Vec<EpcisEvent> = state
+        .epcis_service
+        .export_range(query.start_date, query.end_date, product_id.as_deref())
+        .await?;
+    Ok(document_response(&events, query.format.unwrap_or_default()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/epcis/import",
+    tag = "epcis",
+    params(EpcisImportQuery),
+    request_body(
+        content = String,
+        description = "EPCIS 2.0 document as JSON-LD (application/json, application/ld+json) or XML (application/xml)"
+    ),
+    responses(
+        (status = 201, description = "Events imported", body = EpcisImportResult),
+        (status = 400, description = "Invalid EPCIS document; every rejected event is listed"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - insufficient permissions"),
+        (status = 422, description = "Anchoring requested but not configured")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn import_document(
+    State(state): State<AppState>,
+    Query(query): Query<EpcisImportQuery>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<impl IntoResponse, AppError> {
+    let is_xml = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.contains("xml"));
+
+    let events = state.epcis_service.parse_document(&body, is_xml)?;
+    let result = state
+        .epcis_service
+        .import(events, query.actor_address.as_deref(), query.anchor)
+        .await?;
+    Ok((StatusCode::CREATED, Json(result)))
+}
+
+fn document_response(events: &[EpcisEvent], format: EpcisFormat) -> Response {
+    let now = Utc::now();
+    match format {
+        EpcisFormat::Json => (
+            [(header::CONTENT_TYPE, "application/ld+json")],
+            epcis::document_to_json(events, now).to_string(),
+        )
+            .into_response(),
+        EpcisFormat::Xml => (
+            [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+            epcis::document_to_xml(events, now),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,185 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+    Extension,
+};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    middleware::auth::AuthContext,
+    models::eudr::{DdsQuery, LineageEdge, NewOriginPlot, OriginPlot, ProductOriginPlots},
+    services::access_control::ProductAction,
+    validation::validate_product_id,
+    AppState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/{id}/origin-plots",
+    tag = "eudr",
+    params(
+        ("id" = String, Path, description = "Product ID")
+    ),
+    responses(
+        (status = 200, description = "Own and inherited origin plots", body = ProductOriginPlots),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Product not found"),
+        (status = 429, description = "Rate limit exceeded")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn list_origin_plots(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Json<ProductOriginPlots>, AppError> {
+    validate_product_id(&id)?;
+    state
+        .access_service
+        .authorize_product(&auth, &id, ProductAction::Read)
+        .await?;
+    let plots = state.eudr_service.product_plots(&id).await?;
+    Ok(Json(plots))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/products/{id}/origin-plots",
+    tag = "eudr",
+    params(
+        ("id" = String, Path, description = "Product ID")
+    ),
+    request_body = NewOriginPlot,
+    responses(
+        (status = 201, description = "Origin plot added", body = OriginPlot),
+        (status = 400, description = "Bad request - invalid geometry, country or commodity"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not the product owner or an editing delegate"),
+        (status = 404, description = "Product not found")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn add_origin_plot(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<String>,
+    Json(plot): Json<NewOriginPlot>,
+) -> Result<impl IntoResponse, AppError> {
+    validate_product_id(&id)?;
+    state
+        .access_service
+        .authorize_product(&auth, &id, ProductAction::Edit)
+        .await?;
+    let plot = state.eudr_service.add_plot(&id, plot).await?;
+    Ok((StatusCode::CREATED, Json(plot)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/products/{id}/origin-plots/{plot_id}",
+    tag = "eudr",
+    params(
+        ("id" = String, Path, description = "Product ID"),
+        ("plot_id" = Uuid, Path, description = "Origin plot ID")
+    ),
+    responses(
+        (status = 204, description = "Origin plot removed"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not the product owner or an editing delegate"),
+        (status = 404, description = "Origin plot not found")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn delete_origin_plot(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path((id, plot_id)): Path<(String, Uuid)>,
+) -> Result<StatusCode, AppError> {
+    validate_product_id(&id)?;
+    state
+        .access_service
+        .authorize_product(&auth, &id, ProductAction::Edit)
+        .await?;
+    state.eudr_service.delete_plot(&id, plot_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/{id}/lineage",
+    tag = "eudr",
+    params(
+        ("id" = String, Path, description = "Product ID")
+    ),
+    responses(
+        (status = 200, description = "Upstream lineage edges, nearest first", body = [LineageEdge]),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Product not found"),
+        (status = 429, description = "Rate limit exceeded")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn get_product_lineage(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<LineageEdge>>, AppError> {
+    validate_product_id(&id)?;
+    state
+        .access_service
+        .authorize_product(&auth, &id, ProductAction::Read)
+        .await?;
+    let edges = state.eudr_service.lineage(&id).await?;
+    Ok(Json(edges))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/compliance/eudr/{product_id}/statement",
+    tag = "eudr",
+    params(
+        ("product_id" = String, Path, description = "Product ID"),
+        DdsQuery
+    ),
+    responses(
+        (status = 200, description = "Due-diligence statement; `submittable` is false while EUDR rules report violations"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Insufficient role"),
+        (status = 404, description = "Product not found")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn export_due_diligence_statement(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(product_id): Path<String>,
+    Query(query): Query<DdsQuery>,
+) -> Result<Json<Value>, AppError> {
+    validate_product_id(&product_id)?;
+    state
+        .access_service
+        .authorize_product(&auth, &product_id, ProductAction::Read)
+        .await?;
+    let validation = state
+        .compliance_service
+        .check("eudr", None, Some(&product_id), None, Some(auth.user_id))
+        .await?;
+    let statement = state
+        .eudr_service
+        .statement(&product_id, query.activity, &validation)
+        .await?;
+    Ok(Json(statement))
+}
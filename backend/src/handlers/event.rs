@@ -2,6 +2,7 @@ use axum::{
     extract::{State, Path, Query},
     http::StatusCode,
     response::Json,
+    Extension,
 };
 use chrono::{DateTime, TimeZone, Utc};
 use serde::{Deserialize, Deserializer, Serialize};
@@ -10,7 +11,9 @@ use utoipa::ToSchema;
 use crate::{
     AppState,
     error::AppError,
+    middleware::auth::AuthContext,
     models::{TrackingEvent, NewTrackingEvent},
+    services::access_control::ProductAction,
     validation::{validate_string, validate_stellar_address, sanitize_input, validate_product_id, validate_location, sanitize_json_metadata},
 };
 
@@ -128,23 +131,31 @@ impl From<TrackingEvent> for EventResponse {
 )]
 pub async fn list_events(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Query(query): Query<ListEventsQuery>,
 ) -> Result<Json<PaginatedEventsResponse>, AppError> {
     let offset = query.offset.unwrap_or(0);
     let limit = query.limit.unwrap_or(20).min(100);
+    let scope = auth.tenant_scope();
 
     let (events, total) = if let Some(product_id) = query.product_id {
         validate_product_id(&product_id)?;
         let sanitized_product_id = sanitize_input(&product_id);
+        if auth.restricts_products() {
+            state
+                .access_service
+                .authorize_product(&auth, &sanitized_product_id, ProductAction::Read)
+                .await?;
+        }
 
         let events = if let Some(event_type) = query.event_type {
             validate_string("event_type", &event_type, 64)?;
             state.event_service
-                .list_events_by_type(&sanitized_product_id, &sanitize_input(&event_type), offset, limit)
+                .list_events_by_type(&sanitized_product_id, &sanitize_input(&event_type), offset, limit, scope.as_ref())
                 .await?
         } else {
             state.event_service
-                .list_events_by_product(&sanitized_product_id, offset, limit)
+                .list_events_by_product(&sanitized_product_id, offset, limit, scope.as_ref())
                 .await?
         };
 
@@ -152,7 +163,7 @@ pub async fn list_events(
             events.len() as i64
         } else {
             state.event_service
-                .count_events_by_product(&sanitized_product_id)
+                .count_events_by_product(&sanitized_product_id, scope.as_ref())
                 .await?
         };
 
@@ -187,11 +198,13 @@ const ALLOWED_EVENT_TYPES: &[&str] = &[
         (status = 429, description = "Rate limit exceeded")
     ),
     security(
-        ("jwt" = [])
+        ("jwt" = []),
+        ("api_key" = [])
     )
 )]
 pub async fn create_event(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Json(request): Json<CreateEventRequest>,
 ) -> Result<Json<EventResponse>, AppError> {
     // Validate inputs
@@ -213,6 +226,19 @@ pub async fn create_event(
         return Err(AppError::Validation("timestamp must not be in the future".to_string()));
     }
 
+    // Same rule as the contract: the actor must be the owner or an authorized
+    // actor, and only administrators may record events on someone's behalf.
+    // Organization members may also sign with their organization's addresses.
+    if !state.access_service.may_act_as(&auth, &request.actor_address).await? {
+        return Err(AppError::Forbidden(
+            "actor_address must be your own or one of your organization's Stellar addresses".to_string(),
+        ));
+    }
+    state
+        .access_service
+        .authorize_product(&auth, &request.product_id, ProductAction::AddEvent(&request.event_type))
+        .await?;
+
     let new_event = NewTrackingEvent {
         product_id: sanitize_input(&request.product_id),
         actor_address: request.actor_address,
@@ -251,13 +277,26 @@ pub async fn create_event(
 )]
 pub async fn get_event(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Path(id): Path<i64>,
 ) -> Result<Json<EventResponse>, AppError> {
     let event = state
         .event_service
-        .get_event(id)
+        .get_event(id, auth.tenant_scope().as_ref())
         .await?
         .ok_or_else(|| AppError::NotFound(format!("Event {} not found", id)))?;
+    if auth.restricts_products() {
+        state
+            .access_service
+            .authorize_product(&auth, &event.product_id, ProductAction::Read)
+            .await
+            .map_err(|e| match e {
+                AppError::NotFound(_) | AppError::Forbidden(_) => {
+                    AppError::NotFound(format!("Event {} not found", id))
+                }
+                other => other,
+            })?;
+    }
 
     Ok(Json(EventResponse::from(event)))
 }
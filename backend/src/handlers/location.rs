@@ -0,0 +1,182 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+
+use crate::{
+    error::AppError,
+    models::location::{
+        BoundingBoxEventsQuery, CreateKnownLocationRequest, EventLocation, KnownLocation,
+        KnownLocationQuery, LocatedEvent, NearbyEventsQuery, ResolveLocationQuery,
+        ResolvedLocation,
+    },
+    validation::{sanitize_input, validate_location, validate_product_id},
+    AppState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/locations",
+    tag = "locations",
+    params(KnownLocationQuery),
+    responses(
+        (status = 200, description = "Known locations listed successfully", body = [KnownLocation]),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Rate limit exceeded")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn list_known_locations(
+    State(state): State<AppState>,
+    Query(query): Query<KnownLocationQuery>,
+) -> Result<Json<Vec<KnownLocation>>, AppError> {
+    let search = query.search.as_deref().map(sanitize_input);
+    let locations = state
+        .location_service
+        .list_known_locations(search.as_deref(), query.limit.unwrap_or(100))
+        .await?;
+    Ok(Json(locations))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/locations",
+    tag = "locations",
+    request_body = CreateKnownLocationRequest,
+    responses(
+        (status = 201, description = "Known location created successfully", body = KnownLocation),
+        (status = 400, description = "Bad request - invalid input"),
+        (status = 401, description = "Unauthorized"),
+        (status = 409, description = "Location already exists")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn create_known_location(
+    State(state): State<AppState>,
+    Json(mut request): Json<CreateKnownLocationRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    validate_location(&request.name)?;
+    request.name = sanitize_input(&request.name);
+    if let Some(country) = &request.country_code {
+        if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(AppError::Validation(
+                "country_code must be an ISO 3166-1 alpha-2 code".to_string(),
+            ));
+        }
+    }
+
+    let location = state.location_service.create_known_location(&request).await?;
+    Ok((StatusCode::CREATED, Json(location)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/locations/resolve",
+    tag = "locations",
+    params(ResolveLocationQuery),
+    responses(
+        (status = 200, description = "Location resolved", body = ResolvedLocation),
+        (status = 404, description = "Location could not be resolved"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn resolve_location(
+    State(state): State<AppState>,
+    Query(query): Query<ResolveLocationQuery>,
+) -> Result<Json<ResolvedLocation>, AppError> {
+    validate_location(&query.location)?;
+    let resolved = state
+        .location_service
+        .resolve(&query.location, &serde_json::json!({}))
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Unknown location '{}'", query.location)))?;
+    Ok(Json(resolved))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/events/nearby",
+    tag = "events",
+    params(NearbyEventsQuery),
+    responses(
+        (status = 200, description = "Events within the radius, nearest first", body = [LocatedEvent]),
+        (status = 400, description = "Bad request - invalid coordinates or radius"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Rate limit exceeded")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn events_nearby(
+    State(state): State<AppState>,
+    Query(query): Query<NearbyEventsQuery>,
+) -> Result<Json<Vec<LocatedEvent>>, AppError> {
+    if let Some(product_id) = &query.product_id {
+        validate_product_id(product_id)?;
+    }
+    let events = state.location_service.events_within_radius(&query).await?;
+    Ok(Json(events))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/events/within",
+    tag = "events",
+    params(BoundingBoxEventsQuery),
+    responses(
+        (status = 200, description = "Events inside the bounding box, newest first", body = [LocatedEvent]),
+        (status = 400, description = "Bad request - invalid bounding box"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Rate limit exceeded")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn events_in_bounding_box(
+    State(state): State<AppState>,
+    Query(query): Query<BoundingBoxEventsQuery>,
+) -> Result<Json<Vec<LocatedEvent>>, AppError> {
+    if let Some(product_id) = &query.product_id {
+        validate_product_id(product_id)?;
+    }
+    let events = state.location_service.events_in_bounding_box(&query).await?;
+    Ok(Json(events))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/events/{id}/location",
+    tag = "events",
+    params(
+        ("id" = i64, Path, description = "Event ID")
+    ),
+    responses(
+        (status = 200, description = "Resolved event location", body = EventLocation),
+        (status = 404, description = "Event has no resolved location"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn get_event_location(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<EventLocation>, AppError> {
+    let location = state
+        .location_service
+        .get_event_location(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No resolved location for event {}", id)))?;
+    Ok(Json(location))
+}
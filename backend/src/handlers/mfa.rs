@@ -0,0 +1,194 @@
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    database::UserRepository,
+    error::AppError,
+    middleware::auth::{AuthContext, STEP_UP_MAX_AGE_SECS},
+    models::{MfaEnrollment, MfaStatus, User},
+    AppState,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ActivateMfaRequest {
+    /// Current code from the authenticator app
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct VerifyMfaRequest {
+    pub totp_code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RecoveryCodesResponse {
+    /// Single-use codes for when the authenticator is unavailable. Shown once.
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StepUpResponse {
+    /// Seconds the session may use step-up protected endpoints without another code
+    pub valid_for: i64,
+}
+
+async fn current_user(state: &AppState, auth: &AuthContext) -> Result<User, AppError> {
+    state
+        .user_service
+        .get_user(auth.user_id)
+        .await?
+        .ok_or(AppError::Unauthorized)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/mfa",
+    tag = "authentication",
+    responses(
+        (status = 200, description = "Two-factor authentication state of the current user", body = MfaStatus),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn get_mfa_status(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+) -> Result<Json<MfaStatus>, AppError> {
+    let user = current_user(&state, &auth).await?;
+    Ok(Json(state.mfa_service.status(&user).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/mfa/enroll",
+    tag = "authentication",
+    responses(
+        (status = 200, description = "New TOTP secret; confirm it with /api/v1/auth/mfa/activate", body = MfaEnrollment),
+        (status = 401, description = "Unauthorized"),
+        (status = 409, description = "Two-factor authentication is already enabled")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn enroll_mfa(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+) -> Result<Json<MfaEnrollment>, AppError> {
+    let user = current_user(&state, &auth).await?;
+    Ok(Json(state.mfa_service.enroll(&user).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/mfa/activate",
+    tag = "authentication",
+    request_body = ActivateMfaRequest,
+    responses(
+        (status = 200, description = "Two-factor authentication enabled; the recovery codes are not shown again", body = RecoveryCodesResponse),
+        (status = 400, description = "Code does not match the pending secret"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "No pending enrollment")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn activate_mfa(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Json(req): Json<ActivateMfaRequest>,
+) -> Result<Json<RecoveryCodesResponse>, AppError> {
+    let recovery_codes = state.mfa_service.activate(auth.user_id, &req.code).await?;
+    // The code just given counts as this session's second factor
+    if let Some(session_id) = auth.session_id {
+        state.session_service.mark_mfa_verified(session_id).await?;
+    }
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/mfa/verify",
+    tag = "authentication",
+    request_body = VerifyMfaRequest,
+    responses(
+        (status = 200, description = "Second factor confirmed for this session", body = StepUpResponse),
+        (status = 401, description = "Invalid or reused code")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn verify_mfa(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Json(req): Json<VerifyMfaRequest>,
+) -> Result<Json<StepUpResponse>, AppError> {
+    let session_id = auth.session_id.ok_or(AppError::Unauthorized)?;
+    let verified = state
+        .mfa_service
+        .verify(
+            auth.user_id,
+            req.totp_code.as_deref(),
+            req.recovery_code.as_deref(),
+        )
+        .await?;
+    if !verified {
+        return Err(AppError::MfaRequired("Invalid two-factor code".to_string()));
+    }
+    state.session_service.mark_mfa_verified(session_id).await?;
+    Ok(Json(StepUpResponse {
+        valid_for: STEP_UP_MAX_AGE_SECS,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/mfa/recovery-codes",
+    tag = "authentication",
+    responses(
+        (status = 200, description = "New recovery codes; the previous ones stop working", body = RecoveryCodesResponse),
+        (status = 401, description = "Unauthorized, or no recent second factor in this session"),
+        (status = 422, description = "Two-factor authentication is not enabled")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+) -> Result<Json<RecoveryCodesResponse>, AppError> {
+    let recovery_codes = state
+        .mfa_service
+        .regenerate_recovery_codes(auth.user_id)
+        .await?;
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/auth/mfa",
+    tag = "authentication",
+    responses(
+        (status = 204, description = "Two-factor authentication turned off"),
+        (status = 401, description = "Unauthorized, or no recent second factor in this session"),
+        (status = 403, description = "The user's role requires two-factor authentication")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn disable_mfa(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+) -> Result<StatusCode, AppError> {
+    let user = current_user(&state, &auth).await?;
+    state.mfa_service.disable(&user).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,12 +1,23 @@
+use std::time::{Duration, Instant};
+
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::IntoResponse,
-    Json,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
 };
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{AppState, config::MonitoringConfig, error::AppError, middleware::auth::AuthContext};
+use crate::models::{AlertStatus, NewAlertSilence};
+use crate::monitoring::alerts::SIGNAL_UPGRADE_FAILED;
+use crate::monitoring::{InfrastructureMetrics, PerformanceMonitor};
+use crate::monitoring::metrics::{self, Gauge, METRICS};
 
-use crate::{AppState, error::AppError, middleware::auth::AuthContext};
-use crate::monitoring::{MonitoringSystem, PerformanceMonitor, InfrastructureMonitor};
+/// How long a scrape waits on each source of gauges
+const SCRAPE_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// Get comprehensive monitoring dashboard
 /// 
@@ -14,15 +25,14 @@ use crate::monitoring::{MonitoringSystem, PerformanceMonitor, InfrastructureMoni
 /// Requires auditor or admin authentication.
 pub async fn get_dashboard(
     State(state): State<AppState>,
-    auth: AuthContext,
+    Extension(auth): Extension<AuthContext>,
 ) -> Result<impl IntoResponse, AppError> {
     // Only auditors and admins can view dashboard
     if !matches!(auth.role, crate::models::UserRole::Auditor | crate::models::UserRole::Administrator) {
         return Err(AppError::Forbidden("Auditor or admin access required".to_string()));
     }
     
-    let monitoring_system = MonitoringSystem::new();
-    let dashboard = monitoring_system.get_dashboard().await;
+    let dashboard = state.monitoring_system.get_dashboard().await;
     
     Ok(Json(dashboard))
 }
@@ -33,14 +43,14 @@ pub async fn get_dashboard(
 /// Requires auditor or admin authentication.
 pub async fn get_error_stats(
     State(state): State<AppState>,
-    auth: AuthContext,
+    Extension(auth): Extension<AuthContext>,
 ) -> Result<impl IntoResponse, AppError> {
     // Only auditors and admins can view error stats
     if !matches!(auth.role, crate::models::UserRole::Auditor | crate::models::UserRole::Administrator) {
         return Err(AppError::Forbidden("Auditor or admin access required".to_string()));
     }
     
-    let stats = state.error_monitor.get_stats().await;
+    let stats = state.monitoring_system.error_monitor.get_stats().await;
     
     Ok(Json(stats))
 }
@@ -51,14 +61,14 @@ pub async fn get_error_stats(
 /// Requires auditor or admin authentication.
 pub async fn get_recent_errors(
     State(state): State<AppState>,
-    auth: AuthContext,
+    Extension(auth): Extension<AuthContext>,
 ) -> Result<impl IntoResponse, AppError> {
     // Only auditors and admins can view error details
     if !matches!(auth.role, crate::models::UserRole::Auditor | crate::models::UserRole::Administrator) {
         return Err(AppError::Forbidden("Auditor or admin access required".to_string()));
     }
     
-    let errors = state.error_monitor.get_recent_errors(50).await;
+    let errors = state.monitoring_system.error_monitor.get_recent_errors(50).await;
     
     Ok(Json(errors))
 }
@@ -69,7 +79,7 @@ pub async fn get_recent_errors(
 /// Requires auditor or admin authentication.
 pub async fn get_performance_metrics(
     State(state): State<AppState>,
-    auth: AuthContext,
+    Extension(auth): Extension<AuthContext>,
 ) -> Result<impl IntoResponse, AppError> {
     // Only auditors and admins can view performance metrics
     if !matches!(auth.role, crate::models::UserRole::Auditor | crate::models::UserRole::Administrator) {
@@ -82,23 +92,35 @@ pub async fn get_performance_metrics(
     Ok(Json(metrics))
 }
 
+/// Latest infrastructure sample and the samples before it, oldest first
+#[derive(serde::Serialize)]
+pub struct InfrastructureReport {
+    #[serde(flatten)]
+    pub current: InfrastructureMetrics,
+    pub history: Vec<InfrastructureMetrics>,
+}
+
 /// Get infrastructure metrics
 /// 
-/// Returns database pool usage, Redis status, memory, CPU, and connection metrics.
+/// Returns database pool usage, Redis status, memory, CPU, and websocket connection
+/// metrics from the latest sample, with the recent samples in `history`.
 /// Requires auditor or admin authentication.
 pub async fn get_infrastructure_metrics(
     State(state): State<AppState>,
-    auth: AuthContext,
+    Extension(auth): Extension<AuthContext>,
 ) -> Result<impl IntoResponse, AppError> {
     // Only auditors and admins can view infrastructure metrics
     if !matches!(auth.role, crate::models::UserRole::Auditor | crate::models::UserRole::Administrator) {
         return Err(AppError::Forbidden("Auditor or admin access required".to_string()));
     }
     
-    let infra_monitor = InfrastructureMonitor::new();
-    let metrics = infra_monitor.get_metrics().await;
+    let infra_monitor = &state.monitoring_system.infrastructure_monitor;
+    let report = InfrastructureReport {
+        current: infra_monitor.get_metrics().await,
+        history: infra_monitor.get_history().await,
+    };
     
-    Ok(Json(metrics))
+    Ok(Json(report))
 }
 
 /// Check and trigger alerts
@@ -107,15 +129,14 @@ pub async fn get_infrastructure_metrics(
 /// Requires auditor or admin authentication.
 pub async fn check_alerts(
     State(state): State<AppState>,
-    auth: AuthContext,
+    Extension(auth): Extension<AuthContext>,
 ) -> Result<impl IntoResponse, AppError> {
     // Only auditors and admins can trigger alert checks
     if !matches!(auth.role, crate::models::UserRole::Auditor | crate::models::UserRole::Administrator) {
         return Err(AppError::Forbidden("Auditor or admin access required".to_string()));
     }
     
-    let monitoring_system = MonitoringSystem::new();
-    monitoring_system.check_alerts().await;
+    state.monitoring_system.check_alerts().await;
     
     Ok(Json(serde_json::json!({
         "status": "alert_check_completed",
@@ -123,6 +144,232 @@ pub async fn check_alerts(
     })))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct AlertListQuery {
+    pub status: Option<AlertStatus>,
+}
+
+/// List alerts
+///
+/// Firing alerts first, then the most recently changed. Routes already
+/// require auditor or admin authentication.
+pub async fn list_alerts(
+    State(state): State<AppState>,
+    Query(query): Query<AlertListQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let alerts = state.alert_service.list_alerts(query.status).await?;
+    Ok(Json(alerts))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SilenceListQuery {
+    #[serde(default)]
+    pub include_ended: bool,
+}
+
+/// List alert silences that have not ended, or recent ones too with `include_ended`
+pub async fn list_alert_silences(
+    State(state): State<AppState>,
+    Query(query): Query<SilenceListQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let silences = state
+        .alert_service
+        .list_silences(query.include_ended)
+        .await?;
+    Ok(Json(silences))
+}
+
+/// Silence alerts matching a rule and subject for a while. Routes already
+/// require admin authentication.
+pub async fn create_alert_silence(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Json(silence): Json<NewAlertSilence>,
+) -> Result<impl IntoResponse, AppError> {
+    let created = state
+        .alert_service
+        .create_silence(silence, auth.user_id)
+        .await?;
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+/// Remove an alert silence. Routes already require admin authentication.
+pub async fn delete_alert_silence(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    if !state.alert_service.delete_silence(id).await? {
+        return Err(AppError::NotFound("Alert silence not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// A contract event forwarded by whatever watches the chain
+#[derive(Debug, Deserialize)]
+pub struct ContractEventReport {
+    /// Event name as the contract publishes it, e.g. `upgrade_failed`
+    pub event: String,
+    pub contract_id: String,
+    pub reason: Option<String>,
+}
+
+/// Report a contract upgrade event
+///
+/// `upgrade_failed` raises the upgrade alert for the contract;
+/// `upgrade_completed` and `upgrade_reset` clear it. Routes already require
+/// admin authentication.
+pub async fn report_contract_event(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Json(report): Json<ContractEventReport>,
+) -> Result<impl IntoResponse, AppError> {
+    let contract_id = report.contract_id.trim();
+    if contract_id.is_empty() || contract_id.len() > 64 {
+        return Err(AppError::Validation(
+            "contract_id must be 1 to 64 characters".to_string(),
+        ));
+    }
+    let failed = match report.event.as_str() {
+        "upgrade_failed" => true,
+        "upgrade_completed" | "upgrade_reset" => false,
+        other => {
+            return Err(AppError::Validation(format!(
+                "Unsupported contract event '{}'",
+                other
+            )))
+        }
+    };
+    let reason = report
+        .reason
+        .filter(|_| failed)
+        .map(|reason| reason.chars().take(500).collect());
+    state
+        .alert_service
+        .report_signal(SIGNAL_UPGRADE_FAILED, contract_id, failed, reason)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Prometheus metrics in the text exposition format
+///
+/// Request latency, error codes, ingestion and webhook counters, plus
+/// database pool, Redis and queue gauges read at scrape time. The scraper
+/// sends `METRICS_TOKEN` as a bearer token.
+pub async fn prometheus_metrics(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    if !may_scrape(&state.config.monitoring, &headers) {
+        return Err(AppError::Unauthorized);
+    }
+
+    let gauges = scrape_gauges(&state).await;
+    let body = METRICS.render(&gauges);
+    Ok(([(header::CONTENT_TYPE, metrics::CONTENT_TYPE)], body).into_response())
+}
+
+/// Whether a scrape presents the metrics token. With no token configured only
+/// deployments that opted into `metrics_public` serve metrics.
+fn may_scrape(config: &MonitoringConfig, headers: &HeaderMap) -> bool {
+    let Some(expected) = &config.metrics_token else {
+        return config.metrics_public;
+    };
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    // Digests compare in constant time with respect to the token
+    Sha256::digest(presented.as_bytes()) == Sha256::digest(expected.as_bytes())
+}
+
+/// Current state of the pool, Redis and the queues. A source that does not
+/// answer in time is left out rather than holding up the scrape.
+async fn scrape_gauges(state: &AppState) -> Vec<Gauge> {
+    let pool = state.db.pool();
+    let size = pool.size() as f64;
+    let idle = pool.num_idle() as f64;
+    let mut gauges = vec![
+        Gauge {
+            name: "db_pool_connections",
+            help: "Database pool connections by state",
+            samples: vec![
+                (vec![("state", "idle".to_string())], idle),
+                (vec![("state", "in_use".to_string())], (size - idle).max(0.0)),
+            ],
+        },
+        Gauge::single(
+            "db_pool_max_connections",
+            "Most connections the database pool opens",
+            state.config.database.max_connections as f64,
+        ),
+    ];
+
+    let started = Instant::now();
+    let ping = tokio::time::timeout(SCRAPE_TIMEOUT, async {
+        let mut conn = state.redis_client.get_multiplexed_tokio_connection().await?;
+        redis::cmd("PING").query_async::<_, String>(&mut conn).await
+    })
+    .await;
+    let redis_up = matches!(ping, Ok(Ok(_)));
+    gauges.push(Gauge::single(
+        "redis_up",
+        "Whether Redis answered a ping",
+        if redis_up { 1.0 } else { 0.0 },
+    ));
+    if redis_up {
+        gauges.push(Gauge::single(
+            "redis_ping_seconds",
+            "Round trip of a Redis ping",
+            started.elapsed().as_secs_f64(),
+        ));
+    }
+
+    let sync_lag = tokio::time::timeout(
+        SCRAPE_TIMEOUT,
+        sqlx::query_as::<_, (Option<f64>,)>(
+            "SELECT EXTRACT(EPOCH FROM NOW() - MAX(timestamp))::FLOAT8 FROM tracking_events",
+        )
+        .fetch_one(pool),
+    )
+    .await;
+    if let Ok(Ok((Some(lag),))) = sync_lag {
+        gauges.push(Gauge::single(
+            "sync_lag_seconds",
+            "Time since the newest recorded tracking event happened",
+            lag.max(0.0),
+        ));
+    }
+
+    let queue = tokio::time::timeout(
+        SCRAPE_TIMEOUT,
+        sqlx::query_as::<_, (i64, Option<f64>)>(
+            r#"
+            SELECT COUNT(*),
+                   EXTRACT(EPOCH FROM NOW() - MIN(next_attempt_at) FILTER (WHERE next_attempt_at <= NOW()))::FLOAT8
+            FROM webhook_deliveries WHERE status = 'pending'
+            "#,
+        )
+        .fetch_one(pool),
+    )
+    .await;
+    if let Ok(Ok((pending, overdue))) = queue {
+        gauges.push(Gauge::single(
+            "webhook_queue_pending",
+            "Webhook deliveries waiting to be sent or retried",
+            pending as f64,
+        ));
+        gauges.push(Gauge::single(
+            "webhook_queue_overdue_seconds",
+            "How long the longest-waiting due webhook delivery has waited",
+            overdue.unwrap_or(0.0).max(0.0),
+        ));
+    }
+
+    gauges
+}
+
 /// Health check endpoint with error rate monitoring
 /// 
 /// Returns service health status including error rate.
@@ -130,7 +377,7 @@ pub async fn check_alerts(
 pub async fn health_check_with_errors(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, AppError> {
-    let stats = state.error_monitor.get_stats().await;
+    let stats = state.monitoring_system.error_monitor.get_stats().await;
     
     // Check if error rate is too high
     let is_healthy = stats.error_rate < 50.0; // 50 errors per minute threshold
@@ -170,4 +417,27 @@ mod tests {
         let stats = monitor.get_stats().await;
         assert!(stats.error_rate < 50.0);
     }
+
+    #[test]
+    fn metrics_need_the_token_unless_made_public() {
+        let mut config = MonitoringConfig {
+            metrics_token: None,
+            metrics_public: false,
+            sample_interval_secs: 10,
+            sample_history: 720,
+        };
+        let mut scrape = HeaderMap::new();
+        assert!(!may_scrape(&config, &scrape));
+
+        config.metrics_public = true;
+        assert!(may_scrape(&config, &scrape));
+
+        // A configured token is always required
+        config.metrics_token = Some("0123456789abcdef".to_string());
+        assert!(!may_scrape(&config, &scrape));
+        scrape.insert(header::AUTHORIZATION, "Bearer wrong".parse().unwrap());
+        assert!(!may_scrape(&config, &scrape));
+        scrape.insert(header::AUTHORIZATION, "Bearer 0123456789abcdef".parse().unwrap());
+        assert!(may_scrape(&config, &scrape));
+    }
 }
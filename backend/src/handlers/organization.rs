@@ -0,0 +1,514 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+    Extension,
+};
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    middleware::auth::AuthContext,
+    models::organization::{
+        AddAddressRequest, AddMemberRequest, AddressChallengeRequest, CreateOrganizationRequest,
+        OrgRole, Organization, OrganizationAddress, OrganizationMember, OrganizationMembership,
+        UpdateMemberRequest,
+    },
+    models::{UserRole, WalletChallenge},
+    services::sep10::ChallengePurpose,
+    validation::validate_string,
+    AppState,
+};
+
+/// Checks the caller belongs to the organization (administrators always do)
+/// and returns their role in it. Non-members get a 404.
+pub(crate) async fn require_member(
+    state: &AppState,
+    auth: &AuthContext,
+    organization_id: Uuid,
+) -> Result<Option<OrgRole>, AppError> {
+    let role = state
+        .organization_service
+        .membership(organization_id, auth.user_id)
+        .await?;
+    check_access(&auth.role, role, organization_id, false)?;
+    Ok(role)
+}
+
+/// Like [`require_member`], but only owners and admins of the organization
+/// (or platform administrators) pass
+pub(crate) async fn require_manager(
+    state: &AppState,
+    auth: &AuthContext,
+    organization_id: Uuid,
+) -> Result<Option<OrgRole>, AppError> {
+    let role = state
+        .organization_service
+        .membership(organization_id, auth.user_id)
+        .await?;
+    check_access(&auth.role, role, organization_id, true)?;
+    Ok(role)
+}
+
+/// The rule behind [`require_member`] and [`require_manager`], given the
+/// caller's role in the organization
+fn check_access(
+    platform_role: &UserRole,
+    role: Option<OrgRole>,
+    organization_id: Uuid,
+    manage: bool,
+) -> Result<(), AppError> {
+    if matches!(platform_role, UserRole::Administrator) {
+        return Ok(());
+    }
+    match role {
+        None => Err(AppError::NotFound(format!(
+            "Organization {} not found",
+            organization_id
+        ))),
+        Some(role) if manage && !role.can_manage() => Err(AppError::Forbidden(
+            "Only organization owners and admins may do this".to_string(),
+        )),
+        Some(_) => Ok(()),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/organizations",
+    tag = "organizations",
+    request_body = CreateOrganizationRequest,
+    responses(
+        (status = 201, description = "Organization created with the caller as owner", body = Organization),
+        (status = 400, description = "Bad request - invalid name or slug"),
+        (status = 401, description = "Unauthorized"),
+        (status = 409, description = "Slug already taken")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn create_organization(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Json(request): Json<CreateOrganizationRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    validate_string("name", &request.name, 128)?;
+    let organization = state
+        .organization_service
+        .create(&request.name, request.slug.as_deref(), auth.user_id)
+        .await?;
+    Ok((StatusCode::CREATED, Json(organization)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/organizations",
+    tag = "organizations",
+    responses(
+        (status = 200, description = "Organizations the caller belongs to, with their role", body = [OrganizationMembership]),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn list_organizations(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+) -> Result<Json<Vec<OrganizationMembership>>, AppError> {
+    let organizations = state
+        .organization_service
+        .list_for_user(auth.user_id)
+        .await?;
+    Ok(Json(organizations))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/organizations/{id}",
+    tag = "organizations",
+    params(
+        ("id" = Uuid, Path, description = "Organization ID")
+    ),
+    responses(
+        (status = 200, description = "Organization", body = Organization),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Organization not found")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn get_organization(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Organization>, AppError> {
+    require_member(&state, &auth, id).await?;
+    let organization = state.organization_service.get(id).await?;
+    Ok(Json(organization))
+}
+
+// ── Members ───────────────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/organizations/{id}/members",
+    tag = "organizations",
+    params(
+        ("id" = Uuid, Path, description = "Organization ID")
+    ),
+    responses(
+        (status = 200, description = "Members and their roles", body = [OrganizationMember]),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Organization not found")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn list_members(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<OrganizationMember>>, AppError> {
+    require_member(&state, &auth, id).await?;
+    let members = state.organization_service.members(id).await?;
+    Ok(Json(members))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/organizations/{id}/members",
+    tag = "organizations",
+    params(
+        ("id" = Uuid, Path, description = "Organization ID")
+    ),
+    request_body = AddMemberRequest,
+    responses(
+        (status = 201, description = "Member added", body = OrganizationMember),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an owner or admin of the organization, or an admin changing an owner"),
+        (status = 404, description = "Organization or user not found"),
+        (status = 409, description = "Already a member")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn add_member(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<AddMemberRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let caller = require_manager(&state, &auth, id).await?;
+    check_rank(&auth.role, caller, None, Some(request.role))?;
+    let member = state
+        .organization_service
+        .add_member(id, request.user_id, request.role)
+        .await?;
+    Ok((StatusCode::CREATED, Json(member)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/organizations/{id}/members/{user_id}",
+    tag = "organizations",
+    params(
+        ("id" = Uuid, Path, description = "Organization ID"),
+        ("user_id" = Uuid, Path, description = "Member's user ID")
+    ),
+    request_body = UpdateMemberRequest,
+    responses(
+        (status = 200, description = "Role changed", body = OrganizationMember),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an owner or admin of the organization, or an admin changing an owner"),
+        (status = 404, description = "Organization or member not found"),
+        (status = 422, description = "Would leave the organization without an owner")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn update_member(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+    Json(request): Json<UpdateMemberRequest>,
+) -> Result<Json<OrganizationMember>, AppError> {
+    let caller = require_manager(&state, &auth, id).await?;
+    let target = state.organization_service.membership(id, user_id).await?;
+    check_rank(&auth.role, caller, target, Some(request.role))?;
+    let member = state
+        .organization_service
+        .update_member(id, user_id, request.role)
+        .await?;
+    Ok(Json(member))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/organizations/{id}/members/{user_id}",
+    tag = "organizations",
+    params(
+        ("id" = Uuid, Path, description = "Organization ID"),
+        ("user_id" = Uuid, Path, description = "Member's user ID")
+    ),
+    responses(
+        (status = 204, description = "Member removed"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an owner or admin of the organization, or an admin changing an owner"),
+        (status = 404, description = "Organization or member not found"),
+        (status = 422, description = "Would leave the organization without an owner")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn remove_member(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, AppError> {
+    // Members may always leave on their own
+    if user_id == auth.user_id {
+        require_member(&state, &auth, id).await?;
+    } else {
+        let caller = require_manager(&state, &auth, id).await?;
+        let target = state.organization_service.membership(id, user_id).await?;
+        check_rank(&auth.role, caller, target, None)?;
+    }
+    state
+        .organization_service
+        .remove_member(id, user_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Only owners make someone an owner, or change or remove an owner; admins
+/// manage the members below them. `target` is the member's current role and
+/// `new_role` the one they would get.
+fn check_rank(
+    platform_role: &UserRole,
+    caller: Option<OrgRole>,
+    target: Option<OrgRole>,
+    new_role: Option<OrgRole>,
+) -> Result<(), AppError> {
+    if matches!(platform_role, UserRole::Administrator) || caller == Some(OrgRole::Owner) {
+        return Ok(());
+    }
+    if target == Some(OrgRole::Owner) || new_role == Some(OrgRole::Owner) {
+        return Err(AppError::Forbidden(
+            "Only organization owners may appoint, change or remove owners".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// ── Addresses ─────────────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/organizations/{id}/addresses",
+    tag = "organizations",
+    params(
+        ("id" = Uuid, Path, description = "Organization ID")
+    ),
+    responses(
+        (status = 200, description = "Stellar addresses operated by the organization", body = [OrganizationAddress]),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Organization not found")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn list_addresses(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<OrganizationAddress>>, AppError> {
+    require_member(&state, &auth, id).await?;
+    let addresses = state.organization_service.addresses(id).await?;
+    Ok(Json(addresses))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/organizations/{id}/addresses/challenge",
+    tag = "organizations",
+    params(
+        ("id" = Uuid, Path, description = "Organization ID")
+    ),
+    request_body = AddressChallengeRequest,
+    responses(
+        (status = 200, description = "Challenge transaction for the address's wallet to sign; valid for 5 minutes", body = WalletChallenge),
+        (status = 400, description = "Bad request - not a Stellar account address"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an owner or admin of the organization")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn address_challenge(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<AddressChallengeRequest>,
+) -> Result<Json<WalletChallenge>, AppError> {
+    require_manager(&state, &auth, id).await?;
+    let challenge = state
+        .wallet_auth_service
+        .issue_challenge(
+            request.stellar_address.trim(),
+            ChallengePurpose::LinkToOrganization(id),
+        )
+        .await?;
+    Ok(Json(challenge))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/organizations/{id}/addresses",
+    tag = "organizations",
+    params(
+        ("id" = Uuid, Path, description = "Organization ID")
+    ),
+    request_body = AddAddressRequest,
+    responses(
+        (status = 201, description = "Address added; products it owns now belong to the organization", body = OrganizationAddress),
+        (status = 400, description = "Bad request - invalid label"),
+        (status = 401, description = "Unauthorized, or the transaction is not an open challenge for this organization signed by its address"),
+        (status = 403, description = "Not an owner or admin of the organization"),
+        (status = 409, description = "Address belongs to an organization already")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn add_address(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<AddAddressRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(label) = &request.label {
+        validate_string("label", label, 64)?;
+    }
+    require_manager(&state, &auth, id).await?;
+    // Products only move once the address's holder has signed for it
+    let verified = state
+        .wallet_auth_service
+        .redeem_challenge(&request.transaction, ChallengePurpose::LinkToOrganization(id))
+        .await?;
+    let address = state
+        .organization_service
+        .add_address(id, &verified, request.label.as_deref())
+        .await?;
+    Ok((StatusCode::CREATED, Json(address)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/organizations/{id}/addresses/{stellar_address}",
+    tag = "organizations",
+    params(
+        ("id" = Uuid, Path, description = "Organization ID"),
+        ("stellar_address" = String, Path, description = "Address to release")
+    ),
+    responses(
+        (status = 204, description = "Address released"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an owner or admin of the organization"),
+        (status = 404, description = "Organization or address not found")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn remove_address(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path((id, stellar_address)): Path<(Uuid, String)>,
+) -> Result<StatusCode, AppError> {
+    require_manager(&state, &auth, id).await?;
+    state
+        .organization_service
+        .remove_address(id, &stellar_address)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn other_organizations_look_missing() {
+        let org = Uuid::new_v4();
+        for manage in [false, true] {
+            assert!(matches!(
+                check_access(&UserRole::Supplier, None, org, manage),
+                Err(AppError::NotFound(_))
+            ));
+            assert!(check_access(&UserRole::Administrator, None, org, manage).is_ok());
+        }
+    }
+
+    #[test]
+    fn only_owners_and_admins_manage() {
+        let org = Uuid::new_v4();
+        for role in [OrgRole::Owner, OrgRole::Admin] {
+            assert!(check_access(&UserRole::Supplier, Some(role), org, true).is_ok());
+        }
+        for role in [OrgRole::Member, OrgRole::Viewer] {
+            assert!(check_access(&UserRole::Supplier, Some(role), org, false).is_ok());
+            assert!(matches!(
+                check_access(&UserRole::Supplier, Some(role), org, true),
+                Err(AppError::Forbidden(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn only_owners_touch_owners() {
+        let admin = Some(OrgRole::Admin);
+        let owner = Some(OrgRole::Owner);
+        let supplier = &UserRole::Supplier;
+
+        // Admins manage the members below them
+        assert!(check_rank(supplier, admin, Some(OrgRole::Member), Some(OrgRole::Admin)).is_ok());
+        assert!(check_rank(supplier, admin, Some(OrgRole::Viewer), None).is_ok());
+        // but cannot demote or remove an owner, or make anyone (themselves
+        // included) an owner
+        for (target, new_role) in [
+            (owner, Some(OrgRole::Admin)),
+            (owner, None),
+            (admin, Some(OrgRole::Owner)),
+            (None, Some(OrgRole::Owner)),
+        ] {
+            assert!(matches!(
+                check_rank(supplier, admin, target, new_role),
+                Err(AppError::Forbidden(_))
+            ));
+            assert!(check_rank(supplier, owner, target, new_role).is_ok());
+            assert!(check_rank(&UserRole::Administrator, None, target, new_role).is_ok());
+        }
+    }
+
+    #[test]
+    fn adding_an_address_needs_a_signed_challenge() {
+        let unsigned = serde_json::json!({
+            "stellar_address": "GBRPYHIL2CI3FNQ4BXLFMNDLFJUNPU2HY3ZMFSHONUCEOASW7QC7OX2H",
+            "label": "treasury",
+        });
+        assert!(serde_json::from_value::<AddAddressRequest>(unsigned.clone()).is_err());
+
+        let mut signed = unsigned;
+        signed["transaction"] = "AAAAAgAAAAA=".into();
+        assert!(serde_json::from_value::<AddAddressRequest>(signed).is_ok());
+    }
+}
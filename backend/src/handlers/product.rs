@@ -2,6 +2,7 @@ use axum::{
     extract::{State, Path, Query},
     http::StatusCode,
     response::Json,
+    Extension,
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -9,8 +10,11 @@ use utoipa::ToSchema;
 
 use crate::{
     AppState,
+    database::ProductRepository,
     error::AppError,
-    models::{Product, NewProduct, ProductFilters},
+    middleware::auth::AuthContext,
+    models::{Product, NewProduct, ProductFilters, UserRole},
+    services::access_control::{can_create_products, ProductAction},
     validation::{validate_string, sanitize_input, validate_stellar_address, validate_product_id, validate_location, sanitize_json_metadata},
 };
 
@@ -40,6 +44,10 @@ pub struct CreateProductRequest {
     pub media_hashes: Vec<String>,
     #[serde(alias = "customFields")]
     pub custom_fields: serde_json::Value,
+    /// Address to own the product: your own or one of your organization's.
+    /// Defaults to your own address, else your organization's first address.
+    #[serde(alias = "ownerAddress")]
+    pub owner_address: Option<String>,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -76,6 +84,7 @@ pub struct ProductResponse {
     pub updated_at: chrono::DateTime<chrono::Utc>,
     pub created_by: String,
     pub updated_by: String,
+    pub organization_id: Option<Uuid>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -104,6 +113,7 @@ impl From<Product> for ProductResponse {
             updated_at: product.updated_at,
             created_by: product.created_by,
             updated_by: product.updated_by,
+            organization_id: product.organization_id,
         }
     }
 }
@@ -124,8 +134,13 @@ impl From<Product> for ProductResponse {
 )]
 pub async fn list_products(
     State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
     Query(query): Query<ListProductsQuery>,
 ) -> Result<Json<PaginatedProductsResponse>, AppError> {
+    // Only products of the caller's organization or shared with it
+    let scope = auth_context.tenant_scope();
+    let key_restrictions = auth_context.key_restrictions.as_ref();
+
     let offset = query.offset.unwrap_or(0);
     let limit = query.limit.unwrap_or(20).min(100); // Cap at 100
 
@@ -142,9 +157,10 @@ pub async fn list_products(
 
     let products = if let Some(search_query) = query.search {
         state.product_service
-            .search_products(&sanitize_input(&search_query), limit)
+            .search_products(&sanitize_input(&search_query), limit, scope.as_ref())
             .await?
             .into_iter()
+            .filter(|product| auth_context.key_allows_product(product))
             .map(ProductResponse::from)
             .collect()
     } else {
@@ -154,6 +170,9 @@ pub async fn list_products(
             is_active: query.is_active,
             created_after: None,
             created_before: None,
+            scope: scope.clone(),
+            product_ids: key_restrictions.and_then(|r| r.product_ids.clone()),
+            categories: key_restrictions.and_then(|r| r.categories.clone()),
         };
 
         state.product_service
@@ -173,6 +192,9 @@ pub async fn list_products(
             is_active: query.is_active,
             created_after: None,
             created_before: None,
+            scope: scope.clone(),
+            product_ids: key_restrictions.and_then(|r| r.product_ids.clone()),
+            categories: key_restrictions.and_then(|r| r.categories.clone()),
         };
         state.product_service
             .count_products(Some(filters))
@@ -205,8 +227,41 @@ pub async fn list_products(
 )]
 pub async fn create_product(
     State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
     Json(request): Json<CreateProductRequest>,
 ) -> Result<Json<ProductResponse>, AppError> {
+    if !can_create_products(&auth_context.role) {
+        return Err(AppError::Forbidden("Only suppliers can register products".to_string()));
+    }
+    // The owner address is what the chain and product ACLs recognise; it also
+    // decides which organization the product belongs to
+    let owner_address = match request.owner_address.or(auth_context.stellar_address.clone()) {
+        Some(address) => address,
+        None => match auth_context.organization_id {
+            Some(organization_id) => state
+                .organization_service
+                .addresses(organization_id)
+                .await?
+                .into_iter()
+                .next()
+                .map(|a| a.stellar_address)
+                .unwrap_or_default(),
+            None => String::new(),
+        },
+    };
+    let is_admin = matches!(auth_context.role, UserRole::Administrator);
+    if owner_address.is_empty() && !is_admin {
+        return Err(AppError::Validation("A Stellar address is required to own products".to_string()));
+    }
+    if !owner_address.is_empty() {
+        validate_stellar_address(&owner_address)?;
+        if !state.access_service.may_act_as(&auth_context, &owner_address).await? {
+            return Err(AppError::Forbidden(
+                "owner_address must be your own or one of your organization's addresses".to_string(),
+            ));
+        }
+    }
+
     // Validate inputs
     validate_product_id(&request.id)?;
     validate_string("name", &request.name, 128)?;
@@ -216,9 +271,6 @@ pub async fn create_product(
         return Err(AppError::Validation("description must not exceed 2048 characters".to_string()));
     }
 
-    // Get auth context
-    let auth_context = crate::middleware::auth::get_auth_context(&axum::extract::Request::builder().uri("/").body(()).unwrap())?;
-
     let new_product = NewProduct {
         id: sanitize_input(&request.id),
         name: sanitize_input(&request.name),
@@ -233,9 +285,16 @@ pub async fn create_product(
             sanitize_json_metadata(&mut fields);
             fields
         },
-        owner_address: auth_context.stellar_address.clone().unwrap_or_default(),
+        owner_address,
         created_by: auth_context.user_id.to_string(),
     };
+    if auth_context
+        .key_restrictions
+        .as_ref()
+        .is_some_and(|r| !r.allows_product(&new_product.id, &new_product.category))
+    {
+        return Err(AppError::Forbidden("API key is not allowed to register this product".to_string()));
+    }
 
     let product = state.product_service.create_product(new_product).await?;
     Ok(Json(ProductResponse::from(product)))
@@ -260,6 +319,7 @@ pub async fn create_product(
 )]
 pub async fn get_product(
     State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
     Path(id): Path<String>,
 ) -> Result<Json<ProductResponse>, AppError> {
     validate_product_id(&id)?;
@@ -268,6 +328,10 @@ pub async fn get_product(
         .get_product(&id)
         .await?
         .ok_or_else(|| AppError::NotFound(format!("Product {} not found", id)))?;
+    state
+        .access_service
+        .authorize(&auth_context, &product, ProductAction::Read)
+        .await?;
 
     Ok(Json(ProductResponse::from(product)))
 }
@@ -294,18 +358,17 @@ pub async fn get_product(
 )]
 pub async fn update_product(
     State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
     Path(id): Path<String>,
     Json(request): Json<UpdateProductRequest>,
 ) -> Result<Json<ProductResponse>, AppError> {
-    let auth_context = crate::middleware::auth::get_auth_context(&axum::extract::Request::builder().uri("/").body(()).unwrap())?;
-    
     validate_product_id(&id)?;
 
+    // Owners and delegates with edit access only
     let mut product = state
-        .product_service
-        .get_product(&id)
-        .await?
-        .ok_or_else(|| AppError::NotFound(format!("Product {} not found", id)))?;
+        .access_service
+        .authorize_product(&auth_context, &id, ProductAction::Edit)
+        .await?;
 
     // Update fields if provided with validation
     if let Some(name) = request.name {
@@ -369,16 +432,16 @@ pub async fn update_product(
 )]
 pub async fn delete_product(
     State(state): State<AppState>,
+    Extension(auth_context): Extension<AuthContext>,
     Path(id): Path<String>,
 ) -> Result<StatusCode, AppError> {
     validate_product_id(&id)?;
 
-    // Check if product exists
+    // Only the owner (or an administrator) may delete
     state
-        .product_service
-        .get_product(&id)
-        .await?
-        .ok_or_else(|| AppError::NotFound(format!("Product {} not found", id)))?;
+        .access_service
+        .authorize_product(&auth_context, &id, ProductAction::Delete)
+        .await?;
 
     state.product_service.delete_product(&id).await?;
     Ok(StatusCode::NO_CONTENT)
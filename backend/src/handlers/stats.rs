@@ -1,15 +1,15 @@
-use axum::{extract::State, response::Json};
+use axum::{extract::State, response::Json, Extension};
 use serde_json::json;
 use utoipa::ToSchema;
 
-use crate::{AppState, error::AppError};
+use crate::{AppState, database::{EventRepository, ProductRepository}, error::AppError, middleware::auth::AuthContext};
 
 #[utoipa::path(
     get,
     path = "/api/v1/stats",
     tag = "stats",
     responses(
-        (status = 200, description = "Statistics retrieved successfully"),
+        (status = 200, description = "Statistics for the caller's organization (platform-wide for administrators)"),
         (status = 401, description = "Unauthorized"),
         (status = 429, description = "Rate limit exceeded")
     ),
@@ -17,8 +17,11 @@ use crate::{AppState, error::AppError};
         ("api_key" = [])
     )
 )]
-pub async fn get_stats(State(state): State<AppState>) -> Result<Json<serde_json::Value>, AppError> {
-    let global_stats = state.event_service.get_global_stats().await?;
+pub async fn get_stats(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let global_stats = state.event_service.get_global_stats(auth.tenant_scope().as_ref()).await?;
     
     Ok(Json(json!({
         "total_products": global_stats.total_products,
@@ -0,0 +1,389 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    handlers::organization::{require_manager, require_member},
+    middleware::auth::AuthContext,
+    models::{NewWebhook, Webhook, WebhookDelivery, WebhookDeliveryAttempt, WebhookDeliveryStatus},
+    services::webhook_delivery,
+    validation::validate_string,
+    AppState,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateWebhookRequest {
+    /// Public HTTPS endpoint deliveries are POSTed to
+    pub url: String,
+    /// Event types to deliver, e.g. `["event.created"]`. Empty or missing means all.
+    pub events: Option<Vec<String>>,
+    /// Only deliver events for these products. Empty or missing means every
+    /// product the organization can see.
+    pub product_ids: Option<Vec<String>>,
+    pub description: Option<String>,
+    /// Organization the webhook belongs to; defaults to the caller's
+    pub organization_id: Option<Uuid>,
+}
+
+/// Fields left out stay as they are
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateWebhookRequest {
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+    /// An empty list removes the product filter
+    pub product_ids: Option<Vec<String>>,
+    pub description: Option<String>,
+    /// Paused webhooks queue nothing new; what is already queued waits
+    pub is_active: Option<bool>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookCreatedResponse {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    /// Signing secret for verifying deliveries. Shown only once.
+    pub secret: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListDeliveriesQuery {
+    pub status: Option<WebhookDeliveryStatus>,
+    pub offset: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookDeliveryDetail {
+    #[serde(flatten)]
+    pub delivery: WebhookDelivery,
+    /// Every try, oldest first
+    pub attempts: Vec<WebhookDeliveryAttempt>,
+}
+
+/// Loads a webhook the caller may see; managing it also needs an owner or
+/// admin role in its organization. Webhooks of other organizations are 404s.
+async fn load_webhook(
+    state: &AppState,
+    auth: &AuthContext,
+    id: Uuid,
+    manage: bool,
+) -> Result<Webhook, AppError> {
+    let not_found = || AppError::NotFound(format!("Webhook {} not found", id));
+    let webhook = state.webhook_service.get(id).await?.ok_or_else(not_found)?;
+    let organization_id = webhook.organization_id.ok_or_else(not_found)?;
+    let allowed = if manage {
+        require_manager(state, auth, organization_id).await
+    } else {
+        require_member(state, auth, organization_id).await
+    };
+    match allowed {
+        Ok(_) => Ok(webhook),
+        Err(AppError::NotFound(_)) => Err(not_found()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Trims and de-duplicates a product filter; an empty list means no filter
+fn product_filter(product_ids: Vec<String>) -> Result<Option<Vec<String>>, AppError> {
+    let mut list: Vec<String> = Vec::new();
+    for id in product_ids {
+        let id = id.trim().to_string();
+        validate_string("product_ids", &id, 64)?;
+        if !list.contains(&id) {
+            list.push(id);
+        }
+    }
+    Ok((!list.is_empty()).then_some(list))
+}
+
+fn check_url(url: &str) -> Result<String, AppError> {
+    webhook_delivery::validate_url(url.trim())
+        .map(|url| url.to_string())
+        .map_err(AppError::Validation)
+}
+
+fn check_events(events: &[String]) -> Result<Vec<String>, AppError> {
+    webhook_delivery::normalize_event_types(events).map_err(AppError::Validation)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/webhooks",
+    tag = "webhooks",
+    request_body = CreateWebhookRequest,
+    responses(
+        (status = 201, description = "Webhook created; the signing secret is only returned here", body = WebhookCreatedResponse),
+        (status = 400, description = "Bad request - non-public or non-HTTPS URL, or unknown event type"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Only organization owners and admins may add webhooks")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Json(request): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<WebhookCreatedResponse>), AppError> {
+    let organization_id = request
+        .organization_id
+        .or(auth.organization_id)
+        .ok_or_else(|| AppError::BadRequest("organization_id is required".to_string()))?;
+    require_manager(&state, &auth, organization_id).await?;
+
+    let url = check_url(&request.url)?;
+    let events = check_events(&request.events.unwrap_or_default())?;
+    let product_ids = product_filter(request.product_ids.unwrap_or_default())?;
+    if let Some(description) = &request.description {
+        validate_string("description", description, 256)?;
+    }
+
+    let secret = webhook_delivery::generate_secret();
+    let webhook = state
+        .webhook_service
+        .create(NewWebhook {
+            user_id: auth.user_id,
+            organization_id,
+            url,
+            secret: secret.clone(),
+            events,
+            product_ids,
+            description: request.description,
+        })
+        .await?;
+    Ok((
+        StatusCode::CREATED,
+        Json(WebhookCreatedResponse { webhook, secret }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/webhooks",
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "Webhooks of the caller's organization", body = [Webhook]),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn list_webhooks(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+) -> Result<Json<Vec<Webhook>>, AppError> {
+    let Some(organization_id) = auth.organization_id else {
+        return Ok(Json(Vec::new()));
+    };
+    require_member(&state, &auth, organization_id).await?;
+    let webhooks = state.webhook_service.list(organization_id).await?;
+    Ok(Json(webhooks))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/webhooks/{id}",
+    tag = "webhooks",
+    params(
+        ("id" = Uuid, Path, description = "Webhook ID")
+    ),
+    responses(
+        (status = 200, description = "Webhook", body = Webhook),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Webhook not found")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn get_webhook(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Webhook>, AppError> {
+    let webhook = load_webhook(&state, &auth, id, false).await?;
+    Ok(Json(webhook))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/webhooks/{id}",
+    tag = "webhooks",
+    params(
+        ("id" = Uuid, Path, description = "Webhook ID")
+    ),
+    request_body = UpdateWebhookRequest,
+    responses(
+        (status = 200, description = "Webhook updated", body = Webhook),
+        (status = 400, description = "Bad request - non-public or non-HTTPS URL, or unknown event type"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Only organization owners and admins may change webhooks"),
+        (status = 404, description = "Webhook not found")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn update_webhook(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<UpdateWebhookRequest>,
+) -> Result<Json<Webhook>, AppError> {
+    let mut webhook = load_webhook(&state, &auth, id, true).await?;
+    if let Some(url) = &request.url {
+        webhook.url = check_url(url)?;
+    }
+    if let Some(events) = &request.events {
+        webhook.events = check_events(events)?;
+    }
+    if let Some(product_ids) = request.product_ids {
+        webhook.product_ids = product_filter(product_ids)?;
+    }
+    if let Some(description) = request.description {
+        validate_string("description", &description, 256)?;
+        webhook.description = Some(description);
+    }
+    if let Some(is_active) = request.is_active {
+        webhook.is_active = is_active;
+    }
+    let updated = state.webhook_service.update(&webhook).await?;
+    Ok(Json(updated))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/webhooks/{id}",
+    tag = "webhooks",
+    params(
+        ("id" = Uuid, Path, description = "Webhook ID")
+    ),
+    responses(
+        (status = 204, description = "Webhook deleted with its queued deliveries and delivery log"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Only organization owners and admins may delete webhooks"),
+        (status = 404, description = "Webhook not found")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    load_webhook(&state, &auth, id, true).await?;
+    state.webhook_service.delete(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/webhooks/{id}/deliveries",
+    tag = "webhooks",
+    params(
+        ("id" = Uuid, Path, description = "Webhook ID"),
+        ("status" = Option<String>, Query, description = "pending, delivered or dead"),
+        ("offset" = Option<i64>, Query, description = "Deliveries to skip"),
+        ("limit" = Option<i64>, Query, description = "Deliveries to return, at most 100")
+    ),
+    responses(
+        (status = 200, description = "Delivery log, newest first", body = [WebhookDelivery]),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Webhook not found")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn list_deliveries(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<ListDeliveriesQuery>,
+) -> Result<Json<Vec<WebhookDelivery>>, AppError> {
+    load_webhook(&state, &auth, id, false).await?;
+    let offset = query.offset.unwrap_or(0).max(0);
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+    let deliveries = state
+        .webhook_service
+        .list_deliveries(id, query.status, limit, offset)
+        .await?;
+    Ok(Json(deliveries))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/webhooks/{id}/deliveries/{delivery_id}",
+    tag = "webhooks",
+    params(
+        ("id" = Uuid, Path, description = "Webhook ID"),
+        ("delivery_id" = Uuid, Path, description = "Delivery ID")
+    ),
+    responses(
+        (status = 200, description = "Delivery with every attempt", body = WebhookDeliveryDetail),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Webhook or delivery not found")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn get_delivery(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path((id, delivery_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<WebhookDeliveryDetail>, AppError> {
+    load_webhook(&state, &auth, id, false).await?;
+    let delivery = state
+        .webhook_service
+        .get_delivery(id, delivery_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Delivery {} not found", delivery_id)))?;
+    let attempts = state.webhook_service.list_attempts(delivery_id).await?;
+    Ok(Json(WebhookDeliveryDetail { delivery, attempts }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/webhooks/{id}/deliveries/{delivery_id}/replay",
+    tag = "webhooks",
+    params(
+        ("id" = Uuid, Path, description = "Webhook ID"),
+        ("delivery_id" = Uuid, Path, description = "Delivery to send again")
+    ),
+    responses(
+        (status = 202, description = "Event queued again as a new delivery", body = WebhookDelivery),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Only organization owners and admins may replay deliveries"),
+        (status = 404, description = "Webhook or delivery not found"),
+        (status = 422, description = "Delivery is still pending")
+    ),
+    security(
+        ("jwt" = [])
+    )
+)]
+pub async fn replay_delivery(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path((id, delivery_id)): Path<(Uuid, Uuid)>,
+) -> Result<(StatusCode, Json<WebhookDelivery>), AppError> {
+    load_webhook(&state, &auth, id, true).await?;
+    let delivery = state
+        .webhook_service
+        .get_delivery(id, delivery_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Delivery {} not found", delivery_id)))?;
+    let replayed = state.webhook_service.replay(&delivery).await?;
+    Ok((StatusCode::ACCEPTED, Json(replayed)))
+}
@@ -0,0 +1,38 @@
+use axum::{
+    extract::{State, WebSocketUpgrade},
+    response::Response,
+    Extension,
+};
+
+use crate::{
+    middleware::auth::AuthContext,
+    websocket::{connection_manager, WebSocketHandler},
+    AppState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/ws",
+    tag = "realtime",
+    params(
+        ("token" = Option<String>, Query, description = "Access token or API key, for clients that cannot set the Authorization header")
+    ),
+    responses(
+        (status = 101, description = "Switched to the websocket protocol"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "API key lacks the events:read scope")
+    ),
+    security(
+        ("jwt" = []),
+        ("api_key" = [])
+    )
+)]
+pub async fn connect(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    ws.on_upgrade(move |socket| {
+        WebSocketHandler::new(connection_manager(), state).handle_connection(socket, auth)
+    })
+}
@@ -1,5 +1,4 @@
 use axum::{
-    middleware,
     routing::{get, post},
     Router,
 };
@@ -29,6 +28,13 @@ use config::Config;
 use database::Database;
 use error::AppError;
 use monitoring::MonitoringSystem;
+use services::{
+    AccessService, AlertService, AnalyticsService, AnomalyService, ApiKeyService, AuditService,
+    CarbonService, ComplianceService, DigitalTwinService, EpcisService, EudrService, EventService,
+    FinancialService, LocationService, MfaService, OrganizationService, ProductService,
+    SessionService, SyncService, UsageService, UserService, WalletAuthService, WebhookService,
+};
+use utils::CronService;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -41,6 +47,21 @@ pub struct AppState {
     pub financial_service: Arc<FinancialService>,
     pub analytics_service: Arc<AnalyticsService>,
     pub carbon_service: Arc<CarbonService>,
+    pub digital_twin_service: Arc<DigitalTwinService>,
+    pub anomaly_service: Arc<AnomalyService>,
+    pub location_service: Arc<LocationService>,
+    pub epcis_service: Arc<EpcisService>,
+    pub audit_service: Arc<AuditService>,
+    pub compliance_service: Arc<ComplianceService>,
+    pub eudr_service: Arc<EudrService>,
+    pub access_service: Arc<AccessService>,
+    pub organization_service: Arc<OrganizationService>,
+    pub wallet_auth_service: Arc<WalletAuthService>,
+    pub session_service: Arc<SessionService>,
+    pub mfa_service: Arc<MfaService>,
+    pub webhook_service: Arc<WebhookService>,
+    pub usage_service: Arc<UsageService>,
+    pub alert_service: Arc<AlertService>,
     pub redis_client: redis::Client,
     pub config: Config,
     pub monitoring_system: MonitoringSystem,
@@ -75,9 +96,46 @@ impl AppState {
             config.redis.url.clone(),
         ));
         let carbon_service = Arc::new(CarbonService::new(db.pool().clone()));
+        let digital_twin_service = Arc::new(DigitalTwinService::new(db.pool().clone()));
+        let anomaly_service = Arc::new(AnomalyService::new(db.pool().clone()));
+        let location_service = Arc::new(LocationService::new(db.pool().clone()));
+        let epcis_service = Arc::new(EpcisService::new(db.pool().clone(), event_service.clone()));
+        let audit_service = Arc::new(AuditService::new(db.pool().clone()));
+        let compliance_service = Arc::new(ComplianceService::new(db.pool().clone()));
+        let eudr_service = Arc::new(EudrService::new(db.pool().clone()));
+        let access_service = Arc::new(AccessService::new(db.pool().clone()));
+        let organization_service = Arc::new(OrganizationService::new(db.pool().clone()));
+        let wallet_auth_service =
+            Arc::new(WalletAuthService::new(db.pool().clone(), &config.web_auth)?);
+        let jwt_keys = services::jwt_keys::JwtKeyring::from_config(&config.jwt, &config.jwt_secret)?;
+        let session_service = Arc::new(SessionService::new(
+            db.pool().clone(),
+            jwt_keys,
+            &config.jwt,
+        ));
+        let mfa_service = Arc::new(MfaService::new(
+            db.pool().clone(),
+            config.encryption_key.clone(),
+        ));
+        let webhook_service = Arc::new(WebhookService::new(
+            db.pool().clone(),
+            config.encryption_key.clone(),
+        ));
+        let usage_service = Arc::new(UsageService::new(db.pool().clone(), redis_client.clone()));
         
+        // Alerts are logged, and sent to whichever sinks are configured
+        let alert_router = monitoring::alert_sinks::AlertRouter::from_config(&config.alerting)?;
+        let alert_service = Arc::new(AlertService::from_config(
+            db.pool().clone(),
+            alert_router.clone(),
+            &config.alerting,
+        )?);
+
         // Initialize comprehensive monitoring system
-        let monitoring_system = MonitoringSystem::new();
+        let mut monitoring_system =
+            MonitoringSystem::with_infrastructure_history(config.monitoring.sample_history);
+        monitoring_system.alerter =
+            monitoring::ErrorAlerter::with_router(monitoring::AlertConfig::default(), alert_router);
         
         Ok(Self {
             db,
@@ -89,6 +147,21 @@ impl AppState {
             financial_service,
             analytics_service,
             carbon_service,
+            digital_twin_service,
+            anomaly_service,
+            location_service,
+            epcis_service,
+            audit_service,
+            compliance_service,
+            eudr_service,
+            access_service,
+            organization_service,
+            wallet_auth_service,
+            session_service,
+            mfa_service,
+            webhook_service,
+            usage_service,
+            alert_service,
             redis_client,
             config,
             monitoring_system,
@@ -96,19 +169,68 @@ impl AppState {
     }
 }
 
+/// Backup commands, run against `DATABASE_URL` in place of the server
+async fn run_backup_command(config: &Config, args: &[String]) -> Result<(), AppError> {
+    let db = Database::new(&config.database).await?;
+    let backups = services::BackupService::new(db, config);
+    match (args[0].as_str(), args.get(1)) {
+        ("backup", None) => {
+            let created = backups.create(chrono::Utc::now()).await?;
+            tracing::info!(
+                path = %created.path.display(),
+                tables = created.manifest.tables.len(),
+                rows = created.manifest.rows(),
+                bytes = created.bytes,
+                "Backup written"
+            );
+        }
+        ("verify-backup", Some(dir)) => {
+            let manifest = backups.verify(std::path::Path::new(dir)).await?;
+            tracing::info!(
+                backup = %manifest.id,
+                schema_version = manifest.schema_version,
+                rows = manifest.rows(),
+                "Backup verified"
+            );
+        }
+        ("restore", Some(dir)) => {
+            let manifest = backups.restore(std::path::Path::new(dir)).await?;
+            tracing::info!(
+                backup = %manifest.id,
+                schema_version = manifest.schema_version,
+                rows = manifest.rows(),
+                "Backup restored"
+            );
+        }
+        _ => {
+            return Err(AppError::BadRequest(
+                "usage: backup | verify-backup <dir> | restore <dir>".to_string(),
+            ))
+        }
+    }
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize tracing
+    let config = Config::from_env()?;
+
+    // Initialize logging and trace export; the guard flushes spans on exit
     let log_format = env::var("LOG_FORMAT").unwrap_or_else(|_| "json".to_string());
-    let env_filter = tracing_subscriber::EnvFilter::from_default_env();
-    if log_format.eq_ignore_ascii_case("pretty") {
-        tracing_subscriber::fmt().with_env_filter(env_filter).init();
-    } else {
-        tracing_subscriber::fmt()
-            .with_env_filter(env_filter)
-            .json()
-            .flatten_event(true)
-            .init();
+    let _telemetry = monitoring::telemetry::init(
+        &config.telemetry,
+        !log_format.eq_ignore_ascii_case("pretty"),
+    )?;
+
+    // `backup`, `verify-backup <dir>` and `restore <dir>` run and exit
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        // Returning the error exits non-zero after the telemetry guard flushes
+        if let Err(e) = run_backup_command(&config, &args).await {
+            tracing::error!(command = %args[0], error = %e, "Backup command failed");
+            return Err(e.into());
+        }
+        return Ok(());
     }
 
     // Create application state
@@ -118,6 +240,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cron_service =
         CronService::new(app_state.db.pool().clone(), app_state.redis_client.clone());
     cron_service.start_scheduler().await;
+    // Websocket messages reach subscribers on every instance through Redis
+    websocket::backplane::start(app_state.redis_client.clone());
+    // Sends queued webhook deliveries and retries failed ones
+    app_state.webhook_service.clone().start_worker();
+    // Evaluates alert rules and notifies the configured sinks
+    app_state.alert_service.clone().start();
+    // Takes scheduled backups and prunes them by retention
+    Arc::new(services::BackupService::new(
+        app_state.db.clone(),
+        &app_state.config,
+    ))
+    .start();
+    // Feeds /api/v1/monitoring/infrastructure with process, pool and Redis usage
+    monitoring::sampler::start(
+        app_state.monitoring_system.infrastructure_monitor.clone(),
+        app_state.db.pool().clone(),
+        app_state.redis_client.clone(),
+        websocket::connection_manager(),
+        std::time::Duration::from_secs(app_state.config.monitoring.sample_interval_secs),
+    );
 
     // Build router with security middleware
     let app = Router::new()
@@ -126,23 +268,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .merge(crate::docs::create_swagger_ui())
         .layer(
             ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(middleware::from_fn(
+                .layer(
+                    TraceLayer::new_for_http()
+                        .make_span_with(monitoring::telemetry::request_span)
+                        .on_response(monitoring::telemetry::record_response),
+                )
+                .layer(axum::middleware::from_fn(middleware::metrics::track_metrics))
+                .layer(axum::middleware::from_fn(
                     middleware::error_handler::request_logger,
                 ))
-                .layer(middleware::from_fn_with_state(
+                .layer(axum::middleware::from_fn_with_state(
                     app_state.clone(),
                     middleware::error_handler::global_error_handler,
                 ))
-                .layer(middleware::from_fn_with_state(
+                .layer(axum::middleware::from_fn_with_state(
                     app_state.clone(),
                     middleware::security::enforce_https,
                 ))
-                .layer(middleware::from_fn_with_state(
+                .layer(axum::middleware::from_fn_with_state(
                     app_state.clone(),
                     middleware::security::security_headers,
                 ))
-                .layer(middleware::from_fn_with_state(
+                .layer(axum::middleware::from_fn_with_state(
                     app_state.clone(),
                     middleware::security::cors_policy,
                 )),
@@ -150,7 +297,6 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with_state(app_state.clone());
 
     // Run server
-    let config = Config::from_env()?;
     let addr = SocketAddr::from((
         config.server.host.parse::<std::net::IpAddr>()?,
         config.server.port,
@@ -161,7 +307,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("TLS enabled: {}", config.server.tls_enabled);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    // Peer addresses feed API key IP allowlists
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -2,4 +2,6 @@
 pub mod auth;
 pub mod rate_limit;
 pub mod security;
-pub mod error_handler;
\ No newline at end of file
+pub mod error_handler;
+pub mod audit;
+pub mod metrics;
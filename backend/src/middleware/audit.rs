@@ -0,0 +1,142 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{HeaderMap, Method},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::json;
+
+use crate::{compliance::audit::NewAuditEntry, middleware::auth::AuthContext, AppState};
+
+/// Appends an entry to the hash-chained audit log for every mutating request.
+/// Must be layered inside the auth middleware so the caller is known.
+/// A failed write is logged but never fails the request.
+pub async fn audit_log(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if !is_mutating(request.method()) {
+        return next.run(request).await;
+    }
+
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| path.clone());
+    let user_id = request.extensions().get::<AuthContext>().map(|c| c.user_id);
+    let correlation_id = request.extensions().get::<String>().cloned();
+    let ip_address = client_ip(request.headers());
+    let user_agent = request
+        .headers()
+        .get(axum::http::header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let start = std::time::Instant::now();
+
+    let response = next.run(request).await;
+
+    let (resource_type, resource_id) = resource_from_route(&route, &path);
+    let entry = NewAuditEntry {
+        user_id,
+        action: format!("{} {}", method, route),
+        resource_type,
+        resource_id,
+        changes: Some(json!({
+            "method": method.as_str(),
+            "path": path,
+            "status": response.status().as_u16(),
+            "duration_ms": start.elapsed().as_millis() as u64,
+            "correlation_id": correlation_id,
+        })),
+        ip_address,
+        user_agent,
+    };
+    if let Err(e) = state.audit_service.record(entry).await {
+        tracing::error!(route = %route, "Failed to write audit log entry: {}", e);
+    }
+
+    response
+}
+
+fn is_mutating(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    )
+}
+
+/// First hop of `X-Forwarded-For`, else `X-Real-IP`
+pub(crate) fn client_ip(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("x-forwarded-for")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .or_else(|| headers.get("x-real-ip").and_then(|v| v.to_str().ok()))
+        .map(str::trim)
+        .filter(|ip| ip.parse::<std::net::IpAddr>().is_ok())
+        .map(str::to_string)
+}
+
+/// Splits a matched route into a resource type (the static segments before
+/// the first parameter, without the `/api/v1` and `/admin` prefixes) and the
+/// first parameter's value from the actual path.
+fn resource_from_route(route: &str, path: &str) -> (Option<String>, Option<String>) {
+    let route_segments: Vec<&str> = route.split('/').filter(|s| !s.is_empty()).collect();
+    let path_segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    let mut resource_type = Vec::new();
+    let mut resource_id = None;
+    for (i, segment) in route_segments.iter().enumerate() {
+        if segment.starts_with(':') {
+            resource_id = path_segments.get(i).map(|s| s.to_string());
+            break;
+        }
+        if (i < 2 && matches!(*segment, "api" | "v1")) || (i == 2 && *segment == "admin") {
+            continue;
+        }
+        resource_type.push(*segment);
+    }
+
+    let resource_type = (!resource_type.is_empty()).then(|| resource_type.join("/"));
+    (resource_type, resource_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resource_from_route() {
+        assert_eq!(
+            resource_from_route(
+                "/api/v1/admin/products/:id",
+                "/api/v1/admin/products/PROD-1"
+            ),
+            (Some("products".to_string()), Some("PROD-1".to_string()))
+        );
+        assert_eq!(
+            resource_from_route("/api/v1/keys/:id/revoke", "/api/v1/keys/abc/revoke"),
+            (Some("keys".to_string()), Some("abc".to_string()))
+        );
+        assert_eq!(
+            resource_from_route(
+                "/api/v1/carbon/credits/retire",
+                "/api/v1/carbon/credits/retire"
+            ),
+            (Some("carbon/credits/retire".to_string()), None)
+        );
+    }
+
+    #[test]
+    fn test_client_ip_prefers_forwarded_for() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-real-ip", "10.0.0.2".parse().unwrap());
+        assert_eq!(client_ip(&headers).as_deref(), Some("10.0.0.2"));
+
+        headers.insert("x-forwarded-for", "203.0.113.7, 10.0.0.1".parse().unwrap());
+        assert_eq!(client_ip(&headers).as_deref(), Some("203.0.113.7"));
+
+        headers.insert("x-forwarded-for", "not-an-ip".parse().unwrap());
+        assert_eq!(client_ip(&headers), None);
+    }
+}
@@ -1,51 +1,158 @@
 use axum::{
-    extract::{Request, State},
-    http::{header, StatusCode},
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{header, Method, StatusCode},
     middleware::Next,
     response::Response,
 };
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use tower::ServiceExt;
 
-use crate::{AppState, error::AppError, models::UserRole};
+use crate::{AppState, database::{ApiKeyRepository, UserRepository}, error::AppError, models::UserRole};
+use crate::models::organization::{OrgRole, TenantScope};
+use crate::models::Product;
+use crate::services::api_key_policy::{self, KeyRestrictions};
 use serde::{Deserialize, Serialize};
 
+/// How recently a session must have proved a second factor to use step-up
+/// protected endpoints
+pub const STEP_UP_MAX_AGE_SECS: i64 = 600;
+
+/// Header a JWT session uses to pick which of the user's organizations it acts for
+pub const ORGANIZATION_HEADER: &str = "x-organization-id";
+
 #[derive(Debug, Clone)]
 pub struct AuthContext {
     pub user_id: uuid::Uuid,
     pub api_key_id: Option<uuid::Uuid>,
     pub tier: Option<crate::models::ApiKeyTier>,
+    /// The API key's own requests-per-minute limit
+    pub rate_limit_per_minute: Option<i32>,
+    /// The user's verified Stellar address; `None` until they sign in with
+    /// their wallet
     pub stellar_address: Option<String>,
     pub role: UserRole,
+    /// Organization the request acts for
+    pub organization_id: Option<uuid::Uuid>,
+    /// The caller's role in that organization
+    pub org_role: Option<OrgRole>,
+    /// Login session of a JWT request
+    pub session_id: Option<uuid::Uuid>,
+    /// Whether the user has two-factor authentication turned on
+    pub mfa_enabled: bool,
+    /// When the login session last proved a second factor
+    pub mfa_verified_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Scopes and product limits of the API key the request was made with
+    pub key_restrictions: Option<KeyRestrictions>,
+}
+
+impl AuthContext {
+    /// Data isolation for this caller; administrators and auditors see every
+    /// organization
+    pub fn tenant_scope(&self) -> Option<TenantScope> {
+        match self.role {
+            UserRole::Administrator => None,
+            // Auditors read across organizations; `read_only_auditors` keeps
+            // them from writing anywhere
+            UserRole::Auditor => None,
+            // Users outside any organization only see what was shared with them
+            _ => Some(TenantScope {
+                organization_id: self.organization_id.unwrap_or_else(uuid::Uuid::nil),
+                stellar_address: self.stellar_address.clone(),
+            }),
+        }
+    }
+
+    /// Whether the request's API key is limited to certain products
+    pub fn restricts_products(&self) -> bool {
+        self.key_restrictions
+            .as_ref()
+            .is_some_and(|r| r.restricts_products())
+    }
+
+    /// Whether the request's API key may touch `product`. Always true for
+    /// sessions and unrestricted keys.
+    pub fn key_allows_product(&self, product: &Product) -> bool {
+        self.key_restrictions
+            .as_ref()
+            .is_none_or(|r| r.allows_product(&product.id, &product.category))
+    }
+}
+
+/// Works out the organization a request acts for. An explicitly requested
+/// organization must be one the user belongs to (administrators may act in
+/// any); otherwise the user's first organization is used.
+async fn resolve_organization(
+    state: &AppState,
+    user_id: uuid::Uuid,
+    role: &UserRole,
+    requested: Option<uuid::Uuid>,
+) -> Result<(Option<uuid::Uuid>, Option<OrgRole>), AppError> {
+    match requested {
+        Some(organization_id) => {
+            let org_role = state.organization_service.membership(organization_id, user_id).await?;
+            if org_role.is_none() && !matches!(role, UserRole::Administrator) {
+                return Err(AppError::Forbidden("Not a member of this organization".to_string()));
+            }
+            Ok((Some(organization_id), org_role))
+        }
+        None => Ok(state
+            .organization_service
+            .default_membership(user_id)
+            .await?
+            .map_or((None, None), |(id, role)| (Some(id), Some(role)))),
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String, // User ID
+    pub iat: usize,
     pub exp: usize,
     pub role: UserRole,
+    /// Session the token was issued in; revoking it ends the token early
+    pub sid: uuid::Uuid,
 }
 
+/// Authenticates a bearer access token. Administrators and auditors must have
+/// two-factor authentication turned on to get any further.
 pub async fn jwt_auth(
     State(state): State<AppState>,
     mut request: Request,
     next: Next,
 ) -> Result<Response, AppError> {
-    let auth_header = request
-        .headers()
-        .get(header::AUTHORIZATION)
-        .and_then(|h| h.to_str().ok())
-        .and_then(|h| h.strip_prefix("Bearer "))
-        .ok_or_else(|| AppError::Unauthorized)?;
+    let auth_context = authenticate_session(&state, &request).await?;
+    ensure_mfa_enrolled(&auth_context)?;
 
-    let token_data = jsonwebtoken::decode::<Claims>(
-        auth_header,
-        &jsonwebtoken::DecodingKey::from_secret(state.config.jwt_secret.as_bytes()),
-        &jsonwebtoken::Validation::default(),
-    )
-    .map_err(|_| AppError::Unauthorized)?;
+    request.extensions_mut().insert(auth_context);
+    Ok(next.run(request).await)
+}
+
+fn ensure_mfa_enrolled(auth_context: &AuthContext) -> Result<(), AppError> {
+    if auth_context.role.requires_mfa() && !auth_context.mfa_enabled {
+        return Err(AppError::MfaRequired(
+            "Your role requires two-factor authentication. Enroll at /api/v1/auth/mfa/enroll".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// `jwt_auth` without the two-factor requirement, for the endpoints a user
+/// needs to enroll and manage their sessions
+pub async fn jwt_auth_allow_unenrolled(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let auth_context = authenticate_session(&state, &request).await?;
+    request.extensions_mut().insert(auth_context);
+    Ok(next.run(request).await)
+}
 
-    let user_id = uuid::Uuid::parse_str(&token_data.claims.sub)
+async fn authenticate_session(state: &AppState, request: &Request) -> Result<AuthContext, AppError> {
+    let session = state.session_service.authenticate(bearer_token(request)?).await?;
+
+    let user_id = uuid::Uuid::parse_str(&session.claims.sub)
         .map_err(|_| AppError::Unauthorized)?;
 
     let user = state.user_service.get_user(user_id).await?
@@ -55,31 +162,112 @@ pub async fn jwt_auth(
         return Err(AppError::Unauthorized);
     }
 
-    let auth_context = AuthContext {
+    let requested_org = match request.headers().get(ORGANIZATION_HEADER) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|v| uuid::Uuid::parse_str(v.trim()).ok())
+                .ok_or_else(|| AppError::BadRequest("Invalid X-Organization-Id header".to_string()))?,
+        ),
+        None => None,
+    };
+    let (organization_id, org_role) =
+        resolve_organization(state, user.id, &user.role, requested_org).await?;
+
+    Ok(AuthContext {
         user_id: user.id,
         api_key_id: None,
         tier: None,
-        stellar_address: user.stellar_address,
+        rate_limit_per_minute: None,
+        stellar_address: user.verified_stellar_address(),
         role: user.role,
-    };
+        organization_id,
+        org_role,
+        session_id: Some(session.claims.sid),
+        mfa_enabled: session.mfa_enabled,
+        mfa_verified_at: session.mfa_verified_at,
+        key_restrictions: None,
+    })
+}
 
+/// Authenticates an API key and checks the key may call this endpoint: its
+/// scopes must cover the route, and the request must come from one of its
+/// allowed networks.
+pub async fn api_key_auth(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let auth_context = authenticate_api_key(&state, &request).await?;
     request.extensions_mut().insert(auth_context);
     Ok(next.run(request).await)
 }
 
-pub async fn api_key_auth(
+/// Accepts either a session access token or an API key, for endpoints
+/// machines write to as well as people, such as event capture
+pub async fn jwt_or_api_key_auth(
     State(state): State<AppState>,
     mut request: Request,
     next: Next,
 ) -> Result<Response, AppError> {
-    let auth_header = request
+    let is_api_key = bearer_token(&request)?.starts_with(API_KEY_PREFIX);
+    let auth_context = if is_api_key {
+        authenticate_api_key(&state, &request).await?
+    } else {
+        let auth_context = authenticate_session(&state, &request).await?;
+        ensure_mfa_enrolled(&auth_context)?;
+        auth_context
+    };
+    request.extensions_mut().insert(auth_context);
+    Ok(next.run(request).await)
+}
+
+/// Browsers cannot set headers on a websocket handshake, so the endpoint also
+/// takes the access token or API key as `?token=`. A real Authorization
+/// header wins.
+pub async fn bearer_from_query(mut request: Request, next: Next) -> Response {
+    if !request.headers().contains_key(header::AUTHORIZATION) {
+        let token = request.uri().query().and_then(|query| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("token="))
+                .map(str::to_string)
+        });
+        if let Some(value) = token.and_then(|t| header::HeaderValue::from_str(&format!("Bearer {}", t)).ok()) {
+            request.headers_mut().insert(header::AUTHORIZATION, value);
+        }
+    }
+    next.run(request).await
+}
+
+/// Every generated API key starts with this; access tokens never do
+const API_KEY_PREFIX: &str = "cl_";
+
+fn bearer_token(request: &Request) -> Result<&str, AppError> {
+    request
         .headers()
         .get(header::AUTHORIZATION)
         .and_then(|h| h.to_str().ok())
         .and_then(|h| h.strip_prefix("Bearer "))
-        .ok_or_else(|| AppError::Unauthorized)?;
+        .ok_or_else(|| AppError::Unauthorized)
+}
+
+/// The client's address: the peer, or what a proxy in front of us reports
+pub fn client_ip(request: &Request) -> Option<IpAddr> {
+    let headers = request.headers();
+    api_key_policy::request_ip(
+        request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip()),
+        headers.get("x-forwarded-for").and_then(|v| v.to_str().ok()),
+        headers.get("x-real-ip").and_then(|v| v.to_str().ok()),
+    )
+}
 
-    let key_hash = crate::services::ApiKeyService::hash_api_key(auth_header);
+async fn authenticate_api_key(state: &AppState, request: &Request) -> Result<AuthContext, AppError> {
+    let key_hash = crate::services::ApiKeyService::hash_api_key(bearer_token(request)?);
 
     let api_key = state
         .api_key_service
@@ -97,6 +285,28 @@ pub async fn api_key_auth(
         }
     }
 
+    if let Some(allowed_ips) = &api_key.allowed_ips {
+        let ip = client_ip(request);
+        if !ip.is_some_and(|ip| api_key_policy::ip_allowed(allowed_ips, ip)) {
+            tracing::warn!(key_id = %api_key.id, ?ip, "API key used from a network outside its allowlist");
+            return Err(AppError::Forbidden("API key is not allowed from this address".to_string()));
+        }
+    }
+
+    let restrictions = KeyRestrictions {
+        scopes: api_key.scopes.clone(),
+        product_ids: api_key.allowed_product_ids.clone(),
+        categories: api_key.allowed_categories.clone(),
+    };
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str())
+        .unwrap_or_default();
+    restrictions
+        .check_route(request.method().as_str(), route)
+        .map_err(AppError::Forbidden)?;
+
     let user = state
         .user_service
         .get_user(api_key.user_id)
@@ -107,18 +317,31 @@ pub async fn api_key_auth(
         return Err(AppError::Unauthorized);
     }
 
+    // A key keeps acting for its organization only while its user belongs to it
+    let (organization_id, org_role) =
+        resolve_organization(state, user.id, &user.role, api_key.organization_id)
+            .await
+            .map_err(|e| match e {
+                AppError::Forbidden(_) => AppError::Unauthorized,
+                other => other,
+            })?;
+
     let _ = state.api_key_service.update_last_used(api_key.id).await;
 
-    let auth_context = AuthContext {
+    Ok(AuthContext {
         user_id: user.id,
         api_key_id: Some(api_key.id),
         tier: Some(api_key.tier),
-        stellar_address: user.stellar_address,
+        rate_limit_per_minute: Some(api_key.rate_limit_per_minute),
+        stellar_address: user.verified_stellar_address(),
         role: user.role,
-    };
-
-    request.extensions_mut().insert(auth_context);
-    Ok(next.run(request).await)
+        organization_id,
+        org_role,
+        session_id: None,
+        mfa_enabled: false,
+        mfa_verified_at: None,
+        key_restrictions: Some(restrictions),
+    })
 }
 
 pub fn require_role(roles: Vec<UserRole>) -> impl Fn(Request, Next) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response, AppError>> + Send>> + Clone {
@@ -154,6 +377,44 @@ pub async fn require_admin(
     }
 }
 
+/// Guards sensitive endpoints such as API key rotation. Users with two-factor
+/// authentication (and every role that requires it) must have proved a second
+/// factor in this session within the last `STEP_UP_MAX_AGE_SECS`; API keys
+/// cannot answer the challenge, so they are turned away.
+pub async fn require_step_up(
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let auth_context = get_auth_context(&request)?;
+    if auth_context.session_id.is_none() {
+        return Err(AppError::Forbidden("This operation requires an interactive login".to_string()));
+    }
+
+    if auth_context.mfa_enabled || auth_context.role.requires_mfa() {
+        let cutoff = chrono::Utc::now() - chrono::Duration::seconds(STEP_UP_MAX_AGE_SECS);
+        if !auth_context.mfa_verified_at.is_some_and(|at| at >= cutoff) {
+            return Err(AppError::MfaRequired(
+                "Confirm a two-factor code at /api/v1/auth/mfa/verify to continue".to_string(),
+            ));
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+/// Auditors may read everything but change nothing
+pub async fn read_only_auditors(
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let auth_context = get_auth_context(&request)?;
+    let is_read = matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+
+    if matches!(auth_context.role, UserRole::Auditor) && !is_read {
+        return Err(AppError::Forbidden("Auditors have read-only access".to_string()));
+    }
+    Ok(next.run(request).await)
+}
+
 pub fn get_auth_context(request: &Request) -> Result<&AuthContext, AppError> {
     request
         .extensions()
@@ -1,7 +1,7 @@
 use axum::{
     body::Body,
     extract::Request,
-    http::StatusCode,
+    http::{StatusCode, Uri},
     middleware::Next,
     response::{IntoResponse, Response},
     Json,
@@ -76,6 +76,23 @@ pub fn handle_panic(err: Box<dyn std::any::Any + Send + 'static>) -> Response {
     (StatusCode::INTERNAL_SERVER_ERROR, Json(response)).into_response()
 }
 
+/// `uri` with the value of any `token` query parameter (websocket clients
+/// authenticate that way) replaced, so credentials never reach the logs
+fn redacted_uri(uri: &Uri) -> String {
+    let Some(query) = uri.query() else {
+        return uri.to_string();
+    };
+    let query = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some(("token", _)) => "token=REDACTED",
+            _ => pair,
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{}?{}", uri.path(), query)
+}
+
 /// Request logging middleware with correlation ID
 pub async fn request_logger(
     request: Request,
@@ -88,7 +105,7 @@ pub async fn request_logger(
         .unwrap_or_else(|| Uuid::new_v4().to_string());
     
     let method = request.method().clone();
-    let uri = request.uri().clone();
+    let uri = redacted_uri(request.uri());
     let start = std::time::Instant::now();
     
     tracing::info!(
@@ -159,6 +176,16 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
     }
 
+    #[test]
+    fn test_redacted_uri_hides_tokens() {
+        let uri: Uri = "/api/v1/ws?channel=alerts&token=eyJhbGciOi.secret&x=1".parse().unwrap();
+        assert_eq!(redacted_uri(&uri), "/api/v1/ws?channel=alerts&token=REDACTED&x=1");
+
+        let uri: Uri = "/api/v1/products?page=2".parse().unwrap();
+        assert_eq!(redacted_uri(&uri), "/api/v1/products?page=2");
+        assert_eq!(redacted_uri(&"/health".parse().unwrap()), "/health");
+    }
+
     #[tokio::test]
     async fn test_request_logger_error() {
         let app = Router::new()
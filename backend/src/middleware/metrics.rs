@@ -0,0 +1,35 @@
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::error::ErrorCode;
+use crate::monitoring::metrics::{METRICS, UNMATCHED_ROUTE};
+
+/// Records every request's latency by route pattern and status, and counts
+/// error responses by their error code. Must be layered on the router, not
+/// outside it, so the matched route is known.
+pub async fn track_metrics(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().clone();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| UNMATCHED_ROUTE.to_string(), |p| p.as_str().to_string());
+
+    let response = next.run(request).await;
+
+    METRICS.observe_request(
+        method.as_str(),
+        &route,
+        response.status().as_u16(),
+        start.elapsed().as_secs_f64(),
+    );
+    if let Some(code) = response.extensions().get::<ErrorCode>() {
+        METRICS.count_error(*code);
+    }
+    response
+}
@@ -1,95 +1,460 @@
+/// Request rate limiting with token buckets.
+///
+/// Every caller has a bucket holding a few seconds' worth of its per-minute
+/// limit, refilled continuously. A request takes as many tokens as its route
+/// costs, so exports and simulations use up more than simple reads, and the
+/// small bucket keeps bursts at window edges from doubling the limit. A route
+/// costing more than the bucket holds may fill it up to its cost, so on small
+/// limits it is still paid in full, by waiting for the refill. Buckets
+/// live in Redis and are updated by one Lua script, so every instance shares
+/// them; while Redis is unreachable each instance falls back to buckets of
+/// its own.
+///
+/// Requests made with an API key that get past their bucket are also metered
+/// against the key's monthly quota, at the same cost (see `usage_metering`).
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use axum::{
-    extract::{Request, State},
+    extract::{MatchedPath, Request, State},
+    http::HeaderValue,
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
-use redis::AsyncCommands;
-use std::sync::Arc;
-use chrono::{Utc, Timelike};
+use tracing::Instrument;
+
+use crate::middleware::auth::{client_ip, AuthContext};
+use crate::monitoring::telemetry;
+use crate::{error::AppError, AppState};
+
+/// Lua token bucket. Refills by elapsed time on Redis' own clock, so
+/// instances with drifting clocks agree, then takes `cost` tokens if there
+/// are enough. Returns `{allowed, tokens left, ms until cost is available}`.
+const TOKEN_BUCKET_SCRIPT: &str = r#"
+local capacity = tonumber(ARGV[1])
+local rate = tonumber(ARGV[2])
+local cost = tonumber(ARGV[3])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+local state = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(state[1]) or capacity
+local ts = tonumber(state[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * rate)
+
+local allowed = 0
+local wait = 0
+if tokens >= cost then
+    tokens = tokens - cost
+    allowed = 1
+else
+    wait = math.ceil((cost - tokens) / rate)
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / rate) + 1000)
+return {allowed, math.floor(tokens), wait}
+"#;
+
+/// What requests to unknown paths are metered as
+const UNMATCHED_ROUTE: &str = "(unmatched)";
+/// How long to wait on Redis before using the local buckets
+const REDIS_TIMEOUT: Duration = Duration::from_millis(250);
+/// Local buckets kept before full ones are dropped
+const LOCAL_BUCKET_LIMIT: usize = 10_000;
+
+/// Tokens a request costs by route; anything else costs 1
+const ROUTE_COSTS: &[(&str, &str, u32)] = &[
+    // Exports and reports
+    ("GET", "/api/v1/epcis/products/:product_id", 10),
+    ("GET", "/api/v1/epcis/events", 10),
+    ("GET", "/api/v1/analytics/export", 20),
+    ("GET", "/api/v1/compliance/eudr/:product_id/statement", 10),
+    ("GET", "/api/v1/audit/report", 10),
+    ("GET", "/api/v1/audit/verify", 20),
+    ("POST", "/api/v1/carbon/reports", 10),
+    // Bulk writes and re-evaluation
+    ("POST", "/api/v1/admin/epcis/import", 10),
+    ("POST", "/api/v1/compliance/report/:product_id/evaluate", 5),
+    // Simulations and predictions
+    ("POST", "/api/v1/digital-twins/simulations", 5),
+    ("POST", "/api/v1/digital-twins/simulations/:id/run", 25),
+    ("POST", "/api/v1/digital-twins/predictions", 10),
+];
+
+lazy_static::lazy_static! {
+    static ref LOCAL_BUCKETS: Mutex<LocalBuckets> = Mutex::new(LocalBuckets::default());
+}
+
+/// Set while Redis is unreachable, so the fallback is logged once
+static DEGRADED: AtomicBool = AtomicBool::new(false);
+
+// ── Limits ────────────────────────────────────────────────────────────────────
+
+/// A caller's limit: `per_minute` tokens a minute, at most `burst_secs`
+/// seconds' worth at once
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limit {
+    pub per_minute: u32,
+    pub burst_secs: u32,
+}
+
+impl Limit {
+    /// Bucket size, at least one token
+    pub fn capacity(&self) -> f64 {
+        let burst = self.per_minute as f64 * self.burst_secs as f64 / 60.0;
+        burst.max(1.0)
+    }
+
+    /// Bucket size as seen by a request costing `cost`: large enough to ever
+    /// pay for it, but cheaper requests cannot burst past `capacity`
+    pub fn capacity_for(&self, cost: u32) -> f64 {
+        self.capacity().max(cost as f64)
+    }
 
-use crate::{AppState, error::AppError, models::ApiKeyTier};
+    /// Tokens added per millisecond
+    pub fn rate_per_ms(&self) -> f64 {
+        self.per_minute.max(1) as f64 / 60_000.0
+    }
+}
+
+/// What a request costs against its caller's bucket
+pub fn route_cost(method: &str, route: &str) -> u32 {
+    ROUTE_COSTS
+        .iter()
+        .find(|(m, r, _)| *m == method && *r == route)
+        .map_or(1, |(_, _, cost)| *cost)
+}
+
+/// Outcome of taking tokens from a bucket
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    /// Whole tokens left afterwards
+    pub remaining: u64,
+    /// How long until the request would be allowed, when it is not
+    pub retry_after_ms: u64,
+}
+
+// ── Local fallback ────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy)]
+struct LocalBucket {
+    tokens: f64,
+    updated_ms: u64,
+}
 
+/// Per-instance buckets, used while Redis is down. Same arithmetic as the
+/// Lua script.
+#[derive(Debug, Default)]
+pub struct LocalBuckets {
+    buckets: HashMap<String, LocalBucket>,
+}
+
+impl LocalBuckets {
+    pub fn take(&mut self, key: &str, limit: Limit, cost: u32, now_ms: u64) -> Decision {
+        let capacity = limit.capacity_for(cost);
+        let rate = limit.rate_per_ms();
+        if self.buckets.len() >= LOCAL_BUCKET_LIMIT && !self.buckets.contains_key(key) {
+            // Buckets that have refilled are the same as no bucket
+            self.buckets.retain(|_, b| {
+                b.tokens + now_ms.saturating_sub(b.updated_ms) as f64 * rate < capacity
+            });
+        }
+        let bucket = self.buckets.entry(key.to_string()).or_insert(LocalBucket {
+            tokens: capacity,
+            updated_ms: now_ms,
+        });
+        let elapsed = now_ms.saturating_sub(bucket.updated_ms) as f64;
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.updated_ms = now_ms.max(bucket.updated_ms);
+
+        let cost = cost as f64;
+        if bucket.tokens >= cost {
+            bucket.tokens -= cost;
+            Decision {
+                allowed: true,
+                remaining: bucket.tokens as u64,
+                retry_after_ms: 0,
+            }
+        } else {
+            Decision {
+                allowed: false,
+                remaining: bucket.tokens as u64,
+                retry_after_ms: ((cost - bucket.tokens) / rate).ceil() as u64,
+            }
+        }
+    }
+}
+
+// ── Buckets ───────────────────────────────────────────────────────────────────
+
+/// Takes `cost` tokens from the bucket under `key`, in Redis when it answers
+/// in time and locally otherwise
+async fn take(client: &redis::Client, key: &str, limit: Limit, cost: u32) -> Decision {
+    let cost = cost.max(1);
+    let shared = take_shared(client, key, limit, cost).instrument(telemetry::redis_span("EVALSHA"));
+    match tokio::time::timeout(REDIS_TIMEOUT, shared).await {
+        Ok(Ok(decision)) => {
+            if DEGRADED.swap(false, Ordering::Relaxed) {
+                tracing::info!("Rate limiting is using Redis again");
+            }
+            decision
+        }
+        failed => {
+            if !DEGRADED.swap(true, Ordering::Relaxed) {
+                let reason = match failed {
+                    Ok(Err(e)) => e.to_string(),
+                    _ => "timed out".to_string(),
+                };
+                tracing::warn!(
+                    "Rate limiting falls back to per-instance buckets: {}",
+                    reason
+                );
+            }
+            let now_ms = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64;
+            LOCAL_BUCKETS
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .take(key, limit, cost, now_ms)
+        }
+    }
+}
+
+async fn take_shared(
+    client: &redis::Client,
+    key: &str,
+    limit: Limit,
+    cost: u32,
+) -> redis::RedisResult<Decision> {
+    lazy_static::lazy_static! {
+        static ref SCRIPT: redis::Script = redis::Script::new(TOKEN_BUCKET_SCRIPT);
+    }
+    let mut conn = client.get_multiplexed_tokio_connection().await?;
+    let (allowed, remaining, retry_after_ms): (i64, i64, i64) = SCRIPT
+        .key(key)
+        .arg(limit.capacity_for(cost))
+        .arg(limit.rate_per_ms())
+        .arg(cost)
+        .invoke_async(&mut conn)
+        .await?;
+    Ok(Decision {
+        allowed: allowed == 1,
+        remaining: remaining.max(0) as u64,
+        retry_after_ms: retry_after_ms.max(0) as u64,
+    })
+}
+
+// ── Middleware ────────────────────────────────────────────────────────────────
+
+/// Who a request counts against and their limit: API keys by their tier or
+/// own lower limit, signed-in users by the user limit, and anyone else by
+/// client address
+fn bucket_for(state: &AppState, auth: Option<&AuthContext>, ip: Option<IpAddr>) -> (String, u32) {
+    let limits = &state.config.rate_limit;
+    match auth {
+        Some(AuthContext {
+            api_key_id: Some(key_id),
+            tier,
+            rate_limit_per_minute,
+            ..
+        }) => {
+            let tier_limit = tier
+                .as_ref()
+                .map_or(limits.user_per_minute, |t| t.requests_per_minute());
+            let per_minute = match rate_limit_per_minute {
+                Some(own) if *own > 0 && (*own as u32) < tier_limit => *own as u32,
+                _ => tier_limit,
+            };
+            (format!("ratelimit:key:{}", key_id), per_minute)
+        }
+        Some(auth) => (
+            format!("ratelimit:user:{}", auth.user_id),
+            limits.user_per_minute,
+        ),
+        None => (
+            format!(
+                "ratelimit:ip:{}",
+                ip.map_or_else(|| "unknown".to_string(), |ip| ip.to_string())
+            ),
+            limits.anonymous_per_minute,
+        ),
+    }
+}
+
+/// Limits requests per caller. Runs after authentication where a route has
+/// it; on public routes callers are told apart by address.
 pub async fn rate_limit_middleware(
     State(state): State<AppState>,
     request: Request,
     next: Next,
-) -> Result<Response, AppError> {
-    // Get auth context from request extensions
-    let auth_context = request
+) -> Response {
+    let (key, per_minute) = bucket_for(
+        &state,
+        request.extensions().get::<AuthContext>(),
+        client_ip(&request),
+    );
+    let limit = Limit {
+        per_minute,
+        burst_secs: state.config.rate_limit.burst_secs,
+    };
+    let method = request.method().as_str().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string());
+    let cost = route
+        .as_deref()
+        .map_or(1, |route| route_cost(&method, route));
+    let decision = take(&state.redis_client, &key, limit, cost).await;
+
+    // API key, tier and organization of keyed requests, which are metered
+    let api_key = request
         .extensions()
-        .get::<crate::middleware::auth::AuthContext>()
-        .ok_or_else(|| AppError::Unauthorized)?;
-
-    // Get API key details to check rate limit
-    let api_key = state
-        .api_key_service
-        .get_api_key(auth_context.api_key_id)
-        .await?
-        .ok_or_else(|| AppError::Unauthorized)?;
-
-    // Check rate limit based on tier
-    let tier_limit = match api_key.tier {
-        ApiKeyTier::Basic => 60,
-        ApiKeyTier::Standard => 300,
-        ApiKeyTier::Premium => 1000,
-        ApiKeyTier::Enterprise => 5000,
+        .get::<AuthContext>()
+        .and_then(|auth| Some((auth.api_key_id?, auth.tier.clone()?, auth.organization_id)));
+    let metered = match api_key {
+        Some((key_id, tier, organization_id)) if decision.allowed => {
+            state
+                .usage_service
+                .meter(
+                    key_id,
+                    &tier,
+                    organization_id,
+                    &method,
+                    route.as_deref().unwrap_or(UNMATCHED_ROUTE),
+                    cost,
+                )
+                .await
+        }
+        _ => None,
     };
 
-    // Use the custom rate limit if it's set and lower than the tier limit
-    let effective_limit = if api_key.rate_limit_per_minute > 0 && api_key.rate_limit_per_minute < tier_limit {
-        api_key.rate_limit_per_minute
+    let mut response = if !decision.allowed {
+        let mut response = AppError::RateLimit.into_response();
+        let retry_after = decision.retry_after_ms.div_ceil(1000).max(1);
+        response
+            .headers_mut()
+            .insert("Retry-After", HeaderValue::from(retry_after));
+        response
+    } else if metered.is_some_and(|m| !m.allowed) {
+        AppError::QuotaExceeded.into_response()
     } else {
-        tier_limit
+        next.run(request).await
     };
+    let headers = response.headers_mut();
+    if let Some(metered) = metered {
+        headers.insert("X-Quota-Limit", HeaderValue::from(metered.quota));
+        headers.insert(
+            "X-Quota-Remaining",
+            HeaderValue::from(metered.quota.saturating_sub(metered.used)),
+        );
+    }
+    headers.insert("X-RateLimit-Limit", HeaderValue::from(per_minute));
+    headers.insert(
+        "X-RateLimit-Remaining",
+        HeaderValue::from(decision.remaining),
+    );
+    headers.insert("X-RateLimit-Cost", HeaderValue::from(cost));
+    response
+}
 
-    // Redis rate limiting logic (Fixed Window)
-    let mut conn = state.redis_client.get_multiplexed_tokio_connection().await
-        .map_err(|e| {
-            tracing::error!("Redis connection error: {}", e);
-            AppError::Internal(e.to_string())
-        })?;
-
-    let now = Utc::now();
-    let window_key = format!("ratelimit:{}:{}", auth_context.api_key_id, now.format("%Y%m%d%H%M"));
-    
-    let count: u32 = conn.incr(&window_key, 1).await
-        .map_err(|e| {
-            tracing::error!("Redis INCR error: {}", e);
-            AppError::Internal(e.to_string())
-        })?;
-
-    if count == 1 {
-        let _: () = conn.expire(&window_key, 60).await
-            .map_err(|e| {
-                tracing::error!("Redis EXPIRE error: {}", e);
-                AppError::Internal(e.to_string())
-            })?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Limit = Limit {
+        per_minute: 60,
+        burst_secs: 10,
+    };
+
+    #[test]
+    fn weighs_expensive_routes() {
+        assert_eq!(route_cost("GET", "/api/v1/products"), 1);
+        assert_eq!(route_cost("GET", "/api/v1/analytics/export"), 20);
+        assert_eq!(
+            route_cost("POST", "/api/v1/digital-twins/simulations/:id/run"),
+            25
+        );
+        assert_eq!(
+            route_cost("GET", "/api/v1/digital-twins/simulations/:id"),
+            1
+        );
+        assert_eq!(route_cost("POST", "/api/v1/epcis/events"), 1);
     }
 
-    if count > effective_limit {
-        return Err(AppError::RateLimit);
+    #[test]
+    fn bursts_are_bounded_and_refill_continuously() {
+        assert_eq!(MINUTE.capacity(), 10.0);
+        let mut buckets = LocalBuckets::default();
+        for _ in 0..10 {
+            assert!(buckets.take("k", MINUTE, 1, 0).allowed);
+        }
+        let denied = buckets.take("k", MINUTE, 1, 0);
+        assert!(!denied.allowed);
+        assert_eq!(denied.retry_after_ms, 1000);
+
+        // One token a second
+        assert!(buckets.take("k", MINUTE, 1, 1000).allowed);
+        assert!(!buckets.take("k", MINUTE, 1, 1500).allowed);
+
+        // A full minute later the bucket is full, not overfull
+        let refilled = buckets.take("k", MINUTE, 1, 61_000);
+        assert!(refilled.allowed);
+        assert_eq!(refilled.remaining, 9);
+
+        // Other callers have their own bucket
+        assert!(buckets.take("other", MINUTE, 1, 0).allowed);
     }
 
-    // Add rate limit headers to response
-    let response = next.run(request).await;
-    
-    let (mut parts, body) = response.into_parts();
-    parts.headers.insert(
-        "X-RateLimit-Limit",
-        effective_limit.to_string().parse().unwrap(),
-    );
-    parts.headers.insert(
-        "X-RateLimit-Remaining",
-        effective_limit.saturating_sub(count).to_string().parse().unwrap(),
-    );
-    parts.headers.insert(
-        "X-RateLimit-Reset",
-        ((now + chrono::Duration::minutes(1)).with_second(0).unwrap().with_nanosecond(0).unwrap())
-            .timestamp()
-            .to_string()
-            .parse()
-            .unwrap(),
-    );
+    #[test]
+    fn at_most_limit_plus_burst_per_minute() {
+        let mut buckets = LocalBuckets::default();
+        let allowed = (0..=60_000u64)
+            .step_by(100)
+            .filter(|now| buckets.take("k", MINUTE, 1, *now).allowed)
+            .count() as u32;
+        assert!(allowed <= MINUTE.per_minute + MINUTE.capacity() as u32);
+        assert!(allowed >= MINUTE.per_minute);
+    }
+
+    #[test]
+    fn costly_requests_take_more_tokens() {
+        let mut buckets = LocalBuckets::default();
+        let export = buckets.take("k", MINUTE, 10, 0);
+        assert!(export.allowed);
+        assert_eq!(export.remaining, 0);
+        let next = buckets.take("k", MINUTE, 10, 5_000);
+        assert!(!next.allowed);
+        assert_eq!(next.retry_after_ms, 5_000);
+    }
+
+    #[test]
+    fn costs_above_the_bucket_are_paid_in_full() {
+        // A tenth of a token a second, and a bucket of under two tokens
+        let small = Limit {
+            per_minute: 6,
+            burst_secs: 10,
+        };
+        assert_eq!(small.capacity(), 1.0);
+        let mut buckets = LocalBuckets::default();
 
-    Ok(Response::from_parts(parts, body))
+        assert!(buckets.take("k", small, 25, 0).allowed);
+        // The next run waits for all 25 tokens, not for a bucketful
+        let next = buckets.take("k", small, 25, 0);
+        assert!(!next.allowed);
+        assert_eq!(next.retry_after_ms, 250_000);
+        assert!(!buckets.take("k", small, 25, 249_999).allowed);
+        assert!(buckets.take("k", small, 25, 250_000).allowed);
+
+        // Cheap requests still burst no further than the bucket
+        let mut buckets = LocalBuckets::default();
+        assert!(buckets.take("k", small, 1, 600_000).allowed);
+        assert!(!buckets.take("k", small, 1, 600_000).allowed);
+    }
 }
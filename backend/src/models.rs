@@ -7,6 +7,12 @@ use utoipa::ToSchema;
 pub mod analytics;
 pub mod carbon;
 pub mod digital_twin;
+pub mod location;
+pub mod epcis;
+pub mod compliance;
+pub mod eudr;
+pub mod access;
+pub mod organization;
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Product {
@@ -25,6 +31,8 @@ pub struct Product {
     pub updated_at: DateTime<Utc>,
     pub created_by: String,
     pub updated_by: String,
+    /// Organization holding `owner_address`, if any
+    pub organization_id: Option<Uuid>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
@@ -53,6 +61,13 @@ pub enum UserRole {
     Auditor,
 }
 
+impl UserRole {
+    /// Roles that must use two-factor authentication
+    pub fn requires_mfa(&self) -> bool {
+        matches!(self, UserRole::Administrator | UserRole::Auditor)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct User {
     pub id: Uuid,
@@ -66,6 +81,64 @@ pub struct User {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub last_login_at: Option<DateTime<Utc>>,
+    /// When the user last proved control of `stellar_address` by signing in
+    /// with their wallet
+    pub stellar_address_verified_at: Option<DateTime<Utc>>,
+}
+
+impl User {
+    /// `stellar_address` once the user proved control of it; an address that
+    /// was only typed in at registration confers no ownership
+    pub fn verified_stellar_address(&self) -> Option<String> {
+        self.stellar_address_verified_at
+            .and(self.stellar_address.clone())
+    }
+}
+
+/// A login session. Each refresh extends it with a new refresh token until
+/// `expires_at`; revoking it ends its access tokens too.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct AuthSession {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// When the session last proved a second factor
+    pub mfa_verified_at: Option<DateTime<Utc>>,
+}
+
+/// A SEP-10 challenge transaction for the wallet to sign
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct WalletChallenge {
+    pub stellar_address: String,
+    /// Base64 XDR transaction envelope, signed by the server
+    pub transaction: String,
+    /// Network the transaction is to be signed for
+    pub network_passphrase: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Two-factor authentication state of a user
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MfaStatus {
+    pub enabled: bool,
+    pub enabled_at: Option<DateTime<Utc>>,
+    /// Whether the user's role may not turn two-factor authentication off
+    pub required: bool,
+    pub recovery_codes_remaining: i64,
+}
+
+/// A TOTP secret waiting to be confirmed with a first code
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MfaEnrollment {
+    /// Base32 secret for manual entry
+    pub secret: String,
+    /// `otpauth://` URI to show as a QR code
+    pub otpauth_uri: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
@@ -80,6 +153,16 @@ pub struct ApiKey {
     pub expires_at: Option<DateTime<Utc>>,
     pub last_used_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
+    /// Organization the key acts for
+    pub organization_id: Option<Uuid>,
+    /// What the key may do, e.g. `events:write`
+    pub scopes: Vec<String>,
+    /// Only these products, when set
+    pub allowed_product_ids: Option<Vec<String>>,
+    /// Only products in these categories, when set
+    pub allowed_categories: Option<Vec<String>>,
+    /// IP addresses and CIDR blocks the key may be used from, when set
+    pub allowed_ips: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type, ToSchema)]
@@ -91,16 +174,186 @@ pub enum ApiKeyTier {
     Enterprise,
 }
 
+impl ApiKeyTier {
+    /// Requests a key of this tier may make per minute, unless the key has a
+    /// lower limit of its own
+    pub fn requests_per_minute(&self) -> u32 {
+        match self {
+            ApiKeyTier::Basic => 60,
+            ApiKeyTier::Standard => 300,
+            ApiKeyTier::Premium => 1000,
+            ApiKeyTier::Enterprise => 5000,
+        }
+    }
+
+    /// Units a key of this tier may use per calendar month. Requests cost
+    /// what they cost against the rate limit, mostly one unit.
+    pub fn monthly_quota(&self) -> u64 {
+        match self {
+            ApiKeyTier::Basic => 100_000,
+            ApiKeyTier::Standard => 1_000_000,
+            ApiKeyTier::Premium => 10_000_000,
+            ApiKeyTier::Enterprise => 100_000_000,
+        }
+    }
+}
+
+/// An API key's use of one endpoint over a period
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct EndpointUsage {
+    pub method: String,
+    /// Route pattern, e.g. `/api/v1/products/:id`
+    pub route: String,
+    pub requests: i64,
+    /// Quota units used; costly routes take more than one per request
+    pub units: i64,
+}
+
+/// An API key's use in one hour
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct HourlyUsage {
+    pub hour: DateTime<Utc>,
+    pub requests: i64,
+    pub units: i64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Webhook {
     pub id: Uuid,
+    /// Who created the webhook
     pub user_id: Uuid,
     pub url: String,
+    /// Encrypted signing secret; only shown when the webhook is created
+    #[serde(skip_serializing)]
     pub secret: String,
+    /// Event types delivered; empty means all
     pub events: Vec<String>,
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Organization whose products the webhook hears about
+    pub organization_id: Option<Uuid>,
+    /// Only these products, when set
+    pub product_ids: Option<Vec<String>>,
+    pub description: Option<String>,
+}
+
+/// Where a webhook delivery stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookDeliveryStatus {
+    /// Waiting for its next attempt
+    Pending,
+    /// The subscriber answered with a 2xx status
+    Delivered,
+    /// Given up on after too many failures; can be replayed
+    Dead,
+}
+
+/// One event queued for one webhook
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    /// Same for every delivery and replay of one event
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub product_id: Option<String>,
+    /// The body sent to the subscriber
+    pub payload: serde_json::Value,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    /// The delivery this one re-sends
+    pub replay_of: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// One try at a delivery
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct WebhookDeliveryAttempt {
+    pub attempt: i32,
+    /// None when no response came back
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+    pub attempted_at: DateTime<Utc>,
+}
+
+/// How urgent an alert is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AlertSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl AlertSeverity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertSeverity::Info => "info",
+            AlertSeverity::Warning => "warning",
+            AlertSeverity::Critical => "critical",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AlertStatus {
+    Firing,
+    Resolved,
+}
+
+impl AlertStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertStatus::Firing => "firing",
+            AlertStatus::Resolved => "resolved",
+        }
+    }
+}
+
+/// The last known state of one alert: a rule firing for one subject
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Alert {
+    /// `rule:subject`; notifications for the same alert share it
+    pub fingerprint: String,
+    pub rule: String,
+    /// What the alert is about, e.g. an instance or an oracle feed; empty for
+    /// system-wide rules
+    pub subject: String,
+    pub severity: AlertSeverity,
+    pub status: AlertStatus,
+    pub summary: String,
+    pub started_at: DateTime<Utc>,
+    /// None until a notification about the current firing reached a sink
+    pub last_notified_at: Option<DateTime<Utc>>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Holds back notifications for matching alerts for a while. Alerts still
+/// fire and are recorded.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct AlertSilence {
+    pub id: Uuid,
+    /// None silences every rule
+    pub rule: Option<String>,
+    /// None silences every subject
+    pub subject: Option<String>,
+    pub reason: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
@@ -155,12 +408,31 @@ pub struct NewApiKey {
     pub tier: ApiKeyTier,
     pub rate_limit_per_minute: i32,
     pub expires_at: Option<DateTime<Utc>>,
+    pub organization_id: Option<Uuid>,
+    pub scopes: Vec<String>,
+    pub allowed_product_ids: Option<Vec<String>>,
+    pub allowed_categories: Option<Vec<String>>,
+    pub allowed_ips: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct NewWebhook {
     pub user_id: Uuid,
+    pub organization_id: Uuid,
     pub url: String,
+    /// Plaintext; stored encrypted
     pub secret: String,
     pub events: Vec<String>,
-}
\ No newline at end of file
+    pub product_ids: Option<Vec<String>>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NewAlertSilence {
+    pub rule: Option<String>,
+    pub subject: Option<String>,
+    pub reason: String,
+    /// Defaults to now
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: DateTime<Utc>,
+}
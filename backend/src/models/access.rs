@@ -0,0 +1,45 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// What a grant lets a non-owner do with a product
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ProductPermission {
+    /// Record tracking events; the off-chain copy of an on-chain
+    /// `AuthorizationContract::add_authorized_actor` grant
+    AddEvents,
+    /// Record events and edit the product (delegated org members)
+    Edit,
+}
+
+/// Where a grant came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum GrantSource {
+    /// Granted through the API by the owner or an administrator
+    Api,
+    /// Mirrored from the on-chain `AuthorizationContract`
+    Chain,
+}
+
+/// A Stellar address allowed to act on a product it does not own
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct ProductAccessGrant {
+    pub product_id: String,
+    pub actor_address: String,
+    pub permission: ProductPermission,
+    pub source: GrantSource,
+    pub granted_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct GrantProductAccessRequest {
+    pub actor_address: String,
+    pub permission: ProductPermission,
+}
@@ -1,5 +1,7 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
 
 // --- Dashboard Analytics ---
 
@@ -162,3 +164,50 @@ impl CacheKey {
         format!("analytics:product:{}", product_id)
     }
 }
+
+// --- Anomalies ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyType {
+    ImpossibleTravel,
+    UnusualActor,
+    OutOfOrderTimestamp,
+    DuplicateDataHash,
+    EventBurst,
+}
+
+impl AnomalyType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnomalyType::ImpossibleTravel => "impossible_travel",
+            AnomalyType::UnusualActor => "unusual_actor",
+            AnomalyType::OutOfOrderTimestamp => "out_of_order_timestamp",
+            AnomalyType::DuplicateDataHash => "duplicate_data_hash",
+            AnomalyType::EventBurst => "event_burst",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct EventAnomaly {
+    pub id: Uuid,
+    pub tracking_event_id: i64,
+    pub product_id: String,
+    pub anomaly_type: String,
+    pub score: f64,
+    pub severity: String,
+    pub details: serde_json::Value,
+    pub detected_at: DateTime<Utc>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AnomalyQuery {
+    pub product_id: Option<String>,
+    pub anomaly_type: Option<String>,
+    pub min_score: Option<f64>,
+    pub start_date: Option<DateTime<Utc>>,
+    pub end_date: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
@@ -0,0 +1,79 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::compliance::{ComplianceRule, RuleFormat};
+
+/// One version of a declarative rule as stored in `compliance_rules`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct StoredComplianceRule {
+    pub rule_id: String,
+    pub version: i32,
+    pub compliance_type: String,
+    pub description: String,
+    pub definition: Value,
+    pub source_format: String,
+    /// Definition exactly as submitted, including YAML comments
+    pub source: Option<String>,
+    pub is_active: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl StoredComplianceRule {
+    pub fn rule(&self) -> Result<ComplianceRule, serde_json::Error> {
+        let mut rule: ComplianceRule = serde_json::from_value(self.definition.clone())?;
+        rule.version = self.version;
+        Ok(rule)
+    }
+}
+
+/// A stored validation result
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct ComplianceRecord {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub product_id: Option<String>,
+    pub compliance_type: String,
+    /// `compliant` or `non_compliant`
+    pub status: String,
+    pub rule_id: Option<String>,
+    pub rule_version: Option<i32>,
+    pub validation_data: Option<Value>,
+    /// `manual` for explicit checks, `continuous` for evaluation on new events
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub verified_at: Option<DateTime<Utc>>,
+}
+
+/// Pushed to `product:{id}` when continuous evaluation changes a rule's status
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ComplianceStatusChange {
+    pub product_id: String,
+    pub rule_id: String,
+    pub rule_version: i32,
+    pub compliance_type: String,
+    /// `None` the first time the product is evaluated against the rule
+    pub previous_status: Option<String>,
+    pub status: String,
+    pub violations: Vec<String>,
+    pub evaluated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+pub struct ComplianceRuleQuery {
+    pub compliance_type: Option<String>,
+    /// Include superseded and deactivated versions
+    #[serde(default)]
+    pub include_inactive: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+pub struct ComplianceRuleUploadQuery {
+    /// Overrides the format implied by the Content-Type header
+    pub format: Option<RuleFormat>,
+}
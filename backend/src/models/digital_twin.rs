@@ -17,6 +17,7 @@ pub struct DigitalTwin {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub last_sync_at: Option<DateTime<Utc>>,
+    pub sync_mapping: serde_json::Value,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
@@ -138,6 +139,42 @@ pub enum ComplexityLevel {
     High,
 }
 
+/// TwinSyncMapping describes how observed events and oracle snapshots drive a twin's state
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TwinSyncMapping {
+    /// Event types that update the twin; empty means every event type
+    #[serde(default)]
+    pub event_types: Vec<String>,
+    /// Field mappings; when empty the default product mapping is used
+    #[serde(default)]
+    pub fields: Vec<StateFieldMapping>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateFieldMapping {
+    /// Dotted path into the observation, e.g. `location` or `metadata.temperature_c`
+    pub source: String,
+    /// Dotted path into `current_state` that receives the value
+    pub target: String,
+    /// Allowed numeric drift from the predicted value before a divergence is raised
+    #[serde(default)]
+    pub tolerance: Option<f64>,
+}
+
+/// TwinDivergence records an observation that disagreed with the twin's predicted state
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TwinDivergence {
+    pub id: Uuid,
+    pub twin_id: Uuid,
+    pub tracking_event_id: Option<i64>,
+    pub state_path: String,
+    pub predicted_value: Option<serde_json::Value>,
+    pub observed_value: Option<serde_json::Value>,
+    pub source: String,
+    pub detected_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
 /// TwinMetrics aggregates key performance indicators for a digital twin
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TwinMetrics {
@@ -162,6 +199,8 @@ pub struct CreateDigitalTwinRequest {
     pub description: String,
     pub initial_state: serde_json::Value,
     pub metadata: serde_json::Value,
+    #[serde(default)]
+    pub sync_mapping: TwinSyncMapping,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -216,3 +255,27 @@ pub struct PredictionRequest {
     pub prediction_horizon: i32,
     pub input_features: serde_json::Value,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OracleSnapshotRequest {
+    pub source: String,
+    pub snapshot: serde_json::Value,
+}
+
+/// EtaPrediction estimates when an in-flight product will be received,
+/// learned from historical ship/receive legs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EtaPrediction {
+    pub product_id: String,
+    pub origin: String,
+    pub destination: Option<String>,
+    pub transport_mode: String,
+    pub departed_at: DateTime<Utc>,
+    pub estimated_arrival: DateTime<Utc>,
+    pub lower_bound: DateTime<Utc>,
+    pub upper_bound: DateTime<Utc>,
+    pub confidence_level: f64,
+    pub sample_size: usize,
+    pub segment: crate::services::eta_predictor::EtaSegment,
+    pub overdue: bool,
+}
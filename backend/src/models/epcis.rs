@@ -0,0 +1,54 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Serialization of an exported EPCIS document
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EpcisFormat {
+    #[default]
+    Json,
+    Xml,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EpcisProductExportQuery {
+    pub format: Option<EpcisFormat>,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EpcisRangeExportQuery {
+    pub start_date: DateTime<Utc>,
+    pub end_date: DateTime<Utc>,
+    pub product_id: Option<String>,
+    pub format: Option<EpcisFormat>,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EpcisImportQuery {
+    /// Actor recorded for events without a `cl:actorAddress` extension
+    pub actor_address: Option<String>,
+    /// Also record each imported event on-chain
+    #[serde(default)]
+    pub anchor: bool,
+}
+
+/// Outcome of anchoring one imported event on-chain
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EpcisAnchorOutcome {
+    pub event_id: i64,
+    pub result: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EpcisImportResult {
+    /// Ids of the tracking events created
+    pub event_ids: Vec<i64>,
+    /// Events already present (same EPCIS eventID, or same data hash and time)
+    pub skipped_duplicates: usize,
+    pub anchored: Vec<EpcisAnchorOutcome>,
+}
@@ -0,0 +1,77 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// A production plot (farm, plantation, forest stand) a product was sourced from
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct OriginPlot {
+    pub id: Uuid,
+    pub product_id: String,
+    /// Operator's own identifier for the plot
+    pub plot_reference: Option<String>,
+    /// ISO 3166-1 alpha-2 country of production
+    pub country_code: String,
+    pub commodity: String,
+    /// GeoJSON Point, Polygon or MultiPolygon in WGS84
+    pub geometry: Value,
+    pub area_hectares: f64,
+    pub production_start: Option<NaiveDate>,
+    pub production_end: Option<NaiveDate>,
+    /// Reference to proof the plot was not deforested after the cut-off date
+    /// (satellite analysis report, certificate number, ...)
+    pub deforestation_free_evidence: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NewOriginPlot {
+    pub plot_reference: Option<String>,
+    pub country_code: String,
+    pub commodity: String,
+    pub geometry: Value,
+    /// Required for Point geometries; computed from the polygon otherwise
+    pub area_hectares: Option<f64>,
+    pub production_start: Option<NaiveDate>,
+    pub production_end: Option<NaiveDate>,
+    pub deforestation_free_evidence: Option<String>,
+}
+
+/// Edge from an input product to a product made or relabelled from it
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct LineageEdge {
+    pub parent_product_id: String,
+    pub child_product_id: String,
+    pub event_id: i64,
+    /// Lower-cased type of the event that created the edge (`process`, `transfer`, ...)
+    pub relation: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A product's own plots together with those inherited through its lineage
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ProductOriginPlots {
+    pub product_id: String,
+    /// Plots whose `product_id` differs from the requested product were inherited
+    pub plots: Vec<OriginPlot>,
+    /// Every upstream product that contributed plots or lineage
+    pub ancestors: Vec<String>,
+}
+
+/// Nature of the placement on, or export from, the EU market
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DdsActivity {
+    #[default]
+    Import,
+    Export,
+    Domestic,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+pub struct DdsQuery {
+    #[serde(default)]
+    pub activity: DdsActivity,
+}
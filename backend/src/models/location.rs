@@ -0,0 +1,126 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::models::TrackingEvent;
+
+/// A facility, port or warehouse in the geocoding table
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct KnownLocation {
+    pub id: Uuid,
+    pub name: String,
+    pub normalized_name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub geohash: String,
+    pub unlocode: Option<String>,
+    pub gln: Option<String>,
+    pub country_code: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// How a free-text location was turned into coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolvedBy {
+    Coordinates,
+    Unlocode,
+    Gln,
+    Name,
+}
+
+impl ResolvedBy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResolvedBy::Coordinates => "coordinates",
+            ResolvedBy::Unlocode => "unlocode",
+            ResolvedBy::Gln => "gln",
+            ResolvedBy::Name => "name",
+        }
+    }
+}
+
+/// Structured location for an event or product origin
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ResolvedLocation {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub geohash: String,
+    pub unlocode: Option<String>,
+    pub gln: Option<String>,
+    pub known_location_id: Option<Uuid>,
+    pub resolved_by: ResolvedBy,
+}
+
+/// Row in `event_locations`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct EventLocation {
+    pub tracking_event_id: i64,
+    pub known_location_id: Option<Uuid>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub geohash: String,
+    pub unlocode: Option<String>,
+    pub gln: Option<String>,
+    pub resolved_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A tracking event with its resolved position
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LocatedEvent {
+    #[serde(flatten)]
+    pub event: TrackingEvent,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub geohash: String,
+    /// Distance from the query centre, for radius queries
+    pub distance_km: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct CreateKnownLocationRequest {
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub unlocode: Option<String>,
+    pub gln: Option<String>,
+    pub country_code: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct KnownLocationQuery {
+    pub search: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ResolveLocationQuery {
+    pub location: String,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NearbyEventsQuery {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub radius_km: f64,
+    pub product_id: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BoundingBoxEventsQuery {
+    pub min_latitude: f64,
+    pub min_longitude: f64,
+    pub max_latitude: f64,
+    pub max_longitude: f64,
+    pub product_id: Option<String>,
+    pub limit: Option<i64>,
+}
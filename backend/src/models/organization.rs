@@ -0,0 +1,112 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// A tenant: the company (or single user) that owns products, API keys and
+/// carbon credits
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Organization {
+    pub id: Uuid,
+    pub name: String,
+    pub slug: String,
+    /// Created automatically for a user registering on their own
+    pub is_personal: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A member's role within one organization, independent of their platform
+/// `UserRole`
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type, ToSchema,
+)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum OrgRole {
+    /// Read-only access to the organization's data
+    Viewer,
+    /// Record events and edit the organization's products
+    Member,
+    /// Everything a member can do, plus managing members, addresses and grants
+    Admin,
+    /// Like an admin; the last owner cannot leave or be demoted
+    Owner,
+}
+
+impl OrgRole {
+    pub fn can_manage(&self) -> bool {
+        matches!(self, OrgRole::Owner | OrgRole::Admin)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct OrganizationMember {
+    pub organization_id: Uuid,
+    pub user_id: Uuid,
+    pub role: OrgRole,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A Stellar address the organization operates. Products owned by it belong
+/// to the organization.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct OrganizationAddress {
+    pub stellar_address: String,
+    pub organization_id: Uuid,
+    pub label: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An organization as seen by one of its members
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct OrganizationMembership {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub organization: Organization,
+    pub role: OrgRole,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct CreateOrganizationRequest {
+    pub name: String,
+    /// URL-safe identifier; derived from the name when omitted
+    pub slug: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct AddMemberRequest {
+    pub user_id: Uuid,
+    pub role: OrgRole,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct UpdateMemberRequest {
+    pub role: OrgRole,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct AddressChallengeRequest {
+    pub stellar_address: String,
+}
+
+/// Proof of control is the challenge transaction from
+/// `POST /organizations/{id}/addresses/challenge`, signed by the address. The
+/// address added is the one the challenge was issued for.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct AddAddressRequest {
+    pub label: Option<String>,
+    /// The challenge transaction with the account's signature added, as a
+    /// base64 XDR envelope
+    pub transaction: String,
+}
+
+/// What a non-administrator request may see: the organization it acts for,
+/// plus products shared with it (or with the caller's own address) through
+/// grants. Administrators carry no scope.
+#[derive(Debug, Clone)]
+pub struct TenantScope {
+    pub organization_id: Uuid,
+    pub stellar_address: Option<String>,
+}
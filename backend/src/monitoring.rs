@@ -1,11 +1,19 @@
+pub mod alert_sinks;
+pub mod alerts;
+pub mod metrics;
+pub mod sampler;
+pub mod telemetry;
+
 use std::sync::Arc;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use tokio::sync::RwLock;
 use chrono::{DateTime, Utc, Duration};
 use serde::{Deserialize, Serialize};
 use std::time::Instant;
 
 use crate::error::ErrorCode;
+use crate::models::{Alert, AlertSeverity, AlertStatus};
+use alert_sinks::AlertRouter;
 
 /// Error metrics and monitoring
 #[derive(Clone)]
@@ -79,16 +87,25 @@ pub struct InfrastructureMetrics {
     /// Database connection pool usage
     pub db_pool_usage: f64,
     
+    /// Connections the pool has open
+    pub db_pool_size: u32,
+    
+    /// Open connections not in use
+    pub db_pool_idle: u32,
+    
     /// Redis connection status
     pub redis_connected: bool,
     
+    /// Round trip of a Redis PING, when Redis answered
+    pub redis_ping_ms: Option<f64>,
+    
     /// Memory usage in MB
     pub memory_usage_mb: u64,
     
     /// CPU usage percentage
     pub cpu_usage_percent: f64,
     
-    /// Active connections
+    /// Active websocket connections
     pub active_connections: u64,
     
     /// Last updated timestamp
@@ -329,26 +346,55 @@ impl Default for PerformanceMonitor {
     }
 }
 
+/// Samples kept when no history length is configured
+const DEFAULT_HISTORY_LEN: usize = 720;
+
 /// Infrastructure monitoring
 #[derive(Clone)]
 pub struct InfrastructureMonitor {
     metrics: Arc<RwLock<InfrastructureMetrics>>,
+    /// Recent samples, oldest first
+    history: Arc<RwLock<VecDeque<InfrastructureMetrics>>>,
+    history_len: usize,
 }
 
 impl InfrastructureMonitor {
     pub fn new() -> Self {
+        Self::with_history(DEFAULT_HISTORY_LEN)
+    }
+
+    /// Monitor that keeps the last `history_len` samples
+    pub fn with_history(history_len: usize) -> Self {
         Self {
             metrics: Arc::new(RwLock::new(InfrastructureMetrics {
                 db_pool_usage: 0.0,
+                db_pool_size: 0,
+                db_pool_idle: 0,
                 redis_connected: false,
+                redis_ping_ms: None,
                 memory_usage_mb: 0,
                 cpu_usage_percent: 0.0,
                 active_connections: 0,
                 last_updated: Utc::now(),
             })),
+            history: Arc::new(RwLock::new(VecDeque::with_capacity(history_len))),
+            history_len: history_len.max(1),
         }
     }
 
+    /// Replace the current metrics with a full sample and add it to the
+    /// history, dropping the oldest sample once the history is full
+    pub async fn record_sample(&self, sample: InfrastructureMetrics) {
+        {
+            let mut history = self.history.write().await;
+            while history.len() >= self.history_len {
+                history.pop_front();
+            }
+            history.push_back(sample.clone());
+        }
+        *self.metrics.write().await = sample;
+    }
+
     /// Update database pool usage
     pub async fn update_db_pool_usage(&self, usage: f64) {
         let mut metrics = self.metrics.write().await;
@@ -388,6 +434,11 @@ impl InfrastructureMonitor {
     pub async fn get_metrics(&self) -> InfrastructureMetrics {
         self.metrics.read().await.clone()
     }
+
+    /// Recorded samples, oldest first
+    pub async fn get_history(&self) -> Vec<InfrastructureMetrics> {
+        self.history.read().await.iter().cloned().collect()
+    }
 }
 
 impl Default for InfrastructureMonitor {
@@ -407,10 +458,15 @@ pub struct MonitoringSystem {
 
 impl MonitoringSystem {
     pub fn new() -> Self {
+        Self::with_infrastructure_history(DEFAULT_HISTORY_LEN)
+    }
+
+    /// Monitoring system whose infrastructure monitor keeps `history_len` samples
+    pub fn with_infrastructure_history(history_len: usize) -> Self {
         Self {
             error_monitor: ErrorMonitor::new(),
             performance_monitor: PerformanceMonitor::new(),
-            infrastructure_monitor: InfrastructureMonitor::new(),
+            infrastructure_monitor: InfrastructureMonitor::with_history(history_len),
             alerter: ErrorAlerter::new(AlertConfig::default()),
         }
     }
@@ -502,17 +558,29 @@ impl Default for AlertConfig {
     }
 }
 
+/// Rule name of alerts raised through `ErrorAlerter::trigger_alert`
+pub const ERROR_ALERTER_RULE: &str = "error_alerter";
+
 /// Error alerting system
+#[derive(Clone)]
 pub struct ErrorAlerter {
     config: AlertConfig,
     last_alert: Arc<RwLock<Option<DateTime<Utc>>>>,
+    router: AlertRouter,
 }
 
 impl ErrorAlerter {
+    /// Alerter whose alerts are only logged
     pub fn new(config: AlertConfig) -> Self {
+        Self::with_router(config, AlertRouter::default())
+    }
+
+    /// Alerter whose alerts go to the router's sinks
+    pub fn with_router(config: AlertConfig, router: AlertRouter) -> Self {
         Self {
             config,
             last_alert: Arc::new(RwLock::new(None)),
+            router,
         }
     }
     
@@ -551,16 +619,22 @@ impl ErrorAlerter {
         true
     }
     
-    /// Trigger an alert (placeholder for actual alerting implementation)
+    /// Trigger an alert, sending it to every configured sink
     pub async fn trigger_alert(&self, message: String) {
-        tracing::error!(
-            alert = true,
-            message = %message,
-            "Error alert triggered"
-        );
-        
-        // TODO: Implement actual alerting (email, Slack, PagerDuty, etc.)
-        // For now, just log the alert
+        let now = Utc::now();
+        let alert = Alert {
+            fingerprint: alerts::fingerprint(ERROR_ALERTER_RULE, ""),
+            rule: ERROR_ALERTER_RULE.to_string(),
+            subject: String::new(),
+            severity: AlertSeverity::Critical,
+            status: AlertStatus::Firing,
+            summary: message,
+            started_at: now,
+            last_notified_at: None,
+            resolved_at: None,
+            updated_at: now,
+        };
+        self.router.send(&alert).await;
     }
 }
 
@@ -624,6 +698,22 @@ mod tests {
         assert_eq!(stats.top_errors[0].1, 3); // DatabaseError should be first
     }
 
+    #[tokio::test]
+    async fn test_infrastructure_history_keeps_latest_samples() {
+        let monitor = InfrastructureMonitor::with_history(3);
+        let mut sample = monitor.get_metrics().await;
+
+        for count in 1..=5 {
+            sample.active_connections = count;
+            monitor.record_sample(sample.clone()).await;
+        }
+
+        let history = monitor.get_history().await;
+        let counts: Vec<u64> = history.iter().map(|s| s.active_connections).collect();
+        assert_eq!(counts, vec![3, 4, 5]);
+        assert_eq!(monitor.get_metrics().await.active_connections, 5);
+    }
+
     #[tokio::test]
     async fn test_alerter_cooldown() {
         let config = AlertConfig {
@@ -0,0 +1,252 @@
+/// Where alert notifications go.
+///
+/// Each destination implements `AlertSink`; the `AlertRouter` sends every
+/// notification to all configured sinks and logs it. A notification counts
+/// as sent when at least one sink accepted it, so a flaky sink does not cause
+/// repeats on the others.
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde_json::Value;
+
+use crate::config::{AlertingConfig, SmtpConfig};
+use crate::models::{Alert, AlertSeverity, AlertStatus};
+use crate::monitoring::alerts;
+use crate::services::webhook_delivery::{self, SIGNATURE_HEADER};
+
+const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// `source` of PagerDuty events
+const SOURCE: &str = "chainlogistics-backend";
+
+#[async_trait]
+pub trait AlertSink: Send + Sync {
+    /// Name used in logs
+    fn name(&self) -> &'static str;
+
+    async fn send(&self, alert: &Alert) -> Result<(), String>;
+}
+
+async fn post_json(request: reqwest::RequestBuilder, body: &Value) -> Result<(), String> {
+    let response = request
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("responded with {}", response.status()))
+    }
+}
+
+// ── Sinks ─────────────────────────────────────────────────────────────────────
+
+/// Posts the alert as JSON, signed with `X-ChainLogistics-Signature` when a
+/// secret is set
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+    secret: Option<String>,
+}
+
+#[async_trait]
+impl AlertSink for WebhookSink {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<(), String> {
+        let body = alerts::webhook_payload(alert);
+        let mut request = self.client.post(&self.url);
+        if let Some(secret) = &self.secret {
+            let signature =
+                webhook_delivery::sign(secret, Utc::now().timestamp(), &body.to_string());
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+        post_json(request, &body).await
+    }
+}
+
+/// Posts a Slack incoming-webhook message
+pub struct SlackSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+#[async_trait]
+impl AlertSink for SlackSink {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<(), String> {
+        post_json(self.client.post(&self.url), &alerts::slack_payload(alert)).await
+    }
+}
+
+/// Triggers and resolves PagerDuty incidents through the Events API v2
+pub struct PagerDutySink {
+    client: reqwest::Client,
+    routing_key: String,
+}
+
+#[async_trait]
+impl AlertSink for PagerDutySink {
+    fn name(&self) -> &'static str {
+        "pagerduty"
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<(), String> {
+        let body = alerts::pagerduty_payload(&self.routing_key, SOURCE, alert);
+        post_json(self.client.post(PAGERDUTY_EVENTS_URL), &body).await
+    }
+}
+
+/// Emails the alert to a fixed list of recipients
+pub struct EmailSink {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl EmailSink {
+    pub fn new(config: &SmtpConfig) -> Result<Self, String> {
+        let mut transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+            .map_err(|e| format!("Invalid SMTP host {}: {}", config.host, e))?
+            .port(config.port)
+            .timeout(Some(REQUEST_TIMEOUT));
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        let from = config
+            .from
+            .parse()
+            .map_err(|e| format!("Invalid alert email sender {}: {}", config.from, e))?;
+        let to = config
+            .to
+            .iter()
+            .map(|to| {
+                to.parse()
+                    .map_err(|e| format!("Invalid alert email recipient {}: {}", to, e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            transport: transport.build(),
+            from,
+            to,
+        })
+    }
+}
+
+#[async_trait]
+impl AlertSink for EmailSink {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<(), String> {
+        let mut message = Message::builder()
+            .from(self.from.clone())
+            .subject(alerts::email_subject(alert))
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        let message = message
+            .body(alerts::email_body(alert))
+            .map_err(|e| e.to_string())?;
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+// ── Router ────────────────────────────────────────────────────────────────────
+
+#[derive(Clone, Default)]
+pub struct AlertRouter {
+    sinks: Arc<Vec<Arc<dyn AlertSink>>>,
+}
+
+impl AlertRouter {
+    pub fn new(sinks: Vec<Arc<dyn AlertSink>>) -> Self {
+        Self {
+            sinks: Arc::new(sinks),
+        }
+    }
+
+    /// A router with a sink for each destination in `config`
+    pub fn from_config(config: &AlertingConfig) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent("ChainLogistics-Alerts/1.0")
+            .build()
+            .map_err(|e| e.to_string())?;
+        let mut sinks: Vec<Arc<dyn AlertSink>> = Vec::new();
+        if let Some(url) = &config.webhook_url {
+            sinks.push(Arc::new(WebhookSink {
+                client: client.clone(),
+                url: url.clone(),
+                secret: config.webhook_secret.clone(),
+            }));
+        }
+        if let Some(url) = &config.slack_webhook_url {
+            sinks.push(Arc::new(SlackSink {
+                client: client.clone(),
+                url: url.clone(),
+            }));
+        }
+        if let Some(routing_key) = &config.pagerduty_routing_key {
+            sinks.push(Arc::new(PagerDutySink {
+                client,
+                routing_key: routing_key.clone(),
+            }));
+        }
+        if let Some(smtp) = &config.smtp {
+            sinks.push(Arc::new(EmailSink::new(smtp)?));
+        }
+        Ok(Self::new(sinks))
+    }
+
+    /// Logs the alert and sends it to every sink. True when it was logged
+    /// only, or at least one sink accepted it.
+    pub async fn send(&self, alert: &Alert) -> bool {
+        match (alert.status, alert.severity) {
+            (AlertStatus::Resolved, _) => {
+                tracing::info!(alert = %alert.fingerprint, "Alert resolved: {}", alert.summary)
+            }
+            (_, AlertSeverity::Critical) => {
+                tracing::error!(alert = %alert.fingerprint, "Alert firing: {}", alert.summary)
+            }
+            _ => tracing::warn!(alert = %alert.fingerprint, "Alert firing: {}", alert.summary),
+        }
+        if self.sinks.is_empty() {
+            return true;
+        }
+
+        let results =
+            futures::future::join_all(self.sinks.iter().map(|sink| sink.send(alert))).await;
+        let mut delivered = false;
+        for (sink, result) in self.sinks.iter().zip(results) {
+            match result {
+                Ok(()) => delivered = true,
+                Err(e) => tracing::error!(
+                    alert = %alert.fingerprint,
+                    sink = sink.name(),
+                    "Failed to send alert notification: {}",
+                    e
+                ),
+            }
+        }
+        delivered
+    }
+}
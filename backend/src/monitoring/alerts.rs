@@ -0,0 +1,632 @@
+/// Alert rules, deduplication and silencing.
+///
+/// Rules turn a snapshot of signals into firing alerts, one per rule and
+/// subject: the error rate and latency of one instance, how far behind event
+/// ingestion is, and conditions reported to the backend such as a tripped
+/// oracle circuit breaker. `step` compares those with the stored state of
+/// each alert to decide what to notify. An alert is notified when it starts
+/// firing, again every repeat interval while it keeps firing, and once when
+/// it resolves. Silenced alerts are still tracked but not notified; one that
+/// is still firing when its silence ends is notified then.
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::models::{Alert, AlertSeverity, AlertSilence, AlertStatus};
+
+/// Reported signal kind for an oracle feed whose circuit breaker tripped
+pub const SIGNAL_ORACLE_CIRCUIT_BREAKER: &str = "oracle_circuit_breaker";
+/// Reported signal kind for a contract whose upgrade failed
+pub const SIGNAL_UPGRADE_FAILED: &str = "upgrade_failed";
+
+// ── Rules ─────────────────────────────────────────────────────────────────────
+
+/// What a rule watches
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Condition {
+    /// Server errors (5xx) per minute on an instance above `threshold`
+    ErrorRate { threshold: f64 },
+    /// 95th percentile request latency on an instance, in milliseconds,
+    /// above `threshold`
+    LatencyP95 { threshold: f64 },
+    /// Seconds since the newest recorded tracking event happened above
+    /// `threshold`
+    SyncLag { threshold: f64 },
+    /// An oracle feed's circuit breaker is tripped; one alert per feed
+    OracleCircuitBreaker,
+    /// A contract upgrade failed; one alert per contract
+    UpgradeFailed,
+}
+
+impl Condition {
+    /// Whether each instance measures the condition for itself, so that its
+    /// alerts are per instance
+    pub fn is_per_instance(&self) -> bool {
+        matches!(
+            self,
+            Condition::ErrorRate { .. } | Condition::LatencyP95 { .. }
+        )
+    }
+
+    fn threshold(&self) -> Option<f64> {
+        match self {
+            Condition::ErrorRate { threshold }
+            | Condition::LatencyP95 { threshold }
+            | Condition::SyncLag { threshold } => Some(*threshold),
+            Condition::OracleCircuitBreaker | Condition::UpgradeFailed => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub name: String,
+    pub severity: AlertSeverity,
+    #[serde(flatten)]
+    pub condition: Condition,
+}
+
+/// Rules used when no rules file is configured
+pub fn default_rules() -> Vec<AlertRule> {
+    let rule = |name: &str, severity, condition| AlertRule {
+        name: name.to_string(),
+        severity,
+        condition,
+    };
+    vec![
+        rule(
+            "high_error_rate",
+            AlertSeverity::Critical,
+            Condition::ErrorRate { threshold: 10.0 },
+        ),
+        rule(
+            "slow_responses",
+            AlertSeverity::Warning,
+            Condition::LatencyP95 { threshold: 2000.0 },
+        ),
+        rule(
+            "sync_lag",
+            AlertSeverity::Warning,
+            Condition::SyncLag { threshold: 900.0 },
+        ),
+        rule(
+            "oracle_circuit_breaker",
+            AlertSeverity::Critical,
+            Condition::OracleCircuitBreaker,
+        ),
+        rule(
+            "upgrade_failed",
+            AlertSeverity::Critical,
+            Condition::UpgradeFailed,
+        ),
+    ]
+}
+
+/// Parses a YAML list of rules and checks that names are unique and
+/// thresholds positive
+pub fn parse_rules(yaml: &str) -> Result<Vec<AlertRule>, String> {
+    let rules: Vec<AlertRule> =
+        serde_yaml::from_str(yaml).map_err(|e| format!("Invalid alert rules: {}", e))?;
+    let mut names = HashSet::new();
+    for rule in &rules {
+        if rule.name.trim().is_empty() || rule.name.contains(':') {
+            return Err(format!(
+                "Alert rule name '{}' must be non-empty and contain no ':'",
+                rule.name
+            ));
+        }
+        if !names.insert(rule.name.as_str()) {
+            return Err(format!("Alert rule '{}' is defined twice", rule.name));
+        }
+        if let Some(threshold) = rule.condition.threshold() {
+            if !threshold.is_finite() || threshold <= 0.0 {
+                return Err(format!(
+                    "Alert rule '{}' needs a positive threshold",
+                    rule.name
+                ));
+            }
+        }
+    }
+    Ok(rules)
+}
+
+// ── Evaluation ────────────────────────────────────────────────────────────────
+
+/// A condition reported to the backend that is currently active
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportedSignal {
+    pub kind: String,
+    pub subject: String,
+    pub detail: Option<String>,
+}
+
+/// What the rules are evaluated against. Measurements are None when they
+/// could not be taken, which fires nothing.
+#[derive(Debug, Clone, Default)]
+pub struct Signals {
+    /// This instance, the subject of per-instance alerts
+    pub instance: String,
+    pub error_rate: Option<f64>,
+    pub latency_p95_ms: Option<f64>,
+    pub sync_lag_secs: Option<f64>,
+    pub reported: Vec<ReportedSignal>,
+}
+
+/// A rule that currently holds for a subject
+#[derive(Debug, Clone, PartialEq)]
+pub struct Firing {
+    pub rule: String,
+    pub subject: String,
+    pub severity: AlertSeverity,
+    pub summary: String,
+}
+
+pub fn fingerprint(rule: &str, subject: &str) -> String {
+    format!("{}:{}", rule, subject)
+}
+
+pub fn evaluate(rules: &[AlertRule], signals: &Signals) -> Vec<Firing> {
+    let mut firings = Vec::new();
+    for rule in rules {
+        let mut fire = |subject: &str, summary: String| {
+            firings.push(Firing {
+                rule: rule.name.clone(),
+                subject: subject.to_string(),
+                severity: rule.severity,
+                summary,
+            })
+        };
+        match &rule.condition {
+            Condition::ErrorRate { threshold } => {
+                if let Some(rate) = signals.error_rate.filter(|rate| rate > threshold) {
+                    fire(
+                        &signals.instance,
+                        format!(
+                            "{}: {:.1} server errors per minute (threshold {})",
+                            signals.instance, rate, threshold
+                        ),
+                    );
+                }
+            }
+            Condition::LatencyP95 { threshold } => {
+                if let Some(p95) = signals.latency_p95_ms.filter(|p95| p95 > threshold) {
+                    fire(
+                        &signals.instance,
+                        format!(
+                            "{}: p95 latency {:.0} ms (threshold {} ms)",
+                            signals.instance, p95, threshold
+                        ),
+                    );
+                }
+            }
+            Condition::SyncLag { threshold } => {
+                if let Some(lag) = signals.sync_lag_secs.filter(|lag| lag > threshold) {
+                    fire(
+                        "",
+                        format!(
+                            "No tracking event recorded for {:.0}s (threshold {}s)",
+                            lag, threshold
+                        ),
+                    );
+                }
+            }
+            Condition::OracleCircuitBreaker => {
+                for signal in reported(signals, SIGNAL_ORACLE_CIRCUIT_BREAKER) {
+                    fire(
+                        &signal.subject,
+                        with_detail(
+                            format!("Circuit breaker tripped on oracle feed {}", signal.subject),
+                            signal,
+                        ),
+                    );
+                }
+            }
+            Condition::UpgradeFailed => {
+                for signal in reported(signals, SIGNAL_UPGRADE_FAILED) {
+                    fire(
+                        &signal.subject,
+                        with_detail(
+                            format!("Upgrade of contract {} failed", signal.subject),
+                            signal,
+                        ),
+                    );
+                }
+            }
+        }
+    }
+    firings
+}
+
+fn reported<'a>(signals: &'a Signals, kind: &'a str) -> impl Iterator<Item = &'a ReportedSignal> {
+    signals.reported.iter().filter(move |s| s.kind == kind)
+}
+
+fn with_detail(summary: String, signal: &ReportedSignal) -> String {
+    match &signal.detail {
+        Some(detail) if !detail.is_empty() => format!("{}: {}", summary, detail),
+        _ => summary,
+    }
+}
+
+// ── State ─────────────────────────────────────────────────────────────────────
+
+/// What to do about one alert after an evaluation
+#[derive(Debug, Clone)]
+pub struct Step {
+    /// The alert's new state. `last_notified_at` is left as it was; the
+    /// caller sets it once a notification went out.
+    pub alert: Alert,
+    pub notify: bool,
+}
+
+/// How often and by whom alerts are re-evaluated
+#[derive(Debug, Clone, Copy)]
+pub struct Timing {
+    /// A firing alert is notified again after this long
+    pub repeat_interval: Duration,
+    /// A firing per-instance alert its instance has not updated for this
+    /// long is resolved by whichever instance evaluates next
+    pub stale_after: Duration,
+}
+
+/// Moves alerts to their next state. `previous` holds the stored alerts;
+/// firing ones are resolved when no longer firing, as long as this instance
+/// is the one that evaluates them.
+pub fn step(
+    now: DateTime<Utc>,
+    timing: Timing,
+    rules: &[AlertRule],
+    instance: &str,
+    firings: Vec<Firing>,
+    previous: &[Alert],
+    silences: &[AlertSilence],
+) -> Vec<Step> {
+    let previous: HashMap<&str, &Alert> = previous
+        .iter()
+        .map(|alert| (alert.fingerprint.as_str(), alert))
+        .collect();
+    let per_instance: HashSet<&str> = rules
+        .iter()
+        .filter(|rule| rule.condition.is_per_instance())
+        .map(|rule| rule.name.as_str())
+        .collect();
+
+    let mut steps = Vec::new();
+    let mut seen = HashSet::new();
+    for firing in firings {
+        let fingerprint = fingerprint(&firing.rule, &firing.subject);
+        let before = previous
+            .get(fingerprint.as_str())
+            .filter(|alert| alert.status == AlertStatus::Firing);
+        let alert = Alert {
+            fingerprint: fingerprint.clone(),
+            rule: firing.rule,
+            subject: firing.subject,
+            severity: firing.severity,
+            status: AlertStatus::Firing,
+            summary: firing.summary,
+            started_at: before.map_or(now, |alert| alert.started_at),
+            last_notified_at: before.and_then(|alert| alert.last_notified_at),
+            resolved_at: None,
+            updated_at: now,
+        };
+        let due = alert
+            .last_notified_at
+            .is_none_or(|at| now - at >= timing.repeat_interval);
+        let notify = due && !is_silenced(&alert, silences, now);
+        seen.insert(fingerprint);
+        steps.push(Step { alert, notify });
+    }
+
+    for alert in previous.values() {
+        if alert.status != AlertStatus::Firing || seen.contains(&alert.fingerprint) {
+            continue;
+        }
+        let ours = !per_instance.contains(alert.rule.as_str())
+            || alert.subject == instance
+            || now - alert.updated_at >= timing.stale_after;
+        if !ours {
+            continue;
+        }
+        let resolved = Alert {
+            status: AlertStatus::Resolved,
+            resolved_at: Some(now),
+            updated_at: now,
+            ..(*alert).clone()
+        };
+        let notify = alert.last_notified_at.is_some() && !is_silenced(&resolved, silences, now);
+        steps.push(Step {
+            alert: resolved,
+            notify,
+        });
+    }
+    steps
+}
+
+pub fn is_silenced(alert: &Alert, silences: &[AlertSilence], now: DateTime<Utc>) -> bool {
+    silences.iter().any(|silence| {
+        silence.starts_at <= now
+            && now < silence.ends_at
+            && silence
+                .rule
+                .as_deref()
+                .is_none_or(|rule| rule == alert.rule)
+            && silence
+                .subject
+                .as_deref()
+                .is_none_or(|subject| subject == alert.subject)
+    })
+}
+
+// ── Payloads ──────────────────────────────────────────────────────────────────
+
+/// Body posted to a generic alert webhook
+pub fn webhook_payload(alert: &Alert) -> Value {
+    json!({
+        "type": format!("alert.{}", alert.status.as_str()),
+        "alert": alert,
+    })
+}
+
+/// Message for a Slack incoming webhook, or anything that accepts its format
+pub fn slack_payload(alert: &Alert) -> Value {
+    let (icon, color) = match (alert.status, alert.severity) {
+        (AlertStatus::Resolved, _) => (":white_check_mark:", "#2eb886"),
+        (_, AlertSeverity::Critical) => (":rotating_light:", "#d00000"),
+        (_, AlertSeverity::Warning) => (":warning:", "#daa038"),
+        (_, AlertSeverity::Info) => (":information_source:", "#439fe0"),
+    };
+    let title = format!(
+        "{} [{}] {}",
+        icon,
+        alert.status.as_str().to_uppercase(),
+        alert.rule
+    );
+    json!({
+        "text": format!("{} {}", title, alert.summary),
+        "attachments": [{
+            "color": color,
+            "title": title,
+            "text": alert.summary,
+            "fields": [
+                { "title": "Severity", "value": alert.severity.as_str(), "short": true },
+                { "title": "Since", "value": alert.started_at.to_rfc3339(), "short": true },
+            ],
+        }],
+    })
+}
+
+/// A PagerDuty Events API v2 event. The fingerprint is the dedup key, so a
+/// resolve closes the incident its trigger opened.
+pub fn pagerduty_payload(routing_key: &str, source: &str, alert: &Alert) -> Value {
+    match alert.status {
+        AlertStatus::Firing => json!({
+            "routing_key": routing_key,
+            "event_action": "trigger",
+            "dedup_key": alert.fingerprint,
+            "payload": {
+                "summary": alert.summary,
+                "source": source,
+                "severity": match alert.severity {
+                    AlertSeverity::Critical => "critical",
+                    AlertSeverity::Warning => "warning",
+                    AlertSeverity::Info => "info",
+                },
+                "timestamp": alert.started_at.to_rfc3339(),
+                "component": alert.rule,
+                "custom_details": { "subject": alert.subject },
+            },
+        }),
+        AlertStatus::Resolved => json!({
+            "routing_key": routing_key,
+            "event_action": "resolve",
+            "dedup_key": alert.fingerprint,
+        }),
+    }
+}
+
+pub fn email_subject(alert: &Alert) -> String {
+    format!(
+        "[{} {}] {}",
+        alert.status.as_str().to_uppercase(),
+        alert.severity.as_str(),
+        alert.summary
+    )
+}
+
+pub fn email_body(alert: &Alert) -> String {
+    let mut body = format!(
+        "{}\n\nRule: {}\nSeverity: {}\nStatus: {}\nSince: {}\n",
+        alert.summary,
+        alert.rule,
+        alert.severity.as_str(),
+        alert.status.as_str(),
+        alert.started_at.to_rfc3339()
+    );
+    if !alert.subject.is_empty() {
+        body.push_str(&format!("Subject: {}\n", alert.subject));
+    }
+    if let Some(resolved_at) = alert.resolved_at {
+        body.push_str(&format!("Resolved: {}\n", resolved_at.to_rfc3339()));
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn timing() -> Timing {
+        Timing {
+            repeat_interval: Duration::hours(4),
+            stale_after: Duration::minutes(10),
+        }
+    }
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-03-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+            + Duration::minutes(minutes)
+    }
+
+    fn signals(error_rate: f64) -> Signals {
+        Signals {
+            instance: "api-1".to_string(),
+            error_rate: Some(error_rate),
+            ..Default::default()
+        }
+    }
+
+    fn silence(rule: Option<&str>, from: i64, to: i64) -> AlertSilence {
+        AlertSilence {
+            id: Uuid::new_v4(),
+            rule: rule.map(str::to_string),
+            subject: None,
+            reason: "maintenance".to_string(),
+            starts_at: at(from),
+            ends_at: at(to),
+            created_by: None,
+            created_at: at(from),
+        }
+    }
+
+    /// Runs one evaluation and applies it the way the service does, with
+    /// every notification going out
+    fn run(
+        now: DateTime<Utc>,
+        instance: &str,
+        signals: &Signals,
+        state: &mut Vec<Alert>,
+        silences: &[AlertSilence],
+    ) -> Vec<(String, AlertStatus)> {
+        let rules = default_rules();
+        let firings = evaluate(&rules, signals);
+        let steps = step(now, timing(), &rules, instance, firings, state, silences);
+        let mut notified = Vec::new();
+        for mut step in steps {
+            if step.notify {
+                notified.push((step.alert.fingerprint.clone(), step.alert.status));
+                if step.alert.status == AlertStatus::Firing {
+                    step.alert.last_notified_at = Some(now);
+                }
+            }
+            state.retain(|alert| alert.fingerprint != step.alert.fingerprint);
+            state.push(step.alert);
+        }
+        notified
+    }
+
+    #[test]
+    fn parses_rules_and_rejects_bad_ones() {
+        let rules = parse_rules(
+            "- name: errors\n  severity: critical\n  kind: error_rate\n  threshold: 5\n\
+             - name: breaker\n  severity: warning\n  kind: oracle_circuit_breaker\n",
+        )
+        .unwrap();
+        assert_eq!(rules[0].condition, Condition::ErrorRate { threshold: 5.0 });
+        assert_eq!(rules[1].condition, Condition::OracleCircuitBreaker);
+
+        let twice = "- {name: a, severity: info, kind: upgrade_failed}\n\
+                     - {name: a, severity: info, kind: upgrade_failed}\n";
+        assert!(parse_rules(twice).is_err());
+        assert!(parse_rules("- {name: a, severity: info, kind: sync_lag, threshold: 0}").is_err());
+        assert!(parse_rules("- {name: a, severity: info, kind: cpu}").is_err());
+    }
+
+    #[test]
+    fn fires_once_then_repeats_after_interval_and_resolves() {
+        let mut state = Vec::new();
+        let firing = vec![("high_error_rate:api-1".to_string(), AlertStatus::Firing)];
+
+        assert_eq!(run(at(0), "api-1", &signals(50.0), &mut state, &[]), firing);
+        assert!(run(at(1), "api-1", &signals(50.0), &mut state, &[]).is_empty());
+        assert_eq!(
+            run(at(240), "api-1", &signals(50.0), &mut state, &[]),
+            firing
+        );
+        assert_eq!(state[0].started_at, at(0));
+
+        assert_eq!(
+            run(at(241), "api-1", &signals(0.0), &mut state, &[]),
+            vec![("high_error_rate:api-1".to_string(), AlertStatus::Resolved)]
+        );
+        assert!(run(at(242), "api-1", &signals(0.0), &mut state, &[]).is_empty());
+    }
+
+    #[test]
+    fn silenced_alerts_notify_when_the_silence_ends() {
+        let mut state = Vec::new();
+        let silences = [silence(Some("high_error_rate"), 0, 30)];
+
+        assert!(run(at(1), "api-1", &signals(50.0), &mut state, &silences).is_empty());
+        assert_eq!(state[0].status, AlertStatus::Firing);
+        assert_eq!(
+            run(at(30), "api-1", &signals(50.0), &mut state, &silences),
+            vec![("high_error_rate:api-1".to_string(), AlertStatus::Firing)]
+        );
+
+        // Resolving while silenced says nothing
+        let everything = [silence(None, 31, 60)];
+        assert!(run(at(32), "api-1", &signals(0.0), &mut state, &everything).is_empty());
+        assert_eq!(state[0].status, AlertStatus::Resolved);
+    }
+
+    #[test]
+    fn leaves_other_instances_alerts_until_stale() {
+        let mut state = Vec::new();
+        run(at(0), "api-1", &signals(50.0), &mut state, &[]);
+
+        assert!(run(at(1), "api-2", &signals(0.0), &mut state, &[]).is_empty());
+        assert_eq!(state[0].status, AlertStatus::Firing);
+        assert_eq!(
+            run(at(10), "api-2", &signals(0.0), &mut state, &[]),
+            vec![("high_error_rate:api-1".to_string(), AlertStatus::Resolved)]
+        );
+    }
+
+    #[test]
+    fn reported_signals_fire_per_subject() {
+        let signals = Signals {
+            reported: vec![
+                ReportedSignal {
+                    kind: SIGNAL_ORACLE_CIRCUIT_BREAKER.to_string(),
+                    subject: "temp".to_string(),
+                    detail: None,
+                },
+                ReportedSignal {
+                    kind: SIGNAL_UPGRADE_FAILED.to_string(),
+                    subject: "CCONTRACT".to_string(),
+                    detail: Some("migration panicked".to_string()),
+                },
+            ],
+            sync_lag_secs: Some(60.0),
+            ..Default::default()
+        };
+        let firings = evaluate(&default_rules(), &signals);
+        assert_eq!(firings.len(), 2);
+        assert_eq!(firings[0].subject, "temp");
+        assert_eq!(
+            firings[1].summary,
+            "Upgrade of contract CCONTRACT failed: migration panicked"
+        );
+    }
+
+    #[test]
+    fn pagerduty_resolves_by_fingerprint() {
+        let mut state = Vec::new();
+        run(at(0), "api-1", &signals(50.0), &mut state, &[]);
+        let trigger = pagerduty_payload("key", "chainlogistics", &state[0]);
+        assert_eq!(trigger["event_action"], "trigger");
+        assert_eq!(trigger["payload"]["severity"], "critical");
+
+        run(at(1), "api-1", &signals(0.0), &mut state, &[]);
+        let resolve = pagerduty_payload("key", "chainlogistics", &state[0]);
+        assert_eq!(resolve["event_action"], "resolve");
+        assert_eq!(resolve["dedup_key"], trigger["dedup_key"]);
+    }
+}
@@ -0,0 +1,512 @@
+/// Prometheus metrics.
+///
+/// Counters and latency histograms are kept in process as requests, events
+/// and webhook deliveries happen, and rendered in the Prometheus text format
+/// on `GET /metrics`. Gauges describing the current state, such as database
+/// pool usage, are read at scrape time and passed in, so they are never
+/// stale. Label values come from route patterns and fixed sets, never from
+/// raw paths or user input, to keep the number of series bounded.
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+use crate::error::ErrorCode;
+
+/// Content type of the text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+const PREFIX: &str = "chainlogistics_";
+
+/// Request latency buckets, in seconds
+pub const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Buckets for how long after it happened an event was recorded, in seconds
+pub const INGEST_LAG_BUCKETS: &[f64] =
+    &[1.0, 5.0, 30.0, 60.0, 300.0, 900.0, 3600.0, 21600.0, 86400.0];
+
+/// Route label of requests that matched no route
+pub const UNMATCHED_ROUTE: &str = "(unmatched)";
+
+const METHODS: &[&str] = &["GET", "POST", "PUT", "PATCH", "DELETE", "HEAD", "OPTIONS"];
+
+lazy_static::lazy_static! {
+    /// The process-wide registry
+    pub static ref METRICS: Metrics = Metrics::default();
+}
+
+// ── Histograms ────────────────────────────────────────────────────────────────
+
+/// A cumulative histogram with fixed upper bounds
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// Observations at or below each bound, not yet cumulative
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        if let Some(index) = self.bounds.iter().position(|bound| value <= *bound) {
+            self.counts[index] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// `(upper bound, observations at or below it)`, ending with `+Inf`
+    pub fn cumulative(&self) -> Vec<(f64, u64)> {
+        let mut total = 0;
+        let mut buckets: Vec<(f64, u64)> = self
+            .bounds
+            .iter()
+            .zip(&self.counts)
+            .map(|(bound, count)| {
+                total += count;
+                (*bound, total)
+            })
+            .collect();
+        buckets.push((f64::INFINITY, self.count));
+        buckets
+    }
+}
+
+// ── Registry ──────────────────────────────────────────────────────────────────
+
+/// How a webhook delivery attempt ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WebhookOutcome {
+    Delivered,
+    /// Failed and will be retried
+    Failed,
+    /// Failed for the last time
+    Dead,
+}
+
+impl WebhookOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookOutcome::Delivered => "delivered",
+            WebhookOutcome::Failed => "failed",
+            WebhookOutcome::Dead => "dead",
+        }
+    }
+}
+
+/// A gauge read at scrape time: one value per label set
+#[derive(Debug, Clone)]
+pub struct Gauge {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub samples: Vec<(Vec<(&'static str, String)>, f64)>,
+}
+
+impl Gauge {
+    /// A gauge with a single, unlabelled value
+    pub fn single(name: &'static str, help: &'static str, value: f64) -> Self {
+        Self {
+            name,
+            help,
+            samples: vec![(Vec::new(), value)],
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Registry {
+    /// By method, route pattern and status
+    requests: BTreeMap<(&'static str, String, u16), Histogram>,
+    /// By error code name and number
+    errors: BTreeMap<(String, u16), u64>,
+    events_ingested: u64,
+    ingest_lag: Histogram,
+    webhook_deliveries: BTreeMap<WebhookOutcome, u64>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self {
+            requests: BTreeMap::new(),
+            errors: BTreeMap::new(),
+            events_ingested: 0,
+            ingest_lag: Histogram::new(INGEST_LAG_BUCKETS),
+            webhook_deliveries: BTreeMap::new(),
+        }
+    }
+}
+
+/// Server errors and request latencies so far, which the alert rules compare
+/// between evaluations
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestTotals {
+    /// Responses with a 5xx status
+    pub server_errors: u64,
+    /// Requests per `LATENCY_BUCKETS` bucket across routes, not cumulative;
+    /// the last entry counts requests slower than the last bound
+    pub latency_buckets: Vec<u64>,
+}
+
+impl RequestTotals {
+    pub fn server_errors_since(&self, earlier: &RequestTotals) -> u64 {
+        self.server_errors.saturating_sub(earlier.server_errors)
+    }
+
+    /// Quantile `q` of the latency, in seconds, of requests served since
+    /// `earlier`, interpolated within its bucket. Requests slower than the
+    /// last bound count as the last bound. None when there were none.
+    pub fn latency_quantile(&self, earlier: &RequestTotals, q: f64) -> Option<f64> {
+        let deltas: Vec<u64> = self
+            .latency_buckets
+            .iter()
+            .enumerate()
+            .map(|(i, count)| {
+                count.saturating_sub(earlier.latency_buckets.get(i).copied().unwrap_or(0))
+            })
+            .collect();
+        let total: u64 = deltas.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let rank = q.clamp(0.0, 1.0) * total as f64;
+        let mut below = 0;
+        for (i, count) in deltas.iter().enumerate() {
+            if *count > 0 && (below + count) as f64 >= rank {
+                let Some(upper) = LATENCY_BUCKETS.get(i) else {
+                    break;
+                };
+                let lower = if i == 0 { 0.0 } else { LATENCY_BUCKETS[i - 1] };
+                let within = (rank - below as f64) / *count as f64;
+                return Some(lower + (upper - lower) * within);
+            }
+            below += count;
+        }
+        LATENCY_BUCKETS.last().copied()
+    }
+}
+
+/// Counters and histograms of the process
+#[derive(Debug, Default)]
+pub struct Metrics {
+    registry: Mutex<Registry>,
+}
+
+impl Metrics {
+    fn registry(&self) -> std::sync::MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records a served request. `route` is the matched route pattern.
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, seconds: f64) {
+        let method = METHODS
+            .iter()
+            .find(|m| m.eq_ignore_ascii_case(method))
+            .copied()
+            .unwrap_or("OTHER");
+        self.registry()
+            .requests
+            .entry((method, route.to_string(), status))
+            .or_insert_with(|| Histogram::new(LATENCY_BUCKETS))
+            .observe(seconds);
+    }
+
+    pub fn request_totals(&self) -> RequestTotals {
+        let registry = self.registry();
+        let mut totals = RequestTotals {
+            server_errors: 0,
+            latency_buckets: vec![0; LATENCY_BUCKETS.len() + 1],
+        };
+        for ((_, _, status), histogram) in &registry.requests {
+            if *status >= 500 {
+                totals.server_errors += histogram.count;
+            }
+            let mut counted = 0;
+            for (total, count) in totals.latency_buckets.iter_mut().zip(&histogram.counts) {
+                *total += count;
+                counted += count;
+            }
+            totals.latency_buckets[LATENCY_BUCKETS.len()] += histogram.count - counted;
+        }
+        totals
+    }
+
+    /// Counts an error response by its error code
+    pub fn count_error(&self, code: ErrorCode) {
+        let name = serde_json::to_value(code)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_else(|| format!("{:?}", code));
+        *self
+            .registry()
+            .errors
+            .entry((name, code as u16))
+            .or_insert(0) += 1;
+    }
+
+    /// Counts a recorded tracking event, `lag_seconds` after it happened
+    pub fn count_event_ingested(&self, lag_seconds: f64) {
+        let mut registry = self.registry();
+        registry.events_ingested += 1;
+        registry.ingest_lag.observe(lag_seconds.max(0.0));
+    }
+
+    pub fn count_webhook_delivery(&self, outcome: WebhookOutcome) {
+        *self
+            .registry()
+            .webhook_deliveries
+            .entry(outcome)
+            .or_insert(0) += 1;
+    }
+
+    /// Everything in the text exposition format, followed by `gauges`
+    pub fn render(&self, gauges: &[Gauge]) -> String {
+        let registry = self.registry();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "http_request_duration_seconds",
+            "HTTP request latency by route and status",
+            "histogram",
+        );
+        for ((method, route, status), histogram) in &registry.requests {
+            let labels = [
+                ("method", method.to_string()),
+                ("route", route.clone()),
+                ("status", status.to_string()),
+            ];
+            write_histogram(
+                &mut out,
+                "http_request_duration_seconds",
+                &labels,
+                histogram,
+            );
+        }
+
+        header(
+            &mut out,
+            "errors_total",
+            "Error responses by error code",
+            "counter",
+        );
+        for ((name, number), count) in &registry.errors {
+            let labels = [("code", name.clone()), ("number", number.to_string())];
+            sample(&mut out, "errors_total", &labels, *count as f64);
+        }
+
+        header(
+            &mut out,
+            "events_ingested_total",
+            "Tracking events recorded",
+            "counter",
+        );
+        sample(
+            &mut out,
+            "events_ingested_total",
+            &[],
+            registry.events_ingested as f64,
+        );
+
+        header(
+            &mut out,
+            "event_ingest_lag_seconds",
+            "Time from an event happening to it being recorded",
+            "histogram",
+        );
+        write_histogram(
+            &mut out,
+            "event_ingest_lag_seconds",
+            &[],
+            &registry.ingest_lag,
+        );
+
+        header(
+            &mut out,
+            "webhook_deliveries_total",
+            "Webhook delivery attempts by outcome",
+            "counter",
+        );
+        for outcome in [
+            WebhookOutcome::Delivered,
+            WebhookOutcome::Failed,
+            WebhookOutcome::Dead,
+        ] {
+            let count = registry
+                .webhook_deliveries
+                .get(&outcome)
+                .copied()
+                .unwrap_or(0);
+            let labels = [("outcome", outcome.as_str().to_string())];
+            sample(&mut out, "webhook_deliveries_total", &labels, count as f64);
+        }
+        drop(registry);
+
+        for gauge in gauges {
+            header(&mut out, gauge.name, gauge.help, "gauge");
+            for (labels, value) in &gauge.samples {
+                sample(&mut out, gauge.name, labels, *value);
+            }
+        }
+        out
+    }
+}
+
+// ── Text format ───────────────────────────────────────────────────────────────
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {}{} {}", PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}{} {}", PREFIX, name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, String)], value: f64) {
+    let _ = write!(out, "{}{}", PREFIX, name);
+    if !labels.is_empty() {
+        out.push('{');
+        for (i, (label, value)) in labels.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{}=\"{}\"", label, escape(value));
+        }
+        out.push('}');
+    }
+    let _ = writeln!(out, " {}", format_value(value));
+}
+
+fn write_histogram(out: &mut String, name: &str, labels: &[(&str, String)], histogram: &Histogram) {
+    let bucket_name = format!("{}_bucket", name);
+    for (bound, count) in histogram.cumulative() {
+        let mut bucket_labels = labels.to_vec();
+        bucket_labels.push(("le", format_value(bound)));
+        sample(out, &bucket_name, &bucket_labels, count as f64);
+    }
+    sample(out, &format!("{}_sum", name), labels, histogram.sum);
+    sample(
+        out,
+        &format!("{}_count", name),
+        labels,
+        histogram.count as f64,
+    );
+}
+
+/// Label values escape backslashes, quotes and line breaks
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value == f64::INFINITY {
+        "+Inf".to_string()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_string()
+    } else if value.is_nan() {
+        "NaN".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histograms_are_cumulative() {
+        let mut histogram = Histogram::new(&[0.1, 1.0]);
+        histogram.observe(0.05);
+        histogram.observe(0.1);
+        histogram.observe(0.5);
+        histogram.observe(30.0);
+        assert_eq!(
+            histogram.cumulative(),
+            vec![(0.1, 2), (1.0, 3), (f64::INFINITY, 4)]
+        );
+        assert_eq!(histogram.count, 4);
+        assert!((histogram.sum - 30.65).abs() < 1e-9);
+    }
+
+    #[test]
+    fn renders_text_format() {
+        let metrics = Metrics::default();
+        metrics.observe_request("get", "/api/v1/products/:id", 200, 0.02);
+        metrics.observe_request("GET", "/api/v1/products/:id", 200, 0.3);
+        metrics.observe_request("BREW", UNMATCHED_ROUTE, 404, 0.001);
+        metrics.count_error(ErrorCode::RateLimitExceeded);
+        metrics.count_error(ErrorCode::RateLimitExceeded);
+        metrics.count_event_ingested(12.0);
+        metrics.count_webhook_delivery(WebhookOutcome::Dead);
+
+        let text = metrics.render(&[Gauge::single("redis_up", "Whether Redis answers", 1.0)]);
+        assert!(text.contains("# TYPE chainlogistics_http_request_duration_seconds histogram\n"));
+        assert!(text.contains(
+            "chainlogistics_http_request_duration_seconds_bucket{method=\"GET\",route=\"/api/v1/products/:id\",status=\"200\",le=\"0.025\"} 1\n"
+        ));
+        assert!(text.contains(
+            "chainlogistics_http_request_duration_seconds_bucket{method=\"GET\",route=\"/api/v1/products/:id\",status=\"200\",le=\"+Inf\"} 2\n"
+        ));
+        assert!(text.contains(
+            "chainlogistics_http_request_duration_seconds_count{method=\"GET\",route=\"/api/v1/products/:id\",status=\"200\"} 2\n"
+        ));
+        assert!(text.contains("method=\"OTHER\",route=\"(unmatched)\",status=\"404\""));
+        assert!(text.contains(
+            "chainlogistics_errors_total{code=\"RATE_LIMIT_EXCEEDED\",number=\"1300\"} 2\n"
+        ));
+        assert!(text.contains("chainlogistics_events_ingested_total 1\n"));
+        assert!(text.contains("chainlogistics_event_ingest_lag_seconds_bucket{le=\"30\"} 1\n"));
+        assert!(text.contains("chainlogistics_webhook_deliveries_total{outcome=\"dead\"} 1\n"));
+        assert!(text.contains("chainlogistics_webhook_deliveries_total{outcome=\"failed\"} 0\n"));
+        assert!(text.ends_with("# TYPE chainlogistics_redis_up gauge\nchainlogistics_redis_up 1\n"));
+    }
+
+    #[test]
+    fn latency_quantile_covers_requests_since_earlier_totals() {
+        let metrics = Metrics::default();
+        metrics.observe_request("GET", "/a", 200, 3.0);
+        let earlier = metrics.request_totals();
+
+        for _ in 0..9 {
+            metrics.observe_request("GET", "/a", 200, 0.004);
+        }
+        metrics.observe_request("POST", "/b", 503, 0.2);
+        let now = metrics.request_totals();
+
+        assert_eq!(now.server_errors_since(&earlier), 1);
+        // Nine of ten in the first bucket, the tenth between 0.1 and 0.25
+        let median = now.latency_quantile(&earlier, 0.5).unwrap();
+        assert!((median - 0.005 * 5.0 / 9.0).abs() < 1e-12);
+        assert_eq!(now.latency_quantile(&earlier, 1.0), Some(0.25));
+        assert_eq!(now.latency_quantile(&now, 0.95), None);
+
+        metrics.observe_request("GET", "/a", 200, 60.0);
+        assert_eq!(
+            metrics.request_totals().latency_quantile(&now, 0.95),
+            Some(10.0)
+        );
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        let mut out = String::new();
+        sample(
+            &mut out,
+            "x",
+            &[("route", "a\"b\\c\nd".to_string())],
+            f64::NAN,
+        );
+        assert_eq!(out, "chainlogistics_x{route=\"a\\\"b\\\\c\\nd\"} NaN\n");
+    }
+}
@@ -0,0 +1,194 @@
+/// Infrastructure sampling.
+///
+/// A background task that, on a fixed interval, reads this process's memory
+/// and CPU use from `/proc`, the database pool's size and idle count, the
+/// round trip of a Redis PING and the number of open websocket connections,
+/// and records them with the `InfrastructureMonitor`. Memory and CPU are left
+/// at zero where `/proc` is not available.
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::monitoring::{InfrastructureMetrics, InfrastructureMonitor};
+use crate::websocket::ConnectionManager;
+
+/// How long a sample waits for Redis to answer
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+// ── /proc parsing ─────────────────────────────────────────────────────────────
+
+/// Resident set size in KiB, from the `VmRSS` line of `/proc/self/status`
+pub fn parse_rss_kb(status: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|kb| kb.parse().ok())
+}
+
+/// User plus system clock ticks used by the process, from `/proc/self/stat`
+pub fn parse_process_ticks(stat: &str) -> Option<u64> {
+    // The command name is in parentheses and may itself contain spaces or
+    // parentheses, so fields are counted from the last closing one. The
+    // first field after it is the state (field 3); utime and stime are
+    // fields 14 and 15.
+    let (_, rest) = stat.rsplit_once(')')?;
+    let mut fields = rest.split_whitespace().skip(11);
+    let utime: u64 = fields.next()?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    Some(utime + stime)
+}
+
+/// Clock ticks spent by all CPUs, from the `cpu` line of `/proc/stat`
+pub fn parse_total_ticks(stat: &str) -> Option<u64> {
+    let line = stat.lines().find(|line| line.starts_with("cpu "))?;
+    // user nice system idle iowait irq softirq steal; guest time is already
+    // counted in user and nice
+    line.split_whitespace()
+        .skip(1)
+        .take(8)
+        .map(|ticks| ticks.parse::<u64>().ok())
+        .sum()
+}
+
+/// Process and machine CPU time at one moment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuTimes {
+    pub process: u64,
+    pub total: u64,
+}
+
+/// Share of the machine's CPU time the process used between two readings,
+/// as a percentage. None when no time passed between them.
+pub fn cpu_percent(previous: CpuTimes, current: CpuTimes) -> Option<f64> {
+    let total = current.total.checked_sub(previous.total)?;
+    if total == 0 {
+        return None;
+    }
+    let process = current.process.saturating_sub(previous.process);
+    Some((process as f64 / total as f64 * 100.0).min(100.0))
+}
+
+// ── Sampling ──────────────────────────────────────────────────────────────────
+
+async fn read_rss_mb() -> Option<u64> {
+    let status = tokio::fs::read_to_string("/proc/self/status").await.ok()?;
+    parse_rss_kb(&status).map(|kb| kb / 1024)
+}
+
+async fn read_cpu_times() -> Option<CpuTimes> {
+    let process = tokio::fs::read_to_string("/proc/self/stat").await.ok()?;
+    let total = tokio::fs::read_to_string("/proc/stat").await.ok()?;
+    Some(CpuTimes {
+        process: parse_process_ticks(&process)?,
+        total: parse_total_ticks(&total)?,
+    })
+}
+
+/// Round trip of a PING in milliseconds, or None when Redis did not answer
+async fn ping_redis(client: &redis::Client) -> Option<f64> {
+    let started = Instant::now();
+    let ping = tokio::time::timeout(PING_TIMEOUT, async {
+        let mut conn = client.get_multiplexed_tokio_connection().await?;
+        redis::cmd("PING").query_async::<_, String>(&mut conn).await
+    })
+    .await;
+    match ping {
+        Ok(Ok(_)) => Some(started.elapsed().as_secs_f64() * 1000.0),
+        _ => None,
+    }
+}
+
+/// Samples infrastructure usage every `interval` and records it with `monitor`
+pub fn start(
+    monitor: InfrastructureMonitor,
+    pool: PgPool,
+    redis_client: redis::Client,
+    connections: ConnectionManager,
+    interval: Duration,
+) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut previous_cpu = read_cpu_times().await;
+        if previous_cpu.is_none() {
+            tracing::info!("/proc is not readable; memory and CPU usage will not be sampled");
+        }
+
+        loop {
+            ticker.tick().await;
+
+            let cpu = read_cpu_times().await;
+            let cpu_usage_percent = match (previous_cpu, cpu) {
+                (Some(previous), Some(current)) => cpu_percent(previous, current),
+                _ => None,
+            };
+            previous_cpu = cpu;
+
+            let size = pool.size();
+            let idle = pool.num_idle() as u32;
+            let max = pool.options().get_max_connections();
+            let in_use = size.saturating_sub(idle);
+            let redis_ping_ms = ping_redis(&redis_client).await;
+
+            monitor
+                .record_sample(InfrastructureMetrics {
+                    db_pool_usage: if max > 0 {
+                        in_use as f64 / max as f64
+                    } else {
+                        0.0
+                    },
+                    db_pool_size: size,
+                    db_pool_idle: idle,
+                    redis_connected: redis_ping_ms.is_some(),
+                    redis_ping_ms,
+                    memory_usage_mb: read_rss_mb().await.unwrap_or(0),
+                    cpu_usage_percent: cpu_usage_percent.unwrap_or(0.0),
+                    active_connections: connections.connection_count().await as u64,
+                    last_updated: Utc::now(),
+                })
+                .await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rss_kb() {
+        let status =
+            "Name:\tchainlogistics\nVmPeak:\t  204800 kB\nVmRSS:\t   51200 kB\nThreads:\t8\n";
+        assert_eq!(parse_rss_kb(status), Some(51200));
+        assert_eq!(parse_rss_kb("Name:\tkthreadd\n"), None);
+    }
+
+    #[test]
+    fn test_parse_process_ticks_with_odd_command_name() {
+        let stat = "4242 (my (odd) proc) S 1 4242 4242 0 -1 4194560 1200 0 0 0 350 125 0 0 20 0 8 0 1000 1000000 12800";
+        assert_eq!(parse_process_ticks(stat), Some(475));
+        assert_eq!(parse_process_ticks("4242 (short) S 1"), None);
+    }
+
+    #[test]
+    fn test_parse_total_ticks_ignores_guest_time() {
+        let stat = "cpu  100 10 50 800 20 5 5 10 40 0\ncpu0 50 5 25 400 10 2 3 5 20 0\n";
+        assert_eq!(parse_total_ticks(stat), Some(1000));
+    }
+
+    #[test]
+    fn test_cpu_percent() {
+        let previous = CpuTimes {
+            process: 100,
+            total: 10_000,
+        };
+        let current = CpuTimes {
+            process: 150,
+            total: 10_200,
+        };
+        assert_eq!(cpu_percent(previous, current), Some(25.0));
+        assert_eq!(cpu_percent(current, current), None);
+    }
+}
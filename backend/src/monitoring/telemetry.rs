@@ -0,0 +1,455 @@
+/// Distributed tracing.
+///
+/// Sets up logging and, when an exporter is configured, OpenTelemetry: spans
+/// are exported over OTLP/HTTP, or written as JSON to stdout or a file for
+/// local use. Traces are joined across services with W3C `traceparent`
+/// headers: read from incoming requests, and sent on webhook deliveries and
+/// Soroban RPC calls. sqlx logs every statement it runs; those logs are turned
+/// into client spans of whatever span ran the statement, and Redis commands
+/// get spans from `redis_span`, so one slow request can be followed through
+/// the database and cache.
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use axum::{
+    body::Body,
+    extract::MatchedPath,
+    http::{HeaderMap, Request, Response},
+};
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::trace::{SpanKind, TraceContextExt, Tracer as _, TracerProvider as _};
+use opentelemetry::{Context as OtelContext, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, Tracer, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Span, Subscriber};
+use tracing_opentelemetry::{OpenTelemetrySpanExt, OtelData, PreSampledTracer};
+use tracing_subscriber::filter::{EnvFilter, Targets};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+use crate::config::{TelemetryConfig, TraceExporter};
+
+pub const TRACEPARENT: &str = "traceparent";
+/// Target sqlx logs executed statements under
+const QUERY_TARGET: &str = "sqlx::query";
+
+/// Keeps the tracer provider alive; dropping it flushes the spans not yet
+/// exported
+pub struct Telemetry {
+    _provider: Option<TracerProvider>,
+}
+
+/// Installs the global subscriber: logs filtered by `RUST_LOG`, as JSON or
+/// human-readable, plus span export when `config` names an exporter
+pub fn init(config: &TelemetryConfig, json_logs: bool) -> Result<Telemetry, String> {
+    let logs = if json_logs {
+        tracing_subscriber::fmt::layer()
+            .json()
+            .flatten_event(true)
+            .boxed()
+    } else {
+        tracing_subscriber::fmt::layer().boxed()
+    };
+    let registry =
+        tracing_subscriber::registry().with(logs.with_filter(EnvFilter::from_default_env()));
+
+    let Some(provider) = tracer_provider(config)? else {
+        registry.init();
+        return Ok(Telemetry { _provider: None });
+    };
+    let tracer = provider.tracer("chainlogistics-backend");
+    registry
+        .with(
+            tracing_opentelemetry::layer()
+                .with_tracer(tracer.clone())
+                .with_filter(Targets::new().with_default(Level::INFO)),
+        )
+        .with(QuerySpans { tracer }.with_filter(query_filter()))
+        .init();
+    tracing::info!(exporter = ?config.exporter, "Exporting traces");
+    Ok(Telemetry {
+        _provider: Some(provider),
+    })
+}
+
+fn tracer_provider(config: &TelemetryConfig) -> Result<Option<TracerProvider>, String> {
+    let trace_config = opentelemetry_sdk::trace::Config::default()
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sample_ratio,
+        ))))
+        .with_resource(Resource::new(vec![
+            KeyValue::new("service.name", config.service_name.clone()),
+            KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+        ]));
+    let builder = TracerProvider::builder().with_config(trace_config);
+
+    let provider = match config.exporter {
+        TraceExporter::None => return Ok(None),
+        TraceExporter::Otlp => {
+            let exporter = opentelemetry_otlp::new_exporter()
+                .http()
+                .with_endpoint(config.otlp_endpoint.trim_end_matches('/'))
+                .build_span_exporter()
+                .map_err(|e| format!("Invalid OTLP exporter settings: {}", e))?;
+            builder.with_batch_exporter(exporter, runtime::Tokio)
+        }
+        TraceExporter::Stdout => builder.with_batch_exporter(
+            opentelemetry_stdout::SpanExporter::builder()
+                .with_writer(std::io::stdout())
+                .build(),
+            runtime::Tokio,
+        ),
+        TraceExporter::File => {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&config.file_path)
+                .map_err(|e| format!("Could not open trace file {}: {}", config.file_path, e))?;
+            builder.with_batch_exporter(
+                opentelemetry_stdout::SpanExporter::builder()
+                    .with_writer(file)
+                    .build(),
+                runtime::Tokio,
+            )
+        }
+    };
+    Ok(Some(provider.build()))
+}
+
+// ── Propagation ───────────────────────────────────────────────────────────────
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// The remote span a `traceparent` names, if it is valid
+fn remote_context(extractor: &dyn Extractor) -> Option<OtelContext> {
+    let context = TraceContextPropagator::new().extract(extractor);
+    context.span().span_context().is_valid().then_some(context)
+}
+
+/// `traceparent`, and `tracestate` when there is one, of the current span
+pub fn trace_headers() -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+    TraceContextPropagator::new().inject_context(&Span::current().context(), &mut headers);
+    headers
+}
+
+/// `traceparent` of the current span, to continue its trace later, or None
+/// when it is not being traced
+pub fn traceparent() -> Option<String> {
+    trace_headers().remove(TRACEPARENT)
+}
+
+/// Adds the current span's trace headers to an outbound request
+pub fn inject(mut request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    for (name, value) in trace_headers() {
+        request = request.header(name, value);
+    }
+    request
+}
+
+/// Makes `span` a child of the span a stored `traceparent` names
+pub fn continue_trace(span: &Span, traceparent: Option<&str>) {
+    let Some(traceparent) = traceparent else {
+        return;
+    };
+    let carrier = HashMap::from([(TRACEPARENT.to_string(), traceparent.to_string())]);
+    if let Some(context) = remote_context(&carrier) {
+        span.set_parent(context);
+    }
+}
+
+// ── Spans ─────────────────────────────────────────────────────────────────────
+
+/// Span for an incoming request, continuing the caller's trace when it sent
+/// a valid `traceparent`. Named after the matched route, not the path, so
+/// that span names stay few.
+pub fn request_span(request: &Request<Body>) -> Span {
+    let method = request.method();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str());
+    let name = match route {
+        Some(route) => format!("{} {}", method, route),
+        None => method.to_string(),
+    };
+    let span = tracing::info_span!(
+        "http_request",
+        otel.name = %name,
+        otel.kind = "server",
+        otel.status_code = tracing::field::Empty,
+        http.request.method = %method,
+        http.route = route,
+        url.path = %request.uri().path(),
+        http.response.status_code = tracing::field::Empty,
+    );
+    if let Some(context) = remote_context(&HeaderExtractor(request.headers())) {
+        span.set_parent(context);
+    }
+    span
+}
+
+/// Records the response status on the request span
+pub fn record_response(response: &Response<Body>, _latency: Duration, span: &Span) {
+    let status = response.status();
+    span.record("http.response.status_code", status.as_u16());
+    if status.is_server_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+}
+
+/// Span for one Redis command; instrument the command's future with it
+pub fn redis_span(command: &'static str) -> Span {
+    tracing::info_span!(
+        "redis",
+        otel.name = command,
+        otel.kind = "client",
+        db.system = "redis",
+        db.operation = command,
+    )
+}
+
+// ── Query spans ───────────────────────────────────────────────────────────────
+
+/// sqlx logs statements at debug; spans still need to reach `QuerySpans` so
+/// it can find each statement's parent
+fn query_filter() -> Targets {
+    Targets::new()
+        .with_default(Level::INFO)
+        .with_target(QUERY_TARGET, Level::DEBUG)
+}
+
+/// What sqlx logs about a finished statement
+#[derive(Debug, Default)]
+struct QueryFields {
+    summary: String,
+    statement: String,
+    rows_affected: u64,
+    rows_returned: u64,
+    elapsed_secs: f64,
+}
+
+impl Visit for QueryFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "summary" => self.summary = value.to_string(),
+            "db.statement" => self.statement = value.trim().to_string(),
+            _ => {}
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "rows_affected" => self.rows_affected = value,
+            "rows_returned" => self.rows_returned = value,
+            _ => {}
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if field.name() == "elapsed_secs" {
+            self.elapsed_secs = value;
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+/// Records each statement sqlx logs as a client span of the span that ran
+/// it, ending now and as long as sqlx timed it. Statements run outside any
+/// span, such as from background tasks between passes, are not recorded.
+struct QuerySpans {
+    tracer: Tracer,
+}
+
+impl<S> Layer<S> for QuerySpans
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if event.metadata().target() != QUERY_TARGET {
+            return;
+        }
+        let Some(parent) = ctx.event_span(event) else {
+            return;
+        };
+        let parent_context = {
+            let mut extensions = parent.extensions_mut();
+            let Some(data) = extensions.get_mut::<OtelData>() else {
+                return;
+            };
+            self.tracer.sampled_context(data)
+        };
+
+        let mut fields = QueryFields::default();
+        event.record(&mut fields);
+        let end = SystemTime::now();
+        let start = Duration::try_from_secs_f64(fields.elapsed_secs)
+            .ok()
+            .and_then(|elapsed| end.checked_sub(elapsed))
+            .unwrap_or(end);
+        // sqlx only spells out statements longer than their summary
+        let statement = if fields.statement.is_empty() {
+            fields.summary.clone()
+        } else {
+            fields.statement
+        };
+
+        let mut span = self
+            .tracer
+            .span_builder(fields.summary)
+            .with_kind(SpanKind::Client)
+            .with_start_time(start)
+            .with_attributes(vec![
+                KeyValue::new("db.system", "postgresql"),
+                KeyValue::new("db.statement", statement),
+                KeyValue::new("db.rows_affected", fields.rows_affected as i64),
+                KeyValue::new("db.rows_returned", fields.rows_returned as i64),
+            ])
+            .start_with_context(&self.tracer, &parent_context);
+        opentelemetry::trace::Span::end_with_timestamp(&mut span, end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use futures::future::BoxFuture;
+    use opentelemetry::trace::TraceId;
+    use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+
+    #[derive(Debug, Clone, Default)]
+    struct Collected(Arc<Mutex<Vec<SpanData>>>);
+
+    impl SpanExporter for Collected {
+        fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+            self.0.lock().unwrap().extend(batch);
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    /// Runs `f` with the same layers `init` installs, exporting into memory
+    fn traced<T>(f: impl FnOnce() -> T) -> (T, Vec<SpanData>) {
+        let collected = Collected::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(collected.clone())
+            .build();
+        let tracer = provider.tracer("test");
+        let subscriber = tracing_subscriber::registry()
+            .with(
+                tracing_opentelemetry::layer()
+                    .with_tracer(tracer.clone())
+                    .with_filter(Targets::new().with_default(Level::INFO)),
+            )
+            .with(QuerySpans { tracer }.with_filter(query_filter()));
+        let result = tracing::subscriber::with_default(subscriber, f);
+        provider.force_flush();
+        let spans = collected.0.lock().unwrap().clone();
+        (result, spans)
+    }
+
+    const REMOTE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn test_request_span_continues_incoming_trace() {
+        let request = Request::builder()
+            .uri("/api/v1/products")
+            .header(TRACEPARENT, REMOTE)
+            .body(Body::empty())
+            .unwrap();
+        let (outgoing, spans) = traced(|| {
+            let span = request_span(&request);
+            let _entered = span.enter();
+            traceparent()
+        });
+
+        let trace_id = TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].span_context.trace_id(), trace_id);
+        assert_eq!(spans[0].parent_span_id.to_string(), "00f067aa0ba902b7");
+        // Calls made while handling it carry the same trace on
+        let outgoing = outgoing.unwrap();
+        assert!(outgoing.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+        assert!(!outgoing.contains("00f067aa0ba902b7"));
+    }
+
+    #[test]
+    fn test_invalid_traceparent_starts_new_trace() {
+        let request = Request::builder()
+            .uri("/health")
+            .header(
+                TRACEPARENT,
+                "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            )
+            .body(Body::empty())
+            .unwrap();
+        let (_, spans) = traced(|| drop(request_span(&request).entered()));
+        assert_eq!(spans.len(), 1);
+        assert!(spans[0].span_context.is_valid());
+        assert_eq!(
+            spans[0].parent_span_id,
+            opentelemetry::trace::SpanId::INVALID
+        );
+    }
+
+    #[test]
+    fn test_logged_statements_become_child_spans() {
+        let (_, spans) = traced(|| {
+            let span = tracing::info_span!("create_product");
+            continue_trace(&span, Some(REMOTE));
+            span.in_scope(|| {
+                tracing::debug!(
+                    target: "sqlx::query",
+                    summary = "INSERT INTO products (id, …",
+                    db.statement = "\n\nINSERT INTO products (id, name)\nVALUES ($1, $2)\n",
+                    rows_affected = 1_u64,
+                    rows_returned = 0_u64,
+                    elapsed_secs = 0.25,
+                )
+            });
+            drop(span);
+            // Not part of any traced operation
+            tracing::debug!(target: "sqlx::query", summary = "SELECT 1", elapsed_secs = 0.001);
+        });
+
+        assert_eq!(spans.len(), 2);
+        let query = spans
+            .iter()
+            .find(|span| span.name == "INSERT INTO products (id, …")
+            .unwrap();
+        let parent = spans
+            .iter()
+            .find(|span| span.name == "create_product")
+            .unwrap();
+        assert_eq!(query.span_kind, SpanKind::Client);
+        assert_eq!(query.parent_span_id, parent.span_context.span_id());
+        assert_eq!(
+            query.span_context.trace_id(),
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap()
+        );
+        let took = query.end_time.duration_since(query.start_time).unwrap();
+        assert!((took.as_secs_f64() - 0.25).abs() < 0.01);
+        assert!(query
+            .attributes
+            .iter()
+            .any(|kv| kv.key.as_str() == "db.statement"
+                && kv.value.as_str() == "INSERT INTO products (id, name)\nVALUES ($1, $2)"));
+    }
+}
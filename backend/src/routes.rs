@@ -1,44 +1,89 @@
 use axum::{Router, routing::{get, post, put, delete}, middleware};
 use super::AppState;
 use crate::models::UserRole;
-use crate::middleware::auth::{jwt_auth, api_key_auth, require_role, require_admin};
+use crate::middleware::auth::{jwt_auth, jwt_auth_allow_unenrolled, jwt_or_api_key_auth, bearer_from_query, api_key_auth, require_role, require_admin, require_step_up, read_only_auditors};
 
 pub mod analytics;
 
+// Within each group, layers run bottom-up: authentication first, so rate
+// limits apply per API key or user, then the rest
 pub fn api_routes() -> Router<AppState> {
     Router::new()
         .nest("/api/v1", public_api_routes())
-        .nest("/api/v1/admin", admin_api_routes())
+        .nest("/api/v1/auth", auth_routes())
+        .nest("/api/v1/admin", admin_api_routes().merge(product_write_routes()))
         .nest("/api/v1/analytics", analytics_routes())
         .nest("/api/v1/carbon", carbon_routes())
+        .nest("/api/v1/digital-twins", digital_twin_routes())
         .nest("/api/v1/keys", key_management_routes())
+        .nest("/api/v1/organizations", organization_routes())
         .nest("/api/v1/monitoring", monitoring_routes())
+        .nest("/api/v1/ws", websocket_routes())
+        .nest("/api/v1/webhooks", webhook_routes())
 }
 
 fn public_api_routes() -> Router<AppState> {
     Router::new()
         .route("/products", get(crate::handlers::product::list_products))
         .route("/products/:id", get(crate::handlers::product::get_product))
+        .route("/products/:id/origin-plots", get(crate::handlers::eudr::list_origin_plots))
+        .route("/products/:id/lineage", get(crate::handlers::eudr::get_product_lineage))
+        .route("/products/:id/access", get(crate::handlers::access::list_product_access))
         .route("/events", get(crate::handlers::event::list_events))
         .route("/events/:id", get(crate::handlers::event::get_event))
+        .route("/events/:id/location", get(crate::handlers::location::get_event_location))
+        .route("/events/nearby", get(crate::handlers::location::events_nearby))
+        .route("/events/within", get(crate::handlers::location::events_in_bounding_box))
+        .route("/locations", get(crate::handlers::location::list_known_locations))
+        .route("/locations/resolve", get(crate::handlers::location::resolve_location))
+        .route("/epcis/products/:product_id", get(crate::handlers::epcis::export_product))
+        .route("/epcis/events", get(crate::handlers::epcis::export_events))
         .route("/stats", get(crate::handlers::stats::get_stats))
         .route("/transactions", get(crate::handlers::financial::list_transactions))
         .route("/transactions/:id", get(crate::handlers::financial::get_transaction))
         .route("/compliance/check", post(crate::handlers::compliance::check_compliance)
             .layer(middleware::from_fn(require_role(vec![UserRole::Inspector, UserRole::Administrator]))))
+        .route("/compliance/rules", get(crate::handlers::compliance::list_compliance_rules)
+            .layer(middleware::from_fn(require_role(vec![UserRole::Inspector, UserRole::Auditor, UserRole::Administrator]))))
+        .route("/compliance/rules/:rule_id/versions", get(crate::handlers::compliance::list_compliance_rule_versions)
+            .layer(middleware::from_fn(require_role(vec![UserRole::Inspector, UserRole::Auditor, UserRole::Administrator]))))
         .route("/compliance/report/:product_id", get(crate::handlers::compliance::get_compliance_report)
             .layer(middleware::from_fn(require_role(vec![UserRole::Auditor, UserRole::Administrator]))))
+        .route("/compliance/report/:product_id/evaluate", post(crate::handlers::compliance::evaluate_product_compliance)
+            .layer(middleware::from_fn(require_role(vec![UserRole::Inspector, UserRole::Auditor, UserRole::Administrator]))))
+        .route("/compliance/eudr/:product_id/statement", get(crate::handlers::eudr::export_due_diligence_statement)
+            .layer(middleware::from_fn(require_role(vec![UserRole::Supplier, UserRole::Auditor, UserRole::Administrator]))))
         .route("/audit/report", get(crate::handlers::compliance::generate_audit_report)
             .layer(middleware::from_fn(require_role(vec![UserRole::Auditor, UserRole::Administrator]))))
-        .layer(middleware::from_fn(api_key_auth))
+        .route("/audit/verify", get(crate::handlers::audit::verify_audit_log)
+            .layer(middleware::from_fn(require_role(vec![UserRole::Auditor, UserRole::Administrator]))))
+        .layer(middleware::from_fn(crate::middleware::audit::audit_log))
         .layer(middleware::from_fn(crate::middleware::rate_limit::rate_limit_middleware))
+        .layer(middleware::from_fn(api_key_auth))
 }
 
-fn admin_api_routes() -> Router<AppState> {
+// Writes on products and their events. Open to every role that can own or be
+// granted access to a product; handlers check ownership and grants. API keys
+// with a write scope work here too, e.g. scanners holding only `events:write`.
+fn product_write_routes() -> Router<AppState> {
     Router::new()
-        .route("/products", post(crate::handlers::product::create_product))
+        .route("/products", post(crate::handlers::product::create_product)
+            .layer(middleware::from_fn(require_role(vec![UserRole::Supplier, UserRole::Administrator]))))
         .route("/products/:id", put(crate::handlers::product::update_product).delete(crate::handlers::product::delete_product))
+        .route("/products/:id/access", post(crate::handlers::access::grant_product_access))
+        .route("/products/:id/access/:actor_address", delete(crate::handlers::access::revoke_product_access))
+        .route("/products/:id/origin-plots", post(crate::handlers::eudr::add_origin_plot))
+        .route("/products/:id/origin-plots/:plot_id", delete(crate::handlers::eudr::delete_origin_plot))
         .route("/events", post(crate::handlers::event::create_event)
+            .layer(middleware::from_fn(require_role(vec![UserRole::Supplier, UserRole::Carrier, UserRole::Inspector, UserRole::Administrator]))))
+        .layer(middleware::from_fn(crate::middleware::audit::audit_log))
+        .layer(middleware::from_fn(crate::middleware::rate_limit::rate_limit_middleware))
+        .layer(middleware::from_fn(jwt_or_api_key_auth))
+}
+
+fn admin_api_routes() -> Router<AppState> {
+    Router::new()
+        .route("/epcis/import", post(crate::handlers::epcis::import_document)
             .layer(middleware::from_fn(require_role(vec![UserRole::Supplier, UserRole::Carrier, UserRole::Administrator]))))
         .route("/transactions", post(crate::handlers::financial::create_transaction)
             .layer(middleware::from_fn(require_role(vec![UserRole::Supplier, UserRole::Administrator]))))
@@ -46,20 +91,67 @@ fn admin_api_routes() -> Router<AppState> {
             .layer(middleware::from_fn(require_role(vec![UserRole::Supplier, UserRole::Administrator]))))
         .route("/financing/request", post(crate::handlers::financial::request_financing)
             .layer(middleware::from_fn(require_role(vec![UserRole::Supplier, UserRole::Administrator]))))
-        .route("/users", post(crate::handlers::user::create_user))
+        .route("/locations", post(crate::handlers::location::create_known_location))
+        .route("/users", post(crate::handlers::user::create_user)
+            .layer(middleware::from_fn(require_step_up)))
         .route("/users/me", get(crate::handlers::user::get_current_user))
         .route("/auth/login", post(crate::handlers::auth::login))
-        .route("/auth/register", post(crate::handlers::auth::register))
+        .route("/auth/register", post(crate::handlers::auth::register)
+            .layer(middleware::from_fn(require_step_up)))
+        .route("/compliance/rules", post(crate::handlers::compliance::create_compliance_rule))
+        .route("/compliance/rules/:rule_id", delete(crate::handlers::compliance::deactivate_compliance_rule))
+        .route("/audit/anchor", post(crate::handlers::audit::anchor_audit_log))
+        .layer(middleware::from_fn(crate::middleware::audit::audit_log))
         .layer(middleware::from_fn(require_admin))
+        .layer(middleware::from_fn(crate::middleware::rate_limit::rate_limit_middleware))
         .layer(middleware::from_fn(jwt_auth))
+}
+
+// Wallet sign-in and token refresh carry their own credentials; session and
+// two-factor management need a valid access token, but not enrollment, so
+// administrators and auditors can enroll from here
+fn auth_routes() -> Router<AppState> {
+    let sessions = Router::new()
+        .route("/logout", post(crate::handlers::auth::logout))
+        .route("/logout-all", post(crate::handlers::auth::logout_all))
+        .route("/sessions", get(crate::handlers::auth::list_sessions))
+        .route("/sessions/:id", delete(crate::handlers::auth::revoke_session))
+        .route("/mfa", get(crate::handlers::mfa::get_mfa_status))
+        .route("/mfa", delete(crate::handlers::mfa::disable_mfa)
+            .layer(middleware::from_fn(require_step_up)))
+        .route("/mfa/enroll", post(crate::handlers::mfa::enroll_mfa))
+        .route("/mfa/activate", post(crate::handlers::mfa::activate_mfa))
+        .route("/mfa/verify", post(crate::handlers::mfa::verify_mfa))
+        .route("/mfa/recovery-codes", post(crate::handlers::mfa::regenerate_recovery_codes)
+            .layer(middleware::from_fn(require_step_up)))
+        .layer(middleware::from_fn(crate::middleware::audit::audit_log))
+        .layer(middleware::from_fn(jwt_auth_allow_unenrolled));
+
+    Router::new()
+        .route("/stellar/challenge", post(crate::handlers::auth::wallet_challenge))
+        .route("/stellar/login", post(crate::handlers::auth::wallet_login))
+        .route("/refresh", post(crate::handlers::auth::refresh))
+        .merge(sessions)
         .layer(middleware::from_fn(crate::middleware::rate_limit::rate_limit_middleware))
 }
 
+// Live updates for dashboards and integrations; subscriptions are authorized
+// per channel once connected
+fn websocket_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(crate::handlers::websocket::connect))
+        .layer(middleware::from_fn(crate::middleware::rate_limit::rate_limit_middleware))
+        .layer(middleware::from_fn(jwt_or_api_key_auth))
+        .layer(middleware::from_fn(bearer_from_query))
+}
+
 // Public routes that don't require authentication
 pub fn health_routes() -> Router<AppState> {
     Router::new()
         .route("/health", get(crate::handlers::health::health_check))
         .route("/health/db", get(crate::handlers::health::db_health_check))
+        // Prometheus scrape target; needs METRICS_TOKEN unless METRICS_PUBLIC is set
+        .route("/metrics", get(crate::handlers::monitoring::prometheus_metrics))
 }
 
 fn analytics_routes() -> Router<AppState> {
@@ -68,19 +160,58 @@ fn analytics_routes() -> Router<AppState> {
         .route("/products/:id", get(crate::routes::analytics::product_analytics))
         .route("/events", get(crate::routes::analytics::event_analytics))
         .route("/users", get(crate::routes::analytics::user_analytics))
+        .route("/anomalies", get(crate::routes::analytics::anomalies))
         .route("/export", get(crate::routes::analytics::export))
         .layer(middleware::from_fn(require_role(vec![UserRole::Auditor, UserRole::Administrator])))
-        .layer(middleware::from_fn(jwt_auth))
         .layer(middleware::from_fn(crate::middleware::rate_limit::rate_limit_middleware))
+        .layer(middleware::from_fn(jwt_auth))
 }
 
 fn key_management_routes() -> Router<AppState> {
     Router::new()
-        .route("/", get(crate::handlers::api_keys::list_keys).post(crate::handlers::api_keys::create_key))
-        .route("/:id/revoke", post(crate::handlers::api_keys::revoke_key))
-        .route("/:id/rotate", post(crate::handlers::api_keys::rotate_key))
+        .route("/", get(crate::handlers::api_keys::list_keys))
+        // Minting and revoking keys needs a recent second factor
+        .route("/", post(crate::handlers::api_keys::create_key)
+            .layer(middleware::from_fn(require_step_up)))
+        .route("/:id/revoke", post(crate::handlers::api_keys::revoke_key)
+            .layer(middleware::from_fn(require_step_up)))
+        .route("/:id/rotate", post(crate::handlers::api_keys::rotate_key)
+            .layer(middleware::from_fn(require_step_up)))
+        .route("/:id/usage", get(crate::handlers::api_keys::get_key_usage))
+        .layer(middleware::from_fn(crate::middleware::audit::audit_log))
+        .layer(middleware::from_fn(crate::middleware::rate_limit::rate_limit_middleware))
+        .layer(middleware::from_fn(jwt_auth))
+}
+
+fn webhook_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(crate::handlers::webhooks::list_webhooks))
+        // Pointing deliveries at a URL needs a recent second factor
+        .route("/", post(crate::handlers::webhooks::create_webhook)
+            .layer(middleware::from_fn(require_step_up)))
+        .route("/:id", get(crate::handlers::webhooks::get_webhook).delete(crate::handlers::webhooks::delete_webhook))
+        .route("/:id", put(crate::handlers::webhooks::update_webhook)
+            .layer(middleware::from_fn(require_step_up)))
+        .route("/:id/deliveries", get(crate::handlers::webhooks::list_deliveries))
+        .route("/:id/deliveries/:delivery_id", get(crate::handlers::webhooks::get_delivery))
+        .route("/:id/deliveries/:delivery_id/replay", post(crate::handlers::webhooks::replay_delivery))
+        .layer(middleware::from_fn(crate::middleware::audit::audit_log))
+        .layer(middleware::from_fn(crate::middleware::rate_limit::rate_limit_middleware))
         .layer(middleware::from_fn(jwt_auth))
+}
+
+fn organization_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(crate::handlers::organization::list_organizations).post(crate::handlers::organization::create_organization))
+        .route("/:id", get(crate::handlers::organization::get_organization))
+        .route("/:id/members", get(crate::handlers::organization::list_members).post(crate::handlers::organization::add_member))
+        .route("/:id/members/:user_id", put(crate::handlers::organization::update_member).delete(crate::handlers::organization::remove_member))
+        .route("/:id/addresses", get(crate::handlers::organization::list_addresses).post(crate::handlers::organization::add_address))
+        .route("/:id/addresses/challenge", post(crate::handlers::organization::address_challenge))
+        .route("/:id/addresses/:stellar_address", delete(crate::handlers::organization::remove_address))
+        .layer(middleware::from_fn(crate::middleware::audit::audit_log))
         .layer(middleware::from_fn(crate::middleware::rate_limit::rate_limit_middleware))
+        .layer(middleware::from_fn(jwt_auth))
 }
 
 fn carbon_routes() -> Router<AppState> {
@@ -104,11 +235,47 @@ fn carbon_routes() -> Router<AppState> {
         .route("/verify/:credit_id", get(crate::handlers::carbon::list_verifications))
         // Reports
         .route("/reports", get(crate::handlers::carbon::list_reports).post(crate::handlers::carbon::generate_report))
-        .layer(middleware::from_fn(jwt_auth))
+        .layer(middleware::from_fn(crate::middleware::audit::audit_log))
+        .layer(middleware::from_fn(read_only_auditors))
+        .layer(middleware::from_fn(crate::middleware::rate_limit::rate_limit_middleware))
+        .layer(middleware::from_fn(jwt_or_api_key_auth))
+}
+
+fn digital_twin_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", post(crate::handlers::digital_twin::create_digital_twin))
+        .route("/health", get(crate::handlers::digital_twin::digital_twin_health))
+        .route("/stats", get(crate::handlers::digital_twin::get_digital_twin_stats))
+        .route("/:id", get(crate::handlers::digital_twin::get_digital_twin))
+        .route("/:id/state", put(crate::handlers::digital_twin::update_twin_state))
+        .route("/:id/history", get(crate::handlers::digital_twin::get_state_history))
+        .route("/:id/analytics", get(crate::handlers::digital_twin::get_twin_analytics))
+        .route("/:id/simulations", get(crate::handlers::digital_twin::list_simulations))
+        // Live sync
+        .route("/:id/sync-mapping", put(crate::handlers::digital_twin::update_sync_mapping))
+        .route("/:id/divergences", get(crate::handlers::digital_twin::list_divergences))
+        .route("/products/:product_id", get(crate::handlers::digital_twin::get_product_twins))
+        .route("/products/:product_id/snapshots", post(crate::handlers::digital_twin::ingest_oracle_snapshot))
+        .route("/products/:product_id/eta", get(crate::handlers::digital_twin::predict_eta))
+        // Simulations & predictions
+        .route("/simulations", post(crate::handlers::digital_twin::create_simulation))
+        .route("/simulations/:id", get(crate::handlers::digital_twin::get_simulation))
+        .route("/simulations/:id/run", post(crate::handlers::digital_twin::run_simulation))
+        .route("/predictions", post(crate::handlers::digital_twin::create_prediction))
+        .layer(middleware::from_fn(crate::middleware::audit::audit_log))
+        .layer(middleware::from_fn(read_only_auditors))
         .layer(middleware::from_fn(crate::middleware::rate_limit::rate_limit_middleware))
+        .layer(middleware::from_fn(jwt_auth))
 }
 
 fn monitoring_routes() -> Router<AppState> {
+    // Auditors watch alerting; only administrators change it
+    let admin_routes = Router::new()
+        .route("/alerts/silences", post(crate::handlers::monitoring::create_alert_silence))
+        .route("/alerts/silences/:id", delete(crate::handlers::monitoring::delete_alert_silence))
+        .route("/contract-events", post(crate::handlers::monitoring::report_contract_event))
+        .layer(middleware::from_fn(require_admin));
+
     Router::new()
         .route("/dashboard", get(crate::handlers::monitoring::get_dashboard))
         .route("/errors", get(crate::handlers::monitoring::get_error_stats))
@@ -116,7 +283,11 @@ fn monitoring_routes() -> Router<AppState> {
         .route("/performance", get(crate::handlers::monitoring::get_performance_metrics))
         .route("/infrastructure", get(crate::handlers::monitoring::get_infrastructure_metrics))
         .route("/alerts/check", post(crate::handlers::monitoring::check_alerts))
+        .route("/alerts", get(crate::handlers::monitoring::list_alerts))
+        .route("/alerts/silences", get(crate::handlers::monitoring::list_alert_silences))
+        .merge(admin_routes)
         .layer(middleware::from_fn(require_role(vec![UserRole::Auditor, UserRole::Administrator])))
-        .layer(middleware::from_fn(jwt_auth))
+        .layer(middleware::from_fn(crate::middleware::audit::audit_log))
         .layer(middleware::from_fn(crate::middleware::rate_limit::rate_limit_middleware))
+        .layer(middleware::from_fn(jwt_auth))
 }
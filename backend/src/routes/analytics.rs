@@ -2,29 +2,46 @@ use axum::{
     extract::{Path, Query, State},
     http::{header, StatusCode},
     response::{IntoResponse, Json, Response},
+    Extension,
 };
 use chrono::{Duration, Utc};
 use serde_json::json;
 
 use crate::{
     error::AppError,
-    models::analytics::{ExportQuery, TimeSeriesQuery},
+    middleware::auth::AuthContext,
+    models::analytics::{AnomalyQuery, ExportQuery, TimeSeriesQuery},
+    services::access_control::ProductAction,
     AppState,
 };
 
+/// Organization the caller's aggregates are limited to; `None` for administrators
+fn organization_of(auth: &AuthContext) -> Option<uuid::Uuid> {
+    auth.tenant_scope().map(|scope| scope.organization_id)
+}
+
 /// GET /api/v1/analytics/dashboard
 pub async fn dashboard(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let metrics = state.analytics_service.get_dashboard_metrics().await?;
+    let metrics = state
+        .analytics_service
+        .get_dashboard_metrics(organization_of(&auth))
+        .await?;
     Ok(Json(json!(metrics)))
 }
 
 /// GET /api/v1/analytics/products/:id
 pub async fn product_analytics(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Path(product_id): Path<String>,
 ) -> Result<Json<serde_json::Value>, AppError> {
+    state
+        .access_service
+        .authorize_product(&auth, &product_id, ProductAction::Read)
+        .await?;
     let analytics = state
         .analytics_service
         .get_product_analytics(&product_id)
@@ -35,6 +52,7 @@ pub async fn product_analytics(
 /// GET /api/v1/analytics/events?start_date=&end_date=&event_type=
 pub async fn event_analytics(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Query(params): Query<TimeSeriesQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     let end = params.end_date.unwrap_or_else(Utc::now);
@@ -42,7 +60,7 @@ pub async fn event_analytics(
 
     let analytics = state
         .analytics_service
-        .get_event_analytics(start, end, params.event_type.as_deref())
+        .get_event_analytics(start, end, params.event_type.as_deref(), organization_of(&auth))
         .await?;
     Ok(Json(json!(analytics)))
 }
@@ -50,16 +68,49 @@ pub async fn event_analytics(
 /// GET /api/v1/analytics/users
 pub async fn user_analytics(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let analytics = state.analytics_service.get_user_analytics().await?;
+    let analytics = state
+        .analytics_service
+        .get_user_analytics(organization_of(&auth))
+        .await?;
     Ok(Json(json!(analytics)))
 }
 
+/// GET /api/v1/analytics/anomalies?product_id=&anomaly_type=&min_score=&start_date=&end_date=&limit=
+pub async fn anomalies(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
+    Query(params): Query<AnomalyQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let anomalies = state
+        .anomaly_service
+        .list_anomalies(&params, organization_of(&auth))
+        .await?;
+    Ok(Json(json!({
+        "total": anomalies.len(),
+        "anomalies": anomalies,
+    })))
+}
+
 /// GET /api/v1/analytics/export?format=csv&start_date=&end_date=&product_id=&limit=
 pub async fn export(
     State(state): State<AppState>,
+    Extension(auth): Extension<AuthContext>,
     Query(params): Query<ExportQuery>,
 ) -> Result<Response, AppError> {
+    // A single product may be exported wherever it is visible, including
+    // products shared with the caller's organization
+    let organization_id = match params.product_id.as_deref() {
+        Some(product_id) => {
+            state
+                .access_service
+                .authorize_product(&auth, product_id, ProductAction::Read)
+                .await?;
+            None
+        }
+        None => organization_of(&auth),
+    };
     let end = params.end_date.unwrap_or_else(Utc::now);
     let start = params.start_date.unwrap_or_else(|| end - Duration::days(30));
     let limit = params.limit.unwrap_or(10_000).min(50_000);
@@ -69,7 +120,7 @@ pub async fn export(
         "csv" => {
             let csv = state
                 .analytics_service
-                .export_events_csv(start, end, params.product_id.as_deref(), limit)
+                .export_events_csv(start, end, params.product_id.as_deref(), limit, organization_id)
                 .await?;
 
             Ok((
@@ -89,7 +140,7 @@ pub async fn export(
             // JSON export — reuse event analytics with full data
             let analytics = state
                 .analytics_service
-                .get_event_analytics(start, end, params.event_type.as_deref())
+                .get_event_analytics(start, end, params.event_type.as_deref(), organization_of(&auth))
                 .await?;
             Ok(Json(json!(analytics)).into_response())
         }
@@ -1,12 +1,18 @@
 use async_trait::async_trait;
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
 use uuid::Uuid;
 use sha2::{Sha256, Digest};
 use rand::Rng;
 use crate::database::{ProductRepository, EventRepository, UserRepository, ApiKeyRepository, ProductFilters, GlobalStats};
+use crate::error::AppError;
 use crate::models::*;
+use crate::models::organization::TenantScope;
 use bcrypt::{hash, DEFAULT_COST};
 use redis::AsyncCommands;
+use serde_json::json;
+use tracing::Instrument;
+use crate::monitoring::telemetry;
+use crate::websocket::channels;
 
 pub mod financial;
 pub use financial::FinancialService;
@@ -20,17 +26,101 @@ pub use carbon::CarbonService;
 
 pub mod digital_twin_service;
 pub use digital_twin_service::DigitalTwinService;
+pub mod twin_sync;
+pub mod eta_predictor;
+
+pub mod anomaly_detector;
+pub mod anomaly_service;
+pub use anomaly_service::AnomalyService;
+
+pub mod location_service;
+pub use location_service::LocationService;
+
+pub mod epcis;
+pub mod epcis_service;
+pub use epcis_service::EpcisService;
+
+pub mod audit_service;
+pub use audit_service::AuditService;
+
+pub mod compliance_service;
+pub use compliance_service::ComplianceService;
+
+pub mod lineage_service;
+pub use lineage_service::LineageService;
+
+pub mod eudr;
+pub mod eudr_service;
+pub use eudr_service::EudrService;
+
+pub mod access_control;
+pub mod api_key_policy;
+pub mod access_service;
+pub use access_service::AccessService;
+
+pub mod organization_service;
+pub use organization_service::OrganizationService;
+
+pub mod jwt_keys;
+pub mod mfa_service;
+pub use mfa_service::MfaService;
+pub mod sep10;
+pub mod session_service;
+pub use session_service::SessionService;
+pub mod totp;
+pub mod wallet_auth_service;
+pub use wallet_auth_service::WalletAuthService;
+
+pub mod webhook_delivery;
+pub mod webhook_service;
+pub use webhook_service::WebhookService;
+
+pub mod usage_metering;
+pub mod usage_service;
+pub use usage_service::UsageService;
+
+pub mod alert_service;
+pub use alert_service::AlertService;
+
+pub mod backup;
+pub mod backup_service;
+pub use backup_service::BackupService;
 
 /// Service layer for managing product operations and database interactions.
 /// Provides a clean abstraction over database operations for products.
 pub struct ProductService {
     pool: PgPool,
     redis_client: redis::Client,
+    location_service: LocationService,
 }
 
 impl ProductService {
     pub fn new(pool: PgPool, redis_client: redis::Client) -> Self {
-        Self { pool, redis_client }
+        let location_service = LocationService::new(pool.clone());
+        Self { pool, redis_client, location_service }
+    }
+
+    /// Resolves the product origin to coordinates; unknown origins are left unresolved.
+    async fn locate_origin(&self, product: &Product) {
+        if let Err(e) = self
+            .location_service
+            .locate_product(&product.id, &product.origin_location, &product.custom_fields)
+            .await
+        {
+            tracing::warn!(product_id = %product.id, "Origin geocoding failed: {}", e);
+        }
+    }
+
+    /// Pushes a product change to its websocket subscribers and webhooks
+    async fn publish_change(&self, kind: &str, product: &Product) {
+        let data = json!({ "kind": kind, "product": product });
+        channels::publish(
+            &channels::for_product(&product.id, product.organization_id),
+            data.clone(),
+        )
+        .await;
+        webhook_service::enqueue(&self.pool, kind, &product.id, product.organization_id, data)
+            .await;
     }
 }
 
@@ -62,8 +152,11 @@ impl ProductRepository for ProductService {
             INSERT INTO products (
                 id, name, description, origin_location, category, tags,
                 certifications, media_hashes, custom_fields, owner_address,
-                is_active, created_by, updated_by
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, true, $11, $11)
+                is_active, created_by, updated_by, organization_id
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, true, $11, $11,
+                (SELECT organization_id FROM organization_addresses WHERE stellar_address = $10)
+            )
             RETURNING *
             "#,
             product.id,
@@ -84,6 +177,9 @@ impl ProductRepository for ProductService {
         // Invalidate global stats cache
         let _ = self.invalidate_global_stats().await;
 
+        self.locate_origin(&created).await;
+        self.publish_change("product.created", &created).await;
+
         Ok(created)
     }
 
@@ -99,12 +195,14 @@ impl ProductRepository for ProductService {
         let cache_key = format!("cache:product:{}", id);
         
         // Try to get from cache
-        if let Ok(mut conn) = self.redis_client.get_multiplexed_tokio_connection().await {
-            if let Ok(cached) = conn.get::<_, String>(&cache_key).await {
-                if let Ok(product) = serde_json::from_str::<Product>(&cached) {
-                    return Ok(Some(product));
-                }
-            }
+        let cached = async {
+            let mut conn = self.redis_client.get_multiplexed_tokio_connection().await.ok()?;
+            conn.get::<_, String>(&cache_key).await.ok()
+        }
+        .instrument(telemetry::redis_span("GET"))
+        .await;
+        if let Some(product) = cached.and_then(|c| serde_json::from_str::<Product>(&c).ok()) {
+            return Ok(Some(product));
         }
 
         let product = sqlx::query_as!(
@@ -117,11 +215,15 @@ impl ProductRepository for ProductService {
 
         // Save to cache if found
         if let Some(ref p) = product {
-            if let Ok(mut conn) = self.redis_client.get_multiplexed_tokio_connection().await {
-                if let Ok(serialized) = serde_json::to_string(p) {
-                    let _: Result<(), _> = conn.set_ex(&cache_key, serialized, 3600).await;
+            async {
+                if let Ok(mut conn) = self.redis_client.get_multiplexed_tokio_connection().await {
+                    if let Ok(serialized) = serde_json::to_string(p) {
+                        let _: Result<(), _> = conn.set_ex(&cache_key, serialized, 3600).await;
+                    }
                 }
             }
+            .instrument(telemetry::redis_span("SETEX"))
+            .await;
         }
 
         Ok(product)
@@ -142,7 +244,8 @@ impl ProductRepository for ProductService {
                 custom_fields = $9,
                 owner_address = $10,
                 is_active = $11,
-                updated_by = $12
+                updated_by = $12,
+                organization_id = (SELECT organization_id FROM organization_addresses WHERE stellar_address = $10)
             WHERE id = $1
             RETURNING *
             "#,
@@ -166,17 +269,30 @@ impl ProductRepository for ProductService {
         let _ = self.invalidate_product_cache(id).await;
         let _ = self.invalidate_global_stats().await;
 
+        self.locate_origin(&updated).await;
+        self.publish_change("product.updated", &updated).await;
+
         Ok(updated)
     }
 
     async fn delete_product(&self, id: &str) -> Result<(), sqlx::Error> {
-        sqlx::query!("DELETE FROM products WHERE id = $1", id)
-            .execute(&self.pool)
-            .await?;
+        let deleted = sqlx::query_scalar!(
+            "DELETE FROM products WHERE id = $1 RETURNING organization_id",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
         
         // Invalidate cache
         let _ = self.invalidate_product_cache(id).await;
         let _ = self.invalidate_global_stats().await;
+
+        if let Some(organization_id) = deleted {
+            let data = json!({ "kind": "product.deleted", "product_id": id });
+            channels::publish(&channels::for_product(id, organization_id), data.clone()).await;
+            webhook_service::enqueue(&self.pool, "product.deleted", id, organization_id, data)
+                .await;
+        }
         
         Ok(())
     }
@@ -240,6 +356,32 @@ impl ProductRepository for ProductService {
                 bindings.push(before.to_rfc3339());
                 bind_index += 1;
             }
+            if let Some(scope) = f.scope {
+                query.push_str(&format!(
+                    " AND product_visible_to(${}::uuid, NULLIF(${}, ''), id)",
+                    bind_index,
+                    bind_index + 1
+                ));
+                bindings.push(scope.organization_id.to_string());
+                bindings.push(scope.stellar_address.unwrap_or_default());
+                bind_index += 2;
+            }
+            if let Some(ids) = f.product_ids {
+                query.push_str(&format!(
+                    " AND id IN (SELECT jsonb_array_elements_text(${}::jsonb))",
+                    bind_index
+                ));
+                bindings.push(serde_json::to_string(&ids).unwrap_or_default());
+                bind_index += 1;
+            }
+            if let Some(categories) = f.categories {
+                query.push_str(&format!(
+                    " AND category IN (SELECT jsonb_array_elements_text(${}::jsonb))",
+                    bind_index
+                ));
+                bindings.push(serde_json::to_string(&categories).unwrap_or_default());
+                bind_index += 1;
+            }
         }
 
         query.push_str(&format!(" ORDER BY created_at DESC LIMIT ${} OFFSET ${}", bind_index, bind_index + 1));
@@ -288,6 +430,32 @@ impl ProductRepository for ProductService {
                 bindings.push(before.to_rfc3339());
                 bind_index += 1;
             }
+            if let Some(scope) = f.scope {
+                query.push_str(&format!(
+                    " AND product_visible_to(${}::uuid, NULLIF(${}, ''), id)",
+                    bind_index,
+                    bind_index + 1
+                ));
+                bindings.push(scope.organization_id.to_string());
+                bindings.push(scope.stellar_address.unwrap_or_default());
+                bind_index += 2;
+            }
+            if let Some(ids) = f.product_ids {
+                query.push_str(&format!(
+                    " AND id IN (SELECT jsonb_array_elements_text(${}::jsonb))",
+                    bind_index
+                ));
+                bindings.push(serde_json::to_string(&ids).unwrap_or_default());
+                bind_index += 1;
+            }
+            if let Some(categories) = f.categories {
+                query.push_str(&format!(
+                    " AND category IN (SELECT jsonb_array_elements_text(${}::jsonb))",
+                    bind_index
+                ));
+                bindings.push(serde_json::to_string(&categories).unwrap_or_default());
+                bind_index += 1;
+            }
         }
 
         let mut q = sqlx::QueryBuilder::new(query);
@@ -319,38 +487,54 @@ impl ProductRepository for ProductService {
 /// # Performance
 /// - Utilizes PostgreSQL GIN indexes for efficient full-text search
 /// - Orders by ts_rank for most relevant results first
-    async fn search_products(&self, query: &str, limit: i64) -> Result<Vec<Product>, sqlx::Error> {
+    async fn search_products(
+        &self,
+        query: &str,
+        limit: i64,
+        scope: Option<&TenantScope>,
+    ) -> Result<Vec<Product>, sqlx::Error> {
         sqlx::query_as!(
             Product,
             r#"
             SELECT * FROM products 
             WHERE 
-                to_tsvector('english', name || ' ' || COALESCE(description, '') || ' ' || category) 
+                (to_tsvector('english', name || ' ' || COALESCE(description, '') || ' ' || category) 
                 @@ plainto_tsquery('english', $1)
                 OR name ILIKE $2
-                OR id ILIKE $2
+                OR id ILIKE $2)
+                AND ($4::uuid IS NULL OR product_visible_to($4, $5, id))
             ORDER BY ts_rank(to_tsvector('english', name || ' ' || COALESCE(description, '') || ' ' || category), plainto_tsquery('english', $1)) DESC
             LIMIT $3
             "#,
             query,
             format!("%{}%", query),
-            limit
+            limit,
+            scope.map(|s| s.organization_id),
+            scope.and_then(|s| s.stellar_address.as_deref())
         )
         .fetch_all(&self.pool)
         .await
     }
 
     async fn invalidate_product_cache(&self, id: &str) -> Result<(), AppError> {
-        if let Ok(mut conn) = self.redis_client.get_multiplexed_tokio_connection().await {
-            let _: Result<(), _> = conn.del(format!("cache:product:{}", id)).await;
+        async {
+            if let Ok(mut conn) = self.redis_client.get_multiplexed_tokio_connection().await {
+                let _: Result<(), _> = conn.del(format!("cache:product:{}", id)).await;
+            }
         }
+        .instrument(telemetry::redis_span("DEL"))
+        .await;
         Ok(())
     }
 
     async fn invalidate_global_stats(&self) -> Result<(), AppError> {
-        if let Ok(mut conn) = self.redis_client.get_multiplexed_tokio_connection().await {
-            let _: Result<(), _> = conn.del("cache:global_stats").await;
+        async {
+            if let Ok(mut conn) = self.redis_client.get_multiplexed_tokio_connection().await {
+                let _: Result<(), _> = conn.del("cache:global_stats").await;
+            }
         }
+        .instrument(telemetry::redis_span("DEL"))
+        .await;
         Ok(())
     }
 }
@@ -358,11 +542,29 @@ impl ProductRepository for ProductService {
 pub struct EventService {
     pool: PgPool,
     redis_client: redis::Client,
+    location_service: LocationService,
+    twin_service: DigitalTwinService,
+    anomaly_service: AnomalyService,
+    lineage_service: LineageService,
+    compliance_service: ComplianceService,
 }
 
 impl EventService {
     pub fn new(pool: PgPool, redis_client: redis::Client) -> Self {
-        Self { pool, redis_client }
+        let location_service = LocationService::new(pool.clone());
+        let twin_service = DigitalTwinService::new(pool.clone());
+        let anomaly_service = AnomalyService::new(pool.clone());
+        let lineage_service = LineageService::new(pool.clone());
+        let compliance_service = ComplianceService::new(pool.clone());
+        Self {
+            pool,
+            redis_client,
+            location_service,
+            twin_service,
+            anomaly_service,
+            lineage_service,
+            compliance_service,
+        }
     }
 }
 
@@ -390,17 +592,80 @@ impl EventRepository for EventService {
         .fetch_one(&self.pool)
         .await?;
 
+        let lag = created.created_at - created.timestamp;
+        crate::monitoring::metrics::METRICS
+            .count_event_ingested(lag.num_milliseconds() as f64 / 1000.0);
+
         // Invalidate global stats cache
         let _ = self.invalidate_global_stats().await;
 
+        // Resolve the free-text location before downstream consumers need coordinates
+        if let Err(e) = self.location_service.locate_event(&created).await {
+            tracing::warn!(event_id = created.id, "Event geocoding failed: {}", e);
+        }
+
+        // Keep digital twins of this product in step with the new event
+        if let Err(e) = self.twin_service.sync_from_event(&created).await {
+            tracing::warn!(event_id = created.id, "Digital twin sync failed: {}", e);
+        }
+
+        // Flag suspicious events; scoring never blocks the insert
+        if let Err(e) = self.anomaly_service.score_event(&created).await {
+            tracing::warn!(event_id = created.id, "Anomaly scoring failed: {}", e);
+        }
+
+        // Link the product to the inputs it was made from, so origin data follows it
+        if let Err(e) = self.lineage_service.record_from_event(&created).await {
+            tracing::warn!(event_id = created.id, "Lineage recording failed: {}", e);
+        }
+
+        // Re-evaluate the product's compliance now that its history changed
+        if let Err(e) = self.compliance_service.evaluate_product(&created.product_id).await {
+            tracing::warn!(event_id = created.id, "Compliance evaluation failed: {}", e);
+        }
+
+        // Live dashboards and webhooks
+        let organization_id = sqlx::query_scalar!(
+            "SELECT organization_id FROM products WHERE id = $1",
+            created.product_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .ok()
+        .flatten()
+        .flatten();
+        let data = json!({ "kind": "event.created", "event": created });
+        channels::publish(
+            &channels::for_product(&created.product_id, organization_id),
+            data.clone(),
+        )
+        .await;
+        webhook_service::enqueue(
+            &self.pool,
+            "event.created",
+            &created.product_id,
+            organization_id,
+            data,
+        )
+        .await;
+
         Ok(created)
     }
 
-    async fn get_event(&self, id: i64) -> Result<Option<TrackingEvent>, sqlx::Error> {
+    async fn get_event(
+        &self,
+        id: i64,
+        scope: Option<&TenantScope>,
+    ) -> Result<Option<TrackingEvent>, sqlx::Error> {
         sqlx::query_as!(
             TrackingEvent,
-            "SELECT * FROM tracking_events WHERE id = $1",
-            id
+            r#"
+            SELECT * FROM tracking_events
+            WHERE id = $1 AND ($2::uuid IS NULL OR product_visible_to($2, $3, product_id))
+            "#,
+            id,
+            scope.map(|s| s.organization_id),
+            scope.and_then(|s| s.stellar_address.as_deref())
         )
         .fetch_optional(&self.pool)
         .await
@@ -411,22 +676,38 @@ impl EventRepository for EventService {
         product_id: &str,
         offset: i64,
         limit: i64,
+        scope: Option<&TenantScope>,
     ) -> Result<Vec<TrackingEvent>, sqlx::Error> {
         sqlx::query_as!(
             TrackingEvent,
-            "SELECT * FROM tracking_events WHERE product_id = $1 ORDER BY timestamp DESC LIMIT $2 OFFSET $3",
+            r#"
+            SELECT * FROM tracking_events
+            WHERE product_id = $1 AND ($4::uuid IS NULL OR product_visible_to($4, $5, product_id))
+            ORDER BY timestamp DESC LIMIT $2 OFFSET $3
+            "#,
             product_id,
             limit,
-            offset
+            offset,
+            scope.map(|s| s.organization_id),
+            scope.and_then(|s| s.stellar_address.as_deref())
         )
         .fetch_all(&self.pool)
         .await
     }
 
-    async fn count_events_by_product(&self, product_id: &str) -> Result<i64, sqlx::Error> {
+    async fn count_events_by_product(
+        &self,
+        product_id: &str,
+        scope: Option<&TenantScope>,
+    ) -> Result<i64, sqlx::Error> {
         sqlx::query_scalar!(
-            "SELECT COUNT(*) FROM tracking_events WHERE product_id = $1",
-            product_id
+            r#"
+            SELECT COUNT(*) FROM tracking_events
+            WHERE product_id = $1 AND ($2::uuid IS NULL OR product_visible_to($2, $3, product_id))
+            "#,
+            product_id,
+            scope.map(|s| s.organization_id),
+            scope.and_then(|s| s.stellar_address.as_deref())
         )
         .fetch_one(&self.pool)
         .await
@@ -439,14 +720,22 @@ impl EventRepository for EventService {
         event_type: &str,
         offset: i64,
         limit: i64,
+        scope: Option<&TenantScope>,
     ) -> Result<Vec<TrackingEvent>, sqlx::Error> {
         sqlx::query_as!(
             TrackingEvent,
-            "SELECT * FROM tracking_events WHERE product_id = $1 AND event_type = $2 ORDER BY timestamp DESC LIMIT $3 OFFSET $4",
+            r#"
+            SELECT * FROM tracking_events
+            WHERE product_id = $1 AND event_type = $2
+              AND ($5::uuid IS NULL OR product_visible_to($5, $6, product_id))
+            ORDER BY timestamp DESC LIMIT $3 OFFSET $4
+            "#,
             product_id,
             event_type,
             limit,
-            offset
+            offset,
+            scope.map(|s| s.organization_id),
+            scope.and_then(|s| s.stellar_address.as_deref())
         )
         .fetch_all(&self.pool)
         .await
@@ -471,15 +760,23 @@ impl EventRepository for EventService {
         .await
     }
 
-    async fn get_global_stats(&self) -> Result<GlobalStats, sqlx::Error> {
+    async fn get_global_stats(&self, scope: Option<&TenantScope>) -> Result<GlobalStats, sqlx::Error> {
         let cache_key = "cache:global_stats";
+        // Only the platform-wide figures are cached; per-organization counts
+        // go through the organization_id indexes
+        let organization_id = scope.map(|s| s.organization_id);
+        let cacheable = organization_id.is_none();
 
         // Try to get from cache
-        if let Ok(mut conn) = self.redis_client.get_multiplexed_tokio_connection().await {
-            if let Ok(cached) = conn.get::<_, String>(cache_key).await {
-                if let Ok(stats) = serde_json::from_str::<GlobalStats>(&cached) {
-                    return Ok(stats);
-                }
+        if cacheable {
+            let cached = async {
+                let mut conn = self.redis_client.get_multiplexed_tokio_connection().await.ok()?;
+                conn.get::<_, String>(cache_key).await.ok()
+            }
+            .instrument(telemetry::redis_span("GET"))
+            .await;
+            if let Some(stats) = cached.and_then(|c| serde_json::from_str::<GlobalStats>(&c).ok()) {
+                return Ok(stats);
             }
         }
 
@@ -488,12 +785,15 @@ impl EventRepository for EventService {
         let stats = sqlx::query!(
             r#"
             SELECT 
-                (SELECT COUNT(*) FROM products) as total_products,
-                (SELECT COUNT(*) FROM products WHERE is_active = true) as active_products,
-                (SELECT COUNT(*) FROM tracking_events) as total_events,
-                (SELECT COUNT(*) FROM users) as total_users,
-                (SELECT COUNT(*) FROM api_keys WHERE is_active = true) as active_api_keys
-            "#
+                (SELECT COUNT(*) FROM products WHERE $1::uuid IS NULL OR organization_id = $1) as total_products,
+                (SELECT COUNT(*) FROM products WHERE is_active = true AND ($1::uuid IS NULL OR organization_id = $1)) as active_products,
+                (SELECT COUNT(*) FROM tracking_events
+                    WHERE $1::uuid IS NULL OR product_id IN (SELECT id FROM products WHERE organization_id = $1)) as total_events,
+                (SELECT COUNT(*) FROM users
+                    WHERE $1::uuid IS NULL OR id IN (SELECT user_id FROM organization_members WHERE organization_id = $1)) as total_users,
+                (SELECT COUNT(*) FROM api_keys WHERE is_active = true AND ($1::uuid IS NULL OR organization_id = $1)) as active_api_keys
+            "#,
+            organization_id
         )
         .fetch_one(&self.pool)
         .await?;
@@ -507,19 +807,29 @@ impl EventRepository for EventService {
         };
 
         // Save to cache
-        if let Ok(mut conn) = self.redis_client.get_multiplexed_tokio_connection().await {
-            if let Ok(serialized) = serde_json::to_string(&global_stats) {
-                let _: Result<(), _> = conn.set_ex(cache_key, serialized, 300).await;
+        if cacheable {
+            async {
+                if let Ok(mut conn) = self.redis_client.get_multiplexed_tokio_connection().await {
+                    if let Ok(serialized) = serde_json::to_string(&global_stats) {
+                        let _: Result<(), _> = conn.set_ex(cache_key, serialized, 300).await;
+                    }
+                }
             }
+            .instrument(telemetry::redis_span("SETEX"))
+            .await;
         }
 
         Ok(global_stats)
     }
 
     async fn invalidate_global_stats(&self) -> Result<(), AppError> {
-        if let Ok(mut conn) = self.redis_client.get_multiplexed_tokio_connection().await {
-            let _: Result<(), _> = conn.del("cache:global_stats").await;
+        async {
+            if let Ok(mut conn) = self.redis_client.get_multiplexed_tokio_connection().await {
+                let _: Result<(), _> = conn.del("cache:global_stats").await;
+            }
         }
+        .instrument(telemetry::redis_span("DEL"))
+        .await;
         Ok(())
     }
 }
@@ -546,30 +856,8 @@ impl UserService {
 #[async_trait]
 impl UserRepository for UserService {
     async fn create_user(&self, user: NewUser) -> Result<User, sqlx::Error> {
-        let encrypted_email = crate::utils::crypto::encrypt(&user.email, &self.encryption_key)
-            .map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
-        
-        let encrypted_address = if let Some(addr) = &user.stellar_address {
-            Some(crate::utils::crypto::encrypt(addr, &self.encryption_key)
-                .map_err(|e| sqlx::Error::Protocol(e.to_string()))?)
-        } else {
-            None
-        };
-
-        let mut created = sqlx::query_as!(
-            User,
-            r#"
-            INSERT INTO users (email, password_hash, stellar_address, role)
-            VALUES ($1, $2, $3, $4)
-            RETURNING *
-            "#,
-            encrypted_email,
-            user.password_hash,
-            encrypted_address,
-            user.role as UserRole
-        )
-        .fetch_one(&self.pool)
-        .await?;
+        let mut conn = self.pool.acquire().await?;
+        let mut created = self.insert_user(&mut conn, user).await?;
 
         // Decrypt for returning
         let _ = self.decrypt_user(&mut created);
@@ -681,6 +969,45 @@ impl UserRepository for UserService {
 }
 
 impl UserService {
+    /// Creates a self-registered user and, in the same transaction, the
+    /// personal organization they start in
+    pub async fn register(&self, user: NewUser) -> Result<User, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let mut created = self.insert_user(&mut tx, user).await?;
+        OrganizationService::create_personal(&mut tx, created.id).await?;
+        tx.commit().await?;
+
+        let _ = self.decrypt_user(&mut created);
+        Ok(created)
+    }
+
+    async fn insert_user(&self, conn: &mut PgConnection, user: NewUser) -> Result<User, sqlx::Error> {
+        let encrypted_email = crate::utils::crypto::encrypt(&user.email, &self.encryption_key)
+            .map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+        
+        let encrypted_address = if let Some(addr) = &user.stellar_address {
+            Some(crate::utils::crypto::encrypt(addr, &self.encryption_key)
+                .map_err(|e| sqlx::Error::Protocol(e.to_string()))?)
+        } else {
+            None
+        };
+
+        sqlx::query_as!(
+            User,
+            r#"
+            INSERT INTO users (email, password_hash, stellar_address, role)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+            "#,
+            encrypted_email,
+            user.password_hash,
+            encrypted_address,
+            user.role as UserRole
+        )
+        .fetch_one(&mut *conn)
+        .await
+    }
+
     fn decrypt_user(&self, user: &mut User) -> Result<(), AppError> {
         if let Ok(decrypted) = crate::utils::crypto::decrypt(&user.email, &self.encryption_key) {
             user.email = decrypted;
@@ -742,8 +1069,11 @@ impl ApiKeyRepository for ApiKeyService {
         sqlx::query_as!(
             ApiKey,
             r#"
-            INSERT INTO api_keys (user_id, key_hash, name, tier, rate_limit_per_minute, expires_at)
-            VALUES ($1, $2, $3, $4, $5, $6)
+            INSERT INTO api_keys (
+                user_id, key_hash, name, tier, rate_limit_per_minute, expires_at, organization_id,
+                scopes, allowed_product_ids, allowed_categories, allowed_ips
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
             RETURNING *
             "#,
             api_key.user_id,
@@ -751,7 +1081,12 @@ impl ApiKeyRepository for ApiKeyService {
             api_key.name,
             api_key.tier as ApiKeyTier,
             api_key.rate_limit_per_minute,
-            api_key.expires_at
+            api_key.expires_at,
+            api_key.organization_id,
+            &api_key.scopes,
+            api_key.allowed_product_ids.as_deref(),
+            api_key.allowed_categories.as_deref(),
+            api_key.allowed_ips.as_deref()
         )
         .fetch_one(&self.pool)
         .await
@@ -840,6 +1175,7 @@ pub struct SyncService {
     redis_client: redis::Client,
     product_service: ProductService,
     event_service: EventService,
+    access_service: AccessService,
 }
 
 impl SyncService {
@@ -848,6 +1184,7 @@ impl SyncService {
             pool: pool.clone(),
             redis_client: redis_client.clone(),
             product_service: ProductService::new(pool.clone(), redis_client.clone()),
+            access_service: AccessService::new(pool.clone()),
             event_service: EventService::new(pool, redis_client),
         }
     }
@@ -899,6 +1236,19 @@ impl SyncService {
         self.event_service.create_event(event).await
     }
 
+/// Mirrors an `AuthorizationContract` grant or revocation so that the API
+/// enforces the same authorized actors as the chain.
+    pub async fn sync_authorized_actor_from_contract(
+        &self,
+        product_id: &str,
+        actor_address: &str,
+        authorized: bool,
+    ) -> Result<(), AppError> {
+        self.access_service
+            .sync_authorized_actor(product_id, actor_address, authorized)
+            .await
+    }
+
 /// Synchronizes multiple products in a batch for efficient bulk operations.
 /// Processes products sequentially to maintain data consistency while
 /// providing better performance than individual calls.
@@ -0,0 +1,356 @@
+/// Product-level access control.
+///
+/// Mirrors the on-chain `AuthorizationContract`: a product's owner (its
+/// `owner_address`) may do anything with it, addresses the owner authorized
+/// may record events, and nobody records events on a deactivated product.
+/// Roles then narrow what a caller may do:
+///   - administrators act on every product
+///   - auditors and customers are read-only
+///   - inspectors may only record inspection events
+///   - `edit` grants let delegated org members change the product itself
+///
+/// Within the organization a product belongs to, owners and admins act as the
+/// product owner, members as holders of an `edit` grant and viewers only read.
+use crate::models::access::ProductPermission;
+use crate::models::organization::OrgRole;
+use crate::models::{Product, UserRole};
+use uuid::Uuid;
+
+/// Event types an inspector may record
+pub const INSPECTION_EVENT_TYPES: &[&str] = &["QUALITY_CHECK"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProductAction<'a> {
+    Read,
+    /// Change product fields or origin plots
+    Edit,
+    Delete,
+    /// Grant or revoke other addresses' access
+    ManageAccess,
+    /// Record a tracking event of the given type
+    AddEvent(&'a str),
+}
+
+/// The caller, as far as product access is concerned
+#[derive(Debug, Clone, Copy)]
+pub struct Principal<'a> {
+    pub role: &'a UserRole,
+    /// The caller's own verified Stellar address is the product's owner address
+    pub is_owner: bool,
+    /// The caller's role in the product's organization, when acting for it
+    pub org_role: Option<OrgRole>,
+}
+
+/// Whether `address`, the caller's verified address, is `product`'s owner
+pub fn is_owner(address: Option<&str>, product: &Product) -> bool {
+    address.is_some_and(|address| {
+        !product.owner_address.is_empty() && address == product.owner_address
+    })
+}
+
+/// The caller's organization role as far as `product` is concerned: it only
+/// counts for products of the organization the caller acts for
+pub fn org_role_for(
+    product: &Product,
+    acting_for: Option<Uuid>,
+    org_role: Option<OrgRole>,
+) -> Option<OrgRole> {
+    match (product.organization_id, acting_for) {
+        (Some(owner), Some(current)) if owner == current => org_role,
+        _ => None,
+    }
+}
+
+/// Whether `product` exists for the principal at all. Administrators see
+/// every product and auditors read every product; everyone else sees the
+/// products they own, their organization's, and those shared with them.
+pub fn is_visible(principal: Principal<'_>, has_grant: bool, action: ProductAction<'_>) -> bool {
+    match principal.role {
+        UserRole::Administrator => true,
+        UserRole::Auditor if action == ProductAction::Read => true,
+        _ => principal.is_owner || principal.org_role.is_some() || has_grant,
+    }
+}
+
+/// Roles that may register new products (and so become their owner)
+pub fn can_create_products(role: &UserRole) -> bool {
+    matches!(role, UserRole::Supplier | UserRole::Administrator)
+}
+
+/// Decides whether `principal` may perform `action` on `product`, given the
+/// grant (if any) the product holds for the principal's address
+pub fn authorize(
+    principal: Principal<'_>,
+    product: &Product,
+    grant: Option<ProductPermission>,
+    action: ProductAction<'_>,
+) -> Result<(), String> {
+    if let ProductAction::AddEvent(_) = action {
+        if !product.is_active {
+            return Err(format!("Product {} is deactivated", product.id));
+        }
+    }
+
+    match principal.role {
+        UserRole::Administrator => return Ok(()),
+        _ if action == ProductAction::Read => return Ok(()),
+        UserRole::Auditor => return Err("Auditors have read-only access".to_string()),
+        UserRole::Customer => return Err("Customers have read-only access".to_string()),
+        UserRole::Inspector => {
+            let inspection = matches!(action, ProductAction::AddEvent(event_type)
+                if INSPECTION_EVENT_TYPES.contains(&event_type));
+            if !inspection {
+                return Err(format!(
+                    "Inspectors may only record inspection events ({})",
+                    INSPECTION_EVENT_TYPES.join(", ")
+                ));
+            }
+        }
+        UserRole::Supplier | UserRole::Carrier => {}
+    }
+
+    if principal.is_owner || principal.org_role.is_some_and(|role| role.can_manage()) {
+        return Ok(());
+    }
+    // Members may do what an `edit` delegate may
+    let grant = match principal.org_role {
+        Some(OrgRole::Member) => Some(ProductPermission::Edit),
+        _ => grant,
+    };
+
+    match action {
+        ProductAction::Read => Ok(()),
+        ProductAction::AddEvent(_) if grant.is_some() => Ok(()),
+        ProductAction::AddEvent(_) => Err(
+            "Only the product owner or an authorized actor may record events for this product"
+                .to_string(),
+        ),
+        ProductAction::Edit if grant == Some(ProductPermission::Edit) => Ok(()),
+        ProductAction::Edit => Err(
+            "Only the product owner or a delegate with edit access may change this product"
+                .to_string(),
+        ),
+        ProductAction::Delete => Err("Only the product owner may delete this product".to_string()),
+        ProductAction::ManageAccess => {
+            Err("Only the product owner may manage access to this product".to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::User;
+    use chrono::Utc;
+    use serde_json::json;
+
+    const OWNER: &str = "GOWNER";
+    const ACTOR: &str = "GACTOR";
+
+    fn product(is_active: bool) -> Product {
+        Product {
+            id: "PROD-1".to_string(),
+            name: "Coffee".to_string(),
+            description: String::new(),
+            origin_location: "Huila".to_string(),
+            category: "coffee".to_string(),
+            tags: vec![],
+            certifications: vec![],
+            media_hashes: vec![],
+            custom_fields: json!({}),
+            owner_address: OWNER.to_string(),
+            is_active,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            created_by: String::new(),
+            updated_by: String::new(),
+            organization_id: None,
+        }
+    }
+
+    fn check(
+        role: UserRole,
+        address: Option<&str>,
+        grant: Option<ProductPermission>,
+        action: ProductAction<'_>,
+    ) -> Result<(), String> {
+        let principal = Principal {
+            role: &role,
+            is_owner: address == Some(OWNER),
+            org_role: None,
+        };
+        authorize(principal, &product(true), grant, action)
+    }
+
+    fn check_member(
+        role: UserRole,
+        org_role: OrgRole,
+        action: ProductAction<'_>,
+    ) -> Result<(), String> {
+        let principal = Principal {
+            role: &role,
+            is_owner: false,
+            org_role: Some(org_role),
+        };
+        authorize(principal, &product(true), None, action)
+    }
+
+    #[test]
+    fn owner_may_do_everything_and_others_only_read() {
+        for action in [
+            ProductAction::Edit,
+            ProductAction::Delete,
+            ProductAction::ManageAccess,
+            ProductAction::AddEvent("SHIP"),
+        ] {
+            assert!(check(UserRole::Supplier, Some(OWNER), None, action).is_ok());
+            assert!(check(UserRole::Supplier, Some(ACTOR), None, action).is_err());
+            assert!(check(UserRole::Supplier, None, None, action).is_err());
+        }
+        assert!(check(UserRole::Carrier, Some(ACTOR), None, ProductAction::Read).is_ok());
+    }
+
+    #[test]
+    fn grants_mirror_authorized_actors() {
+        let (add, edit) = (
+            Some(ProductPermission::AddEvents),
+            Some(ProductPermission::Edit),
+        );
+        let ship = ProductAction::AddEvent("SHIP");
+        assert!(check(UserRole::Carrier, Some(ACTOR), add, ship).is_ok());
+        assert!(check(UserRole::Carrier, Some(ACTOR), add, ProductAction::Edit).is_err());
+        assert!(check(UserRole::Supplier, Some(ACTOR), edit, ProductAction::Edit).is_ok());
+        assert!(check(UserRole::Supplier, Some(ACTOR), edit, ProductAction::Delete).is_err());
+        let manage = ProductAction::ManageAccess;
+        assert!(check(UserRole::Supplier, Some(ACTOR), edit, manage).is_err());
+    }
+
+    #[test]
+    fn roles_narrow_access() {
+        let (add, edit) = (
+            Some(ProductPermission::AddEvents),
+            Some(ProductPermission::Edit),
+        );
+        let ship = ProductAction::AddEvent("SHIP");
+        let inspect = ProductAction::AddEvent("QUALITY_CHECK");
+        assert!(check(UserRole::Auditor, Some(OWNER), edit, ProductAction::Read).is_ok());
+        assert!(check(UserRole::Auditor, Some(OWNER), edit, ProductAction::Edit).is_err());
+        assert!(check(UserRole::Customer, Some(OWNER), edit, ship).is_err());
+
+        assert!(check(UserRole::Inspector, Some(ACTOR), add, inspect).is_ok());
+        assert!(check(UserRole::Inspector, Some(ACTOR), None, inspect).is_err());
+        assert!(check(UserRole::Inspector, Some(ACTOR), add, ship).is_err());
+        assert!(check(UserRole::Inspector, Some(OWNER), edit, ProductAction::Edit).is_err());
+
+        assert!(check(UserRole::Administrator, None, None, ProductAction::Delete).is_ok());
+    }
+
+    #[test]
+    fn organization_roles_act_for_the_owner() {
+        let ship = ProductAction::AddEvent("SHIP");
+        for action in [ProductAction::Delete, ProductAction::ManageAccess, ship] {
+            assert!(check_member(UserRole::Supplier, OrgRole::Owner, action).is_ok());
+            assert!(check_member(UserRole::Supplier, OrgRole::Admin, action).is_ok());
+        }
+
+        assert!(check_member(UserRole::Supplier, OrgRole::Member, ProductAction::Edit).is_ok());
+        assert!(check_member(UserRole::Carrier, OrgRole::Member, ship).is_ok());
+        assert!(check_member(UserRole::Supplier, OrgRole::Member, ProductAction::Delete).is_err());
+
+        assert!(check_member(UserRole::Supplier, OrgRole::Viewer, ProductAction::Read).is_ok());
+        assert!(check_member(UserRole::Supplier, OrgRole::Viewer, ship).is_err());
+
+        // Platform roles still narrow what organization roles allow
+        assert!(check_member(UserRole::Auditor, OrgRole::Owner, ProductAction::Edit).is_err());
+    }
+
+    #[test]
+    fn deactivated_products_take_no_events() {
+        let admin = UserRole::Administrator;
+        let principal = Principal {
+            role: &admin,
+            is_owner: false,
+            org_role: None,
+        };
+        let inactive = product(false);
+        assert!(authorize(principal, &inactive, None, ProductAction::AddEvent("SHIP")).is_err());
+        assert!(authorize(principal, &inactive, None, ProductAction::Edit).is_ok());
+    }
+
+    #[test]
+    fn unverified_address_matching_the_owner_is_refused() {
+        let mut user = User {
+            id: Uuid::new_v4(),
+            email: "owner@example.com".to_string(),
+            password_hash: String::new(),
+            stellar_address: Some(OWNER.to_string()),
+            role: UserRole::Supplier,
+            api_key: None,
+            api_key_hash: None,
+            is_active: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            last_login_at: None,
+            stellar_address_verified_at: None,
+        };
+        let check_user = |user: &User| {
+            let principal = Principal {
+                role: &user.role,
+                is_owner: is_owner(user.verified_stellar_address().as_deref(), &product(true)),
+                org_role: None,
+            };
+            authorize(principal, &product(true), None, ProductAction::Delete)
+        };
+
+        assert!(check_user(&user).is_err());
+        user.stellar_address_verified_at = Some(Utc::now());
+        assert!(check_user(&user).is_ok());
+    }
+
+    #[test]
+    fn organization_roles_stop_at_the_organization() {
+        let (ours, theirs) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut product = product(true);
+        product.organization_id = Some(theirs);
+        let supplier = UserRole::Supplier;
+        let principal = |org_role| Principal {
+            role: &supplier,
+            is_owner: false,
+            org_role,
+        };
+
+        let role = org_role_for(&product, Some(ours), Some(OrgRole::Owner));
+        assert_eq!(role, None);
+        for action in [
+            ProductAction::Edit,
+            ProductAction::Delete,
+            ProductAction::AddEvent("SHIP"),
+        ] {
+            assert!(authorize(principal(role), &product, None, action).is_err());
+        }
+
+        let role = org_role_for(&product, Some(theirs), Some(OrgRole::Owner));
+        assert!(authorize(principal(role), &product, None, ProductAction::Delete).is_ok());
+
+        product.organization_id = None;
+        assert_eq!(
+            org_role_for(&product, Some(ours), Some(OrgRole::Owner)),
+            None
+        );
+    }
+
+    #[test]
+    fn auditors_read_everywhere_but_see_nothing_else() {
+        let principal = |role| Principal {
+            role,
+            is_owner: false,
+            org_role: None,
+        };
+
+        assert!(is_visible(principal(&UserRole::Auditor), false, ProductAction::Read));
+        assert!(!is_visible(principal(&UserRole::Auditor), false, ProductAction::Edit));
+        assert!(is_visible(principal(&UserRole::Administrator), false, ProductAction::Delete));
+        assert!(!is_visible(principal(&UserRole::Supplier), false, ProductAction::Read));
+        assert!(is_visible(principal(&UserRole::Supplier), true, ProductAction::Read));
+    }
+}
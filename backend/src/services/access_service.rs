@@ -0,0 +1,223 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::middleware::auth::AuthContext;
+use crate::models::access::{GrantSource, ProductAccessGrant, ProductPermission};
+use crate::models::organization::OrgRole;
+use crate::models::{Product, UserRole};
+use crate::services::access_control::{self, Principal, ProductAction};
+
+/// Product ownership and per-product grants, kept in step with the on-chain
+/// `AuthorizationContract` and enforced by the product, event and origin
+/// plot handlers.
+///
+/// Products outside the caller's organization, and not shared with it or the
+/// caller through a grant, are reported as not found. Auditors may read
+/// every product.
+pub struct AccessService {
+    pool: PgPool,
+}
+
+impl AccessService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Loads the product and checks that the caller may perform `action` on it
+    pub async fn authorize_product(
+        &self,
+        auth: &AuthContext,
+        product_id: &str,
+        action: ProductAction<'_>,
+    ) -> Result<Product, AppError> {
+        let product = sqlx::query_as::<_, Product>("SELECT * FROM products WHERE id = $1")
+            .bind(product_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Product {} not found", product_id)))?;
+        self.authorize(auth, &product, action).await?;
+        Ok(product)
+    }
+
+    pub async fn authorize(
+        &self,
+        auth: &AuthContext,
+        product: &Product,
+        action: ProductAction<'_>,
+    ) -> Result<(), AppError> {
+        let is_owner = access_control::is_owner(auth.stellar_address.as_deref(), product);
+        let org_role = access_control::org_role_for(product, auth.organization_id, auth.org_role);
+        let grants = self.grants_for(&product.id, auth).await?;
+
+        let principal = Principal {
+            role: &auth.role,
+            is_owner,
+            org_role,
+        };
+        // Products outside an API key's restrictions do not exist for it
+        let visible = access_control::is_visible(principal, !grants.is_empty(), action);
+        if !visible || !auth.key_allows_product(product) {
+            return Err(AppError::NotFound(format!(
+                "Product {} not found",
+                product.id
+            )));
+        }
+
+        // Grants to the organization's addresses do not lift its viewers
+        // above read-only
+        let viewer = auth.org_role == Some(OrgRole::Viewer);
+        let grant = grants
+            .into_iter()
+            .filter(|(_, via_organization)| !(viewer && *via_organization))
+            .map(|(permission, _)| permission)
+            .max_by_key(|permission| *permission == ProductPermission::Edit);
+
+        access_control::authorize(principal, product, grant, action).map_err(AppError::Forbidden)
+    }
+
+    /// Grants the product holds for the caller's own address or for any
+    /// address of the organization the caller acts for
+    async fn grants_for(
+        &self,
+        product_id: &str,
+        auth: &AuthContext,
+    ) -> Result<Vec<(ProductPermission, bool)>, AppError> {
+        let grants = sqlx::query_as::<_, (ProductPermission, bool)>(
+            r#"
+            SELECT permission, actor_address IS DISTINCT FROM $2 AS via_organization
+            FROM product_access_grants
+            WHERE product_id = $1
+              AND (actor_address = $2
+                   OR actor_address IN (SELECT stellar_address FROM organization_addresses
+                                        WHERE organization_id = $3))
+            "#,
+        )
+        .bind(product_id)
+        .bind(auth.stellar_address.as_deref())
+        .bind(auth.organization_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(grants)
+    }
+
+    /// Whether the caller may sign as `address`: their own verified address
+    /// or, for members who can write, one of their organization's addresses
+    pub async fn may_act_as(&self, auth: &AuthContext, address: &str) -> Result<bool, AppError> {
+        if matches!(auth.role, UserRole::Administrator)
+            || auth.stellar_address.as_deref() == Some(address)
+        {
+            return Ok(true);
+        }
+        let (Some(organization_id), Some(org_role)) = (auth.organization_id, auth.org_role) else {
+            return Ok(false);
+        };
+        if org_role == OrgRole::Viewer {
+            return Ok(false);
+        }
+        let held = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM organization_addresses
+                           WHERE organization_id = $1 AND stellar_address = $2)
+            "#,
+        )
+        .bind(organization_id)
+        .bind(address)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(held)
+    }
+
+    // ── Grants ────────────────────────────────────────────────────────────────
+
+    pub async fn list_grants(&self, product_id: &str) -> Result<Vec<ProductAccessGrant>, AppError> {
+        let grants = sqlx::query_as::<_, ProductAccessGrant>(
+            "SELECT * FROM product_access_grants WHERE product_id = $1 ORDER BY created_at",
+        )
+        .bind(product_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(grants)
+    }
+
+    /// Grants or changes an address's access. Re-granting replaces the
+    /// permission; the owner needs no grant.
+    pub async fn grant(
+        &self,
+        product_id: &str,
+        actor_address: &str,
+        permission: ProductPermission,
+        source: GrantSource,
+        granted_by: Option<Uuid>,
+    ) -> Result<ProductAccessGrant, AppError> {
+        let grant = sqlx::query_as::<_, ProductAccessGrant>(
+            r#"
+            INSERT INTO product_access_grants (product_id, actor_address, permission, source, granted_by)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (product_id, actor_address) DO UPDATE
+            SET permission = EXCLUDED.permission,
+                source = EXCLUDED.source,
+                granted_by = EXCLUDED.granted_by,
+                created_at = NOW()
+            RETURNING *
+            "#,
+        )
+        .bind(product_id)
+        .bind(actor_address)
+        .bind(permission)
+        .bind(source)
+        .bind(granted_by)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(grant)
+    }
+
+    pub async fn revoke(&self, product_id: &str, actor_address: &str) -> Result<(), AppError> {
+        let result = sqlx::query(
+            "DELETE FROM product_access_grants WHERE product_id = $1 AND actor_address = $2",
+        )
+        .bind(product_id)
+        .bind(actor_address)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!(
+                "{} has no access grant on product {}",
+                actor_address, product_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Applies an `add_authorized_actor` / `remove_authorized_actor` call seen
+    /// on chain. Existing `edit` grants are kept when the chain re-authorizes.
+    pub async fn sync_authorized_actor(
+        &self,
+        product_id: &str,
+        actor_address: &str,
+        authorized: bool,
+    ) -> Result<(), AppError> {
+        if authorized {
+            sqlx::query(
+                r#"
+                INSERT INTO product_access_grants (product_id, actor_address, permission, source)
+                VALUES ($1, $2, 'add_events', 'chain')
+                ON CONFLICT (product_id, actor_address) DO NOTHING
+                "#,
+            )
+            .bind(product_id)
+            .bind(actor_address)
+            .execute(&self.pool)
+            .await?;
+        } else {
+            sqlx::query(
+                "DELETE FROM product_access_grants WHERE product_id = $1 AND actor_address = $2",
+            )
+            .bind(product_id)
+            .bind(actor_address)
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+}
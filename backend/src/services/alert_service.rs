@@ -0,0 +1,363 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::AlertingConfig;
+use crate::error::AppError;
+use crate::models::{Alert, AlertSilence, AlertStatus, NewAlertSilence};
+use crate::monitoring::alert_sinks::AlertRouter;
+use crate::monitoring::alerts::{self, AlertRule, ReportedSignal, Signals, Timing};
+use crate::monitoring::metrics::{RequestTotals, METRICS};
+
+/// Serializes evaluations across instances, so an alert is notified once
+const EVALUATION_LOCK: &str = "alert_evaluation";
+/// A per-instance alert its instance has not refreshed for this many
+/// evaluation intervals is resolved by another instance
+const STALE_INTERVALS: u32 = 10;
+/// Resolved alerts and ended silences are kept this long
+const RETENTION_DAYS: i32 = 30;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+const LIST_LIMIT: i64 = 200;
+
+/// Evaluates the alert rules on an interval, keeps each alert's state in the
+/// database and sends notifications through the router. Every instance
+/// evaluates; evaluations take turns under an advisory lock.
+pub struct AlertService {
+    pool: PgPool,
+    router: AlertRouter,
+    rules: Vec<AlertRule>,
+    /// Subject of this instance's per-instance alerts
+    instance: String,
+    interval: Duration,
+    timing: Timing,
+}
+
+impl AlertService {
+    pub fn new(
+        pool: PgPool,
+        router: AlertRouter,
+        rules: Vec<AlertRule>,
+        config: &AlertingConfig,
+    ) -> Self {
+        let interval = Duration::from_secs(config.evaluation_interval_secs);
+        let instance = std::env::var("HOSTNAME")
+            .ok()
+            .filter(|h| !h.is_empty())
+            .unwrap_or_else(|| format!("instance-{}", &Uuid::new_v4().simple().to_string()[..8]));
+        Self {
+            pool,
+            router,
+            rules,
+            instance,
+            interval,
+            timing: Timing {
+                repeat_interval: chrono::Duration::seconds(config.repeat_interval_secs as i64),
+                stale_after: chrono::Duration::seconds(
+                    (config.evaluation_interval_secs * STALE_INTERVALS as u64) as i64,
+                ),
+            },
+        }
+    }
+
+    /// Uses the rules in `config.rules_path`, or the built-in ones
+    pub fn from_config(
+        pool: PgPool,
+        router: AlertRouter,
+        config: &AlertingConfig,
+    ) -> Result<Self, String> {
+        let rules = match &config.rules_path {
+            Some(path) => {
+                let yaml = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Could not read alert rules {}: {}", path, e))?;
+                alerts::parse_rules(&yaml)?
+            }
+            None => alerts::default_rules(),
+        };
+        Ok(Self::new(pool, router, rules, config))
+    }
+
+    pub fn rules(&self) -> &[AlertRule] {
+        &self.rules
+    }
+
+    // ── Evaluation ────────────────────────────────────────────────────────────
+
+    pub fn start(self: Arc<Self>) {
+        let evaluator = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(evaluator.interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            let mut since = (Instant::now(), METRICS.request_totals());
+            loop {
+                interval.tick().await;
+                let totals = METRICS.request_totals();
+                let minutes = since.0.elapsed().as_secs_f64() / 60.0;
+                let result = match evaluator.collect_signals(&since.1, &totals, minutes).await {
+                    Ok(signals) => evaluator.evaluate(signals).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    tracing::error!("Alert evaluation failed: {}", e);
+                }
+                since = (Instant::now(), totals);
+            }
+        });
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.prune().await {
+                    tracing::error!("Failed to prune alert history: {}", e);
+                }
+            }
+        });
+    }
+
+    /// This instance's request totals since `earlier`, `minutes` ago, and the
+    /// shared signals from the database
+    async fn collect_signals(
+        &self,
+        earlier: &RequestTotals,
+        totals: &RequestTotals,
+        minutes: f64,
+    ) -> Result<Signals, AppError> {
+        let (sync_lag_secs,) = sqlx::query_as::<_, (Option<f64>,)>(
+            "SELECT EXTRACT(EPOCH FROM NOW() - MAX(timestamp))::FLOAT8 FROM tracking_events",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        let reported = sqlx::query_as::<_, (String, String, Option<String>)>(
+            "SELECT kind, subject, detail FROM alert_signals WHERE active",
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|(kind, subject, detail)| ReportedSignal {
+            kind,
+            subject,
+            detail,
+        })
+        .collect();
+
+        Ok(Signals {
+            instance: self.instance.clone(),
+            error_rate: (minutes > 0.0)
+                .then(|| totals.server_errors_since(earlier) as f64 / minutes),
+            latency_p95_ms: totals
+                .latency_quantile(earlier, 0.95)
+                .map(|seconds| seconds * 1000.0),
+            sync_lag_secs: sync_lag_secs.map(|lag| lag.max(0.0)),
+            reported,
+        })
+    }
+
+    /// Moves every alert to its next state and sends the notifications that
+    /// are due. Runs under the evaluation lock, notifications included, so
+    /// that two instances never notify the same change.
+    async fn evaluate(&self, signals: Signals) -> Result<(), AppError> {
+        let firings = alerts::evaluate(&self.rules, &signals);
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+            .bind(EVALUATION_LOCK)
+            .execute(&mut *tx)
+            .await?;
+        let previous =
+            sqlx::query_as::<_, Alert>("SELECT * FROM alert_states WHERE status = 'firing'")
+                .fetch_all(&mut *tx)
+                .await?;
+        let silences = sqlx::query_as::<_, AlertSilence>(
+            "SELECT * FROM alert_silences WHERE starts_at <= NOW() AND ends_at > NOW()",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let now = Utc::now();
+        let steps = alerts::step(
+            now,
+            self.timing,
+            &self.rules,
+            &self.instance,
+            firings,
+            &previous,
+            &silences,
+        );
+        for step in steps {
+            let mut alert = step.alert;
+            if step.notify && self.router.send(&alert).await && alert.status == AlertStatus::Firing
+            {
+                alert.last_notified_at = Some(now);
+            }
+            sqlx::query(
+                r#"
+                INSERT INTO alert_states
+                    (fingerprint, rule, subject, severity, status, summary,
+                     started_at, last_notified_at, resolved_at, updated_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                ON CONFLICT (fingerprint) DO UPDATE SET
+                    severity = EXCLUDED.severity,
+                    status = EXCLUDED.status,
+                    summary = EXCLUDED.summary,
+                    started_at = EXCLUDED.started_at,
+                    last_notified_at = EXCLUDED.last_notified_at,
+                    resolved_at = EXCLUDED.resolved_at,
+                    updated_at = EXCLUDED.updated_at
+                "#,
+            )
+            .bind(&alert.fingerprint)
+            .bind(&alert.rule)
+            .bind(&alert.subject)
+            .bind(alert.severity)
+            .bind(alert.status)
+            .bind(&alert.summary)
+            .bind(alert.started_at)
+            .bind(alert.last_notified_at)
+            .bind(alert.resolved_at)
+            .bind(alert.updated_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Drops resolved alerts and ended silences past the retention period
+    async fn prune(&self) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            DELETE FROM alert_states
+            WHERE status = 'resolved' AND resolved_at < NOW() - make_interval(days => $1)
+            "#,
+        )
+        .bind(RETENTION_DAYS)
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("DELETE FROM alert_silences WHERE ends_at < NOW() - make_interval(days => $1)")
+            .bind(RETENTION_DAYS)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // ── Reported signals ──────────────────────────────────────────────────────
+
+    /// Records whether a reported condition, such as a tripped oracle circuit
+    /// breaker, currently holds for `subject`. Rules pick it up on their next
+    /// evaluation.
+    pub async fn report_signal(
+        &self,
+        kind: &str,
+        subject: &str,
+        active: bool,
+        detail: Option<String>,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            INSERT INTO alert_signals (kind, subject, active, detail)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (kind, subject) DO UPDATE SET
+                active = EXCLUDED.active,
+                detail = EXCLUDED.detail,
+                updated_at = NOW()
+            WHERE alert_signals.active IS DISTINCT FROM EXCLUDED.active
+               OR alert_signals.detail IS DISTINCT FROM EXCLUDED.detail
+            "#,
+        )
+        .bind(kind)
+        .bind(subject)
+        .bind(active)
+        .bind(detail)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // ── Alerts and silences ───────────────────────────────────────────────────
+
+    /// Alerts, firing ones first, then most recently changed
+    pub async fn list_alerts(&self, status: Option<AlertStatus>) -> Result<Vec<Alert>, AppError> {
+        let alerts = sqlx::query_as::<_, Alert>(
+            r#"
+            SELECT * FROM alert_states
+            WHERE $1::TEXT IS NULL OR status = $1
+            ORDER BY status = 'firing' DESC, updated_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(status.map(|s| s.as_str()))
+        .bind(LIST_LIMIT)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(alerts)
+    }
+
+    /// Silences that have not ended, or every recent one
+    pub async fn list_silences(&self, include_ended: bool) -> Result<Vec<AlertSilence>, AppError> {
+        let silences = sqlx::query_as::<_, AlertSilence>(
+            r#"
+            SELECT * FROM alert_silences
+            WHERE $1 OR ends_at > NOW()
+            ORDER BY starts_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(include_ended)
+        .bind(LIST_LIMIT)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(silences)
+    }
+
+    pub async fn create_silence(
+        &self,
+        silence: NewAlertSilence,
+        created_by: Uuid,
+    ) -> Result<AlertSilence, AppError> {
+        let starts_at = silence.starts_at.unwrap_or_else(Utc::now);
+        if silence.reason.trim().is_empty() {
+            return Err(AppError::Validation("A silence needs a reason".to_string()));
+        }
+        if silence.ends_at <= starts_at || silence.ends_at <= Utc::now() {
+            return Err(AppError::Validation(
+                "ends_at must be in the future and after starts_at".to_string(),
+            ));
+        }
+        if let Some(rule) = &silence.rule {
+            if !self.rules.iter().any(|r| &r.name == rule) {
+                return Err(AppError::Validation(format!(
+                    "Unknown alert rule '{}'",
+                    rule
+                )));
+            }
+        }
+
+        let created = sqlx::query_as::<_, AlertSilence>(
+            r#"
+            INSERT INTO alert_silences (rule, subject, reason, starts_at, ends_at, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+            "#,
+        )
+        .bind(&silence.rule)
+        .bind(&silence.subject)
+        .bind(silence.reason.trim())
+        .bind(starts_at)
+        .bind(silence.ends_at)
+        .bind(created_by)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(created)
+    }
+
+    pub async fn delete_silence(&self, id: Uuid) -> Result<bool, AppError> {
+        let result = sqlx::query("DELETE FROM alert_silences WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
 use redis::AsyncCommands;
 use sqlx::PgPool;
+use uuid::Uuid;
 
 use crate::error::AppError;
 use crate::models::analytics::{
@@ -13,6 +14,11 @@ use crate::utils::aggregation::{
 
 const CACHE_TTL_SECS: usize = 300; // 5 minutes
 
+/// Cache key segment for an organization; `all` is the platform-wide view
+fn scope_key(organization_id: Option<Uuid>) -> String {
+    organization_id.map_or_else(|| "all".to_string(), |id| id.to_string())
+}
+
 pub struct AnalyticsService {
     pool: PgPool,
     redis_url: String,
@@ -41,11 +47,18 @@ impl AnalyticsService {
         }
     }
 
+    // Every aggregate takes an optional organization: `Some` restricts it to
+    // that organization's own products, users and keys, `None` covers the
+    // whole platform (administrators).
+
     // --- Dashboard Analytics ---
 
-    pub async fn get_dashboard_metrics(&self) -> Result<DashboardMetrics, AppError> {
-        let cache_key = "analytics:dashboard";
-        if let Some(cached) = self.cache_get(cache_key).await {
+    pub async fn get_dashboard_metrics(
+        &self,
+        organization_id: Option<Uuid>,
+    ) -> Result<DashboardMetrics, AppError> {
+        let cache_key = format!("analytics:dashboard:{}", scope_key(organization_id));
+        if let Some(cached) = self.cache_get(&cache_key).await {
             if let Ok(metrics) = serde_json::from_str::<DashboardMetrics>(&cached) {
                 return Ok(metrics);
             }
@@ -59,20 +72,25 @@ impl AnalyticsService {
         // Core counts
         let counts = sqlx::query!(
             r#"
+            WITH p AS (SELECT * FROM products WHERE $4::uuid IS NULL OR organization_id = $4),
+                 e AS (SELECT * FROM tracking_events WHERE $4::uuid IS NULL OR product_id IN (SELECT id FROM p))
             SELECT
-                (SELECT COUNT(*) FROM products)                                    AS total_products,
-                (SELECT COUNT(*) FROM products WHERE is_active = true)             AS active_products,
-                (SELECT COUNT(*) FROM products WHERE is_active = false)            AS inactive_products,
-                (SELECT COUNT(*) FROM tracking_events)                             AS total_events,
-                (SELECT COUNT(*) FROM users)                                       AS total_users,
-                (SELECT COUNT(*) FROM tracking_events WHERE created_at >= $1)      AS events_last_24h,
-                (SELECT COUNT(*) FROM tracking_events WHERE created_at >= $2)      AS events_last_7d,
-                (SELECT COUNT(*) FROM tracking_events WHERE created_at >= $3)      AS events_last_30d,
-                (SELECT COUNT(*) FROM products WHERE created_at >= $3)             AS products_last_30d
+                (SELECT COUNT(*) FROM p)                                           AS total_products,
+                (SELECT COUNT(*) FROM p WHERE is_active = true)                    AS active_products,
+                (SELECT COUNT(*) FROM p WHERE is_active = false)                   AS inactive_products,
+                (SELECT COUNT(*) FROM e)                                           AS total_events,
+                (SELECT COUNT(*) FROM users
+                    WHERE $4::uuid IS NULL
+                       OR id IN (SELECT user_id FROM organization_members WHERE organization_id = $4)) AS total_users,
+                (SELECT COUNT(*) FROM e WHERE created_at >= $1)                    AS events_last_24h,
+                (SELECT COUNT(*) FROM e WHERE created_at >= $2)                    AS events_last_7d,
+                (SELECT COUNT(*) FROM e WHERE created_at >= $3)                    AS events_last_30d,
+                (SELECT COUNT(*) FROM p WHERE created_at >= $3)                    AS products_last_30d
             "#,
             day_ago,
             week_ago,
             month_ago,
+            organization_id,
         )
         .fetch_one(&self.pool)
         .await?;
@@ -82,10 +100,12 @@ impl AnalyticsService {
             r#"
             SELECT event_type, COUNT(*) AS count
             FROM tracking_events
+            WHERE $1::uuid IS NULL OR product_id IN (SELECT id FROM products WHERE organization_id = $1)
             GROUP BY event_type
             ORDER BY count DESC
             LIMIT 10
-            "#
+            "#,
+            organization_id,
         )
         .fetch_all(&self.pool)
         .await?;
@@ -104,10 +124,12 @@ impl AnalyticsService {
                 COUNT(*) AS count,
                 COUNT(*) FILTER (WHERE is_active = true) AS active_count
             FROM products
+            WHERE $1::uuid IS NULL OR organization_id = $1
             GROUP BY category
             ORDER BY count DESC
             LIMIT 10
-            "#
+            "#,
+            organization_id,
         )
         .fetch_all(&self.pool)
         .await?;
@@ -137,7 +159,7 @@ impl AnalyticsService {
         };
 
         if let Ok(json) = serde_json::to_string(&metrics) {
-            self.cache_set(cache_key, &json).await;
+            self.cache_set(&cache_key, &json).await;
         }
 
         Ok(metrics)
@@ -266,9 +288,11 @@ impl AnalyticsService {
         start: DateTime<Utc>,
         end: DateTime<Utc>,
         event_type_filter: Option<&str>,
+        organization_id: Option<Uuid>,
     ) -> Result<EventAnalytics, AppError> {
         let cache_key = format!(
-            "analytics:events:{}:{}:{}",
+            "analytics:events:{}:{}:{}:{}",
+            scope_key(organization_id),
             start.format("%Y%m%d"),
             end.format("%Y%m%d"),
             event_type_filter.unwrap_or("all")
@@ -281,9 +305,14 @@ impl AnalyticsService {
 
         // Total count
         let total_row = sqlx::query!(
-            "SELECT COUNT(*) AS count FROM tracking_events WHERE timestamp BETWEEN $1 AND $2",
+            r#"
+            SELECT COUNT(*) AS count FROM tracking_events
+            WHERE timestamp BETWEEN $1 AND $2
+              AND ($3::uuid IS NULL OR product_id IN (SELECT id FROM products WHERE organization_id = $3))
+            "#,
             start,
-            end
+            end,
+            organization_id,
         )
         .fetch_one(&self.pool)
         .await?;
@@ -295,12 +324,14 @@ impl AnalyticsService {
             SELECT event_type, COUNT(*) AS count
             FROM tracking_events
             WHERE timestamp BETWEEN $1 AND $2
+              AND ($3::uuid IS NULL OR product_id IN (SELECT id FROM products WHERE organization_id = $3))
             GROUP BY event_type
             ORDER BY count DESC
             LIMIT 20
             "#,
             start,
-            end
+            end,
+            organization_id,
         )
         .fetch_all(&self.pool)
         .await?;
@@ -316,12 +347,14 @@ impl AnalyticsService {
             SELECT location, COUNT(*) AS count
             FROM tracking_events
             WHERE timestamp BETWEEN $1 AND $2
+              AND ($3::uuid IS NULL OR product_id IN (SELECT id FROM products WHERE organization_id = $3))
             GROUP BY location
             ORDER BY count DESC
             LIMIT 20
             "#,
             start,
-            end
+            end,
+            organization_id,
         )
         .fetch_all(&self.pool)
         .await?;
@@ -339,12 +372,14 @@ impl AnalyticsService {
             SELECT actor_address, COUNT(*) AS count
             FROM tracking_events
             WHERE timestamp BETWEEN $1 AND $2
+              AND ($3::uuid IS NULL OR product_id IN (SELECT id FROM products WHERE organization_id = $3))
             GROUP BY actor_address
             ORDER BY count DESC
             LIMIT 20
             "#,
             start,
-            end
+            end,
+            organization_id,
         )
         .fetch_all(&self.pool)
         .await?;
@@ -362,11 +397,13 @@ impl AnalyticsService {
             SELECT EXTRACT(HOUR FROM timestamp)::INT AS hour, COUNT(*) AS count
             FROM tracking_events
             WHERE timestamp BETWEEN $1 AND $2
+              AND ($3::uuid IS NULL OR product_id IN (SELECT id FROM products WHERE organization_id = $3))
             GROUP BY EXTRACT(HOUR FROM timestamp)
             ORDER BY hour
             "#,
             start,
-            end
+            end,
+            organization_id,
         )
         .fetch_all(&self.pool)
         .await?;
@@ -384,11 +421,13 @@ impl AnalyticsService {
                 COUNT(*) AS count
             FROM tracking_events
             WHERE timestamp BETWEEN $1 AND $2
+              AND ($3::uuid IS NULL OR product_id IN (SELECT id FROM products WHERE organization_id = $3))
             GROUP BY DATE_TRUNC('day', timestamp)
             ORDER BY DATE_TRUNC('day', timestamp)
             "#,
             start,
-            end
+            end,
+            organization_id,
         )
         .fetch_all(&self.pool)
         .await?;
@@ -405,9 +444,14 @@ impl AnalyticsService {
 
         // Avg events per product
         let product_count_row = sqlx::query!(
-            "SELECT COUNT(DISTINCT product_id) AS count FROM tracking_events WHERE timestamp BETWEEN $1 AND $2",
+            r#"
+            SELECT COUNT(DISTINCT product_id) AS count FROM tracking_events
+            WHERE timestamp BETWEEN $1 AND $2
+              AND ($3::uuid IS NULL OR product_id IN (SELECT id FROM products WHERE organization_id = $3))
+            "#,
             start,
-            end
+            end,
+            organization_id,
         )
         .fetch_one(&self.pool)
         .await?;
@@ -421,12 +465,14 @@ impl AnalyticsService {
             FROM tracking_events e
             JOIN products p ON p.id = e.product_id
             WHERE e.timestamp BETWEEN $1 AND $2
+              AND ($3::uuid IS NULL OR p.organization_id = $3)
             GROUP BY e.product_id, p.name
             ORDER BY event_count DESC
             LIMIT 10
             "#,
             start,
-            end
+            end,
+            organization_id,
         )
         .fetch_all(&self.pool)
         .await?;
@@ -459,9 +505,12 @@ impl AnalyticsService {
 
     // --- User Analytics ---
 
-    pub async fn get_user_analytics(&self) -> Result<UserAnalytics, AppError> {
-        let cache_key = "analytics:users";
-        if let Some(cached) = self.cache_get(cache_key).await {
+    pub async fn get_user_analytics(
+        &self,
+        organization_id: Option<Uuid>,
+    ) -> Result<UserAnalytics, AppError> {
+        let cache_key = format!("analytics:users:{}", scope_key(organization_id));
+        if let Some(cached) = self.cache_get(&cache_key).await {
             if let Ok(analytics) = serde_json::from_str::<UserAnalytics>(&cached) {
                 return Ok(analytics);
             }
@@ -471,15 +520,22 @@ impl AnalyticsService {
 
         let counts = sqlx::query!(
             r#"
+            WITH u AS (
+                     SELECT * FROM users
+                     WHERE $2::uuid IS NULL
+                        OR id IN (SELECT user_id FROM organization_members WHERE organization_id = $2)
+                 ),
+                 k AS (SELECT * FROM api_keys WHERE $2::uuid IS NULL OR organization_id = $2)
             SELECT
-                (SELECT COUNT(*) FROM users)                                        AS total_users,
-                (SELECT COUNT(*) FROM users WHERE is_active = true)                 AS active_users,
-                (SELECT COUNT(*) FROM users WHERE stellar_address IS NOT NULL)      AS users_with_stellar,
-                (SELECT COUNT(*) FROM users WHERE created_at >= $1)                 AS new_users_last_30d,
-                (SELECT COUNT(*) FROM api_keys)                                     AS total_api_keys,
-                (SELECT COUNT(*) FROM api_keys WHERE is_active = true)              AS active_api_keys
+                (SELECT COUNT(*) FROM u)                                            AS total_users,
+                (SELECT COUNT(*) FROM u WHERE is_active = true)                     AS active_users,
+                (SELECT COUNT(*) FROM u WHERE stellar_address IS NOT NULL)          AS users_with_stellar,
+                (SELECT COUNT(*) FROM u WHERE created_at >= $1)                     AS new_users_last_30d,
+                (SELECT COUNT(*) FROM k)                                            AS total_api_keys,
+                (SELECT COUNT(*) FROM k WHERE is_active = true)                     AS active_api_keys
             "#,
             month_ago,
+            organization_id,
         )
         .fetch_one(&self.pool)
         .await?;
@@ -489,9 +545,11 @@ impl AnalyticsService {
             r#"
             SELECT tier, COUNT(*) AS count
             FROM api_keys
+            WHERE $1::uuid IS NULL OR organization_id = $1
             GROUP BY tier
             ORDER BY count DESC
-            "#
+            "#,
+            organization_id,
         )
         .fetch_all(&self.pool)
         .await?;
@@ -512,10 +570,13 @@ impl AnalyticsService {
                 COUNT(*) AS count
             FROM users
             WHERE created_at >= $1
+              AND ($2::uuid IS NULL
+                   OR id IN (SELECT user_id FROM organization_members WHERE organization_id = $2))
             GROUP BY DATE_TRUNC('day', created_at)
             ORDER BY DATE_TRUNC('day', created_at)
             "#,
             series_start,
+            organization_id,
         )
         .fetch_all(&self.pool)
         .await?;
@@ -543,7 +604,7 @@ impl AnalyticsService {
         };
 
         if let Ok(json) = serde_json::to_string(&analytics) {
-            self.cache_set(cache_key, &json).await;
+            self.cache_set(&cache_key, &json).await;
         }
 
         Ok(analytics)
@@ -557,6 +618,7 @@ impl AnalyticsService {
         end: DateTime<Utc>,
         product_id: Option<&str>,
         limit: i64,
+        organization_id: Option<Uuid>,
     ) -> Result<String, AppError> {
         let rows = sqlx::query!(
             r#"
@@ -574,6 +636,7 @@ impl AnalyticsService {
             JOIN products p ON p.id = e.product_id
             WHERE e.timestamp BETWEEN $1 AND $2
               AND ($3::TEXT IS NULL OR e.product_id = $3)
+              AND ($5::uuid IS NULL OR p.organization_id = $5)
             ORDER BY e.timestamp DESC
             LIMIT $4
             "#,
@@ -581,6 +644,7 @@ impl AnalyticsService {
             end,
             product_id,
             limit,
+            organization_id,
         )
         .fetch_all(&self.pool)
         .await?;
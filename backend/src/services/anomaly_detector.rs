@@ -0,0 +1,390 @@
+/// Scoring rules for suspicious tracking events.
+///
+/// Each rule looks at a new event together with the context gathered by
+/// `AnomalyService` and returns a finding with a score between 0 and 1:
+///   - Impossible travel:  implied speed between consecutive locations
+///   - Unusual actor:      first-time actor with little history anywhere
+///   - Out-of-order:       timestamp earlier than the previous event, or in the future
+///   - Duplicate hash:     data hash already recorded (on another product = likely clone)
+///   - Event burst:        too many events for one product in a short window
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{json, Value};
+
+use crate::models::analytics::AnomalyType;
+use crate::models::TrackingEvent;
+use crate::utils::geo::{self, Coordinates};
+
+/// Fastest plausible door-to-door speed (commercial air freight), km/h
+pub const MAX_PLAUSIBLE_SPEED_KMH: f64 = 1_000.0;
+/// Actors with fewer events than this across all products are considered new
+pub const UNUSUAL_ACTOR_MAX_HISTORY: i64 = 3;
+/// Allowed clock skew before a timestamp counts as being in the future
+pub const FUTURE_SKEW_SECONDS: i64 = 300;
+/// Window and threshold for burst detection
+pub const BURST_WINDOW_SECONDS: i64 = 60;
+pub const BURST_THRESHOLD: i64 = 10;
+
+/// Findings at or above this score are reported as alerts
+pub const ALERT_SCORE: f64 = 0.5;
+
+/// Everything the rules need besides the event itself
+#[derive(Debug, Clone, Default)]
+pub struct AnomalyContext {
+    /// Most recent earlier-recorded event for the same product
+    pub previous: Option<TrackingEvent>,
+    /// Resolved coordinates of the event and of `previous`, when geocoded
+    pub location: Option<Coordinates>,
+    pub previous_location: Option<Coordinates>,
+    /// Owner of the product the event belongs to
+    pub product_owner: Option<String>,
+    /// Whether the actor has recorded events on this product before
+    pub actor_seen_on_product: bool,
+    /// Events recorded by the actor across every product
+    pub actor_total_events: i64,
+    /// Other events with the same data hash: (event id, product id)
+    pub duplicate_hashes: Vec<(i64, String)>,
+    /// Events for the product within `BURST_WINDOW_SECONDS` of this one, including it
+    pub events_in_burst_window: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnomalyFinding {
+    pub anomaly_type: AnomalyType,
+    pub score: f64,
+    pub details: Value,
+}
+
+impl AnomalyFinding {
+    pub fn severity(&self) -> &'static str {
+        severity_for(self.score)
+    }
+}
+
+// ── Public API ────────────────────────────────────────────────────────────────
+
+/// Runs every rule against the event and returns the findings that fired.
+pub fn score_event(
+    event: &TrackingEvent,
+    ctx: &AnomalyContext,
+    now: DateTime<Utc>,
+) -> Vec<AnomalyFinding> {
+    [
+        impossible_travel(event, ctx),
+        unusual_actor(event, ctx),
+        out_of_order(event, ctx, now),
+        duplicate_hash(event, ctx),
+        burst(ctx),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+pub fn severity_for(score: f64) -> &'static str {
+    if score >= 0.8 {
+        "high"
+    } else if score >= ALERT_SCORE {
+        "medium"
+    } else {
+        "low"
+    }
+}
+
+/// Reads coordinates from event metadata (`latitude`/`longitude` or `lat`/`lon`)
+/// or from a `"lat,lon"` location string.
+pub fn coordinates_of(event: &TrackingEvent) -> Option<Coordinates> {
+    geo::coordinates_from_metadata(&event.metadata)
+        .or_else(|| geo::parse_coordinates(&event.location))
+}
+
+// ── Rules ─────────────────────────────────────────────────────────────────────
+
+fn impossible_travel(event: &TrackingEvent, ctx: &AnomalyContext) -> Option<AnomalyFinding> {
+    let previous = ctx.previous.as_ref()?;
+    let from = ctx.previous_location.or_else(|| coordinates_of(previous))?;
+    let to = ctx.location.or_else(|| coordinates_of(event))?;
+
+    let distance_km = geo::haversine_km(from, to);
+    let hours = (event.timestamp - previous.timestamp).num_seconds().abs() as f64 / 3600.0;
+    // Same instant at two distant places is as impossible as it gets
+    let speed_kmh = if hours > 0.0 {
+        distance_km / hours
+    } else if distance_km > 1.0 {
+        f64::INFINITY
+    } else {
+        0.0
+    };
+
+    if speed_kmh <= MAX_PLAUSIBLE_SPEED_KMH {
+        return None;
+    }
+
+    Some(AnomalyFinding {
+        anomaly_type: AnomalyType::ImpossibleTravel,
+        score: (1.0 - MAX_PLAUSIBLE_SPEED_KMH / speed_kmh).clamp(0.5, 1.0),
+        details: json!({
+            "previous_event_id": previous.id,
+            "from": previous.location,
+            "to": event.location,
+            "distance_km": round2(distance_km),
+            "elapsed_hours": round2(hours),
+            "implied_speed_kmh": if speed_kmh.is_finite() { json!(round2(speed_kmh)) } else { Value::Null },
+        }),
+    })
+}
+
+fn unusual_actor(event: &TrackingEvent, ctx: &AnomalyContext) -> Option<AnomalyFinding> {
+    let is_owner = ctx.product_owner.as_deref() == Some(event.actor_address.as_str());
+    if is_owner || ctx.actor_seen_on_product || ctx.actor_total_events >= UNUSUAL_ACTOR_MAX_HISTORY
+    {
+        return None;
+    }
+
+    // A brand-new actor is more suspicious than one with a little history
+    let score = if ctx.actor_total_events == 0 {
+        0.6
+    } else {
+        0.4
+    };
+
+    Some(AnomalyFinding {
+        anomaly_type: AnomalyType::UnusualActor,
+        score,
+        details: json!({
+            "actor_address": event.actor_address,
+            "actor_total_events": ctx.actor_total_events,
+        }),
+    })
+}
+
+fn out_of_order(
+    event: &TrackingEvent,
+    ctx: &AnomalyContext,
+    now: DateTime<Utc>,
+) -> Option<AnomalyFinding> {
+    if event.timestamp > now + Duration::seconds(FUTURE_SKEW_SECONDS) {
+        return Some(AnomalyFinding {
+            anomaly_type: AnomalyType::OutOfOrderTimestamp,
+            score: 0.9,
+            details: json!({
+                "reason": "future_timestamp",
+                "timestamp": event.timestamp,
+                "received_at": now,
+            }),
+        });
+    }
+
+    let previous = ctx.previous.as_ref()?;
+    if event.timestamp >= previous.timestamp {
+        return None;
+    }
+
+    Some(AnomalyFinding {
+        anomaly_type: AnomalyType::OutOfOrderTimestamp,
+        score: 0.7,
+        details: json!({
+            "reason": "earlier_than_previous",
+            "previous_event_id": previous.id,
+            "previous_timestamp": previous.timestamp,
+            "timestamp": event.timestamp,
+        }),
+    })
+}
+
+fn duplicate_hash(event: &TrackingEvent, ctx: &AnomalyContext) -> Option<AnomalyFinding> {
+    if ctx.duplicate_hashes.is_empty() {
+        return None;
+    }
+
+    let cross_product = ctx
+        .duplicate_hashes
+        .iter()
+        .any(|(_, product_id)| product_id != &event.product_id);
+    let event_ids: Vec<i64> = ctx.duplicate_hashes.iter().map(|(id, _)| *id).collect();
+
+    Some(AnomalyFinding {
+        anomaly_type: AnomalyType::DuplicateDataHash,
+        // The same payload on another product is the classic counterfeit signature
+        score: if cross_product { 1.0 } else { 0.8 },
+        details: json!({
+            "data_hash": event.data_hash,
+            "duplicate_event_ids": event_ids,
+            "cross_product": cross_product,
+        }),
+    })
+}
+
+fn burst(ctx: &AnomalyContext) -> Option<AnomalyFinding> {
+    if ctx.events_in_burst_window <= BURST_THRESHOLD {
+        return None;
+    }
+
+    let ratio = ctx.events_in_burst_window as f64 / BURST_THRESHOLD as f64;
+    Some(AnomalyFinding {
+        anomaly_type: AnomalyType::EventBurst,
+        score: (0.4 + (ratio - 1.0) * 0.3).clamp(0.4, 1.0),
+        details: json!({
+            "events_in_window": ctx.events_in_burst_window,
+            "window_seconds": BURST_WINDOW_SECONDS,
+            "threshold": BURST_THRESHOLD,
+        }),
+    })
+}
+
+// ── Internal helpers ──────────────────────────────────────────────────────────
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+// ── Tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(id: i64, location: &str, minutes: i64) -> TrackingEvent {
+        let base = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        TrackingEvent {
+            id,
+            product_id: "p1".into(),
+            actor_address: "GCARRIER".into(),
+            timestamp: base + Duration::minutes(minutes),
+            event_type: "SHIP".into(),
+            location: location.into(),
+            data_hash: "abc".into(),
+            note: String::new(),
+            metadata: json!({}),
+            created_at: base,
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-01-02T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn known_actor() -> AnomalyContext {
+        AnomalyContext {
+            actor_seen_on_product: true,
+            actor_total_events: 50,
+            events_in_burst_window: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_clean_event_has_no_findings() {
+        let ctx = AnomalyContext {
+            previous: Some(event(1, "51.92,4.48", 0)),
+            ..known_actor()
+        };
+        // Rotterdam -> Hamburg (~410 km) in 8 hours
+        let findings = score_event(&event(2, "53.55,9.99", 480), &ctx, now());
+        assert!(findings.is_empty(), "{:?}", findings);
+    }
+
+    #[test]
+    fn test_impossible_travel() {
+        let ctx = AnomalyContext {
+            previous: Some(event(1, "51.92,4.48", 0)),
+            ..known_actor()
+        };
+        // Rotterdam -> Shanghai in 30 minutes
+        let findings = score_event(&event(2, "31.23,121.47", 30), &ctx, now());
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].anomaly_type, AnomalyType::ImpossibleTravel);
+        assert!(findings[0].score > 0.9);
+        assert_eq!(findings[0].severity(), "high");
+    }
+
+    #[test]
+    fn test_impossible_travel_uses_resolved_locations() {
+        let ctx = AnomalyContext {
+            previous: Some(event(1, "Rotterdam", 0)),
+            previous_location: Coordinates::new(51.92, 4.48),
+            location: Coordinates::new(31.23, 121.47),
+            ..known_actor()
+        };
+        let findings = score_event(&event(2, "Shanghai", 30), &ctx, now());
+
+        assert_eq!(findings[0].anomaly_type, AnomalyType::ImpossibleTravel);
+    }
+
+    #[test]
+    fn test_coordinates_from_metadata() {
+        let mut e = event(1, "Port of Rotterdam", 0);
+        assert!(coordinates_of(&e).is_none());
+
+        e.metadata = json!({"latitude": 51.92, "longitude": 4.48});
+        assert_eq!(coordinates_of(&e), Coordinates::new(51.92, 4.48));
+
+        e.metadata = json!({"lat": 95.0, "lon": 4.48});
+        assert!(coordinates_of(&e).is_none());
+    }
+
+    #[test]
+    fn test_out_of_order_and_future_timestamps() {
+        let ctx = AnomalyContext {
+            previous: Some(event(1, "Rotterdam", 60)),
+            ..known_actor()
+        };
+        let earlier = score_event(&event(2, "Rotterdam", 30), &ctx, now());
+        assert_eq!(earlier[0].anomaly_type, AnomalyType::OutOfOrderTimestamp);
+
+        let future = score_event(&event(3, "Rotterdam", 60 * 24 * 3), &known_actor(), now());
+        assert_eq!(future[0].details["reason"], "future_timestamp");
+    }
+
+    #[test]
+    fn test_unusual_actor_skips_owner() {
+        let ctx = AnomalyContext {
+            events_in_burst_window: 1,
+            ..Default::default()
+        };
+        let findings = score_event(&event(1, "Rotterdam", 0), &ctx, now());
+        assert_eq!(findings[0].anomaly_type, AnomalyType::UnusualActor);
+
+        let owner_ctx = AnomalyContext {
+            product_owner: Some("GCARRIER".into()),
+            ..ctx
+        };
+        assert!(score_event(&event(1, "Rotterdam", 0), &owner_ctx, now()).is_empty());
+    }
+
+    #[test]
+    fn test_cross_product_duplicate_hash_scores_highest() {
+        let same = AnomalyContext {
+            duplicate_hashes: vec![(7, "p1".into())],
+            ..known_actor()
+        };
+        let cross = AnomalyContext {
+            duplicate_hashes: vec![(7, "p2".into())],
+            ..known_actor()
+        };
+
+        let same_score = score_event(&event(1, "Rotterdam", 0), &same, now())[0].score;
+        let cross_score = score_event(&event(1, "Rotterdam", 0), &cross, now())[0].score;
+        assert!(cross_score > same_score);
+    }
+
+    #[test]
+    fn test_burst_threshold() {
+        let at_threshold = AnomalyContext {
+            events_in_burst_window: BURST_THRESHOLD,
+            ..known_actor()
+        };
+        assert!(score_event(&event(1, "Rotterdam", 0), &at_threshold, now()).is_empty());
+
+        let over = AnomalyContext {
+            events_in_burst_window: BURST_THRESHOLD * 3,
+            ..known_actor()
+        };
+        let findings = score_event(&event(1, "Rotterdam", 0), &over, now());
+        assert_eq!(findings[0].anomaly_type, AnomalyType::EventBurst);
+    }
+}
@@ -0,0 +1,223 @@
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::models::analytics::{AnomalyQuery, EventAnomaly};
+use crate::models::TrackingEvent;
+use crate::services::anomaly_detector::{self, AnomalyContext, AnomalyFinding, ALERT_SCORE};
+use crate::services::webhook_service;
+use crate::utils::geo::Coordinates;
+use crate::websocket::channels;
+
+/// Websocket channel that receives every anomaly alert
+pub const ALERTS_CHANNEL: &str = channels::ALERTS;
+
+/// Scores new tracking events, persists findings and pushes alerts to websocket
+/// subscribers and webhooks
+pub struct AnomalyService {
+    pool: PgPool,
+}
+
+impl AnomalyService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Score a freshly recorded event and store any findings
+    pub async fn score_event(&self, event: &TrackingEvent) -> Result<Vec<EventAnomaly>, AppError> {
+        let ctx = self.load_context(event).await?;
+        let findings = anomaly_detector::score_event(event, &ctx, Utc::now());
+
+        let mut stored = Vec::with_capacity(findings.len());
+        for finding in findings {
+            let anomaly = self.insert_anomaly(event, &finding).await?;
+            if anomaly.score >= ALERT_SCORE {
+                self.publish_alert(&anomaly).await;
+            }
+            stored.push(anomaly);
+        }
+
+        Ok(stored)
+    }
+
+    /// List stored anomalies, newest first
+    /// Anomalies of `organization_id`'s products, or of every product when `None`
+    pub async fn list_anomalies(
+        &self,
+        query: &AnomalyQuery,
+        organization_id: Option<Uuid>,
+    ) -> Result<Vec<EventAnomaly>, AppError> {
+        let end = query.end_date.unwrap_or_else(Utc::now);
+        let start = query.start_date.unwrap_or_else(|| end - Duration::days(30));
+        let limit = query.limit.unwrap_or(100).clamp(1, 1_000);
+
+        let anomalies = sqlx::query_as::<_, EventAnomaly>(
+            r#"
+            SELECT * FROM event_anomalies
+            WHERE detected_at BETWEEN $1 AND $2
+              AND ($3::TEXT IS NULL OR product_id = $3)
+              AND ($4::TEXT IS NULL OR anomaly_type = $4)
+              AND score >= $5
+              AND ($7::uuid IS NULL OR product_id IN (SELECT id FROM products WHERE organization_id = $7))
+            ORDER BY detected_at DESC
+            LIMIT $6
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .bind(&query.product_id)
+        .bind(&query.anomaly_type)
+        .bind(query.min_score.unwrap_or(0.0))
+        .bind(limit)
+        .bind(organization_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(anomalies)
+    }
+
+    async fn load_context(&self, event: &TrackingEvent) -> Result<AnomalyContext, AppError> {
+        let previous = sqlx::query_as::<_, TrackingEvent>(
+            "SELECT * FROM tracking_events WHERE product_id = $1 AND id < $2 ORDER BY id DESC LIMIT 1",
+        )
+        .bind(&event.product_id)
+        .bind(event.id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let ids: Vec<i64> = std::iter::once(event.id)
+            .chain(previous.as_ref().map(|p| p.id))
+            .collect();
+        let located: Vec<(i64, f64, f64)> = sqlx::query_as(
+            "SELECT tracking_event_id, latitude, longitude FROM event_locations WHERE tracking_event_id = ANY($1)",
+        )
+        .bind(&ids)
+        .fetch_all(&self.pool)
+        .await?;
+        let point_of = |id: i64| {
+            located
+                .iter()
+                .find(|(event_id, _, _)| *event_id == id)
+                .and_then(|(_, lat, lon)| Coordinates::new(*lat, *lon))
+        };
+        let location = point_of(event.id);
+        let previous_location = previous.as_ref().and_then(|p| point_of(p.id));
+
+        let product_owner: Option<String> =
+            sqlx::query_scalar("SELECT owner_address FROM products WHERE id = $1")
+                .bind(&event.product_id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        let (actor_on_product, actor_total_events): (i64, i64) = sqlx::query_as(
+            r#"
+            SELECT
+                COUNT(*) FILTER (WHERE product_id = $2),
+                COUNT(*)
+            FROM tracking_events
+            WHERE actor_address = $1 AND id <> $3
+            "#,
+        )
+        .bind(&event.actor_address)
+        .bind(&event.product_id)
+        .bind(event.id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let duplicate_hashes: Vec<(i64, String)> = if event.data_hash.is_empty() {
+            Vec::new()
+        } else {
+            sqlx::query_as(
+                "SELECT id, product_id FROM tracking_events WHERE data_hash = $1 AND id <> $2 LIMIT 20",
+            )
+            .bind(&event.data_hash)
+            .bind(event.id)
+            .fetch_all(&self.pool)
+            .await?
+        };
+
+        let window = Duration::seconds(anomaly_detector::BURST_WINDOW_SECONDS);
+        let events_in_burst_window: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM tracking_events WHERE product_id = $1 AND created_at > $2",
+        )
+        .bind(&event.product_id)
+        .bind(event.created_at - window)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(AnomalyContext {
+            previous,
+            location,
+            previous_location,
+            product_owner,
+            actor_seen_on_product: actor_on_product > 0,
+            actor_total_events,
+            duplicate_hashes,
+            events_in_burst_window,
+        })
+    }
+
+    async fn insert_anomaly(
+        &self,
+        event: &TrackingEvent,
+        finding: &AnomalyFinding,
+    ) -> Result<EventAnomaly, AppError> {
+        let anomaly = sqlx::query_as::<_, EventAnomaly>(
+            r#"
+            INSERT INTO event_anomalies (
+                id, tracking_event_id, product_id, anomaly_type, score, severity, details
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(event.id)
+        .bind(&event.product_id)
+        .bind(finding.anomaly_type.as_str())
+        .bind(finding.score)
+        .bind(finding.severity())
+        .bind(&finding.details)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(anomaly)
+    }
+
+    async fn publish_alert(&self, anomaly: &EventAnomaly) {
+        let data = match serde_json::to_value(anomaly) {
+            Ok(data) => data,
+            Err(_) => return,
+        };
+        let organization_id = sqlx::query_scalar::<_, Option<Uuid>>(
+            "SELECT organization_id FROM products WHERE id = $1",
+        )
+        .bind(&anomaly.product_id)
+        .fetch_optional(&self.pool)
+        .await
+        .ok()
+        .flatten()
+        .flatten();
+
+        let mut targets = channels::for_product(&anomaly.product_id, organization_id);
+        targets.push(ALERTS_CHANNEL.to_string());
+        channels::publish(&targets, data.clone()).await;
+        webhook_service::enqueue(
+            &self.pool,
+            "anomaly.detected",
+            &anomaly.product_id,
+            organization_id,
+            data,
+        )
+        .await;
+
+        tracing::warn!(
+            product_id = %anomaly.product_id,
+            event_id = anomaly.tracking_event_id,
+            anomaly_type = %anomaly.anomaly_type,
+            score = anomaly.score,
+            "Tracking event anomaly detected"
+        );
+    }
+}
@@ -0,0 +1,416 @@
+/// What an API key may do.
+///
+/// Every key carries scopes naming the resources it may read or change
+/// (`events:write`, `carbon:trade`, ...). A key can further be limited to
+/// certain products or product categories, and to the networks it may be used
+/// from. A warehouse scanner, say, gets `events:write` and nothing else.
+use std::net::IpAddr;
+
+/// Every scope a key can hold
+pub const SCOPES: &[&str] = &[
+    "products:read",
+    "products:write",
+    "events:read",
+    "events:write",
+    "locations:read",
+    "epcis:read",
+    "stats:read",
+    "financial:read",
+    "compliance:read",
+    "compliance:check",
+    "carbon:read",
+    "carbon:write",
+    "carbon:trade",
+];
+
+/// Scopes of keys created without any, and of keys created before scopes
+/// existed: the read-only public API, as API keys have always had
+pub const DEFAULT_SCOPES: &[&str] = &[
+    "products:read",
+    "events:read",
+    "locations:read",
+    "epcis:read",
+    "stats:read",
+    "financial:read",
+    "compliance:read",
+    "compliance:check",
+];
+
+/// Routes a key limited to certain products may call. Each of them either
+/// names the product and checks it, or filters what it returns; anything
+/// spanning products is off limits.
+const PRODUCT_SCOPED_ROUTES: &[&str] = &[
+    "/api/v1/products",
+    "/api/v1/products/:id",
+    "/api/v1/products/:id/origin-plots",
+    "/api/v1/products/:id/lineage",
+    "/api/v1/products/:id/access",
+    "/api/v1/events",
+    "/api/v1/events/:id",
+    "/api/v1/epcis/products/:product_id",
+    "/api/v1/admin/products",
+    "/api/v1/admin/products/:id",
+    "/api/v1/admin/products/:id/access",
+    "/api/v1/admin/products/:id/access/:actor_address",
+    "/api/v1/admin/products/:id/origin-plots",
+    "/api/v1/admin/products/:id/origin-plots/:plot_id",
+    "/api/v1/admin/events",
+    "/api/v1/carbon/footprint/calculate",
+    "/api/v1/carbon/footprint/:product_id",
+    // Channels are authorized one by one
+    "/api/v1/ws",
+];
+
+/// The limits of the API key a request was made with
+#[derive(Debug, Clone, Default)]
+pub struct KeyRestrictions {
+    pub scopes: Vec<String>,
+    /// Only these products, when set
+    pub product_ids: Option<Vec<String>>,
+    /// Only products in these categories, when set
+    pub categories: Option<Vec<String>>,
+}
+
+impl KeyRestrictions {
+    pub fn allows_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+
+    /// Whether the key is limited to certain products or categories
+    pub fn restricts_products(&self) -> bool {
+        self.product_ids.is_some() || self.categories.is_some()
+    }
+
+    pub fn allows_product(&self, product_id: &str, category: &str) -> bool {
+        self.product_ids
+            .as_ref()
+            .is_none_or(|ids| ids.iter().any(|id| id == product_id))
+            && self
+                .categories
+                .as_ref()
+                .is_none_or(|categories| categories.iter().any(|c| c == category))
+    }
+
+    /// Checks the key may call `method` on the matched `route`
+    pub fn check_route(&self, method: &str, route: &str) -> Result<(), String> {
+        let scope = required_scope(method, route)
+            .ok_or_else(|| "API keys cannot use this endpoint".to_string())?;
+        if !self.allows_scope(scope) {
+            return Err(format!("API key lacks the '{}' scope", scope));
+        }
+        if self.restricts_products() && !PRODUCT_SCOPED_ROUTES.contains(&route) {
+            return Err("API key is limited to specific products".to_string());
+        }
+        Ok(())
+    }
+}
+
+// ── Scopes ────────────────────────────────────────────────────────────────────
+
+/// The scope a request needs, from its method and matched route. `None` for
+/// routes no scope covers.
+pub fn required_scope(method: &str, route: &str) -> Option<&'static str> {
+    let path = route.strip_prefix("/api/v1")?;
+    let path = path.strip_prefix("/admin").unwrap_or(path);
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let read = matches!(method, "GET" | "HEAD" | "OPTIONS");
+
+    let scope = match (*segments.first()?, read) {
+        ("products", true) => "products:read",
+        ("products", false) => "products:write",
+        ("events", true) => "events:read",
+        ("events", false) => "events:write",
+        // Live updates are the event feed
+        ("ws", true) => "events:read",
+        ("locations", true) => "locations:read",
+        ("epcis", true) => "epcis:read",
+        ("stats", true) => "stats:read",
+        ("transactions", true) => "financial:read",
+        ("compliance" | "audit", true) => "compliance:read",
+        // Running checks records results but changes no product
+        ("compliance", false) if matches!(segments.last().copied(), Some("check" | "evaluate")) => {
+            "compliance:check"
+        }
+        ("carbon", true) => "carbon:read",
+        ("carbon", false) => match segments[1..] {
+            ["market", "list" | "purchase"] | ["credits", "retire"] => "carbon:trade",
+            _ => "carbon:write",
+        },
+        _ => return None,
+    };
+    Some(scope)
+}
+
+/// Trims, lowercases and de-duplicates requested scopes, rejecting unknown ones
+pub fn normalize_scopes(scopes: &[String]) -> Result<Vec<String>, String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for scope in scopes {
+        let scope = scope.trim().to_ascii_lowercase();
+        if !SCOPES.contains(&scope.as_str()) {
+            return Err(format!(
+                "Unknown scope '{}'. Available: {}",
+                scope,
+                SCOPES.join(", ")
+            ));
+        }
+        if !normalized.contains(&scope) {
+            normalized.push(scope);
+        }
+    }
+    if normalized.is_empty() {
+        return Err("A key needs at least one scope".to_string());
+    }
+    Ok(normalized)
+}
+
+// ── Networks ──────────────────────────────────────────────────────────────────
+
+/// An IP address or CIDR block, e.g. `203.0.113.7` or `10.20.0.0/16`
+fn parse_network(entry: &str) -> Result<(IpAddr, u8), String> {
+    let invalid = || format!("'{}' is not an IP address or CIDR block", entry);
+    let (address, prefix) = match entry.trim().split_once('/') {
+        Some((address, prefix)) => (address, Some(prefix)),
+        None => (entry.trim(), None),
+    };
+    let address: IpAddr = address.parse().map_err(|_| invalid())?;
+    let max = if address.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(prefix) => prefix
+            .parse::<u8>()
+            .ok()
+            .filter(|p| *p <= max)
+            .ok_or_else(invalid)?,
+        None => max,
+    };
+    Ok((address, prefix))
+}
+
+/// Validates an IP allowlist and brings it to canonical `address/prefix` form
+pub fn normalize_allowlist(entries: &[String]) -> Result<Vec<String>, String> {
+    if entries.is_empty() {
+        return Err("An IP allowlist needs at least one entry".to_string());
+    }
+    entries
+        .iter()
+        .map(|entry| {
+            parse_network(entry).map(|(address, prefix)| format!("{}/{}", address, prefix))
+        })
+        .collect()
+}
+
+/// Whether `ip` is in one of the allowlisted networks. Malformed entries
+/// match nothing.
+pub fn ip_allowed(allowlist: &[String], ip: IpAddr) -> bool {
+    allowlist.iter().any(|entry| match parse_network(entry) {
+        Ok((network, prefix)) => in_network(ip, network, prefix),
+        Err(_) => false,
+    })
+}
+
+fn in_network(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V4(_)) => ip
+            .to_ipv4_mapped()
+            .is_some_and(|ip| in_network(IpAddr::V4(ip), network, prefix)),
+        (IpAddr::V4(_), IpAddr::V6(_)) => false,
+    }
+}
+
+/// The address a request came from. Forwarding headers are only believed
+/// when the connection itself comes from a private network, i.e. from our own
+/// load balancer, which appends the client to `X-Forwarded-For`.
+pub fn request_ip(
+    peer: Option<IpAddr>,
+    forwarded_for: Option<&str>,
+    real_ip: Option<&str>,
+) -> Option<IpAddr> {
+    let behind_proxy = match peer {
+        Some(IpAddr::V4(ip)) => ip.is_loopback() || ip.is_private(),
+        Some(IpAddr::V6(ip)) => ip.is_loopback() || (ip.segments()[0] & 0xfe00) == 0xfc00,
+        None => false,
+    };
+    if !behind_proxy {
+        return peer;
+    }
+    forwarded_for
+        .and_then(|value| value.rsplit(',').next())
+        .or(real_ip)
+        .and_then(|value| value.trim().parse().ok())
+        .or(peer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn restrictions(scopes: &[&str]) -> KeyRestrictions {
+        KeyRestrictions {
+            scopes: scopes.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn maps_routes_to_scopes() {
+        assert_eq!(
+            required_scope("GET", "/api/v1/products/:id"),
+            Some("products:read")
+        );
+        assert_eq!(
+            required_scope("PUT", "/api/v1/admin/products/:id"),
+            Some("products:write")
+        );
+        assert_eq!(
+            required_scope("POST", "/api/v1/admin/events"),
+            Some("events:write")
+        );
+        assert_eq!(
+            required_scope("GET", "/api/v1/events/nearby"),
+            Some("events:read")
+        );
+        assert_eq!(
+            required_scope("GET", "/api/v1/audit/verify"),
+            Some("compliance:read")
+        );
+        assert_eq!(
+            required_scope("POST", "/api/v1/compliance/check"),
+            Some("compliance:check")
+        );
+        assert_eq!(
+            required_scope("POST", "/api/v1/compliance/report/:product_id/evaluate"),
+            Some("compliance:check")
+        );
+        assert_eq!(
+            required_scope("POST", "/api/v1/carbon/market/purchase"),
+            Some("carbon:trade")
+        );
+        assert_eq!(
+            required_scope("POST", "/api/v1/carbon/credits/retire"),
+            Some("carbon:trade")
+        );
+        assert_eq!(
+            required_scope("POST", "/api/v1/carbon/credits/generate"),
+            Some("carbon:write")
+        );
+        assert_eq!(
+            required_scope("GET", "/api/v1/carbon/market"),
+            Some("carbon:read")
+        );
+        // Nothing covers key management or writes to read-only resources
+        assert_eq!(required_scope("POST", "/api/v1/keys"), None);
+        assert_eq!(required_scope("POST", "/api/v1/admin/epcis/import"), None);
+        assert_eq!(required_scope("GET", "/health"), None);
+    }
+
+    #[test]
+    fn scanners_may_only_append_events() {
+        let scanner = restrictions(&["events:write"]);
+        assert!(scanner.check_route("POST", "/api/v1/admin/events").is_ok());
+        assert!(scanner.check_route("GET", "/api/v1/events").is_err());
+        assert!(scanner
+            .check_route("POST", "/api/v1/admin/products")
+            .is_err());
+        assert!(scanner
+            .check_route("DELETE", "/api/v1/admin/products/:id")
+            .is_err());
+        assert!(scanner
+            .check_route("POST", "/api/v1/carbon/market/purchase")
+            .is_err());
+    }
+
+    #[test]
+    fn product_limited_keys_stay_on_product_routes() {
+        let mut key = restrictions(&["events:read", "events:write", "stats:read"]);
+        key.product_ids = Some(vec!["PROD-1".to_string()]);
+        assert!(key.check_route("POST", "/api/v1/admin/events").is_ok());
+        assert!(key.check_route("GET", "/api/v1/events/:id").is_ok());
+        // Spans every product
+        assert!(key.check_route("GET", "/api/v1/events/nearby").is_err());
+        assert!(key.check_route("GET", "/api/v1/stats").is_err());
+
+        assert!(key.allows_product("PROD-1", "coffee"));
+        assert!(!key.allows_product("PROD-2", "coffee"));
+
+        key.categories = Some(vec!["coffee".to_string()]);
+        assert!(key.allows_product("PROD-1", "coffee"));
+        assert!(!key.allows_product("PROD-1", "cocoa"));
+
+        let unrestricted = restrictions(&["products:read"]);
+        assert!(!unrestricted.restricts_products());
+        assert!(unrestricted.allows_product("anything", "any"));
+    }
+
+    #[test]
+    fn normalizes_scopes() {
+        let scopes = normalize_scopes(&[
+            " Events:Write ".to_string(),
+            "events:write".to_string(),
+            "carbon:trade".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(scopes, vec!["events:write", "carbon:trade"]);
+        assert!(normalize_scopes(&["events:delete".to_string()]).is_err());
+        assert!(normalize_scopes(&[]).is_err());
+        assert!(DEFAULT_SCOPES.iter().all(|s| SCOPES.contains(s)));
+    }
+
+    #[test]
+    fn matches_ip_allowlists() {
+        let allowlist = normalize_allowlist(&[
+            "10.20.0.0/16".to_string(),
+            " 203.0.113.7 ".to_string(),
+            "2001:db8::/32".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(
+            allowlist,
+            vec!["10.20.0.0/16", "203.0.113.7/32", "2001:db8::/32"]
+        );
+
+        let allowed = |ip: &str| ip_allowed(&allowlist, ip.parse().unwrap());
+        assert!(allowed("10.20.255.1"));
+        assert!(!allowed("10.21.0.1"));
+        assert!(allowed("203.0.113.7"));
+        assert!(!allowed("203.0.113.8"));
+        assert!(allowed("2001:db8:1::5"));
+        assert!(allowed("::ffff:10.20.1.1"));
+        assert!(!allowed("2001:db9::1"));
+
+        assert!(ip_allowed(
+            &["0.0.0.0/0".to_string()],
+            "198.51.100.1".parse().unwrap()
+        ));
+        assert!(normalize_allowlist(&["10.0.0.0/33".to_string()]).is_err());
+        assert!(normalize_allowlist(&["warehouse".to_string()]).is_err());
+        assert!(normalize_allowlist(&[]).is_err());
+    }
+
+    #[test]
+    fn trusts_forwarding_headers_only_from_the_proxy() {
+        let proxy = Some("10.0.0.5".parse().unwrap());
+        let client: IpAddr = "198.51.100.9".parse().unwrap();
+
+        // The load balancer appends the real client after whatever it was sent
+        assert_eq!(
+            request_ip(proxy, Some("203.0.113.7, 198.51.100.9"), None),
+            Some(client)
+        );
+        assert_eq!(request_ip(proxy, None, Some("198.51.100.9")), Some(client));
+        assert_eq!(request_ip(proxy, None, None), proxy);
+
+        // A client talking to us directly cannot claim another address
+        assert_eq!(
+            request_ip(Some(client), Some("10.20.0.1"), None),
+            Some(client)
+        );
+        assert_eq!(request_ip(None, Some("10.20.0.1"), None), None);
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::blockchain::ChainAnchor;
+use crate::compliance::audit::{
+    self, AuditAnchor, AuditChainVerification, AuditRecord, ChainCursor, NewAuditEntry,
+};
+use crate::error::AppError;
+
+/// Advisory lock serializing appends so sequences stay gapless
+const AUDIT_CHAIN_LOCK_KEY: i64 = 0x4155_4449_545f_4c4f; // "AUDIT_LO"
+const VERIFY_PAGE_SIZE: i64 = 1_000;
+/// Issues returned by one verification run
+const MAX_REPORTED_ISSUES: usize = 100;
+
+const RECORD_COLUMNS: &str = "id, sequence, user_id, action, resource_type, resource_id, changes, \
+     ip_address, user_agent, created_at, prev_hash, entry_hash";
+
+/// Persists the tamper-evident audit log and anchors its head on-chain
+pub struct AuditService {
+    pool: PgPool,
+    anchor: Option<ChainAnchor>,
+}
+
+impl AuditService {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            anchor: ChainAnchor::from_env(),
+        }
+    }
+
+    /// Appends an entry linked to the current chain head
+    pub async fn record(&self, entry: NewAuditEntry) -> Result<AuditRecord, AppError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(AUDIT_CHAIN_LOCK_KEY)
+            .execute(&mut *tx)
+            .await?;
+
+        let head = sqlx::query_as::<_, (i64, String)>(
+            "SELECT sequence, entry_hash FROM audit_logs WHERE sequence IS NOT NULL ORDER BY sequence DESC LIMIT 1",
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let cursor = match head {
+            Some((sequence, hash)) => ChainCursor {
+                next_sequence: sequence + 1,
+                prev_hash: hash,
+            },
+            None => ChainCursor::default(),
+        };
+
+        // Postgres keeps microseconds; hash what will be read back
+        let now = Utc::now();
+        let created_at = DateTime::from_timestamp_micros(now.timestamp_micros()).unwrap_or(now);
+
+        let mut record = AuditRecord {
+            id: Uuid::new_v4(),
+            sequence: cursor.next_sequence,
+            user_id: entry.user_id,
+            action: entry.action,
+            resource_type: entry.resource_type,
+            resource_id: entry.resource_id,
+            changes: entry.changes,
+            ip_address: entry.ip_address,
+            user_agent: entry.user_agent,
+            created_at,
+            prev_hash: cursor.prev_hash,
+            entry_hash: String::new(),
+        };
+        record.entry_hash = record.compute_hash();
+
+        sqlx::query(
+            r#"
+            INSERT INTO audit_logs (
+                id, sequence, user_id, action, resource_type, resource_id, changes,
+                ip_address, user_agent, created_at, prev_hash, entry_hash
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            "#,
+        )
+        .bind(record.id)
+        .bind(record.sequence)
+        .bind(record.user_id)
+        .bind(&record.action)
+        .bind(&record.resource_type)
+        .bind(&record.resource_id)
+        .bind(&record.changes)
+        .bind(&record.ip_address)
+        .bind(&record.user_agent)
+        .bind(record.created_at)
+        .bind(&record.prev_hash)
+        .bind(&record.entry_hash)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(record)
+    }
+
+    /// Walks the whole chain, recomputing every hash and checking links,
+    /// sequence gaps and on-chain anchors.
+    pub async fn verify(&self) -> Result<AuditChainVerification, AppError> {
+        let anchors: HashMap<i64, String> =
+            sqlx::query_as::<_, (i64, String)>("SELECT sequence, entry_hash FROM audit_anchors")
+                .fetch_all(&self.pool)
+                .await?
+                .into_iter()
+                .collect();
+
+        let mut cursor = ChainCursor::default();
+        let mut issues = Vec::new();
+        let mut verified = 0i64;
+        let mut after = 0i64;
+        loop {
+            let page = sqlx::query_as::<_, AuditRecord>(&format!(
+                "SELECT {} FROM audit_logs WHERE sequence > $1 ORDER BY sequence ASC LIMIT $2",
+                RECORD_COLUMNS
+            ))
+            .bind(after)
+            .bind(VERIFY_PAGE_SIZE)
+            .fetch_all(&self.pool)
+            .await?;
+            let Some(last) = page.last() else { break };
+            after = last.sequence;
+            verified += page.len() as i64;
+
+            issues.extend(audit::verify_chain(&page, &mut cursor, &anchors));
+            if issues.len() >= MAX_REPORTED_ISSUES {
+                break;
+            }
+        }
+        issues.extend(audit::check_truncation(&cursor, &anchors));
+        issues.truncate(MAX_REPORTED_ISSUES);
+
+        let has_head = cursor.next_sequence > 1;
+        Ok(AuditChainVerification {
+            valid: issues.is_empty(),
+            verified_entries: verified,
+            head_sequence: has_head.then_some(cursor.next_sequence - 1),
+            head_hash: has_head.then_some(cursor.prev_hash),
+            latest_anchor: self.latest_anchor().await?,
+            issues,
+        })
+    }
+
+    /// Publishes the chain head on Stellar unless it is already anchored.
+    /// Returns `None` when there is nothing new to anchor.
+    pub async fn anchor_head(&self) -> Result<Option<AuditAnchor>, AppError> {
+        let Some(anchor) = &self.anchor else {
+            return Err(AppError::Configuration(
+                "STELLAR_ANCHOR_ACCOUNT is not configured".to_string(),
+            ));
+        };
+
+        let head = sqlx::query_as::<_, (i64, String)>(
+            "SELECT sequence, entry_hash FROM audit_logs WHERE sequence IS NOT NULL ORDER BY sequence DESC LIMIT 1",
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some((sequence, entry_hash)) = head else {
+            return Ok(None);
+        };
+        if let Some(latest) = self.latest_anchor().await? {
+            if latest.sequence >= sequence {
+                return Ok(None);
+            }
+        }
+
+        let transaction_hash = anchor
+            .anchor_digest(&entry_hash)
+            .await
+            .map_err(AppError::Blockchain)?;
+
+        let anchored = sqlx::query_as::<_, AuditAnchor>(
+            r#"
+            INSERT INTO audit_anchors (sequence, entry_hash, network, transaction_hash)
+            VALUES ($1, $2, 'stellar', $3)
+            RETURNING *
+            "#,
+        )
+        .bind(sequence)
+        .bind(&entry_hash)
+        .bind(&transaction_hash)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Some(anchored))
+    }
+
+    pub async fn latest_anchor(&self) -> Result<Option<AuditAnchor>, AppError> {
+        let anchor = sqlx::query_as::<_, AuditAnchor>(
+            "SELECT * FROM audit_anchors ORDER BY sequence DESC LIMIT 1",
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(anchor)
+    }
+}
@@ -0,0 +1,555 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+
+use crate::config::{BackupConfig, Config};
+use crate::database::Database;
+use crate::error::AppError;
+use crate::services::backup::{
+    self, Manifest, PartManifest, RowDigest, TableManifest, MANIFEST_FILE, PARTIAL_SUFFIX,
+    PART_ROWS,
+};
+
+/// Serializes scheduled backups across instances, so each is taken once
+const SCHEDULE_LOCK: &str = "scheduled_backup";
+const CHECK_INTERVAL: Duration = Duration::from_secs(300);
+/// A run still marked running after this long is taken to have died; its
+/// partial directory is removed and the next check starts over
+const STALE_RUN_HOURS: i32 = 6;
+/// A failed run is retried after this long rather than on the next check
+const RETRY_AFTER_HOURS: i32 = 1;
+/// Tables describing the database rather than holding its data
+const SKIPPED_TABLES: [&str; 2] = ["_sqlx_migrations", "backup_runs"];
+
+pub struct CreatedBackup {
+    pub manifest: Manifest,
+    pub path: PathBuf,
+    /// Size of the part files
+    pub bytes: u64,
+}
+
+/// Takes, verifies, restores and prunes the backups described in
+/// `services::backup`. Every instance runs the schedule; a row claimed in
+/// `backup_runs` under an advisory lock makes sure one of them takes each
+/// backup.
+pub struct BackupService {
+    db: Database,
+    config: BackupConfig,
+    key: String,
+}
+
+impl BackupService {
+    pub fn new(db: Database, config: &Config) -> Self {
+        Self {
+            db,
+            config: config.backup.clone(),
+            key: config.backup_key().to_string(),
+        }
+    }
+
+    fn pool(&self) -> &PgPool {
+        self.db.pool()
+    }
+
+    // ── Schedule ──────────────────────────────────────────────────────────────
+
+    pub fn start(self: Arc<Self>) {
+        if self.config.interval_hours == 0 {
+            tracing::info!("Scheduled backups are disabled");
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.run_if_due().await {
+                    tracing::error!("Scheduled backup failed: {:?}", e);
+                }
+            }
+        });
+    }
+
+    /// Takes a backup when none has completed within the interval, then
+    /// prunes old ones
+    async fn run_if_due(&self) -> Result<(), AppError> {
+        let now = Utc::now();
+        let backup_id = backup::backup_id(now);
+
+        let mut tx = self.pool().begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+            .bind(SCHEDULE_LOCK)
+            .execute(&mut *tx)
+            .await?;
+        let claimed = sqlx::query(
+            r#"
+            INSERT INTO backup_runs (backup_id, status, started_at)
+            SELECT $1, 'running', $2
+            WHERE NOT EXISTS (
+                SELECT 1 FROM backup_runs
+                WHERE (status = 'completed' AND started_at > $2 - make_interval(hours => $3))
+                   OR (status = 'running' AND started_at > $2 - make_interval(hours => $4))
+                   OR (status = 'failed' AND started_at > $2 - make_interval(hours => $5))
+            )
+            "#,
+        )
+        .bind(&backup_id)
+        .bind(now)
+        .bind(self.config.interval_hours as i32)
+        .bind(STALE_RUN_HOURS)
+        .bind(RETRY_AFTER_HOURS)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+        tx.commit().await?;
+        if !claimed {
+            return Ok(());
+        }
+
+        let result = self.create(now).await;
+        let (status, rows, bytes, error) = match &result {
+            Ok(created) => (
+                "completed",
+                Some(created.manifest.rows() as i64),
+                Some(created.bytes as i64),
+                None,
+            ),
+            Err(e) => ("failed", None, None, Some(format!("{:?}", e))),
+        };
+        sqlx::query(
+            r#"
+            UPDATE backup_runs
+            SET status = $2, finished_at = NOW(), rows = $3, bytes = $4, error = $5
+            WHERE backup_id = $1
+            "#,
+        )
+        .bind(&backup_id)
+        .bind(status)
+        .bind(rows)
+        .bind(bytes)
+        .bind(error)
+        .execute(self.pool())
+        .await?;
+
+        let created = result?;
+        tracing::info!(
+            backup = %created.manifest.id,
+            rows = created.manifest.rows(),
+            bytes = created.bytes,
+            "Backup completed"
+        );
+        self.prune().await?;
+        Ok(())
+    }
+
+    // ── Backup ────────────────────────────────────────────────────────────────
+
+    /// Writes every table, read from one snapshot, to a new backup directory.
+    /// The directory carries `PARTIAL_SUFFIX` until its manifest is written.
+    pub async fn create(&self, now: DateTime<Utc>) -> Result<CreatedBackup, AppError> {
+        let mut tx = self.pool().begin().await?;
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+            .execute(&mut *tx)
+            .await?;
+        let (schema_version,) = sqlx::query_as::<_, (Option<i64>,)>(
+            "SELECT MAX(version) FROM _sqlx_migrations WHERE success",
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        let schema_version = schema_version.ok_or_else(|| {
+            AppError::Internal("The database has no migrations applied".to_string())
+        })?;
+        let mut manifest = Manifest::new(now, schema_version);
+
+        let root = PathBuf::from(&self.config.dir);
+        let path = root.join(&manifest.id);
+        let partial = root.join(format!("{}{}", manifest.id, PARTIAL_SUFFIX));
+        tokio::fs::create_dir_all(&root)
+            .await
+            .map_err(io_error(&root))?;
+        tokio::fs::create_dir(&partial)
+            .await
+            .map_err(io_error(&partial))?;
+
+        let written = self.write_tables(&mut tx, &mut manifest, &partial).await;
+        tx.commit().await?;
+        let bytes = match written {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                let _ = tokio::fs::remove_dir_all(&partial).await;
+                return Err(e);
+            }
+        };
+        tokio::fs::rename(&partial, &path)
+            .await
+            .map_err(io_error(&path))?;
+
+        Ok(CreatedBackup {
+            manifest,
+            path,
+            bytes,
+        })
+    }
+
+    /// Writes the part files of every table, then the manifest; returns the
+    /// size of the parts
+    async fn write_tables(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        manifest: &mut Manifest,
+        dir: &Path,
+    ) -> Result<u64, AppError> {
+        let mut bytes = 0;
+        for name in tables(tx).await? {
+            let mut table = TableManifest {
+                columns: columns(tx, &name).await?,
+                name,
+                rows: 0,
+                digest: String::new(),
+                parts: Vec::new(),
+            };
+            let mut digest = RowDigest::default();
+            let mut batch = Vec::with_capacity(PART_ROWS);
+
+            let select = format!(
+                "SELECT to_jsonb(t)::TEXT FROM {} t",
+                quote_ident(&table.name)
+            );
+            let mut rows = sqlx::query_scalar::<_, String>(&select).fetch(&mut **tx);
+            while let Some(row) = rows.try_next().await? {
+                digest.add(&row);
+                batch.push(row);
+                if batch.len() == PART_ROWS {
+                    bytes += self.write_part(dir, &mut table, &batch).await?;
+                    batch.clear();
+                }
+            }
+            if !batch.is_empty() {
+                bytes += self.write_part(dir, &mut table, &batch).await?;
+            }
+
+            table.digest = digest.to_hex();
+            manifest.tables.push(table);
+        }
+
+        let path = dir.join(MANIFEST_FILE);
+        tokio::fs::write(&path, manifest.seal(&self.key)?)
+            .await
+            .map_err(io_error(&path))?;
+        Ok(bytes)
+    }
+
+    async fn write_part(
+        &self,
+        dir: &Path,
+        table: &mut TableManifest,
+        rows: &[String],
+    ) -> Result<u64, AppError> {
+        let file = backup::part_file(&table.name, table.parts.len());
+        let encoded = backup::encode_part(rows, &self.key)?;
+        let path = dir.join(&file);
+        tokio::fs::write(&path, &encoded)
+            .await
+            .map_err(io_error(&path))?;
+
+        table.rows += rows.len() as u64;
+        table.parts.push(PartManifest {
+            file,
+            rows: rows.len() as u64,
+            sha256: backup::sha256_hex(&encoded),
+        });
+        Ok(encoded.len() as u64)
+    }
+
+    // ── Verify and restore ────────────────────────────────────────────────────
+
+    /// Checks a backup without a database: the manifest's signature, and the
+    /// checksum, decryption and rows of every part
+    pub async fn verify(&self, dir: &Path) -> Result<Manifest, AppError> {
+        let path = dir.join(MANIFEST_FILE);
+        let json = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(io_error(&path))?;
+        let manifest = Manifest::open(&json, &self.key)?;
+
+        for table in &manifest.tables {
+            let mut digest = RowDigest::default();
+            for part in &table.parts {
+                self.read_part(dir, part)
+                    .await?
+                    .iter()
+                    .for_each(|row| digest.add(row));
+            }
+            if digest.to_hex() != table.digest {
+                return Err(AppError::Validation(format!(
+                    "Rows of {} do not match the manifest",
+                    table.name
+                )));
+            }
+        }
+        Ok(manifest)
+    }
+
+    async fn read_part(&self, dir: &Path, part: &PartManifest) -> Result<Vec<String>, AppError> {
+        let path = dir.join(&part.file);
+        let bytes = tokio::fs::read(&path).await.map_err(io_error(&path))?;
+        backup::decode_part(&bytes, part, &self.key)
+    }
+
+    /// Restores a backup into this service's database, which must be empty.
+    /// The database is migrated to the backup's schema version, then every
+    /// table is loaded in one transaction that only commits once each
+    /// table's rows match the manifest and the foreign keys hold.
+    pub async fn restore(&self, dir: &Path) -> Result<Manifest, AppError> {
+        let manifest = self.verify(dir).await?;
+
+        let (existing,) = sqlx::query_as::<_, (i64,)>(
+            "SELECT COUNT(*) FROM information_schema.tables WHERE table_schema = 'public'",
+        )
+        .fetch_one(self.pool())
+        .await?;
+        if existing > 0 {
+            return Err(AppError::BusinessRule(
+                "Backups are restored into an empty database; this one has tables".to_string(),
+            ));
+        }
+        self.db
+            .migrate_to(manifest.schema_version)
+            .await
+            .map_err(|e| {
+                AppError::Internal(format!(
+                    "Could not migrate to schema version {}: {}",
+                    manifest.schema_version, e
+                ))
+            })?;
+
+        let mut tx = self.pool().begin().await?;
+        let target = tables(&mut tx).await?;
+        let backed_up: BTreeSet<&str> = manifest.tables.iter().map(|t| t.name.as_str()).collect();
+        if target.iter().map(String::as_str).collect::<BTreeSet<_>>() != backed_up {
+            return Err(AppError::Validation(format!(
+                "The tables in the backup differ from those of schema version {}",
+                manifest.schema_version
+            )));
+        }
+
+        // Migrations seed some tables; the backup has those rows as well
+        let truncate = target
+            .iter()
+            .map(|t| quote_ident(t))
+            .collect::<Vec<_>>()
+            .join(", ");
+        sqlx::query(&format!("TRUNCATE {}", truncate))
+            .execute(&mut *tx)
+            .await?;
+
+        // Rows arrive table by table rather than in dependency order, so
+        // foreign keys are dropped while loading. Adding them back checks
+        // every reference.
+        let foreign_keys = sqlx::query_as::<_, (String, String, String)>(
+            r#"
+            SELECT c.conrelid::regclass::TEXT, c.conname::TEXT, pg_get_constraintdef(c.oid)
+            FROM pg_constraint c
+            JOIN pg_namespace n ON n.oid = c.connamespace
+            WHERE c.contype = 'f' AND n.nspname = 'public'
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        for (table, name, _) in &foreign_keys {
+            sqlx::query(&format!(
+                "ALTER TABLE {} DROP CONSTRAINT {}",
+                table,
+                quote_ident(name)
+            ))
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for table in &manifest.tables {
+            self.load_table(&mut tx, dir, table).await?;
+        }
+
+        for (table, name, definition) in &foreign_keys {
+            sqlx::query(&format!(
+                "ALTER TABLE {} ADD CONSTRAINT {} {}",
+                table,
+                quote_ident(name),
+                definition
+            ))
+            .execute(&mut *tx)
+            .await?;
+        }
+        reset_sequences(&mut tx).await?;
+        tx.commit().await?;
+
+        Ok(manifest)
+    }
+
+    /// Inserts a table's rows, then reads them back and compares them with
+    /// the manifest
+    async fn load_table(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        dir: &Path,
+        table: &TableManifest,
+    ) -> Result<(), AppError> {
+        let target_columns = columns(tx, &table.name).await?;
+        if table.columns.iter().any(|c| !target_columns.contains(c)) {
+            return Err(AppError::Validation(format!(
+                "Columns of {} in the backup differ from the schema",
+                table.name
+            )));
+        }
+
+        let name = quote_ident(&table.name);
+        let columns = table
+            .columns
+            .iter()
+            .map(|c| quote_ident(c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let insert = format!(
+            "INSERT INTO {name} ({columns}) OVERRIDING SYSTEM VALUE \
+             SELECT {columns} FROM json_populate_recordset(NULL::{name}, $1::JSON)"
+        );
+        for part in &table.parts {
+            let rows = self.read_part(dir, part).await?;
+            sqlx::query(&insert)
+                .bind(format!("[{}]", rows.join(",")))
+                .execute(&mut **tx)
+                .await?;
+        }
+
+        let mut digest = RowDigest::default();
+        let mut count = 0u64;
+        let select = format!("SELECT to_jsonb(t)::TEXT FROM {} t", name);
+        let mut rows = sqlx::query_scalar::<_, String>(&select).fetch(&mut **tx);
+        while let Some(row) = rows.try_next().await? {
+            digest.add(&row);
+            count += 1;
+        }
+        if count != table.rows || digest.to_hex() != table.digest {
+            return Err(AppError::Validation(format!(
+                "{} holds {} rows after loading that do not match the {} backed up",
+                table.name, count, table.rows
+            )));
+        }
+        tracing::info!(table = %table.name, rows = count, "Restored table");
+        Ok(())
+    }
+
+    // ── Retention ─────────────────────────────────────────────────────────────
+
+    /// Deletes backups past retention, keeping the newest
+    /// `retention_min_count`, and the partial directories of runs that died
+    pub async fn prune(&self) -> Result<Vec<String>, AppError> {
+        let root = Path::new(&self.config.dir);
+        let now = Utc::now();
+        let mut entries = tokio::fs::read_dir(root).await.map_err(io_error(root))?;
+        let mut backups = Vec::new();
+        let mut abandoned = Vec::new();
+        while let Some(entry) = entries.next_entry().await.map_err(io_error(root))? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if let Some(taken_at) = backup::parse_backup_id(&name) {
+                backups.push((name, taken_at));
+            } else if name
+                .strip_suffix(PARTIAL_SUFFIX)
+                .and_then(backup::parse_backup_id)
+                .is_some_and(|taken_at| {
+                    taken_at < now - chrono::Duration::hours(STALE_RUN_HOURS as i64)
+                })
+            {
+                abandoned.push(name);
+            }
+        }
+
+        let mut removed = backup::expired(
+            &backups,
+            now,
+            chrono::Duration::days(self.config.retention_days),
+            self.config.retention_min_count,
+        );
+        removed.extend(abandoned);
+        for name in &removed {
+            let path = root.join(name);
+            tokio::fs::remove_dir_all(&path)
+                .await
+                .map_err(io_error(&path))?;
+        }
+        if !removed.is_empty() {
+            tracing::info!("Removed {} old backups", removed.len());
+        }
+        Ok(removed)
+    }
+}
+
+/// Tables backed up, by name
+async fn tables(conn: &mut PgConnection) -> Result<Vec<String>, AppError> {
+    let tables = sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT table_name::TEXT FROM information_schema.tables
+        WHERE table_schema = 'public' AND table_type = 'BASE TABLE'
+          AND table_name::TEXT <> ALL($1)
+        ORDER BY table_name
+        "#,
+    )
+    .bind(SKIPPED_TABLES.as_slice())
+    .fetch_all(conn)
+    .await?;
+    Ok(tables)
+}
+
+/// Columns of `table` a restore inserts into, leaving out generated ones
+async fn columns(conn: &mut PgConnection, table: &str) -> Result<Vec<String>, AppError> {
+    let columns = sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT column_name::TEXT FROM information_schema.columns
+        WHERE table_schema = 'public' AND table_name = $1 AND is_generated = 'NEVER'
+        ORDER BY ordinal_position
+        "#,
+    )
+    .bind(table)
+    .fetch_all(conn)
+    .await?;
+    Ok(columns)
+}
+
+/// Moves each serial and identity sequence past the largest restored value
+async fn reset_sequences(tx: &mut Transaction<'_, Postgres>) -> Result<(), AppError> {
+    let sequences = sqlx::query_as::<_, (String, String, String)>(
+        r#"
+        SELECT table_name::TEXT, column_name::TEXT,
+               pg_get_serial_sequence(quote_ident(table_name), column_name)
+        FROM information_schema.columns
+        WHERE table_schema = 'public'
+          AND pg_get_serial_sequence(quote_ident(table_name), column_name) IS NOT NULL
+        "#,
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    for (table, column, sequence) in sequences {
+        sqlx::query(&format!(
+            "SELECT setval($1, COALESCE(MAX({}), 0) + 1, false) FROM {}",
+            quote_ident(&column),
+            quote_ident(&table)
+        ))
+        .bind(sequence)
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> AppError + '_ {
+    move |e| AppError::Internal(format!("{}: {}", path.display(), e))
+}
@@ -10,24 +10,36 @@ use crate::models::carbon::{
     PurchaseCreditRequest, RequestVerificationRequest, RetireCreditRequest,
 };
 use crate::services::carbon_calculator;
+use crate::services::LocationService;
 
 pub struct CarbonService {
     pool: PgPool,
+    location_service: LocationService,
 }
 
 impl CarbonService {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        let location_service = LocationService::new(pool.clone());
+        Self { pool, location_service }
     }
 
     // ── Footprint ─────────────────────────────────────────────────────────────
 
     /// Calculate and persist a carbon footprint record for a product/event.
+    /// When `distance_km` is omitted it is derived from the product's geocoded events:
+    /// the leg ending at `tracking_event_id`, or the whole route so far.
     pub async fn calculate_footprint(
         &self,
         req: &CalculateFootprintRequest,
     ) -> Result<CarbonFootprint, AppError> {
-        let breakdown = carbon_calculator::calculate(req);
+        let mut req = req.clone();
+        if req.distance_km.is_none() {
+            req.distance_km = self
+                .location_service
+                .product_distance_km(&req.product_id, req.tracking_event_id)
+                .await?;
+        }
+        let breakdown = carbon_calculator::calculate(&req);
 
         let record = sqlx::query_as!(
             CarbonFootprint,
@@ -59,7 +71,7 @@ impl CarbonService {
             req.distance_km,
             req.transport_mode,
             req.energy_source,
-            serde_json::to_value(req).unwrap_or_default(),
+            serde_json::to_value(&req).unwrap_or_default(),
         )
         .fetch_one(&self.pool)
         .await?;
@@ -67,6 +79,13 @@ impl CarbonService {
         Ok(record)
     }
 
+    pub async fn get_footprint(&self, id: Uuid) -> Result<CarbonFootprint, AppError> {
+        sqlx::query_as!(CarbonFootprint, "SELECT * FROM carbon_footprints WHERE id = $1", id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Footprint record not found".into()))
+    }
+
     /// Get all footprint records for a product.
     pub async fn list_footprints(
         &self,
@@ -89,21 +108,16 @@ impl CarbonService {
 
     // ── Credits ───────────────────────────────────────────────────────────────
 
-    /// Generate a carbon credit from a verified footprint reduction.
+    /// Generate a carbon credit from a verified footprint reduction. The credit
+    /// is held by `owner_id` on behalf of `organization_id`.
     pub async fn generate_credit(
         &self,
         owner_id: Uuid,
+        organization_id: Option<Uuid>,
         req: &GenerateCreditRequest,
     ) -> Result<CarbonCredit, AppError> {
         // Fetch the footprint to validate eligible credits
-        let footprint = sqlx::query_as!(
-            CarbonFootprint,
-            "SELECT * FROM carbon_footprints WHERE id = $1",
-            req.footprint_id
-        )
-        .fetch_optional(&self.pool)
-        .await?
-        .ok_or_else(|| AppError::NotFound("Footprint record not found".into()))?;
+        let footprint = self.get_footprint(req.footprint_id).await?;
 
         let breakdown = carbon_calculator::calculate(&CalculateFootprintRequest {
             product_id: footprint.product_id.clone(),
@@ -133,11 +147,11 @@ impl CarbonService {
             INSERT INTO carbon_credits (
                 owner_id, product_id, serial_number, vintage_year,
                 credit_type, standard, quantity, price_per_tonne,
-                status, registry_id, verification_body
+                status, registry_id, verification_body, organization_id
             ) VALUES (
                 $1, $2, $3, $4,
                 $5, $6, $7, $8,
-                'pending', $9, $10
+                'pending', $9, $10, $11
             )
             RETURNING *
             "#,
@@ -151,6 +165,7 @@ impl CarbonService {
             req.price_per_tonne,
             req.registry_id,
             req.verification_body,
+            organization_id,
         )
         .fetch_one(&self.pool)
         .await?;
@@ -165,9 +180,11 @@ impl CarbonService {
             .ok_or_else(|| AppError::NotFound(format!("Credit {} not found", id)))
     }
 
+    /// Credits held by the user or by the organization they act for
     pub async fn list_credits(
         &self,
         owner_id: Uuid,
+        organization_id: Option<Uuid>,
         query: &ListCreditsQuery,
     ) -> Result<Vec<CarbonCredit>, AppError> {
         let offset = query.offset.unwrap_or(0);
@@ -177,7 +194,7 @@ impl CarbonService {
             CarbonCredit,
             r#"
             SELECT * FROM carbon_credits
-            WHERE owner_id = $1
+            WHERE (owner_id = $1 OR organization_id = $7)
               AND ($2::TEXT IS NULL OR status = $2)
               AND ($3::INT IS NULL OR vintage_year = $3)
               AND ($4::TEXT IS NULL OR standard = $4)
@@ -190,6 +207,7 @@ impl CarbonService {
             query.standard,
             limit,
             offset,
+            organization_id,
         )
         .fetch_all(&self.pool)
         .await?;
@@ -201,11 +219,12 @@ impl CarbonService {
     pub async fn retire_credit(
         &self,
         owner_id: Uuid,
+        organization_id: Option<Uuid>,
         req: &RetireCreditRequest,
     ) -> Result<CarbonCredit, AppError> {
         let credit = self.get_credit(req.credit_id).await?;
 
-        if credit.owner_id != owner_id {
+        if !holds_credit(&credit, owner_id, organization_id) {
             return Err(AppError::Forbidden("You do not own this credit".into()));
         }
         if credit.status == "retired" {
@@ -235,11 +254,12 @@ impl CarbonService {
     pub async fn create_trade(
         &self,
         seller_id: Uuid,
+        organization_id: Option<Uuid>,
         req: &CreateTradeRequest,
     ) -> Result<CarbonTrade, AppError> {
         let credit = self.get_credit(req.credit_id).await?;
 
-        if credit.owner_id != seller_id {
+        if !holds_credit(&credit, seller_id, organization_id) {
             return Err(AppError::Forbidden("You do not own this credit".into()));
         }
         if !["verified", "pending"].contains(&credit.status.as_str()) {
@@ -295,6 +315,7 @@ impl CarbonService {
     pub async fn purchase_credit(
         &self,
         buyer_id: Uuid,
+        buyer_organization_id: Option<Uuid>,
         req: &PurchaseCreditRequest,
     ) -> Result<CarbonTrade, AppError> {
         let trade = sqlx::query_as!(
@@ -342,9 +363,14 @@ impl CarbonService {
 
         // Transfer credit ownership
         sqlx::query!(
-            "UPDATE carbon_credits SET owner_id = $1, status = 'sold', updated_at = NOW() WHERE id = $2",
+            r#"
+            UPDATE carbon_credits
+            SET owner_id = $1, organization_id = $3, status = 'sold', updated_at = NOW()
+            WHERE id = $2
+            "#,
             buyer_id,
             trade.credit_id,
+            buyer_organization_id,
         )
         .execute(&self.pool)
         .await?;
@@ -429,10 +455,11 @@ impl CarbonService {
     pub async fn request_verification(
         &self,
         requester_id: Uuid,
+        organization_id: Option<Uuid>,
         req: &RequestVerificationRequest,
     ) -> Result<CarbonVerification, AppError> {
         let credit = self.get_credit(req.credit_id).await?;
-        if credit.owner_id != requester_id {
+        if !holds_credit(&credit, requester_id, organization_id) {
             return Err(AppError::Forbidden("You do not own this credit".into()));
         }
 
@@ -607,3 +634,10 @@ fn generate_serial_number(vintage_year: i32) -> String {
         &Uuid::new_v4().to_string()[..8].to_uppercase()
     )
 }
+
+/// A credit is held by its owner and by the organization it was issued to
+/// or bought for
+pub fn holds_credit(credit: &CarbonCredit, user_id: Uuid, organization_id: Option<Uuid>) -> bool {
+    credit.owner_id == user_id
+        || organization_id.is_some_and(|id| credit.organization_id == Some(id))
+}
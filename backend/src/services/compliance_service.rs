@@ -0,0 +1,506 @@
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::compliance::{ComplianceRule, ComplianceValidator, RuleFormat, ValidationResult};
+use crate::error::AppError;
+use crate::models::compliance::{
+    ComplianceRecord, ComplianceRuleQuery, ComplianceStatusChange, StoredComplianceRule,
+};
+use crate::models::{Product, TrackingEvent};
+use crate::services::anomaly_service::ALERTS_CHANNEL;
+use crate::services::{webhook_service, EudrService};
+use crate::websocket::channels;
+
+/// Events loaded per product for `event_sequence` checks
+const MAX_SEQUENCE_EVENTS: i64 = 10_000;
+
+/// Stores versioned compliance rules, records every validation against the
+/// exact rule version that produced it, and keeps each product's status
+/// current as events arrive
+pub struct ComplianceService {
+    pool: PgPool,
+    eudr: EudrService,
+}
+
+impl ComplianceService {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            eudr: EudrService::new(pool.clone()),
+            pool,
+        }
+    }
+
+    // ── Rules ─────────────────────────────────────────────────────────────────
+
+    pub async fn list_rules(
+        &self,
+        query: &ComplianceRuleQuery,
+    ) -> Result<Vec<StoredComplianceRule>, AppError> {
+        let rules = sqlx::query_as::<_, StoredComplianceRule>(
+            r#"
+            SELECT * FROM compliance_rules
+            WHERE ($1::text IS NULL OR compliance_type = $1)
+              AND ($2 OR is_active)
+            ORDER BY rule_id, version DESC
+            "#,
+        )
+        .bind(&query.compliance_type)
+        .bind(query.include_inactive)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rules)
+    }
+
+    /// Every version of a rule, newest first
+    pub async fn rule_versions(
+        &self,
+        rule_id: &str,
+    ) -> Result<Vec<StoredComplianceRule>, AppError> {
+        let versions = sqlx::query_as::<_, StoredComplianceRule>(
+            "SELECT * FROM compliance_rules WHERE rule_id = $1 ORDER BY version DESC",
+        )
+        .bind(rule_id)
+        .fetch_all(&self.pool)
+        .await?;
+        if versions.is_empty() {
+            return Err(AppError::NotFound(format!(
+                "Compliance rule {} not found",
+                rule_id
+            )));
+        }
+        Ok(versions)
+    }
+
+    /// Saves a definition as the next version of its rule and makes it the
+    /// active one. Earlier versions stay in place for records that cite them.
+    pub async fn save_rule(
+        &self,
+        source: &str,
+        format: RuleFormat,
+        created_by: Option<Uuid>,
+    ) -> Result<StoredComplianceRule, AppError> {
+        let mut rule = ComplianceRule::parse(source, format)
+            .map_err(|e| AppError::Validation(format!("Invalid compliance rule: {}", e)))?;
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+            .bind(&rule.rule_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let latest = sqlx::query_scalar::<_, Option<i32>>(
+            "SELECT MAX(version) FROM compliance_rules WHERE rule_id = $1",
+        )
+        .bind(&rule.rule_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        rule.version = latest.unwrap_or(0) + 1;
+
+        sqlx::query(
+            "UPDATE compliance_rules SET is_active = FALSE WHERE rule_id = $1 AND is_active",
+        )
+        .bind(&rule.rule_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let definition = serde_json::to_value(&rule)
+            .map_err(|e| AppError::Internal(format!("Failed to serialize rule: {}", e)))?;
+        let stored = sqlx::query_as::<_, StoredComplianceRule>(
+            r#"
+            INSERT INTO compliance_rules
+                (rule_id, version, compliance_type, description, definition, source_format, source, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING *
+            "#,
+        )
+        .bind(&rule.rule_id)
+        .bind(rule.version)
+        .bind(&rule.compliance_type)
+        .bind(&rule.description)
+        .bind(definition)
+        .bind(format.as_str())
+        .bind(source)
+        .bind(created_by)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(stored)
+    }
+
+    /// Stops a rule from being evaluated; its history is kept
+    pub async fn deactivate_rule(&self, rule_id: &str) -> Result<(), AppError> {
+        let result = sqlx::query(
+            "UPDATE compliance_rules SET is_active = FALSE WHERE rule_id = $1 AND is_active",
+        )
+        .bind(rule_id)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!(
+                "No active compliance rule {}",
+                rule_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Active rules of a compliance type, optionally narrowed to one rule
+    pub async fn active_rules(
+        &self,
+        compliance_type: &str,
+        rule_id: Option<&str>,
+    ) -> Result<Vec<ComplianceRule>, AppError> {
+        let stored = sqlx::query_as::<_, StoredComplianceRule>(
+            r#"
+            SELECT * FROM compliance_rules
+            WHERE is_active AND compliance_type = $1
+              AND ($2::text IS NULL OR rule_id = $2)
+            ORDER BY rule_id
+            "#,
+        )
+        .bind(compliance_type)
+        .bind(rule_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        stored
+            .iter()
+            .map(|s| {
+                s.rule().map_err(|e| {
+                    AppError::Internal(format!(
+                        "Stored compliance rule {} v{} is invalid: {}",
+                        s.rule_id, s.version, e
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    // ── Validation ────────────────────────────────────────────────────────────
+
+    /// Validates against every active rule of `compliance_type` and records
+    /// one result per rule. With a `product_id`, the product's events are
+    /// available to sequence checks and `data` defaults to the product record.
+    pub async fn check(
+        &self,
+        compliance_type: &str,
+        rule_id: Option<&str>,
+        product_id: Option<&str>,
+        data: Option<Value>,
+        user_id: Option<Uuid>,
+    ) -> Result<Vec<ValidationResult>, AppError> {
+        let rules = self.active_rules(compliance_type, rule_id).await?;
+        if rules.is_empty() {
+            return Err(AppError::BadRequest(format!(
+                "No active compliance rules for type '{}'",
+                compliance_type
+            )));
+        }
+
+        let (data, events) = match (data, product_id) {
+            (data, Some(product_id)) => {
+                let (product, events) = self.load_product(product_id).await?;
+                let data = match data {
+                    Some(data) => data,
+                    None => self.product_data(&product, &events).await?,
+                };
+                (data, events)
+            }
+            (Some(data), None) => (data, Vec::new()),
+            (None, None) => {
+                return Err(AppError::Validation(
+                    "Either data or product_id is required".to_string(),
+                ))
+            }
+        };
+
+        let results: Vec<ValidationResult> = rules
+            .iter()
+            .map(|rule| ComplianceValidator::validate(rule, &data, &events))
+            .collect();
+
+        for result in &results {
+            self.record(result, product_id, &data, user_id).await?;
+        }
+        Ok(results)
+    }
+
+    /// Re-evaluates a product against every active rule whose `applies_to`
+    /// scope matches it. Each rule keeps one current record per product; a
+    /// new record is written (and pushed to websocket subscribers and
+    /// webhooks) only when the status or rule version changes.
+    pub async fn evaluate_product(
+        &self,
+        product_id: &str,
+    ) -> Result<Vec<ComplianceStatusChange>, AppError> {
+        let (product, events) = self.load_product(product_id).await?;
+        let rules = self.scoped_rules().await?;
+        let applicable: Vec<&ComplianceRule> = rules
+            .iter()
+            .filter(|rule| {
+                rule.applies_to.as_ref().is_some_and(|scope| {
+                    scope.matches(&product.category, &product.tags, &product.certifications)
+                })
+            })
+            .collect();
+        if applicable.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let data = self.product_data(&product, &events).await?;
+        let mut changes = Vec::new();
+        for rule in applicable {
+            let result = ComplianceValidator::validate(rule, &data, &events);
+            if let Some(change) = self.store_continuous(product_id, &result).await? {
+                changes.push(change);
+            }
+        }
+
+        for change in &changes {
+            publish_status_change(&self.pool, change, product.organization_id).await;
+        }
+        Ok(changes)
+    }
+
+    /// Latest continuous result of each rule for a product
+    pub async fn product_status(
+        &self,
+        product_id: &str,
+    ) -> Result<Vec<ComplianceRecord>, AppError> {
+        let exists =
+            sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM products WHERE id = $1)")
+                .bind(product_id)
+                .fetch_one(&self.pool)
+                .await?;
+        if !exists {
+            return Err(AppError::NotFound(format!(
+                "Product {} not found",
+                product_id
+            )));
+        }
+
+        let records = sqlx::query_as::<_, ComplianceRecord>(
+            r#"
+            SELECT DISTINCT ON (rule_id) *
+            FROM compliance_records
+            WHERE product_id = $1 AND source = 'continuous'
+            ORDER BY rule_id, created_at DESC
+            "#,
+        )
+        .bind(product_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(records)
+    }
+
+    async fn scoped_rules(&self) -> Result<Vec<ComplianceRule>, AppError> {
+        let stored = sqlx::query_as::<_, StoredComplianceRule>(
+            "SELECT * FROM compliance_rules WHERE is_active AND definition ? 'applies_to'",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        // A broken stored rule must not stop the others from being evaluated
+        Ok(stored
+            .iter()
+            .filter_map(|s| match s.rule() {
+                Ok(rule) => Some(rule),
+                Err(e) => {
+                    tracing::error!(rule_id = %s.rule_id, version = s.version, "Invalid stored compliance rule: {}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// [`product_record`] extended with the product's EUDR origin summary
+    /// under `eudr`
+    async fn product_data(
+        &self,
+        product: &Product,
+        events: &[TrackingEvent],
+    ) -> Result<Value, AppError> {
+        let mut data = product_record(product, events);
+        data["eudr"] = self.eudr.summary(&product.id).await?;
+        Ok(data)
+    }
+
+    async fn load_product(
+        &self,
+        product_id: &str,
+    ) -> Result<(Product, Vec<TrackingEvent>), AppError> {
+        let product = sqlx::query_as::<_, Product>("SELECT * FROM products WHERE id = $1")
+            .bind(product_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Product {} not found", product_id)))?;
+
+        let events = sqlx::query_as::<_, TrackingEvent>(
+            "SELECT * FROM tracking_events WHERE product_id = $1 ORDER BY timestamp ASC LIMIT $2",
+        )
+        .bind(product_id)
+        .bind(MAX_SEQUENCE_EVENTS)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok((product, events))
+    }
+
+    async fn record(
+        &self,
+        result: &ValidationResult,
+        product_id: Option<&str>,
+        data: &Value,
+        user_id: Option<Uuid>,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            INSERT INTO compliance_records
+                (user_id, product_id, compliance_type, status, rule_id, rule_version,
+                 validation_data, source, created_at, updated_at, verified_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, 'manual', NOW(), NOW(), NOW())
+            "#,
+        )
+        .bind(user_id)
+        .bind(product_id)
+        .bind(&result.compliance_type)
+        .bind(status_of(result))
+        .bind(&result.rule_id)
+        .bind(result.rule_version)
+        .bind(json!({ "input": data, "findings": result.findings }))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Refreshes the current record, or starts a new one when the outcome changed
+    async fn store_continuous(
+        &self,
+        product_id: &str,
+        result: &ValidationResult,
+    ) -> Result<Option<ComplianceStatusChange>, AppError> {
+        let status = status_of(result);
+        let validation_data = json!({ "findings": result.findings });
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+            .bind(format!("compliance:{}:{}", product_id, result.rule_id))
+            .execute(&mut *tx)
+            .await?;
+
+        let current = sqlx::query_as::<_, (Uuid, String, Option<i32>)>(
+            r#"
+            SELECT id, status, rule_version FROM compliance_records
+            WHERE product_id = $1 AND rule_id = $2 AND source = 'continuous'
+            ORDER BY created_at DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(product_id)
+        .bind(&result.rule_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some((id, current_status, version)) = &current {
+            if current_status == status && *version == Some(result.rule_version) {
+                sqlx::query(
+                    "UPDATE compliance_records SET validation_data = $2, updated_at = NOW(), verified_at = NOW() WHERE id = $1",
+                )
+                .bind(id)
+                .bind(&validation_data)
+                .execute(&mut *tx)
+                .await?;
+                tx.commit().await?;
+                return Ok(None);
+            }
+        }
+
+        let evaluated_at = sqlx::query_scalar::<_, DateTime<Utc>>(
+            r#"
+            INSERT INTO compliance_records
+                (product_id, compliance_type, status, rule_id, rule_version,
+                 validation_data, source, created_at, updated_at, verified_at)
+            VALUES ($1, $2, $3, $4, $5, $6, 'continuous', NOW(), NOW(), NOW())
+            RETURNING created_at
+            "#,
+        )
+        .bind(product_id)
+        .bind(&result.compliance_type)
+        .bind(status)
+        .bind(&result.rule_id)
+        .bind(result.rule_version)
+        .bind(&validation_data)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        let previous_status = current.map(|(_, status, _)| status);
+        if previous_status.as_deref() == Some(status) {
+            // Only the rule version changed
+            return Ok(None);
+        }
+        Ok(Some(ComplianceStatusChange {
+            product_id: product_id.to_string(),
+            rule_id: result.rule_id.clone(),
+            rule_version: result.rule_version,
+            compliance_type: result.compliance_type.clone(),
+            previous_status,
+            status: status.to_string(),
+            violations: result.violations.clone(),
+            evaluated_at,
+        }))
+    }
+}
+
+fn status_of(result: &ValidationResult) -> &'static str {
+    if result.is_compliant {
+        "compliant"
+    } else {
+        "non_compliant"
+    }
+}
+
+/// The product as rules see it: every product column, plus a summary of its
+/// event history under `events`
+fn product_record(product: &Product, events: &[TrackingEvent]) -> Value {
+    let mut record = serde_json::to_value(product).unwrap_or_else(|_| json!({}));
+    let mut event_types: Vec<&str> = events.iter().map(|e| e.event_type.as_str()).collect();
+    event_types.sort_unstable();
+    event_types.dedup();
+
+    record["events"] = json!({
+        "count": events.len(),
+        "types": event_types,
+        "first_at": events.first().map(|e| e.timestamp),
+        "last_at": events.last().map(|e| e.timestamp),
+        "last_location": events.last().map(|e| e.location.clone()),
+    });
+    record
+}
+
+/// Product and organization subscribers see every change; regressions also
+/// go to `alerts`. Webhooks get every change too.
+async fn publish_status_change(
+    pool: &PgPool,
+    change: &ComplianceStatusChange,
+    organization_id: Option<Uuid>,
+) {
+    let Ok(data) = serde_json::to_value(change) else {
+        return;
+    };
+    let mut targets = channels::for_product(&change.product_id, organization_id);
+    if change.status == "non_compliant" {
+        targets.push(ALERTS_CHANNEL.to_string());
+    }
+    channels::publish(&targets, data.clone()).await;
+    webhook_service::enqueue(
+        pool,
+        "compliance.status_changed",
+        &change.product_id,
+        organization_id,
+        data,
+    )
+    .await;
+}
@@ -1,16 +1,35 @@
 use crate::models::digital_twin::*;
+use crate::models::TrackingEvent;
 use crate::error::AppError;
+use crate::models::carbon::CalculateFootprintRequest;
+use crate::services::carbon_calculator;
+use crate::services::eta_predictor::{self, EtaModel};
+use crate::services::LocationService;
+use crate::utils::geo::{self, Coordinates};
+use crate::services::twin_sync::{self, ObservedDivergence};
 use chrono::Utc;
 use sqlx::PgPool;
 use uuid::Uuid;
 
+/// Lookback window for ETA training data
+const ETA_TRAINING_DAYS: i64 = 365;
+/// Upper bound on events loaded to train the ETA model
+const ETA_TRAINING_MAX_EVENTS: i64 = 50_000;
+/// Confidence level used when an ETA is requested through the predictions API
+const DEFAULT_ETA_CONFIDENCE: f64 = 0.9;
+/// Planning assumptions for route simulations
+const ROUTE_AVG_SPEED_KMH: f64 = 62.5;
+const ROUTE_COST_PER_KM_USD: f64 = 1.67;
+
 pub struct DigitalTwinService {
     pool: PgPool,
+    location_service: LocationService,
 }
 
 impl DigitalTwinService {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        let location_service = LocationService::new(pool.clone());
+        Self { pool, location_service }
     }
 
     /// Create a new digital twin
@@ -22,9 +41,9 @@ impl DigitalTwinService {
             r#"
             INSERT INTO digital_twins (
                 id, product_id, twin_type, name, description,
-                current_state, metadata, is_active, created_at, updated_at
+                current_state, metadata, is_active, created_at, updated_at, sync_mapping
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, true, $8, $8)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, true, $8, $8, $9)
             RETURNING *
             "#,
         )
@@ -36,9 +55,9 @@ impl DigitalTwinService {
         .bind(&request.initial_state)
         .bind(&request.metadata)
         .bind(Utc::now())
+        .bind(serde_json::to_value(&request.sync_mapping)?)
         .fetch_one(&self.pool)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        .await?;
 
         Ok(twin)
     }
@@ -66,8 +85,7 @@ impl DigitalTwinService {
         )
         .bind(product_id)
         .fetch_all(&self.pool)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        .await?;
 
         Ok(twins)
     }
@@ -90,8 +108,7 @@ impl DigitalTwinService {
         .bind(Utc::now())
         .bind(twin_id)
         .execute(&self.pool)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        .await?;
 
         // Record state history
         let state = sqlx::query_as::<_, TwinState>(
@@ -108,8 +125,7 @@ impl DigitalTwinService {
         .bind(Utc::now())
         .bind(&request.source)
         .fetch_one(&self.pool)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        .await?;
 
         Ok(state)
     }
@@ -126,12 +142,227 @@ impl DigitalTwinService {
         .bind(twin_id)
         .bind(limit)
         .fetch_all(&self.pool)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        .await?;
 
         Ok(states)
     }
 
+    /// Replace the sync mapping for a twin
+    pub async fn update_sync_mapping(
+        &self,
+        twin_id: Uuid,
+        mapping: TwinSyncMapping,
+    ) -> Result<DigitalTwin, AppError> {
+        let twin = sqlx::query_as::<_, DigitalTwin>(
+            "UPDATE digital_twins SET sync_mapping = $1, updated_at = $2 WHERE id = $3 RETURNING *"
+        )
+        .bind(serde_json::to_value(&mapping)?)
+        .bind(Utc::now())
+        .bind(twin_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Digital twin {} not found", twin_id)))?;
+
+        Ok(twin)
+    }
+
+    /// Apply a newly recorded tracking event to every active twin of its product.
+    /// Returns the number of twins updated.
+    pub async fn sync_from_event(&self, event: &TrackingEvent) -> Result<usize, AppError> {
+        let observation = twin_sync::observation_from_event(event);
+        let twins = self.get_twins_by_product(&event.product_id).await?;
+        let mut updated = 0;
+
+        for twin in twins {
+            let mapping = twin_sync::resolve_mapping(&twin.sync_mapping);
+            if !twin_sync::accepts_event(&mapping, &event.event_type) {
+                continue;
+            }
+
+            self.apply_observation(&twin, &mapping, &observation, "tracking_event", Some(event.id))
+                .await?;
+            updated += 1;
+        }
+
+        Ok(updated)
+    }
+
+    /// Apply an oracle snapshot to every active twin of a product.
+    /// Snapshots bypass the event type filter.
+    pub async fn sync_from_snapshot(
+        &self,
+        product_id: &str,
+        request: OracleSnapshotRequest,
+    ) -> Result<Vec<TwinDivergence>, AppError> {
+        let twins = self.get_twins_by_product(product_id).await?;
+        let source = format!("oracle:{}", request.source);
+        let mut divergences = Vec::new();
+
+        for twin in twins {
+            let mapping = twin_sync::resolve_mapping(&twin.sync_mapping);
+            let recorded = self
+                .apply_observation(&twin, &mapping, &request.snapshot, &source, None)
+                .await?;
+            divergences.extend(recorded);
+        }
+
+        Ok(divergences)
+    }
+
+    /// Get divergence alerts for a twin, newest first
+    pub async fn list_divergences(
+        &self,
+        twin_id: Uuid,
+        open_only: bool,
+    ) -> Result<Vec<TwinDivergence>, AppError> {
+        let divergences = sqlx::query_as::<_, TwinDivergence>(
+            r#"
+            SELECT * FROM twin_divergences
+            WHERE twin_id = $1 AND ($2 = false OR resolved_at IS NULL)
+            ORDER BY detected_at DESC
+            "#,
+        )
+        .bind(twin_id)
+        .bind(open_only)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(divergences)
+    }
+
+    /// Write an observation into a twin's state and record any divergences
+    async fn apply_observation(
+        &self,
+        twin: &DigitalTwin,
+        mapping: &TwinSyncMapping,
+        observation: &serde_json::Value,
+        source: &str,
+        tracking_event_id: Option<i64>,
+    ) -> Result<Vec<TwinDivergence>, AppError> {
+        let (next_state, divergences) =
+            twin_sync::apply_mapping(&twin.current_state, mapping, observation);
+
+        self.update_twin_state(
+            twin.id,
+            UpdateTwinStateRequest {
+                state_data: next_state,
+                metrics: serde_json::json!({ "divergences": divergences.len() }),
+                source: source.to_string(),
+            },
+        )
+        .await?;
+
+        let mut recorded = Vec::with_capacity(divergences.len());
+        for divergence in divergences {
+            recorded.push(
+                self.record_divergence(twin.id, divergence, source, tracking_event_id)
+                    .await?,
+            );
+        }
+
+        if !recorded.is_empty() {
+            tracing::warn!(
+                twin_id = %twin.id,
+                count = recorded.len(),
+                source,
+                "Digital twin diverged from predicted state"
+            );
+        }
+
+        Ok(recorded)
+    }
+
+    async fn record_divergence(
+        &self,
+        twin_id: Uuid,
+        divergence: ObservedDivergence,
+        source: &str,
+        tracking_event_id: Option<i64>,
+    ) -> Result<TwinDivergence, AppError> {
+        let row = sqlx::query_as::<_, TwinDivergence>(
+            r#"
+            INSERT INTO twin_divergences (
+                id, twin_id, tracking_event_id, state_path,
+                predicted_value, observed_value, source, detected_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(twin_id)
+        .bind(tracking_event_id)
+        .bind(&divergence.state_path)
+        .bind(&divergence.predicted)
+        .bind(&divergence.observed)
+        .bind(source)
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    /// Estimate arrival for a product whose latest event is a shipment.
+    /// The model is trained on ship/receive legs from the last `ETA_TRAINING_DAYS`.
+    pub async fn predict_eta(
+        &self,
+        product_id: &str,
+        confidence: f64,
+    ) -> Result<EtaPrediction, AppError> {
+        let latest = sqlx::query_as::<_, TrackingEvent>(
+            "SELECT * FROM tracking_events WHERE product_id = $1 ORDER BY timestamp DESC LIMIT 1"
+        )
+        .bind(product_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No events recorded for product {}", product_id)))?;
+
+        let leg = eta_predictor::in_flight_leg(&latest).ok_or_else(|| {
+            AppError::BusinessRule(format!(
+                "Product {} is not in transit (latest event: {})",
+                product_id, latest.event_type
+            ))
+        })?;
+
+        let history = sqlx::query_as::<_, TrackingEvent>(
+            r#"
+            SELECT * FROM tracking_events
+            WHERE UPPER(event_type) IN ('SHIP', 'SHIPPED', 'RECEIVE', 'RECEIVED')
+              AND timestamp > $1
+            ORDER BY product_id, timestamp
+            LIMIT $2
+            "#,
+        )
+        .bind(Utc::now() - chrono::Duration::days(ETA_TRAINING_DAYS))
+        .bind(ETA_TRAINING_MAX_EVENTS)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let model = EtaModel::train(&history);
+        let now = Utc::now();
+        let estimate = model.estimate(&leg, now, confidence).ok_or_else(|| {
+            AppError::BusinessRule("Not enough transit history to estimate arrival".to_string())
+        })?;
+
+        let at = |hours: f64| leg.departed_at + chrono::Duration::seconds((hours * 3600.0) as i64);
+
+        Ok(EtaPrediction {
+            product_id: product_id.to_string(),
+            origin: leg.origin.clone(),
+            destination: leg.destination.clone(),
+            transport_mode: leg.mode.clone(),
+            departed_at: leg.departed_at,
+            estimated_arrival: at(estimate.point_hours),
+            lower_bound: at(estimate.lower_hours),
+            upper_bound: at(estimate.upper_hours),
+            confidence_level: confidence.clamp(0.5, 0.99),
+            sample_size: estimate.sample_size,
+            segment: estimate.segment,
+            overdue: estimate.overdue,
+        })
+    }
+
     /// Create a simulation
     pub async fn create_simulation(
         &self,
@@ -157,8 +388,7 @@ impl DigitalTwinService {
         .bind(Utc::now())
         .bind(&request.created_by)
         .fetch_one(&self.pool)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        .await?;
 
         Ok(simulation)
     }
@@ -178,8 +408,7 @@ impl DigitalTwinService {
         .bind(start_time)
         .bind(simulation_id)
         .execute(&self.pool)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        .await?;
 
         // Get simulation details
         let simulation = sqlx::query_as::<_, Simulation>(
@@ -187,8 +416,7 @@ impl DigitalTwinService {
         )
         .bind(simulation_id)
         .fetch_one(&self.pool)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        .await?;
 
         // Perform simulation based on type
         let results = self.execute_simulation(&simulation).await?;
@@ -209,8 +437,7 @@ impl DigitalTwinService {
         .bind(end_time)
         .bind(simulation_id)
         .execute(&self.pool)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        .await?;
 
         Ok(SimulationResult {
             simulation_id,
@@ -229,7 +456,7 @@ impl DigitalTwinService {
     ) -> Result<serde_json::Value, AppError> {
         match simulation.simulation_type {
             SimulationType::RouteOptimization => {
-                self.simulate_route_optimization(&simulation.parameters).await
+                self.simulate_route_optimization(simulation).await
             }
             SimulationType::DemandForecasting => {
                 self.simulate_demand_forecasting(&simulation.parameters).await
@@ -249,40 +476,136 @@ impl DigitalTwinService {
         }
     }
 
-    /// Simulate route optimization
+    /// Simulate route optimization.
+    /// Waypoints come from `parameters.waypoints` (location names, "lat,lon" strings or
+    /// objects with `latitude`/`longitude`), or else from the product's geocoded events.
+    /// The first and last waypoint stay fixed; intermediate stops are reordered.
     async fn simulate_route_optimization(
         &self,
-        parameters: &serde_json::Value,
+        simulation: &Simulation,
     ) -> Result<serde_json::Value, AppError> {
-        // Simplified route optimization simulation
+        let parameters = &simulation.parameters;
+        let waypoints = self.route_waypoints(simulation).await?;
+
+        if waypoints.len() < 2 {
+            return Err(AppError::BusinessRule(
+                "Route optimization needs at least two geocoded waypoints".to_string(),
+            ));
+        }
+
+        let points: Vec<Coordinates> = waypoints.iter().map(|(_, p)| *p).collect();
+        let order = geo::optimize_route(&points);
+        let optimized: Vec<Coordinates> = order.iter().map(|&i| points[i]).collect();
+
+        let transport_mode = parameters
+            .get("transport_mode")
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+        let weight_kg = parameters.get("weight_kg").and_then(|v| v.as_f64());
+        let route_summary = |distance_km: f64| {
+            let carbon_kg = carbon_calculator::calculate(&CalculateFootprintRequest {
+                product_id: String::new(),
+                tracking_event_id: None,
+                transport_mode: transport_mode.clone(),
+                distance_km: Some(distance_km),
+                energy_source: None,
+                weight_kg,
+                packaging_type: None,
+                storage_hours: None,
+                baseline_emissions: None,
+            })
+            .transport_emissions;
+            (
+                distance_km,
+                distance_km / ROUTE_AVG_SPEED_KMH,
+                distance_km * ROUTE_COST_PER_KM_USD,
+                carbon_kg,
+            )
+        };
+
+        let original = route_summary(geo::path_distance_km(&points));
+        let improved = route_summary(geo::path_distance_km(&optimized));
+        let round = |v: f64| (v * 100.0).round() / 100.0;
+        let summary_json = |(distance, hours, cost, carbon): (f64, f64, f64, f64)| {
+            serde_json::json!({
+                "distance_km": round(distance),
+                "estimated_time_hours": round(hours),
+                "cost_usd": round(cost),
+                "carbon_kg": round(carbon)
+            })
+        };
+
+        let reduction_percent = if original.0 > 0.0 {
+            (original.0 - improved.0) / original.0 * 100.0
+        } else {
+            0.0
+        };
+
         Ok(serde_json::json!({
-            "original_route": {
-                "distance_km": 1500,
-                "estimated_time_hours": 24,
-                "cost_usd": 2500,
-                "carbon_kg": 450
-            },
-            "optimized_route": {
-                "distance_km": 1350,
-                "estimated_time_hours": 21,
-                "cost_usd": 2200,
-                "carbon_kg": 405
-            },
+            "original_route": summary_json(original),
+            "optimized_route": summary_json(improved),
             "improvements": {
-                "distance_reduction_percent": 10.0,
-                "time_savings_hours": 3,
-                "cost_savings_usd": 300,
-                "carbon_reduction_kg": 45
+                "distance_reduction_percent": round(reduction_percent),
+                "time_savings_hours": round(original.1 - improved.1),
+                "cost_savings_usd": round(original.2 - improved.2),
+                "carbon_reduction_kg": round(original.3 - improved.3)
             },
-            "waypoints": [
-                {"location": "Origin", "arrival": "2024-03-15T08:00:00Z"},
-                {"location": "Hub A", "arrival": "2024-03-15T14:00:00Z"},
-                {"location": "Hub B", "arrival": "2024-03-15T20:00:00Z"},
-                {"location": "Destination", "arrival": "2024-03-16T05:00:00Z"}
-            ]
+            "waypoints": order
+                .iter()
+                .map(|&i| {
+                    let (name, point) = &waypoints[i];
+                    serde_json::json!({
+                        "location": name,
+                        "latitude": point.latitude,
+                        "longitude": point.longitude
+                    })
+                })
+                .collect::<Vec<_>>()
         }))
     }
 
+    /// Resolve the waypoints of a route simulation to coordinates
+    async fn route_waypoints(
+        &self,
+        simulation: &Simulation,
+    ) -> Result<Vec<(String, Coordinates)>, AppError> {
+        let Some(raw) = simulation.parameters.get("waypoints").and_then(|v| v.as_array()) else {
+            let twin = self.get_twin(simulation.twin_id).await?;
+            let path = self.location_service.product_path(&twin.product_id).await?;
+            return Ok(path
+                .into_iter()
+                .enumerate()
+                .map(|(i, p)| (format!("Stop {}", i + 1), p))
+                .collect());
+        };
+
+        let mut waypoints = Vec::with_capacity(raw.len());
+        for (i, waypoint) in raw.iter().enumerate() {
+            let (name, metadata) = match waypoint {
+                serde_json::Value::String(name) => (name.clone(), serde_json::json!({})),
+                other => (
+                    other
+                        .get("name")
+                        .and_then(|v| v.as_str())
+                        .map(str::to_string)
+                        .unwrap_or_else(|| format!("Stop {}", i + 1)),
+                    other.clone(),
+                ),
+            };
+
+            let resolved = self
+                .location_service
+                .resolve(&name, &metadata)
+                .await?
+                .ok_or_else(|| AppError::Validation(format!("Unknown waypoint location: {}", name)))?;
+            let point = Coordinates::new(resolved.latitude, resolved.longitude)
+                .ok_or_else(|| AppError::Validation(format!("Invalid coordinates for {}", name)))?;
+            waypoints.push((name, point));
+        }
+
+        Ok(waypoints)
+    }
+
     /// Simulate demand forecasting
     async fn simulate_demand_forecasting(
         &self,
@@ -542,8 +865,7 @@ impl DigitalTwinService {
         )
         .bind(twin_id)
         .fetch_all(&self.pool)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        .await?;
 
         Ok(simulations)
     }
@@ -576,8 +898,7 @@ impl DigitalTwinService {
         .bind(Utc::now())
         .bind(Utc::now() + chrono::Duration::hours(request.prediction_horizon as i64))
         .fetch_one(&self.pool)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        .await?;
 
         Ok(prediction)
     }
@@ -589,10 +910,9 @@ impl DigitalTwinService {
     ) -> Result<serde_json::Value, AppError> {
         match request.prediction_type {
             PredictionType::ArrivalTime => {
-                Ok(serde_json::json!({
-                    "estimated_arrival": "2024-03-20T14:30:00Z",
-                    "variance_hours": 2
-                }))
+                let twin = self.get_twin(request.twin_id).await?;
+                let eta = self.predict_eta(&twin.product_id, DEFAULT_ETA_CONFIDENCE).await?;
+                Ok(serde_json::to_value(eta)?)
             }
             PredictionType::Delay => {
                 Ok(serde_json::json!({
@@ -639,32 +959,28 @@ impl DigitalTwinService {
         )
         .bind(twin_id)
         .fetch_all(&self.pool)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        .await?;
 
         let active_optimizations = sqlx::query_as::<_, Optimization>(
             "SELECT * FROM optimizations WHERE twin_id = $1 AND applied_at IS NULL ORDER BY created_at DESC LIMIT 5"
         )
         .bind(twin_id)
         .fetch_all(&self.pool)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        .await?;
 
         let simulation_count: (i64,) = sqlx::query_as(
             "SELECT COUNT(*) FROM simulations WHERE twin_id = $1"
         )
         .bind(twin_id)
         .fetch_one(&self.pool)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        .await?;
 
         let state_history_count: (i64,) = sqlx::query_as(
             "SELECT COUNT(*) FROM twin_states WHERE twin_id = $1"
         )
         .bind(twin_id)
         .fetch_one(&self.pool)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        .await?;
 
         Ok(TwinAnalytics {
             twin_id,
@@ -0,0 +1,951 @@
+/// GS1 EPCIS 2.0 mapping for tracking events.
+///
+/// Tracking events become EPCIS events as follows:
+/// - events with `metadata.epcis.child_epcs` become AggregationEvents,
+/// - PROCESS events, and events with `metadata.epcis.input_epcs`, become TransformationEvents,
+/// - everything else becomes an ObjectEvent.
+///
+/// EPCIS has no slot for the actor, free-text location, note, data hash or
+/// metadata, so these travel as `cl:` extension fields. A document we export
+/// therefore re-imports without loss.
+///
+/// Documents are written as JSON-LD or XML and parsed from either. XML events
+/// are first converted to the JSON-LD shape and then share the JSON parser.
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::models::{NewTrackingEvent, TrackingEvent};
+use crate::validation::gs1_check_digit;
+
+pub const EPCIS_CONTEXT: &str = "https://ref.gs1.org/standards/epcis/2.0.0/epcis-context.jsonld";
+pub const EXTENSION_NAMESPACE: &str = "https://chainlogistics.io/epcis/";
+const XML_NAMESPACE: &str = "urn:epcglobal:epcis:xsd:2";
+const SCHEMA_VERSION: &str = "2.0";
+
+const PRODUCT_URN_PREFIX: &str = "urn:chainlogistics:product:";
+const EVENT_URN_PREFIX: &str = "urn:chainlogistics:event:";
+const DIGITAL_LINK_PREFIX: &str = "https://id.gs1.org/";
+const BIZ_STEP_PREFIXES: &[&str] = &[
+    "urn:epcglobal:cbv:bizstep:",
+    "https://ref.gs1.org/cbv/BizStep-",
+];
+const DISPOSITION_PREFIXES: &[&str] = &["urn:epcglobal:cbv:disp:", "https://ref.gs1.org/cbv/Disp-"];
+
+/// Tracking event types and their CBV business step. On import the first
+/// matching row wins, so HARVEST takes `commissioning` for Object events.
+const BIZ_STEPS: &[(&str, &str)] = &[
+    ("HARVEST", "commissioning"),
+    ("REGISTER", "creating_class_instance"),
+    ("PROCESS", "commissioning"),
+    ("PACKAGE", "packing"),
+    ("SHIP", "shipping"),
+    ("RECEIVE", "receiving"),
+    ("QUALITY_CHECK", "inspecting"),
+    ("TRANSFER", "consigning"),
+    ("CHECKPOINT", "transporting"),
+];
+
+/// Additional business steps accepted from trading partners on import
+const IMPORT_BIZ_STEP_ALIASES: &[(&str, &str)] = &[
+    ("SHIP", "departing"),
+    ("RECEIVE", "arriving"),
+    ("RECEIVE", "accepting"),
+    ("PACKAGE", "repackaging"),
+    ("QUALITY_CHECK", "sampling"),
+];
+
+/// Event type used for unknown business steps
+const FALLBACK_EVENT_TYPE: &str = "CHECKPOINT";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EpcisEventType {
+    ObjectEvent,
+    AggregationEvent,
+    TransformationEvent,
+}
+
+impl EpcisEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EpcisEventType::ObjectEvent => "ObjectEvent",
+            EpcisEventType::AggregationEvent => "AggregationEvent",
+            EpcisEventType::TransformationEvent => "TransformationEvent",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "ObjectEvent" => Some(EpcisEventType::ObjectEvent),
+            "AggregationEvent" => Some(EpcisEventType::AggregationEvent),
+            "TransformationEvent" => Some(EpcisEventType::TransformationEvent),
+            _ => None,
+        }
+    }
+}
+
+/// ChainLogistics fields carried as `cl:` extensions
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EpcisExtension {
+    pub event_type: Option<String>,
+    pub actor_address: Option<String>,
+    pub location: Option<String>,
+    pub data_hash: Option<String>,
+    pub note: Option<String>,
+    pub metadata: Option<Value>,
+}
+
+/// Format-neutral EPCIS event
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpcisEvent {
+    pub event_type: EpcisEventType,
+    pub event_id: Option<String>,
+    pub event_time: DateTime<Utc>,
+    pub event_time_zone_offset: String,
+    pub action: Option<String>,
+    pub biz_step: Option<String>,
+    pub disposition: Option<String>,
+    pub epc_list: Vec<String>,
+    pub parent_id: Option<String>,
+    pub child_epcs: Vec<String>,
+    pub input_epcs: Vec<String>,
+    pub output_epcs: Vec<String>,
+    pub read_point: Option<String>,
+    pub biz_location: Option<String>,
+    pub extension: EpcisExtension,
+}
+
+/// A validation problem, with the position of the offending event when known
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpcisIssue {
+    pub index: Option<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for EpcisIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.index {
+            Some(i) => write!(f, "event {}: {}", i, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+// ── Identifiers ───────────────────────────────────────────────────────────────
+
+/// EPC URI for a product: `custom_fields.epc` if set, a GS1 Digital Link when
+/// `custom_fields.gtin` is a valid GTIN (serial = product id), otherwise a
+/// ChainLogistics URN.
+pub fn product_epc(product_id: &str, custom_fields: &Value) -> String {
+    if let Some(epc) = custom_fields.get("epc").and_then(Value::as_str) {
+        return epc.to_string();
+    }
+    if let Some(gtin) = custom_fields
+        .get("gtin")
+        .and_then(Value::as_str)
+        .and_then(normalize_gtin)
+    {
+        return format!("{}01/{}/21/{}", DIGITAL_LINK_PREFIX, gtin, product_id);
+    }
+    format!("{}{}", PRODUCT_URN_PREFIX, product_id)
+}
+
+/// Recovers a product id from EPCs we issue. Other EPCs need a lookup
+/// against `custom_fields.epc`.
+pub fn product_id_from_epc(epc: &str) -> Option<String> {
+    if let Some(id) = epc.strip_prefix(PRODUCT_URN_PREFIX) {
+        return (!id.is_empty()).then(|| id.to_string());
+    }
+    let path = epc.strip_prefix(DIGITAL_LINK_PREFIX)?;
+    let (_, serial) = path.split_once("/21/")?;
+    let serial = serial.split(['/', '?']).next()?;
+    (!serial.is_empty()).then(|| serial.to_string())
+}
+
+/// Pads a GTIN-8/12/13/14 to 14 digits, returning `None` if the check digit is wrong.
+pub fn normalize_gtin(gtin: &str) -> Option<String> {
+    if !matches!(gtin.len(), 8 | 12 | 13 | 14) || !gtin.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (body, check) = gtin.split_at(gtin.len() - 1);
+    (gs1_check_digit(body)? == check.parse::<u32>().ok()?).then(|| format!("{:0>14}", gtin))
+}
+
+fn gln_from_location_id(id: &str) -> Option<&str> {
+    let gln = id.strip_prefix(DIGITAL_LINK_PREFIX)?.strip_prefix("414/")?;
+    Some(gln.split(['/', '?']).next().unwrap_or(gln))
+}
+
+// ── Tracking events → EPCIS ───────────────────────────────────────────────────
+
+/// Maps a stored tracking event onto an EPCIS event for the given product EPC.
+pub fn to_epcis_event(event: &TrackingEvent, epc: &str) -> EpcisEvent {
+    let epcis = event.metadata.get("epcis").cloned().unwrap_or(Value::Null);
+    let str_field = |key: &str| epcis.get(key).and_then(Value::as_str).map(str::to_string);
+    let list_field = |key: &str| string_list(epcis.get(key));
+
+    let child_epcs = list_field("child_epcs");
+    let input_epcs = list_field("input_epcs");
+    let event_type = if !child_epcs.is_empty() {
+        EpcisEventType::AggregationEvent
+    } else if event.event_type == "PROCESS" || !input_epcs.is_empty() {
+        EpcisEventType::TransformationEvent
+    } else {
+        EpcisEventType::ObjectEvent
+    };
+
+    let action = match event_type {
+        EpcisEventType::TransformationEvent => None,
+        _ => Some(
+            str_field("action").unwrap_or_else(|| default_action(&event.event_type).to_string()),
+        ),
+    };
+
+    let (epc_list, parent_id, output_epcs) = match event_type {
+        EpcisEventType::ObjectEvent => (vec![epc.to_string()], None, Vec::new()),
+        EpcisEventType::AggregationEvent => (Vec::new(), Some(epc.to_string()), Vec::new()),
+        EpcisEventType::TransformationEvent => (Vec::new(), None, vec![epc.to_string()]),
+    };
+
+    let biz_location = str_field("biz_location").or_else(|| {
+        event
+            .metadata
+            .get("gln")
+            .and_then(Value::as_str)
+            .map(|gln| format!("{}414/{}", DIGITAL_LINK_PREFIX, gln))
+    });
+
+    let mut metadata = event.metadata.clone();
+    if let Some(obj) = metadata.as_object_mut() {
+        obj.remove("epcis");
+    }
+    let metadata = metadata
+        .as_object()
+        .is_some_and(|obj| !obj.is_empty())
+        .then_some(metadata);
+
+    EpcisEvent {
+        event_type,
+        event_id: Some(
+            str_field("event_id").unwrap_or_else(|| format!("{}{}", EVENT_URN_PREFIX, event.id)),
+        ),
+        event_time: event.timestamp,
+        event_time_zone_offset: str_field("event_time_zone_offset")
+            .unwrap_or_else(|| "+00:00".to_string()),
+        action,
+        biz_step: biz_step_for(&event.event_type).map(str::to_string),
+        disposition: str_field("disposition"),
+        epc_list,
+        parent_id,
+        child_epcs,
+        input_epcs,
+        output_epcs,
+        read_point: str_field("read_point"),
+        biz_location,
+        extension: EpcisExtension {
+            event_type: Some(event.event_type.clone()),
+            actor_address: Some(event.actor_address.clone()),
+            location: Some(event.location.clone()),
+            data_hash: Some(event.data_hash.clone()),
+            note: (!event.note.is_empty()).then(|| event.note.clone()),
+            metadata,
+        },
+    }
+}
+
+/// CBV business step for a tracking event type
+pub fn biz_step_for(event_type: &str) -> Option<&'static str> {
+    BIZ_STEPS
+        .iter()
+        .find(|(t, _)| *t == event_type)
+        .map(|(_, step)| *step)
+}
+
+fn default_action(event_type: &str) -> &'static str {
+    match event_type {
+        "HARVEST" | "REGISTER" | "PACKAGE" => "ADD",
+        _ => "OBSERVE",
+    }
+}
+
+// ── EPCIS → tracking events ───────────────────────────────────────────────────
+
+impl EpcisEvent {
+    /// EPCs identifying the products this event is recorded against
+    pub fn subject_epcs(&self) -> Vec<&str> {
+        match self.event_type {
+            EpcisEventType::ObjectEvent => self.epc_list.iter().map(String::as_str).collect(),
+            EpcisEventType::AggregationEvent => self.parent_id.iter().map(String::as_str).collect(),
+            EpcisEventType::TransformationEvent => {
+                self.output_epcs.iter().map(String::as_str).collect()
+            }
+        }
+    }
+
+    /// Tracking event type: the `cl:eventType` extension when present, otherwise
+    /// derived from the EPCIS event type and business step.
+    pub fn tracking_event_type(&self) -> String {
+        if let Some(t) = &self.extension.event_type {
+            return t.to_ascii_uppercase();
+        }
+        if self.event_type == EpcisEventType::TransformationEvent {
+            return "PROCESS".to_string();
+        }
+        let Some(step) = self.biz_step.as_deref() else {
+            return FALLBACK_EVENT_TYPE.to_string();
+        };
+        BIZ_STEPS
+            .iter()
+            .chain(IMPORT_BIZ_STEP_ALIASES)
+            .find(|(_, s)| *s == step)
+            .map(|(t, _)| t.to_string())
+            .unwrap_or_else(|| FALLBACK_EVENT_TYPE.to_string())
+    }
+
+    /// Location text: `cl:location`, else the business location, else the read point
+    pub fn location(&self) -> Option<&str> {
+        self.extension
+            .location
+            .as_deref()
+            .or(self.biz_location.as_deref())
+            .or(self.read_point.as_deref())
+    }
+
+    /// Builds the tracking event for one product. The EPCIS identifiers are kept
+    /// under `metadata.epcis` so the event exports back to the same shape.
+    pub fn to_new_tracking_event(
+        &self,
+        product_id: &str,
+        fallback_actor: Option<&str>,
+    ) -> Result<NewTrackingEvent, String> {
+        let actor_address = self
+            .extension
+            .actor_address
+            .as_deref()
+            .or(fallback_actor)
+            .ok_or("no actor address: set cl:actorAddress or pass actor_address")?
+            .to_string();
+        let location = self
+            .location()
+            .ok_or("no location: set bizLocation, readPoint or cl:location")?
+            .to_string();
+        let event_type = self.tracking_event_type();
+        if biz_step_for(&event_type).is_none() {
+            return Err(format!("unsupported event type '{}'", event_type));
+        }
+
+        let mut metadata = match &self.extension.metadata {
+            Some(Value::Object(obj)) => obj.clone(),
+            _ => Map::new(),
+        };
+        if let Some(gln) = self.biz_location.as_deref().and_then(gln_from_location_id) {
+            metadata
+                .entry("gln")
+                .or_insert_with(|| Value::String(gln.to_string()));
+        }
+        metadata.insert("epcis".to_string(), self.metadata_record());
+
+        let data_hash = self.extension.data_hash.clone().unwrap_or_else(|| {
+            hex::encode(Sha256::digest(event_to_json(self).to_string().as_bytes()))
+        });
+
+        Ok(NewTrackingEvent {
+            product_id: product_id.to_string(),
+            actor_address,
+            timestamp: self.event_time,
+            event_type,
+            location,
+            data_hash,
+            note: self.extension.note.clone().unwrap_or_default(),
+            metadata: Value::Object(metadata),
+        })
+    }
+
+    fn metadata_record(&self) -> Value {
+        let mut record = Map::new();
+        let mut put = |key: &str, value: Option<&String>| {
+            if let Some(v) = value {
+                record.insert(key.to_string(), Value::String(v.clone()));
+            }
+        };
+        put("event_id", self.event_id.as_ref());
+        put("action", self.action.as_ref());
+        put("disposition", self.disposition.as_ref());
+        put("read_point", self.read_point.as_ref());
+        put("biz_location", self.biz_location.as_ref());
+        if self.event_time_zone_offset != "+00:00" {
+            put("event_time_zone_offset", Some(&self.event_time_zone_offset));
+        }
+        record.insert("type".to_string(), json!(self.event_type.as_str()));
+        for (key, list) in [
+            ("child_epcs", &self.child_epcs),
+            ("input_epcs", &self.input_epcs),
+        ] {
+            if !list.is_empty() {
+                record.insert(key.to_string(), json!(list));
+            }
+        }
+        Value::Object(record)
+    }
+}
+
+// ── JSON-LD ───────────────────────────────────────────────────────────────────
+
+/// Wraps events in an EPCIS 2.0 JSON-LD document.
+pub fn document_to_json(events: &[EpcisEvent], creation_date: DateTime<Utc>) -> Value {
+    json!({
+        "@context": [EPCIS_CONTEXT, {"cl": EXTENSION_NAMESPACE}],
+        "type": "EPCISDocument",
+        "schemaVersion": SCHEMA_VERSION,
+        "creationDate": timestamp(creation_date),
+        "epcisBody": {
+            "eventList": events.iter().map(event_to_json).collect::<Vec<_>>()
+        }
+    })
+}
+
+pub fn event_to_json(event: &EpcisEvent) -> Value {
+    let mut obj = Map::new();
+    let mut put = |key: &str, value: Value| {
+        obj.insert(key.to_string(), value);
+    };
+
+    put("type", json!(event.event_type.as_str()));
+    if let Some(id) = &event.event_id {
+        put("eventID", json!(id));
+    }
+    put("eventTime", json!(timestamp(event.event_time)));
+    put("eventTimeZoneOffset", json!(event.event_time_zone_offset));
+    match event.event_type {
+        EpcisEventType::ObjectEvent => put("epcList", json!(event.epc_list)),
+        EpcisEventType::AggregationEvent => {
+            if let Some(parent) = &event.parent_id {
+                put("parentID", json!(parent));
+            }
+            put("childEPCs", json!(event.child_epcs));
+        }
+        EpcisEventType::TransformationEvent => {
+            put("inputEPCList", json!(event.input_epcs));
+            put("outputEPCList", json!(event.output_epcs));
+        }
+    }
+    if let Some(action) = &event.action {
+        put("action", json!(action));
+    }
+    if let Some(step) = &event.biz_step {
+        put("bizStep", json!(step));
+    }
+    if let Some(disposition) = &event.disposition {
+        put("disposition", json!(disposition));
+    }
+    if let Some(id) = &event.read_point {
+        put("readPoint", json!({ "id": id }));
+    }
+    if let Some(id) = &event.biz_location {
+        put("bizLocation", json!({ "id": id }));
+    }
+
+    let ext = &event.extension;
+    for (key, value) in [
+        ("cl:eventType", &ext.event_type),
+        ("cl:actorAddress", &ext.actor_address),
+        ("cl:location", &ext.location),
+        ("cl:dataHash", &ext.data_hash),
+        ("cl:note", &ext.note),
+    ] {
+        if let Some(v) = value {
+            put(key, json!(v));
+        }
+    }
+    if let Some(metadata) = &ext.metadata {
+        put("cl:metadata", metadata.clone());
+    }
+
+    Value::Object(obj)
+}
+
+/// Parses and validates an EPCIS 2.0 JSON-LD document. All issues are
+/// collected so a partner can fix a document in one pass.
+pub fn parse_json_document(doc: &Value) -> Result<Vec<EpcisEvent>, Vec<EpcisIssue>> {
+    if doc.get("type").and_then(Value::as_str) != Some("EPCISDocument") {
+        return Err(vec![document_issue("type must be \"EPCISDocument\"")]);
+    }
+    check_schema_version(doc.get("schemaVersion").and_then(Value::as_str))?;
+
+    let Some(list) = doc
+        .get("epcisBody")
+        .and_then(|b| b.get("eventList"))
+        .and_then(Value::as_array)
+    else {
+        return Err(vec![document_issue("epcisBody.eventList is missing")]);
+    };
+
+    collect_events(list.iter().map(event_from_json))
+}
+
+/// Parses one EPCIS event in JSON-LD form.
+pub fn event_from_json(value: &Value) -> Result<EpcisEvent, String> {
+    let text = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+    let nested_id = |key: &str| {
+        value
+            .get(key)
+            .and_then(|v| v.get("id").or(Some(v)))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+
+    let raw_type = text("type").ok_or("type is missing")?;
+    let event_type = EpcisEventType::parse(&raw_type)
+        .ok_or_else(|| format!("unsupported event type '{}'", raw_type))?;
+
+    let event_time = text("eventTime").ok_or("eventTime is missing")?;
+    let event_time = DateTime::parse_from_rfc3339(&event_time)
+        .map_err(|_| format!("eventTime '{}' is not an ISO 8601 timestamp", event_time))?
+        .with_timezone(&Utc);
+
+    let action = text("action");
+    match (event_type, action.as_deref()) {
+        (EpcisEventType::TransformationEvent, _) => {}
+        (_, Some("ADD" | "OBSERVE" | "DELETE")) => {}
+        (_, Some(other)) => return Err(format!("invalid action '{}'", other)),
+        (_, None) => return Err("action is missing".to_string()),
+    }
+
+    let event = EpcisEvent {
+        event_type,
+        event_id: text("eventID"),
+        event_time,
+        event_time_zone_offset: text("eventTimeZoneOffset").unwrap_or_else(|| "+00:00".to_string()),
+        action: if event_type == EpcisEventType::TransformationEvent {
+            None
+        } else {
+            action
+        },
+        biz_step: text("bizStep").map(|s| strip_vocabulary(&s, BIZ_STEP_PREFIXES)),
+        disposition: text("disposition").map(|s| strip_vocabulary(&s, DISPOSITION_PREFIXES)),
+        epc_list: string_list(value.get("epcList")),
+        parent_id: text("parentID"),
+        child_epcs: string_list(value.get("childEPCs")),
+        input_epcs: string_list(value.get("inputEPCList")),
+        output_epcs: string_list(value.get("outputEPCList")),
+        read_point: nested_id("readPoint"),
+        biz_location: nested_id("bizLocation"),
+        extension: EpcisExtension {
+            event_type: text("cl:eventType"),
+            actor_address: text("cl:actorAddress"),
+            location: text("cl:location"),
+            data_hash: text("cl:dataHash"),
+            note: text("cl:note"),
+            metadata: value.get("cl:metadata").cloned(),
+        },
+    };
+
+    if event.subject_epcs().is_empty() {
+        return Err(match event_type {
+            EpcisEventType::ObjectEvent => "epcList is empty (quantityList is not supported)",
+            EpcisEventType::AggregationEvent => "parentID is missing",
+            EpcisEventType::TransformationEvent => "outputEPCList is empty",
+        }
+        .to_string());
+    }
+
+    Ok(event)
+}
+
+// ── XML ───────────────────────────────────────────────────────────────────────
+
+/// Serializes events as an EPCIS 2.0 XML document.
+pub fn document_to_xml(events: &[EpcisEvent], creation_date: DateTime<Utc>) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<epcis:EPCISDocument xmlns:epcis=\"{}\" xmlns:cl=\"{}\" schemaVersion=\"{}\" creationDate=\"{}\">\n",
+        XML_NAMESPACE,
+        EXTENSION_NAMESPACE,
+        SCHEMA_VERSION,
+        timestamp(creation_date)
+    ));
+    xml.push_str("  <EPCISBody>\n    <EventList>\n");
+    for event in events {
+        write_xml_event(&mut xml, event);
+    }
+    xml.push_str("    </EventList>\n  </EPCISBody>\n</epcis:EPCISDocument>\n");
+    xml
+}
+
+fn write_xml_event(xml: &mut String, event: &EpcisEvent) {
+    let name = event.event_type.as_str();
+    let indent = "        ";
+    let element = |xml: &mut String, tag: &str, value: &str| {
+        xml.push_str(&format!(
+            "{}<{}>{}</{}>\n",
+            indent,
+            tag,
+            escape_xml(value),
+            tag
+        ));
+    };
+    let epc_list = |xml: &mut String, tag: &str, epcs: &[String]| {
+        xml.push_str(&format!("{}<{}>\n", indent, tag));
+        for epc in epcs {
+            xml.push_str(&format!("{}  <epc>{}</epc>\n", indent, escape_xml(epc)));
+        }
+        xml.push_str(&format!("{}</{}>\n", indent, tag));
+    };
+
+    xml.push_str(&format!("      <{}>\n", name));
+    element(xml, "eventTime", &timestamp(event.event_time));
+    element(xml, "eventTimeZoneOffset", &event.event_time_zone_offset);
+    if let Some(id) = &event.event_id {
+        element(xml, "eventID", id);
+    }
+    match event.event_type {
+        EpcisEventType::ObjectEvent => epc_list(xml, "epcList", &event.epc_list),
+        EpcisEventType::AggregationEvent => {
+            if let Some(parent) = &event.parent_id {
+                element(xml, "parentID", parent);
+            }
+            epc_list(xml, "childEPCs", &event.child_epcs);
+        }
+        EpcisEventType::TransformationEvent => {
+            epc_list(xml, "inputEPCList", &event.input_epcs);
+            epc_list(xml, "outputEPCList", &event.output_epcs);
+        }
+    }
+    if let Some(action) = &event.action {
+        element(xml, "action", action);
+    }
+    if let Some(step) = &event.biz_step {
+        element(xml, "bizStep", &format!("{}{}", BIZ_STEP_PREFIXES[0], step));
+    }
+    if let Some(disposition) = &event.disposition {
+        element(
+            xml,
+            "disposition",
+            &format!("{}{}", DISPOSITION_PREFIXES[0], disposition),
+        );
+    }
+    for (tag, id) in [
+        ("readPoint", &event.read_point),
+        ("bizLocation", &event.biz_location),
+    ] {
+        if let Some(id) = id {
+            xml.push_str(&format!(
+                "{}<{}><id>{}</id></{}>\n",
+                indent,
+                tag,
+                escape_xml(id),
+                tag
+            ));
+        }
+    }
+
+    let ext = &event.extension;
+    for (tag, value) in [
+        ("cl:eventType", &ext.event_type),
+        ("cl:actorAddress", &ext.actor_address),
+        ("cl:location", &ext.location),
+        ("cl:dataHash", &ext.data_hash),
+        ("cl:note", &ext.note),
+    ] {
+        if let Some(v) = value {
+            element(xml, tag, v);
+        }
+    }
+    if let Some(metadata) = &ext.metadata {
+        element(xml, "cl:metadata", &metadata.to_string());
+    }
+    xml.push_str(&format!("      </{}>\n", name));
+}
+
+/// Parses and validates an EPCIS 2.0 XML document.
+pub fn parse_xml_document(text: &str) -> Result<Vec<EpcisEvent>, Vec<EpcisIssue>> {
+    let doc = roxmltree::Document::parse(text)
+        .map_err(|e| vec![document_issue(&format!("invalid XML: {}", e))])?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "EPCISDocument" {
+        return Err(vec![document_issue("root element must be EPCISDocument")]);
+    }
+    check_schema_version(root.attribute("schemaVersion"))?;
+
+    let Some(list) = root
+        .children()
+        .find(|n| n.has_tag_name("EPCISBody"))
+        .and_then(|body| body.children().find(|n| n.has_tag_name("EventList")))
+    else {
+        return Err(vec![document_issue("EPCISBody/EventList is missing")]);
+    };
+
+    collect_events(
+        list.children()
+            .filter(|n| n.is_element())
+            .map(|n| event_from_json(&xml_event_to_json(n))),
+    )
+}
+
+/// Converts an XML event element to the JSON-LD shape
+fn xml_event_to_json(node: roxmltree::Node) -> Value {
+    let mut obj = Map::new();
+    obj.insert("type".to_string(), json!(node.tag_name().name()));
+
+    for child in node.children().filter(|n| n.is_element()) {
+        let name = child.tag_name().name();
+        let text = child.text().unwrap_or("").trim().to_string();
+        let value = match name {
+            _ if child.tag_name().namespace() == Some(EXTENSION_NAMESPACE) => {
+                let value = if name == "metadata" {
+                    serde_json::from_str(&text).unwrap_or(Value::String(text))
+                } else {
+                    Value::String(text)
+                };
+                obj.insert(format!("cl:{}", name), value);
+                continue;
+            }
+            "epcList" | "childEPCs" | "inputEPCList" | "outputEPCList" => json!(child
+                .children()
+                .filter(|n| n.has_tag_name("epc"))
+                .filter_map(|n| n.text())
+                .map(str::trim)
+                .collect::<Vec<_>>()),
+            "readPoint" | "bizLocation" => {
+                let id = child
+                    .children()
+                    .find(|n| n.has_tag_name("id"))
+                    .and_then(|n| n.text())
+                    .unwrap_or("")
+                    .trim();
+                json!({ "id": id })
+            }
+            _ => Value::String(text),
+        };
+        obj.insert(name.to_string(), value);
+    }
+
+    Value::Object(obj)
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// ── Internal helpers ──────────────────────────────────────────────────────────
+
+fn timestamp(dt: DateTime<Utc>) -> String {
+    dt.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn string_list(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn strip_vocabulary(value: &str, prefixes: &[&str]) -> String {
+    prefixes
+        .iter()
+        .find_map(|p| value.strip_prefix(p))
+        .unwrap_or(value)
+        .to_string()
+}
+
+fn check_schema_version(version: Option<&str>) -> Result<(), Vec<EpcisIssue>> {
+    match version {
+        Some(v) if v == SCHEMA_VERSION || v == "2" => Ok(()),
+        Some(v) => Err(vec![document_issue(&format!(
+            "unsupported schemaVersion '{}', expected {}",
+            v, SCHEMA_VERSION
+        ))]),
+        None => Err(vec![document_issue("schemaVersion is missing")]),
+    }
+}
+
+fn collect_events(
+    results: impl Iterator<Item = Result<EpcisEvent, String>>,
+) -> Result<Vec<EpcisEvent>, Vec<EpcisIssue>> {
+    let mut events = Vec::new();
+    let mut issues = Vec::new();
+    for (index, result) in results.enumerate() {
+        match result {
+            Ok(event) => events.push(event),
+            Err(message) => issues.push(EpcisIssue {
+                index: Some(index),
+                message,
+            }),
+        }
+    }
+    if issues.is_empty() {
+        Ok(events)
+    } else {
+        Err(issues)
+    }
+}
+
+fn document_issue(message: &str) -> EpcisIssue {
+    EpcisIssue {
+        index: None,
+        message: message.to_string(),
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn tracking_event(event_type: &str, metadata: Value) -> TrackingEvent {
+        let ts = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+        TrackingEvent {
+            id: 42,
+            product_id: "PROD-1".to_string(),
+            actor_address: "GACTOR".to_string(),
+            timestamp: ts,
+            event_type: event_type.to_string(),
+            location: "Port of Rotterdam".to_string(),
+            data_hash: "abc123".to_string(),
+            note: "on <time> & dry".to_string(),
+            metadata,
+            created_at: ts,
+        }
+    }
+
+    #[test]
+    fn test_product_epc_and_reverse_lookup() {
+        assert_eq!(
+            product_epc("PROD-1", &json!({})),
+            "urn:chainlogistics:product:PROD-1"
+        );
+        let link = product_epc("PROD-1", &json!({"gtin": "4006381333931"}));
+        assert_eq!(link, "https://id.gs1.org/01/04006381333931/21/PROD-1");
+        assert_eq!(product_id_from_epc(&link).as_deref(), Some("PROD-1"));
+        assert_eq!(
+            product_id_from_epc("urn:chainlogistics:product:PROD-1").as_deref(),
+            Some("PROD-1")
+        );
+        assert!(product_id_from_epc("urn:epc:id:sgtin:0614141.107346.2017").is_none());
+        // Bad check digit falls back to the URN
+        assert!(product_epc("PROD-1", &json!({"gtin": "4006381333932"})).starts_with("urn:"));
+    }
+
+    #[test]
+    fn test_event_shapes() {
+        let ship = to_epcis_event(&tracking_event("SHIP", json!({})), "urn:x");
+        assert_eq!(ship.event_type, EpcisEventType::ObjectEvent);
+        assert_eq!(ship.action.as_deref(), Some("OBSERVE"));
+        assert_eq!(ship.biz_step.as_deref(), Some("shipping"));
+
+        let pack = to_epcis_event(
+            &tracking_event(
+                "PACKAGE",
+                json!({"epcis": {"child_epcs": ["urn:a", "urn:b"]}}),
+            ),
+            "urn:pallet",
+        );
+        assert_eq!(pack.event_type, EpcisEventType::AggregationEvent);
+        assert_eq!(pack.parent_id.as_deref(), Some("urn:pallet"));
+        assert_eq!(pack.action.as_deref(), Some("ADD"));
+
+        let process = to_epcis_event(
+            &tracking_event("PROCESS", json!({"epcis": {"input_epcs": ["urn:beans"]}})),
+            "urn:roast",
+        );
+        assert_eq!(process.event_type, EpcisEventType::TransformationEvent);
+        assert_eq!(process.subject_epcs(), vec!["urn:roast"]);
+        assert!(process.action.is_none());
+    }
+
+    #[test]
+    fn test_json_round_trip_preserves_tracking_fields() {
+        let original = tracking_event(
+            "SHIP",
+            json!({"temperature_c": 4.5, "gln": "4012345000009"}),
+        );
+        let epcis = to_epcis_event(&original, "urn:chainlogistics:product:PROD-1");
+        let doc = document_to_json(std::slice::from_ref(&epcis), original.timestamp);
+
+        let parsed = parse_json_document(&doc).unwrap();
+        assert_eq!(parsed, vec![epcis]);
+
+        let event = parsed[0].to_new_tracking_event("PROD-1", None).unwrap();
+        assert_eq!(event.event_type, "SHIP");
+        assert_eq!(event.location, "Port of Rotterdam");
+        assert_eq!(event.data_hash, "abc123");
+        assert_eq!(event.metadata["temperature_c"], json!(4.5));
+        assert_eq!(
+            event.metadata["epcis"]["event_id"],
+            json!("urn:chainlogistics:event:42")
+        );
+    }
+
+    #[test]
+    fn test_xml_round_trip() {
+        let original = tracking_event(
+            "PACKAGE",
+            json!({"epcis": {"child_epcs": ["urn:a"], "disposition": "in_progress"}}),
+        );
+        let epcis = to_epcis_event(&original, "urn:pallet");
+        let xml = document_to_xml(std::slice::from_ref(&epcis), original.timestamp);
+
+        assert!(xml.contains("on &lt;time&gt; &amp; dry"));
+        assert!(xml.contains("urn:epcglobal:cbv:bizstep:packing"));
+        assert_eq!(parse_xml_document(&xml).unwrap(), vec![epcis]);
+    }
+
+    #[test]
+    fn test_partner_event_without_extensions() {
+        let doc = json!({
+            "type": "EPCISDocument",
+            "schemaVersion": "2.0",
+            "epcisBody": {"eventList": [{
+                "type": "ObjectEvent",
+                "eventTime": "2024-03-01T10:00:00.000+01:00",
+                "epcList": ["urn:chainlogistics:product:PROD-1"],
+                "action": "OBSERVE",
+                "bizStep": "https://ref.gs1.org/cbv/BizStep-arriving",
+                "bizLocation": {"id": "https://id.gs1.org/414/4012345000009"}
+            }]}
+        });
+        let events = parse_json_document(&doc).unwrap();
+        let event = events[0]
+            .to_new_tracking_event("PROD-1", Some("GPARTNER"))
+            .unwrap();
+
+        assert_eq!(event.event_type, "RECEIVE");
+        assert_eq!(event.actor_address, "GPARTNER");
+        assert_eq!(event.metadata["gln"], json!("4012345000009"));
+        assert_eq!(event.data_hash.len(), 64);
+        assert!(events[0].to_new_tracking_event("PROD-1", None).is_err());
+    }
+
+    #[test]
+    fn test_validation_collects_all_issues() {
+        let doc = json!({
+            "type": "EPCISDocument",
+            "schemaVersion": "2.0",
+            "epcisBody": {"eventList": [
+                {"type": "ObjectEvent", "eventTime": "yesterday", "epcList": ["urn:a"], "action": "OBSERVE"},
+                {"type": "AggregationEvent", "eventTime": "2024-03-01T10:00:00Z", "action": "ADD"},
+                {"type": "AssociationEvent", "eventTime": "2024-03-01T10:00:00Z"},
+                {"type": "ObjectEvent", "eventTime": "2024-03-01T10:00:00Z", "epcList": ["urn:a"], "action": "MOVE"}
+            ]}
+        });
+        let issues = parse_json_document(&doc).unwrap_err();
+        let indexes: Vec<_> = issues.iter().map(|i| i.index).collect();
+        assert_eq!(indexes, vec![Some(0), Some(1), Some(2), Some(3)]);
+
+        let wrong_version = json!({"type": "EPCISDocument", "schemaVersion": "1.2"});
+        assert!(parse_json_document(&wrong_version).is_err());
+    }
+}
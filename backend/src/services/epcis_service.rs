@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::PgPool;
+
+use crate::blockchain::ChainAnchor;
+use crate::database::EventRepository;
+use crate::error::AppError;
+use crate::models::epcis::{EpcisAnchorOutcome, EpcisImportResult};
+use crate::models::{NewTrackingEvent, Product, TrackingEvent};
+use crate::services::epcis::{self, EpcisEvent, EpcisIssue};
+use crate::services::EventService;
+use crate::validation::{
+    sanitize_input, sanitize_json_metadata, validate_location, validate_product_id,
+    validate_stellar_address,
+};
+
+const MAX_EXPORT_EVENTS: i64 = 10_000;
+const MAX_IMPORT_EVENTS: usize = 1_000;
+/// Issues listed in a rejected import before the message is truncated
+const MAX_REPORTED_ISSUES: usize = 20;
+
+/// Imports and exports tracking data as GS1 EPCIS 2.0 documents
+pub struct EpcisService {
+    pool: PgPool,
+    event_service: Arc<EventService>,
+    anchor: Option<ChainAnchor>,
+}
+
+impl EpcisService {
+    pub fn new(pool: PgPool, event_service: Arc<EventService>) -> Self {
+        Self {
+            pool,
+            event_service,
+            anchor: ChainAnchor::from_env(),
+        }
+    }
+
+    // ── Export ────────────────────────────────────────────────────────────────
+
+    /// All events of one product, oldest first
+    pub async fn export_product(&self, product_id: &str) -> Result<Vec<EpcisEvent>, AppError> {
+        let product = sqlx::query_as::<_, Product>("SELECT * FROM products WHERE id = $1")
+            .bind(product_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Product {} not found", product_id)))?;
+
+        let events = sqlx::query_as::<_, TrackingEvent>(
+            "SELECT * FROM tracking_events WHERE product_id = $1 ORDER BY timestamp ASC, id ASC LIMIT $2",
+        )
+        .bind(product_id)
+        .bind(MAX_EXPORT_EVENTS)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let epc = epcis::product_epc(&product.id, &product.custom_fields);
+        Ok(events
+            .iter()
+            .map(|e| epcis::to_epcis_event(e, &epc))
+            .collect())
+    }
+
+    /// Events recorded in `[start, end)`, optionally for a single product
+    pub async fn export_range(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        product_id: Option<&str>,
+    ) -> Result<Vec<EpcisEvent>, AppError> {
+        if start >= end {
+            return Err(AppError::Validation(
+                "start_date must be before end_date".to_string(),
+            ));
+        }
+
+        let events = sqlx::query_as::<_, TrackingEvent>(
+            r#"
+            SELECT * FROM tracking_events
+            WHERE timestamp >= $1 AND timestamp < $2
+              AND ($3::text IS NULL OR product_id = $3)
+            ORDER BY timestamp ASC, id ASC
+            LIMIT $4
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .bind(product_id)
+        .bind(MAX_EXPORT_EVENTS)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut product_ids: Vec<String> = events.iter().map(|e| e.product_id.clone()).collect();
+        product_ids.sort();
+        product_ids.dedup();
+
+        let custom_fields: HashMap<String, Value> = sqlx::query_as::<_, (String, Value)>(
+            "SELECT id, custom_fields FROM products WHERE id = ANY($1)",
+        )
+        .bind(&product_ids)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .collect();
+
+        let empty = Value::Object(Default::default());
+        Ok(events
+            .iter()
+            .map(|e| {
+                let fields = custom_fields.get(&e.product_id).unwrap_or(&empty);
+                epcis::to_epcis_event(e, &epcis::product_epc(&e.product_id, fields))
+            })
+            .collect())
+    }
+
+    // ── Import ────────────────────────────────────────────────────────────────
+
+    /// Parses a JSON-LD or XML document, reporting every invalid event at once
+    pub fn parse_document(&self, body: &str, is_xml: bool) -> Result<Vec<EpcisEvent>, AppError> {
+        let parsed = if is_xml {
+            epcis::parse_xml_document(body)
+        } else {
+            let doc: Value = serde_json::from_str(body)
+                .map_err(|e| AppError::Validation(format!("Invalid EPCIS JSON: {}", e)))?;
+            epcis::parse_json_document(&doc)
+        };
+        parsed.map_err(|issues| AppError::Validation(issue_summary(&issues)))
+    }
+
+    /// Validates every event first and only writes when the whole document is
+    /// acceptable. Events already imported are skipped, so re-sending a
+    /// document is safe.
+    pub async fn import(
+        &self,
+        events: Vec<EpcisEvent>,
+        default_actor: Option<&str>,
+        anchor: bool,
+    ) -> Result<EpcisImportResult, AppError> {
+        if events.is_empty() {
+            return Err(AppError::Validation(
+                "EPCIS document contains no events".to_string(),
+            ));
+        }
+        if events.len() > MAX_IMPORT_EVENTS {
+            return Err(AppError::Validation(format!(
+                "EPCIS document contains {} events; the limit is {}",
+                events.len(),
+                MAX_IMPORT_EVENTS
+            )));
+        }
+        let anchor = match (anchor, &self.anchor) {
+            (false, _) => None,
+            (true, Some(anchor)) if anchor.has_contract() => Some(anchor),
+            (true, _) => {
+                return Err(AppError::BusinessRule(
+                    "On-chain anchoring is not configured".to_string(),
+                ))
+            }
+        };
+        if let Some(actor) = default_actor {
+            validate_stellar_address(actor)?;
+        }
+
+        let mut pending = Vec::new();
+        let mut issues = Vec::new();
+        for (index, event) in events.iter().enumerate() {
+            let mut issue = |message: String| {
+                issues.push(EpcisIssue {
+                    index: Some(index),
+                    message,
+                })
+            };
+            for epc in event.subject_epcs() {
+                let Some(product_id) = self.resolve_epc(epc).await? else {
+                    issue(format!("no product matches EPC '{}'", epc));
+                    continue;
+                };
+                match event
+                    .to_new_tracking_event(&product_id, default_actor)
+                    .and_then(validate_imported)
+                {
+                    Ok(new_event) => pending.push((event.event_id.clone(), new_event)),
+                    Err(message) => issue(message),
+                }
+            }
+        }
+        if !issues.is_empty() {
+            return Err(AppError::Validation(issue_summary(&issues)));
+        }
+
+        let mut result = EpcisImportResult {
+            event_ids: Vec::new(),
+            skipped_duplicates: 0,
+            anchored: Vec::new(),
+        };
+        for (event_id, new_event) in pending {
+            if self.is_duplicate(event_id.as_deref(), &new_event).await? {
+                result.skipped_duplicates += 1;
+                continue;
+            }
+
+            let created = self.event_service.create_event(new_event).await?;
+            result.event_ids.push(created.id);
+
+            if let Some(anchor) = anchor {
+                let outcome = anchor.anchor_event(&created).await;
+                if let Err(e) = &outcome {
+                    tracing::warn!(event_id = created.id, "EPCIS event anchoring failed: {}", e);
+                }
+                result.anchored.push(EpcisAnchorOutcome {
+                    event_id: created.id,
+                    result: outcome.as_ref().ok().cloned(),
+                    error: outcome.err(),
+                });
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Maps an EPC to an existing product id
+    async fn resolve_epc(&self, epc: &str) -> Result<Option<String>, AppError> {
+        if let Some(id) = epcis::product_id_from_epc(epc) {
+            let found = sqlx::query_scalar::<_, String>("SELECT id FROM products WHERE id = $1")
+                .bind(&id)
+                .fetch_optional(&self.pool)
+                .await?;
+            if found.is_some() {
+                return Ok(found);
+            }
+        }
+
+        let found = sqlx::query_scalar::<_, String>(
+            "SELECT id FROM products WHERE custom_fields->>'epc' = $1 LIMIT 1",
+        )
+        .bind(epc)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(found)
+    }
+
+    async fn is_duplicate(
+        &self,
+        epcis_event_id: Option<&str>,
+        event: &NewTrackingEvent,
+    ) -> Result<bool, AppError> {
+        let exists = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM tracking_events
+                WHERE product_id = $1
+                  AND ((metadata->'epcis'->>'event_id') = $2
+                       OR (data_hash = $3 AND timestamp = $4))
+            )
+            "#,
+        )
+        .bind(&event.product_id)
+        .bind(epcis_event_id)
+        .bind(&event.data_hash)
+        .bind(event.timestamp)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(exists)
+    }
+}
+
+/// Applies the same checks and sanitization as `POST /admin/events`
+fn validate_imported(mut event: NewTrackingEvent) -> Result<NewTrackingEvent, String> {
+    validate_product_id(&event.product_id).map_err(error_message)?;
+    validate_stellar_address(&event.actor_address).map_err(error_message)?;
+    validate_location(&event.location).map_err(error_message)?;
+    if event.note.len() > 256 {
+        return Err("note must not exceed 256 characters".to_string());
+    }
+    if event.timestamp > Utc::now() {
+        return Err("eventTime must not be in the future".to_string());
+    }
+
+    event.location = sanitize_input(&event.location);
+    event.note = sanitize_input(&event.note);
+    sanitize_json_metadata(&mut event.metadata);
+    Ok(event)
+}
+
+fn error_message(error: AppError) -> String {
+    match error {
+        AppError::Validation(message) => message,
+        other => other.to_string(),
+    }
+}
+
+fn issue_summary(issues: &[EpcisIssue]) -> String {
+    let mut lines: Vec<String> = issues
+        .iter()
+        .take(MAX_REPORTED_ISSUES)
+        .map(ToString::to_string)
+        .collect();
+    if issues.len() > MAX_REPORTED_ISSUES {
+        lines.push(format!(
+            "... and {} more",
+            issues.len() - MAX_REPORTED_ISSUES
+        ));
+    }
+    format!("Invalid EPCIS document: {}", lines.join("; "))
+}
@@ -0,0 +1,408 @@
+/// Arrival time estimation learned from historical transit legs.
+///
+/// A transit leg is a "shipped" event followed by the next "received" event for
+/// the same product. Legs are segmented by origin, destination and transport
+/// mode; when a lane has too few legs the estimate falls back to coarser
+/// segments (origin + mode, mode, then every leg).
+///
+/// Estimates are conditioned on the time already spent in transit, so a
+/// shipment that is running late only considers legs that took at least as long.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::TrackingEvent;
+
+/// Minimum legs in a segment before it is trusted over a coarser one
+pub const MIN_SEGMENT_SAMPLES: usize = 5;
+
+/// A completed shipment leg
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransitSample {
+    pub origin: String,
+    pub destination: String,
+    pub mode: String,
+    pub hours: f64,
+}
+
+/// An in-flight shipment to estimate
+#[derive(Debug, Clone)]
+pub struct InFlightLeg {
+    pub origin: String,
+    pub destination: Option<String>,
+    pub mode: String,
+    pub departed_at: DateTime<Utc>,
+}
+
+/// Granularity of the segment that produced an estimate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EtaSegment {
+    Lane,
+    OriginMode,
+    Mode,
+    Global,
+}
+
+/// Hours from departure, with an interval at the requested confidence
+#[derive(Debug, Clone, PartialEq)]
+pub struct EtaEstimate {
+    pub point_hours: f64,
+    pub lower_hours: f64,
+    pub upper_hours: f64,
+    pub sample_size: usize,
+    pub segment: EtaSegment,
+    /// The shipment has already outlasted every comparable leg
+    pub overdue: bool,
+}
+
+/// Transit-time model built from historical legs
+#[derive(Debug, Clone, Default)]
+pub struct EtaModel {
+    samples: Vec<TransitSample>,
+}
+
+// ── Public API ────────────────────────────────────────────────────────────────
+
+impl EtaModel {
+    /// Trains the model from events ordered by product and timestamp.
+    pub fn train(events: &[TrackingEvent]) -> Self {
+        Self {
+            samples: extract_transit_samples(events),
+        }
+    }
+
+    pub fn from_samples(samples: Vec<TransitSample>) -> Self {
+        Self { samples }
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Estimates arrival for an in-flight leg. `confidence` is clamped to 0.5..=0.99.
+    /// Returns `None` when there is no history at all.
+    pub fn estimate(
+        &self,
+        leg: &InFlightLeg,
+        now: DateTime<Utc>,
+        confidence: f64,
+    ) -> Option<EtaEstimate> {
+        let (segment, mut hours) = self.select_segment(leg)?;
+        hours.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        let confidence = confidence.clamp(0.5, 0.99);
+        let elapsed = ((now - leg.departed_at).num_seconds() as f64 / 3600.0).max(0.0);
+        let sample_size = hours.len();
+
+        let remaining: Vec<f64> = hours.iter().copied().filter(|h| *h >= elapsed).collect();
+        if remaining.is_empty() {
+            let spread = std_dev(&hours);
+            return Some(EtaEstimate {
+                point_hours: elapsed,
+                lower_hours: elapsed,
+                upper_hours: elapsed + spread,
+                sample_size,
+                segment,
+                overdue: true,
+            });
+        }
+
+        let tail = (1.0 - confidence) / 2.0;
+        Some(EtaEstimate {
+            point_hours: quantile(&remaining, 0.5),
+            lower_hours: quantile(&remaining, tail).max(elapsed),
+            upper_hours: quantile(&remaining, 1.0 - tail),
+            sample_size,
+            segment,
+            overdue: false,
+        })
+    }
+
+    fn select_segment(&self, leg: &InFlightLeg) -> Option<(EtaSegment, Vec<f64>)> {
+        let origin = normalize_location(&leg.origin);
+        let destination = leg.destination.as_deref().map(normalize_location);
+        let mode = normalize_mode(Some(&leg.mode));
+
+        let lane: Vec<f64> = match &destination {
+            Some(dest) => {
+                self.hours_where(|s| s.origin == origin && &s.destination == dest && s.mode == mode)
+            }
+            None => Vec::new(),
+        };
+        let origin_mode = self.hours_where(|s| s.origin == origin && s.mode == mode);
+        let by_mode = self.hours_where(|s| s.mode == mode);
+        let global = self.hours_where(|_| true);
+
+        let candidates = [
+            (EtaSegment::Lane, lane),
+            (EtaSegment::OriginMode, origin_mode),
+            (EtaSegment::Mode, by_mode),
+            (EtaSegment::Global, global),
+        ];
+
+        // Prefer the most specific segment with enough history, otherwise the
+        // most specific segment with any history at all.
+        if let Some(found) = candidates
+            .iter()
+            .find(|(_, h)| h.len() >= MIN_SEGMENT_SAMPLES)
+        {
+            return Some(found.clone());
+        }
+        candidates.into_iter().find(|(_, h)| !h.is_empty())
+    }
+
+    fn hours_where(&self, predicate: impl Fn(&TransitSample) -> bool) -> Vec<f64> {
+        self.samples
+            .iter()
+            .filter(|s| predicate(s))
+            .map(|s| s.hours)
+            .collect()
+    }
+}
+
+/// Pairs each shipped event with the next received event of the same product.
+pub fn extract_transit_samples(events: &[TrackingEvent]) -> Vec<TransitSample> {
+    let mut samples = Vec::new();
+    let mut open: Option<&TrackingEvent> = None;
+
+    for event in events {
+        if let Some(shipped) = open {
+            if shipped.product_id != event.product_id {
+                open = None;
+            }
+        }
+
+        if is_ship_event(&event.event_type) {
+            open = Some(event);
+        } else if is_receive_event(&event.event_type) {
+            if let Some(shipped) = open.take() {
+                let hours = (event.timestamp - shipped.timestamp).num_seconds() as f64 / 3600.0;
+                if hours > 0.0 {
+                    samples.push(TransitSample {
+                        origin: normalize_location(&shipped.location),
+                        destination: normalize_location(&event.location),
+                        mode: transport_mode_of(shipped),
+                        hours,
+                    });
+                }
+            }
+        }
+    }
+
+    samples
+}
+
+/// Builds the in-flight leg for a product whose latest event is a shipment.
+pub fn in_flight_leg(latest: &TrackingEvent) -> Option<InFlightLeg> {
+    if !is_ship_event(&latest.event_type) {
+        return None;
+    }
+
+    Some(InFlightLeg {
+        origin: latest.location.clone(),
+        destination: latest
+            .metadata
+            .get("destination")
+            .and_then(|v| v.as_str())
+            .map(str::to_string),
+        mode: transport_mode_of(latest),
+        departed_at: latest.timestamp,
+    })
+}
+
+pub fn is_ship_event(event_type: &str) -> bool {
+    matches!(event_type.to_ascii_uppercase().as_str(), "SHIP" | "SHIPPED")
+}
+
+pub fn is_receive_event(event_type: &str) -> bool {
+    matches!(
+        event_type.to_ascii_uppercase().as_str(),
+        "RECEIVE" | "RECEIVED"
+    )
+}
+
+// ── Internal helpers ──────────────────────────────────────────────────────────
+
+fn transport_mode_of(event: &TrackingEvent) -> String {
+    normalize_mode(
+        event
+            .metadata
+            .get("transport_mode")
+            .and_then(|v| v.as_str()),
+    )
+}
+
+/// Collapses transport mode aliases to the categories used by the carbon calculator
+fn normalize_mode(mode: Option<&str>) -> String {
+    match mode.map(|m| m.trim().to_ascii_lowercase()).as_deref() {
+        Some("road") | Some("truck") | Some("road_diesel") | Some("road_electric") => "road",
+        Some("rail") | Some("train") => "rail",
+        Some("sea") | Some("ship") | Some("ocean") => "sea",
+        Some("air") | Some("plane") => "air",
+        _ => "unknown",
+    }
+    .to_string()
+}
+
+fn normalize_location(location: &str) -> String {
+    location.trim().to_lowercase()
+}
+
+/// Linear-interpolated quantile of an ascending slice
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.len() == 1 {
+        return sorted[0];
+    }
+    let pos = q.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = pos.floor() as usize;
+    let upper = pos.ceil() as usize;
+    let weight = pos - lower as f64;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * weight
+}
+
+fn std_dev(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance =
+        values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+    variance.sqrt()
+}
+
+// ── Tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use serde_json::json;
+
+    fn event(
+        product: &str,
+        event_type: &str,
+        location: &str,
+        hour: i64,
+        mode: Option<&str>,
+    ) -> TrackingEvent {
+        let base = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        TrackingEvent {
+            id: hour,
+            product_id: product.into(),
+            actor_address: "GACTOR".into(),
+            timestamp: base + Duration::hours(hour),
+            event_type: event_type.into(),
+            location: location.into(),
+            data_hash: String::new(),
+            note: String::new(),
+            metadata: mode
+                .map(|m| json!({ "transport_mode": m }))
+                .unwrap_or(json!({})),
+            created_at: base,
+        }
+    }
+
+    fn sample(origin: &str, destination: &str, mode: &str, hours: f64) -> TransitSample {
+        TransitSample {
+            origin: origin.into(),
+            destination: destination.into(),
+            mode: mode.into(),
+            hours,
+        }
+    }
+
+    fn leg(
+        destination: Option<&str>,
+        mode: &str,
+        departed_hours_ago: i64,
+        now: DateTime<Utc>,
+    ) -> InFlightLeg {
+        InFlightLeg {
+            origin: "Rotterdam".into(),
+            destination: destination.map(str::to_string),
+            mode: mode.into(),
+            departed_at: now - Duration::hours(departed_hours_ago),
+        }
+    }
+
+    #[test]
+    fn test_extracts_ship_receive_pairs_per_product() {
+        let events = vec![
+            event("p1", "SHIP", "Rotterdam", 0, Some("truck")),
+            event("p1", "RECEIVE", "Hamburg", 10, None),
+            event("p2", "SHIP", "Rotterdam", 0, Some("rail")),
+            event("p3", "RECEIVE", "Hamburg", 5, None),
+        ];
+
+        let samples = extract_transit_samples(&events);
+
+        assert_eq!(samples, vec![sample("rotterdam", "hamburg", "road", 10.0)]);
+    }
+
+    #[test]
+    fn test_prefers_lane_with_enough_history() {
+        let mut samples: Vec<_> = (0..5)
+            .map(|i| sample("rotterdam", "hamburg", "road", 10.0 + i as f64))
+            .collect();
+        samples.extend((0..5).map(|_| sample("rotterdam", "paris", "road", 40.0)));
+        let model = EtaModel::from_samples(samples);
+        let now = Utc::now();
+
+        let estimate = model
+            .estimate(&leg(Some("Hamburg"), "road", 0, now), now, 0.9)
+            .unwrap();
+
+        assert_eq!(estimate.segment, EtaSegment::Lane);
+        assert_eq!(estimate.point_hours, 12.0);
+        assert!(estimate.lower_hours <= estimate.point_hours);
+        assert!(estimate.upper_hours >= estimate.point_hours);
+        assert!(!estimate.overdue);
+    }
+
+    #[test]
+    fn test_falls_back_to_mode_segment() {
+        let samples: Vec<_> = (0..6)
+            .map(|i| sample("antwerp", "lyon", "rail", 20.0 + i as f64))
+            .collect();
+        let model = EtaModel::from_samples(samples);
+        let now = Utc::now();
+
+        let estimate = model
+            .estimate(&leg(Some("Hamburg"), "train", 0, now), now, 0.8)
+            .unwrap();
+
+        assert_eq!(estimate.segment, EtaSegment::Mode);
+        assert_eq!(estimate.sample_size, 6);
+    }
+
+    #[test]
+    fn test_conditions_on_elapsed_time() {
+        let samples: Vec<_> = [5.0, 6.0, 7.0, 30.0, 32.0]
+            .iter()
+            .map(|h| sample("rotterdam", "hamburg", "road", *h))
+            .collect();
+        let model = EtaModel::from_samples(samples);
+        let now = Utc::now();
+
+        let late = model
+            .estimate(&leg(Some("Hamburg"), "road", 20, now), now, 0.9)
+            .unwrap();
+        assert!(late.point_hours >= 30.0);
+        assert!(late.lower_hours >= 20.0);
+
+        let overdue = model
+            .estimate(&leg(Some("Hamburg"), "road", 50, now), now, 0.9)
+            .unwrap();
+        assert!(overdue.overdue);
+    }
+
+    #[test]
+    fn test_no_history_returns_none() {
+        let now = Utc::now();
+        assert!(EtaModel::default()
+            .estimate(&leg(None, "air", 1, now), now, 0.9)
+            .is_none());
+    }
+}
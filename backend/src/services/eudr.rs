@@ -0,0 +1,502 @@
+/// EU Deforestation Regulation (Regulation (EU) 2023/1115) due diligence.
+///
+/// Pure logic shared by `EudrService` and continuous compliance evaluation:
+///   - GeoJSON validation and area of production plots
+///   - Country risk per the Commission's Article 29 benchmarking
+///   - A product-level summary that declarative `eudr` rules check
+///   - The due-diligence statement (DDS) document itself
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::compliance::ValidationResult;
+use crate::models::eudr::{DdsActivity, OriginPlot};
+use crate::models::Product;
+use crate::utils::geo::Coordinates;
+
+/// Plots up to this size may be geolocated by a single point (Art. 2(28))
+pub const POINT_MAX_AREA_HA: f64 = 4.0;
+/// Commodities in scope of Annex I
+pub const COMMODITIES: &[&str] = &[
+    "cattle", "cocoa", "coffee", "oil_palm", "rubber", "soya", "wood",
+];
+
+/// Land deforested after this date makes a commodity non-compliant (Art. 2(13))
+pub fn deforestation_cutoff() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2020, 12, 31).expect("valid date")
+}
+
+/// WGS84 semi-major axis, metres
+const EARTH_RADIUS_M: f64 = 6_378_137.0;
+
+const HIGH_RISK_COUNTRIES: &[&str] = &["BY", "KP", "MM", "RU"];
+/// EU member states and the other low-risk countries of the benchmarking.
+/// Countries not listed here or above are treated as standard risk.
+const LOW_RISK_COUNTRIES: &[&str] = &[
+    "AT", "BE", "BG", "CY", "CZ", "DE", "DK", "EE", "ES", "FI", "FR", "GR", "HR", "HU", "IE", "IT",
+    "LT", "LU", "LV", "MT", "NL", "PL", "PT", "RO", "SE", "SI", "SK", // EU-27
+    "AD", "AU", "CA", "CH", "CN", "GB", "IS", "JP", "LI", "MC", "NO", "NZ", "SM", "US", "VA",
+];
+
+// ── Risk ──────────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RiskLevel {
+    Low,
+    Standard,
+    High,
+}
+
+impl RiskLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RiskLevel::Low => "low",
+            RiskLevel::Standard => "standard",
+            RiskLevel::High => "high",
+        }
+    }
+}
+
+pub fn country_risk(country_code: &str) -> RiskLevel {
+    let code = country_code.to_ascii_uppercase();
+    if HIGH_RISK_COUNTRIES.contains(&code.as_str()) {
+        RiskLevel::High
+    } else if LOW_RISK_COUNTRIES.contains(&code.as_str()) {
+        RiskLevel::Low
+    } else {
+        RiskLevel::Standard
+    }
+}
+
+/// Everything declarative `eudr` rules look at, exposed as `eudr.*` on the
+/// product record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EudrSummary {
+    pub plot_count: usize,
+    pub countries: Vec<String>,
+    pub commodities: Vec<String>,
+    pub total_area_ha: f64,
+    /// Plots above `POINT_MAX_AREA_HA` geolocated by a point only
+    pub plots_missing_polygon: usize,
+    /// Plots whose production started after the cut-off date, when the land
+    /// may have been cleared for it
+    pub plots_after_cutoff: usize,
+    /// Plots outside low-risk countries, or producing since after the
+    /// cut-off, with no deforestation-free evidence
+    pub plots_needing_evidence: usize,
+    pub risk_level: RiskLevel,
+    /// Article 13: every plot is in a low-risk country and nothing escalated
+    pub simplified_due_diligence: bool,
+    pub risk_factors: Vec<String>,
+}
+
+pub fn summarize(plots: &[OriginPlot]) -> EudrSummary {
+    let countries: BTreeSet<String> = plots
+        .iter()
+        .map(|p| p.country_code.to_ascii_uppercase())
+        .collect();
+    let commodities: BTreeSet<String> = plots.iter().map(|p| p.commodity.clone()).collect();
+    let plots_missing_polygon = plots
+        .iter()
+        .filter(|p| p.area_hectares > POINT_MAX_AREA_HA && is_point(&p.geometry))
+        .count();
+    let after_cutoff =
+        |p: &OriginPlot| p.production_start.is_some_and(|d| d > deforestation_cutoff());
+    let plots_after_cutoff = plots.iter().filter(|p| after_cutoff(p)).count();
+    let plots_needing_evidence = plots
+        .iter()
+        .filter(|p| {
+            (country_risk(&p.country_code) != RiskLevel::Low || after_cutoff(p))
+                && p.deforestation_free_evidence
+                    .as_deref()
+                    .is_none_or(|e| e.trim().is_empty())
+        })
+        .count();
+
+    let mut risk_level = RiskLevel::Low;
+    let mut risk_factors = Vec::new();
+    if plots.is_empty() {
+        risk_level = RiskLevel::High;
+        risk_factors.push("No geolocated production plots".to_string());
+    }
+    for country in &countries {
+        let risk = country_risk(country);
+        if risk != RiskLevel::Low {
+            risk_factors.push(format!("{} is a {}-risk country", country, risk.as_str()));
+        }
+        risk_level = risk_level.max(risk);
+    }
+    if plots_missing_polygon > 0 {
+        risk_level = RiskLevel::High;
+        risk_factors.push(format!(
+            "{} plot(s) over {} ha lack a polygon",
+            plots_missing_polygon, POINT_MAX_AREA_HA
+        ));
+    }
+    if plots_after_cutoff > 0 {
+        risk_factors.push(format!(
+            "{} plot(s) started production after {}",
+            plots_after_cutoff,
+            deforestation_cutoff()
+        ));
+    }
+    if plots_needing_evidence > 0 {
+        risk_level = RiskLevel::High;
+        risk_factors.push(format!(
+            "{} plot(s) lack deforestation-free evidence",
+            plots_needing_evidence
+        ));
+    }
+
+    EudrSummary {
+        plot_count: plots.len(),
+        countries: countries.into_iter().collect(),
+        commodities: commodities.into_iter().collect(),
+        total_area_ha: plots.iter().map(|p| p.area_hectares).sum(),
+        plots_missing_polygon,
+        plots_after_cutoff,
+        plots_needing_evidence,
+        simplified_due_diligence: risk_level == RiskLevel::Low,
+        risk_level,
+        risk_factors,
+    }
+}
+
+// ── Geometry ──────────────────────────────────────────────────────────────────
+
+/// Checks a GeoJSON Point, Polygon or MultiPolygon and returns its area in
+/// hectares (`None` for points)
+pub fn validate_geometry(geometry: &Value) -> Result<Option<f64>, String> {
+    let coordinates = geometry
+        .get("coordinates")
+        .ok_or("geometry needs `coordinates`")?;
+    match geometry.get("type").and_then(Value::as_str) {
+        Some("Point") => {
+            position(coordinates)?;
+            Ok(None)
+        }
+        Some("Polygon") => polygon_area_m2(coordinates).map(|m2| Some(m2 / 10_000.0)),
+        Some("MultiPolygon") => {
+            let polygons = coordinates
+                .as_array()
+                .filter(|p| !p.is_empty())
+                .ok_or("MultiPolygon needs at least one polygon")?;
+            let mut total = 0.0;
+            for polygon in polygons {
+                total += polygon_area_m2(polygon)?;
+            }
+            Ok(Some(total / 10_000.0))
+        }
+        _ => Err("geometry type must be Point, Polygon or MultiPolygon".to_string()),
+    }
+}
+
+fn is_point(geometry: &Value) -> bool {
+    geometry.get("type").and_then(Value::as_str) == Some("Point")
+}
+
+fn position(value: &Value) -> Result<Coordinates, String> {
+    let pair = value
+        .as_array()
+        .filter(|p| p.len() >= 2)
+        .ok_or("positions must be [longitude, latitude]")?;
+    let (lon, lat) = (pair[0].as_f64(), pair[1].as_f64());
+    lon.zip(lat)
+        .and_then(|(lon, lat)| Coordinates::new(lat, lon))
+        .ok_or_else(|| format!("position {} is outside WGS84 bounds", value))
+}
+
+/// Exterior ring minus holes, in square metres
+fn polygon_area_m2(rings: &Value) -> Result<f64, String> {
+    let rings = rings
+        .as_array()
+        .filter(|r| !r.is_empty())
+        .ok_or("Polygon needs at least one linear ring")?;
+    let mut area = 0.0;
+    for (index, ring) in rings.iter().enumerate() {
+        let points = ring
+            .as_array()
+            .ok_or("linear rings must be arrays of positions")?
+            .iter()
+            .map(position)
+            .collect::<Result<Vec<_>, _>>()?;
+        if points.len() < 4 {
+            return Err("linear rings need at least 4 positions".to_string());
+        }
+        if points.first() != points.last() {
+            return Err("linear rings must be closed (first position = last)".to_string());
+        }
+        let ring_area = ring_area_m2(&points);
+        area += if index == 0 { ring_area } else { -ring_area };
+    }
+    Ok(area.max(0.0))
+}
+
+/// Spherical ring area (Chamberlain & Duquette, 2007)
+fn ring_area_m2(points: &[Coordinates]) -> f64 {
+    let sum: f64 = points
+        .windows(2)
+        .map(|w| {
+            let (a, b) = (w[0], w[1]);
+            (b.longitude - a.longitude).to_radians()
+                * (2.0 + a.latitude.to_radians().sin() + b.latitude.to_radians().sin())
+        })
+        .sum();
+    (sum * EARTH_RADIUS_M * EARTH_RADIUS_M / 2.0).abs()
+}
+
+// ── Statement ─────────────────────────────────────────────────────────────────
+
+/// Plots as the GeoJSON FeatureCollection the EU Information System accepts
+pub fn plots_feature_collection(plots: &[OriginPlot]) -> Value {
+    let features: Vec<Value> = plots
+        .iter()
+        .map(|plot| {
+            json!({
+                "type": "Feature",
+                "geometry": plot.geometry,
+                "properties": {
+                    "ProductionPlace": plot.plot_reference.clone().unwrap_or_else(|| plot.id.to_string()),
+                    "ProducerCountry": plot.country_code.to_ascii_uppercase(),
+                    "Area": plot.area_hectares,
+                    "Commodity": plot.commodity,
+                    "SourceProduct": plot.product_id,
+                },
+            })
+        })
+        .collect();
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+/// A due-diligence statement. It is `submittable` only when every `eudr` rule
+/// passed and the risk is not high, i.e. no more than negligible risk remains.
+pub fn build_statement(
+    product: &Product,
+    plots: &[OriginPlot],
+    activity: DdsActivity,
+    validation: &[ValidationResult],
+    generated_at: DateTime<Utc>,
+) -> Value {
+    let summary = summarize(plots);
+    let field = |key: &str| {
+        product
+            .custom_fields
+            .get(key)
+            .cloned()
+            .unwrap_or(Value::Null)
+    };
+    let is_compliant = validation.iter().all(|r| r.is_compliant);
+
+    json!({
+        "reference": format!("DDS-{}-{}", product.id, generated_at.format("%Y%m%d%H%M%S")),
+        "generated_at": generated_at,
+        "regulation": "Regulation (EU) 2023/1115",
+        "activity": activity,
+        "operator": {
+            "stellar_address": product.owner_address,
+            "name": field("operator_name"),
+            "eori": field("eori"),
+        },
+        "product": {
+            "id": product.id,
+            "name": product.name,
+            "description": product.description,
+            "hs_code": field("hs_code"),
+            "net_mass_kg": field("net_mass_kg"),
+            "scientific_name": field("scientific_name"),
+            "commodities": summary.commodities,
+            "countries_of_production": summary.countries,
+        },
+        "geolocation": plots_feature_collection(plots),
+        "risk_assessment": {
+            "risk_level": summary.risk_level,
+            "simplified_due_diligence": summary.simplified_due_diligence,
+            "factors": summary.risk_factors,
+        },
+        "validation": {
+            "is_compliant": is_compliant,
+            "results": validation,
+        },
+        "submittable": is_compliant && summary.risk_level != RiskLevel::High,
+        "declaration": "By submitting this due diligence statement the operator confirms that due diligence in accordance with Regulation (EU) 2023/1115 was carried out and that no or only a negligible risk was found that the relevant products do not comply with Article 3, point (a) or (b), of that Regulation.",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compliance::{ComplianceRule, ComplianceValidator, RuleFormat};
+    use uuid::Uuid;
+
+    fn plot(country: &str, geometry: Value, area: f64, evidence: Option<&str>) -> OriginPlot {
+        OriginPlot {
+            id: Uuid::new_v4(),
+            product_id: "COFFEE-1".to_string(),
+            plot_reference: None,
+            country_code: country.to_string(),
+            commodity: "coffee".to_string(),
+            geometry,
+            area_hectares: area,
+            production_start: None,
+            production_end: None,
+            deforestation_free_evidence: evidence.map(str::to_string),
+            created_at: Utc::now(),
+        }
+    }
+
+    fn product(custom_fields: Value) -> Product {
+        Product {
+            id: "COFFEE-1".to_string(),
+            name: "Green coffee".to_string(),
+            description: String::new(),
+            origin_location: "Huila".to_string(),
+            category: "coffee".to_string(),
+            tags: vec![],
+            certifications: vec![],
+            media_hashes: vec![],
+            custom_fields,
+            owner_address: "GOWNER".to_string(),
+            is_active: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            created_by: "GOWNER".to_string(),
+            updated_by: "GOWNER".to_string(),
+            organization_id: None,
+        }
+    }
+
+    /// The error checks of the built-in `eudr_due_diligence` rule, run the
+    /// way continuous evaluation runs them
+    fn statement(product: &Product, plots: &[OriginPlot]) -> Value {
+        let rule = ComplianceRule::parse(
+            r#"
+rule_id: eudr_due_diligence
+compliance_type: eudr
+description: d
+checks:
+  - {type: constraint, field: eudr.plot_count, op: gte, value: 1, message: geolocation}
+  - {type: constraint, field: eudr.plots_missing_polygon, op: eq, value: 0, message: polygon}
+  - {type: constraint, field: eudr.plots_needing_evidence, op: eq, value: 0, message: evidence}
+  - {type: required, field: custom_fields.hs_code, message: hs_code}
+  - {type: required, field: custom_fields.net_mass_kg, message: net_mass}
+"#,
+            RuleFormat::Yaml,
+        )
+        .unwrap();
+        let mut data = serde_json::to_value(product).unwrap();
+        data["eudr"] = serde_json::to_value(summarize(plots)).unwrap();
+        let result = ComplianceValidator::validate(&rule, &data, &[]);
+        build_statement(product, plots, DdsActivity::Import, &[result], Utc::now())
+    }
+
+    fn violations(statement: &Value) -> Vec<&str> {
+        statement["validation"]["results"][0]["violations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(Value::as_str)
+            .collect()
+    }
+
+    /// ~1 km x ~1 km square at the equator
+    fn square() -> Value {
+        json!({"type": "Polygon", "coordinates": [[
+            [0.0, 0.0], [0.008983, 0.0], [0.008983, 0.009043], [0.0, 0.009043], [0.0, 0.0]
+        ]]})
+    }
+
+    #[test]
+    fn test_polygon_area_and_validation() {
+        let area = validate_geometry(&square()).unwrap().unwrap();
+        assert!((area - 100.0).abs() < 1.0, "got {} ha", area);
+
+        let point = json!({"type": "Point", "coordinates": [-75.7, 4.5]});
+        assert_eq!(validate_geometry(&point).unwrap(), None);
+
+        let open = json!({"type": "Polygon", "coordinates": [[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]]});
+        assert!(validate_geometry(&open).unwrap_err().contains("closed"));
+        let outside = json!({"type": "Point", "coordinates": [200.0, 0.0]});
+        assert!(validate_geometry(&outside).is_err());
+        assert!(validate_geometry(&json!({"type": "LineString", "coordinates": []})).is_err());
+    }
+
+    #[test]
+    fn test_country_risk() {
+        assert_eq!(country_risk("de"), RiskLevel::Low);
+        assert_eq!(country_risk("BR"), RiskLevel::Standard);
+        assert_eq!(country_risk("RU"), RiskLevel::High);
+    }
+
+    #[test]
+    fn test_summary_risk_classification() {
+        let low = summarize(&[plot("ES", square(), 100.0, None)]);
+        assert_eq!(low.risk_level, RiskLevel::Low);
+        assert!(low.simplified_due_diligence);
+
+        let evidenced = summarize(&[plot("CO", square(), 100.0, Some("sat-report-17"))]);
+        assert_eq!(evidenced.risk_level, RiskLevel::Standard);
+        assert_eq!(evidenced.plots_needing_evidence, 0);
+
+        let point = json!({"type": "Point", "coordinates": [-75.7, 4.5]});
+        let escalated = summarize(&[plot("CO", point, 12.0, None)]);
+        assert_eq!(escalated.risk_level, RiskLevel::High);
+        assert_eq!(escalated.plots_missing_polygon, 1);
+        assert_eq!(escalated.plots_needing_evidence, 1);
+
+        assert_eq!(summarize(&[]).risk_level, RiskLevel::High);
+    }
+
+    #[test]
+    fn test_statement_without_geolocation_is_not_submittable() {
+        let complete = product(json!({"hs_code": "090111", "net_mass_kg": 1200}));
+        let statement = statement(&complete, &[]);
+        assert_eq!(statement["submittable"], false);
+        assert_eq!(statement["risk_assessment"]["risk_level"], "high");
+        assert_eq!(violations(&statement), ["geolocation"]);
+        assert_eq!(statement["geolocation"]["features"], json!([]));
+    }
+
+    #[test]
+    fn test_production_after_cutoff_needs_evidence() {
+        let mut cleared = plot("ES", square(), 100.0, None);
+        cleared.production_start = NaiveDate::from_ymd_opt(2021, 6, 1);
+        let summary = summarize(std::slice::from_ref(&cleared));
+        assert_eq!(summary.plots_after_cutoff, 1);
+        assert_eq!(summary.plots_needing_evidence, 1);
+        assert_eq!(summary.risk_level, RiskLevel::High);
+        assert!(!summary.simplified_due_diligence);
+
+        let complete = product(json!({"hs_code": "090111", "net_mass_kg": 1200}));
+        let statement = statement(&complete, std::slice::from_ref(&cleared));
+        assert_eq!(statement["submittable"], false);
+        assert_eq!(violations(&statement), ["evidence"]);
+
+        cleared.deforestation_free_evidence = Some("sat-report-21".to_string());
+        assert_eq!(summarize(&[cleared]).plots_needing_evidence, 0);
+
+        let mut established = plot("ES", square(), 100.0, None);
+        established.production_start = NaiveDate::from_ymd_opt(2020, 12, 31);
+        let summary = summarize(&[established]);
+        assert_eq!(summary.plots_after_cutoff, 0);
+        assert_eq!(summary.risk_level, RiskLevel::Low);
+    }
+
+    #[test]
+    fn test_incomplete_statement_is_not_submittable() {
+        let plots = [plot("ES", square(), 100.0, None)];
+        let incomplete = statement(&product(json!({"hs_code": "090111"})), &plots);
+        assert_eq!(incomplete["submittable"], false);
+        assert_eq!(incomplete["validation"]["is_compliant"], false);
+        assert_eq!(violations(&incomplete), ["net_mass"]);
+        assert_eq!(incomplete["product"]["net_mass_kg"], Value::Null);
+
+        let complete = statement(
+            &product(json!({"hs_code": "090111", "net_mass_kg": 1200})),
+            &plots,
+        );
+        assert_eq!(complete["submittable"], true);
+    }
+}
@@ -0,0 +1,209 @@
+use chrono::Utc;
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::compliance::ValidationResult;
+use crate::error::AppError;
+use crate::models::eudr::{
+    DdsActivity, LineageEdge, NewOriginPlot, OriginPlot, ProductOriginPlots,
+};
+use crate::models::Product;
+use crate::services::eudr::{self, COMMODITIES, POINT_MAX_AREA_HA};
+use crate::services::LineageService;
+use crate::validation::validate_string;
+
+/// Stated and computed polygon areas may differ by this fraction
+const AREA_TOLERANCE: f64 = 0.2;
+
+/// Origin plots and EU Deforestation Regulation due-diligence statements
+pub struct EudrService {
+    pool: PgPool,
+    lineage: LineageService,
+}
+
+impl EudrService {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            lineage: LineageService::new(pool.clone()),
+            pool,
+        }
+    }
+
+    // ── Plots ─────────────────────────────────────────────────────────────────
+
+    pub async fn add_plot(
+        &self,
+        product_id: &str,
+        plot: NewOriginPlot,
+    ) -> Result<OriginPlot, AppError> {
+        let country_code = plot.country_code.trim().to_ascii_uppercase();
+        if country_code.len() != 2 || !country_code.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(AppError::Validation(
+                "country_code must be an ISO 3166-1 alpha-2 code".to_string(),
+            ));
+        }
+        let commodity = plot.commodity.trim().to_lowercase();
+        if !COMMODITIES.contains(&commodity.as_str()) {
+            return Err(AppError::Validation(format!(
+                "commodity must be one of: {}",
+                COMMODITIES.join(", ")
+            )));
+        }
+        if let Some(reference) = &plot.plot_reference {
+            validate_string("plot_reference", reference, 100)?;
+        }
+        if let (Some(start), Some(end)) = (plot.production_start, plot.production_end) {
+            if start > end {
+                return Err(AppError::Validation(
+                    "production_start must not be after production_end".to_string(),
+                ));
+            }
+        }
+        let area_hectares = plot_area(&plot.geometry, plot.area_hectares)?;
+
+        let exists =
+            sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM products WHERE id = $1)")
+                .bind(product_id)
+                .fetch_one(&self.pool)
+                .await?;
+        if !exists {
+            return Err(AppError::NotFound(format!(
+                "Product {} not found",
+                product_id
+            )));
+        }
+
+        let stored = sqlx::query_as::<_, OriginPlot>(
+            r#"
+            INSERT INTO product_origin_plots (
+                id, product_id, plot_reference, country_code, commodity, geometry,
+                area_hectares, production_start, production_end, deforestation_free_evidence
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(product_id)
+        .bind(&plot.plot_reference)
+        .bind(&country_code)
+        .bind(&commodity)
+        .bind(&plot.geometry)
+        .bind(area_hectares)
+        .bind(plot.production_start)
+        .bind(plot.production_end)
+        .bind(&plot.deforestation_free_evidence)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(stored)
+    }
+
+    pub async fn delete_plot(&self, product_id: &str, plot_id: Uuid) -> Result<(), AppError> {
+        let result =
+            sqlx::query("DELETE FROM product_origin_plots WHERE id = $1 AND product_id = $2")
+                .bind(plot_id)
+                .bind(product_id)
+                .execute(&self.pool)
+                .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!(
+                "Origin plot {} not found on product {}",
+                plot_id, product_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// The product's own plots plus every plot of its upstream products
+    pub async fn product_plots(&self, product_id: &str) -> Result<ProductOriginPlots, AppError> {
+        let ancestors = self.lineage.ancestors(product_id).await?;
+        let mut product_ids = ancestors.clone();
+        product_ids.push(product_id.to_string());
+
+        let plots = sqlx::query_as::<_, OriginPlot>(
+            r#"
+            SELECT * FROM product_origin_plots
+            WHERE product_id = ANY($1)
+            ORDER BY (product_id = $2) DESC, product_id, created_at
+            "#,
+        )
+        .bind(&product_ids)
+        .bind(product_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(ProductOriginPlots {
+            product_id: product_id.to_string(),
+            plots,
+            ancestors,
+        })
+    }
+
+    /// `eudr.*` block of the product record seen by compliance rules
+    pub async fn summary(&self, product_id: &str) -> Result<Value, AppError> {
+        let plots = self.product_plots(product_id).await?.plots;
+        serde_json::to_value(eudr::summarize(&plots))
+            .map_err(|e| AppError::Internal(format!("Failed to serialize EUDR summary: {}", e)))
+    }
+
+    /// Upstream lineage edges of a product
+    pub async fn lineage(&self, product_id: &str) -> Result<Vec<LineageEdge>, AppError> {
+        self.lineage.ancestry(product_id).await
+    }
+
+    // ── Statement ─────────────────────────────────────────────────────────────
+
+    /// Builds the due-diligence statement from the product, its (inherited)
+    /// plots and the results of the `eudr` compliance rules
+    pub async fn statement(
+        &self,
+        product_id: &str,
+        activity: DdsActivity,
+        validation: &[ValidationResult],
+    ) -> Result<Value, AppError> {
+        let product = sqlx::query_as::<_, Product>("SELECT * FROM products WHERE id = $1")
+            .bind(product_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Product {} not found", product_id)))?;
+        let plots = self.product_plots(product_id).await?;
+
+        let mut statement =
+            eudr::build_statement(&product, &plots.plots, activity, validation, Utc::now());
+        statement["lineage"] = serde_json::json!({ "ancestors": plots.ancestors });
+        Ok(statement)
+    }
+}
+
+/// Area of a plot in hectares. Polygons are measured; a stated area must agree
+/// with the measurement. Points need a stated area of at most 4 ha.
+fn plot_area(geometry: &Value, stated: Option<f64>) -> Result<f64, AppError> {
+    let measured = eudr::validate_geometry(geometry)
+        .map_err(|e| AppError::Validation(format!("Invalid plot geometry: {}", e)))?;
+    if let Some(stated) = stated {
+        if !stated.is_finite() || stated <= 0.0 {
+            return Err(AppError::Validation(
+                "area_hectares must be positive".to_string(),
+            ));
+        }
+    }
+
+    match (measured, stated) {
+        (Some(measured), Some(stated)) if (stated - measured).abs() > measured * AREA_TOLERANCE => {
+            Err(AppError::Validation(format!(
+                "area_hectares {} differs from the polygon area {:.2} ha",
+                stated, measured
+            )))
+        }
+        (Some(measured), _) => Ok(stated.unwrap_or(measured)),
+        (None, Some(stated)) if stated > POINT_MAX_AREA_HA => Err(AppError::Validation(format!(
+            "Plots over {} ha must be described by a polygon",
+            POINT_MAX_AREA_HA
+        ))),
+        (None, Some(stated)) => Ok(stated),
+        (None, None) => Err(AppError::Validation(
+            "area_hectares is required when the geometry is a point".to_string(),
+        )),
+    }
+}
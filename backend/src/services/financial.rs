@@ -26,6 +26,22 @@ pub struct Invoice {
     pub due_date: String,
 }
 
+/// One line of an itemized invoice, in cents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceLine {
+    pub description: String,
+    pub quantity: i64,
+    pub unit_price_cents: i64,
+    pub amount_cents: i64,
+}
+
+/// Formats cents as a decimal amount, e.g. `4900` as `"49.00"`
+pub fn format_cents(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    let cents = cents.unsigned_abs();
+    format!("{}{}.{:02}", sign, cents / 100, cents % 100)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FinancingRequest {
     pub id: String,
@@ -77,13 +93,52 @@ impl FinancialService {
         user_id: &str,
         amount: &str,
         due_date: &str,
+    ) -> Result<Invoice, String> {
+        self.insert_invoice(user_id, amount, due_date, None, None).await
+    }
+
+    /// Creates a draft invoice from line items; the amount is their total
+    pub async fn create_itemized_invoice(
+        &self,
+        user_id: &str,
+        due_date: &str,
+        description: &str,
+        lines: &[InvoiceLine],
+    ) -> Result<Invoice, String> {
+        if lines.is_empty() {
+            return Err("An itemized invoice needs at least one line".to_string());
+        }
+        let total: i64 = lines.iter().map(|line| line.amount_cents).sum();
+        let line_items = serde_json::to_value(lines).map_err(|e| e.to_string())?;
+        self.insert_invoice(
+            user_id,
+            &format_cents(total),
+            due_date,
+            Some(description),
+            Some(line_items),
+        )
+        .await
+    }
+
+    async fn insert_invoice(
+        &self,
+        user_id: &str,
+        amount: &str,
+        due_date: &str,
+        description: Option<&str>,
+        line_items: Option<serde_json::Value>,
     ) -> Result<Invoice, String> {
         let id = Uuid::new_v4().to_string();
-        let invoice_number = format!("INV-{}", chrono::Utc::now().timestamp());
+        // Several invoices may be created in the same second, e.g. monthly usage invoices
+        let invoice_number = format!(
+            "INV-{}-{}",
+            chrono::Utc::now().timestamp(),
+            &id[..8]
+        );
 
         let result = sqlx::query_as::<_, Invoice>(
-            "INSERT INTO invoices (id, user_id, invoice_number, amount, currency, status, due_date) 
-             VALUES ($1, $2, $3, $4, 'USD', 'draft', $5) 
+            "INSERT INTO invoices (id, user_id, invoice_number, amount, currency, status, due_date, description, line_items) 
+             VALUES ($1, $2, $3, $4, 'USD', 'draft', $5, $6, $7) 
              RETURNING id, user_id, invoice_number, amount, currency, status, due_date"
         )
         .bind(&id)
@@ -91,6 +146,8 @@ impl FinancialService {
         .bind(&invoice_number)
         .bind(amount)
         .bind(due_date)
+        .bind(description)
+        .bind(line_items)
         .fetch_one(&self.pool)
         .await
         .map_err(|e| e.to_string())?;
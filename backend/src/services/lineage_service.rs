@@ -0,0 +1,124 @@
+use serde_json::Value;
+use sqlx::PgPool;
+
+use crate::error::AppError;
+use crate::models::eudr::LineageEdge;
+use crate::models::TrackingEvent;
+use crate::services::epcis;
+
+/// Upstream hops followed when resolving a product's ancestry
+const MAX_LINEAGE_DEPTH: i32 = 20;
+
+/// Records which products were made or relabelled from which, so origin
+/// data can follow goods through processing and transfers.
+///
+/// Edges come from tracking events that name their inputs, either natively in
+/// `metadata.input_products` or as EPCIS `metadata.epcis.input_epcs`.
+pub struct LineageService {
+    pool: PgPool,
+}
+
+impl LineageService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Stores an edge from every input named by the event to its product
+    pub async fn record_from_event(&self, event: &TrackingEvent) -> Result<usize, AppError> {
+        let mut parents = string_list(event.metadata.get("input_products"));
+        for epc in string_list(event.metadata.pointer("/epcis/input_epcs")) {
+            match self.resolve_epc(&epc).await? {
+                Some(product_id) => parents.push(product_id),
+                None => {
+                    tracing::debug!(event_id = event.id, epc = %epc, "Lineage input EPC matches no product")
+                }
+            }
+        }
+        parents.retain(|p| p != &event.product_id);
+        parents.sort();
+        parents.dedup();
+
+        let mut recorded = 0;
+        for parent in parents {
+            let result = sqlx::query(
+                r#"
+                INSERT INTO product_lineage (parent_product_id, child_product_id, event_id, relation)
+                SELECT $1, $2, $3, $4
+                WHERE EXISTS (SELECT 1 FROM products WHERE id = $1)
+                ON CONFLICT DO NOTHING
+                "#,
+            )
+            .bind(&parent)
+            .bind(&event.product_id)
+            .bind(event.id)
+            .bind(event.event_type.to_lowercase())
+            .execute(&self.pool)
+            .await?;
+            recorded += result.rows_affected() as usize;
+        }
+        Ok(recorded)
+    }
+
+    /// Every edge upstream of a product, nearest first
+    pub async fn ancestry(&self, product_id: &str) -> Result<Vec<LineageEdge>, AppError> {
+        let edges = sqlx::query_as::<_, LineageEdge>(
+            r#"
+            WITH RECURSIVE upstream AS (
+                SELECT l.*, 1 AS depth FROM product_lineage l
+                WHERE l.child_product_id = $1
+                UNION
+                SELECT l.*, u.depth + 1 FROM product_lineage l
+                JOIN upstream u ON l.child_product_id = u.parent_product_id
+                WHERE u.depth < $2
+            )
+            SELECT DISTINCT ON (parent_product_id, child_product_id, event_id)
+                parent_product_id, child_product_id, event_id, relation, created_at
+            FROM upstream
+            ORDER BY parent_product_id, child_product_id, event_id, depth
+            "#,
+        )
+        .bind(product_id)
+        .bind(MAX_LINEAGE_DEPTH)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(edges)
+    }
+
+    /// Distinct upstream product ids, excluding the product itself
+    pub async fn ancestors(&self, product_id: &str) -> Result<Vec<String>, AppError> {
+        let mut ancestors: Vec<String> = self
+            .ancestry(product_id)
+            .await?
+            .into_iter()
+            .map(|e| e.parent_product_id)
+            .filter(|p| p != product_id)
+            .collect();
+        ancestors.sort();
+        ancestors.dedup();
+        Ok(ancestors)
+    }
+
+    async fn resolve_epc(&self, epc: &str) -> Result<Option<String>, AppError> {
+        let found = sqlx::query_scalar::<_, String>(
+            "SELECT id FROM products WHERE id = $1 OR custom_fields->>'epc' = $2 LIMIT 1",
+        )
+        .bind(epcis::product_id_from_epc(epc))
+        .bind(epc)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(found)
+    }
+}
+
+fn string_list(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
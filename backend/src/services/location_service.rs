@@ -0,0 +1,460 @@
+use chrono::Utc;
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::models::location::{
+    BoundingBoxEventsQuery, CreateKnownLocationRequest, EventLocation, KnownLocation,
+    LocatedEvent, NearbyEventsQuery, ResolvedBy, ResolvedLocation,
+};
+use crate::models::TrackingEvent;
+use crate::utils::geo::{self, BoundingBox, Coordinates, DEFAULT_GEOHASH_PRECISION};
+use crate::validation::{validate_gln, validate_unlocode};
+
+const MAX_QUERY_RESULTS: i64 = 1_000;
+
+/// Resolves free-text locations to coordinates and answers spatial event queries
+pub struct LocationService {
+    pool: PgPool,
+}
+
+impl LocationService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // ── Geocoding table ───────────────────────────────────────────────────────
+
+    /// Add a known facility to the geocoding table
+    pub async fn create_known_location(
+        &self,
+        req: &CreateKnownLocationRequest,
+    ) -> Result<KnownLocation, AppError> {
+        let point = Coordinates::new(req.latitude, req.longitude)
+            .ok_or_else(|| AppError::Validation("Coordinates are out of range".to_string()))?;
+        let unlocode = req.unlocode.as_deref().map(str::to_ascii_uppercase);
+        if let Some(code) = &unlocode {
+            validate_unlocode(code)?;
+        }
+        if let Some(gln) = &req.gln {
+            validate_gln(gln)?;
+        }
+
+        let location = sqlx::query_as::<_, KnownLocation>(
+            r#"
+            INSERT INTO known_locations (
+                id, name, normalized_name, latitude, longitude, geohash,
+                unlocode, gln, country_code, created_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $10)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(req.name.trim())
+        .bind(normalize_name(&req.name))
+        .bind(point.latitude)
+        .bind(point.longitude)
+        .bind(geo::geohash_encode(point, DEFAULT_GEOHASH_PRECISION))
+        .bind(&unlocode)
+        .bind(&req.gln)
+        .bind(req.country_code.as_deref().map(str::to_ascii_uppercase))
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => AppError::AlreadyExists(
+                "A known location with this name, UN/LOCODE or GLN already exists".to_string(),
+            ),
+            other => AppError::Database(other),
+        })?;
+
+        Ok(location)
+    }
+
+    /// Search the geocoding table by name
+    pub async fn list_known_locations(
+        &self,
+        search: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<KnownLocation>, AppError> {
+        let pattern = search.map(|s| format!("%{}%", normalize_name(s)));
+        let locations = sqlx::query_as::<_, KnownLocation>(
+            r#"
+            SELECT * FROM known_locations
+            WHERE ($1::TEXT IS NULL OR normalized_name LIKE $1)
+            ORDER BY name
+            LIMIT $2
+            "#,
+        )
+        .bind(pattern)
+        .bind(limit.clamp(1, MAX_QUERY_RESULTS))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(locations)
+    }
+
+    /// Resolve a location string plus optional metadata.
+    /// Order: explicit coordinates, UN/LOCODE or GLN identifiers, then known facility names.
+    pub async fn resolve(
+        &self,
+        location: &str,
+        metadata: &Value,
+    ) -> Result<Option<ResolvedLocation>, AppError> {
+        let unlocode = metadata
+            .get("unlocode")
+            .and_then(|v| v.as_str())
+            .map(str::to_ascii_uppercase)
+            .or_else(|| {
+                let candidate = location.trim().to_ascii_uppercase();
+                validate_unlocode(&candidate).ok().map(|_| candidate)
+            });
+        let gln = metadata
+            .get("gln")
+            .and_then(|v| v.as_str())
+            .filter(|g| validate_gln(g).is_ok())
+            .map(str::to_string);
+
+        if let Some(point) =
+            geo::coordinates_from_metadata(metadata).or_else(|| geo::parse_coordinates(location))
+        {
+            return Ok(Some(resolved(point, ResolvedBy::Coordinates, None, unlocode, gln)));
+        }
+
+        if let Some(code) = &unlocode {
+            if let Some(known) = self.find_known("unlocode", code).await? {
+                return Ok(Some(from_known(known, ResolvedBy::Unlocode)));
+            }
+        }
+        if let Some(gln) = &gln {
+            if let Some(known) = self.find_known("gln", gln).await? {
+                return Ok(Some(from_known(known, ResolvedBy::Gln)));
+            }
+        }
+        if let Some(known) = self.find_known("normalized_name", &normalize_name(location)).await? {
+            return Ok(Some(from_known(known, ResolvedBy::Name)));
+        }
+
+        Ok(None)
+    }
+
+    // ── Events and products ───────────────────────────────────────────────────
+
+    /// Resolve and store the position of a tracking event
+    pub async fn locate_event(
+        &self,
+        event: &TrackingEvent,
+    ) -> Result<Option<EventLocation>, AppError> {
+        let Some(resolved) = self.resolve(&event.location, &event.metadata).await? else {
+            return Ok(None);
+        };
+
+        let row = sqlx::query_as::<_, EventLocation>(
+            r#"
+            INSERT INTO event_locations (
+                tracking_event_id, known_location_id, latitude, longitude,
+                geohash, unlocode, gln, resolved_by
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (tracking_event_id) DO UPDATE SET
+                known_location_id = EXCLUDED.known_location_id,
+                latitude = EXCLUDED.latitude,
+                longitude = EXCLUDED.longitude,
+                geohash = EXCLUDED.geohash,
+                unlocode = EXCLUDED.unlocode,
+                gln = EXCLUDED.gln,
+                resolved_by = EXCLUDED.resolved_by
+            RETURNING *
+            "#,
+        )
+        .bind(event.id)
+        .bind(resolved.known_location_id)
+        .bind(resolved.latitude)
+        .bind(resolved.longitude)
+        .bind(&resolved.geohash)
+        .bind(&resolved.unlocode)
+        .bind(&resolved.gln)
+        .bind(resolved.resolved_by.as_str())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Some(row))
+    }
+
+    /// Resolve and store the origin of a product
+    pub async fn locate_product(
+        &self,
+        product_id: &str,
+        origin_location: &str,
+        custom_fields: &Value,
+    ) -> Result<Option<ResolvedLocation>, AppError> {
+        let Some(resolved) = self.resolve(origin_location, custom_fields).await? else {
+            return Ok(None);
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO product_locations (
+                product_id, known_location_id, latitude, longitude,
+                geohash, unlocode, gln, resolved_by
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (product_id) DO UPDATE SET
+                known_location_id = EXCLUDED.known_location_id,
+                latitude = EXCLUDED.latitude,
+                longitude = EXCLUDED.longitude,
+                geohash = EXCLUDED.geohash,
+                unlocode = EXCLUDED.unlocode,
+                gln = EXCLUDED.gln,
+                resolved_by = EXCLUDED.resolved_by
+            "#,
+        )
+        .bind(product_id)
+        .bind(resolved.known_location_id)
+        .bind(resolved.latitude)
+        .bind(resolved.longitude)
+        .bind(&resolved.geohash)
+        .bind(&resolved.unlocode)
+        .bind(&resolved.gln)
+        .bind(resolved.resolved_by.as_str())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(Some(resolved))
+    }
+
+    /// Stored position of a tracking event
+    pub async fn get_event_location(
+        &self,
+        tracking_event_id: i64,
+    ) -> Result<Option<EventLocation>, AppError> {
+        let row = sqlx::query_as::<_, EventLocation>(
+            "SELECT * FROM event_locations WHERE tracking_event_id = $1",
+        )
+        .bind(tracking_event_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    /// Located events of a product in chronological order
+    pub async fn product_path(&self, product_id: &str) -> Result<Vec<Coordinates>, AppError> {
+        let rows: Vec<(f64, f64)> = sqlx::query_as(
+            r#"
+            SELECT l.latitude, l.longitude
+            FROM tracking_events e
+            JOIN event_locations l ON l.tracking_event_id = e.id
+            WHERE e.product_id = $1
+            ORDER BY e.timestamp, e.id
+            "#,
+        )
+        .bind(product_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(lat, lon)| Coordinates::new(lat, lon))
+            .collect())
+    }
+
+    /// Distance travelled by a product, up to and including `until_event_id` when given.
+    /// Returns `None` when fewer than two events are located.
+    pub async fn product_distance_km(
+        &self,
+        product_id: &str,
+        until_event_id: Option<i64>,
+    ) -> Result<Option<f64>, AppError> {
+        let rows: Vec<(i64, f64, f64)> = sqlx::query_as(
+            r#"
+            SELECT e.id, l.latitude, l.longitude
+            FROM tracking_events e
+            JOIN event_locations l ON l.tracking_event_id = e.id
+            WHERE e.product_id = $1
+            ORDER BY e.timestamp, e.id
+            "#,
+        )
+        .bind(product_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut path = Vec::with_capacity(rows.len());
+        for (id, lat, lon) in rows {
+            if let Some(point) = Coordinates::new(lat, lon) {
+                path.push(point);
+            }
+            if Some(id) == until_event_id {
+                // Only the leg that ends at this event
+                let start = path.len().saturating_sub(2);
+                path.drain(..start);
+                break;
+            }
+        }
+
+        Ok((path.len() >= 2).then(|| geo::path_distance_km(&path)))
+    }
+
+    // ── Spatial queries ───────────────────────────────────────────────────────
+
+    /// Events within `radius_km` of a point, nearest first
+    pub async fn events_within_radius(
+        &self,
+        query: &NearbyEventsQuery,
+    ) -> Result<Vec<LocatedEvent>, AppError> {
+        let center = Coordinates::new(query.latitude, query.longitude)
+            .ok_or_else(|| AppError::Validation("Coordinates are out of range".to_string()))?;
+        if !(query.radius_km > 0.0 && query.radius_km <= 20_000.0) {
+            return Err(AppError::Validation(
+                "radius_km must be between 0 and 20000".to_string(),
+            ));
+        }
+
+        // Box prefilter in SQL, exact great-circle filter here
+        let bbox = BoundingBox::around(center, query.radius_km);
+        let limit = query.limit.unwrap_or(100).clamp(1, MAX_QUERY_RESULTS);
+        let candidates = self
+            .events_in_box(&bbox, query.product_id.as_deref(), MAX_QUERY_RESULTS * 10)
+            .await?;
+
+        let mut events: Vec<LocatedEvent> = candidates
+            .into_iter()
+            .filter_map(|mut e| {
+                let point = Coordinates::new(e.latitude, e.longitude)?;
+                let distance = geo::haversine_km(center, point);
+                (distance <= query.radius_km).then(|| {
+                    e.distance_km = Some(distance);
+                    e
+                })
+            })
+            .collect();
+        events.sort_by(|a, b| {
+            a.distance_km
+                .partial_cmp(&b.distance_km)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        events.truncate(limit as usize);
+
+        Ok(events)
+    }
+
+    /// Events inside a bounding box, newest first
+    pub async fn events_in_bounding_box(
+        &self,
+        query: &BoundingBoxEventsQuery,
+    ) -> Result<Vec<LocatedEvent>, AppError> {
+        let bbox = BoundingBox {
+            min_latitude: query.min_latitude,
+            min_longitude: query.min_longitude,
+            max_latitude: query.max_latitude,
+            max_longitude: query.max_longitude,
+        };
+        if !bbox.is_valid() {
+            return Err(AppError::Validation("Invalid bounding box".to_string()));
+        }
+
+        let limit = query.limit.unwrap_or(100).clamp(1, MAX_QUERY_RESULTS);
+        self.events_in_box(&bbox, query.product_id.as_deref(), limit).await
+    }
+
+    async fn events_in_box(
+        &self,
+        bbox: &BoundingBox,
+        product_id: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<LocatedEvent>, AppError> {
+        let rows: Vec<(i64, f64, f64, String)> = sqlx::query_as(
+            r#"
+            SELECT l.tracking_event_id, l.latitude, l.longitude, l.geohash
+            FROM event_locations l
+            JOIN tracking_events e ON e.id = l.tracking_event_id
+            WHERE l.latitude BETWEEN $1 AND $2
+              AND l.longitude BETWEEN $3 AND $4
+              AND ($5::TEXT IS NULL OR e.product_id = $5)
+            ORDER BY e.timestamp DESC
+            LIMIT $6
+            "#,
+        )
+        .bind(bbox.min_latitude)
+        .bind(bbox.max_latitude)
+        .bind(bbox.min_longitude)
+        .bind(bbox.max_longitude)
+        .bind(product_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let ids: Vec<i64> = rows.iter().map(|r| r.0).collect();
+        let events = sqlx::query_as::<_, TrackingEvent>(
+            "SELECT * FROM tracking_events WHERE id = ANY($1)",
+        )
+        .bind(&ids)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut by_id: std::collections::HashMap<i64, TrackingEvent> =
+            events.into_iter().map(|e| (e.id, e)).collect();
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(id, latitude, longitude, geohash)| {
+                Some(LocatedEvent {
+                    event: by_id.remove(&id)?,
+                    latitude,
+                    longitude,
+                    geohash,
+                    distance_km: None,
+                })
+            })
+            .collect())
+    }
+
+    async fn find_known(&self, column: &str, value: &str) -> Result<Option<KnownLocation>, AppError> {
+        // `column` is always one of our own constants, never user input
+        let sql = format!("SELECT * FROM known_locations WHERE {} = $1 LIMIT 1", column);
+        let location = sqlx::query_as::<_, KnownLocation>(&sql)
+            .bind(value)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(location)
+    }
+}
+
+fn normalize_name(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn resolved(
+    point: Coordinates,
+    resolved_by: ResolvedBy,
+    known_location_id: Option<Uuid>,
+    unlocode: Option<String>,
+    gln: Option<String>,
+) -> ResolvedLocation {
+    ResolvedLocation {
+        latitude: point.latitude,
+        longitude: point.longitude,
+        geohash: geo::geohash_encode(point, DEFAULT_GEOHASH_PRECISION),
+        unlocode,
+        gln,
+        known_location_id,
+        resolved_by,
+    }
+}
+
+fn from_known(known: KnownLocation, resolved_by: ResolvedBy) -> ResolvedLocation {
+    ResolvedLocation {
+        latitude: known.latitude,
+        longitude: known.longitude,
+        geohash: known.geohash,
+        unlocode: known.unlocode,
+        gln: known.gln,
+        known_location_id: Some(known.id),
+        resolved_by,
+    }
+}
@@ -0,0 +1,245 @@
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::models::{MfaEnrollment, MfaStatus, User};
+use crate::services::totp;
+use crate::utils::crypto::{decrypt_secret, encrypt_secret};
+
+/// Shown in authenticator apps next to the account
+const TOTP_ISSUER: &str = "ChainLogistics";
+
+/// TOTP enrollment and verification, and the recovery codes that stand in for
+/// a lost authenticator. Secrets are stored encrypted and recovery codes hashed.
+pub struct MfaService {
+    pool: PgPool,
+    encryption_key: String,
+}
+
+impl MfaService {
+    pub fn new(pool: PgPool, encryption_key: String) -> Self {
+        Self {
+            pool,
+            encryption_key,
+        }
+    }
+
+    pub async fn status(&self, user: &User) -> Result<MfaStatus, AppError> {
+        let enabled_at = sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+            "SELECT enabled_at FROM user_mfa WHERE user_id = $1",
+        )
+        .bind(user.id)
+        .fetch_optional(&self.pool)
+        .await?
+        .flatten();
+        let recovery_codes_remaining = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM user_recovery_codes WHERE user_id = $1 AND used_at IS NULL",
+        )
+        .bind(user.id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(MfaStatus {
+            enabled: enabled_at.is_some(),
+            enabled_at,
+            required: user.role.requires_mfa(),
+            recovery_codes_remaining,
+        })
+    }
+
+    pub async fn is_enabled(&self, user_id: Uuid) -> Result<bool, AppError> {
+        let enabled = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS (SELECT 1 FROM user_mfa WHERE user_id = $1 AND enabled_at IS NOT NULL)",
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(enabled)
+    }
+
+    /// Starts enrollment with a new secret. Nothing is enforced until the
+    /// secret is confirmed with `activate`; enrolling again replaces a
+    /// pending secret.
+    pub async fn enroll(&self, user: &User) -> Result<MfaEnrollment, AppError> {
+        if self.is_enabled(user.id).await? {
+            return Err(AppError::AlreadyExists(
+                "Two-factor authentication is already enabled".to_string(),
+            ));
+        }
+
+        let secret = totp::generate_secret();
+        sqlx::query(
+            r#"
+            INSERT INTO user_mfa (user_id, totp_secret)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE
+            SET totp_secret = EXCLUDED.totp_secret, last_used_step = NULL, created_at = NOW()
+            WHERE user_mfa.enabled_at IS NULL
+            "#,
+        )
+        .bind(user.id)
+        .bind(encrypt_secret(&secret, &self.encryption_key)?)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(MfaEnrollment {
+            otpauth_uri: totp::otpauth_uri(TOTP_ISSUER, &user.email, &secret),
+            secret,
+        })
+    }
+
+    /// Confirms a pending enrollment with a code from the authenticator and
+    /// turns two-factor authentication on. Returns the user's recovery codes;
+    /// they are not shown again.
+    pub async fn activate(&self, user_id: Uuid, code: &str) -> Result<Vec<String>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let secret = sqlx::query_scalar::<_, String>(
+            "SELECT totp_secret FROM user_mfa WHERE user_id = $1 AND enabled_at IS NULL FOR UPDATE",
+        )
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound("No pending two-factor enrollment; enroll first".to_string())
+        })?;
+
+        let step = self
+            .check_totp(&secret, code, None)?
+            .ok_or_else(|| AppError::Validation("Invalid two-factor code".to_string()))?;
+        sqlx::query(
+            "UPDATE user_mfa SET enabled_at = NOW(), last_used_step = $2 WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .bind(step)
+        .execute(&mut *tx)
+        .await?;
+        let codes = self.replace_recovery_codes(&mut tx, user_id).await?;
+        tx.commit().await?;
+        Ok(codes)
+    }
+
+    /// Checks a TOTP code or, failing that, a recovery code. An accepted TOTP
+    /// step and a used recovery code are both spent.
+    pub async fn verify(
+        &self,
+        user_id: Uuid,
+        totp_code: Option<&str>,
+        recovery_code: Option<&str>,
+    ) -> Result<bool, AppError> {
+        if let Some(code) = totp_code {
+            let mut tx = self.pool.begin().await?;
+            let row = sqlx::query_as::<_, (String, Option<i64>)>(
+                r#"
+                SELECT totp_secret, last_used_step FROM user_mfa
+                WHERE user_id = $1 AND enabled_at IS NOT NULL
+                FOR UPDATE
+                "#,
+            )
+            .bind(user_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let Some((secret, last_used_step)) = row else {
+                return Ok(false);
+            };
+            if let Some(step) = self.check_totp(&secret, code, last_used_step)? {
+                sqlx::query("UPDATE user_mfa SET last_used_step = $2 WHERE user_id = $1")
+                    .bind(user_id)
+                    .bind(step)
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await?;
+                return Ok(true);
+            }
+        }
+
+        if let Some(code) = recovery_code.and_then(totp::normalize_recovery_code) {
+            let used = sqlx::query(
+                r#"
+                UPDATE user_recovery_codes SET used_at = NOW()
+                WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+                "#,
+            )
+            .bind(user_id)
+            .bind(hash_recovery_code(&code))
+            .execute(&self.pool)
+            .await?;
+            if used.rows_affected() > 0 {
+                tracing::info!(%user_id, "Recovery code used");
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Replaces the user's recovery codes with a fresh set
+    pub async fn regenerate_recovery_codes(&self, user_id: Uuid) -> Result<Vec<String>, AppError> {
+        if !self.is_enabled(user_id).await? {
+            return Err(AppError::BusinessRule(
+                "Two-factor authentication is not enabled".to_string(),
+            ));
+        }
+        let mut tx = self.pool.begin().await?;
+        let codes = self.replace_recovery_codes(&mut tx, user_id).await?;
+        tx.commit().await?;
+        Ok(codes)
+    }
+
+    /// Turns two-factor authentication off, except for roles that require it
+    pub async fn disable(&self, user: &User) -> Result<(), AppError> {
+        if user.role.requires_mfa() {
+            return Err(AppError::Forbidden(
+                "Your role requires two-factor authentication".to_string(),
+            ));
+        }
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM user_recovery_codes WHERE user_id = $1")
+            .bind(user.id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM user_mfa WHERE user_id = $1")
+            .bind(user.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    fn check_totp(
+        &self,
+        encrypted_secret: &str,
+        code: &str,
+        last_used_step: Option<i64>,
+    ) -> Result<Option<i64>, AppError> {
+        let secret = decrypt_secret(encrypted_secret, &self.encryption_key)?;
+        totp::verify(&secret, code, Utc::now().timestamp(), last_used_step)
+            .map_err(AppError::Internal)
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        conn: &mut PgConnection,
+        user_id: Uuid,
+    ) -> Result<Vec<String>, AppError> {
+        sqlx::query("DELETE FROM user_recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await?;
+        let codes = totp::generate_recovery_codes();
+        for code in &codes {
+            sqlx::query("INSERT INTO user_recovery_codes (user_id, code_hash) VALUES ($1, $2)")
+                .bind(user_id)
+                .bind(hash_recovery_code(code))
+                .execute(&mut *conn)
+                .await?;
+        }
+        Ok(codes)
+    }
+}
+
+/// Recovery codes carry about 50 random bits and are single use, so a plain
+/// SHA-256 is enough
+fn hash_recovery_code(code: &str) -> String {
+    hex::encode(Sha256::digest(code.as_bytes()))
+}
@@ -0,0 +1,410 @@
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::models::organization::{
+    OrgRole, Organization, OrganizationAddress, OrganizationMember, OrganizationMembership,
+};
+use crate::models::User;
+use crate::services::wallet_auth_service::VerifiedAddress;
+
+/// Personal organizations are `user-<user id>`; nobody else may take a slug
+/// starting with this
+const PERSONAL_SLUG_PREFIX: &str = "user-";
+
+/// Organizations, their members and the Stellar addresses they operate.
+///
+/// A product belongs to the organization holding its `owner_address`, so
+/// adding or removing an address moves the products it owns with it. Only
+/// addresses whose holder signed a wallet challenge can be added.
+pub struct OrganizationService {
+    pool: PgPool,
+}
+
+impl OrganizationService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // ── Organizations ─────────────────────────────────────────────────────────
+
+    /// Creates an organization with `owner` as its first owner
+    pub async fn create(
+        &self,
+        name: &str,
+        slug: Option<&str>,
+        owner: Uuid,
+    ) -> Result<Organization, AppError> {
+        let slug = match slug {
+            Some(slug) => slug.to_string(),
+            None => slugify(name),
+        };
+        if slug.is_empty() || slug.len() > 64 {
+            return Err(AppError::Validation(
+                "slug must be 1-64 lowercase letters, digits or dashes".to_string(),
+            ));
+        }
+        if slugify(&slug) != slug {
+            return Err(AppError::Validation(
+                "slug may only contain lowercase letters, digits and single dashes".to_string(),
+            ));
+        }
+        if slug.starts_with(PERSONAL_SLUG_PREFIX) {
+            return Err(AppError::Validation(format!(
+                "slugs starting with '{}' are reserved for personal organizations",
+                PERSONAL_SLUG_PREFIX
+            )));
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let organization = Self::insert(&mut tx, name.trim(), &slug, owner, false).await?;
+        tx.commit().await?;
+        Ok(organization)
+    }
+
+    /// The organization a user registering on their own acts for, created in
+    /// the transaction that creates the user. Their Stellar address joins it
+    /// once they sign in with their wallet.
+    pub async fn create_personal(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+    ) -> Result<Organization, AppError> {
+        Self::insert(conn, "Personal", &personal_slug(user_id), user_id, true).await
+    }
+
+    async fn insert(
+        conn: &mut PgConnection,
+        name: &str,
+        slug: &str,
+        owner: Uuid,
+        is_personal: bool,
+    ) -> Result<Organization, AppError> {
+        let organization = sqlx::query_as::<_, Organization>(
+            "INSERT INTO organizations (name, slug, is_personal) VALUES ($1, $2, $3) RETURNING *",
+        )
+        .bind(name)
+        .bind(slug)
+        .bind(is_personal)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                AppError::AlreadyExists(format!("Organization slug '{}' is taken", slug))
+            }
+            other => AppError::Database(other),
+        })?;
+        sqlx::query(
+            "INSERT INTO organization_members (organization_id, user_id, role) VALUES ($1, $2, 'owner')",
+        )
+        .bind(organization.id)
+        .bind(owner)
+        .execute(&mut *conn)
+        .await?;
+        Ok(organization)
+    }
+
+    /// Registers the user's wallet-verified address with their personal
+    /// organization unless some organization holds it already
+    pub async fn ensure_personal_address(
+        &self,
+        user: &User,
+        address: &VerifiedAddress,
+    ) -> Result<(), AppError> {
+        let organization_id = sqlx::query_scalar::<_, Uuid>(
+            "SELECT id FROM organizations WHERE slug = $1 AND is_personal",
+        )
+        .bind(personal_slug(user.id))
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(organization_id) = organization_id else {
+            return Ok(());
+        };
+        match self
+            .add_address(organization_id, address, Some("personal"))
+            .await
+        {
+            Ok(_) | Err(AppError::AlreadyExists(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<Organization, AppError> {
+        sqlx::query_as::<_, Organization>("SELECT * FROM organizations WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Organization {} not found", id)))
+    }
+
+    pub async fn list_for_user(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<OrganizationMembership>, AppError> {
+        let organizations = sqlx::query_as::<_, OrganizationMembership>(
+            r#"
+            SELECT o.*, m.role
+            FROM organizations o
+            JOIN organization_members m ON m.organization_id = o.id
+            WHERE m.user_id = $1
+            ORDER BY m.created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(organizations)
+    }
+
+    // ── Membership ────────────────────────────────────────────────────────────
+
+    pub async fn membership(
+        &self,
+        organization_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<OrgRole>, AppError> {
+        let role = sqlx::query_scalar::<_, OrgRole>(
+            "SELECT role FROM organization_members WHERE organization_id = $1 AND user_id = $2",
+        )
+        .bind(organization_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(role)
+    }
+
+    /// The organization a session acts for when it does not pick one: the
+    /// first the user joined
+    pub async fn default_membership(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<(Uuid, OrgRole)>, AppError> {
+        let membership = sqlx::query_as::<_, (Uuid, OrgRole)>(
+            r#"
+            SELECT organization_id, role FROM organization_members
+            WHERE user_id = $1
+            ORDER BY created_at, organization_id
+            LIMIT 1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(membership)
+    }
+
+    pub async fn members(
+        &self,
+        organization_id: Uuid,
+    ) -> Result<Vec<OrganizationMember>, AppError> {
+        let members = sqlx::query_as::<_, OrganizationMember>(
+            "SELECT * FROM organization_members WHERE organization_id = $1 ORDER BY created_at",
+        )
+        .bind(organization_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(members)
+    }
+
+    pub async fn add_member(
+        &self,
+        organization_id: Uuid,
+        user_id: Uuid,
+        role: OrgRole,
+    ) -> Result<OrganizationMember, AppError> {
+        sqlx::query_as::<_, OrganizationMember>(
+            r#"
+            INSERT INTO organization_members (organization_id, user_id, role)
+            VALUES ($1, $2, $3)
+            RETURNING *
+            "#,
+        )
+        .bind(organization_id)
+        .bind(user_id)
+        .bind(role)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                AppError::AlreadyExists("User is already a member of this organization".to_string())
+            }
+            sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+                AppError::NotFound(format!("User {} not found", user_id))
+            }
+            other => AppError::Database(other),
+        })
+    }
+
+    pub async fn update_member(
+        &self,
+        organization_id: Uuid,
+        user_id: Uuid,
+        role: OrgRole,
+    ) -> Result<OrganizationMember, AppError> {
+        let mut tx = self.pool.begin().await?;
+        if role != OrgRole::Owner {
+            Self::ensure_not_last_owner(&mut tx, organization_id, user_id).await?;
+        }
+        let member = sqlx::query_as::<_, OrganizationMember>(
+            r#"
+            UPDATE organization_members SET role = $3
+            WHERE organization_id = $1 AND user_id = $2
+            RETURNING *
+            "#,
+        )
+        .bind(organization_id)
+        .bind(user_id)
+        .bind(role)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("User {} is not a member", user_id)))?;
+        tx.commit().await?;
+        Ok(member)
+    }
+
+    pub async fn remove_member(
+        &self,
+        organization_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        Self::ensure_not_last_owner(&mut tx, organization_id, user_id).await?;
+        let result = sqlx::query(
+            "DELETE FROM organization_members WHERE organization_id = $1 AND user_id = $2",
+        )
+        .bind(organization_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!(
+                "User {} is not a member",
+                user_id
+            )));
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Refuses to let `user_id` stop being an owner if they are the last one.
+    /// The owners stay locked until the transaction ends, so two owners
+    /// cannot demote each other at the same time.
+    async fn ensure_not_last_owner(
+        conn: &mut PgConnection,
+        organization_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<(), AppError> {
+        let owners = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT user_id FROM organization_members
+            WHERE organization_id = $1 AND role = 'owner'
+            FOR UPDATE
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        if owners == [user_id] {
+            return Err(AppError::BusinessRule(
+                "An organization must keep at least one owner".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    // ── Addresses ─────────────────────────────────────────────────────────────
+
+    pub async fn addresses(
+        &self,
+        organization_id: Uuid,
+    ) -> Result<Vec<OrganizationAddress>, AppError> {
+        let addresses = sqlx::query_as::<_, OrganizationAddress>(
+            "SELECT * FROM organization_addresses WHERE organization_id = $1 ORDER BY created_at",
+        )
+        .bind(organization_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(addresses)
+    }
+
+    /// Registers an address whose control was just proven with the
+    /// organization and moves the products it owns along with it
+    pub async fn add_address(
+        &self,
+        organization_id: Uuid,
+        address: &VerifiedAddress,
+        label: Option<&str>,
+    ) -> Result<OrganizationAddress, AppError> {
+        let stellar_address = address.as_str();
+        let mut tx = self.pool.begin().await?;
+        let address = sqlx::query_as::<_, OrganizationAddress>(
+            r#"
+            INSERT INTO organization_addresses (stellar_address, organization_id, label)
+            VALUES ($1, $2, $3)
+            RETURNING *
+            "#,
+        )
+        .bind(stellar_address)
+        .bind(organization_id)
+        .bind(label)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => AppError::AlreadyExists(
+                format!("{} already belongs to an organization", stellar_address),
+            ),
+            other => AppError::Database(other),
+        })?;
+        sqlx::query("UPDATE products SET organization_id = $1 WHERE owner_address = $2")
+            .bind(organization_id)
+            .bind(stellar_address)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(address)
+    }
+
+    /// Releases an address. Products it owns no longer belong to any
+    /// organization until the address is registered again.
+    pub async fn remove_address(
+        &self,
+        organization_id: Uuid,
+        stellar_address: &str,
+    ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(
+            "DELETE FROM organization_addresses WHERE organization_id = $1 AND stellar_address = $2",
+        )
+        .bind(organization_id)
+        .bind(stellar_address)
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!(
+                "{} is not an address of this organization",
+                stellar_address
+            )));
+        }
+        sqlx::query(
+            "UPDATE products SET organization_id = NULL WHERE owner_address = $1 AND organization_id = $2",
+        )
+        .bind(stellar_address)
+        .bind(organization_id)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+fn personal_slug(user_id: Uuid) -> String {
+    format!("{}{}", PERSONAL_SLUG_PREFIX, user_id)
+}
+
+/// Lower-cases `name` and joins its alphanumeric runs with single dashes
+fn slugify(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
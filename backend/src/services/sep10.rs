@@ -0,0 +1,515 @@
+/// Sign-In-With-Stellar challenges, following SEP-10.
+///
+/// The server issues a challenge transaction from its signing account with
+/// sequence number 0, so it can never be submitted, valid for a few minutes.
+/// Its first `manage_data` operation is sourced from the client account and
+/// holds a random nonce; the next names the `web_auth_domain`, and a link to
+/// an organization adds one naming it. The server signs the transaction, the
+/// wallet adds the account's signature and sends the envelope back, and the
+/// server accepts it if the transaction is exactly the one it issued and the
+/// account's key signed it.
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use stellar_xdr::curr::{
+    DataValue, DecoratedSignature, Hash, Limits, ManageDataOp, Memo, MuxedAccount, Operation,
+    OperationBody, Preconditions, ReadXdr, SequenceNumber, SignatureHint, String64, TimeBounds,
+    TimePoint, Transaction, TransactionEnvelope, TransactionExt, TransactionSignaturePayload,
+    TransactionSignaturePayloadTaggedTransaction, TransactionV1Envelope, Uint256, WriteXdr,
+};
+use uuid::Uuid;
+
+/// How long a challenge may be answered
+pub const CHALLENGE_TTL_SECONDS: i64 = 300;
+
+/// Fee per operation, in stroops. Never charged: a challenge cannot be
+/// submitted.
+const BASE_FEE: u32 = 100;
+/// A signed challenge is well under a kilobyte; anything much larger is not one
+const MAX_ENVELOPE_LEN: usize = 4096;
+/// Nesting the envelope of a challenge needs, with room to spare
+const MAX_ENVELOPE_DEPTH: u32 = 32;
+
+const WEB_AUTH_DOMAIN_KEY: &str = "web_auth_domain";
+const ORGANIZATION_KEY: &str = "organization";
+
+// ── Challenge ─────────────────────────────────────────────────────────────────
+
+/// What answering a challenge does. A challenge can only be redeemed for the
+/// purpose it was issued for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChallengePurpose {
+    SignIn,
+    /// Prove control of an address an organization wants to register
+    LinkToOrganization(Uuid),
+}
+
+impl ChallengePurpose {
+    /// Stored with the challenge
+    pub fn as_string(&self) -> String {
+        match self {
+            ChallengePurpose::SignIn => "sign_in".to_string(),
+            ChallengePurpose::LinkToOrganization(id) => format!("organization:{}", id),
+        }
+    }
+}
+
+/// Issues challenges signed with the server's key and checks the answers
+pub struct ChallengeIssuer {
+    signing_key: SigningKey,
+    network_passphrase: String,
+    home_domain: String,
+    web_auth_domain: String,
+}
+
+impl ChallengeIssuer {
+    pub fn new(
+        signing_key: SigningKey,
+        network_passphrase: &str,
+        home_domain: &str,
+        web_auth_domain: &str,
+    ) -> Self {
+        Self {
+            signing_key,
+            network_passphrase: network_passphrase.to_string(),
+            home_domain: home_domain.to_string(),
+            web_auth_domain: web_auth_domain.to_string(),
+        }
+    }
+
+    /// The `G...` address challenges come from; stellar.toml publishes it as
+    /// `SIGNING_KEY`
+    pub fn account(&self) -> String {
+        stellar_strkey::ed25519::PublicKey(self.signing_key.verifying_key().to_bytes()).to_string()
+    }
+
+    pub fn network_passphrase(&self) -> &str {
+        &self.network_passphrase
+    }
+
+    /// Builds and signs the challenge for `address`, returned as a base64
+    /// transaction envelope
+    pub fn issue(
+        &self,
+        purpose: ChallengePurpose,
+        address: &str,
+        nonce: &str,
+        issued_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<String, String> {
+        let client = muxed_account(&account_key(address)?);
+        let server = muxed_account(&self.signing_key.verifying_key());
+
+        let mut operations = vec![
+            manage_data(client, &format!("{} auth", self.home_domain), nonce)?,
+            manage_data(server.clone(), WEB_AUTH_DOMAIN_KEY, &self.web_auth_domain)?,
+        ];
+        if let ChallengePurpose::LinkToOrganization(id) = purpose {
+            operations.push(manage_data(
+                server.clone(),
+                ORGANIZATION_KEY,
+                &id.to_string(),
+            )?);
+        }
+
+        let tx = Transaction {
+            source_account: server,
+            fee: BASE_FEE * operations.len() as u32,
+            seq_num: SequenceNumber(0),
+            cond: Preconditions::Time(TimeBounds {
+                min_time: TimePoint(issued_at.timestamp().max(0) as u64),
+                max_time: TimePoint(expires_at.timestamp().max(0) as u64),
+            }),
+            memo: Memo::None,
+            operations: operations
+                .try_into()
+                .map_err(|_| "too many operations".to_string())?,
+            ext: TransactionExt::V0,
+        };
+        let signature = self.signing_key.sign(&self.hash(&tx)?);
+        let envelope = TransactionEnvelope::Tx(TransactionV1Envelope {
+            tx,
+            signatures: vec![decorated(&self.signing_key.verifying_key(), &signature)]
+                .try_into()
+                .map_err(|_| "too many signatures".to_string())?,
+        });
+        envelope
+            .to_xdr_base64(Limits::none())
+            .map_err(|e| format!("could not encode the challenge: {}", e))
+    }
+
+    /// Checks `signed` is the `issued` challenge carrying the client account's
+    /// signature. Besides the server's, no other signature is allowed.
+    pub fn verify(&self, issued: &str, signed: &str) -> Result<(), String> {
+        let issued = decode(issued)?;
+        let signed = decode(signed)?;
+        if signed.tx != issued.tx {
+            return Err("transaction is not the issued challenge".to_string());
+        }
+        let (client, _) = client_and_nonce(&issued.tx)?;
+        let client = account_key(&client)?;
+        let server = match &issued.tx.source_account {
+            MuxedAccount::Ed25519(key) => VerifyingKey::from_bytes(&key.0)
+                .map_err(|_| "challenge source is not a valid key".to_string())?,
+            MuxedAccount::MuxedEd25519(_) => {
+                return Err("challenge source is not the server account".to_string())
+            }
+        };
+
+        let hash = self.hash(&signed.tx)?;
+        let mut client_signed = false;
+        for signature in signed.signatures.iter() {
+            let Ok(bytes) = Signature::from_slice(signature.signature.0.as_slice()) else {
+                return Err("signature must be 64 bytes".to_string());
+            };
+            let by = |key: &VerifyingKey| {
+                signature.hint == hint(key) && key.verify(&hash, &bytes).is_ok()
+            };
+            if by(&client) {
+                client_signed = true;
+            } else if !by(&server) {
+                return Err("challenge carries a signature by another key".to_string());
+            }
+        }
+        if !client_signed {
+            return Err("challenge is not signed by the account".to_string());
+        }
+        Ok(())
+    }
+
+    /// What the wallet and the server sign: the transaction, bound to the
+    /// network
+    fn hash(&self, tx: &Transaction) -> Result<[u8; 32], String> {
+        let payload = TransactionSignaturePayload {
+            network_id: Hash(Sha256::digest(self.network_passphrase.as_bytes()).into()),
+            tagged_transaction: TransactionSignaturePayloadTaggedTransaction::Tx(tx.clone()),
+        };
+        let bytes = payload
+            .to_xdr(Limits::none())
+            .map_err(|e| format!("could not encode the transaction: {}", e))?;
+        Ok(Sha256::digest(bytes).into())
+    }
+}
+
+/// The client account and nonce a signed challenge claims to answer, used to
+/// find the issued challenge. Nothing is verified yet.
+pub fn read_challenge(signed: &str) -> Result<(String, String), String> {
+    client_and_nonce(&decode(signed)?.tx)
+}
+
+/// A random nonce: 48 bytes, base64 encoded to the 64 characters a
+/// `manage_data` value holds
+pub fn generate_nonce() -> String {
+    let mut bytes = [0u8; 48];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    general_purpose::STANDARD.encode(bytes)
+}
+
+// ── Keys ──────────────────────────────────────────────────────────────────────
+
+/// Reads a secret seed (`S...`) into the key challenges are signed with
+pub fn signing_key(seed: &str) -> Result<SigningKey, String> {
+    let seed = stellar_strkey::ed25519::PrivateKey::from_string(seed.trim())
+        .map_err(|_| "not a Stellar secret seed".to_string())?;
+    Ok(SigningKey::from_bytes(&seed.0))
+}
+
+/// Decodes a Stellar account address (`G...`) into its ed25519 public key
+pub fn account_key(address: &str) -> Result<VerifyingKey, String> {
+    let key = stellar_strkey::ed25519::PublicKey::from_string(address)
+        .map_err(|_| format!("{} is not a Stellar account address", address))?;
+    VerifyingKey::from_bytes(&key.0)
+        .map_err(|_| format!("{} does not encode a valid ed25519 key", address))
+}
+
+fn muxed_account(key: &VerifyingKey) -> MuxedAccount {
+    MuxedAccount::Ed25519(Uint256(key.to_bytes()))
+}
+
+/// Signatures are tagged with the last four bytes of the signer's key
+fn hint(key: &VerifyingKey) -> SignatureHint {
+    let bytes = key.to_bytes();
+    SignatureHint([bytes[28], bytes[29], bytes[30], bytes[31]])
+}
+
+fn decorated(key: &VerifyingKey, signature: &Signature) -> DecoratedSignature {
+    DecoratedSignature {
+        hint: hint(key),
+        signature: stellar_xdr::curr::Signature(
+            signature
+                .to_bytes()
+                .to_vec()
+                .try_into()
+                .expect("ed25519 signatures are 64 bytes"),
+        ),
+    }
+}
+
+// ── Transactions ──────────────────────────────────────────────────────────────
+
+fn manage_data(source: MuxedAccount, name: &str, value: &str) -> Result<Operation, String> {
+    let data_name = String64(
+        name.try_into()
+            .map_err(|_| format!("data name '{}' is over 64 bytes", name))?,
+    );
+    let data_value = DataValue(
+        value
+            .as_bytes()
+            .try_into()
+            .map_err(|_| format!("value of '{}' is over 64 bytes", name))?,
+    );
+    Ok(Operation {
+        source_account: Some(source),
+        body: OperationBody::ManageData(ManageDataOp {
+            data_name,
+            data_value: Some(data_value),
+        }),
+    })
+}
+
+fn decode(envelope: &str) -> Result<TransactionV1Envelope, String> {
+    if envelope.len() > MAX_ENVELOPE_LEN {
+        return Err("transaction is too large to be a challenge".to_string());
+    }
+    let limits = Limits {
+        depth: MAX_ENVELOPE_DEPTH,
+        len: MAX_ENVELOPE_LEN,
+    };
+    match TransactionEnvelope::from_xdr_base64(envelope.trim(), limits) {
+        Ok(TransactionEnvelope::Tx(envelope)) => Ok(envelope),
+        Ok(_) => Err("challenge must be a v1 transaction envelope".to_string()),
+        Err(_) => Err("transaction must be a base64 XDR envelope".to_string()),
+    }
+}
+
+/// The account the first operation is sourced from and the nonce it holds
+fn client_and_nonce(tx: &Transaction) -> Result<(String, String), String> {
+    let first = tx
+        .operations
+        .first()
+        .ok_or_else(|| "challenge has no operations".to_string())?;
+    let (Some(MuxedAccount::Ed25519(client)), OperationBody::ManageData(op)) =
+        (&first.source_account, &first.body)
+    else {
+        return Err("first operation must be manage_data from the client account".to_string());
+    };
+    let nonce = op
+        .data_value
+        .as_ref()
+        .and_then(|value| String::from_utf8(value.0.to_vec()).ok())
+        .ok_or_else(|| "challenge nonce is missing".to_string())?;
+    Ok((
+        stellar_strkey::ed25519::PublicKey(client.0).to_string(),
+        nonce,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const TESTNET: &str = "Test SDF Network ; September 2015";
+
+    fn issuer() -> ChallengeIssuer {
+        ChallengeIssuer::new(
+            SigningKey::from_bytes(&[1u8; 32]),
+            TESTNET,
+            "chainlogistics.io",
+            "api.chainlogistics.io",
+        )
+    }
+
+    fn account() -> (SigningKey, String) {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let address =
+            stellar_strkey::ed25519::PublicKey(key.verifying_key().to_bytes()).to_string();
+        (key, address)
+    }
+
+    fn challenge(purpose: ChallengePurpose, address: &str) -> String {
+        let issued_at = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+        issuer()
+            .issue(
+                purpose,
+                address,
+                &generate_nonce(),
+                issued_at,
+                issued_at + chrono::Duration::seconds(CHALLENGE_TTL_SECONDS),
+            )
+            .unwrap()
+    }
+
+    /// What a wallet does: adds its signature to the envelope
+    fn sign(key: &SigningKey, envelope: &str) -> String {
+        let mut envelope = decode(envelope).unwrap();
+        let signature = key.sign(&issuer().hash(&envelope.tx).unwrap());
+        let mut signatures = envelope.signatures.to_vec();
+        signatures.push(decorated(&key.verifying_key(), &signature));
+        envelope.signatures = signatures.try_into().unwrap();
+        TransactionEnvelope::Tx(envelope)
+            .to_xdr_base64(Limits::none())
+            .unwrap()
+    }
+
+    fn data(op: &Operation) -> (String, String) {
+        let OperationBody::ManageData(op) = &op.body else {
+            panic!("not manage_data");
+        };
+        (
+            op.data_name.0.to_utf8_string().unwrap(),
+            String::from_utf8(op.data_value.as_ref().unwrap().0.to_vec()).unwrap(),
+        )
+    }
+
+    #[test]
+    fn challenge_follows_sep10() {
+        let (_, address) = account();
+        let envelope = decode(&challenge(ChallengePurpose::SignIn, &address)).unwrap();
+        let tx = &envelope.tx;
+
+        assert_eq!(tx.seq_num, SequenceNumber(0));
+        assert_eq!(
+            tx.source_account,
+            muxed_account(&issuer().signing_key.verifying_key())
+        );
+        let Preconditions::Time(bounds) = &tx.cond else {
+            panic!("challenge has no time bounds");
+        };
+        assert_eq!(
+            bounds.max_time.0 - bounds.min_time.0,
+            CHALLENGE_TTL_SECONDS as u64
+        );
+
+        assert_eq!(tx.operations.len(), 2);
+        let (name, nonce) = data(&tx.operations[0]);
+        assert_eq!(name, "chainlogistics.io auth");
+        assert_eq!(nonce.len(), 64);
+        assert_eq!(
+            tx.operations[0].source_account,
+            Some(muxed_account(&account_key(&address).unwrap()))
+        );
+        assert_eq!(
+            data(&tx.operations[1]),
+            (
+                "web_auth_domain".to_string(),
+                "api.chainlogistics.io".to_string()
+            )
+        );
+        assert_eq!(
+            tx.operations[1].source_account,
+            Some(tx.source_account.clone())
+        );
+
+        // Signed by the server only
+        assert_eq!(envelope.signatures.len(), 1);
+        let server = issuer().signing_key.verifying_key();
+        let signature =
+            Signature::from_slice(envelope.signatures[0].signature.0.as_slice()).unwrap();
+        assert!(server
+            .verify(&issuer().hash(tx).unwrap(), &signature)
+            .is_ok());
+        assert_eq!(
+            read_challenge(&challenge(ChallengePurpose::SignIn, &address))
+                .unwrap()
+                .0,
+            address
+        );
+    }
+
+    #[test]
+    fn organization_links_name_the_organization() {
+        let (_, address) = account();
+        let organization = Uuid::nil();
+        let envelope = decode(&challenge(
+            ChallengePurpose::LinkToOrganization(organization),
+            &address,
+        ))
+        .unwrap();
+        assert_eq!(
+            data(&envelope.tx.operations[2]),
+            ("organization".to_string(), organization.to_string())
+        );
+        assert_ne!(
+            ChallengePurpose::SignIn.as_string(),
+            ChallengePurpose::LinkToOrganization(organization).as_string()
+        );
+    }
+
+    #[test]
+    fn accepts_the_accounts_signature() {
+        let (key, address) = account();
+        let issued = challenge(ChallengePurpose::SignIn, &address);
+        let signed = sign(&key, &issued);
+        assert_eq!(issuer().verify(&issued, &signed), Ok(()));
+
+        let (client, nonce) = read_challenge(&signed).unwrap();
+        assert_eq!(client, address);
+        assert_eq!(nonce, data(&decode(&issued).unwrap().tx.operations[0]).1);
+    }
+
+    #[test]
+    fn rejects_other_keys_and_transactions() {
+        let (key, address) = account();
+        let issued = challenge(ChallengePurpose::SignIn, &address);
+
+        // Not signed by the account at all
+        assert!(issuer().verify(&issued, &issued).is_err());
+
+        let other = SigningKey::from_bytes(&[8u8; 32]);
+        assert!(issuer().verify(&issued, &sign(&other, &issued)).is_err());
+        // A stray extra signature is refused too
+        assert!(issuer()
+            .verify(&issued, &sign(&other, &sign(&key, &issued)))
+            .is_err());
+
+        // Another challenge, even one for the same account, does not answer it
+        let another = challenge(ChallengePurpose::SignIn, &address);
+        assert!(issuer().verify(&issued, &sign(&key, &another)).is_err());
+
+        // Signatures commit to the network
+        let mainnet = ChallengeIssuer::new(
+            SigningKey::from_bytes(&[1u8; 32]),
+            "Public Global Stellar Network ; September 2015",
+            "chainlogistics.io",
+            "api.chainlogistics.io",
+        );
+        assert!(mainnet.verify(&issued, &sign(&key, &issued)).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        let (_, address) = account();
+        let issued = challenge(ChallengePurpose::SignIn, &address);
+
+        assert!(account_key("GABC").is_err());
+        // Contract addresses are not accounts
+        assert!(account_key("CA3D5KRYM6CB7OWQ6TWYRR3Z4T7GNZLKERYNZGGA5SOAOPIFY6YQGAXE").is_err());
+        assert!(issuer()
+            .issue(
+                ChallengePurpose::SignIn,
+                "GABC",
+                "n",
+                Utc::now(),
+                Utc::now()
+            )
+            .is_err());
+
+        assert!(read_challenge("not a transaction").is_err());
+        assert!(issuer().verify(&issued, "AAAA").is_err());
+        assert!(issuer()
+            .verify(&issued, &"A".repeat(MAX_ENVELOPE_LEN + 4))
+            .is_err());
+    }
+
+    #[test]
+    fn reads_secret_seeds() {
+        let seed = stellar_strkey::ed25519::PrivateKey([1u8; 32]).to_string();
+        assert_eq!(signing_key(&seed).unwrap().to_bytes(), [1u8; 32]);
+        assert!(signing_key("SABC").is_err());
+        let (_, address) = account();
+        assert!(signing_key(&address).is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::JwtConfig;
+use crate::error::AppError;
+use crate::middleware::auth::Claims;
+use crate::models::{AuthSession, User};
+use crate::services::jwt_keys::JwtKeyring;
+
+/// What a login or refresh hands back to the client
+#[derive(Debug, Clone)]
+pub struct IssuedTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Seconds until `access_token` expires
+    pub expires_in: i64,
+}
+
+/// An access token that checked out, with its session's two-factor state
+#[derive(Debug)]
+pub struct AuthenticatedSession {
+    pub claims: Claims,
+    /// Whether the user has two-factor authentication turned on
+    pub mfa_enabled: bool,
+    /// When the session last proved a second factor
+    pub mfa_verified_at: Option<DateTime<Utc>>,
+}
+
+/// Login sessions, short-lived access tokens and rotating refresh tokens.
+///
+/// Every refresh token is good for one use. Presenting one a second time means
+/// it leaked, so the whole session is revoked.
+pub struct SessionService {
+    pool: PgPool,
+    keys: JwtKeyring,
+    access_ttl: Duration,
+    refresh_ttl: Duration,
+}
+
+impl SessionService {
+    pub fn new(pool: PgPool, keys: JwtKeyring, config: &JwtConfig) -> Self {
+        Self {
+            pool,
+            keys,
+            access_ttl: Duration::seconds(config.access_token_ttl_secs),
+            refresh_ttl: Duration::seconds(config.refresh_token_ttl_secs),
+        }
+    }
+
+    /// Opens a session for a user who just logged in. `mfa_verified` says
+    /// whether the login included a second factor.
+    pub async fn start(
+        &self,
+        user: &User,
+        mfa_verified: bool,
+        user_agent: Option<&str>,
+        ip_address: Option<&str>,
+    ) -> Result<IssuedTokens, AppError> {
+        let expires_at = Utc::now() + self.refresh_ttl;
+        let mut tx = self.pool.begin().await?;
+        // Forget the user's sessions that ended over a week ago
+        sqlx::query(
+            r#"
+            DELETE FROM auth_sessions
+            WHERE user_id = $1
+              AND (expires_at < NOW() - INTERVAL '7 days' OR revoked_at < NOW() - INTERVAL '7 days')
+            "#,
+        )
+        .bind(user.id)
+        .execute(&mut *tx)
+        .await?;
+
+        let session_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO auth_sessions (user_id, user_agent, ip_address, expires_at, mfa_verified_at)
+            VALUES ($1, $2, $3, $4, CASE WHEN $5 THEN NOW() END)
+            RETURNING id
+            "#,
+        )
+        .bind(user.id)
+        .bind(user_agent)
+        .bind(ip_address)
+        .bind(expires_at)
+        .bind(mfa_verified)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let refresh_token = generate_refresh_token();
+        sqlx::query(
+            "INSERT INTO refresh_tokens (token_hash, session_id, expires_at) VALUES ($1, $2, $3)",
+        )
+        .bind(hash_refresh_token(&refresh_token))
+        .bind(session_id)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        let (access_token, expires_in) = self.access_token(user, session_id)?;
+        Ok(IssuedTokens {
+            access_token,
+            refresh_token,
+            expires_in,
+        })
+    }
+
+    /// Trades a refresh token for a new one in the same session and extends
+    /// the session. Returns the session and the new refresh token.
+    pub async fn rotate(&self, refresh_token: &str) -> Result<(AuthSession, String), AppError> {
+        let mut tx = self.pool.begin().await?;
+        let (session_id, used) = sqlx::query_as::<_, (Uuid, bool)>(
+            r#"
+            SELECT session_id, used_at IS NOT NULL FROM refresh_tokens
+            WHERE token_hash = $1 AND expires_at > NOW()
+            FOR UPDATE
+            "#,
+        )
+        .bind(hash_refresh_token(refresh_token))
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+        if used {
+            tracing::warn!(%session_id, "Refresh token reused; revoking session");
+            sqlx::query(
+                "UPDATE auth_sessions SET revoked_at = NOW() WHERE id = $1 AND revoked_at IS NULL",
+            )
+            .bind(session_id)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            return Err(AppError::Unauthorized);
+        }
+
+        let expires_at = Utc::now() + self.refresh_ttl;
+        let session = sqlx::query_as::<_, AuthSession>(
+            r#"
+            UPDATE auth_sessions SET last_used_at = NOW(), expires_at = $2
+            WHERE id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+            RETURNING *
+            "#,
+        )
+        .bind(session_id)
+        .bind(expires_at)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+        sqlx::query("UPDATE refresh_tokens SET used_at = NOW() WHERE token_hash = $1")
+            .bind(hash_refresh_token(refresh_token))
+            .execute(&mut *tx)
+            .await?;
+        let next = generate_refresh_token();
+        sqlx::query(
+            "INSERT INTO refresh_tokens (token_hash, session_id, expires_at) VALUES ($1, $2, $3)",
+        )
+        .bind(hash_refresh_token(&next))
+        .bind(session.id)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok((session, next))
+    }
+
+    /// Signs an access token for `user` in `session_id`. Returns the token and
+    /// its lifetime in seconds.
+    pub fn access_token(&self, user: &User, session_id: Uuid) -> Result<(String, i64), AppError> {
+        let now = Utc::now();
+        let claims = Claims {
+            sub: user.id.to_string(),
+            iat: now.timestamp() as usize,
+            exp: (now + self.access_ttl).timestamp() as usize,
+            role: user.role.clone(),
+            sid: session_id,
+        };
+        let token = self
+            .keys
+            .sign(&claims)
+            .map_err(|_| AppError::Internal("Token issuance failed".to_string()))?;
+        Ok((token, self.access_ttl.num_seconds()))
+    }
+
+    /// Verifies an access token and checks its session is still open
+    pub async fn authenticate(&self, access_token: &str) -> Result<AuthenticatedSession, AppError> {
+        let claims: Claims = self.keys.verify(access_token)?;
+        let user_id = Uuid::parse_str(&claims.sub).map_err(|_| AppError::Unauthorized)?;
+        let (mfa_enabled, mfa_verified_at) = sqlx::query_as::<_, (bool, Option<DateTime<Utc>>)>(
+            r#"
+                SELECT m.enabled_at IS NOT NULL, s.mfa_verified_at
+                FROM auth_sessions s
+                LEFT JOIN user_mfa m ON m.user_id = s.user_id
+                WHERE s.id = $1 AND s.user_id = $2 AND s.revoked_at IS NULL AND s.expires_at > NOW()
+                "#,
+        )
+        .bind(claims.sid)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+        Ok(AuthenticatedSession {
+            claims,
+            mfa_enabled,
+            mfa_verified_at,
+        })
+    }
+
+    /// Records that the session just proved a second factor
+    pub async fn mark_mfa_verified(&self, session_id: Uuid) -> Result<(), AppError> {
+        sqlx::query("UPDATE auth_sessions SET mfa_verified_at = NOW() WHERE id = $1")
+            .bind(session_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// The user's open sessions, most recently used first
+    pub async fn list(&self, user_id: Uuid) -> Result<Vec<AuthSession>, AppError> {
+        let sessions = sqlx::query_as::<_, AuthSession>(
+            r#"
+            SELECT * FROM auth_sessions
+            WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+            ORDER BY last_used_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(sessions)
+    }
+
+    /// Whether the session is neither revoked nor expired
+    pub async fn is_active(&self, session_id: Uuid) -> Result<bool, AppError> {
+        let active = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM auth_sessions
+                           WHERE id = $1 AND revoked_at IS NULL AND expires_at > NOW())
+            "#,
+        )
+        .bind(session_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(active)
+    }
+
+    pub async fn revoke(&self, user_id: Uuid, session_id: Uuid) -> Result<(), AppError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE auth_sessions SET revoked_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(session_id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!(
+                "Session {} not found",
+                session_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Ends every session of the user. Returns how many were open.
+    pub async fn revoke_all(&self, user_id: Uuid) -> Result<u64, AppError> {
+        let result = sqlx::query(
+            "UPDATE auth_sessions SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+/// `rt_` followed by 64 hex characters (256 bits)
+fn generate_refresh_token() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    format!("rt_{}", hex::encode(bytes))
+}
+
+/// Refresh tokens are random with full entropy, so a plain SHA-256 is enough
+fn hash_refresh_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
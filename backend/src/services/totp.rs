@@ -0,0 +1,246 @@
+/// Time-based one-time passwords (RFC 6238) and recovery codes.
+///
+/// Codes are 6 digits over HMAC-SHA1 with 30-second steps, which is what
+/// Google Authenticator, 1Password, Authy and friends expect from an
+/// `otpauth://` URI. One step of clock skew either way is tolerated, and a
+/// step that was already used is refused so an observed code cannot be
+/// replayed.
+use base32::Alphabet;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha1::Sha1;
+
+pub const DIGITS: u32 = 6;
+pub const STEP_SECONDS: i64 = 30;
+/// Steps accepted either side of the current one
+pub const SKEW_STEPS: i64 = 1;
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+const SECRET_BYTES: usize = 20;
+const BASE32: Alphabet = Alphabet::RFC4648 { padding: false };
+/// Recovery codes avoid characters that are easy to misread
+const RECOVERY_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+// ── Secrets ───────────────────────────────────────────────────────────────────
+
+/// A fresh 160-bit secret, base32 encoded for authenticator apps
+pub fn generate_secret() -> String {
+    let bytes: [u8; SECRET_BYTES] = rand::thread_rng().gen();
+    base32::encode(BASE32, &bytes)
+}
+
+/// The provisioning URI authenticator apps read from a QR code
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(issuer),
+        percent_encode(account),
+        secret,
+        percent_encode(issuer),
+        DIGITS,
+        STEP_SECONDS
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+// ── Codes ─────────────────────────────────────────────────────────────────────
+
+/// The HOTP value for `counter` (RFC 4226), as `DIGITS` zero-padded digits
+pub fn hotp(key: &[u8], counter: u64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let truncated = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    format!(
+        "{:0width$}",
+        truncated % 10u32.pow(DIGITS),
+        width = DIGITS as usize
+    )
+}
+
+pub fn step_at(unix_time: i64) -> i64 {
+    unix_time.div_euclid(STEP_SECONDS)
+}
+
+/// Checks `code` against the steps around `unix_time`. Returns the matching
+/// step, which the caller stores so it is refused next time. Steps at or
+/// before `last_used_step` never match.
+pub fn verify(
+    secret: &str,
+    code: &str,
+    unix_time: i64,
+    last_used_step: Option<i64>,
+) -> Result<Option<i64>, String> {
+    let key = base32::decode(BASE32, secret).ok_or_else(|| "invalid TOTP secret".to_string())?;
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(None);
+    }
+
+    let current = step_at(unix_time);
+    let earliest = (current - SKEW_STEPS).max(last_used_step.map_or(0, |last| last + 1));
+    Ok((earliest..=(current + SKEW_STEPS))
+        .find(|step| constant_time_eq(hotp(&key, *step as u64).as_bytes(), code.as_bytes())))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// ── Recovery codes ────────────────────────────────────────────────────────────
+
+/// `RECOVERY_CODE_COUNT` single-use codes shaped `xxxxx-xxxxx`
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let chars: String = (0..10)
+                .map(|_| RECOVERY_ALPHABET[rng.gen_range(0..RECOVERY_ALPHABET.len())] as char)
+                .collect();
+            format!("{}-{}", &chars[..5], &chars[5..])
+        })
+        .collect()
+}
+
+/// Brings a recovery code as typed (any case, with or without the dash or
+/// spaces) to the stored form
+pub fn normalize_recovery_code(code: &str) -> Option<String> {
+    let chars: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    if chars.len() != 10 || !chars.bytes().all(|b| RECOVERY_ALPHABET.contains(&b)) {
+        return None;
+    }
+    Some(format!("{}-{}", &chars[..5], &chars[5..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 6238 appendix B, SHA-1 seed
+    const RFC_KEY: &[u8] = b"12345678901234567890";
+
+    fn rfc_secret() -> String {
+        base32::encode(BASE32, RFC_KEY)
+    }
+
+    #[test]
+    fn matches_rfc_6238_vectors() {
+        // The RFC lists 8 digits; the last 6 are the 6-digit codes
+        for (time, expected) in [
+            (59, "287082"),
+            (1_111_111_109, "081804"),
+            (1_111_111_111, "050471"),
+            (1_234_567_890, "005924"),
+            (2_000_000_000, "279037"),
+            (20_000_000_000, "353130"),
+        ] {
+            assert_eq!(hotp(RFC_KEY, step_at(time) as u64), expected, "t={}", time);
+        }
+    }
+
+    #[test]
+    fn accepts_one_step_of_skew() {
+        let secret = rfc_secret();
+        let now = 1_234_567_890;
+        let code = hotp(RFC_KEY, step_at(now) as u64);
+
+        assert_eq!(verify(&secret, &code, now, None), Ok(Some(step_at(now))));
+        assert!(verify(&secret, &code, now + STEP_SECONDS, None)
+            .unwrap()
+            .is_some());
+        assert!(verify(&secret, &code, now - STEP_SECONDS, None)
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            verify(&secret, &code, now + 2 * STEP_SECONDS, None),
+            Ok(None)
+        );
+        // Spaces as apps often display them
+        assert!(verify(
+            &secret,
+            &format!("{} {}", &code[..3], &code[3..]),
+            now,
+            None
+        )
+        .unwrap()
+        .is_some());
+    }
+
+    #[test]
+    fn refuses_replayed_and_malformed_codes() {
+        let secret = rfc_secret();
+        let now = 1_234_567_890;
+        let code = hotp(RFC_KEY, step_at(now) as u64);
+
+        let step = verify(&secret, &code, now, None).unwrap().unwrap();
+        assert_eq!(verify(&secret, &code, now, Some(step)), Ok(None));
+
+        assert_eq!(verify(&secret, "12345", now, None), Ok(None));
+        assert_eq!(verify(&secret, "abcdef", now, None), Ok(None));
+        assert!(verify("not base32!", &code, now, None).is_err());
+    }
+
+    #[test]
+    fn generated_secrets_round_trip() {
+        let secret = generate_secret();
+        assert_eq!(secret.len(), 32);
+        let key = base32::decode(BASE32, &secret).unwrap();
+        assert_eq!(key.len(), SECRET_BYTES);
+
+        let now = 1_700_000_000;
+        let code = hotp(&key, step_at(now) as u64);
+        assert!(verify(&secret, &code, now, None).unwrap().is_some());
+    }
+
+    #[test]
+    fn provisioning_uri_escapes_labels() {
+        let uri = otpauth_uri("ChainLogistics", "ops@example.com", "JBSWY3DPEHPK3PXP");
+        assert_eq!(
+            uri,
+            "otpauth://totp/ChainLogistics:ops%40example.com?secret=JBSWY3DPEHPK3PXP\
+             &issuer=ChainLogistics&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn recovery_codes_normalize_to_their_stored_form() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        for code in &codes {
+            assert_eq!(
+                normalize_recovery_code(code).as_deref(),
+                Some(code.as_str())
+            );
+            let typed = code.to_uppercase().replace('-', " ");
+            assert_eq!(
+                normalize_recovery_code(&typed).as_deref(),
+                Some(code.as_str())
+            );
+        }
+        assert_eq!(normalize_recovery_code("short"), None);
+        // '0', 'o', '1', 'l' and 'i' never appear
+        assert_eq!(normalize_recovery_code("abcde-0oli1"), None);
+    }
+}
@@ -0,0 +1,242 @@
+/// Mapping engine that projects observations onto a digital twin's `current_state`.
+///
+/// An observation is either a tracking event (serialized as JSON) or an oracle
+/// snapshot. Each `StateFieldMapping` copies the value at `source` into `target`.
+///
+/// Predicted values live under `current_state.predicted`, keyed by the same
+/// target path. When an observation disagrees with a predicted value the
+/// mismatch is reported as a divergence and the observed value still wins.
+use serde_json::{Map, Value};
+
+use crate::models::digital_twin::{StateFieldMapping, TwinSyncMapping};
+use crate::models::TrackingEvent;
+
+/// Key under `current_state` that holds predicted values
+pub const PREDICTED_KEY: &str = "predicted";
+
+/// A mismatch between the predicted and observed value at a state path
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObservedDivergence {
+    pub state_path: String,
+    pub predicted: Value,
+    pub observed: Value,
+}
+
+// ── Public API ────────────────────────────────────────────────────────────────
+
+/// Mapping applied to product twins that were created without one.
+pub fn default_mapping() -> TwinSyncMapping {
+    let field = |source: &str, target: &str| StateFieldMapping {
+        source: source.to_string(),
+        target: target.to_string(),
+        tolerance: None,
+    };
+
+    TwinSyncMapping {
+        event_types: Vec::new(),
+        fields: vec![
+            field("event_type", "status"),
+            field("location", "location"),
+            field("actor_address", "custodian"),
+            field("timestamp", "last_event_at"),
+            field("id", "last_event_id"),
+        ],
+    }
+}
+
+/// Parses a stored mapping, falling back to the default when it is empty or invalid.
+pub fn resolve_mapping(raw: &Value) -> TwinSyncMapping {
+    match serde_json::from_value::<TwinSyncMapping>(raw.clone()) {
+        Ok(mapping) if !mapping.fields.is_empty() => mapping,
+        Ok(mapping) => TwinSyncMapping {
+            event_types: mapping.event_types,
+            fields: default_mapping().fields,
+        },
+        Err(_) => default_mapping(),
+    }
+}
+
+/// Whether events of this type should update a twin using the given mapping.
+pub fn accepts_event(mapping: &TwinSyncMapping, event_type: &str) -> bool {
+    mapping.event_types.is_empty()
+        || mapping
+            .event_types
+            .iter()
+            .any(|t| t.eq_ignore_ascii_case(event_type))
+}
+
+/// Builds the observation document for a tracking event.
+pub fn observation_from_event(event: &TrackingEvent) -> Value {
+    serde_json::to_value(event).unwrap_or(Value::Null)
+}
+
+/// Applies `mapping` to `state`, returning the new state and any divergences
+/// from predicted values. Sources missing from the observation are skipped.
+pub fn apply_mapping(
+    state: &Value,
+    mapping: &TwinSyncMapping,
+    observation: &Value,
+) -> (Value, Vec<ObservedDivergence>) {
+    let mut next = if state.is_object() {
+        state.clone()
+    } else {
+        Value::Object(Map::new())
+    };
+    let mut divergences = Vec::new();
+
+    for field in &mapping.fields {
+        let observed = match get_path(observation, &field.source) {
+            Some(v) if !v.is_null() => v.clone(),
+            _ => continue,
+        };
+
+        let predicted_path = format!("{}.{}", PREDICTED_KEY, field.target);
+        if let Some(predicted) = get_path(state, &predicted_path) {
+            if !values_agree(predicted, &observed, field.tolerance) {
+                divergences.push(ObservedDivergence {
+                    state_path: field.target.clone(),
+                    predicted: predicted.clone(),
+                    observed: observed.clone(),
+                });
+            }
+        }
+
+        set_path(&mut next, &field.target, observed);
+    }
+
+    (next, divergences)
+}
+
+/// Looks up a dotted path (`a.b.c`) in a JSON value.
+pub fn get_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .filter(|s| !s.is_empty())
+        .try_fold(value, |current, segment| current.get(segment))
+}
+
+/// Writes `new_value` at a dotted path, creating intermediate objects as needed.
+pub fn set_path(value: &mut Value, path: &str, new_value: Value) {
+    let segments: Vec<&str> = path.split('.').filter(|s| !s.is_empty()).collect();
+    let Some((last, parents)) = segments.split_last() else {
+        return;
+    };
+
+    let mut current = value;
+    for segment in parents {
+        if !current.is_object() {
+            *current = Value::Object(Map::new());
+        }
+        current = current
+            .as_object_mut()
+            .expect("checked above")
+            .entry(segment.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+
+    if !current.is_object() {
+        *current = Value::Object(Map::new());
+    }
+    if let Some(obj) = current.as_object_mut() {
+        obj.insert(last.to_string(), new_value);
+    }
+}
+
+// ── Internal helpers ──────────────────────────────────────────────────────────
+
+fn values_agree(predicted: &Value, observed: &Value, tolerance: Option<f64>) -> bool {
+    match (predicted.as_f64(), observed.as_f64()) {
+        (Some(p), Some(o)) => (p - o).abs() <= tolerance.unwrap_or(0.0),
+        _ => match (predicted.as_str(), observed.as_str()) {
+            (Some(p), Some(o)) => p.eq_ignore_ascii_case(o),
+            _ => predicted == observed,
+        },
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn mapping(fields: &[(&str, &str, Option<f64>)]) -> TwinSyncMapping {
+        TwinSyncMapping {
+            event_types: Vec::new(),
+            fields: fields
+                .iter()
+                .map(|(source, target, tolerance)| StateFieldMapping {
+                    source: source.to_string(),
+                    target: target.to_string(),
+                    tolerance: *tolerance,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_maps_nested_metadata_into_state() {
+        let m = mapping(&[("metadata.temperature_c", "environment.temperature", None)]);
+        let observation = json!({"metadata": {"temperature_c": 4.5}});
+
+        let (state, divergences) = apply_mapping(&json!({}), &m, &observation);
+
+        assert_eq!(state["environment"]["temperature"], json!(4.5));
+        assert!(divergences.is_empty());
+    }
+
+    #[test]
+    fn test_missing_source_leaves_state_untouched() {
+        let m = mapping(&[("metadata.humidity", "environment.humidity", None)]);
+        let state = json!({"environment": {"humidity": 40}});
+
+        let (next, _) = apply_mapping(&state, &m, &json!({"metadata": {}}));
+
+        assert_eq!(next, state);
+    }
+
+    #[test]
+    fn test_divergence_outside_tolerance() {
+        let m = mapping(&[("metadata.temperature_c", "temperature", Some(1.0))]);
+        let state = json!({"predicted": {"temperature": 4.0}});
+
+        let (_, within) = apply_mapping(&state, &m, &json!({"metadata": {"temperature_c": 4.8}}));
+        assert!(within.is_empty());
+
+        let (next, outside) = apply_mapping(&state, &m, &json!({"metadata": {"temperature_c": 9.0}}));
+        assert_eq!(outside.len(), 1);
+        assert_eq!(outside[0].state_path, "temperature");
+        assert_eq!(next["temperature"], json!(9.0));
+    }
+
+    #[test]
+    fn test_string_divergence_on_location() {
+        let m = mapping(&[("location", "location", None)]);
+        let state = json!({"predicted": {"location": "Rotterdam"}});
+
+        let (_, divergences) = apply_mapping(&state, &m, &json!({"location": "Hamburg"}));
+
+        assert_eq!(divergences.len(), 1);
+        assert_eq!(divergences[0].predicted, json!("Rotterdam"));
+    }
+
+    #[test]
+    fn test_event_type_filter() {
+        let mut m = default_mapping();
+        assert!(accepts_event(&m, "SHIP"));
+
+        m.event_types = vec!["RECEIVE".to_string()];
+        assert!(accepts_event(&m, "receive"));
+        assert!(!accepts_event(&m, "SHIP"));
+    }
+
+    #[test]
+    fn test_resolve_mapping_falls_back_to_default() {
+        let resolved = resolve_mapping(&json!({"event_types": ["SHIP"]}));
+        assert_eq!(resolved.event_types, vec!["SHIP".to_string()]);
+        assert_eq!(resolved.fields.len(), default_mapping().fields.len());
+
+        let invalid = resolve_mapping(&json!("not a mapping"));
+        assert!(invalid.event_types.is_empty());
+    }
+}
@@ -0,0 +1,373 @@
+/// Monthly quotas, usage counters and billing for API keys.
+///
+/// Every request made with an API key is metered in units, the same cost the
+/// rate limiter charges, so an export uses up more of a quota than a simple
+/// read. Counters live in Redis: one per key and month, checked on every
+/// request, and one hash per key and hour broken down by endpoint, which is
+/// periodically moved into Postgres for reports and invoices. Tiers with a
+/// hard quota are refused once it is used up; the others keep working and
+/// the overage is billed.
+use chrono::{DateTime, Datelike, NaiveDateTime, TimeZone, Utc};
+use uuid::Uuid;
+
+use crate::models::ApiKeyTier;
+use crate::services::financial::InvoiceLine;
+
+/// Share of the quota at which the key's organization is warned
+pub const WARN_PERCENT: u64 = 80;
+/// Month counters outlive their month by a few days, for late reports
+pub const MONTH_KEY_TTL_SECS: i64 = 40 * 24 * 60 * 60;
+/// Overage is billed in blocks of this many units
+pub const OVERAGE_BLOCK_UNITS: i64 = 1000;
+
+/// Redis set naming the hourly hashes not yet moved to Postgres
+pub const PENDING_KEY: &str = "usage:pending";
+const HOUR_KEY_PREFIX: &str = "usage:hour:";
+const HOUR_FORMAT: &str = "%Y%m%d%H";
+const REQUESTS_FIELD: &str = "requests:";
+const UNITS_FIELD: &str = "units:";
+
+// ── Plans ─────────────────────────────────────────────────────────────────────
+
+/// What a tier costs and how its quota is enforced
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plan {
+    pub monthly_fee_cents: i64,
+    /// Price of each started block of `OVERAGE_BLOCK_UNITS` over the quota
+    pub overage_cents_per_block: i64,
+    /// Whether requests are refused once the quota is used up
+    pub hard_limit: bool,
+}
+
+pub fn plan(tier: &ApiKeyTier) -> Plan {
+    match tier {
+        ApiKeyTier::Basic => Plan {
+            monthly_fee_cents: 0,
+            overage_cents_per_block: 0,
+            hard_limit: true,
+        },
+        ApiKeyTier::Standard => Plan {
+            monthly_fee_cents: 4_900,
+            overage_cents_per_block: 0,
+            hard_limit: true,
+        },
+        ApiKeyTier::Premium => Plan {
+            monthly_fee_cents: 19_900,
+            overage_cents_per_block: 50,
+            hard_limit: false,
+        },
+        ApiKeyTier::Enterprise => Plan {
+            monthly_fee_cents: 99_900,
+            overage_cents_per_block: 20,
+            hard_limit: false,
+        },
+    }
+}
+
+// ── Quotas ────────────────────────────────────────────────────────────────────
+
+/// Where a key stands against its monthly quota
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaLevel {
+    Ok,
+    /// At or past `WARN_PERCENT`
+    Warning,
+    /// Used up, or a request was refused for going over
+    Exceeded,
+}
+
+impl QuotaLevel {
+    /// Webhook event announcing the level, if it is announced
+    pub fn event_type(&self) -> Option<&'static str> {
+        match self {
+            QuotaLevel::Ok => None,
+            QuotaLevel::Warning => Some("usage.quota_warning"),
+            QuotaLevel::Exceeded => Some("usage.quota_exceeded"),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuotaLevel::Ok => "ok",
+            QuotaLevel::Warning => "warning",
+            QuotaLevel::Exceeded => "exceeded",
+        }
+    }
+}
+
+/// The level for `used` units of `quota`. A refused request means the key is
+/// out of quota even when a few units are left that it could not fit into.
+pub fn quota_level(used: u64, quota: u64, refused: bool) -> QuotaLevel {
+    if refused || used >= quota {
+        QuotaLevel::Exceeded
+    } else if used.saturating_mul(100) >= quota.saturating_mul(WARN_PERCENT) {
+        QuotaLevel::Warning
+    } else {
+        QuotaLevel::Ok
+    }
+}
+
+// ── Counters ──────────────────────────────────────────────────────────────────
+
+/// First instant of the month `at` falls in
+pub fn month_start(at: DateTime<Utc>) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(at.year(), at.month(), 1, 0, 0, 0)
+        .single()
+        .expect("first of the month exists")
+}
+
+/// First instant of the following month
+pub fn next_month_start(at: DateTime<Utc>) -> DateTime<Utc> {
+    let (year, month) = if at.month() == 12 {
+        (at.year() + 1, 1)
+    } else {
+        (at.year(), at.month() + 1)
+    };
+    Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0)
+        .single()
+        .expect("first of the month exists")
+}
+
+/// Units a key has used in the month of `at`
+pub fn month_key(api_key_id: Uuid, at: DateTime<Utc>) -> String {
+    format!("usage:month:{}:{}", api_key_id, at.format("%Y-%m"))
+}
+
+/// Marks that a key's organization has been told about `level` this month
+pub fn notified_key(api_key_id: Uuid, at: DateTime<Utc>, level: QuotaLevel) -> String {
+    format!(
+        "usage:notified:{}:{}:{}",
+        api_key_id,
+        at.format("%Y-%m"),
+        level.as_str()
+    )
+}
+
+/// Per-endpoint counts of a key in the hour of `at`
+pub fn hour_key(api_key_id: Uuid, at: DateTime<Utc>) -> String {
+    format!(
+        "{}{}:{}",
+        HOUR_KEY_PREFIX,
+        api_key_id,
+        at.format(HOUR_FORMAT)
+    )
+}
+
+/// The key and hour an hourly hash counts
+pub fn parse_hour_key(key: &str) -> Option<(Uuid, DateTime<Utc>)> {
+    let (api_key_id, hour) = key.strip_prefix(HOUR_KEY_PREFIX)?.split_once(':')?;
+    let api_key_id = Uuid::parse_str(api_key_id).ok()?;
+    let hour = NaiveDateTime::parse_from_str(&format!("{}00", hour), "%Y%m%d%H%M").ok()?;
+    Some((api_key_id, hour.and_utc()))
+}
+
+/// Hash fields counting requests and units for an endpoint
+pub fn endpoint_fields(method: &str, route: &str) -> (String, String) {
+    (
+        format!("{}{} {}", REQUESTS_FIELD, method, route),
+        format!("{}{} {}", UNITS_FIELD, method, route),
+    )
+}
+
+/// Counts of one endpoint in one hour
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndpointCount {
+    pub method: String,
+    pub route: String,
+    pub requests: i64,
+    pub units: i64,
+}
+
+/// Reads an hourly hash back into per-endpoint counts. Unknown fields are
+/// ignored.
+pub fn parse_counts(fields: &[(String, i64)]) -> Vec<EndpointCount> {
+    let mut counts: Vec<EndpointCount> = Vec::new();
+    for (field, value) in fields {
+        let (endpoint, is_units) = if let Some(endpoint) = field.strip_prefix(REQUESTS_FIELD) {
+            (endpoint, false)
+        } else if let Some(endpoint) = field.strip_prefix(UNITS_FIELD) {
+            (endpoint, true)
+        } else {
+            continue;
+        };
+        let Some((method, route)) = endpoint.split_once(' ') else {
+            continue;
+        };
+        let index = match counts
+            .iter()
+            .position(|c| c.method == method && c.route == route)
+        {
+            Some(index) => index,
+            None => {
+                counts.push(EndpointCount {
+                    method: method.to_string(),
+                    route: route.to_string(),
+                    requests: 0,
+                    units: 0,
+                });
+                counts.len() - 1
+            }
+        };
+        if is_units {
+            counts[index].units += value;
+        } else {
+            counts[index].requests += value;
+        }
+    }
+    counts
+}
+
+// ── Billing ───────────────────────────────────────────────────────────────────
+
+/// A key's usage over a billing period
+#[derive(Debug, Clone)]
+pub struct KeyUsage {
+    pub api_key_id: Uuid,
+    pub name: String,
+    pub tier: ApiKeyTier,
+    pub units: i64,
+}
+
+/// Invoice lines for a period: each key used in it pays its tier's fee, and
+/// keys on soft quotas pay for every started block of units over the quota
+pub fn invoice_lines(usage: &[KeyUsage], period: &str) -> Vec<InvoiceLine> {
+    let mut lines = Vec::new();
+    for key in usage {
+        let plan = plan(&key.tier);
+        if plan.monthly_fee_cents > 0 {
+            lines.push(InvoiceLine {
+                description: format!(
+                    "API access, {:?} tier, key \"{}\", {}",
+                    key.tier, key.name, period
+                ),
+                quantity: 1,
+                unit_price_cents: plan.monthly_fee_cents,
+                amount_cents: plan.monthly_fee_cents,
+            });
+        }
+        let over = key.units - key.tier.monthly_quota() as i64;
+        if over > 0 && plan.overage_cents_per_block > 0 {
+            let blocks = (over + OVERAGE_BLOCK_UNITS - 1) / OVERAGE_BLOCK_UNITS;
+            lines.push(InvoiceLine {
+                description: format!(
+                    "API usage over quota, key \"{}\", {} units, {}",
+                    key.name, over, period
+                ),
+                quantity: blocks,
+                unit_price_cents: plan.overage_cents_per_block,
+                amount_cents: blocks * plan.overage_cents_per_block,
+            });
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warns_then_exceeds() {
+        assert_eq!(quota_level(0, 1000, false), QuotaLevel::Ok);
+        assert_eq!(quota_level(799, 1000, false), QuotaLevel::Ok);
+        assert_eq!(quota_level(800, 1000, false), QuotaLevel::Warning);
+        assert_eq!(quota_level(999, 1000, false), QuotaLevel::Warning);
+        assert_eq!(quota_level(1000, 1000, false), QuotaLevel::Exceeded);
+        // Refused with room left for cheaper requests
+        assert_eq!(quota_level(990, 1000, true), QuotaLevel::Exceeded);
+        assert_eq!(quota_level(u64::MAX, u64::MAX, false), QuotaLevel::Exceeded);
+        assert_eq!(
+            QuotaLevel::Warning.event_type(),
+            Some("usage.quota_warning")
+        );
+        assert_eq!(QuotaLevel::Ok.event_type(), None);
+    }
+
+    #[test]
+    fn hour_keys_round_trip() {
+        let id = Uuid::new_v4();
+        let at = Utc.with_ymd_and_hms(2024, 3, 9, 17, 42, 5).unwrap();
+        let key = hour_key(id, at);
+        assert_eq!(key, format!("usage:hour:{}:2024030917", id));
+        assert_eq!(
+            parse_hour_key(&key),
+            Some((id, Utc.with_ymd_and_hms(2024, 3, 9, 17, 0, 0).unwrap()))
+        );
+        assert_eq!(parse_hour_key("usage:hour:nope:2024030917"), None);
+        assert_eq!(parse_hour_key(&format!("usage:hour:{}:20240399", id)), None);
+        assert_eq!(month_key(id, at), format!("usage:month:{}:2024-03", id));
+    }
+
+    #[test]
+    fn months_roll_over() {
+        let at = Utc.with_ymd_and_hms(2024, 12, 31, 23, 59, 59).unwrap();
+        assert_eq!(
+            month_start(at),
+            Utc.with_ymd_and_hms(2024, 12, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            next_month_start(at),
+            Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn reads_counts_by_endpoint() {
+        let (requests, units) = endpoint_fields("GET", "/api/v1/analytics/export");
+        let (other, _) = endpoint_fields("GET", "/api/v1/products/:id");
+        let counts = parse_counts(&[
+            (requests, 3),
+            (units, 60),
+            (other, 7),
+            ("garbage".to_string(), 1),
+        ]);
+        assert_eq!(
+            counts,
+            vec![
+                EndpointCount {
+                    method: "GET".into(),
+                    route: "/api/v1/analytics/export".into(),
+                    requests: 3,
+                    units: 60,
+                },
+                EndpointCount {
+                    method: "GET".into(),
+                    route: "/api/v1/products/:id".into(),
+                    requests: 7,
+                    units: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn bills_fees_and_started_overage_blocks() {
+        let premium_quota = ApiKeyTier::Premium.monthly_quota() as i64;
+        let usage = vec![
+            KeyUsage {
+                api_key_id: Uuid::new_v4(),
+                name: "free".into(),
+                tier: ApiKeyTier::Basic,
+                units: 5,
+            },
+            KeyUsage {
+                api_key_id: Uuid::new_v4(),
+                name: "erp".into(),
+                tier: ApiKeyTier::Premium,
+                units: premium_quota + 1001,
+            },
+        ];
+        let lines = invoice_lines(&usage, "2024-03");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].amount_cents, 19_900);
+        assert_eq!(lines[1].quantity, 2);
+        assert_eq!(lines[1].amount_cents, 100);
+
+        // Within quota only the fee is due
+        let within = KeyUsage {
+            units: premium_quota,
+            ..usage[1].clone()
+        };
+        assert_eq!(invoice_lines(&[within], "2024-03").len(), 1);
+    }
+}
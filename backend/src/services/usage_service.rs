@@ -0,0 +1,516 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::{FromRow, PgPool};
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::models::{ApiKeyTier, EndpointUsage, HourlyUsage};
+use crate::monitoring::telemetry;
+use crate::services::financial::FinancialService;
+use crate::services::usage_metering::{
+    self, KeyUsage, QuotaLevel, MONTH_KEY_TTL_SECS, PENDING_KEY,
+};
+use crate::services::webhook_service;
+
+/// Counts a request and checks the quota in one step. Keys on a hard quota
+/// are refused, without counting, when the request would take them over.
+/// Returns `{allowed, units used this month}`.
+const METER_SCRIPT: &str = r#"
+local cost = tonumber(ARGV[1])
+local quota = tonumber(ARGV[2])
+local used = tonumber(redis.call('GET', KEYS[1]) or '0')
+if ARGV[3] == '1' and used + cost > quota then
+    return {0, used}
+end
+used = redis.call('INCRBY', KEYS[1], cost)
+redis.call('EXPIRE', KEYS[1], ARGV[6])
+redis.call('HINCRBY', KEYS[2], ARGV[4], 1)
+redis.call('HINCRBY', KEYS[2], ARGV[5], cost)
+redis.call('EXPIRE', KEYS[2], ARGV[7])
+redis.call('SADD', KEYS[3], KEYS[2])
+return {1, used}
+"#;
+
+/// Reads and removes an hourly hash, so counts are moved exactly once
+const TAKE_HOUR_SCRIPT: &str = r#"
+local fields = redis.call('HGETALL', KEYS[1])
+redis.call('DEL', KEYS[1])
+redis.call('SREM', KEYS[2], KEYS[1])
+return fields
+"#;
+
+/// How long to wait on Redis before serving a request unmetered
+const REDIS_TIMEOUT: Duration = Duration::from_millis(250);
+/// Hourly hashes that are never moved to Postgres are dropped after this
+const HOUR_KEY_TTL_SECS: i64 = 7 * 24 * 60 * 60;
+/// Usage invoices are due this long after the month they cover
+const INVOICE_DUE_DAYS: i64 = 30;
+
+/// Set while Redis is unreachable, so the fallback is logged once
+static DEGRADED: AtomicBool = AtomicBool::new(false);
+
+/// Outcome of metering a request
+#[derive(Debug, Clone, Copy)]
+pub struct Metered {
+    pub allowed: bool,
+    /// Units used this month, including the request when it was allowed
+    pub used: u64,
+    pub quota: u64,
+}
+
+#[derive(Debug, FromRow)]
+struct KeyUsageRow {
+    api_key_id: Uuid,
+    name: String,
+    tier: ApiKeyTier,
+    units: i64,
+}
+
+/// Meters API key requests against monthly quotas, keeps the hourly usage
+/// history in Postgres and bills it. Metering fails open: while Redis is
+/// unreachable requests are served and not counted.
+pub struct UsageService {
+    pool: PgPool,
+    redis_client: redis::Client,
+}
+
+impl UsageService {
+    pub fn new(pool: PgPool, redis_client: redis::Client) -> Self {
+        Self { pool, redis_client }
+    }
+
+    // ── Metering ──────────────────────────────────────────────────────────────
+
+    /// Counts a request of `cost` units to `method route` against the key's
+    /// quota. `None` when Redis could not be asked. The key's organization is
+    /// told by webhook the first time in a month the key passes the warning
+    /// level and when it runs out.
+    pub async fn meter(
+        &self,
+        api_key_id: Uuid,
+        tier: &ApiKeyTier,
+        organization_id: Option<Uuid>,
+        method: &str,
+        route: &str,
+        cost: u32,
+    ) -> Option<Metered> {
+        let now = Utc::now();
+        let quota = tier.monthly_quota();
+        let hard_limit = usage_metering::plan(tier).hard_limit;
+        let counted = tokio::time::timeout(
+            REDIS_TIMEOUT,
+            self.count(api_key_id, now, quota, hard_limit, method, route, cost)
+                .instrument(telemetry::redis_span("EVALSHA")),
+        )
+        .await;
+        let (allowed, used) = match counted {
+            Ok(Ok(counted)) => {
+                if DEGRADED.swap(false, Ordering::Relaxed) {
+                    tracing::info!("API usage metering is using Redis again");
+                }
+                counted
+            }
+            failed => {
+                if !DEGRADED.swap(true, Ordering::Relaxed) {
+                    let reason = match failed {
+                        Ok(Err(e)) => e.to_string(),
+                        _ => "timed out".to_string(),
+                    };
+                    tracing::warn!(
+                        "API usage is not metered while Redis is unreachable: {}",
+                        reason
+                    );
+                }
+                return None;
+            }
+        };
+
+        let level = usage_metering::quota_level(used, quota, !allowed);
+        if let (QuotaLevel::Warning | QuotaLevel::Exceeded, Some(organization_id)) =
+            (level, organization_id)
+        {
+            self.notify(api_key_id, tier, organization_id, now, level, used, quota)
+                .await;
+        }
+        Some(Metered {
+            allowed,
+            used,
+            quota,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn count(
+        &self,
+        api_key_id: Uuid,
+        now: DateTime<Utc>,
+        quota: u64,
+        hard_limit: bool,
+        method: &str,
+        route: &str,
+        cost: u32,
+    ) -> redis::RedisResult<(bool, u64)> {
+        lazy_static::lazy_static! {
+            static ref SCRIPT: redis::Script = redis::Script::new(METER_SCRIPT);
+        }
+        let (requests_field, units_field) = usage_metering::endpoint_fields(method, route);
+        let mut conn = self.redis_client.get_multiplexed_tokio_connection().await?;
+        let (allowed, used): (i64, i64) = SCRIPT
+            .key(usage_metering::month_key(api_key_id, now))
+            .key(usage_metering::hour_key(api_key_id, now))
+            .key(PENDING_KEY)
+            .arg(cost)
+            .arg(quota)
+            .arg(if hard_limit { "1" } else { "0" })
+            .arg(requests_field)
+            .arg(units_field)
+            .arg(MONTH_KEY_TTL_SECS)
+            .arg(HOUR_KEY_TTL_SECS)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok((allowed == 1, used.max(0) as u64))
+    }
+
+    /// Queues the quota webhook unless this month's was already sent
+    #[allow(clippy::too_many_arguments)]
+    async fn notify(
+        &self,
+        api_key_id: Uuid,
+        tier: &ApiKeyTier,
+        organization_id: Uuid,
+        now: DateTime<Utc>,
+        level: QuotaLevel,
+        used: u64,
+        quota: u64,
+    ) {
+        let Some(event_type) = level.event_type() else {
+            return;
+        };
+        let first: redis::RedisResult<bool> = async {
+            let mut conn = self.redis_client.get_multiplexed_tokio_connection().await?;
+            let set: Option<String> = redis::cmd("SET")
+                .arg(usage_metering::notified_key(api_key_id, now, level))
+                .arg(1)
+                .arg("NX")
+                .arg("EX")
+                .arg(MONTH_KEY_TTL_SECS)
+                .query_async(&mut conn)
+                .await?;
+            Ok(set.is_some())
+        }
+        .instrument(telemetry::redis_span("SET"))
+        .await;
+        match first {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => {
+                tracing::warn!(api_key_id = %api_key_id, "Failed to check quota notification: {}", e);
+                return;
+            }
+        }
+
+        let pool = self.pool.clone();
+        let data = json!({
+            "api_key_id": api_key_id,
+            "tier": tier,
+            "period": now.format("%Y-%m").to_string(),
+            "monthly_quota": quota,
+            "used": used,
+            "hard_limit": usage_metering::plan(tier).hard_limit,
+        });
+        // The request that crossed the level should not wait for the queue
+        tokio::spawn(
+            async move {
+                webhook_service::enqueue_for_organization(&pool, event_type, organization_id, data)
+                    .await;
+            }
+            .in_current_span(),
+        );
+    }
+
+    // ── Aggregation ───────────────────────────────────────────────────────────
+
+    /// Moves the hourly counts from Redis into `api_key_usage_hourly`.
+    /// Counts that cannot be written are put back for the next run. Returns
+    /// the number of hourly hashes moved.
+    pub async fn flush(&self) -> Result<usize, AppError> {
+        lazy_static::lazy_static! {
+            static ref SCRIPT: redis::Script = redis::Script::new(TAKE_HOUR_SCRIPT);
+        }
+        let mut conn = self.redis_client.get_multiplexed_tokio_connection().await?;
+        let pending: Vec<String> = redis::cmd("SMEMBERS")
+            .arg(PENDING_KEY)
+            .query_async(&mut conn)
+            .await?;
+
+        let mut moved = 0;
+        for hour_key in pending {
+            let Some((api_key_id, hour)) = usage_metering::parse_hour_key(&hour_key) else {
+                redis::cmd("SREM")
+                    .arg(PENDING_KEY)
+                    .arg(&hour_key)
+                    .query_async::<_, ()>(&mut conn)
+                    .await?;
+                continue;
+            };
+            let fields: HashMap<String, i64> = SCRIPT
+                .key(&hour_key)
+                .key(PENDING_KEY)
+                .invoke_async(&mut conn)
+                .await?;
+            let fields: Vec<(String, i64)> = fields.into_iter().collect();
+            let counts = usage_metering::parse_counts(&fields);
+            if counts.is_empty() {
+                continue;
+            }
+
+            match self.store(api_key_id, hour, &counts).await {
+                Ok(()) => moved += 1,
+                // The key was deleted; its usage goes with it
+                Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("23503") => {}
+                Err(e) => {
+                    tracing::error!(api_key_id = %api_key_id, "Failed to store API usage: {}", e);
+                    let mut restore = redis::pipe();
+                    for (field, value) in &fields {
+                        restore.hincr(&hour_key, field, *value).ignore();
+                    }
+                    restore.sadd(PENDING_KEY, &hour_key).ignore();
+                    restore.query_async::<_, ()>(&mut conn).await?;
+                }
+            }
+        }
+        Ok(moved)
+    }
+
+    async fn store(
+        &self,
+        api_key_id: Uuid,
+        hour: DateTime<Utc>,
+        counts: &[usage_metering::EndpointCount],
+    ) -> Result<(), sqlx::Error> {
+        let methods: Vec<&str> = counts.iter().map(|c| c.method.as_str()).collect();
+        let routes: Vec<&str> = counts.iter().map(|c| c.route.as_str()).collect();
+        let requests: Vec<i64> = counts.iter().map(|c| c.requests).collect();
+        let units: Vec<i64> = counts.iter().map(|c| c.units).collect();
+        sqlx::query(
+            r#"
+            INSERT INTO api_key_usage_hourly (api_key_id, hour, method, route, requests, units)
+            SELECT $1, $2, * FROM UNNEST($3::TEXT[], $4::TEXT[], $5::BIGINT[], $6::BIGINT[])
+            ON CONFLICT (api_key_id, hour, method, route) DO UPDATE
+            SET requests = api_key_usage_hourly.requests + EXCLUDED.requests,
+                units = api_key_usage_hourly.units + EXCLUDED.units
+            "#,
+        )
+        .bind(api_key_id)
+        .bind(hour)
+        .bind(&methods)
+        .bind(&routes)
+        .bind(&requests)
+        .bind(&units)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // ── Reports ───────────────────────────────────────────────────────────────
+
+    /// Units the key has used this month. Read from the live counter, or from
+    /// Postgres while Redis is unreachable.
+    pub async fn month_to_date(&self, api_key_id: Uuid) -> Result<u64, AppError> {
+        let now = Utc::now();
+        let live: redis::RedisResult<Option<i64>> = async {
+            let mut conn = self.redis_client.get_multiplexed_tokio_connection().await?;
+            redis::cmd("GET")
+                .arg(usage_metering::month_key(api_key_id, now))
+                .query_async(&mut conn)
+                .await
+        }
+        .await;
+        if let Ok(used) = live {
+            return Ok(used.unwrap_or(0).max(0) as u64);
+        }
+        let (units,): (i64,) = sqlx::query_as(
+            r#"
+            SELECT COALESCE(SUM(units), 0)::BIGINT FROM api_key_usage_hourly
+            WHERE api_key_id = $1 AND hour >= $2
+            "#,
+        )
+        .bind(api_key_id)
+        .bind(usage_metering::month_start(now))
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(units.max(0) as u64)
+    }
+
+    /// Use of each endpoint between `from` and `to`, costliest first
+    pub async fn endpoint_usage(
+        &self,
+        api_key_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<EndpointUsage>, AppError> {
+        let usage = sqlx::query_as::<_, EndpointUsage>(
+            r#"
+            SELECT method, route, SUM(requests)::BIGINT AS requests, SUM(units)::BIGINT AS units
+            FROM api_key_usage_hourly
+            WHERE api_key_id = $1 AND hour >= $2 AND hour < $3
+            GROUP BY method, route
+            ORDER BY units DESC, requests DESC
+            "#,
+        )
+        .bind(api_key_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(usage)
+    }
+
+    /// Use in each hour between `from` and `to` that saw any
+    pub async fn hourly_usage(
+        &self,
+        api_key_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<HourlyUsage>, AppError> {
+        let usage = sqlx::query_as::<_, HourlyUsage>(
+            r#"
+            SELECT hour, SUM(requests)::BIGINT AS requests, SUM(units)::BIGINT AS units
+            FROM api_key_usage_hourly
+            WHERE api_key_id = $1 AND hour >= $2 AND hour < $3
+            GROUP BY hour
+            ORDER BY hour
+            "#,
+        )
+        .bind(api_key_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(usage)
+    }
+
+    // ── Billing ───────────────────────────────────────────────────────────────
+
+    /// Invoices every user whose keys were used in the month starting at
+    /// `period_start` and who has no usage invoice for it yet. Safe to run
+    /// again and on several instances. Returns the number of invoices created.
+    pub async fn invoice_month(
+        &self,
+        financial: &FinancialService,
+        period_start: DateTime<Utc>,
+    ) -> Result<usize, AppError> {
+        let period_start = usage_metering::month_start(period_start);
+        let period_end = usage_metering::next_month_start(period_start);
+        let period_date = period_start.date_naive();
+        let period = period_start.format("%Y-%m").to_string();
+
+        let users: Vec<(Uuid,)> = sqlx::query_as(
+            r#"
+            SELECT DISTINCT k.user_id FROM api_key_usage_hourly u
+            JOIN api_keys k ON k.id = u.api_key_id
+            WHERE u.hour >= $1 AND u.hour < $2
+              AND NOT EXISTS (
+                  SELECT 1 FROM api_usage_invoices i
+                  WHERE i.user_id = k.user_id AND i.period_start = $3
+              )
+            "#,
+        )
+        .bind(period_start)
+        .bind(period_end)
+        .bind(period_date)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut created = 0;
+        for (user_id,) in users {
+            // Claim the user and month first, so no other run bills them too
+            let claimed = sqlx::query(
+                r#"
+                INSERT INTO api_usage_invoices (user_id, period_start) VALUES ($1, $2)
+                ON CONFLICT DO NOTHING
+                "#,
+            )
+            .bind(user_id)
+            .bind(period_date)
+            .execute(&self.pool)
+            .await?;
+            if claimed.rows_affected() == 0 {
+                continue;
+            }
+
+            let usage: Vec<KeyUsage> = sqlx::query_as::<_, KeyUsageRow>(
+                r#"
+                SELECT k.id AS api_key_id, k.name, k.tier::TEXT AS tier, SUM(u.units)::BIGINT AS units
+                FROM api_key_usage_hourly u
+                JOIN api_keys k ON k.id = u.api_key_id
+                WHERE k.user_id = $1 AND u.hour >= $2 AND u.hour < $3
+                GROUP BY k.id, k.name, k.tier
+                ORDER BY k.name
+                "#,
+            )
+            .bind(user_id)
+            .bind(period_start)
+            .bind(period_end)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|row| KeyUsage {
+                api_key_id: row.api_key_id,
+                name: row.name,
+                tier: row.tier,
+                units: row.units,
+            })
+            .collect();
+
+            let lines = usage_metering::invoice_lines(&usage, &period);
+            if lines.is_empty() {
+                continue;
+            }
+            let due_date = (period_end + chrono::Duration::days(INVOICE_DUE_DAYS))
+                .format("%Y-%m-%d")
+                .to_string();
+            let invoice = financial
+                .create_itemized_invoice(
+                    &user_id.to_string(),
+                    &due_date,
+                    &format!("API usage, {}", period),
+                    &lines,
+                )
+                .await;
+            match invoice {
+                Ok(invoice) => {
+                    sqlx::query(
+                        r#"
+                        UPDATE api_usage_invoices SET invoice_id = $3::UUID
+                        WHERE user_id = $1 AND period_start = $2
+                        "#,
+                    )
+                    .bind(user_id)
+                    .bind(period_date)
+                    .bind(&invoice.id)
+                    .execute(&self.pool)
+                    .await?;
+                    created += 1;
+                }
+                Err(e) => {
+                    tracing::error!(user_id = %user_id, period, "Failed to create usage invoice: {}", e);
+                    // Leave the user to the next run
+                    sqlx::query(
+                        "DELETE FROM api_usage_invoices WHERE user_id = $1 AND period_start = $2",
+                    )
+                    .bind(user_id)
+                    .bind(period_date)
+                    .execute(&self.pool)
+                    .await?;
+                }
+            }
+        }
+        Ok(created)
+    }
+}
@@ -0,0 +1,148 @@
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::WebAuthConfig;
+use crate::error::AppError;
+use crate::models::WalletChallenge;
+use crate::services::sep10::{self, ChallengeIssuer, ChallengePurpose};
+
+/// An address whose holder just signed a challenge for it. Only
+/// [`WalletAuthService::redeem_challenge`] makes one, so holding one is proof
+/// of control.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedAddress(String);
+
+impl VerifiedAddress {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Issues and redeems SEP-10 challenge transactions. A challenge can be
+/// redeemed once, before it expires, for the purpose it was issued for, with
+/// the account's signature added to it.
+pub struct WalletAuthService {
+    pool: PgPool,
+    issuer: ChallengeIssuer,
+}
+
+impl WalletAuthService {
+    pub fn new(pool: PgPool, config: &WebAuthConfig) -> Result<Self, AppError> {
+        let signing_key = match &config.signing_key {
+            Some(seed) => sep10::signing_key(seed).map_err(AppError::Configuration)?,
+            None => {
+                let mut seed = [0u8; 32];
+                rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut seed);
+                tracing::warn!(
+                    "WEB_AUTH_SIGNING_KEY is not set; wallet challenges are signed with a key that changes at every restart"
+                );
+                ed25519_dalek::SigningKey::from_bytes(&seed)
+            }
+        };
+        let issuer = ChallengeIssuer::new(
+            signing_key,
+            &config.network_passphrase,
+            &config.home_domain,
+            &config.domain,
+        );
+        tracing::info!(signing_key = %issuer.account(), "Wallet sign-in challenges enabled");
+        Ok(Self { pool, issuer })
+    }
+
+    pub async fn issue_challenge(
+        &self,
+        stellar_address: &str,
+        purpose: ChallengePurpose,
+    ) -> Result<WalletChallenge, AppError> {
+        sep10::account_key(stellar_address).map_err(AppError::Validation)?;
+
+        let nonce = sep10::generate_nonce();
+        let issued_at = Utc::now();
+        let expires_at = issued_at + Duration::seconds(sep10::CHALLENGE_TTL_SECONDS);
+        let transaction = self
+            .issuer
+            .issue(purpose, stellar_address, &nonce, issued_at, expires_at)
+            .map_err(AppError::Internal)?;
+
+        // Challenges are short-lived; sweep the stale ones as new ones come in
+        sqlx::query("DELETE FROM wallet_challenges WHERE expires_at < NOW() - INTERVAL '1 hour'")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO wallet_challenges (nonce, stellar_address, purpose, transaction, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(&nonce)
+        .bind(stellar_address)
+        .bind(purpose.as_string())
+        .bind(&transaction)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(WalletChallenge {
+            stellar_address: stellar_address.to_string(),
+            transaction,
+            network_passphrase: self.issuer.network_passphrase().to_string(),
+            expires_at,
+        })
+    }
+
+    /// Checks `transaction` is an open `purpose` challenge signed by the
+    /// account it was issued for and marks the challenge used. Any failure is
+    /// a plain `Unauthorized`, so callers learn nothing about which part was
+    /// wrong.
+    pub async fn redeem_challenge(
+        &self,
+        transaction: &str,
+        purpose: ChallengePurpose,
+    ) -> Result<VerifiedAddress, AppError> {
+        let (stellar_address, nonce) = sep10::read_challenge(transaction).map_err(|reason| {
+            tracing::debug!(%reason, "Rejected wallet challenge");
+            AppError::Unauthorized
+        })?;
+
+        let issued = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT transaction FROM wallet_challenges
+            WHERE nonce = $1 AND stellar_address = $2 AND purpose = $3
+              AND consumed_at IS NULL AND expires_at > NOW()
+            "#,
+        )
+        .bind(&nonce)
+        .bind(&stellar_address)
+        .bind(purpose.as_string())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+        if let Err(reason) = self.issuer.verify(&issued, transaction) {
+            tracing::debug!(%stellar_address, %reason, "Rejected wallet signature");
+            return Err(AppError::Unauthorized);
+        }
+
+        // Only one of two concurrent redemptions wins
+        let consumed = sqlx::query(
+            "UPDATE wallet_challenges SET consumed_at = NOW() WHERE nonce = $1 AND consumed_at IS NULL",
+        )
+        .bind(&nonce)
+        .execute(&self.pool)
+        .await?;
+        if consumed.rows_affected() == 0 {
+            return Err(AppError::Unauthorized);
+        }
+        Ok(VerifiedAddress(stellar_address))
+    }
+
+    pub async fn mark_address_verified(&self, user_id: Uuid) -> Result<(), AppError> {
+        sqlx::query("UPDATE users SET stellar_address_verified_at = NOW() WHERE id = $1")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
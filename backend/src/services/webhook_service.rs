@@ -0,0 +1,548 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::monitoring::metrics::{WebhookOutcome, METRICS};
+use crate::monitoring::telemetry;
+use crate::models::{
+    NewWebhook, Webhook, WebhookDelivery, WebhookDeliveryAttempt, WebhookDeliveryStatus,
+};
+use crate::services::webhook_delivery::{self, DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER};
+use crate::utils::crypto::{decrypt_secret, encrypt_secret};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const BATCH_SIZE: i64 = 50;
+/// How long a claimed delivery is hidden from other workers. If the instance
+/// dies mid-delivery, the delivery is picked up again once this passes.
+const LEASE_SECS: f64 = 120.0;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_ERROR_LEN: usize = 500;
+/// Delivered and dead deliveries are kept this long for the delivery log
+const RETENTION_DAYS: i32 = 30;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// A delivery claimed by the worker, with what it needs to send it
+#[derive(Debug, FromRow)]
+struct ClaimedDelivery {
+    id: Uuid,
+    event_type: String,
+    payload: Value,
+    attempts: i32,
+    url: String,
+    secret: String,
+    /// Trace of the request that queued it
+    traceparent: Option<String>,
+}
+
+/// Queues an event for every webhook that should hear about it: active,
+/// subscribed to the event type and product, and belonging to an
+/// organization that can see the product. `organization_id` is the product's
+/// own organization, which still applies once a product is deleted.
+/// Deliveries continue the current trace. Failures are logged rather than
+/// failing the change that caused them.
+pub async fn enqueue(
+    pool: &PgPool,
+    event_type: &str,
+    product_id: &str,
+    organization_id: Option<Uuid>,
+    data: Value,
+) {
+    let event_id = Uuid::new_v4();
+    let payload = webhook_delivery::envelope(event_id, event_type, Utc::now(), data);
+    let queued = sqlx::query(
+        r#"
+        INSERT INTO webhook_deliveries
+            (webhook_id, event_id, event_type, product_id, payload, traceparent)
+        SELECT w.id, $1, $2, $3, $4, $6 FROM webhooks w
+        WHERE w.is_active
+          AND w.organization_id IS NOT NULL
+          AND (cardinality(w.events) = 0 OR $2 = ANY(w.events))
+          AND (w.product_ids IS NULL OR $3 = ANY(w.product_ids))
+          AND (w.organization_id = $5 OR product_visible_to(w.organization_id, NULL, $3))
+        "#,
+    )
+    .bind(event_id)
+    .bind(event_type)
+    .bind(product_id)
+    .bind(&payload)
+    .bind(organization_id)
+    .bind(telemetry::traceparent())
+    .execute(pool)
+    .await;
+    if let Err(e) = queued {
+        tracing::error!(
+            event_type,
+            product_id,
+            "Failed to queue webhook deliveries: {}",
+            e
+        );
+    }
+}
+
+/// Queues an event about an organization rather than a product, such as an
+/// API key running low on quota. Webhooks narrowed to some products only get
+/// these when they subscribe to the event type by name.
+pub async fn enqueue_for_organization(
+    pool: &PgPool,
+    event_type: &str,
+    organization_id: Uuid,
+    data: Value,
+) {
+    let event_id = Uuid::new_v4();
+    let payload = webhook_delivery::envelope(event_id, event_type, Utc::now(), data);
+    let queued = sqlx::query(
+        r#"
+        INSERT INTO webhook_deliveries (webhook_id, event_id, event_type, payload, traceparent)
+        SELECT w.id, $1, $2, $3, $5 FROM webhooks w
+        WHERE w.is_active
+          AND w.organization_id = $4
+          AND ($2 = ANY(w.events) OR (cardinality(w.events) = 0 AND w.product_ids IS NULL))
+        "#,
+    )
+    .bind(event_id)
+    .bind(event_type)
+    .bind(&payload)
+    .bind(organization_id)
+    .bind(telemetry::traceparent())
+    .execute(pool)
+    .await;
+    if let Err(e) = queued {
+        tracing::error!(
+            event_type,
+            organization_id = %organization_id,
+            "Failed to queue webhook deliveries: {}",
+            e
+        );
+    }
+}
+
+/// Webhook subscriptions and their delivery queue. A background worker sends
+/// due deliveries, retrying failures with backoff until they are delivered
+/// or dead-lettered; every attempt is logged.
+pub struct WebhookService {
+    pool: PgPool,
+    encryption_key: String,
+}
+
+impl WebhookService {
+    pub fn new(pool: PgPool, encryption_key: String) -> Self {
+        Self {
+            pool,
+            encryption_key,
+        }
+    }
+
+    // ── Subscriptions ─────────────────────────────────────────────────────────
+
+    pub async fn create(&self, webhook: NewWebhook) -> Result<Webhook, AppError> {
+        let created = sqlx::query_as::<_, Webhook>(
+            r#"
+            INSERT INTO webhooks (user_id, organization_id, url, secret, events, product_ids, description)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+        )
+        .bind(webhook.user_id)
+        .bind(webhook.organization_id)
+        .bind(&webhook.url)
+        .bind(encrypt_secret(&webhook.secret, &self.encryption_key)?)
+        .bind(&webhook.events)
+        .bind(&webhook.product_ids)
+        .bind(&webhook.description)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(created)
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<Option<Webhook>, AppError> {
+        let webhook = sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(webhook)
+    }
+
+    pub async fn list(&self, organization_id: Uuid) -> Result<Vec<Webhook>, AppError> {
+        let webhooks = sqlx::query_as::<_, Webhook>(
+            "SELECT * FROM webhooks WHERE organization_id = $1 ORDER BY created_at",
+        )
+        .bind(organization_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(webhooks)
+    }
+
+    /// Saves the editable fields of `webhook`
+    pub async fn update(&self, webhook: &Webhook) -> Result<Webhook, AppError> {
+        let updated = sqlx::query_as::<_, Webhook>(
+            r#"
+            UPDATE webhooks
+            SET url = $2, events = $3, product_ids = $4, description = $5, is_active = $6
+            WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(webhook.id)
+        .bind(&webhook.url)
+        .bind(&webhook.events)
+        .bind(&webhook.product_ids)
+        .bind(&webhook.description)
+        .bind(webhook.is_active)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Webhook {} not found", webhook.id)))?;
+        Ok(updated)
+    }
+
+    /// Deletes the webhook along with its queue and delivery log
+    pub async fn delete(&self, id: Uuid) -> Result<bool, AppError> {
+        let result = sqlx::query("DELETE FROM webhooks WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ── Delivery log ──────────────────────────────────────────────────────────
+
+    pub async fn list_deliveries(
+        &self,
+        webhook_id: Uuid,
+        status: Option<WebhookDeliveryStatus>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<WebhookDelivery>, AppError> {
+        let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+            r#"
+            SELECT * FROM webhook_deliveries
+            WHERE webhook_id = $1 AND ($2::text IS NULL OR status = $2)
+            ORDER BY created_at DESC
+            LIMIT $3 OFFSET $4
+            "#,
+        )
+        .bind(webhook_id)
+        .bind(status)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(deliveries)
+    }
+
+    pub async fn get_delivery(
+        &self,
+        webhook_id: Uuid,
+        delivery_id: Uuid,
+    ) -> Result<Option<WebhookDelivery>, AppError> {
+        let delivery = sqlx::query_as::<_, WebhookDelivery>(
+            "SELECT * FROM webhook_deliveries WHERE id = $1 AND webhook_id = $2",
+        )
+        .bind(delivery_id)
+        .bind(webhook_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(delivery)
+    }
+
+    pub async fn list_attempts(
+        &self,
+        delivery_id: Uuid,
+    ) -> Result<Vec<WebhookDeliveryAttempt>, AppError> {
+        let attempts = sqlx::query_as::<_, WebhookDeliveryAttempt>(
+            r#"
+            SELECT attempt, status_code, error, duration_ms, attempted_at
+            FROM webhook_delivery_attempts
+            WHERE delivery_id = $1
+            ORDER BY attempt
+            "#,
+        )
+        .bind(delivery_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(attempts)
+    }
+
+    /// Queues the same event again as a new delivery, sent on the worker's
+    /// next pass. Deliveries still being retried cannot be replayed.
+    pub async fn replay(&self, delivery: &WebhookDelivery) -> Result<WebhookDelivery, AppError> {
+        if delivery.status == WebhookDeliveryStatus::Pending {
+            return Err(AppError::BusinessRule(
+                "Delivery is still pending; wait for it to be delivered or dead-lettered"
+                    .to_string(),
+            ));
+        }
+        let replayed = sqlx::query_as::<_, WebhookDelivery>(
+            r#"
+            INSERT INTO webhook_deliveries
+                (webhook_id, event_id, event_type, product_id, payload, replay_of, traceparent)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+        )
+        .bind(delivery.webhook_id)
+        .bind(delivery.event_id)
+        .bind(&delivery.event_type)
+        .bind(&delivery.product_id)
+        .bind(&delivery.payload)
+        .bind(delivery.id)
+        .bind(telemetry::traceparent())
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(replayed)
+    }
+
+    // ── Worker ────────────────────────────────────────────────────────────────
+
+    /// Starts sending due deliveries from this instance. Instances claim
+    /// deliveries with `SKIP LOCKED`, so any number of them can run workers.
+    pub fn start_worker(self: Arc<Self>) {
+        let worker = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                // Keep going while there is a backlog
+                loop {
+                    match worker.deliver_due().await {
+                        Ok(claimed) if claimed as i64 == BATCH_SIZE => continue,
+                        Ok(_) => break,
+                        Err(e) => {
+                            tracing::error!("Webhook delivery pass failed: {}", e);
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+            loop {
+                interval.tick().await;
+                match self.prune().await {
+                    Ok(n) if n > 0 => tracing::info!("Pruned {} old webhook deliveries", n),
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("Failed to prune webhook deliveries: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Claims a batch of due deliveries and sends them concurrently. Returns
+    /// how many were claimed.
+    async fn deliver_due(&self) -> Result<usize, AppError> {
+        // Claiming counts the attempt, so one cut short by a crash still counts
+        let claimed = sqlx::query_as::<_, ClaimedDelivery>(
+            r#"
+            UPDATE webhook_deliveries d
+            SET attempts = d.attempts + 1,
+                next_attempt_at = NOW() + make_interval(secs => $2)
+            FROM webhooks w
+            WHERE w.id = d.webhook_id
+              AND d.id IN (
+                  SELECT q.id FROM webhook_deliveries q
+                  JOIN webhooks qw ON qw.id = q.webhook_id
+                  WHERE q.status = 'pending' AND q.next_attempt_at <= NOW() AND qw.is_active
+                  ORDER BY q.next_attempt_at
+                  LIMIT $1
+                  FOR UPDATE OF q SKIP LOCKED
+              )
+            RETURNING d.id, d.event_type, d.payload, d.attempts, w.url, w.secret, d.traceparent
+            "#,
+        )
+        .bind(BATCH_SIZE)
+        .bind(LEASE_SECS)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let count = claimed.len();
+        let results = futures::future::join_all(claimed.into_iter().map(|delivery| {
+            let span = tracing::info_span!(
+                "webhook_delivery",
+                otel.kind = "client",
+                delivery_id = %delivery.id,
+                event_type = %delivery.event_type,
+                attempt = delivery.attempts,
+            );
+            telemetry::continue_trace(&span, delivery.traceparent.as_deref());
+            self.attempt(delivery).instrument(span)
+        }))
+        .await;
+        for result in results {
+            if let Err(e) = result {
+                tracing::error!("Failed to record webhook delivery attempt: {}", e);
+            }
+        }
+        Ok(count)
+    }
+
+    /// Sends one delivery and records the outcome: delivered on a 2xx,
+    /// otherwise rescheduled with backoff or dead-lettered
+    async fn attempt(&self, delivery: ClaimedDelivery) -> Result<(), AppError> {
+        let started = Instant::now();
+        let (status_code, error) = match self.send(&delivery).await {
+            Ok(status) if status.is_success() => (Some(status.as_u16() as i32), None),
+            Ok(status) => (
+                Some(status.as_u16() as i32),
+                Some(format!("HTTP {}", status)),
+            ),
+            Err(e) => (None, Some(truncate(e))),
+        };
+        let duration_ms = started.elapsed().as_millis().min(i32::MAX as u128) as i32;
+
+        let (status, next_attempt_at, delivered_at) = if error.is_none() {
+            (
+                WebhookDeliveryStatus::Delivered,
+                Utc::now(),
+                Some(Utc::now()),
+            )
+        } else if webhook_delivery::is_exhausted(delivery.attempts) {
+            (WebhookDeliveryStatus::Dead, Utc::now(), None)
+        } else {
+            let delay = webhook_delivery::retry_delay(delivery.attempts);
+            let next = Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default();
+            (WebhookDeliveryStatus::Pending, next, None)
+        };
+
+        match status {
+            WebhookDeliveryStatus::Dead => {
+                tracing::warn!(
+                    delivery_id = %delivery.id,
+                    attempts = delivery.attempts,
+                    "Webhook delivery dead-lettered"
+                );
+                METRICS.count_webhook_delivery(WebhookOutcome::Dead);
+            }
+            WebhookDeliveryStatus::Pending => {
+                tracing::debug!(
+                    delivery_id = %delivery.id,
+                    attempts = delivery.attempts,
+                    "Webhook delivery failed; will retry"
+                );
+                METRICS.count_webhook_delivery(WebhookOutcome::Failed);
+            }
+            WebhookDeliveryStatus::Delivered => {
+                METRICS.count_webhook_delivery(WebhookOutcome::Delivered)
+            }
+        }
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_delivery_attempts (delivery_id, attempt, status_code, error, duration_ms)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(delivery.id)
+        .bind(delivery.attempts)
+        .bind(status_code)
+        .bind(&error)
+        .bind(duration_ms)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = $2, next_attempt_at = $3, last_status_code = $4, last_error = $5,
+                delivered_at = $6
+            WHERE id = $1
+            "#,
+        )
+        .bind(delivery.id)
+        .bind(status)
+        .bind(next_attempt_at)
+        .bind(status_code)
+        .bind(&error)
+        .bind(delivered_at)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn send(&self, delivery: &ClaimedDelivery) -> Result<reqwest::StatusCode, String> {
+        let url = webhook_delivery::validate_url(&delivery.url)?;
+        // The host may have started resolving to an internal address since
+        // the webhook was saved
+        let host = url
+            .host_str()
+            .unwrap_or_default()
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let port = url.port_or_known_default().unwrap_or(443);
+        let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| format!("Could not resolve {}: {}", host, e))?
+            .collect();
+        if addresses.is_empty()
+            || !addresses
+                .iter()
+                .all(|addr| webhook_delivery::is_public_address(addr.ip()))
+        {
+            return Err(format!("{} does not resolve to a public address", host));
+        }
+        let client = delivery_client(host, addresses[0])
+            .map_err(|e| format!("Could not build HTTP client: {}", e))?;
+
+        let secret = decrypt_secret(&delivery.secret, &self.encryption_key)
+            .map_err(|_| "Signing secret could not be decrypted".to_string())?;
+        let body = delivery.payload.to_string();
+        let signature = webhook_delivery::sign(&secret, Utc::now().timestamp(), &body);
+
+        let response = telemetry::inject(client.post(url))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, signature)
+            .header(EVENT_HEADER, &delivery.event_type)
+            .header(DELIVERY_HEADER, delivery.id.to_string())
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(response.status())
+    }
+
+    /// Drops finished deliveries, and their attempts, past the retention period
+    async fn prune(&self) -> Result<u64, AppError> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM webhook_deliveries
+            WHERE status <> 'pending' AND created_at < NOW() - make_interval(days => $1)
+            "#,
+        )
+        .bind(RETENTION_DAYS)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+/// A client that connects only to `address`, the one `send` checked. Left to
+/// itself reqwest would resolve the host again, and a rebinding DNS server
+/// could answer that second lookup with an internal address. Redirects are
+/// off because they could lead somewhere `validate_url` never saw.
+fn delivery_client(host: &str, address: SocketAddr) -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .resolve(host, address)
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent("ChainLogistics-Webhooks/1.0")
+        .build()
+}
+
+fn truncate(mut error: String) -> String {
+    if error.len() > MAX_ERROR_LEN {
+        let mut end = MAX_ERROR_LEN;
+        while !error.is_char_boundary(end) {
+            end -= 1;
+        }
+        error.truncate(end);
+    }
+    error
+}
@@ -1,97 +1,18 @@
 use std::time::Duration;
 use sqlx::PgPool;
-use chrono::{DateTime, Utc};
-use crate::services::{SyncService, ProductService, EventService, ApiKeyService};
+use chrono::Utc;
+use crate::services::{SyncService, ApiKeyService, AuditService, FinancialService, UsageService};
+use crate::services::usage_metering;
 
 pub mod aggregation;
 pub mod crypto;
-
-pub struct BackupService {
-    pool: PgPool,
-}
-
-impl BackupService {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
-    }
-
-    pub async fn create_backup(&self) -> Result<String, sqlx::Error> {
-        let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
-        let backup_filename = format!("chainlogistics_backup_{}.sql", timestamp);
-        
-        // This would typically call pg_dump or use PostgreSQL's backup APIs
-        // For now, we'll create a logical backup by exporting key tables
-        let backup_path = format!("/backups/{}", backup_filename);
-        
-        // Create backup directory if it doesn't exist
-        tokio::fs::create_dir_all("/backups").await
-            .map_err(|e| sqlx::Error::Io(e.into()))?;
-
-        // Export products
-        let products = sqlx::query!("SELECT * FROM products")
-            .fetch_all(&self.pool)
-            .await?;
-
-        // Export events
-        let events = sqlx::query!("SELECT * FROM tracking_events")
-            .fetch_all(&self.pool)
-            .await?;
-
-        // Write backup file (simplified - in production use pg_dump)
-        let backup_content = format!(
-            "-- ChainLogistics Backup - {}\n\n-- Products\n{:?}\n\n-- Events\n{:?}\n",
-            timestamp, products, events
-        );
-
-        tokio::fs::write(&backup_path, backup_content).await
-            .map_err(|e| sqlx::Error::Io(e.into()))?;
-
-        tracing::info!("Backup created: {}", backup_path);
-        Ok(backup_path)
-    }
-
-    pub async fn restore_backup(&self, backup_path: &str) -> Result<(), sqlx::Error> {
-        // In production, this would use psql or pg_restore
-        tracing::warn!("Restore functionality not implemented for path: {}", backup_path);
-        Ok(())
-    }
-
-    pub async fn cleanup_old_backups(&self, retain_days: i64) -> Result<Vec<String>, sqlx::Error> {
-        let mut dir = tokio::fs::read_dir("/backups").await
-            .map_err(|e| sqlx::Error::Io(e.into()))?;
-
-        let cutoff_time = Utc::now() - chrono::Duration::days(retain_days);
-        let mut removed_files = Vec::new();
-
-        while let Some(entry) = dir.next_entry().await
-            .map_err(|e| sqlx::Error::Io(e.into))? {
-            
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("sql") {
-                if let Ok(metadata) = entry.metadata().await {
-                    if let Ok(modified) = metadata.modified() {
-                        let modified_time: DateTime<Utc> = modified.into();
-                        if modified_time < cutoff_time {
-                            if tokio::fs::remove_file(&path).await.is_ok() {
-                                removed_files.push(path.to_string_lossy().to_string());
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        tracing::info!("Cleaned up {} old backup files", removed_files.len());
-        Ok(removed_files)
-    }
-}
+pub mod geo;
 
 // Cron service for scheduled tasks
 pub struct CronService {
     pool: PgPool,
     redis_client: redis::Client,
-    backup_service: BackupService,
-    sync_service: SyncService,
+    sync_service: std::sync::Arc<SyncService>,
 }
 
 impl CronService {
@@ -99,37 +20,12 @@ impl CronService {
         Self {
             pool: pool.clone(),
             redis_client: redis_client.clone(),
-            backup_service: BackupService::new(pool.clone()),
-            sync_service: SyncService::new(pool, redis_client),
+            sync_service: std::sync::Arc::new(SyncService::new(pool, redis_client)),
         }
     }
 
     pub async fn start_scheduler(&self) {
         let pool = self.pool.clone();
-        let backup_service = self.backup_service.clone();
-        
-        // Daily backup at 2 AM UTC
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(86400)); // 24 hours
-            
-            loop {
-                interval.tick().await;
-                
-                // Check if it's 2 AM UTC (simplified - in production use a proper cron library)
-                let now = Utc::now();
-                if now.hour() == 2 && now.minute() == 0 {
-                    if let Err(e) = backup_service.create_backup().await {
-                        tracing::error!("Failed to create backup: {}", e);
-                    }
-                    
-                    // Clean up backups older than 30 days
-                    if let Err(e) = backup_service.cleanup_old_backups(30).await {
-                        tracing::error!("Failed to cleanup old backups: {}", e);
-                    }
-                }
-            }
-        });
-
         // Sync with smart contracts every 5 minutes
         let sync_service = self.sync_service.clone();
         tokio::spawn(async move {
@@ -162,26 +58,56 @@ impl CronService {
             }
         });
 
-        tracing::info!("Cron scheduler started");
-    }
-}
+        // Anchor the audit log chain head on Stellar every hour
+        let audit_service = AuditService::new(pool.clone());
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(3600));
+            loop {
+                interval.tick().await;
+                match audit_service.anchor_head().await {
+                    Ok(Some(anchor)) => tracing::info!(
+                        sequence = anchor.sequence,
+                        tx = %anchor.transaction_hash,
+                        "Anchored audit log head"
+                    ),
+                    Ok(None) => {}
+                    Err(e) => tracing::error!("Failed to anchor audit log head: {}", e),
+                }
+            }
+        });
 
-// Clone implementations for async tasks
-impl Clone for BackupService {
-    fn clone(&self) -> Self {
-        Self {
-            pool: self.pool.clone(),
-        }
-    }
-}
+        // Move API key usage counts from Redis into Postgres every minute
+        let usage_service = std::sync::Arc::new(UsageService::new(pool.clone(), self.redis_client.clone()));
+        let flush_usage = usage_service.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                if let Err(e) = flush_usage.flush().await {
+                    tracing::error!("Failed to store API key usage: {}", e);
+                }
+            }
+        });
 
-impl Clone for SyncService {
-    fn clone(&self) -> Self {
-        Self {
-            pool: self.pool.clone(),
-            redis_client: self.redis_client.clone(),
-            product_service: ProductService::new(self.pool.clone(), self.redis_client.clone()),
-            event_service: EventService::new(self.pool.clone(), self.redis_client.clone()),
-        }
+        // Invoice last month's API usage, once its counts have been stored
+        let financial_service = FinancialService::new(pool.clone());
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(3600));
+            loop {
+                interval.tick().await;
+                let this_month = usage_metering::month_start(Utc::now());
+                if Utc::now() - this_month < chrono::Duration::hours(1) {
+                    continue;
+                }
+                let last_month = this_month - chrono::Duration::days(1);
+                match usage_service.invoice_month(&financial_service, last_month).await {
+                    Ok(n) if n > 0 => tracing::info!("Created {} API usage invoices", n),
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("Failed to invoice API usage: {}", e),
+                }
+            }
+        });
+
+        tracing::info!("Cron scheduler started");
     }
-}
\ No newline at end of file
+}
@@ -1,5 +1,5 @@
 use aes_gcm::{
-    aead::{Aead, KeyInit},
+    aead::{Aead, AeadCore, KeyInit, OsRng},
     Aes256Gcm, Nonce, Key
 };
 use base64::{engine::general_purpose, Engine as _};
@@ -33,3 +33,81 @@ pub fn decrypt(encrypted_data: &str, key_str: &str) -> Result<String, AppError>
     String::from_utf8(plaintext)
         .map_err(|e| AppError::Internal(format!("UTF-8 decode error: {}", e)))
 }
+
+/// Encrypts binary data, such as a backup file, under a random nonce that is
+/// prepended to the ciphertext
+pub fn encrypt_bytes(data: &[u8], key_str: &str) -> Result<Vec<u8>, AppError> {
+    let cipher = bytes_cipher(key_str)?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+    let ciphertext = cipher
+        .encrypt(&nonce, data)
+        .map_err(|e| AppError::Cryptography(format!("Encryption error: {}", e)))?;
+
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Decrypts data sealed by `encrypt_bytes`; fails if it was altered or
+/// sealed under another key
+pub fn decrypt_bytes(sealed: &[u8], key_str: &str) -> Result<Vec<u8>, AppError> {
+    let cipher = bytes_cipher(key_str)?;
+    if sealed.len() < NONCE_LEN {
+        return Err(AppError::Cryptography("Ciphertext is truncated".to_string()));
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|e| AppError::Cryptography(format!("Decryption error: {}", e)))
+}
+
+/// Encrypts a short secret, such as a TOTP seed or a signing key, under a
+/// random nonce. The result is base64 of the nonce followed by the ciphertext.
+pub fn encrypt_secret(data: &str, key_str: &str) -> Result<String, AppError> {
+    Ok(general_purpose::STANDARD.encode(encrypt_bytes(data.as_bytes(), key_str)?))
+}
+
+/// Decrypts a secret sealed by `encrypt_secret`
+pub fn decrypt_secret(sealed: &str, key_str: &str) -> Result<String, AppError> {
+    let sealed = general_purpose::STANDARD
+        .decode(sealed)
+        .map_err(|e| AppError::Cryptography(format!("Base64 decode error: {}", e)))?;
+
+    String::from_utf8(decrypt_bytes(&sealed, key_str)?)
+        .map_err(|e| AppError::Cryptography(format!("UTF-8 decode error: {}", e)))
+}
+
+const NONCE_LEN: usize = 12;
+
+fn bytes_cipher(key_str: &str) -> Result<Aes256Gcm, AppError> {
+    if key_str.len() != 32 {
+        return Err(AppError::Cryptography(
+            "Key must be exactly 32 bytes (AES-256)".to_string(),
+        ));
+    }
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key_str.as_bytes())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn test_secret_round_trip_uses_a_fresh_nonce() {
+        let first = encrypt_secret("JBSWY3DPEHPK3PXP", KEY).unwrap();
+        let second = encrypt_secret("JBSWY3DPEHPK3PXP", KEY).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(decrypt_secret(&first, KEY).unwrap(), "JBSWY3DPEHPK3PXP");
+        assert_eq!(decrypt_secret(&second, KEY).unwrap(), "JBSWY3DPEHPK3PXP");
+    }
+
+    #[test]
+    fn test_secret_under_another_key_is_refused() {
+        let sealed = encrypt_secret("JBSWY3DPEHPK3PXP", KEY).unwrap();
+        assert!(decrypt_secret(&sealed, "fedcba9876543210fedcba9876543210").is_err());
+    }
+}
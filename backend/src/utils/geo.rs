@@ -0,0 +1,252 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const EARTH_RADIUS_KM: f64 = 6_371.0;
+const GEOHASH_ALPHABET: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Default geohash length (~150m x 150m cells).
+pub const DEFAULT_GEOHASH_PRECISION: usize = 7;
+
+/// A validated WGS84 coordinate pair.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Coordinates {
+    /// Returns `None` when the pair is outside WGS84 bounds or not finite.
+    pub fn new(latitude: f64, longitude: f64) -> Option<Self> {
+        let valid = latitude.is_finite()
+            && longitude.is_finite()
+            && (-90.0..=90.0).contains(&latitude)
+            && (-180.0..=180.0).contains(&longitude);
+        valid.then_some(Self {
+            latitude,
+            longitude,
+        })
+    }
+}
+
+/// An axis-aligned lat/lon rectangle.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoundingBox {
+    pub min_latitude: f64,
+    pub min_longitude: f64,
+    pub max_latitude: f64,
+    pub max_longitude: f64,
+}
+
+impl BoundingBox {
+    /// Smallest box containing every point within `radius_km` of `center`.
+    /// Longitude is widened to the full range near the poles.
+    pub fn around(center: Coordinates, radius_km: f64) -> Self {
+        let lat_delta = (radius_km / EARTH_RADIUS_KM).to_degrees();
+        let min_latitude = (center.latitude - lat_delta).max(-90.0);
+        let max_latitude = (center.latitude + lat_delta).min(90.0);
+
+        let cos_lat = center.latitude.to_radians().cos();
+        let (min_longitude, max_longitude) =
+            if cos_lat < 1e-6 || max_latitude >= 90.0 || min_latitude <= -90.0 {
+                (-180.0, 180.0)
+            } else {
+                let lon_delta = (radius_km / (EARTH_RADIUS_KM * cos_lat)).to_degrees();
+                (
+                    (center.longitude - lon_delta).max(-180.0),
+                    (center.longitude + lon_delta).min(180.0),
+                )
+            };
+
+        Self {
+            min_latitude,
+            min_longitude,
+            max_latitude,
+            max_longitude,
+        }
+    }
+
+    pub fn contains(&self, point: Coordinates) -> bool {
+        (self.min_latitude..=self.max_latitude).contains(&point.latitude)
+            && (self.min_longitude..=self.max_longitude).contains(&point.longitude)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        Coordinates::new(self.min_latitude, self.min_longitude).is_some()
+            && Coordinates::new(self.max_latitude, self.max_longitude).is_some()
+            && self.min_latitude <= self.max_latitude
+            && self.min_longitude <= self.max_longitude
+    }
+}
+
+/// Great-circle distance in kilometres.
+pub fn haversine_km(a: Coordinates, b: Coordinates) -> f64 {
+    let (lat1, lon1) = (a.latitude.to_radians(), a.longitude.to_radians());
+    let (lat2, lon2) = (b.latitude.to_radians(), b.longitude.to_radians());
+    let h = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * h.sqrt().asin()
+}
+
+/// Total distance along a path of points, in order.
+pub fn path_distance_km(points: &[Coordinates]) -> f64 {
+    points.windows(2).map(|w| haversine_km(w[0], w[1])).sum()
+}
+
+/// Encodes coordinates as a base32 geohash of the given length.
+pub fn geohash_encode(point: Coordinates, precision: usize) -> String {
+    let (mut lat_range, mut lon_range) = ((-90.0, 90.0), (-180.0, 180.0));
+    let mut hash = String::with_capacity(precision);
+    let mut bits = 0u8;
+    let mut bit_count = 0;
+    let mut even = true;
+
+    while hash.len() < precision {
+        let (range, value) = if even {
+            (&mut lon_range, point.longitude)
+        } else {
+            (&mut lat_range, point.latitude)
+        };
+        let mid = (range.0 + range.1) / 2.0;
+        bits <<= 1;
+        if value >= mid {
+            bits |= 1;
+            range.0 = mid;
+        } else {
+            range.1 = mid;
+        }
+        even = !even;
+        bit_count += 1;
+
+        if bit_count == 5 {
+            hash.push(GEOHASH_ALPHABET[bits as usize] as char);
+            bits = 0;
+            bit_count = 0;
+        }
+    }
+
+    hash
+}
+
+/// Parses a `"lat,lon"` string.
+pub fn parse_coordinates(text: &str) -> Option<Coordinates> {
+    let (lat, lon) = text.split_once(',')?;
+    Coordinates::new(lat.trim().parse().ok()?, lon.trim().parse().ok()?)
+}
+
+/// Reads `latitude`/`longitude` (or `lat`/`lon`/`lng`) from a JSON object.
+pub fn coordinates_from_metadata(metadata: &Value) -> Option<Coordinates> {
+    let number = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| metadata.get(*k).and_then(|v| v.as_f64()))
+    };
+    Coordinates::new(
+        number(&["latitude", "lat"])?,
+        number(&["longitude", "lon", "lng"])?,
+    )
+}
+
+/// Orders waypoints to shorten the path while keeping the first and last fixed.
+/// Uses nearest-neighbour construction followed by 2-opt improvement.
+/// Returns indices into `points`.
+pub fn optimize_route(points: &[Coordinates]) -> Vec<usize> {
+    let n = points.len();
+    if n <= 3 {
+        return (0..n).collect();
+    }
+
+    // Nearest neighbour from the origin over the intermediate stops
+    let mut order = vec![0];
+    let mut remaining: Vec<usize> = (1..n - 1).collect();
+    while !remaining.is_empty() {
+        let last = points[*order.last().expect("order starts non-empty")];
+        let (pos, _) = remaining
+            .iter()
+            .enumerate()
+            .map(|(pos, &i)| (pos, haversine_km(last, points[i])))
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .expect("remaining is non-empty");
+        order.push(remaining.swap_remove(pos));
+    }
+    order.push(n - 1);
+
+    // 2-opt: reverse segments while it shortens the path
+    let dist = |a: usize, b: usize| haversine_km(points[a], points[b]);
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 1..n - 2 {
+            for j in i + 1..n - 1 {
+                let before = dist(order[i - 1], order[i]) + dist(order[j], order[j + 1]);
+                let after = dist(order[i - 1], order[j]) + dist(order[i], order[j + 1]);
+                if after + 1e-9 < before {
+                    order[i..=j].reverse();
+                    improved = true;
+                }
+            }
+        }
+    }
+
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn point(lat: f64, lon: f64) -> Coordinates {
+        Coordinates::new(lat, lon).unwrap()
+    }
+
+    #[test]
+    fn test_haversine_rotterdam_hamburg() {
+        let d = haversine_km(point(51.9244, 4.4777), point(53.5511, 9.9937));
+        assert!((d - 413.0).abs() < 5.0, "got {}", d);
+    }
+
+    #[test]
+    fn test_geohash_known_value() {
+        // Reference value from the original geohash.org example
+        assert_eq!(geohash_encode(point(57.64911, 10.40744), 11), "u4pruydqqvj");
+    }
+
+    #[test]
+    fn test_bounding_box_contains_radius() {
+        let center = point(51.9244, 4.4777);
+        let bbox = BoundingBox::around(center, 50.0);
+        assert!(bbox.contains(point(52.0, 4.9)));
+        assert!(!bbox.contains(point(53.5511, 9.9937)));
+        assert!(bbox.is_valid());
+    }
+
+    #[test]
+    fn test_parse_coordinates() {
+        assert_eq!(parse_coordinates(" 51.9, 4.48 "), Some(point(51.9, 4.48)));
+        assert!(parse_coordinates("Rotterdam").is_none());
+        assert!(parse_coordinates("91,0").is_none());
+        assert_eq!(
+            coordinates_from_metadata(&json!({"lat": 1.5, "lng": 2.5})),
+            Some(point(1.5, 2.5))
+        );
+    }
+
+    #[test]
+    fn test_optimize_route_keeps_endpoints_and_shortens() {
+        // Zig-zag along a line: 0 -> 3 -> 1 -> 2 -> 4
+        let points = vec![
+            point(0.0, 0.0),
+            point(0.0, 3.0),
+            point(0.0, 1.0),
+            point(0.0, 2.0),
+            point(0.0, 4.0),
+        ];
+        let order = optimize_route(&points);
+
+        assert_eq!(order.first(), Some(&0));
+        assert_eq!(order.last(), Some(&4));
+
+        let original = path_distance_km(&points);
+        let optimized: Vec<Coordinates> = order.iter().map(|&i| points[i]).collect();
+        assert!(path_distance_km(&optimized) < original);
+    }
+}
@@ -7,6 +7,7 @@ lazy_static! {
     static ref XSS_REGEX: Regex = Regex::new(r"(?i)<script.*?>.*?</script>|on\w+?\s*=").unwrap();
     static ref PRODUCT_ID_REGEX: Regex = Regex::new(r"^[a-zA-Z0-9-_]+$").unwrap();
     static ref LOCATION_REGEX: Regex = Regex::new(r"^[\w\s,.\-']+$").unwrap();
+    static ref UNLOCODE_REGEX: Regex = Regex::new(r"^[A-Z]{2}[A-Z2-9]{3}$").unwrap();
 }
 
 const STELLAR_ADDRESS_LEN: usize = 56;
@@ -124,6 +125,35 @@ pub fn validate_location(location: &str) -> Result<(), AppError> {
     Ok(())
 }
 
+/// Validates a UN/LOCODE: ISO country code plus a 3-character location (e.g. `NLRTM`).
+pub fn validate_unlocode(code: &str) -> Result<(), AppError> {
+    if !UNLOCODE_REGEX.is_match(code) {
+        return Err(AppError::Validation(format!("Invalid UN/LOCODE: '{}'", code)));
+    }
+    Ok(())
+}
+
+/// Validates a GS1 Global Location Number (13 digits, mod-10 check digit).
+pub fn validate_gln(gln: &str) -> Result<(), AppError> {
+    let valid = gln.len() == 13
+        && gln.chars().all(|c| c.is_ascii_digit())
+        && gs1_check_digit(&gln[..12]) == gln[12..].parse().ok();
+    if !valid {
+        return Err(AppError::Validation(format!("Invalid GLN: '{}'", gln)));
+    }
+    Ok(())
+}
+
+/// Computes the GS1 mod-10 check digit for a string of digits (without the check digit).
+pub fn gs1_check_digit(digits: &str) -> Option<u32> {
+    let mut sum = 0;
+    for (i, c) in digits.chars().rev().enumerate() {
+        let d = c.to_digit(10)?;
+        sum += if i % 2 == 0 { d * 3 } else { d };
+    }
+    Some((10 - sum % 10) % 10)
+}
+
 /// Recursively sanitizes JSON metadata by sanitizing all string values.
 pub fn sanitize_json_metadata(value: &mut serde_json::Value) {
     match value {
@@ -0,0 +1,263 @@
+/// Fan-out of websocket messages across backend instances.
+///
+/// Every published message is appended to one capped Redis stream, and each
+/// instance tails the stream and delivers new entries to its own subscribers.
+/// Stream entry IDs double as message ids, so a client that reconnects can
+/// name the last id it saw and be sent what it missed while that is still in
+/// the stream.
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use redis::streams::{
+    StreamId, StreamMaxlen, StreamRangeReply, StreamReadOptions, StreamReadReply,
+};
+use redis::{AsyncCommands, Value};
+use tracing::Instrument;
+
+use crate::monitoring::telemetry;
+use crate::websocket::{connection_manager, MessageType, WebSocketMessage};
+
+pub const STREAM_KEY: &str = "ws:messages";
+/// Roughly how many messages the stream keeps for replay
+const STREAM_MAXLEN: usize = 10_000;
+/// Most messages replayed for one subscription
+pub const REPLAY_LIMIT: usize = 500;
+const READ_BLOCK_MS: usize = 5_000;
+const READ_BATCH: usize = 100;
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+static CLIENT: OnceLock<redis::Client> = OnceLock::new();
+
+/// What a resumed subscription missed
+#[derive(Debug, Default)]
+pub struct Replay {
+    pub messages: Vec<WebSocketMessage>,
+    /// False when older messages have already left the stream, or there were
+    /// more than `REPLAY_LIMIT`
+    pub complete: bool,
+}
+
+/// Starts tailing the stream on this instance. Until this runs, messages are
+/// only delivered locally.
+pub fn start(client: redis::Client) {
+    if CLIENT.set(client.clone()).is_ok() {
+        tokio::spawn(tail(client));
+    }
+}
+
+/// Appends a message to the stream. Returns false when there is no backplane
+/// or Redis is unreachable, in which case the caller delivers it locally.
+pub async fn append(channel: &str, data: &serde_json::Value) -> bool {
+    let Some(client) = CLIENT.get() else {
+        return false;
+    };
+    let span = telemetry::redis_span("XADD");
+    let Ok(mut conn) = client
+        .get_multiplexed_tokio_connection()
+        .instrument(span.clone())
+        .await
+    else {
+        return false;
+    };
+    let fields = [
+        ("channel", channel.to_string()),
+        ("data", data.to_string()),
+        ("ts", chrono::Utc::now().timestamp_millis().to_string()),
+    ];
+    let added: redis::RedisResult<String> = conn
+        .xadd_maxlen(
+            STREAM_KEY,
+            StreamMaxlen::Approx(STREAM_MAXLEN),
+            "*",
+            &fields,
+        )
+        .instrument(span)
+        .await;
+    match added {
+        Ok(_) => true,
+        Err(e) => {
+            tracing::warn!("Websocket backplane append failed: {}", e);
+            false
+        }
+    }
+}
+
+/// Messages on `channel` after `last_id`, oldest first
+pub async fn replay(channel: &str, last_id: &str) -> Result<Replay, String> {
+    let since = parse_id(last_id).ok_or_else(|| format!("Invalid message id '{}'", last_id))?;
+    let client = CLIENT
+        .get()
+        .ok_or_else(|| "Replay is not available".to_string())?;
+    let mut conn = client
+        .get_multiplexed_tokio_connection()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let oldest: StreamRangeReply = conn
+        .xrange_count(STREAM_KEY, "-", "+", 1)
+        .await
+        .map_err(|e| e.to_string())?;
+    // Whatever followed `last_id` is still there if the stream reaches back to it
+    let mut complete = oldest
+        .ids
+        .first()
+        .and_then(|entry| parse_id(&entry.id))
+        .is_none_or(|first| first <= next_id(since));
+
+    let mut messages = Vec::new();
+    let mut cursor = last_id.to_string();
+    'pages: loop {
+        let page: StreamRangeReply = conn
+            .xrange_count(STREAM_KEY, format!("({}", cursor), "+", READ_BATCH)
+            .await
+            .map_err(|e| e.to_string())?;
+        for entry in &page.ids {
+            cursor = entry.id.clone();
+            match message_from_entry(entry) {
+                Some((entry_channel, message)) if entry_channel == channel => {
+                    if messages.len() == REPLAY_LIMIT {
+                        complete = false;
+                        break 'pages;
+                    }
+                    messages.push(message);
+                }
+                _ => {}
+            }
+        }
+        if page.ids.len() < READ_BATCH {
+            break;
+        }
+    }
+    Ok(Replay { messages, complete })
+}
+
+/// Delivers every new stream entry to this instance's subscribers. Reconnects
+/// after Redis errors and carries on from the last entry it delivered.
+async fn tail(client: redis::Client) {
+    let mut last_id = "$".to_string();
+    loop {
+        let mut conn = match client.get_multiplexed_tokio_connection().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                tracing::warn!("Websocket backplane unavailable: {}", e);
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+        let options = StreamReadOptions::default()
+            .block(READ_BLOCK_MS)
+            .count(READ_BATCH);
+        loop {
+            let reply: redis::RedisResult<StreamReadReply> = conn
+                .xread_options(&[STREAM_KEY], &[last_id.as_str()], &options)
+                .await;
+            let reply = match reply {
+                Ok(reply) => reply,
+                Err(e) => {
+                    tracing::warn!("Websocket backplane read failed: {}", e);
+                    break;
+                }
+            };
+            let manager = connection_manager();
+            for entry in reply.keys.iter().flat_map(|key| &key.ids) {
+                last_id = entry.id.clone();
+                if let Some((channel, message)) = message_from_entry(entry) {
+                    if let Ok(text) = serde_json::to_string(&message) {
+                        manager.broadcast(&channel, text).await;
+                    }
+                }
+            }
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+// ── Entries ───────────────────────────────────────────────────────────────────
+
+fn message_from_entry(entry: &StreamId) -> Option<(String, WebSocketMessage)> {
+    let field = |name: &str| match entry.map.get(name) {
+        Some(Value::Data(bytes)) => String::from_utf8(bytes.clone()).ok(),
+        _ => None,
+    };
+    let channel = field("channel")?;
+    let data = serde_json::from_str(&field("data")?).ok()?;
+    let timestamp = field("ts")
+        .and_then(|ts| ts.parse().ok())
+        .unwrap_or_default();
+    let message = WebSocketMessage {
+        id: entry.id.clone(),
+        timestamp,
+        message_type: MessageType::Event {
+            channel: channel.clone(),
+            data,
+        },
+    };
+    Some((channel, message))
+}
+
+/// A stream entry ID, `<milliseconds>-<sequence>`
+fn parse_id(id: &str) -> Option<(u64, u64)> {
+    let (millis, seq) = id.split_once('-')?;
+    Some((millis.parse().ok()?, seq.parse().ok()?))
+}
+
+fn next_id((millis, seq): (u64, u64)) -> (u64, u64) {
+    match seq.checked_add(1) {
+        Some(seq) => (millis, seq),
+        None => (millis + 1, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn entry(id: &str, fields: &[(&str, &str)]) -> StreamId {
+        StreamId {
+            id: id.to_string(),
+            map: fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), Value::Data(v.as_bytes().to_vec())))
+                .collect::<HashMap<_, _>>(),
+        }
+    }
+
+    #[test]
+    fn entries_become_event_messages() {
+        let (channel, message) = message_from_entry(&entry(
+            "1700000000000-3",
+            &[
+                ("channel", "product:PROD-1"),
+                ("data", r#"{"kind":"event.created"}"#),
+                ("ts", "1700000000000"),
+            ],
+        ))
+        .unwrap();
+        assert_eq!(channel, "product:PROD-1");
+        assert_eq!(message.id, "1700000000000-3");
+        assert_eq!(message.timestamp, 1_700_000_000_000);
+        match message.message_type {
+            MessageType::Event { channel, data } => {
+                assert_eq!(channel, "product:PROD-1");
+                assert_eq!(data["kind"], "event.created");
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+
+        assert!(message_from_entry(&entry("1-0", &[("channel", "alerts")])).is_none());
+        assert!(
+            message_from_entry(&entry("1-0", &[("channel", "alerts"), ("data", "{")])).is_none()
+        );
+    }
+
+    #[test]
+    fn orders_stream_ids() {
+        assert_eq!(parse_id("1700000000000-3"), Some((1_700_000_000_000, 3)));
+        assert_eq!(parse_id("1700000000000"), None);
+        assert_eq!(parse_id("abc-1"), None);
+        assert!(parse_id("9-5") < parse_id("10-0"));
+        assert_eq!(next_id((9, 5)), (9, 6));
+        assert_eq!(next_id((9, u64::MAX)), (10, 0));
+    }
+}
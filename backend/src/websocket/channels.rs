@@ -0,0 +1,99 @@
+/// Channel names and publishing.
+///
+/// Clients subscribe to `product:{id}` for one product, `org:{id}` for every
+/// product of an organization, or `alerts` for anomaly and compliance alerts
+/// across the platform. Services publish through `publish`, which wraps the
+/// payload in an `event` message for each channel.
+use uuid::Uuid;
+
+use crate::websocket::{backplane, connection_manager, WebSocketMessage};
+
+/// Every anomaly and compliance alert on the platform
+pub const ALERTS: &str = "alerts";
+
+const PRODUCT_PREFIX: &str = "product:";
+const ORGANIZATION_PREFIX: &str = "org:";
+
+/// A channel a client asked for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Channel {
+    Product(String),
+    Organization(Uuid),
+    Alerts,
+}
+
+impl Channel {
+    pub fn parse(name: &str) -> Option<Self> {
+        if name == ALERTS {
+            return Some(Channel::Alerts);
+        }
+        if let Some(id) = name.strip_prefix(PRODUCT_PREFIX) {
+            return (!id.is_empty() && id.len() <= 64).then(|| Channel::Product(id.to_string()));
+        }
+        name.strip_prefix(ORGANIZATION_PREFIX)
+            .and_then(|id| Uuid::parse_str(id).ok())
+            .map(Channel::Organization)
+    }
+}
+
+pub fn product(id: &str) -> String {
+    format!("{}{}", PRODUCT_PREFIX, id)
+}
+
+pub fn organization(id: Uuid) -> String {
+    format!("{}{}", ORGANIZATION_PREFIX, id)
+}
+
+/// Where changes to a product go: its own channel and its organization's
+pub fn for_product(product_id: &str, organization_id: Option<Uuid>) -> Vec<String> {
+    let mut channels = vec![product(product_id)];
+    channels.extend(organization_id.map(organization));
+    channels
+}
+
+/// Sends `data` to the subscribers of each channel on every instance. Nobody
+/// listening is fine. Without Redis, only this instance's subscribers get it.
+pub async fn publish(channels: &[String], data: serde_json::Value) {
+    let manager = connection_manager();
+    for channel in channels {
+        if backplane::append(channel, &data).await {
+            continue;
+        }
+        let message = WebSocketMessage::event(channel.clone(), data.clone());
+        if let Ok(text) = serde_json::to_string(&message) {
+            manager.broadcast(channel, text).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_channel_names() {
+        let org = Uuid::new_v4();
+        assert_eq!(Channel::parse("alerts"), Some(Channel::Alerts));
+        assert_eq!(
+            Channel::parse("product:PROD-1"),
+            Some(Channel::Product("PROD-1".to_string()))
+        );
+        assert_eq!(
+            Channel::parse(&organization(org)),
+            Some(Channel::Organization(org))
+        );
+        assert_eq!(Channel::parse("product:"), None);
+        assert_eq!(Channel::parse("org:acme"), None);
+        assert_eq!(Channel::parse("users"), None);
+    }
+
+    #[test]
+    fn product_changes_reach_the_organization() {
+        let org = Uuid::new_v4();
+        assert_eq!(
+            for_product("PROD-1", Some(org)),
+            vec!["product:PROD-1".to_string(), format!("org:{}", org)]
+        );
+        assert_eq!(for_product("PROD-1", None), vec!["product:PROD-1"]);
+    }
+}
@@ -1,63 +1,190 @@
-use crate::websocket::{ConnectionManager, WebSocketMessage, MessageType};
-use tokio::sync::mpsc;
+use std::time::Duration;
+
+use axum::extract::ws::{Message, WebSocket};
 use futures::{SinkExt, StreamExt};
-use warp::ws::{WebSocket, Message};
+use tokio::sync::mpsc;
+
+use crate::database::ApiKeyRepository;
+use crate::middleware::auth::AuthContext;
+use crate::models::UserRole;
+use crate::services::access_control::ProductAction;
+use crate::websocket::backplane;
+use crate::websocket::channels::Channel;
+use crate::websocket::manager::ConnectionId;
+use crate::websocket::{ConnectionManager, MessageType, WebSocketMessage};
+use crate::AppState;
 
+/// How often an open connection checks that its session or API key still stands
+const REVALIDATE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Serves one authenticated websocket connection: subscriptions are checked
+/// against what the caller may see before any message is delivered, and the
+/// connection closes once its session is logged out or its API key revoked.
 pub struct WebSocketHandler {
     manager: ConnectionManager,
+    state: AppState,
 }
 
 impl WebSocketHandler {
-    pub fn new(manager: ConnectionManager) -> Self {
-        WebSocketHandler { manager }
+    pub fn new(manager: ConnectionManager, state: AppState) -> Self {
+        WebSocketHandler { manager, state }
     }
 
-    pub async fn handle_connection(&self, ws: WebSocket) {
+    pub async fn handle_connection(self, ws: WebSocket, auth: AuthContext) {
         let (mut user_ws_tx, mut user_ws_rx) = ws.split();
         let (tx, mut rx) = mpsc::unbounded_channel();
 
-        let connection_id = self.manager.add_connection(tx).await;
-        let manager = self.manager.clone();
-        let conn_id = connection_id.clone();
+        let conn_id = self.manager.add_connection(tx).await;
 
-        // Spawn task to handle incoming messages
+        // Outgoing messages; ends once the connection is removed and its sender dropped
         tokio::spawn(async move {
-            while let Some(result) = user_ws_rx.next().await {
-                match result {
-                    Ok(msg) => {
-                        if let Ok(text) = msg.to_str() {
-                            if let Ok(ws_msg) = serde_json::from_str::<WebSocketMessage>(text) {
-                                match ws_msg.message_type {
-                                    MessageType::Subscribe { channel } => {
-                                        let _ = manager.subscribe(&conn_id, &channel).await;
-                                    }
-                                    MessageType::Unsubscribe { channel } => {
-                                        let _ = manager.unsubscribe(&conn_id, &channel).await;
-                                    }
-                                    MessageType::Ping => {
-                                        let pong = WebSocketMessage::pong();
-                                        let _ = manager
-                                            .send_to_connection(&conn_id, serde_json::to_string(&pong).unwrap())
-                                            .await;
-                                    }
-                                    _ => {}
+            while let Some(msg) = rx.recv().await {
+                if user_ws_tx.send(Message::Text(msg)).await.is_err() {
+                    break;
+                }
+            }
+            let _ = user_ws_tx.close().await;
+        });
+
+        let mut revalidate = tokio::time::interval(REVALIDATE_INTERVAL);
+        revalidate.tick().await;
+
+        loop {
+            let msg = tokio::select! {
+                msg = user_ws_rx.next() => msg,
+                _ = revalidate.tick() => {
+                    if self.revoked(&auth).await {
+                        self.send(&conn_id, WebSocketMessage::error("Session ended".to_string())).await;
+                        break;
+                    }
+                    continue;
+                }
+            };
+            let Some(Ok(msg)) = msg else {
+                break;
+            };
+            let text = match msg {
+                Message::Text(text) => text,
+                Message::Close(_) => break,
+                // Pings are answered by the protocol layer
+                _ => continue,
+            };
+            let reply = match serde_json::from_str::<WebSocketMessage>(&text) {
+                Ok(ws_msg) => match ws_msg.message_type {
+                    MessageType::Subscribe {
+                        channel,
+                        last_event_id,
+                    } => match self.authorize(&auth, &channel).await {
+                        Ok(()) => {
+                            let _ = self.manager.subscribe(&conn_id, &channel).await;
+                            let replay_complete = match last_event_id {
+                                Some(last_id) => {
+                                    Some(self.replay(&conn_id, &channel, &last_id).await)
                                 }
-                            }
+                                None => None,
+                            };
+                            Some(WebSocketMessage::subscribed(channel, replay_complete))
                         }
+                        Err(message) => Some(WebSocketMessage::error(message)),
+                    },
+                    MessageType::Unsubscribe { channel } => {
+                        let _ = self.manager.unsubscribe(&conn_id, &channel).await;
+                        None
+                    }
+                    MessageType::Ping => Some(WebSocketMessage::pong()),
+                    _ => None,
+                },
+                Err(_) => Some(WebSocketMessage::error("Malformed message".to_string())),
+            };
+            if let Some(reply) = reply {
+                self.send(&conn_id, reply).await;
+            }
+        }
+        self.manager.remove_connection(&conn_id).await;
+    }
+
+    async fn send(&self, conn_id: &ConnectionId, message: WebSocketMessage) {
+        if let Ok(text) = serde_json::to_string(&message) {
+            let _ = self.manager.send_to_connection(conn_id, text).await;
+        }
+    }
+
+    /// Whether the session the connection was opened with has been logged out,
+    /// or its API key revoked or expired. Lookup failures keep the connection.
+    async fn revoked(&self, auth: &AuthContext) -> bool {
+        if let Some(session_id) = auth.session_id {
+            return matches!(
+                self.state.session_service.is_active(session_id).await,
+                Ok(false)
+            );
+        }
+        if let Some(key_id) = auth.api_key_id {
+            return match self.state.api_key_service.get_api_key(key_id).await {
+                Ok(Some(key)) => {
+                    !key.is_active || key.expires_at.is_some_and(|at| at < chrono::Utc::now())
+                }
+                Ok(None) => true,
+                Err(_) => false,
+            };
+        }
+        false
+    }
+
+    /// Sends what the channel carried after `last_id`. Subscribing first means
+    /// nothing falls in between, though a message may arrive twice; clients
+    /// skip ids they have seen. Returns whether the replay is complete.
+    async fn replay(&self, conn_id: &ConnectionId, channel: &str, last_id: &str) -> bool {
+        match backplane::replay(channel, last_id).await {
+            Ok(replay) => {
+                for message in replay.messages {
+                    if let Ok(text) = serde_json::to_string(&message) {
+                        let _ = self.manager.send_to_connection(conn_id, text).await;
                     }
-                    Err(_) => break,
                 }
+                replay.complete
             }
-            manager.remove_connection(&conn_id).await;
-        });
+            Err(e) => {
+                tracing::debug!(channel, "Websocket replay failed: {}", e);
+                false
+            }
+        }
+    }
 
-        // Spawn task to handle outgoing messages
-        tokio::spawn(async move {
-            while let Some(msg) = rx.recv().await {
-                if user_ws_tx.send(Message::text(msg)).await.is_err() {
-                    break;
+    /// Whether the caller may listen on `channel`. Products follow the product
+    /// ACL; organization channels need membership and carry every product of
+    /// the organization, so product-limited API keys cannot use them.
+    async fn authorize(&self, auth: &AuthContext, channel: &str) -> Result<(), String> {
+        let denied = || format!("Not allowed to subscribe to '{}'", channel);
+        match Channel::parse(channel).ok_or_else(|| format!("Unknown channel '{}'", channel))? {
+            Channel::Product(product_id) => self
+                .state
+                .access_service
+                .authorize_product(auth, &product_id, ProductAction::Read)
+                .await
+                .map(|_| ())
+                .map_err(|_| denied()),
+            Channel::Organization(organization_id) => {
+                if auth.restricts_products() {
+                    return Err(denied());
                 }
+                let allowed = match (&auth.role, auth.api_key_id) {
+                    (UserRole::Administrator, _) => true,
+                    // A key only speaks for the organization it acts in
+                    (_, Some(_)) => auth.organization_id == Some(organization_id),
+                    (_, None) => self
+                        .state
+                        .organization_service
+                        .membership(organization_id, auth.user_id)
+                        .await
+                        .map_err(|_| denied())?
+                        .is_some(),
+                };
+                allowed.then_some(()).ok_or_else(denied)
             }
-        });
+            // Platform-wide, so only for administrators
+            Channel::Alerts => matches!(auth.role, UserRole::Administrator)
+                .then_some(())
+                .ok_or_else(denied),
+        }
     }
 }
@@ -2,11 +2,21 @@ use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::{RwLock, mpsc};
 use uuid::Uuid;
+use lazy_static::lazy_static;
 
 pub type ConnectionId = String;
 pub type Channel = String;
 pub type Sender = mpsc::UnboundedSender<String>;
 
+lazy_static! {
+    static ref GLOBAL_MANAGER: ConnectionManager = ConnectionManager::new();
+}
+
+/// Process-wide manager shared by the websocket endpoint and the services that publish to it
+pub fn global() -> ConnectionManager {
+    GLOBAL_MANAGER.clone()
+}
+
 #[derive(Clone)]
 pub struct ConnectionManager {
     connections: Arc<RwLock<HashMap<ConnectionId, Connection>>>,
@@ -110,6 +120,11 @@ impl ConnectionManager {
         let channels = self.channels.read().await;
         channels.get(channel).map(|s| s.len()).unwrap_or(0)
     }
+
+    /// Connections open on this instance
+    pub async fn connection_count(&self) -> usize {
+        self.connections.read().await.len()
+    }
 }
 
 impl Default for ConnectionManager {
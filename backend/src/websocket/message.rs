@@ -3,10 +3,23 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum MessageType {
+    /// `last_event_id` resumes the channel after the last message a client saw
     #[serde(rename = "subscribe")]
-    Subscribe { channel: String },
+    Subscribe {
+        channel: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        last_event_id: Option<String>,
+    },
     #[serde(rename = "unsubscribe")]
     Unsubscribe { channel: String },
+    /// Confirms a subscription was accepted. For a resumed subscription it
+    /// follows the replayed messages and says whether any were lost.
+    #[serde(rename = "subscribed")]
+    Subscribed {
+        channel: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        replay_complete: Option<bool>,
+    },
     #[serde(rename = "event")]
     Event { channel: String, data: serde_json::Value },
     #[serde(rename = "ping")]
@@ -35,13 +48,23 @@ impl WebSocketMessage {
     }
 
     pub fn subscribe(channel: String) -> Self {
-        Self::new(MessageType::Subscribe { channel })
+        Self::new(MessageType::Subscribe {
+            channel,
+            last_event_id: None,
+        })
     }
 
     pub fn unsubscribe(channel: String) -> Self {
         Self::new(MessageType::Unsubscribe { channel })
     }
 
+    pub fn subscribed(channel: String, replay_complete: Option<bool>) -> Self {
+        Self::new(MessageType::Subscribed {
+            channel,
+            replay_complete,
+        })
+    }
+
     pub fn event(channel: String, data: serde_json::Value) -> Self {
         Self::new(MessageType::Event { channel, data })
     }
@@ -1,7 +1,9 @@
+pub mod backplane;
+pub mod channels;
 pub mod handler;
 pub mod message;
 pub mod manager;
 
 pub use handler::WebSocketHandler;
 pub use message::{WebSocketMessage, MessageType};
-pub use manager::ConnectionManager;
+pub use manager::{global as connection_manager, ConnectionManager};
@@ -12,6 +12,7 @@ pub struct ChainLogisticsClient {
     products: crate::products::ProductsService,
     events: crate::events::EventsService,
     stats: crate::stats::StatsService,
+    digital_twins: crate::digital_twins::DigitalTwinsService,
 }
 
 impl ChainLogisticsClient {
@@ -27,6 +28,8 @@ impl ChainLogisticsClient {
         let products = crate::products::ProductsService::new(client.clone(), config.clone());
         let events = crate::events::EventsService::new(client.clone(), config.clone());
         let stats = crate::stats::StatsService::new(client.clone(), config.clone());
+        let digital_twins =
+            crate::digital_twins::DigitalTwinsService::new(client.clone(), config.clone());
 
         Ok(Self {
             config,
@@ -34,6 +37,7 @@ impl ChainLogisticsClient {
             products,
             events,
             stats,
+            digital_twins,
         })
     }
 
@@ -57,6 +61,11 @@ impl ChainLogisticsClient {
         &self.stats
     }
 
+    /// Get the digital twins service
+    pub fn digital_twins(&self) -> &crate::digital_twins::DigitalTwinsService {
+        &self.digital_twins
+    }
+
     /// Perform a health check
     pub async fn health_check(&self) -> Result<crate::models::HealthResponse> {
         let response = self
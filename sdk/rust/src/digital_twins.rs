@@ -0,0 +1,40 @@
+use crate::{
+    client::HttpClient,
+    models::EtaPrediction,
+    Config, Result,
+};
+
+/// Service for digital twin predictions
+#[derive(Debug, Clone)]
+pub struct DigitalTwinsService {
+    client: HttpClient,
+}
+
+impl DigitalTwinsService {
+    pub(crate) fn new(client: reqwest::Client, config: Config) -> Self {
+        Self {
+            client: HttpClient::new(client, config),
+        }
+    }
+
+    /// Predict arrival time for an in-flight product.
+    /// `confidence` defaults to 0.9 on the server and must be between 0.5 and 0.99.
+    pub async fn predict_eta(&self, product_id: &str, confidence: Option<f64>) -> Result<EtaPrediction> {
+        if let Some(c) = confidence {
+            if !(0.5..=0.99).contains(&c) {
+                return Err(crate::Error::Validation(
+                    "confidence must be between 0.5 and 0.99".to_string(),
+                ));
+            }
+        }
+
+        let mut request = self
+            .client
+            .get(&format!("api/v1/digital-twins/products/{}/eta", product_id));
+        if let Some(c) = confidence {
+            request = request.query(&[("confidence", c)]);
+        }
+
+        self.client.execute(request).await
+    }
+}
@@ -29,6 +29,7 @@ pub mod models;
 pub mod products;
 pub mod events;
 pub mod stats;
+pub mod digital_twins;
 
 // Re-export main types for convenience
 pub use client::ChainLogisticsClient;
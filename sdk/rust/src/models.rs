@@ -179,6 +179,33 @@ pub struct GlobalStats {
     pub active_api_keys: i64,
 }
 
+/// Segment of transit history an ETA was learned from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EtaSegment {
+    Lane,
+    OriginMode,
+    Mode,
+    Global,
+}
+
+/// Arrival time prediction for an in-flight product
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EtaPrediction {
+    pub product_id: String,
+    pub origin: String,
+    pub destination: Option<String>,
+    pub transport_mode: String,
+    pub departed_at: DateTime<Utc>,
+    pub estimated_arrival: DateTime<Utc>,
+    pub lower_bound: DateTime<Utc>,
+    pub upper_bound: DateTime<Utc>,
+    pub confidence_level: f64,
+    pub sample_size: usize,
+    pub segment: EtaSegment,
+    pub overdue: bool,
+}
+
 /// Health check response
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HealthResponse {